@@ -0,0 +1,232 @@
+use clap::{App, AppSettings, Arg};
+use std::fs;
+
+const HEADER_SIZE: usize = 48;
+const VERSION: u32 = 0x20;
+
+/// Samples per 16-byte ADPCM block.
+const BLOCK_SAMPLES: usize = 28;
+
+/// Prediction filter coefficients, in 64ths.
+const FILTERS: [(i32, i32); 5] = [(0, 0), (60, 0), (115, -52), (98, -55), (122, -60)];
+
+const FLAG_END: u8 = 1;
+const FLAG_LOOP_END: u8 = 3;
+const FLAG_LOOP_START: u8 = 6;
+
+struct Wav {
+    sample_rate: u32,
+    /// Mixed down to mono.
+    samples: Vec<i16>,
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn parse_wav(bytes: &[u8]) -> Result<Wav, String> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err("not a WAV file".into());
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut offset = 12;
+
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = read_u32(bytes, offset + 4) as usize;
+        let body = &bytes[offset + 8..bytes.len().min(offset + 8 + size)];
+
+        match id {
+            b"fmt " if body.len() >= 16 => format = Some(body),
+            b"data" => data = Some(body),
+            _ => (),
+        }
+
+        // Chunks are padded to an even size.
+        offset += 8 + size + (size & 1);
+    }
+
+    let format = format.ok_or("missing fmt chunk")?;
+    let data = data.ok_or("missing data chunk")?;
+
+    let mut tag = read_u16(format, 0);
+    let channels = read_u16(format, 2) as usize;
+    let sample_rate = read_u32(format, 4);
+    let bits = read_u16(format, 14) as usize;
+
+    // WAVE_FORMAT_EXTENSIBLE keeps the real tag in the sub-format GUID.
+    if tag == 0xfffe && format.len() >= 26 {
+        tag = read_u16(format, 24);
+    }
+
+    if channels == 0 || sample_rate == 0 {
+        return Err("invalid fmt chunk".into());
+    }
+
+    let width = bits / 8;
+    let decode: fn(&[u8]) -> i32 = match (tag, bits) {
+        (1, 8) => |b| (b[0] as i32 - 128) << 8,
+        (1, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as i32,
+        (1, 24) => |b| i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 16,
+        (1, 32) => |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) >> 16,
+        (3, 32) => |b| (f32::from_le_bytes([b[0], b[1], b[2], b[3]]) * 32767.0) as i32,
+        _ => return Err(format!("unsupported format {} with {} bits per sample", tag, bits)),
+    };
+
+    let samples = data
+        .chunks_exact(width * channels)
+        .map(|frame| {
+            let sum: i32 = frame.chunks_exact(width).map(decode).sum();
+            clamp(sum / channels as i32)
+        })
+        .collect();
+
+    Ok(Wav { sample_rate, samples })
+}
+
+fn clamp(value: i32) -> i16 {
+    value.max(i16::MIN as i32).min(i16::MAX as i32) as i16
+}
+
+/// Predict the next sample from the previous two decoded ones.
+fn predict(filter: usize, s1: i32, s2: i32) -> i32 {
+    let (f0, f1) = FILTERS[filter];
+    (s1 * f0 + s2 * f1 + 32) >> 6
+}
+
+/// Encode one block with `filter`, returning the block, the squared error
+/// and the new history.
+fn encode_block(samples: &[i16], filter: usize, history: (i32, i32)) -> ([u8; 16], u64, (i32, i32)) {
+    // Pick the shift from the largest residual against the source itself.
+    let mut max_residual = 0;
+    let (mut s1, mut s2) = history;
+
+    for &sample in samples {
+        let residual = (sample as i32 - predict(filter, s1, s2)).abs();
+        max_residual = max_residual.max(residual);
+        s2 = s1;
+        s1 = sample as i32;
+    }
+
+    let mut range = 0;
+
+    while range < 12 && (7 << range) < max_residual {
+        range += 1;
+    }
+
+    let shift = 12 - range;
+    let step = 1 << range;
+
+    let mut block = [0u8; 16];
+    block[0] = (filter << 4) as u8 | shift as u8;
+
+    let mut error = 0;
+    let (mut s1, mut s2) = history;
+
+    for i in 0..BLOCK_SAMPLES {
+        let sample = samples.get(i).copied().unwrap_or(0) as i32;
+        let prediction = predict(filter, s1, s2);
+        let diff = sample - prediction;
+
+        let nibble = ((diff + step / 2).div_euclid(step)).max(-8).min(7);
+        let decoded = clamp(prediction + nibble * step) as i32;
+
+        error += ((sample - decoded) as i64).pow(2) as u64;
+        block[2 + i / 2] |= ((nibble as u8) & 0xf) << ((i % 2) * 4);
+
+        s2 = s1;
+        s1 = decoded;
+    }
+
+    (block, error, (s1, s2))
+}
+
+fn encode(samples: &[i16], looping: bool) -> Vec<u8> {
+    // VAG data conventionally starts with a silent block.
+    let mut data = vec![0; 16];
+    let mut history = (0, 0);
+    let blocks = (samples.len() + BLOCK_SAMPLES - 1) / BLOCK_SAMPLES;
+
+    for (i, chunk) in samples.chunks(BLOCK_SAMPLES).enumerate() {
+        let (mut block, _, new_history) = (0..FILTERS.len())
+            .map(|filter| encode_block(chunk, filter, history))
+            .min_by_key(|&(_, error, _)| error)
+            .unwrap();
+
+        history = new_history;
+
+        block[1] = match (looping, i == 0, i + 1 == blocks) {
+            (true, _, true) => FLAG_LOOP_END,
+            (true, true, false) => FLAG_LOOP_START,
+            (false, _, true) => FLAG_END,
+            _ => 0,
+        };
+
+        data.extend_from_slice(&block);
+    }
+
+    data
+}
+
+fn main() {
+    let matches = App::new("wav2vag")
+        .version("0.1")
+        .about("Convert WAV files to VAG ADPCM, for the PSP's Sas synthesizer")
+        .setting(AppSettings::ColoredHelp)
+        .arg(
+            Arg::with_name("input.wav")
+                .takes_value(true)
+                .help("Input WAV file. Stereo is mixed down to mono")
+                .required(true)
+        )
+        .arg(
+            Arg::with_name("output.vag")
+                .takes_value(true)
+                .help("Output VAG file")
+                .required(true)
+        )
+        .arg(
+            Arg::with_name("loop")
+                .long("loop")
+                .help("Mark the whole sound as a loop")
+        )
+        .arg(
+            Arg::with_name("name")
+                .long("name")
+                .takes_value(true)
+                .help("Name stored in the header, up to 16 bytes")
+        )
+        .get_matches();
+
+    let input = matches.value_of("input.wav").unwrap();
+    let output = matches.value_of("output.vag").unwrap();
+
+    let bytes = fs::read(input).unwrap_or_else(|e| panic!("failed to read {}: {}", input, e));
+    let wav = parse_wav(&bytes).unwrap_or_else(|e| panic!("failed to parse {}: {}", input, e));
+
+    if wav.samples.is_empty() {
+        panic!("{} has no samples", input);
+    }
+
+    let data = encode(&wav.samples, matches.is_present("loop"));
+
+    let mut vag = vec![0; HEADER_SIZE];
+    vag[0..4].copy_from_slice(b"VAGp");
+    vag[4..8].copy_from_slice(&VERSION.to_be_bytes());
+    vag[12..16].copy_from_slice(&(data.len() as u32).to_be_bytes());
+    vag[16..20].copy_from_slice(&wav.sample_rate.to_be_bytes());
+
+    let name = matches.value_of("name").unwrap_or("");
+    let name = &name.as_bytes()[..name.len().min(16)];
+    vag[32..32 + name.len()].copy_from_slice(name);
+
+    vag.extend(data);
+
+    fs::write(output, vag).unwrap_or_else(|e| panic!("failed to write {}: {}", output, e));
+}
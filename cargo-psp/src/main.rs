@@ -6,8 +6,16 @@ use std::{
     process::{self, Command, Stdio},
 };
 
+// Shared with the `psp` crate, which can't be built for the host.
+#[path = "../../psp/src/video/pmf.rs"]
+mod pmf;
+
 const CONFIG_NAME: &str = "Psp.toml";
 
+/// The XMB only plays 144x80 icons at 29.97 fps.
+const ICON_PMF_SIZE: (u32, u32) = (144, 80);
+const ICON_PMF_FRAME_DURATION: u32 = 3003;
+
 #[derive(serde_derive::Deserialize, Default)]
 struct PspConfig {
     /// Title shown in the XMB menu.
@@ -127,6 +135,36 @@ const MINIMUM_RUSTC_VERSION: Version = Version {
     build: Vec::new(),
 };
 
+fn check_icon_pmf(path: &str) -> Result<(), String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let header = pmf::PmfHeader::parse(&bytes).map_err(|e| e.to_string())?;
+
+    match header.video_size() {
+        Some(ICON_PMF_SIZE) => (),
+        Some((width, height)) => {
+            return Err(format!("the video is {}x{}, not 144x80", width, height));
+        }
+        None => return Err("there is no video stream".into()),
+    }
+
+    let end = header.stream_offset() as u64 + header.stream_size() as u64;
+
+    if end > bytes.len() as u64 {
+        return Err("the file is cut short".into());
+    }
+
+    let stream = &bytes[header.stream_offset() as usize..];
+
+    match pmf::frame_duration(stream) {
+        Some(ICON_PMF_FRAME_DURATION) => Ok(()),
+        Some(duration) => Err(format!(
+            "the video is {:.2} fps, not 29.97 fps",
+            pmf::PTS_RATE as f64 / duration as f64,
+        )),
+        None => Err("no video frames were found".into()),
+    }
+}
+
 fn main() {
     let rustc_version = rustc_version::version_meta().unwrap();
 
@@ -177,6 +215,13 @@ fn main() {
         Err(e) => panic!("{}", e),
     };
 
+    if let Some(path) = &config.xmb_icon_pmf {
+        if let Err(e) = check_icon_pmf(path) {
+            eprintln!("Invalid xmb_icon_pmf {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+
     // Skip `cargo psp`
     let args = env::args().skip(2);
 
@@ -24,6 +24,10 @@ popd
 
 PATH="$(realpath repo)/target/debug:$PATH"
 
+pushd repo/ci/host_tests
+cargo test
+popd
+
 pushd repo/ci/tests
 cargo psp
 popd
@@ -0,0 +1,256 @@
+use crate::sys::ScePspDateTime;
+use crate::time::{self, DateTime, ParseDateTimeError, SystemTime, Weekday};
+use core::convert::TryFrom;
+use core::time::Duration;
+
+const TICKS_PER_DAY: u64 = 86_400 * time::TICKS_PER_SECOND;
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Monday,
+    Weekday::Tuesday,
+    Weekday::Wednesday,
+    Weekday::Thursday,
+    Weekday::Friday,
+    Weekday::Saturday,
+    Weekday::Sunday,
+];
+
+fn at(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> DateTime {
+    DateTime::new(year, month, day, hour, minute, second).unwrap()
+}
+
+/// Walk every day from 0001-01-01, a Monday, to 9999-12-31, counting days
+/// one at a time as the reference for the closed-form conversions.
+#[test]
+fn every_day_matches_a_day_count() {
+    let mut days = 0;
+
+    for year in 1..=9999 {
+        let mut ordinal = 1;
+
+        for month in 1..=12 {
+            for day in 1..=time::days_in_month(year, month) {
+                let date = DateTime::from_date(year, month, day).unwrap();
+
+                assert_eq!(date.ticks(), days * TICKS_PER_DAY, "{}", date);
+                assert_eq!(DateTime::from_ticks(days * TICKS_PER_DAY), Some(date));
+                assert_eq!(date.weekday(), WEEKDAYS[days as usize % 7], "{}", date);
+                assert_eq!(date.ordinal(), ordinal, "{}", date);
+
+                days += 1;
+                ordinal += 1;
+            }
+        }
+
+        let expected = if time::is_leap_year(year) { 367 } else { 366 };
+        assert_eq!(ordinal, expected, "year {}", year);
+    }
+
+    assert_eq!(days * TICKS_PER_DAY, DateTime::MAX.ticks() + 1);
+}
+
+#[test]
+fn leap_years() {
+    let leap: Vec<u16> = (1890..=2010).filter(|&y| time::is_leap_year(y)).collect();
+    let expected: Vec<u16> = (1890..=2010).filter(|&y| y % 4 == 0 && y != 1900).collect();
+
+    assert_eq!(leap, expected);
+    assert!(time::is_leap_year(2000));
+    assert!(time::is_leap_year(1600));
+    assert_eq!(time::days_in_month(2020, 2), 29);
+    assert_eq!(time::days_in_month(2100, 2), 28);
+}
+
+#[test]
+fn time_of_day_and_microseconds() {
+    let date = at(2020, 2, 29, 12, 34, 56)
+        .with_microsecond(789_012)
+        .unwrap();
+    let ticks = date.ticks();
+
+    assert_eq!(
+        ticks % TICKS_PER_DAY,
+        ((12 * 60 + 34) * 60 + 56) * 1_000_000 + 789_012
+    );
+    assert_eq!(DateTime::from_ticks(ticks), Some(date));
+    assert_eq!(DateTime::from_ticks(DateTime::MAX.ticks() + 1), None);
+    assert_eq!(DateTime::from_ticks(0), Some(DateTime::MIN));
+    assert_eq!(date.with_microsecond(1_000_000), None);
+}
+
+#[test]
+fn validation() {
+    assert_eq!(DateTime::new(2019, 2, 29, 0, 0, 0), None);
+    assert_eq!(DateTime::new(2020, 4, 31, 0, 0, 0), None);
+    assert_eq!(DateTime::new(2020, 13, 1, 0, 0, 0), None);
+    assert_eq!(DateTime::new(2020, 1, 0, 0, 0, 0), None);
+    assert_eq!(DateTime::new(0, 1, 1, 0, 0, 0), None);
+    assert_eq!(DateTime::new(10000, 1, 1, 0, 0, 0), None);
+    assert_eq!(DateTime::new(2020, 1, 1, 24, 0, 0), None);
+    assert_eq!(DateTime::new(2020, 1, 1, 0, 60, 0), None);
+    assert_eq!(DateTime::new(2020, 1, 1, 0, 0, 60), None);
+}
+
+#[test]
+fn unix_timestamps() {
+    let epoch = at(1970, 1, 1, 0, 0, 0);
+
+    assert_eq!(epoch.unix_timestamp(), 0);
+    assert_eq!(epoch.ticks(), 62_135_596_800 * time::TICKS_PER_SECOND);
+    assert_eq!(DateTime::from_unix_timestamp(0), Some(epoch));
+    assert_eq!(
+        DateTime::from_unix_timestamp(-1),
+        Some(at(1969, 12, 31, 23, 59, 59))
+    );
+    assert_eq!(
+        DateTime::from_unix_timestamp(1_582_979_696),
+        Some(at(2020, 2, 29, 12, 34, 56))
+    );
+    assert_eq!(
+        DateTime::from_unix_timestamp(i32::MAX as i64),
+        Some(at(2038, 1, 19, 3, 14, 7))
+    );
+    assert_eq!(
+        DateTime::from_unix_timestamp(-62_135_596_800),
+        Some(DateTime::MIN)
+    );
+    assert_eq!(DateTime::from_unix_timestamp(-62_135_596_801), None);
+    assert_eq!(DateTime::from_unix_timestamp(i64::MAX), None);
+    assert_eq!(DateTime::MAX.unix_timestamp(), 253_402_300_799);
+}
+
+#[test]
+fn system_time() {
+    let date = at(2020, 2, 29, 12, 34, 56);
+
+    assert_eq!(DateTime::from(SystemTime::from(date)), date);
+    assert_eq!(
+        DateTime::from(SystemTime::from_ticks(u64::MAX)),
+        DateTime::MAX
+    );
+
+    // The host's clock stands in for the RTC.
+    let now = DateTime::now_utc();
+    assert!(now > at(2020, 1, 1, 0, 0, 0));
+    assert_eq!(now.to_local(), now);
+    assert_eq!(DateTime::local_offset_minutes(), 0);
+}
+
+#[test]
+fn arithmetic() {
+    let date = at(2020, 2, 29, 12, 34, 56);
+
+    assert_eq!(date.add_months(12), DateTime::new(2021, 2, 28, 12, 34, 56));
+    assert_eq!(date.add_months(-3), DateTime::new(2019, 11, 29, 12, 34, 56));
+    assert_eq!(date.add_months(-24), DateTime::new(2018, 2, 28, 12, 34, 56));
+    assert_eq!(date.add_years(4), DateTime::new(2024, 2, 29, 12, 34, 56));
+    assert_eq!(date.add_years(1), DateTime::new(2021, 2, 28, 12, 34, 56));
+    assert_eq!(date.add_days(1), DateTime::new(2020, 3, 1, 12, 34, 56));
+    assert_eq!(date.add_days(-366), DateTime::new(2019, 2, 28, 12, 34, 56));
+    assert_eq!(date.add_years(8000), None);
+    assert_eq!(DateTime::MIN.add_days(-1), None);
+    assert_eq!(DateTime::MAX.checked_add(Duration::from_micros(1)), None);
+    assert_eq!(DateTime::MIN.checked_sub(Duration::from_micros(1)), None);
+
+    assert_eq!(
+        date + Duration::from_secs(12 * 3600),
+        at(2020, 3, 1, 0, 34, 56)
+    );
+    assert_eq!(
+        date - Duration::from_secs(60 * 86_400),
+        at(2019, 12, 31, 12, 34, 56)
+    );
+    assert_eq!(
+        (date + Duration::from_secs(5)).duration_since(date),
+        Some(Duration::from_secs(5))
+    );
+    assert_eq!(date.duration_since(date + Duration::from_secs(5)), None);
+    assert!(date < date + Duration::from_micros(1));
+}
+
+#[test]
+fn formatting() {
+    let date = at(2020, 2, 29, 12, 34, 56);
+
+    assert_eq!(date.to_rfc3339(0), "2020-02-29T12:34:56Z");
+    assert_eq!(date.to_rfc3339(-90), "2020-02-29T11:04:56-01:30");
+    assert_eq!(date.to_rfc2822(540), "Sat, 29 Feb 2020 21:34:56 +0900");
+    assert_eq!(date.to_string(), "2020-02-29T12:34:56Z");
+    assert_eq!(
+        date.with_microsecond(500).unwrap().to_string(),
+        "2020-02-29T12:34:56.000500Z"
+    );
+    assert_eq!(DateTime::MIN.to_string(), "0001-01-01T00:00:00Z");
+}
+
+#[test]
+fn parsing() {
+    let date = at(2020, 2, 29, 12, 34, 56);
+
+    assert_eq!(
+        DateTime::parse_rfc3339("2020-02-29T21:34:56+09:00"),
+        Ok(date)
+    );
+    assert_eq!(DateTime::parse_rfc3339("2020-02-29t12:34:56z"), Ok(date));
+    assert_eq!(
+        "2020-02-29T12:34:56.5Z".parse::<DateTime>(),
+        Ok(date.with_microsecond(500_000).unwrap())
+    );
+    assert_eq!(
+        DateTime::parse_rfc3339("2020-02-30T00:00:00Z"),
+        Err(ParseDateTimeError)
+    );
+    assert_eq!(
+        DateTime::parse_rfc3339("2020-02-29T12:34:56"),
+        Err(ParseDateTimeError)
+    );
+    assert_eq!(
+        DateTime::parse_rfc2822("Sat, 29 Feb 2020 12:34:56 GMT"),
+        Ok(date)
+    );
+    assert_eq!(
+        DateTime::parse_rfc2822("29 feb 2020 07:34 -0500"),
+        Ok(date - Duration::from_secs(56))
+    );
+    assert_eq!(
+        DateTime::parse_rfc2822("29 Foo 2020 07:34 GMT"),
+        Err(ParseDateTimeError)
+    );
+
+    // Formatting and parsing round trip.
+    for &offset in &[0, 60, -600, 345] {
+        let text = date.to_rfc3339(offset);
+        assert_eq!(DateTime::parse_rfc3339(&text), Ok(date), "{}", text);
+
+        let text = date.to_rfc2822(offset);
+        assert_eq!(DateTime::parse_rfc2822(&text), Ok(date), "{}", text);
+    }
+}
+
+#[test]
+fn psp_date_time() {
+    let date = at(2020, 2, 29, 12, 34, 56).with_microsecond(7).unwrap();
+    let raw = ScePspDateTime::from(date);
+
+    assert_eq!(
+        (
+            raw.year,
+            raw.month,
+            raw.day,
+            raw.hour,
+            raw.minutes,
+            raw.seconds,
+            raw.microseconds
+        ),
+        (2020, 2, 29, 12, 34, 56, 7)
+    );
+    assert_eq!(DateTime::try_from(raw), Ok(date));
+    assert_eq!(
+        DateTime::try_from(ScePspDateTime::default()),
+        Err(ParseDateTimeError)
+    );
+    assert_eq!(
+        DateTime::try_from(ScePspDateTime { month: 258, ..raw }),
+        Err(ParseDateTimeError)
+    );
+}
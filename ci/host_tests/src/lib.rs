@@ -0,0 +1,166 @@
+//! Tests of the parts of `psp` which make no system calls, run on the host
+//! with `cargo test`.
+//!
+//! The modules under test are compiled straight from `psp/src`, at the same
+//! module paths, so the `crate::` paths in them resolve. Anything else they
+//! use from the crate is stubbed here.
+
+#![cfg(test)]
+// Keep LLVM from turning the reference loops, and the intrinsics under test,
+// into calls to the C library.
+#![no_builtins]
+// Only some of each module is exercised, and `psp` is built and linted with a
+// 2020 nightly, so lints added since then don't apply to it.
+#![allow(dead_code, unknown_lints, missing_abi)]
+#![allow(clippy::manual_is_multiple_of, clippy::manual_range_contains)]
+#![allow(clippy::manual_clamp, clippy::precedence, clippy::unnecessary_map_or)]
+#![allow(clippy::collapsible_match, clippy::excessive_precision, clippy::manual_div_ceil)]
+#![allow(clippy::mem_replace_with_default, clippy::needless_range_loop)]
+
+extern crate alloc;
+
+#[path = "../../../psp/src/mem"]
+pub mod mem {
+    pub mod intrinsics;
+}
+
+#[path = "../../../psp/src/io.rs"]
+pub mod io;
+
+/// Stands in for `psp::Error`, which the decoders only pass along.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Error(i32);
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "error {:#010x}", self.0)
+    }
+}
+
+pub type SceResult<T> = Result<T, Error>;
+
+/// Stands in for `psp::fs`, for `WavWriter::create`.
+pub mod fs {
+    use crate::io::{self, Seek, SeekFrom, Write};
+
+    pub struct File;
+
+    impl File {
+        pub fn create(_path: &str) -> io::Result<File> {
+            Err(io::Error::InvalidInput)
+        }
+    }
+
+    impl Write for File {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::InvalidInput)
+        }
+    }
+
+    impl Seek for File {
+        fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+            Err(io::Error::InvalidInput)
+        }
+    }
+}
+
+/// Stands in for `psp::audio`, with the real mixing core, players and
+/// software decoders.
+#[path = "../../../psp/src/audio"]
+pub mod audio {
+    mod decoder;
+    mod mixer;
+    mod player;
+    pub mod tracker;
+    pub mod vorbis;
+    pub mod wav;
+
+    pub use decoder::*;
+    pub use mixer::*;
+    pub use player::{Playback, PlaybackControl};
+
+    use crate::SceResult;
+
+    /// Stands in for the hardware channel `PcmPlayer::into_stream` reserves.
+    pub struct AudioChannel;
+
+    impl AudioChannel {
+        pub fn reserve_for_rate(_sample_rate: u32, _sample_count: usize) -> SceResult<Self> {
+            Ok(AudioChannel)
+        }
+    }
+
+    pub struct AudioStream;
+
+    impl AudioStream {
+        pub fn start<F>(_channel: AudioChannel, _fill: F) -> SceResult<Self>
+        where
+            F: FnMut(&mut [i16]) + Send + 'static,
+        {
+            Ok(AudioStream)
+        }
+    }
+}
+
+/// Stands in for `psp::sys`.
+pub mod sys {
+    #[repr(C)]
+    #[derive(Debug, Copy, Clone, Default)]
+    pub struct ScePspDateTime {
+        pub year: u16,
+        pub month: u16,
+        pub day: u16,
+        pub hour: u16,
+        pub minutes: u16,
+        pub seconds: u16,
+        pub microseconds: u32,
+    }
+}
+
+/// The PMF header parser, which cargo-psp includes in the same way.
+#[path = "../../../psp/src/video"]
+pub mod video {
+    pub mod pmf;
+}
+
+/// Stands in for `psp::time`, around the real `date_time`.
+#[path = "../../../psp/src/time"]
+pub mod time {
+    mod date_time;
+    pub use date_time::*;
+
+    pub const TICKS_PER_SECOND: u64 = 1_000_000;
+
+    /// Seconds from 0001-01-01 to 1970-01-01.
+    const UNIX_EPOCH_SECONDS: u64 = 62_135_596_800;
+
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct SystemTime(u64);
+
+    impl SystemTime {
+        /// The host's clock, in place of `sceRtcGetCurrentTick`.
+        pub fn now() -> Self {
+            let since_epoch = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap();
+
+            SystemTime(UNIX_EPOCH_SECONDS * TICKS_PER_SECOND + since_epoch.as_micros() as u64)
+        }
+
+        pub fn ticks(self) -> u64 {
+            self.0
+        }
+
+        pub fn from_ticks(ticks: u64) -> Self {
+            SystemTime(ticks)
+        }
+    }
+}
+
+mod date_time_test;
+mod mem_test;
+mod mixer_test;
+mod pmf_test;
+mod tracker_test;
+mod vorbis_test;
+mod wav_test;
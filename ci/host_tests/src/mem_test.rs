@@ -0,0 +1,153 @@
+use crate::mem::intrinsics::{memcmp, memcpy, memmove, memset};
+use core::ptr;
+
+const BUF_LEN: usize = 320;
+const MAX_OFFSET: usize = 8;
+
+/// Lengths around every path through the intrinsics: the byte loops, the
+/// single word loop and the unrolled one.
+fn lengths() -> impl Iterator<Item = usize> {
+    (0..80).chain(252..260)
+}
+
+// The references work a byte at a time through volatile accesses, so the
+// compiler can't turn them into calls to the intrinsics under test.
+
+fn pattern() -> [u8; BUF_LEN] {
+    let mut buf = [0; BUF_LEN];
+
+    for (i, byte) in buf.iter_mut().enumerate() {
+        unsafe { ptr::write_volatile(byte, (i * 7 + 3) as u8) };
+    }
+
+    buf
+}
+
+fn copy_ref(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        unsafe { ptr::write_volatile(d, ptr::read_volatile(s)) };
+    }
+}
+
+fn assert_same(actual: &[u8], expected: &[u8], context: &str) {
+    for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+        let (a, e) = unsafe { (ptr::read_volatile(a), ptr::read_volatile(e)) };
+        assert_eq!(a, e, "byte {}, {}", i, context);
+    }
+}
+
+#[test]
+fn memset_matches_reference() {
+    for offset in 0..MAX_OFFSET {
+        for len in lengths() {
+            let mut actual = pattern();
+            let mut expected = pattern();
+
+            unsafe { memset(actual.as_mut_ptr().add(offset), 0x1a5, len) };
+
+            for byte in &mut expected[offset..offset + len] {
+                unsafe { ptr::write_volatile(byte, 0xa5) };
+            }
+
+            assert_same(&actual, &expected, &format!("offset {}, len {}", offset, len));
+        }
+    }
+}
+
+#[test]
+fn memcpy_matches_reference() {
+    let src = pattern();
+
+    for dst_offset in 0..MAX_OFFSET {
+        for src_offset in 0..MAX_OFFSET {
+            for len in lengths() {
+                let mut actual = [0u8; BUF_LEN];
+                let mut expected = [0u8; BUF_LEN];
+
+                let ret = unsafe {
+                    memcpy(
+                        actual.as_mut_ptr().add(dst_offset),
+                        src.as_ptr().add(src_offset),
+                        len,
+                    )
+                };
+
+                copy_ref(
+                    &mut expected[dst_offset..],
+                    &src[src_offset..src_offset + len],
+                );
+
+                let context = format!("dst {}, src {}, len {}", dst_offset, src_offset, len);
+                assert_eq!(ret, unsafe { actual.as_mut_ptr().add(dst_offset) }, "{}", context);
+                assert_same(&actual, &expected, &context);
+            }
+        }
+    }
+}
+
+#[test]
+fn memmove_matches_reference() {
+    // Within a single buffer, covering both overlap directions.
+    for dst_offset in 0..2 * MAX_OFFSET {
+        for src_offset in 0..2 * MAX_OFFSET {
+            for len in lengths() {
+                let mut actual = pattern();
+                let mut expected = pattern();
+
+                unsafe {
+                    let ptr = actual.as_mut_ptr();
+                    memmove(ptr.add(dst_offset), ptr.add(src_offset), len);
+                }
+
+                let mut tmp = [0u8; BUF_LEN];
+                copy_ref(&mut tmp, &expected[src_offset..src_offset + len]);
+                copy_ref(&mut expected[dst_offset..], &tmp[..len]);
+
+                let context = format!("dst {}, src {}, len {}", dst_offset, src_offset, len);
+                assert_same(&actual, &expected, &context);
+            }
+        }
+    }
+}
+
+#[test]
+fn memcmp_finds_first_difference() {
+    let a = pattern();
+
+    for a_offset in 0..MAX_OFFSET {
+        for b_offset in 0..MAX_OFFSET {
+            for len in lengths().filter(|&len| len > 0) {
+                let mut b = [0u8; BUF_LEN];
+                copy_ref(&mut b[b_offset..], &a[a_offset..a_offset + len]);
+
+                let cmp = |b: &[u8; BUF_LEN]| unsafe {
+                    memcmp(a.as_ptr().add(a_offset), b.as_ptr().add(b_offset), len)
+                };
+
+                let context = format!("a {}, b {}, len {}", a_offset, b_offset, len);
+                assert_eq!(cmp(&b), 0, "equal: {}", context);
+
+                // Differ at the first, middle and last bytes. Bytes compare
+                // as unsigned.
+                for &at in &[0, len / 2, len - 1] {
+                    let original = b[b_offset + at];
+                    b[b_offset + at] = original.wrapping_add(0x80);
+
+                    let expected = original as i32 - b[b_offset + at] as i32;
+                    assert_eq!(cmp(&b), expected, "differ at {}: {}", at, context);
+
+                    b[b_offset + at] = original;
+                }
+
+                // The first difference decides.
+                if len > 1 {
+                    b[b_offset] = b[b_offset].wrapping_add(1);
+                    b[b_offset + len - 1] = b[b_offset + len - 1].wrapping_add(0x80);
+
+                    let expected = a[a_offset] as i32 - b[b_offset] as i32;
+                    assert_eq!(cmp(&b), expected, "first difference: {}", context);
+                }
+            }
+        }
+    }
+}
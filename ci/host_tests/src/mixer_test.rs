@@ -0,0 +1,271 @@
+use crate::audio::{Interpolation, Mixer, Sound, VoiceParams};
+
+/// Noise from a linear congruential generator, so the tests are repeatable.
+fn noise(len: usize) -> Vec<i16> {
+    let mut state = 12345u32;
+
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as i16
+        })
+        .collect()
+}
+
+fn params(volume: f32, pan: f32) -> VoiceParams {
+    VoiceParams {
+        volume,
+        pan,
+        ..VoiceParams::default()
+    }
+}
+
+fn mix(mixer: &mut Mixer, frames: usize) -> Vec<i16> {
+    let mut out = vec![0; frames * 2];
+    mixer.mix(&mut out);
+    out
+}
+
+#[test]
+fn same_rate_copies_samples() {
+    let samples = noise(64);
+    let mut mixer = Mixer::new(44100, 1);
+    mixer.play_oneshot(
+        &Sound::new(samples.clone(), 1, 44100),
+        VoiceParams::default(),
+    );
+
+    let out = mix(&mut mixer, 64);
+    let left: Vec<i16> = out.iter().step_by(2).copied().collect();
+    let right: Vec<i16> = out.iter().skip(1).step_by(2).copied().collect();
+
+    assert_eq!(left, samples);
+    assert_eq!(right, samples);
+}
+
+#[test]
+fn linear_matches_reference() {
+    let samples = noise(1200);
+    let mut mixer = Mixer::new(48000, 1);
+    mixer.play_oneshot(
+        &Sound::new(samples.clone(), 1, 44100),
+        VoiceParams::default(),
+    );
+
+    let out = mix(&mut mixer, 1000);
+
+    // Positions step by the same fixed-point rate ratio the mixer uses.
+    let step = (44100.0f32 / 48000.0 * (1u64 << 32) as f32) as u64;
+
+    for (i, frame) in out.chunks(2).enumerate() {
+        let position = i as u64 * step;
+        let index = (position >> 32) as usize;
+        let t = (position & 0xffff_ffff) as f64 / (1u64 << 32) as f64;
+
+        let (s0, s1) = (samples[index] as f64, samples[index + 1] as f64);
+        let expected = s0 + (s1 - s0) * t;
+
+        // The mixer keeps 15 bits of `t`, and rounds down.
+        let tolerance = 1.0 + (s1 - s0).abs() / 32768.0;
+
+        assert!(
+            (frame[0] as f64 - expected).abs() <= tolerance,
+            "frame {}: {} vs {}",
+            i,
+            frame[0],
+            expected
+        );
+        assert_eq!(frame[0], frame[1]);
+    }
+}
+
+#[test]
+fn cubic_reproduces_samples_and_lines() {
+    let samples = noise(64);
+    let mut mixer = Mixer::new(44100, 1);
+    mixer.set_interpolation(Interpolation::Cubic);
+    mixer.play_oneshot(
+        &Sound::new(samples.clone(), 1, 44100),
+        VoiceParams::default(),
+    );
+
+    let out = mix(&mut mixer, 64);
+    let left: Vec<i16> = out.iter().step_by(2).copied().collect();
+    assert_eq!(left, samples);
+
+    // Catmull-Rom follows a straight line exactly, away from its ends.
+    let ramp: Vec<i16> = (0..32).map(|i| i * 100).collect();
+    mixer.play_oneshot(&Sound::new(ramp, 1, 22050), VoiceParams::default());
+
+    let out = mix(&mut mixer, 40);
+
+    for i in 2..40 {
+        let expected = i as i32 * 50;
+        assert!((out[i * 2] as i32 - expected).abs() <= 1, "frame {}", i);
+    }
+}
+
+#[test]
+fn volume_and_pan() {
+    let sound = Sound::new(vec![10000; 8], 1, 44100);
+    let frame = |volume, pan| {
+        let mut mixer = Mixer::new(44100, 1);
+        mixer.play_oneshot(&sound, params(volume, pan));
+        let out = mix(&mut mixer, 1);
+        (out[0], out[1])
+    };
+
+    assert_eq!(frame(1.0, 0.0), (10000, 10000));
+    assert_eq!(frame(0.5, 0.0), (5000, 5000));
+    assert_eq!(frame(1.0, -1.0), (10000, 0));
+    assert_eq!(frame(1.0, 1.0), (0, 10000));
+    assert_eq!(frame(1.0, 0.5), (5000, 10000));
+    assert_eq!(frame(0.5, -0.5), (5000, 2500));
+
+    // Out of range pans are clamped.
+    assert_eq!(frame(1.0, -3.0), (10000, 0));
+}
+
+#[test]
+fn master_volume_applies_to_playing_voices() {
+    let sound = Sound::new(vec![8000; 100], 1, 44100);
+    let mut mixer = Mixer::new(44100, 2);
+    mixer.play_oneshot(&sound, VoiceParams::default());
+
+    assert_eq!(mix(&mut mixer, 1), [8000, 8000]);
+    mixer.set_master_volume(0.25);
+    assert_eq!(mix(&mut mixer, 1), [2000, 2000]);
+}
+
+#[test]
+fn stereo_sounds_keep_their_channels() {
+    let sound = Sound::new(vec![1000, -2000, 3000, -4000], 2, 44100);
+    let mut mixer = Mixer::new(44100, 1);
+    mixer.play_oneshot(&sound, VoiceParams::default());
+
+    assert_eq!(mix(&mut mixer, 3), [1000, -2000, 3000, -4000, 0, 0]);
+    assert_eq!(mixer.active_voices(), 0);
+}
+
+#[test]
+fn voices_add_up_and_clip() {
+    let mut mixer = Mixer::new(44100, 4);
+    mixer.play_oneshot(&Sound::new(vec![1000; 4], 1, 44100), VoiceParams::default());
+    mixer.play_oneshot(&Sound::new(vec![-300; 4], 1, 44100), VoiceParams::default());
+    assert_eq!(mix(&mut mixer, 1), [700, 700]);
+
+    mixer.stop_all();
+    mixer.play_oneshot(
+        &Sound::new(vec![30000; 4], 1, 44100),
+        VoiceParams::default(),
+    );
+    mixer.play_oneshot(&Sound::new(vec![30000; 4], 1, 44100), params(1.0, 1.0));
+    mixer.play_oneshot(&Sound::new(vec![-30000; 4], 1, 44100), params(1.0, -1.0));
+    mixer.play_oneshot(&Sound::new(vec![-30000; 4], 1, 44100), params(1.0, -1.0));
+    let out = mix(&mut mixer, 1);
+    assert_eq!(out, [-30000, i16::MAX]);
+}
+
+#[test]
+fn looping_wraps_around() {
+    let sound = Sound::new(vec![1, 2, 3], 1, 44100);
+    let mut mixer = Mixer::new(44100, 1);
+    let voice = mixer
+        .play(
+            &sound,
+            VoiceParams {
+                looping: true,
+                ..VoiceParams::default()
+            },
+        )
+        .unwrap();
+
+    let out = mix(&mut mixer, 7);
+    let left: Vec<i16> = out.iter().step_by(2).copied().collect();
+    assert_eq!(left, [1, 2, 3, 1, 2, 3, 1]);
+
+    // Turning looping off plays to the end, then stops.
+    assert!(mixer.set_looping(voice, false));
+    let out = mix(&mut mixer, 4);
+    let left: Vec<i16> = out.iter().step_by(2).copied().collect();
+    assert_eq!(left, [2, 3, 0, 0]);
+    assert!(!mixer.is_playing(voice));
+}
+
+#[test]
+fn pitch_changes_the_length() {
+    let sound = Sound::new(vec![100; 100], 1, 44100);
+    let mut mixer = Mixer::new(44100, 1);
+    let voice = mixer.play(&sound, VoiceParams::default()).unwrap();
+
+    mixer.set_pitch(voice, 2.0);
+    mix(&mut mixer, 49);
+    assert!(mixer.is_playing(voice));
+    mix(&mut mixer, 1);
+    assert!(!mixer.is_playing(voice));
+
+    // Half the output rate is the same as half the pitch.
+    let mut mixer = Mixer::new(22050, 1);
+    let voice = mixer.play(&sound, VoiceParams::default()).unwrap();
+    mixer.set_pitch(voice, 0.5);
+    mix(&mut mixer, 99);
+    assert!(mixer.is_playing(voice));
+    mix(&mut mixer, 101);
+    assert!(!mixer.is_playing(voice));
+}
+
+#[test]
+fn handles_go_stale() {
+    let sound = Sound::new(vec![0; 100], 1, 44100);
+    let mut mixer = Mixer::new(44100, 1);
+
+    let first = mixer.play(&sound, VoiceParams::default()).unwrap();
+    mixer.stop(first);
+    assert!(!mixer.is_playing(first));
+
+    // The slot is reused, but the old handle doesn't refer to the new voice.
+    let second = mixer.play(&sound, params(0.5, 0.0)).unwrap();
+    assert_ne!(first, second);
+    assert!(!mixer.set_volume(first, 1.0));
+    assert_eq!(mixer.params(second), Some(params(0.5, 0.0)));
+
+    mixer.stop(first);
+    assert!(mixer.is_playing(second));
+}
+
+#[test]
+fn oldest_oneshot_is_replaced() {
+    let sound = |value| Sound::new(vec![value; 100], 1, 44100);
+    let mut mixer = Mixer::new(44100, 3);
+
+    let kept = mixer.play(&sound(1), VoiceParams::default()).unwrap();
+    assert!(mixer.play_oneshot(&sound(10), VoiceParams::default()));
+    assert!(mixer.play_oneshot(&sound(100), VoiceParams::default()));
+
+    // Full: the oneshot playing 10 goes, the handle voice stays.
+    assert!(mixer.play_oneshot(&sound(1000), VoiceParams::default()));
+    assert_eq!(mix(&mut mixer, 1), [1101, 1101]);
+    assert!(mixer.is_playing(kept));
+
+    // Then the one playing 100.
+    assert!(mixer.play(&sound(0), VoiceParams::default()).is_some());
+    assert_eq!(mix(&mut mixer, 1), [1001, 1001]);
+
+    // Only voices with handles are left to replace, so nothing more plays.
+    mixer.stop_all();
+    for _ in 0..3 {
+        mixer.play(&sound(0), VoiceParams::default()).unwrap();
+    }
+    assert_eq!(mixer.play(&sound(0), VoiceParams::default()), None);
+    assert!(!mixer.play_oneshot(&sound(0), VoiceParams::default()));
+}
+
+#[test]
+fn odd_lengths_end_in_silence() {
+    let mut mixer = Mixer::new(44100, 1);
+    mixer.play_oneshot(&Sound::new(vec![500; 10], 1, 44100), VoiceParams::default());
+
+    let mut out = [1i16; 5];
+    mixer.mix(&mut out);
+    assert_eq!(out, [500, 500, 500, 500, 0]);
+}
@@ -0,0 +1,165 @@
+use crate::video::pmf::{self, EpEntry, PmfError, PmfHeader, PmfStream};
+use core::time::Duration;
+
+/// A PMF header for a 144x80 AVC stream and a stereo ATRAC3plus stream, one
+/// second long, with one entry point.
+fn icon_header() -> Vec<u8> {
+    let mut bytes = vec![0u8; pmf::HEADER_SIZE];
+    bytes[..8].copy_from_slice(b"PSMF0015");
+    bytes[8..12].copy_from_slice(&2048u32.to_be_bytes());
+    bytes[12..16].copy_from_slice(&4096u32.to_be_bytes());
+    bytes[0x56..0x5a].copy_from_slice(&90_000u32.to_be_bytes());
+    bytes[0x5c..0x60].copy_from_slice(&180_000u32.to_be_bytes());
+
+    bytes[0x81] = 2;
+    bytes[0x82] = 0xe0;
+    bytes[0x86..0x8a].copy_from_slice(&0x200u32.to_be_bytes());
+    bytes[0x8a..0x8e].copy_from_slice(&1u32.to_be_bytes());
+    bytes[0x8e] = 144 / 16;
+    bytes[0x8f] = 80 / 16;
+    bytes[0x92] = 0xbd;
+    bytes[0xa0] = 2;
+    bytes[0xa1] = 2;
+
+    bytes[0x202..0x206].copy_from_slice(&90_000u32.to_be_bytes());
+    bytes
+}
+
+/// A video PES packet with the given timestamp, after a pack header.
+fn video_packet(pts: u64) -> Vec<u8> {
+    let mut bytes = vec![0, 0, 1, 0xba, 0x44, 0, 4, 0, 4, 1, 0x01, 0x89, 0xc3, 0xf8];
+    bytes.extend_from_slice(&[0, 0, 1, 0xe0, 0, 8, 0x80, 0x80, 5]);
+    bytes.extend_from_slice(&[
+        0x21 | ((pts >> 29) & 0xe) as u8,
+        (pts >> 22) as u8,
+        (pts >> 14) as u8 | 1,
+        (pts >> 7) as u8,
+        (pts << 1) as u8 | 1,
+    ]);
+    bytes
+}
+
+#[test]
+fn header() {
+    let header = icon_header();
+    let parsed = PmfHeader::parse(&header).unwrap();
+
+    assert_eq!(parsed.version(), 15);
+    assert_eq!(parsed.stream_offset(), 2048);
+    assert_eq!(parsed.stream_size(), 4096);
+    assert_eq!(parsed.video_size(), Some((144, 80)));
+    assert_eq!(parsed.duration(), Duration::from_secs(1));
+    assert_eq!(
+        parsed.streams().collect::<Vec<_>>(),
+        [
+            PmfStream::Avc {
+                channel: 0,
+                width: 144,
+                height: 80,
+            },
+            PmfStream::Atrac {
+                channel: 0,
+                channels: 2,
+                sample_rate: Some(44100),
+            },
+        ]
+    );
+}
+
+#[test]
+fn ep_map() {
+    let header = icon_header();
+    let parsed = PmfHeader::parse(&header).unwrap();
+
+    assert_eq!(
+        parsed.ep_map().collect::<Vec<_>>(),
+        [EpEntry {
+            pts: 90_000,
+            offset: 0,
+        }]
+    );
+
+    // Without a video stream, there is no map.
+    let mut audio_only = header.clone();
+    audio_only[0x81] = 1;
+    audio_only.copy_within(0x92..0xa2, 0x82);
+    let parsed = PmfHeader::parse(&audio_only).unwrap();
+    assert_eq!(parsed.ep_map().count(), 0);
+    assert_eq!(parsed.video_size(), None);
+}
+
+#[test]
+fn invalid() {
+    let header = icon_header();
+
+    assert_eq!(PmfHeader::parse(b"RIFF0000").err(), Some(PmfError::NotPmf));
+    assert_eq!(PmfHeader::parse(b"PSMF00x5").err(), Some(PmfError::NotPmf));
+    assert_eq!(PmfHeader::parse(b"PSMF").err(), Some(PmfError::Truncated));
+    assert_eq!(
+        PmfHeader::parse(&header[..0x90]).err(),
+        Some(PmfError::Truncated)
+    );
+
+    // Every cut either parses or is truncated, without panicking.
+    for len in 0..header.len() {
+        match PmfHeader::parse(&header[..len]) {
+            Ok(parsed) => assert_eq!(parsed.ep_map().count(), 1),
+            Err(e) => assert_eq!(e, PmfError::Truncated, "cut at {}", len),
+        }
+    }
+}
+
+#[test]
+fn ep_map_overflow() {
+    // Sizes which overflow, rather than only pointing past the end.
+    for &(offset, entries) in &[(0x200, u32::MAX), (u32::MAX, 1), (u32::MAX, u32::MAX)] {
+        let mut header = icon_header();
+        header[0x86..0x8a].copy_from_slice(&offset.to_be_bytes());
+        header[0x8a..0x8e].copy_from_slice(&entries.to_be_bytes());
+
+        assert_eq!(
+            PmfHeader::parse(&header).err(),
+            Some(PmfError::Truncated),
+            "{:#x} entries at {:#x}",
+            entries,
+            offset
+        );
+    }
+}
+
+#[test]
+fn long_duration() {
+    // 48-bit timestamps, which overflow in microseconds.
+    let mut header = icon_header();
+    header[0x54..0x5a].copy_from_slice(&[0; 6]);
+    header[0x5a..0x60].copy_from_slice(&[0xff; 6]);
+
+    let pts = 0xffff_ffff_ffffu64;
+    let want = Duration::from_secs(pts / 90_000)
+        + Duration::from_micros(pts % 90_000 * 1_000_000 / 90_000);
+    assert_eq!(PmfHeader::parse(&header).unwrap().duration(), want);
+}
+
+#[test]
+fn frame_duration() {
+    // Frames stored out of presentation order, at 29.97 fps.
+    let stream: Vec<u8> = [0, 3, 1, 2, 6, 4, 5]
+        .iter()
+        .flat_map(|&frame| video_packet(90_000 + frame * 3003))
+        .collect();
+    assert_eq!(pmf::frame_duration(&stream), Some(3003));
+
+    // 25 fps, which cargo-psp turns away for an icon.
+    let stream: Vec<u8> = (0..4)
+        .flat_map(|frame| video_packet(frame * 3600))
+        .collect();
+    assert_eq!(pmf::frame_duration(&stream), Some(3600));
+
+    assert_eq!(pmf::frame_duration(&[]), None);
+    assert_eq!(pmf::frame_duration(&video_packet(90_000)), None);
+
+    // A cut packet gives up on the rest, without panicking.
+    for len in 0..stream.len() {
+        pmf::frame_duration(&stream[..len]);
+    }
+}
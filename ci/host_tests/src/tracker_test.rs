@@ -0,0 +1,699 @@
+//! XM and IT playback, checked sample for sample against what the mixer
+//! should produce.
+//!
+//! The modules are built to make that exact: notes play their samples at
+//! the output rate or half of it, and volumes are powers of two, so the
+//! expected output is the sample data scaled by a gain of 4096ths.
+
+use crate::audio::tracker::{Module, ModuleDecoder, ModuleFormat};
+use crate::audio::PcmDecoder;
+use crate::io::Error;
+
+/// Decode all of `decoder`, `chunk` samples at a time.
+fn decode_in_chunks(decoder: &mut ModuleDecoder, chunk: usize) -> Vec<i16> {
+    let mut samples = Vec::new();
+    let mut buffer = vec![0; chunk];
+
+    loop {
+        match decoder.read(&mut buffer).unwrap() {
+            0 => return samples,
+            n => samples.extend_from_slice(&buffer[..n]),
+        }
+    }
+}
+
+/// `sample` scaled by `gain` 4096ths, as the mixer does.
+fn scale(sample: i16, gain: i32) -> i32 {
+    sample as i32 * gain >> 12
+}
+
+fn u16_le(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn u32_le(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+/// A 40-byte XM sample header and its delta coded data.
+struct XmSample {
+    header: Vec<u8>,
+    data: Vec<u8>,
+}
+
+fn xm_sample(data: &[i16], wide: bool, sample_loop: Option<(u32, u32)>) -> XmSample {
+    let size = if wide { 2 } else { 1 };
+    let mut header = Vec::new();
+    u32_le(&mut header, data.len() as u32 * size);
+
+    let (start, len) = sample_loop.unwrap_or((0, 0));
+    u32_le(&mut header, start * size);
+    u32_le(&mut header, len * size);
+
+    // Volume 64, no finetune, the loop and width flags, centered, no
+    // relative note.
+    let flags = sample_loop.map_or(0, |_| 1) | if wide { 0x10 } else { 0 };
+    header.extend_from_slice(&[64, 0, flags, 128, 0, 0]);
+    header.resize(40, 0);
+
+    let mut old = 0i16;
+    let mut bytes = Vec::new();
+
+    for &sample in data {
+        let delta = sample.wrapping_sub(old);
+        old = sample;
+
+        if wide {
+            bytes.extend_from_slice(&delta.to_le_bytes());
+        } else {
+            bytes.push((delta >> 8) as u8);
+        }
+    }
+
+    XmSample {
+        header,
+        data: bytes,
+    }
+}
+
+/// An XM instrument of one sample played by every note, with a volume
+/// envelope of `(tick, value)` points if given.
+fn xm_instrument(sample: XmSample, envelope: &[(u16, u16)]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    u32_le(&mut bytes, 243);
+    bytes.extend_from_slice(&[0; 23]);
+    u16_le(&mut bytes, 1);
+    u32_le(&mut bytes, 40);
+
+    // The keyboard, then the volume and panning envelope points.
+    let mut header = vec![0u8; 210];
+
+    for (i, &(tick, value)) in envelope.iter().enumerate() {
+        header[96 + i * 4..96 + i * 4 + 2].copy_from_slice(&tick.to_le_bytes());
+        header[96 + i * 4 + 2..96 + i * 4 + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    header[192] = envelope.len() as u8;
+    header[200] = if envelope.is_empty() { 0 } else { 1 };
+    bytes.extend(header);
+    assert_eq!(bytes.len(), 243);
+
+    bytes.extend(sample.header);
+    bytes.extend(sample.data);
+    bytes
+}
+
+/// An XM of one pattern with linear slides, at speed 1 and 125 bpm.
+/// Cells are the note, instrument, volume, effect and parameter.
+fn xm(channels: usize, rows: &[Vec<[u8; 5]>], instruments: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut bytes = b"Extended Module: ".to_vec();
+    bytes.extend_from_slice(b"exact\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0");
+    bytes.push(0x1a);
+    bytes.extend_from_slice(&[b' '; 20]);
+    u16_le(&mut bytes, 0x104);
+
+    u32_le(&mut bytes, 276);
+    u16_le(&mut bytes, 1);
+    u16_le(&mut bytes, 0);
+    u16_le(&mut bytes, channels as u16);
+    u16_le(&mut bytes, 1);
+    u16_le(&mut bytes, instruments.len() as u16);
+    u16_le(&mut bytes, 1);
+    u16_le(&mut bytes, 1);
+    u16_le(&mut bytes, 125);
+    bytes.extend_from_slice(&[0; 256]);
+
+    // Every cell unpacked, as its five fields.
+    let packed: Vec<u8> = rows.iter().flatten().flatten().copied().collect();
+    u32_le(&mut bytes, 9);
+    bytes.push(0);
+    u16_le(&mut bytes, rows.len() as u16);
+    u16_le(&mut bytes, packed.len() as u16);
+    bytes.extend(packed);
+
+    for instrument in instruments {
+        bytes.extend(instrument);
+    }
+
+    bytes
+}
+
+/// The frames of each tick at `sample_rate` and 125 bpm, whose fractions
+/// carry over to the next.
+fn tick_starts(sample_rate: u32, ticks: u32) -> Vec<usize> {
+    (0..=ticks)
+        .map(|tick| (tick * sample_rate * 5 / 250) as usize)
+        .collect()
+}
+
+#[test]
+fn xm_playback() {
+    // Plays at C-4 at its own rate of 8363 Hz: one sample a frame. A
+    // forward loop over its second half.
+    let ramp: Vec<i16> = (0..16).map(|i| (i - 8) * 1024 + 7).collect();
+    let looped = xm_instrument(xm_sample(&ramp, true, Some((8, 8))), &[]);
+
+    // Plays at C-3, half its rate, with a volume envelope falling to 0 over
+    // two ticks.
+    let flat = vec![64 << 8; 2000];
+    let enveloped = xm_instrument(xm_sample(&flat, false, None), &[(0, 64), (2, 0)]);
+
+    let rows = vec![
+        // The second channel is panned left by the volume column.
+        vec![[49, 1, 0, 0, 0], [37, 2, 0xc0, 0, 0]],
+        // C20: half volume.
+        vec![[0, 0, 0, 0xc, 0x20], [0, 0, 0, 0, 0]],
+        // Key off, without an envelope, silences the note.
+        vec![[97, 0, 0, 0, 0], [0, 0, 0, 0, 0]],
+        vec![[0, 0, 0, 0, 0], [0, 0, 0, 0, 0]],
+    ];
+
+    let module = Module::from_bytes(&xm(2, &rows, vec![looped, enveloped])).unwrap();
+    assert_eq!(module.title(), "exact");
+    assert_eq!(module.format(), ModuleFormat::Xm);
+    assert_eq!(module.channels(), 2);
+
+    let mut decoder = ModuleDecoder::new(module, 8363);
+    let got = decode_in_chunks(&mut decoder, 1000);
+
+    // XM mixes at 48 of 128, so full volume is a gain of 1536. Both
+    // channels happen to halve, then go silent.
+    let starts = tick_starts(8363, 4);
+    let gains = [1536, 768, 0, 0];
+    let mut want = Vec::new();
+
+    for tick in 0..4 {
+        for frame in starts[tick]..starts[tick + 1] {
+            let i = if frame < 16 {
+                frame
+            } else {
+                8 + (frame - 8) % 8
+            };
+            let first = scale(ramp[i], gains[tick]);
+            let second = scale(64 << 8, gains[tick]);
+
+            want.push((first + second) as i16);
+            want.push(first as i16);
+        }
+    }
+
+    assert_eq!(got.len(), want.len());
+    assert_eq!(got, want);
+
+    // Any buffer size plays the same, and so does a rewound decoder.
+    for &chunk in &[2, 6, 334, 4096] {
+        decoder.rewind().unwrap();
+        assert_eq!(
+            decode_in_chunks(&mut decoder, chunk),
+            want,
+            "chunk {}",
+            chunk
+        );
+    }
+}
+
+#[test]
+fn xm_interpolation() {
+    // At C-3, each frame is halfway between two samples, or on one.
+    let samples: Vec<i16> = vec![0, 4096, -4096, 1001, 30000, -30000];
+    let instrument = xm_instrument(xm_sample(&samples, true, None), &[]);
+    let rows = vec![vec![[37, 1, 0, 0, 0]]];
+
+    let module = Module::from_bytes(&xm(1, &rows, vec![instrument])).unwrap();
+    let got = decode_in_chunks(&mut ModuleDecoder::new(module, 8363), 64);
+
+    let mut want = Vec::new();
+
+    for frame in 0..samples.len() * 2 {
+        let i = frame / 2;
+        let s0 = samples[i] as i32;
+        let s1 = *samples.get(i + 1).unwrap_or(&samples[i]) as i32;
+        let s = if frame % 2 == 0 {
+            s0
+        } else {
+            s0 + ((s1 - s0) * 0x4000 >> 15)
+        };
+
+        want.push(scale(s as i16, 1536) as i16);
+        want.push(scale(s as i16, 1536) as i16);
+    }
+
+    // Then silence to the end of the tick.
+    assert_eq!(&got[..want.len()], &want[..]);
+    assert!(got[want.len()..].iter().all(|&s| s == 0));
+    assert_eq!(got.len(), tick_starts(8363, 1)[1] * 2);
+}
+
+#[test]
+fn xm_invalid() {
+    assert_eq!(
+        Module::from_bytes(b"Extended Module: ").err(),
+        Some(Error::InvalidData)
+    );
+
+    // A loop whose end overflows the PSP's 32-bit usize, and so is rejected
+    // there. Here it only ends past the sample, so the loop is dropped.
+    let mut sample = xm_sample(&[0; 4], false, Some((0, 1)));
+    sample.header[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+    sample.header[8..12].copy_from_slice(&2u32.to_le_bytes());
+    let rows = vec![vec![[49, 1, 0, 0, 0]]];
+
+    match Module::from_bytes(&xm(1, &rows, vec![xm_instrument(sample, &[])])) {
+        Ok(module) => assert_eq!(
+            decode_in_chunks(&mut ModuleDecoder::new(module, 8363), 64).len(),
+            334
+        ),
+        Err(e) => assert_eq!(e, Error::InvalidData),
+    }
+
+    // Cut anywhere, it fails without panicking.
+    let whole = xm(
+        1,
+        &rows,
+        vec![xm_instrument(xm_sample(&[1, 2, 3], true, None), &[])],
+    );
+
+    for len in 0..whole.len() - 6 {
+        assert!(Module::from_bytes(&whole[..len]).is_err(), "cut at {}", len);
+    }
+}
+
+/// Writes bits lowest first, as IT's compressed samples are read.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        for i in 0..count {
+            if self.bits % 8 == 0 {
+                self.bytes.push(0);
+            }
+
+            let bit = (value >> i & 1) as u8;
+            *self.bytes.last_mut().unwrap() |= bit << (self.bits % 8);
+            self.bits += 1;
+        }
+    }
+}
+
+/// Compress `samples` as IT 2.14, or 2.15 with `it215`, switching each value
+/// to the narrowest width it fits.
+fn it_compress(samples: &[i16], wide: bool, it215: bool) -> Vec<u8> {
+    let (max_width, block_samples, width_bits, sample_bits) = if wide {
+        (17u32, 0x4000, 4u32, 16u32)
+    } else {
+        (9, 0x8000, 3, 8)
+    };
+
+    let border =
+        |width: u32| ((1u32 << sample_bits) - 1 >> (max_width - width)) - (1 << (width_bits - 1));
+
+    // The value of `delta` at `width`, if it isn't one which changes width.
+    let encode = |delta: i32, width: u32| -> Option<u32> {
+        let bits = width.min(sample_bits);
+        let (min, max) = (-(1i32 << (bits - 1)), (1i32 << (bits - 1)) - 1);
+
+        if delta < min || delta > max {
+            return None;
+        }
+
+        // Past the sample's width, the extra bit is left clear.
+        let value = delta as u32 & ((1u64 << bits) - 1) as u32;
+
+        let escape = if width < 7 {
+            value == 1 << (width - 1)
+        } else if width < max_width {
+            value > border(width) && value <= border(width) + (1 << width_bits)
+        } else {
+            value & 1 << (max_width - 1) != 0
+        };
+
+        if escape {
+            None
+        } else {
+            Some(value)
+        }
+    };
+
+    let mut out = Vec::new();
+
+    for block in samples.chunks(block_samples) {
+        let mut bits = BitWriter {
+            bytes: Vec::new(),
+            bits: 0,
+        };
+        let mut width = max_width;
+        let (mut last, mut last_change) = (0i32, 0i32);
+
+        for &sample in block {
+            let sample = if wide {
+                sample as i32
+            } else {
+                (sample >> 8) as i32
+            };
+
+            // What the running sums add, wrapped to the sample's width.
+            let change = sample - last;
+            let delta = if it215 { change - last_change } else { change };
+            let shift = 32 - sample_bits;
+            let delta = delta << shift >> shift;
+            last = sample;
+            last_change = change << shift >> shift;
+
+            let new_width = (1..=max_width)
+                .find(|&w| encode(delta, w).is_some())
+                .unwrap();
+
+            if new_width != width {
+                // Widths are stored skipping the current one.
+                let stored = if new_width < width {
+                    new_width
+                } else {
+                    new_width - 1
+                };
+
+                if width < 7 {
+                    bits.write(1 << (width - 1), width);
+                    bits.write(stored - 1, width_bits);
+                } else if width < max_width {
+                    bits.write(border(width) + stored, width);
+                } else {
+                    bits.write(1 << (max_width - 1) | (new_width - 1), width);
+                }
+
+                width = new_width;
+            }
+
+            bits.write(encode(delta, width).unwrap(), width);
+        }
+
+        u16_le(&mut out, bits.bytes.len() as u16);
+        out.extend(bits.bytes);
+    }
+
+    out
+}
+
+/// An IT sample header, with the flags for its loops, and the converted
+/// data it points to.
+struct ItSample {
+    flags: u8,
+    convert: u8,
+    len: u32,
+    sample_loop: (u32, u32),
+    data: Vec<u8>,
+}
+
+impl ItSample {
+    fn new(flags: u8, convert: u8, len: usize, data: Vec<u8>) -> Self {
+        ItSample {
+            flags: flags | 0x01,
+            convert,
+            len: len as u32,
+            sample_loop: (0, 0),
+            data,
+        }
+    }
+
+    /// 16-bit samples, compressed or not.
+    fn wide(samples: &[i16], compressed: bool, it215: bool) -> Self {
+        if compressed {
+            let data = it_compress(samples, true, it215);
+            let convert = if it215 { 0x05 } else { 0x01 };
+            ItSample::new(0x0a, convert, samples.len(), data)
+        } else {
+            let data = samples
+                .iter()
+                .flat_map(|s| s.to_le_bytes().to_vec())
+                .collect();
+            ItSample::new(0x02, 0x01, samples.len(), data)
+        }
+    }
+
+    /// 8-bit samples from the top of `samples`, compressed or unsigned.
+    fn narrow(samples: &[i16], compressed: bool, it215: bool) -> Self {
+        if compressed {
+            let data = it_compress(samples, false, it215);
+            let convert = if it215 { 0x05 } else { 0x01 };
+            ItSample::new(0x08, convert, samples.len(), data)
+        } else {
+            let data = samples.iter().map(|&s| (s >> 8) as u8 ^ 0x80).collect();
+            ItSample::new(0x00, 0x00, samples.len(), data)
+        }
+    }
+}
+
+/// An IT without instruments, with linear slides, stereo and full mixing
+/// volume, of one pattern with a row of notes played at C-5 by the first
+/// `notes.len()` channels. Each channel is panned by `panning`, 0 to 64.
+fn it(speed: u8, tempo: u8, notes: &[(u8, u8)], samples: Vec<ItSample>) -> Vec<u8> {
+    let mut bytes = b"IMPMexact".to_vec();
+    bytes.resize(32, 0);
+
+    let orders = [0, 255];
+    u16_le(&mut bytes, orders.len() as u16);
+    u16_le(&mut bytes, 0);
+    u16_le(&mut bytes, samples.len() as u16);
+    u16_le(&mut bytes, 1);
+    u16_le(&mut bytes, 0x214);
+    u16_le(&mut bytes, 0x214);
+    u16_le(&mut bytes, 0x09);
+    u16_le(&mut bytes, 0);
+    bytes.extend_from_slice(&[128, 128, speed, tempo]);
+    bytes.resize(64, 0);
+
+    let mut panning = [32u8; 64];
+    let mut volume = [64u8; 64];
+
+    for (ch, &(_, pan)) in notes.iter().enumerate() {
+        panning[ch] = pan;
+        volume[ch] = 64;
+    }
+
+    bytes.extend_from_slice(&panning);
+    bytes.extend_from_slice(&volume);
+    bytes.extend_from_slice(&orders);
+
+    // The offsets, filled in below.
+    let offsets = bytes.len();
+    bytes.resize(offsets + 4 * (samples.len() + 1), 0);
+
+    let set_offset = |bytes: &mut Vec<u8>, index: usize| {
+        let offset = bytes.len() as u32;
+        bytes[offsets + index * 4..offsets + index * 4 + 4].copy_from_slice(&offset.to_le_bytes());
+    };
+
+    let mut headers = Vec::new();
+
+    for (i, sample) in samples.iter().enumerate() {
+        set_offset(&mut bytes, i);
+        headers.push(bytes.len());
+
+        let mut header = b"IMPS".to_vec();
+        header.resize(0x11, 0);
+        header.extend_from_slice(&[64, sample.flags, 64]);
+        header.resize(0x2e, 0);
+        header.extend_from_slice(&[sample.convert, 0]);
+        u32_le(&mut header, sample.len);
+        u32_le(&mut header, sample.sample_loop.0);
+        u32_le(&mut header, sample.sample_loop.1);
+        u32_le(&mut header, 8000);
+        u32_le(&mut header, 0);
+        u32_le(&mut header, 0);
+        u32_le(&mut header, 0);
+        header.resize(0x50, 0);
+        bytes.extend(header);
+    }
+
+    for (sample, header) in samples.iter().zip(headers) {
+        let pointer = bytes.len() as u32;
+        bytes[header + 0x48..header + 0x4c].copy_from_slice(&pointer.to_le_bytes());
+        bytes.extend_from_slice(&sample.data);
+    }
+
+    // One row: each channel's note and sample, with a new mask.
+    let mut packed = Vec::new();
+
+    for (ch, &(sample, _)) in notes.iter().enumerate() {
+        packed.extend_from_slice(&[(ch as u8 + 1) | 0x80, 0x03, 60, sample]);
+    }
+
+    packed.push(0);
+
+    set_offset(&mut bytes, samples.len());
+    u16_le(&mut bytes, packed.len() as u16);
+    u16_le(&mut bytes, 1);
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.extend(packed);
+
+    bytes
+}
+
+/// A random walk which covers the whole range, with runs of small steps,
+/// and big jumps, to use every width.
+fn walk(len: usize, seed: u32) -> Vec<i16> {
+    let mut state = seed;
+    let mut sample = 0i16;
+
+    (0..len)
+        .map(|i| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let r = (state >> 8) as i32;
+
+            let step = match (i / 500) % 4 {
+                0 => r % 3 - 1,
+                1 => r % 64 - 32,
+                2 => r % 2048 - 1024,
+                _ => r as i16 as i32,
+            };
+
+            sample = sample.wrapping_add(step as i16);
+            sample
+        })
+        .collect()
+}
+
+/// Play one channel's sample at C-5, and check it comes out as stored, on
+/// both sides, then silence.
+fn check_it_sample(sample: ItSample, want: &[i16]) {
+    // At 8000 Hz and 32 bpm, a tick is 625 frames, and a row of 64 ticks is
+    // 40000.
+    let module = Module::from_bytes(&it(64, 32, &[(1, 32)], vec![sample])).unwrap();
+    assert_eq!(module.format(), ModuleFormat::It);
+    assert_eq!(module.channels(), 1);
+
+    let got = decode_in_chunks(&mut ModuleDecoder::new(module, 8000), 4096);
+    assert_eq!(got.len(), 40000 * 2);
+
+    for (frame, &sample) in want.iter().enumerate() {
+        assert_eq!(
+            (got[frame * 2], got[frame * 2 + 1]),
+            (sample, sample),
+            "frame {}",
+            frame
+        );
+    }
+
+    assert!(got[want.len() * 2..].iter().all(|&s| s == 0));
+}
+
+#[test]
+fn it_uncompressed() {
+    let samples = walk(30000, 1);
+    check_it_sample(ItSample::wide(&samples, false, false), &samples);
+
+    let narrow: Vec<i16> = samples.iter().map(|&s| s & !0xff).collect();
+    check_it_sample(ItSample::narrow(&samples, false, false), &narrow);
+}
+
+#[test]
+fn it_compressed() {
+    // Long enough for more than one block of each width.
+    let samples = walk(39000, 2);
+    let narrow: Vec<i16> = samples.iter().map(|&s| s & !0xff).collect();
+
+    for &it215 in &[false, true] {
+        check_it_sample(ItSample::wide(&samples, true, it215), &samples);
+        check_it_sample(ItSample::narrow(&samples, true, it215), &narrow);
+    }
+}
+
+#[test]
+fn it_compressed_widths() {
+    // Every width is used, and changed to from every other.
+    let mut samples = Vec::new();
+    let mut sample = 0i16;
+
+    for from in 0..17 {
+        for to in 0..17 {
+            for &bits in &[from, to] {
+                let step = if bits == 0 { 0 } else { (1i32 << bits) / 3 };
+                sample = sample.wrapping_add(step as i16);
+                samples.push(sample);
+            }
+        }
+    }
+
+    let narrow: Vec<i16> = samples.iter().map(|&s| s & !0xff).collect();
+
+    for &it215 in &[false, true] {
+        check_it_sample(ItSample::wide(&samples, true, it215), &samples);
+        check_it_sample(ItSample::narrow(&samples, true, it215), &narrow);
+    }
+}
+
+#[test]
+fn it_loops_and_panning() {
+    let ramp: Vec<i16> = (0..8).map(|i| i * 1000 - 3000).collect();
+
+    let mut forward = ItSample::wide(&ramp, false, false);
+    forward.flags |= 0x10;
+    forward.sample_loop = (2, 8);
+
+    let mut ping_pong = ItSample::wide(&ramp, false, false);
+    ping_pong.flags |= 0x10 | 0x40;
+    ping_pong.sample_loop = (4, 8);
+
+    // The first channel is hard left, and the second centered.
+    let module =
+        Module::from_bytes(&it(1, 125, &[(1, 0), (2, 32)], vec![forward, ping_pong])).unwrap();
+    assert_eq!(module.channels(), 2);
+
+    let got = decode_in_chunks(&mut ModuleDecoder::new(module, 8000), 100);
+    assert_eq!(got.len(), 160 * 2);
+
+    for frame in 0..160 {
+        let left = if frame < 8 {
+            frame
+        } else {
+            2 + (frame - 2) % 6
+        };
+
+        // Back and forth over 4 to 7, from the end: 7, 6, 5, 4, 5, 6.
+        let right = if frame < 8 {
+            frame
+        } else {
+            [7, 6, 5, 4, 5, 6][(frame - 7) % 6]
+        };
+
+        assert_eq!(
+            (got[frame * 2], got[frame * 2 + 1]),
+            (ramp[left] + ramp[right], ramp[right]),
+            "frame {}",
+            frame
+        );
+    }
+}
+
+#[test]
+fn it_invalid() {
+    // A compressed sample claiming 2^32 - 1 samples, from a few bytes, fails
+    // rather than allocating them.
+    let mut sample = ItSample::narrow(&[0; 16], true, false);
+    sample.len = u32::MAX;
+    assert_eq!(
+        Module::from_bytes(&it(1, 125, &[(1, 32)], vec![sample])).err(),
+        Some(Error::InvalidData)
+    );
+
+    // As does a compressed block of a bad width.
+    let mut sample = ItSample::narrow(&[0; 16], true, false);
+    sample.data = vec![2, 0, 0xff, 0x01];
+    assert_eq!(
+        Module::from_bytes(&it(1, 125, &[(1, 32)], vec![sample])).err(),
+        Some(Error::InvalidData)
+    );
+
+    let whole = it(
+        1,
+        125,
+        &[(1, 32)],
+        vec![ItSample::narrow(&[0; 16], true, true)],
+    );
+
+    for len in 0..whole.len() {
+        let _ = Module::from_bytes(&whole[..len]);
+    }
+}
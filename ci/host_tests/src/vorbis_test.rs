@@ -0,0 +1,127 @@
+use crate::audio::vorbis::VorbisDecoder;
+use crate::audio::{PcmDecoder, Sound};
+use crate::io::{Cursor, Error};
+use lewton::inside_ogg::OggStreamReader;
+
+/// About 0.06 s, 1358 frames, of stereo tones at 22050 Hz, titled "Tone".
+const TONE: &[u8] = include_bytes!("../../tests/assets/tone.ogg");
+
+/// `TONE` decoded by lewton, the reference decoder.
+fn reference() -> Vec<i16> {
+    let mut reader = OggStreamReader::new(std::io::Cursor::new(TONE)).unwrap();
+    let mut samples = Vec::new();
+
+    while let Some(packet) = reader.read_dec_packet_itl().unwrap() {
+        samples.extend(packet);
+    }
+
+    samples
+}
+
+/// Decode all of `decoder`, `chunk` samples at a time.
+fn decode_in_chunks<D: PcmDecoder>(decoder: &mut D, chunk: usize) -> Vec<i16> {
+    let mut samples = Vec::new();
+    let mut buffer = vec![0; chunk];
+
+    loop {
+        match decoder.read(&mut buffer).unwrap() {
+            0 => return samples,
+            n => samples.extend_from_slice(&buffer[..n]),
+        }
+    }
+}
+
+/// The largest difference between two decodes of the same length.
+fn max_difference(got: &[i16], want: &[i16]) -> i32 {
+    assert_eq!(got.len(), want.len());
+
+    got.iter()
+        .zip(want)
+        .map(|(&a, &b)| (a as i32 - b as i32).abs())
+        .max()
+        .unwrap()
+}
+
+#[test]
+fn header() {
+    let decoder = VorbisDecoder::new(Cursor::new(TONE)).unwrap();
+    assert_eq!((decoder.sample_rate(), decoder.channels()), (22050, 2));
+    assert_eq!(decoder.comment("TITLE"), Some("Tone"));
+    assert_eq!(decoder.comment("title"), Some("Tone"));
+    assert_eq!(decoder.comment("ARTIST"), Some("rust-psp"));
+    assert_eq!(decoder.comment("ALBUM"), None);
+}
+
+#[test]
+fn matches_reference() {
+    let want = reference();
+    let mut decoder = VorbisDecoder::new(Cursor::new(TONE)).unwrap();
+    let got = Sound::decode(&mut decoder).unwrap();
+
+    assert_eq!(got.frames(), 1358);
+    assert_eq!(max_difference(got.samples(), &want), 0);
+}
+
+#[test]
+fn chunk_sizes() {
+    let want = reference();
+
+    for &chunk in &[2, 6, 128, 1000, 4096] {
+        let mut decoder = VorbisDecoder::new(Cursor::new(TONE)).unwrap();
+        let got = decode_in_chunks(&mut decoder, chunk);
+        assert_eq!(max_difference(&got, &want), 0, "chunk {}", chunk);
+    }
+}
+
+#[test]
+fn rewind() {
+    let mut decoder = VorbisDecoder::new(Cursor::new(TONE)).unwrap();
+    let first = decode_in_chunks(&mut decoder, 256);
+
+    decoder.rewind().unwrap();
+    assert_eq!(decode_in_chunks(&mut decoder, 300), first);
+
+    // Part way through, too.
+    decoder.rewind().unwrap();
+    let mut buffer = [0; 500];
+    decoder.read(&mut buffer).unwrap();
+    decoder.rewind().unwrap();
+    assert_eq!(decode_in_chunks(&mut decoder, 256), first);
+}
+
+#[test]
+fn bad_checksum() {
+    let mut corrupt = TONE.to_vec();
+    corrupt[100] ^= 0xff;
+
+    assert_eq!(
+        VorbisDecoder::new(Cursor::new(corrupt)).err(),
+        Some(Error::InvalidData)
+    );
+}
+
+#[test]
+fn truncated() {
+    // Every cut either fails cleanly or decodes a prefix of the whole.
+    let want = reference();
+
+    for len in (0..TONE.len()).step_by(37) {
+        let mut decoder = match VorbisDecoder::new(Cursor::new(&TONE[..len])) {
+            Ok(decoder) => decoder,
+            Err(_) => continue,
+        };
+
+        let mut samples = Vec::new();
+        let mut buffer = [0; 256];
+
+        while let Ok(n) = decoder.read(&mut buffer) {
+            if n == 0 {
+                break;
+            }
+
+            samples.extend_from_slice(&buffer[..n]);
+        }
+
+        assert!(samples.len() <= want.len(), "cut at {}", len);
+    }
+}
@@ -0,0 +1,442 @@
+use crate::audio::wav::{WavEncoding, WavReader, WavWriter};
+use crate::audio::{PcmDecoder, PcmPlayer, Playback, Sound};
+use crate::io::{self, Cursor, Read, Seek, SeekFrom};
+use hound::{SampleFormat, WavSpec};
+
+/// A sweep across the whole range of `bits`-bit integers.
+fn int_signal(bits: u16, len: usize) -> Vec<i32> {
+    let max = (1i64 << (bits - 1)) - 1;
+    let min = -(1i64 << (bits - 1));
+
+    (0..len as i64)
+        .map(|i| {
+            if i == 0 {
+                min as i32
+            } else if i == 1 {
+                max as i32
+            } else {
+                (min + (i * 7919 * (max - min) / len as i64) % (max - min)) as i32
+            }
+        })
+        .collect()
+}
+
+/// An int WAV file written by hound, the reference encoder.
+fn hound_int(bits: u16, channels: u16, samples: &[i32]) -> Vec<u8> {
+    let spec = WavSpec {
+        channels,
+        sample_rate: 11025,
+        bits_per_sample: bits,
+        sample_format: SampleFormat::Int,
+    };
+
+    let mut file = std::io::Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut file, spec).unwrap();
+
+    for &sample in samples {
+        if bits == 8 {
+            writer.write_sample(sample as i8).unwrap();
+        } else {
+            writer.write_sample(sample).unwrap();
+        }
+    }
+
+    writer.finalize().unwrap();
+    file.into_inner()
+}
+
+/// Decode all of `decoder`, `chunk` samples at a time.
+fn decode_in_chunks<D: PcmDecoder>(decoder: &mut D, chunk: usize) -> Vec<i16> {
+    let mut samples = Vec::new();
+    let mut buffer = vec![0; chunk];
+
+    loop {
+        match decoder.read(&mut buffer).unwrap() {
+            0 => return samples,
+            n => samples.extend_from_slice(&buffer[..n]),
+        }
+    }
+}
+
+#[test]
+fn int_pcm() {
+    for &bits in &[8, 16, 24, 32] {
+        for &channels in &[1, 2] {
+            let signal = int_signal(bits, 1000);
+            let file = hound_int(bits, channels, &signal);
+
+            // What hound reads back, cut to the top 16 bits.
+            let want: Vec<i16> = hound::WavReader::new(std::io::Cursor::new(&file))
+                .unwrap()
+                .samples::<i32>()
+                .map(|sample| {
+                    let sample = sample.unwrap();
+
+                    if bits < 16 {
+                        (sample << (16 - bits)) as i16
+                    } else {
+                        (sample >> (bits - 16)) as i16
+                    }
+                })
+                .collect();
+
+            let mut reader = WavReader::new(Cursor::new(file)).unwrap();
+            assert_eq!(reader.encoding(), WavEncoding::Pcm(bits));
+            assert_eq!(reader.channels(), channels as usize);
+            assert_eq!(reader.sample_rate(), 11025);
+            assert_eq!(reader.len(), 1000 / channels as u64);
+
+            let got = decode_in_chunks(&mut reader, 6);
+            assert_eq!(got, want, "{} bits, {} channels", bits, channels);
+        }
+    }
+
+    // The ends of the range come through exactly.
+    let file = hound_int(8, 1, &[-128, 127, 0]);
+    let mut reader = WavReader::new(Cursor::new(file)).unwrap();
+    assert_eq!(decode_in_chunks(&mut reader, 16), [-32768, 127 << 8, 0]);
+}
+
+#[test]
+fn float_pcm() {
+    let signal = [0.0, 0.5, -0.5, 1.0, -1.0, 1.5, -1.5, 0.25, 1e-6, -0.999];
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 48000,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+
+    let mut file = std::io::Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut file, spec).unwrap();
+
+    for &sample in &signal {
+        writer.write_sample(sample as f32).unwrap();
+    }
+
+    writer.finalize().unwrap();
+
+    let mut reader = WavReader::new(Cursor::new(file.into_inner())).unwrap();
+    assert_eq!(reader.encoding(), WavEncoding::Float);
+    assert_eq!(
+        decode_in_chunks(&mut reader, 4),
+        [0, 16384, -16384, 32767, -32768, 32767, -32768, 8192, 0, -32735]
+    );
+}
+
+/// A standard IMA ADPCM encoder, returning the file and the samples its
+/// decoder should reproduce exactly.
+fn ima_file(channels: usize, block_align: usize, signal: &[i16]) -> (Vec<u8>, Vec<i16>) {
+    const STEPS: [i32; 89] = [
+        7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60,
+        66, 73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371,
+        408, 449, 494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878,
+        2066, 2272, 2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845,
+        8630, 9493, 10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086,
+        29794, 32767,
+    ];
+    const INDEX: [i32; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+    let frames_per_block = 1 + (block_align - 4 * channels) / (4 * channels) * 8;
+    let frames = signal.len() / channels;
+    let mut data = Vec::new();
+    let mut decoded = Vec::new();
+    let mut index = vec![0i32; channels];
+
+    for block in (0..frames).step_by(frames_per_block) {
+        let len = (frames - block).min(frames_per_block);
+        let frame = |i: usize, ch: usize| signal[(block + i) * channels + ch] as i32;
+        let mut nibbles = vec![Vec::new(); channels];
+
+        // A short last block still holds whole groups of eight, which hold
+        // the last sample.
+        let groups = (len - 1 + 7) / 8;
+        let padded = 1 + groups * 8;
+        let mut out = vec![0i16; padded * channels];
+
+        for ch in 0..channels {
+            let mut predictor = frame(0, ch);
+            data.extend_from_slice(&(predictor as i16).to_le_bytes());
+            data.extend_from_slice(&[index[ch] as u8, 0]);
+            out[ch] = predictor as i16;
+
+            for i in 1..padded {
+                let step = STEPS[index[ch] as usize];
+                let target = if i < len { frame(i, ch) } else { predictor };
+                let mut diff = target - predictor;
+                let mut nibble = 0;
+
+                if diff < 0 {
+                    nibble = 8;
+                    diff = -diff;
+                }
+
+                let mut delta = step >> 3;
+
+                if diff >= step {
+                    nibble |= 4;
+                    diff -= step;
+                    delta += step;
+                }
+
+                if diff >= step >> 1 {
+                    nibble |= 2;
+                    diff -= step >> 1;
+                    delta += step >> 1;
+                }
+
+                if diff >= step >> 2 {
+                    nibble |= 1;
+                    delta += step >> 2;
+                }
+
+                predictor += if nibble & 8 != 0 { -delta } else { delta };
+                predictor = predictor.max(-32768).min(32767);
+                index[ch] = (index[ch] + INDEX[nibble as usize & 7]).max(0).min(88);
+
+                out[i * channels + ch] = predictor as i16;
+                nibbles[ch].push(nibble as u8);
+            }
+        }
+
+        // Four bytes of each channel in turn, low nibble first.
+        for group in 0..groups {
+            for ch in &nibbles {
+                for pair in ch[group * 8..group * 8 + 8].chunks(2) {
+                    data.push(pair[0] | pair[1] << 4);
+                }
+            }
+        }
+
+        decoded.extend(out);
+    }
+
+    let mut file = Vec::new();
+    file.extend_from_slice(b"RIFF\0\0\0\0WAVE");
+
+    // An odd sized chunk before the format, to skip with its padding.
+    file.extend_from_slice(b"JUNK\x03\0\0\0abc\0");
+
+    file.extend_from_slice(b"fmt \x14\0\0\0");
+    file.extend_from_slice(&0x11u16.to_le_bytes());
+    file.extend_from_slice(&(channels as u16).to_le_bytes());
+    file.extend_from_slice(&8000u32.to_le_bytes());
+    file.extend_from_slice(&4000u32.to_le_bytes());
+    file.extend_from_slice(&(block_align as u16).to_le_bytes());
+    file.extend_from_slice(&4u16.to_le_bytes());
+    file.extend_from_slice(&2u16.to_le_bytes());
+    file.extend_from_slice(&(frames_per_block as u16).to_le_bytes());
+
+    file.extend_from_slice(b"data");
+    file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    file.extend(data);
+
+    let riff_size = (file.len() - 8) as u32;
+    file[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    (file, decoded)
+}
+
+#[test]
+fn ima_adpcm_known_values() {
+    // Predictor 0 at index 0, then a run of 7s: the step grows by 8 indices
+    // each time.
+    let mut file = ima_file(1, 8, &[0; 9]).0;
+    let data = file.len() - 4;
+    file[data..].copy_from_slice(&[0x77; 4]);
+
+    let mut reader = WavReader::new(Cursor::new(file)).unwrap();
+    assert_eq!(reader.encoding(), WavEncoding::ImaAdpcm);
+    assert_eq!(reader.len(), 9);
+
+    let got = decode_in_chunks(&mut reader, 16);
+    assert_eq!(&got[..4], [0, 11, 11 + 30, 11 + 30 + 63]);
+}
+
+#[test]
+fn ima_adpcm() {
+    for &channels in &[1, 2] {
+        let signal: Vec<i16> = (0..2000)
+            .map(|i| {
+                let t = i as f64 / 8000.0;
+                let ch = (i % channels) as f64;
+                ((t * (110.0 + ch * 55.0) * std::f64::consts::TAU).sin() * 8000.0) as i16
+            })
+            .collect();
+
+        // 2000 samples don't fill the last block.
+        let (file, want) = ima_file(channels, 256 * channels, &signal);
+        assert!(want.len() > 2000 && want.len() < 2000 + 8 * channels);
+
+        let mut reader = WavReader::new(Cursor::new(file)).unwrap();
+        assert_eq!(reader.len(), (want.len() / channels) as u64);
+
+        for &chunk in &[2, 10, 1000] {
+            reader.rewind().unwrap();
+            assert_eq!(
+                decode_in_chunks(&mut reader, chunk),
+                want,
+                "chunk {}",
+                chunk
+            );
+        }
+
+        // Once its step has grown, the encoder tracks the sine, so the decode
+        // does too.
+        let worst = want[200..]
+            .iter()
+            .zip(&signal[200..])
+            .map(|(&a, &b)| (a as i32 - b as i32).abs())
+            .max()
+            .unwrap();
+        assert!(worst < 500, "{} channels: off by {}", channels, worst);
+    }
+}
+
+#[test]
+fn writer_round_trip() {
+    let signal = int_signal(16, 300);
+    let samples: Vec<i16> = signal.iter().map(|&s| s as i16).collect();
+
+    let mut writer = WavWriter::new(Cursor::new(Vec::new()), 22050, 2).unwrap();
+    writer.write_samples(&samples[..100]).unwrap();
+    writer.write_samples(&samples[100..]).unwrap();
+    assert_eq!(writer.len(), 150);
+
+    let file = writer.finish().unwrap().into_inner();
+
+    // hound agrees with the header and samples.
+    let mut reference = hound::WavReader::new(std::io::Cursor::new(&file)).unwrap();
+    assert_eq!(reference.spec().channels, 2);
+    assert_eq!(reference.spec().sample_rate, 22050);
+    assert_eq!(reference.spec().bits_per_sample, 16);
+    let read: Vec<i16> = reference.samples::<i16>().map(Result::unwrap).collect();
+    assert_eq!(read, samples);
+
+    let mut reader = WavReader::new(Cursor::new(file)).unwrap();
+    assert_eq!(Sound::decode(&mut reader).unwrap().samples(), &samples[..]);
+}
+
+#[test]
+fn invalid() {
+    assert_eq!(
+        WavReader::new(&b"RIFF\0\0\0\0AVI LIST"[..]).err(),
+        Some(io::Error::InvalidData)
+    );
+
+    // No format before the data.
+    assert_eq!(
+        WavReader::new(&b"RIFF\0\0\0\0WAVEdata\0\0\0\0"[..]).err(),
+        Some(io::Error::InvalidData)
+    );
+
+    // Three channels.
+    let mut file = hound_int(16, 1, &[0; 6]);
+    file[22] = 3;
+    file[32] = 6;
+    assert_eq!(
+        WavReader::new(Cursor::new(file)).err(),
+        Some(io::Error::InvalidData)
+    );
+
+    assert_eq!(
+        WavReader::new(&b"RIFF\0\0\0\0WAVEfmt "[..]).err(),
+        Some(io::Error::UnexpectedEof)
+    );
+}
+
+/// Reads from a file, failing once `limit` bytes have been read.
+struct Failing {
+    file: Cursor<Vec<u8>>,
+    limit: u64,
+}
+
+impl Read for Failing {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.file.position() >= self.limit {
+            return Err(io::Error::InvalidData);
+        }
+
+        let len = buf.len().min((self.limit - self.file.position()) as usize);
+        self.file.read(&mut buf[..len])
+    }
+}
+
+impl Seek for Failing {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+/// A mono 16-bit WAV file of the samples 1 to `len`.
+fn counting(len: i16) -> Vec<u8> {
+    let samples: Vec<i32> = (1..=len as i32).collect();
+    hound_int(16, 1, &samples)
+}
+
+#[test]
+fn player() {
+    let reader = WavReader::new(Cursor::new(counting(10))).unwrap();
+    let mut player = PcmPlayer::new(reader);
+    assert_eq!(player.sample_rate(), 11025);
+    assert_eq!(player.get_ref().channels(), 1);
+
+    // Mono plays on both sides.
+    let mut out = [-1; 8];
+    assert_eq!(player.fill(&mut out), 8);
+    assert_eq!(out, [1, 1, 2, 2, 3, 3, 4, 4]);
+
+    // Paused, it outputs silence without advancing.
+    let control = player.control();
+    control.pause();
+    assert!(player.is_paused());
+    assert_eq!(player.fill(&mut out), 0);
+    assert_eq!(out, [0; 8]);
+    player.resume();
+
+    let mut out = [-1; 16];
+    assert_eq!(player.fill(&mut out), 12);
+    assert_eq!(&out[..12], [5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10]);
+    assert_eq!(&out[12..], [0; 4]);
+    assert!(player.is_finished());
+    assert_eq!(player.fill(&mut out), 0);
+
+    // A restart from another handle takes effect at the next fill.
+    control.restart();
+    assert_eq!(player.fill(&mut out[..4]), 4);
+    assert_eq!(&out[..4], [1, 1, 2, 2]);
+    assert!(!player.is_finished());
+    assert_eq!(player.take_error(), None);
+}
+
+#[test]
+fn player_loops() {
+    let reader = WavReader::new(Cursor::new(counting(3))).unwrap();
+    let mut player = PcmPlayer::new(reader);
+    player.set_looping(true);
+
+    let mut out = [0; 14];
+    assert_eq!(player.fill(&mut out), 14);
+    assert_eq!(out, [1, 1, 2, 2, 3, 3, 1, 1, 2, 2, 3, 3, 1, 1]);
+    assert!(!player.is_finished());
+}
+
+#[test]
+fn player_error() {
+    let file = counting(1000);
+    let limit = file.len() as u64 - 100;
+    let reader = WavReader::new(Failing {
+        file: Cursor::new(file),
+        limit,
+    })
+    .unwrap();
+
+    let mut player = PcmPlayer::new(reader);
+    let mut out = [0; 4000];
+    let written = player.fill(&mut out);
+
+    // Playback stops at the error, which is kept for later.
+    assert!(written < 2000);
+    assert!(player.is_finished());
+    assert_eq!(player.take_error(), Some(io::Error::InvalidData));
+    assert_eq!(player.take_error(), None);
+}
@@ -0,0 +1,39 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use psp::test_runner::TestRunner;
+
+#[repr(align(64))]
+struct Aligned64(u8);
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let before = psp::heap_stats();
+
+    let small: Vec<Box<u8>> = (0..100).map(Box::new).collect();
+    test_runner.check(
+        "small_allocations_share_region",
+        psp::heap_stats().regions,
+        before.regions.max(1),
+    );
+    test_runner.check("small_allocations_intact", *small[42], 42);
+    drop(small);
+
+    let aligned = Box::new(Aligned64(7));
+    test_runner.check("over_aligned_alloc", &*aligned as *const _ as usize % 64, 0);
+    drop(aligned);
+
+    let mut grown = Vec::<u32>::with_capacity(16);
+    grown.extend(0..16);
+    let ptr = grown.as_ptr();
+    grown.reserve_exact(16);
+    test_runner.check("realloc_in_place", grown.as_ptr(), ptr);
+    test_runner.check("realloc_preserves_data", grown[15], 15);
+    drop(grown);
+
+    let large = alloc::vec![0xa5u8; 1024 * 1024];
+    test_runner.check("large_alloc_intact", large[1024 * 1024 - 1], 0xa5);
+    drop(large);
+
+    let after = psp::heap_stats();
+    test_runner.check("no_leaked_allocations", after.allocations, before.allocations);
+    test_runner.check("large_region_released", after.regions, before.regions.max(1));
+}
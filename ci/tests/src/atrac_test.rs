@@ -0,0 +1,198 @@
+use alloc::vec::Vec;
+use psp::audio::atrac::AtracPlayer;
+use psp::audio::Playback;
+use psp::io::{self, Cursor};
+use psp::test_runner::TestRunner;
+use psp::Error;
+
+/// The bytes in an ATRAC3 frame at 132 kbit/s, for both channels.
+const FRAME_SIZE: usize = 384;
+
+/// The samples per channel in an ATRAC3 frame.
+const FRAME_SAMPLES: u32 = 1024;
+
+/// Frames in a file too big for the player's 64 KiB stream buffer.
+const STREAMED_FRAMES: usize = 400;
+
+/// The samples per channel given to a file of `frames` frames, leaving the
+/// last two for the decoder's delay.
+fn samples_for(frames: usize) -> u32 {
+    (frames as u32 - 2) * FRAME_SAMPLES
+}
+
+fn chunk(file: &mut Vec<u8>, id: &[u8], body: &[u8]) {
+    file.extend_from_slice(id);
+    file.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    file.extend_from_slice(body);
+}
+
+fn words(words: &[u32]) -> Vec<u8> {
+    words
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect()
+}
+
+/// A stereo ATRAC3 file of `frames` silent frames at 44100 Hz, looping over
+/// the samples in `looped` if given.
+fn at3(frames: usize, looped: Option<(u32, u32)>) -> Vec<u8> {
+    let mut file = Vec::new();
+    file.extend_from_slice(b"RIFF\0\0\0\0WAVE");
+
+    let mut fmt = Vec::new();
+    fmt.extend_from_slice(&0x270u16.to_le_bytes());
+    fmt.extend_from_slice(&2u16.to_le_bytes());
+    fmt.extend_from_slice(&44100u32.to_le_bytes());
+    fmt.extend_from_slice(&(FRAME_SIZE as u32 * 44100 / FRAME_SAMPLES).to_le_bytes());
+    fmt.extend_from_slice(&(FRAME_SIZE as u16).to_le_bytes());
+    fmt.extend_from_slice(&0u16.to_le_bytes());
+
+    // 14 bytes of extra data, with 0 for separate channels rather than
+    // joint stereo.
+    fmt.extend_from_slice(&[14, 0, 1, 0, 0, 0x10, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0]);
+    chunk(&mut file, b"fmt ", &fmt);
+
+    chunk(&mut file, b"fact", &words(&[samples_for(frames), 0]));
+
+    if let Some((start, end)) = looped {
+        chunk(
+            &mut file,
+            b"smpl",
+            &words(&[0, 0, 0, 60, 0, 0, 0, 1, 0, 0, 0, start, end, 0, 0]),
+        );
+    }
+
+    file.extend_from_slice(b"data");
+    file.extend_from_slice(&((frames * FRAME_SIZE) as u32).to_le_bytes());
+
+    // Each channel's half of a frame starts with its sound unit ID, then
+    // codes no gain control, tonal components or subbands: silence.
+    for _ in 0..frames * 2 {
+        let start = file.len();
+        file.resize(start + FRAME_SIZE / 2, 0);
+        file[start] = 0xa0;
+    }
+
+    let riff_size = (file.len() - 8) as u32;
+    file[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    file
+}
+
+/// Play to the end, returning the samples written and whether they were all
+/// silent.
+fn play(player: &mut AtracPlayer) -> (usize, bool) {
+    let mut buffer = [1i16; 1000];
+    let mut written = 0;
+    let mut silent = true;
+
+    while !player.is_finished() {
+        let n = player.fill(&mut buffer);
+        silent &= buffer[..n].iter().all(|&sample| sample == 0);
+        written += n;
+    }
+
+    (written, silent)
+}
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let mut bytes = [0u8; 1024];
+    bytes[..4].copy_from_slice(b"RIFF");
+    bytes[8..12].copy_from_slice(b"WAVE");
+
+    // A RIFF file without a fmt chunk isn't ATRAC.
+    test_runner.check(
+        "bad_data",
+        AtracPlayer::from_bytes(&bytes).err(),
+        Some(io::Error::Sce(Error::SCE_ATRAC_ERROR_UNKNOWN_FORMAT)),
+    );
+
+    check_in_memory(test_runner);
+    check_loops(test_runner);
+    check_streaming(test_runner);
+}
+
+fn check_in_memory(test_runner: &mut TestRunner) {
+    let mut player = AtracPlayer::from_bytes(&at3(20, None)).unwrap();
+    let samples = samples_for(20);
+
+    test_runner.check(
+        "format",
+        (player.sample_rate(), player.channels()),
+        (44100, 2),
+    );
+    test_runner.check("total_samples", player.total_samples(), Ok(samples));
+    test_runner.check("no_loop_points", player.loop_points(), Ok(None));
+    test_runner.check(
+        "no_loop_count",
+        player.set_loop_count(Some(1)),
+        Err(Error::SCE_ATRAC_ERROR_NO_LOOP_INFORMATION),
+    );
+
+    // Everything is in memory, so there is no stream buffer to count.
+    test_runner.check("remaining_in_memory", player.remaining_frames(), Ok(None));
+
+    test_runner.check("play", play(&mut player), (samples as usize * 2, true));
+    test_runner.check("error", player.take_error(), None);
+
+    player.restart();
+    test_runner.check("restart", play(&mut player).0, samples as usize * 2);
+}
+
+fn check_loops(test_runner: &mut TestRunner) {
+    let (start, end) = (FRAME_SAMPLES, 4 * FRAME_SAMPLES - 1);
+    let looped = (end - start + 1) as usize;
+    let samples = samples_for(20) as usize;
+
+    let mut player = AtracPlayer::from_bytes(&at3(20, Some((start, end)))).unwrap();
+    test_runner.check("loop_points", player.loop_points(), Ok(Some((start, end))));
+
+    // By default the looped section plays just once.
+    test_runner.check("loop_once", play(&mut player).0, samples * 2);
+
+    player.restart();
+    player.set_loop_count(Some(2)).unwrap();
+    test_runner.check("loop_count", player.loop_count(), Ok(Some(2)));
+    test_runner.check(
+        "loop_twice",
+        play(&mut player).0,
+        (samples + 2 * looped) * 2,
+    );
+
+    player.set_loop_count(None).unwrap();
+    test_runner.check("loop_forever", player.loop_count(), Ok(None));
+}
+
+fn check_streaming(test_runner: &mut TestRunner) {
+    let samples = samples_for(STREAMED_FRAMES) as usize;
+    let file = at3(STREAMED_FRAMES, None);
+
+    let mut player = AtracPlayer::new(Cursor::new(file)).unwrap();
+
+    // Only the first 64 KiB are read.
+    let remaining = player.remaining_frames();
+    test_runner.check(
+        "remaining_streamed",
+        matches!(remaining, Ok(Some(n)) if n > 0 && (n as usize) < STREAMED_FRAMES),
+        true,
+    );
+
+    // Playing to the end takes several refills of the stream buffer.
+    test_runner.check("stream", play(&mut player), (samples * 2, true));
+    test_runner.check("stream_error", player.take_error(), None);
+
+    player.restart();
+    test_runner.check("stream_restart", play(&mut player).0, samples * 2);
+
+    // A streamed loop goes back to data no longer in the buffer, and needs
+    // the data after the loop end in a second buffer.
+    let (start, end) = (FRAME_SAMPLES, 4 * FRAME_SAMPLES - 1);
+    let file = at3(STREAMED_FRAMES, Some((start, end)));
+
+    let mut player = AtracPlayer::new(Cursor::new(file)).unwrap();
+    player.set_loop_count(Some(1)).unwrap();
+    test_runner.check(
+        "stream_loop",
+        play(&mut player),
+        ((samples + (end - start + 1) as usize) * 2, true),
+    );
+}
@@ -0,0 +1,49 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+use psp::audio::{AudioChannel, AudioStream};
+use psp::sys::{AudioFormat, AudioOutputFrequency};
+use psp::test_runner::TestRunner;
+use psp::Error;
+
+static FILLS: AtomicU32 = AtomicU32::new(0);
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    test_runner.check(
+        "unaligned_sample_count",
+        AudioChannel::reserve(AudioFormat::Stereo, 100).err(),
+        Some(Error::SCE_AUDIO_ERROR_INVALID_SIZE),
+    );
+    test_runner.check(
+        "src_sample_count",
+        AudioChannel::reserve_src(AudioOutputFrequency::Khz22_05, 8).err(),
+        Some(Error::SCE_AUDIO_ERROR_INVALID_SIZE),
+    );
+
+    let mut channel = AudioChannel::reserve(AudioFormat::Mono, 256).unwrap();
+    test_runner.check("buffer_len", channel.buffer_len(), 256);
+    test_runner.check(
+        "wrong_buffer_len",
+        channel.output_blocking(&[0; 512]),
+        Err(Error::SCE_AUDIO_ERROR_INVALID_SIZE),
+    );
+    test_runner.check("output", channel.output_blocking(&[0; 256]), Ok(()));
+
+    channel.set_pan(0x8000, 0.5);
+    test_runner.check("pan", channel.volume(), (0x4000, 0x8000));
+    drop(channel);
+
+    let channel = AudioChannel::reserve(AudioFormat::Stereo, 1024).unwrap();
+    let stream = AudioStream::start(channel, |buffer| {
+        for sample in buffer.iter_mut() {
+            *sample = 0;
+        }
+
+        FILLS.fetch_add(1, Ordering::SeqCst);
+    })
+    .unwrap();
+
+    psp::time::sleep(core::time::Duration::from_millis(100));
+    let (channel, result) = stream.stop();
+    test_runner.check("stream_result", result, Ok(()));
+    test_runner.check("stream_filled", FILLS.load(Ordering::SeqCst) >= 2, true);
+    test_runner.check("stream_channel", channel.sample_count(), 1024);
+}
@@ -0,0 +1,32 @@
+use psp::cache::{self, DmaBuffer, UncachedPtr};
+use psp::test_runner::TestRunner;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let mut buffer = DmaBuffer::from_elem(0u32, 100);
+    test_runner.check(
+        "dma_buffer_aligned",
+        buffer.as_ptr() as usize % cache::CACHE_LINE_SIZE,
+        0,
+    );
+
+    // Writes through the cache only reach memory once handed to the device.
+    buffer[10] = 0xdead_beef;
+    let device_ptr = buffer.as_device_ptr() as *mut u32;
+    let uncached = UncachedPtr::new(device_ptr);
+    test_runner.check("writeback_visible_uncached", unsafe { uncached.add(10).read() }, 0xdead_beef);
+
+    // Simulate the device writing to memory behind the cache.
+    unsafe { uncached.add(20).write(0x1234_5678) };
+    buffer.sync_for_cpu();
+    test_runner.check("device_write_visible_cached", buffer[20], 0x1234_5678);
+
+    let mut view = buffer.uncached();
+    view.set(30, 42);
+    test_runner.check("uncached_slice_roundtrip", view.get(30), 42);
+
+    test_runner.check(
+        "cached_undoes_uncached",
+        cache::cached(cache::uncached(device_ptr)),
+        device_ptr,
+    );
+}
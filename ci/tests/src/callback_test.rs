@@ -0,0 +1,109 @@
+use alloc::vec::Vec;
+use core::mem::{self, MaybeUninit};
+use core::time::Duration;
+use psp::callback::{self, Event, EventKinds};
+use psp::sys::{
+    self, PowerInfo, SceKernelCallbackInfo, SceKernelIdListType, SceUid, UmdStateFlags,
+};
+use psp::test_runner::TestRunner;
+use psp::time;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let power = callback::subscribe(EventKinds::POWER).unwrap();
+    let umd = callback::subscribe(EventKinds::UMD).unwrap();
+
+    // The callback thread runs above our priority, so its callbacks exist by
+    // the time `subscribe` returns.
+    let power_callback = find_callback(b"power_callback");
+    let umd_callback = find_callback(b"umd_callback");
+    test_runner.check("power_callback_found", power_callback.is_some(), true);
+    test_runner.check("umd_callback_found", umd_callback.is_some(), true);
+
+    let (power_callback, umd_callback) = match (power_callback, umd_callback) {
+        (Some(power), Some(umd)) => (power, umd),
+        _ => return,
+    };
+
+    test_runner.check("nothing_queued", power.try_recv(), None);
+
+    let info = PowerInfo::HOLD_SWITCH | PowerInfo::BATTERY_EXIST;
+    notify(power_callback, info.bits() as i32);
+    test_runner.check("power_event", power.try_recv(), Some(Event::Power(info)));
+    test_runner.check("power_event_taken", power.try_recv(), None);
+    test_runner.check("other_kind_not_queued", umd.try_recv(), None);
+
+    let state = UmdStateFlags::PRESENT | UmdStateFlags::READY;
+    notify(umd_callback, state.bits());
+    notify(umd_callback, UmdStateFlags::NOT_PRESENT.bits());
+    test_runner.check(
+        "umd_events_in_order",
+        umd.try_iter().collect::<Vec<_>>(),
+        alloc::vec![Event::Umd(state), Event::Umd(UmdStateFlags::NOT_PRESENT)],
+    );
+    test_runner.check("umd_not_sent_to_power", power.try_recv(), None);
+
+    // Nothing is queued for a dropped receiver, so a new one starts empty and
+    // the remaining one only gets what it's subscribed to.
+    drop(power);
+    notify(power_callback, info.bits() as i32);
+    test_runner.check("dropped_not_queued", umd.try_recv(), None);
+
+    let power = callback::subscribe(EventKinds::POWER | EventKinds::UMD).unwrap();
+    test_runner.check("resubscribed_empty", power.try_recv(), None);
+
+    notify(umd_callback, state.bits());
+    test_runner.check("both_receive", power.try_recv(), Some(Event::Umd(state)));
+    test_runner.check(
+        "both_receive_other",
+        umd.try_recv(),
+        Some(Event::Umd(state)),
+    );
+}
+
+/// Find one of the callbacks the callback thread created, by name.
+fn find_callback(name: &[u8]) -> Option<SceUid> {
+    let mut ids = [SceUid(0); 64];
+    let mut count = 0;
+
+    let result = unsafe {
+        sys::sceKernelGetThreadmanIdList(
+            SceKernelIdListType::Callback,
+            ids.as_mut_ptr(),
+            ids.len() as i32,
+            &mut count,
+        )
+    };
+
+    if result < 0 {
+        return None;
+    }
+
+    ids[..(count as usize).min(ids.len())]
+        .iter()
+        .copied()
+        .find(|&id| unsafe {
+            let mut info = MaybeUninit::<SceKernelCallbackInfo>::zeroed();
+            (*info.as_mut_ptr()).size = mem::size_of::<SceKernelCallbackInfo>();
+
+            if sys::sceKernelReferCallbackStatus(id, info.as_mut_ptr()) < 0 {
+                return false;
+            }
+
+            let info = info.assume_init();
+            let len = info
+                .name
+                .iter()
+                .position(|&b| b == 0)
+                .unwrap_or(info.name.len());
+            &info.name[..len] == name
+        })
+}
+
+/// Trigger a callback, and give the callback thread time to run it.
+fn notify(callback: SceUid, arg: i32) {
+    unsafe {
+        sys::sceKernelNotifyCallback(callback, arg);
+    }
+
+    time::sleep(Duration::from_millis(5));
+}
@@ -0,0 +1,46 @@
+use core::time::Duration;
+use psp::sys;
+use psp::test_runner::TestRunner;
+use psp::time::{self, DateTime, ParseDateTimeError, Weekday};
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let date = DateTime::new(2020, 2, 29, 12, 34, 56).unwrap();
+
+    test_runner.check("invalid_day", DateTime::new(2019, 2, 29, 0, 0, 0), None);
+    test_runner.check("leap_year", (time::is_leap_year(2000), time::is_leap_year(1900)), (true, false));
+    test_runner.check("weekday", date.weekday(), Weekday::Saturday);
+    test_runner.check("ordinal", date.ordinal(), 60);
+
+    let mut rtc_tick = 0;
+    let psp_date = sys::ScePspDateTime::from(date);
+    unsafe { sys::sceRtcGetTick(&psp_date, &mut rtc_tick) };
+    test_runner.check("ticks_match_rtc", date.ticks(), rtc_tick);
+    test_runner.check("from_ticks", DateTime::from_ticks(rtc_tick), Some(date));
+
+    test_runner.check("unix_timestamp", date.unix_timestamp(), 1_582_979_696);
+    test_runner.check("from_unix_timestamp", DateTime::from_unix_timestamp(1_582_979_696), Some(date));
+
+    test_runner.check("add_months_clamps", date.add_months(12), DateTime::new(2021, 2, 28, 12, 34, 56));
+    test_runner.check("add_months_negative", date.add_months(-3), DateTime::new(2019, 11, 29, 12, 34, 56));
+    test_runner.check("add_days", date.add_days(1), DateTime::new(2020, 3, 1, 12, 34, 56));
+    test_runner.check("add_duration", date + Duration::from_secs(3600), DateTime::new(2020, 2, 29, 13, 34, 56).unwrap());
+    test_runner.check("duration_since", (date + Duration::from_secs(5)).duration_since(date), Some(Duration::from_secs(5)));
+    test_runner.check("ordering", date < date + Duration::from_micros(1), true);
+
+    test_runner.check("rfc3339_utc", date.to_rfc3339(0).as_str(), "2020-02-29T12:34:56Z");
+    test_runner.check("rfc3339_offset", date.to_rfc3339(-90).as_str(), "2020-02-29T11:04:56-01:30");
+    test_runner.check("rfc2822", date.to_rfc2822(540).as_str(), "Sat, 29 Feb 2020 21:34:56 +0900");
+
+    test_runner.check("parse_rfc3339", DateTime::parse_rfc3339("2020-02-29T21:34:56+09:00"), Ok(date));
+    test_runner.check(
+        "parse_rfc3339_fraction",
+        "2020-02-29T12:34:56.5Z".parse::<DateTime>(),
+        Ok(date.with_microsecond(500_000).unwrap()),
+    );
+    test_runner.check("parse_rfc3339_invalid", DateTime::parse_rfc3339("2020-02-30T00:00:00Z"), Err(ParseDateTimeError));
+    test_runner.check("parse_rfc2822", DateTime::parse_rfc2822("Sat, 29 Feb 2020 12:34:56 GMT"), Ok(date));
+    test_runner.check("parse_rfc2822_offset", DateTime::parse_rfc2822("29 feb 2020 07:34 -0500"), date.checked_sub(Duration::from_secs(56)).ok_or(ParseDateTimeError));
+
+    let utc = DateTime::now_utc();
+    test_runner.check("local_roundtrip", utc.to_local().to_utc(), utc);
+}
@@ -0,0 +1,38 @@
+use alloc::format;
+use psp::error::{Facility, SceReturn};
+use psp::sys::{self, IoOpenFlags};
+use psp::test_runner::TestRunner;
+use psp::Error;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let err = Error::SCE_KERNEL_ERROR_NO_MEMORY;
+    test_runner.check("kernel_facility", err.facility(), Facility::Kernel);
+    test_runner.check("kernel_code", err.code(), 0x190);
+    test_runner.check("not_critical", err.is_critical(), false);
+    test_runner.check("name", err.name(), Some("SCE_KERNEL_ERROR_NO_MEMORY"));
+
+    let unknown = Error::from_raw(0x8012_3456u32 as i32);
+    test_runner.check("unknown_facility", unknown.facility(), Facility::Other(0x012));
+    test_runner.check(
+        "unknown_display",
+        &*format!("{}", unknown),
+        "Other(18) error 0x3456 (0x80123456)",
+    );
+
+    test_runner.check("positive_is_ok", 5.into_result(), Ok(5));
+
+    let fd = unsafe {
+        sys::sceIoOpen(
+            b"host0:/this_file_does_not_exist\0".as_ptr(),
+            IoOpenFlags::RD_ONLY,
+            0,
+        )
+    };
+
+    match fd.into_result() {
+        Err(Error::ERRNO_FILE_NOT_FOUND) => {
+            test_runner.pass("missing_file", "Got ERRNO_FILE_NOT_FOUND.")
+        }
+        other => test_runner.fail("missing_file", &format!("Got {:?}", other.map(|fd| fd.0))),
+    }
+}
@@ -0,0 +1,50 @@
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::ptr;
+use core::time::Duration;
+use psp::executor::{self, Executor, Timer};
+use psp::sys;
+use psp::test_runner::TestRunner;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    test_runner.check("block_on", executor::block_on(async { 1 + 2 }), 3);
+
+    let executor = Executor::new().unwrap();
+    let order = Rc::new(RefCell::new(Vec::new()));
+
+    for (id, delay) in [(1, 30), (2, 10), (3, 20)].iter().copied() {
+        let order = order.clone();
+
+        executor.spawn(async move {
+            Timer::after(Duration::from_millis(delay)).await;
+            order.borrow_mut().push(id);
+        });
+    }
+
+    let start = unsafe { sys::sceKernelGetSystemTimeWide() };
+    executor.run();
+    let elapsed = unsafe { sys::sceKernelGetSystemTimeWide() } - start;
+
+    test_runner.check("timer_order", &order.borrow()[..], &[2, 3, 1][..]);
+    test_runner.check("timers_concurrent", elapsed < 60_000, true);
+
+    let vcount = unsafe { sys::sceDisplayGetVcount() };
+    executor.block_on(executor::vblank());
+    test_runner.check("vblank", unsafe { sys::sceDisplayGetVcount() } != vcount, true);
+
+    let sema = unsafe { sys::sceKernelCreateSema(&b"executor_test\0"[0], 0, 0, 1, ptr::null_mut()) };
+    let spawner = executor.spawner();
+
+    let acquired = executor.block_on(async move {
+        spawner.spawn(async move {
+            executor::yield_now().await;
+            unsafe { sys::sceKernelSignalSema(sema, 1) };
+        });
+
+        executor::wait_sema(sema, 1).await
+    });
+
+    test_runner.check("wait_sema", acquired, Ok(()));
+    unsafe { sys::sceKernelDeleteSema(sema) };
+}
@@ -0,0 +1,99 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use psp::fs::{self, AsyncFile, Completion, File, FileType, OpenOptions};
+use psp::io::{self, Read, Seek, SeekFrom, Write};
+use psp::test_runner::TestRunner;
+use psp::Error;
+
+const DIR: &str = "host0:/fs_test/a/b";
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    test_runner.check("create_dir_all", fs::create_dir_all(DIR), Ok(()));
+    test_runner.check("create_dir_all_existing", fs::create_dir_all(DIR), Ok(()));
+
+    let path = "host0:/fs_test/a/b/file.bin";
+    test_runner.check("write", fs::write(path, b"hello, world"), Ok(()));
+    test_runner.check("read", fs::read(path).as_deref(), Ok(&b"hello, world"[..]));
+
+    let mut file = OpenOptions::new().read(true).write(true).open(path).unwrap();
+    test_runner.check("seek", file.seek(SeekFrom::Start(7)), Ok(7));
+    test_runner.check("overwrite", file.write_all(b"there"), Ok(()));
+    test_runner.check("len", file.len(), Ok(12));
+    drop(file);
+
+    let mut contents = [0; 12];
+    let read = File::open(path).and_then(|mut f| f.read_exact(&mut contents));
+    test_runner.check("read_exact", read, Ok(()));
+    test_runner.check("overwritten_contents", &contents, b"hello, there");
+
+    let mut async_file = AsyncFile::open(path).unwrap();
+    async_file.set_completion(Completion::Poll);
+    let (read, buf) = block_on(async_file.read_at(alloc::vec![0; 5], 7));
+    test_runner.check("async_read_at", read, Ok(5));
+    test_runner.check("async_read_at_contents", &buf[..], &b"there"[..]);
+    let (read, _) = block_on(async_file.read_at(alloc::vec![0; 8], 10));
+    test_runner.check("async_read_at_short", read, Ok(2));
+    drop(async_file);
+
+    let mut short = [0; 13];
+    let read = File::open(path).and_then(|mut f| f.read_exact(&mut short));
+    test_runner.check("read_exact_eof", read, Err(io::Error::UnexpectedEof));
+
+    let meta = fs::metadata(path).unwrap();
+    test_runner.check("metadata_len", meta.len(), 12);
+    test_runner.check("metadata_type", meta.file_type(), FileType::File);
+    test_runner.check("metadata_modified", meta.modified().is_ok(), true);
+    test_runner.check("dir_metadata_type", fs::metadata(DIR).map(|m| m.file_type()), Ok(FileType::Dir));
+
+    let renamed = "host0:/fs_test/a/b/renamed.bin";
+    test_runner.check("rename", fs::rename(path, renamed), Ok(()));
+
+    let names: Vec<String> = fs::read_dir(DIR)
+        .unwrap()
+        .map(|entry| String::from(entry.unwrap().file_name()))
+        .collect();
+    test_runner.check("read_dir", names, alloc::vec![String::from("renamed.bin")]);
+
+    test_runner.check(
+        "create_new_existing",
+        OpenOptions::new().write(true).create_new(true).open(renamed).map(|_| ()),
+        Err(io::Error::Sce(Error::ERRNO_FILE_ALREADY_EXISTS)),
+    );
+
+    test_runner.check("remove_file", fs::remove_file(renamed), Ok(()));
+    test_runner.check("removed", fs::exists(renamed), false);
+    test_runner.check("remove_dir", fs::remove_dir(DIR), Ok(()));
+    let _ = fs::remove_dir("host0:/fs_test/a");
+    let _ = fs::remove_dir("host0:/fs_test");
+
+    let mut log = Vec::new();
+    let _ = write!(log, "{} + {}", 1, 2);
+    test_runner.check("write_fmt", &log[..], &b"1 + 2"[..]);
+}
+
+/// Poll `future` until it completes. The executor isn't needed for files
+/// using `Completion::Poll`.
+fn block_on<F: Future>(mut future: F) -> F::Output {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(core::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    let mut cx = Context::from_waker(&waker);
+    let mut future = unsafe { Pin::new_unchecked(&mut future) };
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use alloc::vec::Vec;
+use psp::image::{png, tga, Image, ImageError, Storage, TextureOptions};
+use psp::sys::TexturePixelFormat;
+use psp::test_runner::TestRunner;
+
+/// A 9x9 interlaced PNG of 2-bit palette indices, `(x + y) % 4`, compressed
+/// with fixed Huffman codes. The palette is red, green, blue and transparent
+/// white.
+#[rustfmt::skip]
+const INDEXED_PNG: [u8; 128] = [
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x09, 0x02, 0x03, 0x00, 0x00, 0x01, 0xea, 0xf8, 0xde,
+    0x15, 0x00, 0x00, 0x00, 0x0c, 0x50, 0x4c, 0x54, 0x45, 0xff, 0x00, 0x00, 0x00, 0xff, 0x00, 0x00,
+    0x00, 0xff, 0xff, 0xff, 0xff, 0xfb, 0x00, 0x60, 0xf6, 0x00, 0x00, 0x00, 0x04, 0x74, 0x52, 0x4e,
+    0x53, 0xff, 0xff, 0xff, 0x00, 0x40, 0x2a, 0xa9, 0xf4, 0x00, 0x00, 0x00, 0x1f, 0x49, 0x44, 0x41,
+    0x54, 0x78, 0x01, 0x63, 0x60, 0x80, 0x83, 0x05, 0x60, 0xd8, 0xd1, 0x00, 0x42, 0xe5, 0x0c, 0x77,
+    0xa1, 0x38, 0x27, 0xc7, 0x81, 0xe1, 0xd8, 0xb1, 0x03, 0x30, 0x1a, 0x00, 0xf1, 0x07, 0x0d, 0xd8,
+    0xa7, 0x68, 0x7d, 0xc4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+];
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    // The decoder doesn't check CRCs.
+    png.extend_from_slice(&[0; 4]);
+}
+
+/// An 8-bit RGBA PNG of `pixels`, each row filtered with `Sub`, in a zlib
+/// stream of stored blocks.
+fn rgba_png(width: u32, pixels: &[[u8; 4]]) -> Vec<u8> {
+    let mut raw = Vec::new();
+
+    for row in pixels.chunks(width as usize) {
+        raw.push(1);
+        let mut left = [0; 4];
+
+        for pixel in row {
+            for c in 0..4 {
+                raw.push(pixel[c].wrapping_sub(left[c]));
+            }
+
+            left = *pixel;
+        }
+    }
+
+    let mut zlib = alloc::vec![0x78, 0x01];
+
+    for (i, block) in raw.chunks(7).enumerate() {
+        let last = (i + 1) * 7 >= raw.len();
+        zlib.push(last as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for &byte in &raw {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    zlib.extend_from_slice(&(b << 16 | a).to_be_bytes());
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&(pixels.len() as u32 / width).to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = alloc::vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &zlib);
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// An image with `colors` in raster order.
+fn from_colors(width: u32, height: u32, colors: &[u32]) -> Image {
+    let mut image = Image::new(width, height, width, Storage::Ram).unwrap();
+    image.pixels_mut().copy_from_slice(colors);
+    image
+}
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let pixels = [
+        [255, 0, 0, 255],
+        [0, 255, 0, 128],
+        [0, 0, 255, 0],
+        [1, 2, 3, 4],
+        [250, 251, 252, 253],
+        [9, 8, 7, 6],
+    ];
+    let rgba = rgba_png(3, &pixels);
+
+    test_runner.check("png_dimensions", png::dimensions(&rgba), Ok((3, 2)));
+
+    let image = png::decode(&rgba).unwrap();
+    let expected: Vec<u32> = pixels
+        .iter()
+        .map(|&pixel| u32::from_le_bytes(pixel))
+        .collect();
+    let mut decoded = image.row(0).to_vec();
+    decoded.extend_from_slice(image.row(1));
+    test_runner.check_large_collection("png_rgba", &decoded, &expected);
+
+    let palette = [0xff00_00ff, 0xff00_ff00, 0xffff_0000, 0x00ff_ffff];
+    let indexed = png::decode(&INDEXED_PNG).unwrap();
+    let mismatches = (0..9)
+        .flat_map(|y| (0..9).map(move |x| (x, y)))
+        .filter(|&(x, y)| indexed.row(y)[x as usize] != palette[(x + y) as usize % 4])
+        .count();
+    test_runner.check("png_indexed", (indexed.width(), mismatches), (9, 0));
+
+    test_runner.check(
+        "png_truncated",
+        png::decode(&rgba[..rgba.len() - 20]).err(),
+        Some(ImageError::InvalidData),
+    );
+    test_runner.check(
+        "png_not_png",
+        png::decode(b"GIF89a").err(),
+        Some(ImageError::InvalidData),
+    );
+
+    // A 3x2 run-length encoded 24-bit TGA, stored bottom to top, with a run
+    // crossing from the bottom row to the top one.
+    let mut targa = alloc::vec![0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 2, 0, 24, 0];
+    targa.extend_from_slice(&[0x01, 0x10, 0x20, 0x30, 0x40, 0x50, 0x60]);
+    targa.extend_from_slice(&[0x83, 0xff, 0x00, 0x00]);
+
+    let image = tga::decode(&targa).unwrap();
+    test_runner.check(
+        "tga_rle",
+        (image.row(0).to_vec(), image.row(1).to_vec()),
+        (
+            alloc::vec![0xffff_0000, 0xffff_0000, 0xffff_0000],
+            alloc::vec![0xff10_2030, 0xff40_5060, 0xffff_0000],
+        ),
+    );
+    test_runner.check(
+        "tga_truncated",
+        tga::decode(&targa[..20]).err(),
+        Some(ImageError::InvalidData),
+    );
+
+    let image = image_rgba();
+    let texture = TextureOptions::new(TexturePixelFormat::Psm8888)
+        .convert(&image)
+        .unwrap();
+    test_runner.check(
+        "texture_size",
+        (
+            texture.texture_width(),
+            texture.texture_height(),
+            texture.buffer_width(),
+            texture.data().len(),
+        ),
+        (4, 4, 4, 64),
+    );
+    test_runner.check(
+        "texture_8888",
+        (&texture.data()[12..16], &texture.data()[16..20]),
+        (&[0, 0, 0, 0][..], &0xffff_0000u32.to_le_bytes()[..]),
+    );
+
+    let pixel_16 = |format| {
+        let texture = TextureOptions::new(format).convert(&image).unwrap();
+        (
+            texture.buffer_width(),
+            u16::from_le_bytes([texture.data()[2], texture.data()[3]]),
+        )
+    };
+    test_runner.check(
+        "texture_16_bit",
+        [
+            pixel_16(TexturePixelFormat::Psm5650),
+            pixel_16(TexturePixelFormat::Psm5551),
+            pixel_16(TexturePixelFormat::Psm4444),
+        ],
+        [(8, 0xf81f), (8, 0xfc1f), (8, 0x8f0f)],
+    );
+
+    let texture = TextureOptions::new(TexturePixelFormat::PsmT4)
+        .convert(&image)
+        .unwrap();
+    let clut = texture.clut().unwrap();
+    let color = |x: usize| {
+        let byte = texture.data()[x / 2];
+        clut[(byte >> (x % 2 * 4)) as usize & 0xf]
+    };
+    test_runner.check(
+        "texture_t4",
+        (
+            texture.buffer_width(),
+            clut.len(),
+            color(0),
+            color(1),
+            color(2),
+        ),
+        (32, 16, 0xffff_0000, 0x80ff_00ff, 0xff00_ff00),
+    );
+    test_runner.check("texture_t4_padding", (color(3), clut[0]), (0, 0));
+
+    // Twice as many colors as a T8 CLUT holds, which pair up.
+    let gradient: Vec<u32> = (0..32 * 16)
+        .map(|i| 0xff00_0000 | (i / 256 * 0xff) << 8 | i % 256)
+        .collect();
+    let texture = TextureOptions::new(TexturePixelFormat::PsmT8)
+        .convert(&from_colors(32, 16, &gradient))
+        .unwrap();
+    let clut = texture.clut().unwrap();
+    let error = (0..gradient.len())
+        .map(|i| {
+            let color = clut[texture.data()[i] as usize];
+            (0..4)
+                .map(|c| {
+                    ((color >> (c * 8)) as u8 as i32 - (gradient[i] >> (c * 8)) as u8 as i32).abs()
+                })
+                .max()
+                .unwrap()
+        })
+        .max()
+        .unwrap();
+    test_runner.check(
+        "texture_t8_quantized",
+        (clut.len(), error <= 1),
+        (256, true),
+    );
+
+    // In 16 by 8 byte blocks: the first block holds the first 4 pixels of
+    // rows 0 to 7, and the next the following 4.
+    let pattern: Vec<u32> = (0..8 * 8).collect();
+    let texture = TextureOptions::new(TexturePixelFormat::Psm8888)
+        .swizzle(true)
+        .convert(&from_colors(8, 8, &pattern))
+        .unwrap();
+    let word = |i: usize| {
+        let data = texture.data();
+        u32::from_le_bytes([
+            data[i * 4],
+            data[i * 4 + 1],
+            data[i * 4 + 2],
+            data[i * 4 + 3],
+        ])
+    };
+    test_runner.check(
+        "texture_swizzle",
+        (texture.is_swizzled(), word(0), word(4), word(31), word(32)),
+        (true, 0, 8, 59, 4),
+    );
+
+    test_runner.check(
+        "texture_unsupported_format",
+        TextureOptions::new(TexturePixelFormat::PsmDxt1)
+            .convert(&image)
+            .err(),
+        Some(ImageError::Unsupported),
+    );
+    test_runner.check(
+        "texture_too_large",
+        TextureOptions::new(TexturePixelFormat::Psm8888)
+            .convert(&Image::new(513, 1, 528, Storage::Ram).unwrap())
+            .err(),
+        Some(ImageError::Unsupported),
+    );
+}
+
+/// A 3x3 image of blue, magenta at half alpha and green, repeated on each
+/// row.
+fn image_rgba() -> Image {
+    let row = [0xffff_0000, 0x80ff_00ff, 0xff00_ff00];
+    let mut colors = row.to_vec();
+    colors.extend_from_slice(&row);
+    colors.extend_from_slice(&row);
+    from_colors(3, 3, &colors)
+}
@@ -0,0 +1,107 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use psp::input::{
+    AnalogConfig, Button, ButtonEvent, ButtonEventKind, Controller, Recorder, Recording, Repeat, Replay, Sample,
+};
+use psp::sys::CtrlButtons;
+use core::time::Duration;
+use psp::test_runner::TestRunner;
+
+fn sample(timestamp: u32, buttons: CtrlButtons) -> Sample {
+    Sample {
+        timestamp,
+        buttons,
+        lx: 128,
+        ly: 128,
+        ..Sample::default()
+    }
+}
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let mut controller = Controller::new();
+    controller.set_repeat(Some(Repeat::new(Duration::from_millis(400), Duration::from_millis(100))));
+
+    controller.update_from(sample(0, CtrlButtons::CROSS));
+    test_runner.check("pressed", controller.buttons().just_pressed(CtrlButtons::CROSS), true);
+    test_runner.check("held", controller.buttons().is_held(CtrlButtons::CROSS), true);
+
+    let events: Vec<ButtonEvent> = controller.events().collect();
+    test_runner.check(
+        "pressed_event",
+        events,
+        vec![ButtonEvent {
+            button: Button::Ctrl(CtrlButtons::CROSS),
+            kind: ButtonEventKind::Pressed,
+        }],
+    );
+
+    controller.update_from(sample(16_000, CtrlButtons::CROSS));
+    test_runner.check("not_pressed_again", controller.buttons().just_pressed(CtrlButtons::CROSS), false);
+    test_runner.check("no_repeat_before_delay", controller.buttons().repeated(CtrlButtons::CROSS), false);
+
+    controller.update_from(sample(400_000, CtrlButtons::CROSS));
+    test_runner.check("repeat_after_delay", controller.buttons().repeated(CtrlButtons::CROSS), true);
+
+    controller.update_from(sample(450_000, CtrlButtons::CROSS));
+    test_runner.check("no_repeat_before_rate", controller.buttons().repeated(CtrlButtons::CROSS), false);
+
+    controller.update_from(sample(500_000, CtrlButtons::CROSS));
+    test_runner.check("repeat_at_rate", controller.buttons().repeated(CtrlButtons::CROSS), true);
+
+    controller.update_from(sample(516_000, CtrlButtons::empty()));
+    test_runner.check("released", controller.buttons().just_released(CtrlButtons::CROSS), true);
+
+    // A tap between two samples is only seen through the latch.
+    controller.update_from(Sample {
+        made: CtrlButtons::CIRCLE,
+        broken: CtrlButtons::CIRCLE,
+        ..sample(532_000, CtrlButtons::empty())
+    });
+    test_runner.check("latched_press", controller.buttons().just_pressed(CtrlButtons::CIRCLE), true);
+    test_runner.check("latched_release", controller.buttons().just_released(CtrlButtons::CIRCLE), true);
+
+    let config = AnalogConfig::default();
+    let centered = config.apply(130, 125);
+    test_runner.check("deadzone", (centered.x, centered.y), (0.0, 0.0));
+
+    let full = config.apply(255, 128);
+    test_runner.check("full_right", (full.x, full.y), (1.0, 0.0));
+
+    let config = AnalogConfig {
+        center: (100, 128),
+        deadzone: 0.0,
+    };
+    let left = config.apply(0, 128);
+    test_runner.check("calibrated_left", left.x, -1.0);
+
+    let mut recording = Recording::new();
+    recording.push(&sample(0, CtrlButtons::empty()));
+    recording.push(&sample(16_000, CtrlButtons::START));
+    recording.push(&Sample {
+        lx: 255,
+        made: CtrlButtons::CROSS,
+        broken: CtrlButtons::CROSS,
+        ..sample(32_000, CtrlButtons::START)
+    });
+    recording.push(&sample(48_000, CtrlButtons::empty()));
+
+    let samples: Vec<Sample> = recording.iter().collect();
+    let loaded = Recording::from_bytes(recording.as_bytes().to_vec()).unwrap();
+    test_runner.check("recording_len", loaded.len(), 4);
+    test_runner.check("recording_roundtrip", loaded.iter().collect::<Vec<_>>(), samples);
+    test_runner.check("recording_bad_magic", Recording::from_bytes(vec![0; 8]).is_err(), true);
+
+    let mut replayed = Controller::with_source(Recorder::new(Replay::new(loaded)));
+    replayed.update();
+    replayed.update();
+    test_runner.check("replay_pressed", replayed.buttons().just_pressed(CtrlButtons::START), true);
+    replayed.update();
+    test_runner.check("replay_latched", replayed.buttons().just_pressed(CtrlButtons::CROSS), true);
+    test_runner.check("replay_analog", replayed.raw_analog(), (255, 128));
+    replayed.update();
+    test_runner.check("replay_released", replayed.buttons().just_released(CtrlButtons::START), true);
+
+    let (replay, rerecorded) = replayed.into_source().into_parts();
+    test_runner.check("replay_finished", replay.is_finished(), true);
+    test_runner.check("rerecorded", rerecorded.as_bytes(), recording.as_bytes());
+}
@@ -0,0 +1,398 @@
+use alloc::vec::Vec;
+use core::time::Duration;
+use psp::image::jpeg::{self, software, AviReader, JpegError};
+use psp::image::Storage;
+use psp::io::Cursor;
+use psp::test_runner::TestRunner;
+use psp::Error;
+
+/// Writes entropy-coded data, stuffing a zero after each 0xFF byte.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+/// Category codes of the standard luminance DC table.
+const DC_CODES: [(u32, u32); 12] = [
+    (0b00, 2),
+    (0b010, 3),
+    (0b011, 3),
+    (0b100, 3),
+    (0b101, 3),
+    (0b110, 3),
+    (0b1110, 4),
+    (0b11110, 5),
+    (0b111110, 6),
+    (0b1111110, 7),
+    (0b11111110, 8),
+    (0b111111110, 9),
+];
+
+/// The standard luminance DC table, as a DHT segment.
+const DC_TABLE: [u8; 33] = [
+    0xff, 0xc4, 0, 31, 0x00, 0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6,
+    7, 8, 9, 10, 11,
+];
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    /// A difference from the previous DC coefficient, or an AC coefficient
+    /// after its code: the category, then the bits of the value.
+    fn write_dc(&mut self, diff: i32) {
+        let category = 32 - diff.abs().leading_zeros();
+        let (code, len) = DC_CODES[category as usize];
+        self.write(code, len);
+        self.write_value(diff, category);
+    }
+
+    fn write_value(&mut self, value: i32, category: u32) {
+        let bits = if value < 0 { value - 1 } else { value };
+        self.write(bits as u32 & ((1 << category) - 1), category);
+    }
+
+    fn write(&mut self, value: u32, len: u32) {
+        for i in (0..len).rev() {
+            self.bits = self.bits << 1 | (value >> i) & 1;
+            self.count += 1;
+
+            if self.count == 8 {
+                self.bytes.push(self.bits as u8);
+
+                if self.bits == 0xff {
+                    self.bytes.push(0);
+                }
+
+                self.bits = 0;
+                self.count = 0;
+            }
+        }
+    }
+
+    /// Pad to a whole byte with one bits.
+    fn flush(&mut self) {
+        if self.count > 0 {
+            self.write(0x7f, 8 - self.count);
+        }
+    }
+}
+
+/// A grayscale baseline JPEG of flat 8x8 blocks, with one level per block.
+/// It uses the standard luminance DC table, and an AC table holding only
+/// end-of-block.
+fn gray_jpeg(width: u16, height: u16, levels: &[u8], restart_interval: u16) -> Vec<u8> {
+    let mut bytes = alloc::vec![0xff, 0xd8];
+
+    // All quantizers 1.
+    bytes.extend_from_slice(&[0xff, 0xdb, 0, 67, 0]);
+    bytes.extend_from_slice(&[1; 64]);
+
+    bytes.extend_from_slice(&[0xff, 0xc0, 0, 11, 8]);
+    bytes.extend_from_slice(&height.to_be_bytes());
+    bytes.extend_from_slice(&width.to_be_bytes());
+    bytes.extend_from_slice(&[1, 1, 0x11, 0]);
+
+    bytes.extend_from_slice(&DC_TABLE);
+    bytes.extend_from_slice(&[0xff, 0xc4, 0, 20, 0x10]);
+    bytes.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x00]);
+
+    if restart_interval > 0 {
+        bytes.extend_from_slice(&[0xff, 0xdd, 0, 4]);
+        bytes.extend_from_slice(&restart_interval.to_be_bytes());
+    }
+
+    bytes.extend_from_slice(&[0xff, 0xda, 0, 8, 1, 1, 0x00, 0, 63, 0]);
+
+    let mut writer = BitWriter::new();
+
+    let mut prediction = 0;
+
+    for (i, &level) in levels.iter().enumerate() {
+        if restart_interval > 0 && i > 0 && i % restart_interval as usize == 0 {
+            writer.flush();
+            let marker = 0xd0 + (i / restart_interval as usize - 1) as u8 % 8;
+            writer.bytes.extend_from_slice(&[0xff, marker]);
+            prediction = 0;
+        }
+
+        // A flat block's only coefficient is 8 times its level shifted sample.
+        let dc = (level as i32 - 128) * 8;
+        writer.write_dc(dc - prediction);
+        prediction = dc;
+
+        // End of block.
+        writer.write(0, 1);
+    }
+
+    writer.flush();
+    bytes.extend(writer.bytes);
+    bytes.extend_from_slice(&[0xff, 0xd9]);
+    bytes
+}
+
+/// The one AC coefficient of the ramp in `color_jpeg`.
+const RAMP_AC: i32 = 160;
+
+/// A 32x16 colour baseline JPEG, with 4:2:0 chroma. The first 8x8 luma block
+/// holds a horizontal cosine, from coefficient 1, and the rest are mid grey.
+/// The left 16x16 is grey, and the right is red, from its chroma.
+fn color_jpeg() -> Vec<u8> {
+    let mut bytes = alloc::vec![0xff, 0xd8];
+
+    // All quantizers 1.
+    bytes.extend_from_slice(&[0xff, 0xdb, 0, 67, 0]);
+    bytes.extend_from_slice(&[1; 64]);
+
+    // Y at twice the resolution of Cb and Cr.
+    bytes.extend_from_slice(&[0xff, 0xc0, 0, 17, 8, 0, 16, 0, 32, 3]);
+    bytes.extend_from_slice(&[1, 0x22, 0, 2, 0x11, 0, 3, 0x11, 0]);
+
+    // An AC table of end-of-block, code 0, and an 8-bit value, code 10.
+    bytes.extend_from_slice(&DC_TABLE);
+    bytes.extend_from_slice(&[0xff, 0xc4, 0, 21, 0x10]);
+    bytes.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x08]);
+
+    bytes.extend_from_slice(&[0xff, 0xda, 0, 12, 3, 1, 0x00, 2, 0x00, 3, 0x00, 0, 63, 0]);
+
+    let mut writer = BitWriter::new();
+
+    // Each MCU is 4 luma blocks, then Cb, then Cr. Only Cr changes, so only
+    // its prediction matters.
+    for mcu in 0..2 {
+        for block in 0..5 {
+            writer.write_dc(0);
+
+            if mcu == 0 && block == 0 {
+                writer.write(0b10, 2);
+                writer.write_value(RAMP_AC, 8);
+            }
+
+            writer.write(0, 1);
+        }
+
+        // Cr of 192, 64 above neutral.
+        writer.write_dc(if mcu == 0 { 0 } else { 64 * 8 });
+        writer.write(0, 1);
+    }
+
+    writer.flush();
+    bytes.extend(writer.bytes);
+    bytes.extend_from_slice(&[0xff, 0xd9]);
+    bytes
+}
+
+/// Whether each channel of two pixels is within `tolerance`.
+fn close(a: u32, b: u32, tolerance: i32) -> bool {
+    (0..4).all(|i| {
+        let a = (a >> (i * 8) & 0xff) as i32;
+        let b = (b >> (i * 8) & 0xff) as i32;
+        (a - b).abs() <= tolerance
+    })
+}
+
+/// A Motion-JPEG AVI at 25 fps, holding `frames`, an audio chunk, and a
+/// `rec ` list around the last frame.
+fn avi(frames: &[&[u8]]) -> Vec<u8> {
+    fn chunk(id: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+
+        if data.len() % 2 == 1 {
+            bytes.push(0);
+        }
+
+        bytes
+    }
+
+    fn list(kind: &[u8], chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = kind.to_vec();
+        chunks
+            .iter()
+            .for_each(|chunk| data.extend_from_slice(chunk));
+        chunk(b"LIST", &data)
+    }
+
+    let mut avih = alloc::vec![0; 56];
+    avih[0..4].copy_from_slice(&40_000u32.to_le_bytes());
+    avih[16..20].copy_from_slice(&(frames.len() as u32).to_le_bytes());
+    avih[32..36].copy_from_slice(&16u32.to_le_bytes());
+    avih[36..40].copy_from_slice(&8u32.to_le_bytes());
+
+    let mut strh = alloc::vec![0; 56];
+    strh[0..8].copy_from_slice(b"vidsMJPG");
+    strh[20..24].copy_from_slice(&1u32.to_le_bytes());
+    strh[24..28].copy_from_slice(&25u32.to_le_bytes());
+    strh[32..36].copy_from_slice(&(frames.len() as u32).to_le_bytes());
+
+    let mut strf = alloc::vec![0; 40];
+    strf[16..20].copy_from_slice(b"MJPG");
+
+    let hdrl = list(
+        b"hdrl",
+        &[
+            chunk(b"avih", &avih),
+            list(b"strl", &[chunk(b"strh", &strh), chunk(b"strf", &strf)]),
+        ],
+    );
+
+    let (last, rest) = frames.split_last().unwrap();
+    let mut movi: Vec<Vec<u8>> = rest.iter().map(|frame| chunk(b"00dc", frame)).collect();
+    movi.push(chunk(b"01wb", &[0; 5]));
+    movi.push(list(b"rec ", &[chunk(b"00dc", last)]));
+
+    let mut riff = b"AVI ".to_vec();
+    riff.extend(hdrl);
+    riff.extend(chunk(b"JUNK", &[0; 3]));
+    riff.extend(list(b"movi", &movi));
+    riff.extend(chunk(b"idx1", &[]));
+
+    chunk(b"RIFF", &riff)
+}
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let levels = [0, 85, 170, 255];
+    let gray = gray_jpeg(16, 16, &levels, 0);
+
+    test_runner.check("dimensions", jpeg::dimensions(&gray), Ok((16, 16)));
+    test_runner.check(
+        "not_jpeg",
+        jpeg::decode(b"GIF89a").err(),
+        Some(JpegError::InvalidData),
+    );
+    test_runner.check(
+        "error_mapping",
+        JpegError::from(Error::SCE_JPEG_ERROR_NO_SOI),
+        JpegError::InvalidData,
+    );
+
+    let expected: Vec<u32> = levels
+        .iter()
+        .map(|&level| 0xff00_0000 | level as u32 * 0x01_0101)
+        .collect();
+
+    // The first pixel of each block, in raster order.
+    let corners = |image: &psp::image::Image| -> Vec<u32> {
+        [(0, 0), (8, 0), (0, 8), (8, 8)]
+            .iter()
+            .map(|&(x, y)| image.row(y)[x])
+            .collect()
+    };
+
+    let image = software::decode(&gray).unwrap();
+    test_runner.check("software_size", (image.width(), image.height()), (16, 16));
+    test_runner.check("software_stride", image.stride(), 16);
+    test_runner.check("software_storage", image.storage(), Storage::Ram);
+    test_runner.check_large_collection("software_pixels", &corners(&image), &expected);
+
+    let restarts = software::decode(&gray_jpeg(16, 16, &levels, 1)).unwrap();
+    test_runner.check_large_collection("software_restarts", &corners(&restarts), &corners(&image));
+
+    let narrow = software::decode(&gray_jpeg(12, 12, &levels, 0)).unwrap();
+    test_runner.check(
+        "software_narrow",
+        (narrow.width(), narrow.stride(), narrow.row(11)[11]),
+        (12, 16, expected[3]),
+    );
+
+    // From one coefficient, C(0) C(1) / 4 * 160 * cos((2x + 1) pi / 16).
+    let color = color_jpeg();
+    let ramp = [28, 24, 16, 6, -6, -16, -24, -28];
+    let image = software::decode(&color).unwrap();
+    let ramp_ok = (0..8).all(|x| {
+        let grey = (128 + ramp[x]) as u32 * 0x01_0101;
+        close(image.row(0)[x], 0xff00_0000 | grey, 1)
+            && close(image.row(7)[x], 0xff00_0000 | grey, 1)
+    });
+    test_runner.check("software_ac", ramp_ok, true);
+
+    // R = Y + 1.402 (Cr - 128), G = Y - 0.714 (Cr - 128), B = Y.
+    let (grey, red) = (0xff80_8080, 0xff80_52da);
+    let samples = [(12, 12), (4, 8), (24, 0), (31, 8), (24, 15)];
+    let expected = [grey, grey, red, red, red];
+    let matches = |image: &psp::image::Image| {
+        samples
+            .iter()
+            .zip(&expected)
+            .all(|(&(x, y), &pixel)| close(image.row(y)[x], pixel, 2))
+    };
+    test_runner.check("software_color", matches(&image), true);
+
+    // The Media Engine agrees.
+    let hardware = jpeg::Decoder::new(32, 16)
+        .and_then(|mut decoder| decoder.decode_with(&color, Storage::Ram));
+    test_runner.check("hardware_decode", hardware.as_ref().err(), None);
+
+    if let Ok(hardware) = hardware {
+        test_runner.check(
+            "hardware_size",
+            (hardware.width(), hardware.height()),
+            (32, 16),
+        );
+        test_runner.check("hardware_color", matches(&hardware), true);
+        let ramp_ok = (0..8).all(|x| close(hardware.row(0)[x], image.row(0)[x], 2));
+        test_runner.check("hardware_ac", ramp_ok, true);
+    }
+
+    let mut progressive = gray.clone();
+    let sof = progressive
+        .windows(2)
+        .position(|m| m == [0xff, 0xc0])
+        .unwrap();
+    progressive[sof + 1] = 0xc2;
+    test_runner.check(
+        "software_progressive",
+        software::decode(&progressive).err(),
+        Some(JpegError::Unsupported),
+    );
+
+    let bytes = avi(&[&gray, &[], &gray[..3]]);
+    let mut reader = AviReader::new(Cursor::new(bytes)).unwrap();
+    test_runner.check("avi_size", (reader.width(), reader.height()), (16, 8));
+    test_runner.check(
+        "avi_timing",
+        (reader.frame_duration(), reader.frame_count()),
+        (Duration::from_millis(40), 3),
+    );
+
+    let mut frame = Vec::new();
+    let mut frames = Vec::new();
+
+    while reader.read_frame(&mut frame).unwrap() {
+        frames.push(frame.len());
+    }
+
+    test_runner.check_large_collection("avi_frames", &frames, &[gray.len(), 0, 3]);
+
+    reader.rewind().unwrap();
+    reader.read_frame(&mut frame).unwrap();
+    test_runner.check("avi_rewind", frame == gray, true);
+
+    // A frame cut short fails, without losing the way back to the start.
+    let mut cut = avi(&[&gray, &gray]);
+    // Cut off the empty index and the end of the last frame.
+    cut.truncate(cut.len() - 8 - 4);
+    let mut reader = AviReader::new(Cursor::new(cut)).unwrap();
+    reader.read_frame(&mut frame).unwrap();
+    test_runner.check(
+        "avi_truncated",
+        reader.read_frame(&mut frame).err(),
+        Some(psp::io::Error::UnexpectedEof),
+    );
+    reader.rewind().unwrap();
+    reader.read_frame(&mut frame).unwrap();
+    test_runner.check("avi_truncated_rewind", frame == gray, true);
+
+    test_runner.check(
+        "avi_invalid",
+        AviReader::new(Cursor::new(&b"RIFF\0\0\0\0WAVEfmt "[..])).err(),
+        Some(psp::io::Error::InvalidData),
+    );
+}
@@ -5,17 +5,61 @@
 
 use psp::test_runner::TestRunner;
 
+mod alloc_test;
+mod atrac_test;
+mod audio_test;
 mod bmp_screenshot_test;
+mod cache_test;
+mod callback_test;
+mod date_time_test;
+mod error_test;
+mod executor_test;
+mod fs_test;
+mod image_test;
+mod input_test;
+mod jpeg_test;
 mod math_test;
+mod mem_test;
+mod mixer_test;
+mod mp3_test;
+mod sas_test;
+mod time_test;
+mod timer_test;
+mod tracker_test;
+mod video_test;
+mod vorbis_test;
 mod vram_test;
+mod wav_test;
 
 psp::module!("ci_tests", 1, 1);
 
 fn psp_main() {
     let tests = &[
+        alloc_test::test_main,
         bmp_screenshot_test::test_main,
         vram_test::test_main,
         math_test::test_main,
+        mem_test::test_main,
+        cache_test::test_main,
+        error_test::test_main,
+        fs_test::test_main,
+        audio_test::test_main,
+        mixer_test::test_main,
+        sas_test::test_main,
+        mp3_test::test_main,
+        atrac_test::test_main,
+        wav_test::test_main,
+        vorbis_test::test_main,
+        tracker_test::test_main,
+        video_test::test_main,
+        jpeg_test::test_main,
+        image_test::test_main,
+        executor_test::test_main,
+        time_test::test_main,
+        date_time_test::test_main,
+        timer_test::test_main,
+        input_test::test_main,
+        callback_test::test_main,
     ];
 
     let mut runner = TestRunner::new_file_runner();
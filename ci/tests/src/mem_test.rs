@@ -0,0 +1,242 @@
+use alloc::format;
+use alloc::string::String;
+use core::ffi::c_void;
+use core::ptr;
+use psp::sys::{self, ThreadAttributes};
+use psp::test_runner::TestRunner;
+use psp::Align16;
+
+// Call the crate's intrinsics directly, so the compiler can't inline them.
+extern "C" {
+    fn memset(ptr: *mut u8, value: i32, num: usize) -> *mut u8;
+    fn memcpy(dst: *mut u8, src: *const u8, num: usize) -> *mut u8;
+    fn memmove(dst: *mut u8, src: *const u8, num: usize) -> *mut u8;
+    fn memcmp(ptr1: *const u8, ptr2: *const u8, num: usize) -> i32;
+}
+
+const BUF_LEN: usize = 128;
+const MAX_LEN: usize = 80;
+const MAX_OFFSET: usize = 8;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    check("memset", test_runner, check_memset());
+    check("memcpy", test_runner, check_memcpy());
+    check("memmove", test_runner, check_memmove());
+    check("memcmp", test_runner, check_memcmp());
+    check("copy_vfpu", test_runner, check_copy_vfpu());
+}
+
+fn check(name: &'static str, test_runner: &mut TestRunner, result: Result<(), String>) {
+    match result {
+        Ok(()) => test_runner.pass(name, "All alignments and lengths match."),
+        Err(msg) => test_runner.fail(name, &msg),
+    }
+}
+
+// The references work a byte at a time through volatile accesses, so the
+// compiler can't turn them into calls to the intrinsics under test.
+
+fn pattern() -> [u8; BUF_LEN] {
+    let mut buf = [0; BUF_LEN];
+
+    for (i, byte) in buf.iter_mut().enumerate() {
+        unsafe { ptr::write_volatile(byte, (i * 7 + 3) as u8) };
+    }
+
+    buf
+}
+
+fn copy_ref(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        unsafe { ptr::write_volatile(d, ptr::read_volatile(s)) };
+    }
+}
+
+fn same(actual: &[u8], expected: &[u8]) -> bool {
+    actual.len() == expected.len()
+        && actual
+            .iter()
+            .zip(expected)
+            .all(|(a, e)| unsafe { ptr::read_volatile(a) == ptr::read_volatile(e) })
+}
+
+fn check_memset() -> Result<(), String> {
+    for offset in 0..MAX_OFFSET {
+        for len in 0..MAX_LEN {
+            let mut actual = pattern();
+            let mut expected = pattern();
+
+            unsafe { memset(actual.as_mut_ptr().add(offset), 0x1a5, len) };
+
+            for byte in &mut expected[offset..offset + len] {
+                unsafe { ptr::write_volatile(byte, 0xa5) };
+            }
+
+            if !same(&actual, &expected) {
+                return Err(format!("offset {}, len {}", offset, len));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_memcpy() -> Result<(), String> {
+    let src = pattern();
+
+    for dst_offset in 0..MAX_OFFSET {
+        for src_offset in 0..MAX_OFFSET {
+            for len in 0..MAX_LEN {
+                let mut actual = [0u8; BUF_LEN];
+                let mut expected = [0u8; BUF_LEN];
+
+                unsafe {
+                    memcpy(
+                        actual.as_mut_ptr().add(dst_offset),
+                        src.as_ptr().add(src_offset),
+                        len,
+                    );
+                }
+
+                copy_ref(
+                    &mut expected[dst_offset..],
+                    &src[src_offset..src_offset + len],
+                );
+
+                if !same(&actual, &expected) {
+                    return Err(format!(
+                        "dst offset {}, src offset {}, len {}",
+                        dst_offset, src_offset, len,
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_memmove() -> Result<(), String> {
+    // Move within a single buffer, covering both overlap directions.
+    for dst_offset in 0..2 * MAX_OFFSET {
+        for src_offset in 0..2 * MAX_OFFSET {
+            for len in 0..MAX_LEN {
+                let mut actual = pattern();
+                let mut expected = pattern();
+
+                unsafe {
+                    let ptr = actual.as_mut_ptr();
+                    memmove(ptr.add(dst_offset), ptr.add(src_offset), len);
+                }
+
+                // Through a temporary copy, which can't overlap.
+                let mut tmp = [0u8; MAX_LEN];
+                copy_ref(&mut tmp, &expected[src_offset..src_offset + len]);
+                copy_ref(&mut expected[dst_offset..], &tmp[..len]);
+
+                if !same(&actual, &expected) {
+                    return Err(format!(
+                        "dst offset {}, src offset {}, len {}",
+                        dst_offset, src_offset, len,
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_memcmp() -> Result<(), String> {
+    let a = pattern();
+
+    for a_offset in 0..MAX_OFFSET {
+        for b_offset in 0..MAX_OFFSET {
+            for len in 1..MAX_LEN {
+                let mut b = [0u8; BUF_LEN];
+                copy_ref(&mut b[b_offset..], &a[a_offset..a_offset + len]);
+
+                let cmp = |b: &[u8; BUF_LEN]| unsafe {
+                    memcmp(a.as_ptr().add(a_offset), b.as_ptr().add(b_offset), len)
+                };
+
+                if cmp(&b) != 0 {
+                    return Err(format!(
+                        "equal: a offset {}, b offset {}, len {}",
+                        a_offset, b_offset, len,
+                    ));
+                }
+
+                // Differ in the last byte, where the word loop hands over to
+                // the byte loop. Bytes compare as unsigned.
+                let last = b_offset + len - 1;
+                let original = b[last];
+                b[last] = original.wrapping_add(0x80);
+
+                let expected = original as i32 - b[last] as i32;
+
+                if cmp(&b) != expected {
+                    return Err(format!(
+                        "differ: a offset {}, b offset {}, len {}",
+                        a_offset, b_offset, len,
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy every length up to `BUF_LEN` with `copy_vfpu`, checking the bytes
+/// after each copy are untouched. This needs a thread with the VFPU enabled.
+fn check_copy_vfpu() -> Result<(), String> {
+    extern "C" fn copy_thread(_args: usize, argp: *mut c_void) -> i32 {
+        let result = unsafe { &mut **(argp as *mut *mut Result<(), String>) };
+        let src = Align16(pattern());
+
+        for len in 0..BUF_LEN {
+            let mut actual = Align16([0u8; BUF_LEN]);
+            let mut expected = [0u8; BUF_LEN];
+
+            unsafe { psp::mem::copy_vfpu(actual.0.as_mut_ptr(), src.0.as_ptr(), len) };
+            copy_ref(&mut expected, &src.0[..len]);
+
+            if !same(&actual.0, &expected) {
+                *result = Err(format!("len {}", len));
+                break;
+            }
+        }
+
+        0
+    }
+
+    let mut result = Ok(());
+
+    unsafe {
+        let thread = sys::sceKernelCreateThread(
+            &b"copy_vfpu_test\0"[0],
+            copy_thread,
+            32,
+            64 * 1024,
+            ThreadAttributes::USER | ThreadAttributes::VFPU,
+            ptr::null_mut(),
+        );
+
+        if thread.0 < 0 {
+            return Err(format!("creating the thread failed: {:#x}", thread.0));
+        }
+
+        // The kernel copies the pointer onto the new thread's stack.
+        let arg = &mut result as *mut Result<(), String>;
+        sys::sceKernelStartThread(
+            thread,
+            core::mem::size_of_val(&arg),
+            &arg as *const _ as *mut c_void,
+        );
+        sys::sceKernelWaitThreadEnd(thread, ptr::null_mut());
+        sys::sceKernelDeleteThread(thread);
+    }
+
+    result
+}
@@ -0,0 +1,83 @@
+use alloc::vec;
+use psp::audio::{AudioChannel, Interpolation, Mixer, MixerStream, Sound, VoiceParams};
+use psp::sys::AudioFormat;
+use psp::test_runner::TestRunner;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let constant = Sound::new(vec![1000; 100], 1, 44100);
+    let mut mixer = Mixer::new(44100, 2);
+    let mut buffer = [0i16; 40];
+
+    let voice = mixer
+        .play(
+            &constant,
+            VoiceParams {
+                volume: 0.5,
+                pan: -1.0,
+                ..VoiceParams::default()
+            },
+        )
+        .unwrap();
+
+    mixer.mix(&mut buffer);
+    test_runner.check("volume_pan", (buffer[0], buffer[1]), (500, 0));
+
+    // 20 frames were mixed, so 80 remain. At double speed they last 40.
+    mixer.set_params(voice, VoiceParams::default());
+    mixer.set_pitch(voice, 2.0);
+    mixer.mix(&mut buffer);
+    test_runner.check("pitch_playing", mixer.is_playing(voice), true);
+    mixer.mix(&mut buffer);
+    test_runner.check("pitch_ended", mixer.is_playing(voice), false);
+    mixer.mix(&mut buffer);
+    test_runner.check("ended_silent", &buffer[..], &[0; 40][..]);
+    test_runner.check("stale_handle", mixer.set_volume(voice, 1.0), false);
+
+    let looping = mixer
+        .play(
+            &constant,
+            VoiceParams {
+                looping: true,
+                ..VoiceParams::default()
+            },
+        )
+        .unwrap();
+
+    for _ in 0..10 {
+        mixer.mix(&mut buffer);
+    }
+
+    test_runner.check("looping", mixer.is_playing(looping), true);
+    test_runner.check("looping_output", buffer[38], 1000);
+
+    // Both voices clip rather than wrap around.
+    let loud = Sound::new(vec![32000; 100], 1, 44100);
+    mixer.play_oneshot(&loud, VoiceParams::default());
+    mixer.mix(&mut buffer);
+    test_runner.check("clipped", buffer[0], i16::MAX);
+
+    // Fire-and-forget voices are replaced when full, but handle voices aren't.
+    test_runner.check("replace_oneshot", mixer.play(&constant, VoiceParams::default()).is_some(), true);
+    test_runner.check("full_oneshot", mixer.play_oneshot(&constant, VoiceParams::default()), false);
+    test_runner.check("full", mixer.play(&constant, VoiceParams::default()), None);
+    test_runner.check("voices", mixer.active_voices(), 2);
+
+    // Resampling a ramp half way between samples.
+    let ramp = Sound::new(vec![0, 1000, 2000, 3000, 4000], 1, 22050);
+    let mut mixer = Mixer::new(44100, 1);
+    mixer.play_oneshot(&ramp, VoiceParams::default());
+    mixer.mix(&mut buffer[..8]);
+    test_runner.check("linear", &buffer[..8], &[0, 0, 500, 500, 1000, 1000, 1500, 1500][..]);
+
+    mixer.stop_all();
+    mixer.set_interpolation(Interpolation::Cubic);
+    mixer.play_oneshot(&ramp, VoiceParams::default());
+    mixer.mix(&mut buffer[..8]);
+    test_runner.check("cubic", &buffer[4..8], &[1000, 1000, 1500, 1500][..]);
+
+    let channel = AudioChannel::reserve(AudioFormat::Stereo, 512).unwrap();
+    let stream = MixerStream::start(channel, 4).unwrap();
+    stream.lock().play_oneshot(&constant, VoiceParams::default());
+    psp::time::sleep(core::time::Duration::from_millis(50));
+    test_runner.check("stream_finished", stream.lock().active_voices(), 0);
+}
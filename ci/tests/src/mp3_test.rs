@@ -0,0 +1,52 @@
+use alloc::vec::Vec;
+use psp::audio::mp3::audio_range;
+use psp::io::Cursor;
+use psp::test_runner::TestRunner;
+
+/// An ID3v2 tag of `size` bytes after the header, with an optional footer.
+fn id3v2(size: u32, footer: bool) -> Vec<u8> {
+    let mut tag = alloc::vec![b'I', b'D', b'3', 4, 0, if footer { 0x10 } else { 0 }];
+
+    for shift in &[21, 14, 7, 0] {
+        tag.push((size >> shift) as u8 & 0x7f);
+    }
+
+    tag.resize(tag.len() + size as usize + if footer { 10 } else { 0 }, 0);
+    tag
+}
+
+fn range(bytes: Vec<u8>) -> (u64, u64) {
+    let range = audio_range(&mut Cursor::new(bytes)).unwrap();
+    (range.start, range.end)
+}
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let frames = alloc::vec![0xffu8; 1000];
+
+    test_runner.check("untagged", range(frames.clone()), (0, 1000));
+
+    let mut tagged = id3v2(300, false);
+    tagged.extend_from_slice(&frames);
+    test_runner.check("id3v2", range(tagged), (310, 1310));
+
+    let mut footer = id3v2(300, true);
+    footer.extend_from_slice(&frames);
+    test_runner.check("id3v2_footer", range(footer), (320, 1320));
+
+    let mut repeated = id3v2(20, false);
+    repeated.extend(id3v2(200, false));
+    repeated.extend_from_slice(&frames);
+    test_runner.check("id3v2_repeated", range(repeated), (240, 1240));
+
+    let mut id3v1 = frames.clone();
+    id3v1.extend_from_slice(b"TAG");
+    id3v1.resize(1128, 0);
+    test_runner.check("id3v1", range(id3v1), (0, 1000));
+
+    // A tag claiming more than the file holds leaves no audio.
+    test_runner.check(
+        "truncated",
+        range(id3v2(300, false)[..100].to_vec()),
+        (100, 100),
+    );
+}
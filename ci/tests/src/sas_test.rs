@@ -0,0 +1,52 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use psp::audio::sas::{Adsr, Sas, Vag};
+use psp::sys::SAS_PITCH_BASE;
+use psp::test_runner::TestRunner;
+use psp::Error;
+
+/// A VAG file of `blocks` blocks of silence, the last marked as the end.
+fn silent_vag(blocks: usize, sample_rate: u32) -> Vec<u8> {
+    let mut bytes = alloc::vec![0; 48];
+    bytes[0..4].copy_from_slice(b"VAGp");
+    bytes[12..16].copy_from_slice(&(blocks as u32 * 16).to_be_bytes());
+    bytes[16..20].copy_from_slice(&sample_rate.to_be_bytes());
+    bytes[32..36].copy_from_slice(b"test");
+
+    for i in 0..blocks {
+        let mut block = [0; 16];
+        block[1] = if i + 1 == blocks { 1 } else { 0 };
+        bytes.extend_from_slice(&block);
+    }
+
+    bytes
+}
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    test_runner.check("bad_vag", Vag::from_bytes(b"RIFF").is_err(), true);
+
+    let vag = Vag::from_bytes(&silent_vag(4, 22050)).unwrap();
+    test_runner.check("vag_name", vag.name(), "test");
+    test_runner.check("vag_samples", vag.samples(), 4 * 28);
+    test_runner.check("vag_pitch", vag.pitch(), SAS_PITCH_BASE / 2);
+
+    test_runner.check("bad_grain", Sas::new(100, 8).err(), Some(Error::SCE_SAS_ERROR_INVALID_GRAIN));
+
+    let mut sas = Sas::new(256, 8).unwrap();
+    let mut buffer = [0i16; 512];
+
+    test_runner.check("idle_ended", sas.is_ended(0), true);
+    test_runner.check("set_voice", sas.set_voice(0, Arc::new(vag), false), Ok(()));
+    test_runner.check("adsr", sas.set_adsr(0, &Adsr::default()), Ok(()));
+    test_runner.check("key_on", sas.key_on(0), Ok(()));
+    test_runner.check("mix", sas.mix(&mut buffer), Ok(()));
+    test_runner.check("wrong_len", sas.mix(&mut buffer[..10]), Err(Error::SCE_SAS_ERROR_INVALID_GRAIN));
+    test_runner.check("invalid_voice", sas.key_on(8), Err(Error::SCE_SAS_ERROR_INVALID_VOICE));
+
+    // 112 samples at half speed last well under four grains.
+    for _ in 0..4 {
+        sas.mix(&mut buffer).unwrap();
+    }
+
+    test_runner.check("ended", sas.is_ended(0), true);
+}
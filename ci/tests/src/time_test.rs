@@ -0,0 +1,32 @@
+use core::time::Duration;
+use psp::test_runner::TestRunner;
+use psp::time::{self, Instant, SystemTime, UNIX_EPOCH};
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let start = Instant::now();
+    time::sleep(Duration::from_millis(20));
+    let elapsed = start.elapsed();
+    test_runner.check("sleep_min", elapsed >= Duration::from_millis(20), true);
+    test_runner.check("sleep_max", elapsed < Duration::from_millis(40), true);
+    test_runner.check("instant_sub", start + elapsed - start, elapsed);
+    test_runner.check("instant_saturating", start.saturating_duration_since(start + elapsed), Duration::from_secs(0));
+
+    test_runner.check("micros_u32_saturates", time::as_micros_u32(Duration::from_secs(5000)), u32::MAX);
+    test_runner.check("micros_u64", time::as_micros_u64(Duration::new(3, 4_500)), 3_000_004);
+
+    let epoch = SystemTime::from_unix_timestamp(0);
+    test_runner.check("unix_epoch", epoch, Ok(UNIX_EPOCH));
+    test_runner.check("unix_timestamp", (UNIX_EPOCH + Duration::from_secs(86_400)).unix_timestamp(), Ok(86_400));
+
+    // 2020-01-01 00:00:00 UTC
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    test_runner.check("system_time_after_2020", now.as_secs() > 1_577_836_800, true);
+
+    let later = UNIX_EPOCH + Duration::from_micros(1_500_000);
+    test_runner.check("duration_since", later.duration_since(UNIX_EPOCH), Ok(Duration::from_micros(1_500_000)));
+    test_runner.check(
+        "duration_since_later",
+        UNIX_EPOCH.duration_since(later).map_err(|e| e.duration()),
+        Err(Duration::from_micros(1_500_000)),
+    );
+}
@@ -0,0 +1,67 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+use psp::test_runner::TestRunner;
+use psp::time::{self, Alarm, VTimer};
+
+static ALARM_COUNT: AtomicU32 = AtomicU32::new(0);
+static VTIMER_COUNT: AtomicU32 = AtomicU32::new(0);
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let once = Alarm::after(Duration::from_millis(5), || {
+        ALARM_COUNT.fetch_add(1, Ordering::SeqCst);
+    })
+    .unwrap();
+
+    time::sleep(Duration::from_millis(20));
+    test_runner.check("alarm_once", ALARM_COUNT.load(Ordering::SeqCst), 1);
+    test_runner.check("alarm_done", once.is_pending(), false);
+
+    // What the closure captured is dropped with the alarm, not in the handler.
+    let captured = Arc::new(());
+    let held = captured.clone();
+    let alarm = Alarm::after(Duration::from_millis(5), move || {
+        let _ = &held;
+    })
+    .unwrap();
+
+    time::sleep(Duration::from_millis(20));
+    test_runner.check("alarm_keeps_captures", Arc::strong_count(&captured), 2);
+    drop(alarm);
+    test_runner.check("alarm_drops_captures", Arc::strong_count(&captured), 1);
+
+    ALARM_COUNT.store(0, Ordering::SeqCst);
+    let periodic = Alarm::periodic(Duration::from_millis(10), || {
+        ALARM_COUNT.fetch_add(1, Ordering::SeqCst);
+    })
+    .unwrap();
+
+    time::sleep(Duration::from_millis(55));
+    drop(periodic);
+    let count = ALARM_COUNT.load(Ordering::SeqCst);
+    test_runner.check("alarm_periodic", (4..=6).contains(&count), true);
+
+    time::sleep(Duration::from_millis(30));
+    test_runner.check("alarm_cancelled", ALARM_COUNT.load(Ordering::SeqCst), count);
+
+    let mut vtimer = VTimer::new().unwrap();
+    test_runner.check("vtimer_stopped", vtimer.time(), Duration::from_secs(0));
+
+    vtimer
+        .set_periodic(Duration::from_millis(10), || {
+            VTIMER_COUNT.fetch_add(1, Ordering::SeqCst);
+        })
+        .unwrap();
+
+    vtimer.start().unwrap();
+    time::sleep(Duration::from_millis(35));
+    vtimer.stop().unwrap();
+
+    let elapsed = vtimer.time();
+    test_runner.check("vtimer_time", elapsed >= Duration::from_millis(35), true);
+    test_runner.check("vtimer_handler", VTIMER_COUNT.load(Ordering::SeqCst), 3);
+
+    // A stopped VTimer doesn't advance.
+    time::sleep(Duration::from_millis(20));
+    test_runner.check("vtimer_stop", vtimer.time(), elapsed);
+}
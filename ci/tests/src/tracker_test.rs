@@ -0,0 +1,83 @@
+use alloc::vec::Vec;
+use psp::audio::tracker::{Module, ModuleDecoder, ModuleFormat};
+use psp::audio::PcmDecoder;
+use psp::io::Error;
+use psp::test_runner::TestRunner;
+
+/// A 4-channel MOD of one pattern, playing a looped square wave at C-2 in
+/// the first channel with a speed of 1.
+fn square_mod() -> Vec<u8> {
+    let mut bytes = b"square".to_vec();
+    bytes.resize(20, 0);
+
+    for sample in 0..31 {
+        bytes.extend_from_slice(&[0; 22]);
+
+        if sample == 0 {
+            // 16 words, volume 64, looped whole.
+            bytes.extend_from_slice(&[0, 16, 0, 64, 0, 0, 0, 16]);
+        } else {
+            bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        }
+    }
+
+    bytes.extend_from_slice(&[1, 127]);
+    bytes.resize(bytes.len() + 128, 0);
+    bytes.extend_from_slice(b"M.K.");
+
+    // Row 0: period 428, sample 1, F01.
+    let mut pattern = alloc::vec![0; 64 * 4 * 4];
+    pattern[..4].copy_from_slice(&[0x01, 0xac, 0x1f, 0x01]);
+    bytes.extend(pattern);
+
+    bytes.extend((0..32).map(|i| if i < 16 { 100u8 } else { (-100i8) as u8 }));
+    bytes
+}
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let module = Module::from_bytes(&square_mod()).unwrap();
+    test_runner.check("title", module.title(), "square");
+    test_runner.check("format", module.format(), ModuleFormat::Mod);
+    test_runner.check("channels", module.channels(), 4);
+
+    // At 8000 Hz and 125 bpm, a tick is exactly 160 frames.
+    let mut decoder = ModuleDecoder::new(module, 8000);
+    let mut buffer = [0i16; 320];
+    let mut frames = 0;
+    let mut first = [0i16; 2];
+
+    loop {
+        match decoder.read(&mut buffer).unwrap() {
+            0 => break,
+            n => {
+                if frames == 0 {
+                    first.copy_from_slice(&buffer[..2]);
+                }
+
+                frames += n / 2;
+            }
+        }
+    }
+
+    test_runner.check("song_frames", frames, 64 * 160);
+
+    // The first channel is on the left, at half volume on the right.
+    test_runner.check("left", first[0] > 0, true);
+    test_runner.check("right_half", (first[0] / 2 - first[1]).abs() <= 1, true);
+
+    decoder.rewind().unwrap();
+    let mut again = [0i16; 2];
+    decoder.read(&mut again).unwrap();
+    test_runner.check("rewind", again, first);
+
+    test_runner.check(
+        "unknown_format",
+        Module::from_bytes(b"not a module").err(),
+        Some(Error::InvalidData),
+    );
+    test_runner.check(
+        "truncated_xm",
+        Module::from_bytes(b"Extended Module: ").err(),
+        Some(Error::InvalidData),
+    );
+}
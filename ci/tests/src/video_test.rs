@@ -0,0 +1,357 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::time::Duration;
+use psp::audio::Playback;
+use psp::cache::DmaBuffer;
+use psp::io::{Cursor, Error};
+use psp::sys::{self, DisplayPixelFormat};
+use psp::test_runner::TestRunner;
+use psp::video::pmf;
+use psp::video::VideoPlayer;
+
+/// A PMF header for a 144x80 AVC stream and a stereo ATRAC3plus stream, one
+/// second long, with one entry point.
+fn icon_header() -> Vec<u8> {
+    let mut bytes = vec![0u8; pmf::HEADER_SIZE];
+    bytes[..8].copy_from_slice(b"PSMF0015");
+    bytes[8..12].copy_from_slice(&2048u32.to_be_bytes());
+    bytes[12..16].copy_from_slice(&4096u32.to_be_bytes());
+    bytes[0x56..0x5a].copy_from_slice(&90_000u32.to_be_bytes());
+    bytes[0x5c..0x60].copy_from_slice(&180_000u32.to_be_bytes());
+
+    bytes[0x81] = 2;
+    bytes[0x82] = 0xe0;
+    bytes[0x86..0x8a].copy_from_slice(&0x200u32.to_be_bytes());
+    bytes[0x8a..0x8e].copy_from_slice(&1u32.to_be_bytes());
+    bytes[0x8e] = 144 / 16;
+    bytes[0x8f] = 80 / 16;
+    bytes[0x92] = 0xbd;
+    bytes[0xa0] = 2;
+    bytes[0xa1] = 2;
+
+    bytes[0x202..0x206].copy_from_slice(&90_000u32.to_be_bytes());
+    bytes
+}
+
+/// A 33-bit PES timestamp, after a 4-bit `prefix`.
+fn timestamp(prefix: u8, pts: u64) -> [u8; 5] {
+    [
+        prefix << 4 | ((pts >> 29) & 0xe) as u8 | 1,
+        (pts >> 22) as u8,
+        (pts >> 14) as u8 | 1,
+        (pts >> 7) as u8,
+        (pts << 1) as u8 | 1,
+    ]
+}
+
+/// Writes an H.264 RBSP, most significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            bits: 0,
+        }
+    }
+
+    fn bit(&mut self, bit: bool) {
+        if self.bits % 8 == 0 {
+            self.bytes.push(0);
+        }
+
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 0x80 >> (self.bits % 8);
+        }
+
+        self.bits += 1;
+    }
+
+    fn u(&mut self, width: u32, value: u32) {
+        for i in (0..width).rev() {
+            self.bit(value >> i & 1 != 0);
+        }
+    }
+
+    /// An Exp-Golomb code.
+    fn ue(&mut self, value: u32) {
+        let value = value + 1;
+        let width = 32 - value.leading_zeros();
+        self.u(width - 1, 0);
+        self.u(width, value);
+    }
+
+    fn align(&mut self) {
+        while self.bits % 8 != 0 {
+            self.bit(false);
+        }
+    }
+
+    /// The stop bit and alignment which end every RBSP.
+    fn finish(mut self) -> Vec<u8> {
+        self.bit(true);
+        self.align();
+        self.bytes
+    }
+}
+
+/// A NAL unit with its start code, escaping anything in `rbsp` which would
+/// look like one.
+fn nal(header: u8, rbsp: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0, 0, 0, 1, header];
+    let mut zeros = 0;
+
+    for &byte in rbsp {
+        if zeros == 2 && byte <= 3 {
+            bytes.push(3);
+            zeros = 0;
+        }
+
+        bytes.push(byte);
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+    }
+
+    bytes
+}
+
+const WIDTH_MBS: u32 = 144 / 16;
+const HEIGHT_MBS: u32 = 80 / 16;
+
+/// The luma of every pixel in the test video, with no colour, so mid grey.
+const LUMA: u8 = 126;
+
+/// An access unit: a single grey IDR frame, of uncompressed macroblocks.
+fn idr_frame() -> Vec<u8> {
+    // Main profile, level 2.1, frame numbers from 0 to 15, no picture order
+    // count, one reference frame.
+    let mut sps = BitWriter::new();
+    sps.u(8, 77);
+    sps.u(8, 0);
+    sps.u(8, 21);
+    sps.ue(0);
+    sps.ue(0);
+    sps.ue(2);
+    sps.ue(1);
+    sps.bit(false);
+    sps.ue(WIDTH_MBS - 1);
+    sps.ue(HEIGHT_MBS - 1);
+    sps.bit(true);
+    sps.bit(true);
+    sps.bit(false);
+    sps.bit(false);
+
+    // CAVLC, with the deblocking filter controlled by each slice.
+    let mut pps = BitWriter::new();
+    pps.ue(0);
+    pps.ue(0);
+    pps.bit(false);
+    pps.bit(false);
+    pps.ue(0);
+    pps.ue(0);
+    pps.ue(0);
+    pps.bit(false);
+    pps.u(2, 0);
+    pps.ue(0);
+    pps.ue(0);
+    pps.ue(0);
+    pps.bit(true);
+    pps.bit(false);
+    pps.bit(false);
+
+    let mut slice = BitWriter::new();
+    slice.ue(0);
+    slice.ue(7);
+    slice.ue(0);
+    slice.u(4, 0);
+    slice.ue(0);
+    slice.bit(false);
+    slice.bit(false);
+    slice.ue(0);
+    slice.ue(1);
+
+    for _ in 0..WIDTH_MBS * HEIGHT_MBS {
+        // I_PCM, with 16x16 luma and two 8x8 chroma samples.
+        slice.ue(25);
+        slice.align();
+
+        for i in 0..256 + 2 * 64 {
+            slice.u(8, if i < 256 { LUMA as u32 } else { 128 });
+        }
+    }
+
+    let mut frame = nal(0x09, &[0x10]);
+    frame.extend(nal(0x67, &sps.finish()));
+    frame.extend(nal(0x68, &pps.finish()));
+    frame.extend(nal(0x65, &slice.finish()));
+    frame
+}
+
+/// An access unit: a P frame which repeats the one before.
+fn skipped_frame(frame_num: u32) -> Vec<u8> {
+    let mut slice = BitWriter::new();
+    slice.ue(0);
+    slice.ue(5);
+    slice.ue(0);
+    slice.u(4, frame_num % 16);
+    slice.bit(false);
+    slice.bit(false);
+    slice.bit(false);
+    slice.ue(0);
+    slice.ue(1);
+    slice.ue(WIDTH_MBS * HEIGHT_MBS);
+
+    let mut frame = nal(0x09, &[0x30]);
+    frame.extend(nal(0x41, &slice.finish()));
+    frame
+}
+
+const PACKET_SIZE: usize = 2048;
+
+/// Frames in the test video, at 29.97 per second.
+const FRAMES: u64 = 30;
+const FRAME_DURATION: u64 = 3003;
+
+/// A whole PMF file of `FRAMES` grey frames, with no audio. Each frame
+/// starts a new packet, with a PES header giving its timestamp.
+fn grey_pmf() -> Vec<u8> {
+    // A pack header, for time 0.
+    const PACK: [u8; 14] = [0, 0, 1, 0xba, 0x44, 0, 4, 0, 4, 1, 0x01, 0x89, 0xc3, 0xf8];
+
+    let mut stream = Vec::new();
+
+    for n in 0..FRAMES {
+        let frame = if n == 0 {
+            idr_frame()
+        } else {
+            skipped_frame(n as u32)
+        };
+
+        let pts = 90_000 + n * FRAME_DURATION;
+        let mut data = &frame[..];
+        let mut first = true;
+
+        while !data.is_empty() {
+            let header_len = if first { 19 } else { 9 };
+            let len = core::cmp::min(data.len(), PACKET_SIZE - PACK.len() - header_len);
+            let pes_len = (header_len - 6 + len) as u16;
+
+            stream.extend_from_slice(&PACK);
+            stream.extend_from_slice(&[0, 0, 1, 0xe0]);
+            stream.extend_from_slice(&pes_len.to_be_bytes());
+
+            if first {
+                stream.extend_from_slice(&[0x81, 0xc0, 10]);
+                stream.extend_from_slice(&timestamp(3, pts));
+                stream.extend_from_slice(&timestamp(1, pts));
+            } else {
+                stream.extend_from_slice(&[0x81, 0, 0]);
+            }
+
+            stream.extend_from_slice(&data[..len]);
+            data = &data[len..];
+            first = false;
+
+            // Fill the rest of the packet.
+            let rest = PACKET_SIZE - stream.len() % PACKET_SIZE;
+
+            if rest != PACKET_SIZE {
+                let padding = (rest - 6) as u16;
+                stream.extend_from_slice(&[0, 0, 1, 0xbe]);
+                stream.extend_from_slice(&padding.to_be_bytes());
+                stream.extend(core::iter::repeat(0xff).take(rest - 6));
+            }
+        }
+    }
+
+    let mut header = icon_header();
+    header[12..16].copy_from_slice(&(stream.len() as u32).to_be_bytes());
+    let end = 90_000 + FRAMES * FRAME_DURATION;
+    header[0x5c..0x60].copy_from_slice(&(end as u32).to_be_bytes());
+    header[0x81] = 1;
+    header[0x92] = 0;
+
+    header.extend(stream);
+    header
+}
+
+/// Update `player` once per vertical blank until it finishes, or for at most
+/// `vblanks`. Returns the number of frames shown and of times it looped.
+fn play(player: &mut VideoPlayer, frame: &mut DmaBuffer<u32>, vblanks: usize) -> (usize, usize) {
+    let mut shown = 0;
+    let mut loops = 0;
+    let mut position = Duration::from_secs(0);
+
+    for _ in 0..vblanks {
+        if player.is_finished() {
+            break;
+        }
+
+        let buffer = frame.as_device_ptr() as *mut c_void;
+
+        if unsafe { player.update(buffer, 512) }.unwrap() {
+            shown += 1;
+        }
+
+        if player.position() < position {
+            loops += 1;
+        }
+
+        position = player.position();
+        unsafe { sys::sceDisplayWaitVblankStart() };
+    }
+
+    frame.sync_for_cpu();
+    (shown, loops)
+}
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let format = DisplayPixelFormat::Psm8888;
+
+    test_runner.check(
+        "not_pmf",
+        VideoPlayer::new(Cursor::new(vec![0u8; 4096]), format).err(),
+        Some(Error::InvalidData),
+    );
+
+    // The header alone is a whole packet.
+    test_runner.check(
+        "truncated_header",
+        VideoPlayer::new(Cursor::new(b"PSMF0015".to_vec()), format).err(),
+        Some(Error::InvalidData),
+    );
+
+    let mut player = VideoPlayer::new(Cursor::new(grey_pmf()), format).unwrap();
+    let mut frame = DmaBuffer::from_elem(0u32, 512 * 80);
+
+    // One second of video, with plenty of time to spare.
+    let (shown, _) = play(&mut player, &mut frame, 180);
+    test_runner.check("play_finished", player.is_finished(), true);
+    test_runner.check("play_shown", shown > 0, true);
+    test_runner.check(
+        "play_position",
+        player.position(),
+        Duration::from_micros((FRAMES - 1) * FRAME_DURATION * 1_000_000 / 90_000),
+    );
+
+    // 0xff808080 or so, depending on rounding.
+    let pixel = frame[40 * 512 + 72];
+    let grey = (0..3).all(|i| (0x7c..=0x84).contains(&(pixel >> (i * 8) & 0xff)));
+    test_runner.check("play_pixel", grey, true);
+
+    player.restart();
+    let (shown, _) = play(&mut player, &mut frame, 180);
+    test_runner.check("restart_finished", player.is_finished(), true);
+    test_runner.check("restart_shown", shown > 0, true);
+
+    // More than three times through.
+    player.set_looping(true);
+    player.restart();
+    let (shown, loops) = play(&mut player, &mut frame, 200);
+    test_runner.check("loop_finished", player.is_finished(), false);
+    test_runner.check("loop_loops", loops >= 2, true);
+    test_runner.check("loop_shown", shown > FRAMES as usize, true);
+}
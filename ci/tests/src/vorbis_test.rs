@@ -0,0 +1,65 @@
+use psp::audio::vorbis::VorbisDecoder;
+use psp::audio::{PcmDecoder, Sound};
+use psp::io::Cursor;
+use psp::test_runner::TestRunner;
+
+/// About 0.06 s, 1358 frames, of stereo tones at 22050 Hz, titled "Tone".
+const TONE: &[u8] = include_bytes!("../assets/tone.ogg");
+
+/// Whether `got` is within one of `want`, as the PSP rounds floats a little
+/// differently to the reference decoder.
+fn close(got: &[i16], want: &[i16]) -> bool {
+    got.len() == want.len()
+        && got
+            .iter()
+            .zip(want)
+            .all(|(&a, &b)| (a as i32 - b as i32).abs() <= 1)
+}
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let mut decoder = VorbisDecoder::new(Cursor::new(TONE)).unwrap();
+    test_runner.check(
+        "format",
+        (decoder.sample_rate(), decoder.channels()),
+        (22050, 2),
+    );
+    test_runner.check("title", decoder.comment("TITLE"), Some("Tone"));
+    test_runner.check("missing_comment", decoder.comment("ALBUM"), None);
+
+    let sound = Sound::decode(&mut decoder).unwrap();
+    let samples = sound.samples();
+    test_runner.check("frames", sound.frames(), 1358);
+    test_runner.check(
+        "start",
+        close(&samples[..8], &[236, 772, 69, 734, 31, 650, 114, 524]),
+        true,
+    );
+    test_runner.check(
+        "middle",
+        close(
+            &samples[1000..1008],
+            &[-1291, -686, -1094, -609, -750, -498, -303, -356],
+        ),
+        true,
+    );
+    test_runner.check(
+        "end",
+        close(
+            &samples[samples.len() - 8..],
+            &[2332, 1189, 1673, 1334, 919, 1398, 228, 1379],
+        ),
+        true,
+    );
+
+    decoder.rewind().unwrap();
+    let again = Sound::decode(&mut decoder).unwrap();
+    test_runner.check("rewind", again.samples() == samples, true);
+
+    let mut corrupt = TONE.to_vec();
+    corrupt[100] ^= 0xff;
+    test_runner.check(
+        "bad_checksum",
+        VorbisDecoder::new(Cursor::new(corrupt)).err(),
+        Some(psp::io::Error::InvalidData),
+    );
+}
@@ -1,28 +1,18 @@
 use core::ptr::null_mut;
+use psp::sys::TexturePixelFormat;
 use psp::test_runner::TestRunner;
-use psp::vram_alloc::get_vram_allocator;
+use psp::vram_alloc::{get_vram_allocator, VramAllocError};
 
 pub fn test_main(test_runner: &mut TestRunner) {
-    let mut alloc = get_vram_allocator().unwrap();
-    test_runner.pass("allocator_initialization", "Received VRAM allocator.");
-
-    let fake_alloc = get_vram_allocator();
-    match fake_alloc {
-        Ok(_) => test_runner.fail(
-            "allocator_doubling_prevention",
-            "Received second VRAM allocator! Singleton is not working.",
-        ),
-        Err(_) => test_runner.pass(
-            "allocator_doubling_prevention",
-            "VRAM allocator singleton functional.",
-        ),
-    }
+    let alloc = get_vram_allocator();
+    let initial = alloc.stats();
+    test_runner.check("allocator_initially_empty", initial.used, 0);
 
     unsafe {
         let zero_ptr = null_mut();
 
-        let chunk1 = alloc.alloc_sized::<[u8; 4]>(1);
-        let chunk2 = alloc.alloc_sized::<[u8; 4]>(1);
+        let chunk1 = alloc.alloc_sized::<[u8; 4]>(1).unwrap();
+        let chunk2 = alloc.alloc_sized::<[u8; 4]>(1).unwrap();
 
         test_runner.check_list(&[
             (
@@ -33,7 +23,7 @@ pub fn test_main(test_runner: &mut TestRunner) {
             (
                 "second_chunk_addr_zero",
                 chunk2.as_mut_ptr_direct_to_vram(),
-                psp::sys::sceGeEdramGetAddr().offset(4),
+                psp::sys::sceGeEdramGetAddr().offset(16),
             ),
             (
                 "first_chunk_addr_direct",
@@ -43,16 +33,27 @@ pub fn test_main(test_runner: &mut TestRunner) {
             (
                 "second_chunk_addr_direct",
                 chunk2.as_mut_ptr_from_zero(),
-                zero_ptr.offset(4),
+                zero_ptr.offset(16),
             ),
         ]);
 
-        let muh_item = alloc.move_to_vram([69u8; 16]);
+        // Freeing the first chunk leaves a hole, which is reused.
+        drop(chunk1);
+        test_runner.check("freed_range_counted", alloc.stats().free_ranges, 2);
+
+        let chunk3 = alloc.alloc(16).unwrap();
+        test_runner.check("freed_range_reused", chunk3.as_mut_ptr_from_zero(), zero_ptr);
+
+        drop(chunk2);
+        drop(chunk3);
+        test_runner.check("free_ranges_merged", alloc.stats(), initial);
+
+        let mut muh_item = alloc.move_to_vram([69u8; 16]).unwrap();
 
         test_runner.check(
             "vram_moved_addr",
             muh_item.as_mut_ptr(),
-            0x4000008 as *const u8 as _,
+            psp::sys::sceGeEdramGetAddr(),
         );
 
         test_runner.check("vram_storage_len", muh_item.len(), 16);
@@ -60,4 +61,55 @@ pub fn test_main(test_runner: &mut TestRunner) {
         muh_item[15] = 42;
         test_runner.check("vram_storage_integrity2", muh_item[15], 42);
     }
+
+    let aligned = alloc.alloc_aligned(64, 8192).unwrap();
+    test_runner.check("aligned_alloc", aligned.as_mut_ptr_from_zero() as usize % 8192, 0);
+    drop(aligned);
+
+    let texture = alloc
+        .alloc_texture_pixels(512, 512, TexturePixelFormat::Psm8888)
+        .unwrap();
+    test_runner.check("texture_size", texture.len(), 512 * 512 * 4);
+
+    let rest = alloc.alloc(alloc.stats().free).unwrap();
+    let too_big = alloc.alloc(16);
+
+    match too_big {
+        Err(VramAllocError::OutOfMemory { .. }) => test_runner.pass(
+            "out_of_memory_error",
+            "Exhausting VRAM returned an error.",
+        ),
+        _ => test_runner.fail(
+            "out_of_memory_error",
+            "Exhausting VRAM did not return OutOfMemory.",
+        ),
+    }
+
+    drop(rest);
+    drop(texture);
+
+    // Sizes which overflow a `u32` fail, rather than wrapping to something
+    // small.
+    let overflow = |result: Result<_, VramAllocError>| match result {
+        Err(VramAllocError::OutOfMemory { requested, .. }) => requested,
+        _ => 0,
+    };
+    test_runner.check("overflow_alloc", overflow(alloc.alloc(u32::MAX)), u32::MAX);
+    test_runner.check(
+        "overflow_alloc_sized",
+        overflow(alloc.alloc_sized::<u32>(u32::MAX)),
+        u32::MAX,
+    );
+    test_runner.check(
+        "overflow_texture",
+        overflow(alloc.alloc_texture_pixels(32768, 65536, TexturePixelFormat::Psm8888)),
+        u32::MAX,
+    );
+    test_runner.check(
+        "invalid_alignment",
+        alloc.alloc_aligned(16, 48).err(),
+        Some(VramAllocError::InvalidAlignment(48)),
+    );
+
+    test_runner.check("all_chunks_freed", alloc.stats(), initial);
 }
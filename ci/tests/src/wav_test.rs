@@ -0,0 +1,68 @@
+use alloc::vec::Vec;
+use psp::audio::wav::{WavEncoding, WavReader, WavWriter};
+use psp::audio::{Microphone, PcmDecoder, Sound};
+use psp::io::{Cursor, Write};
+use psp::sys::AudioInputFrequency;
+use psp::test_runner::TestRunner;
+use psp::Error;
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let mut cursor = Cursor::new(Vec::new());
+    cursor.write_all(b"abcd").unwrap();
+    cursor.set_position(2);
+    cursor.write_all(b"xyz").unwrap();
+    test_runner.check(
+        "cursor_write",
+        cursor.get_ref().as_slice(),
+        &b"abxyz"[..],
+    );
+
+    let mut wav = WavWriter::new(Cursor::new(Vec::new()), 22050, 2).unwrap();
+    wav.write_samples(&[1, -1, 2, -2]).unwrap();
+    test_runner.check("wav_len", wav.len(), 2);
+
+    let bytes = wav.finish().unwrap().into_inner();
+    test_runner.check("wav_size", bytes.len(), 44 + 8);
+    test_runner.check("wav_riff", &bytes[0..4], &b"RIFF"[..]);
+    test_runner.check("wav_riff_size", &bytes[4..8], &44u32.to_le_bytes()[..]);
+    test_runner.check("wav_rate", &bytes[24..28], &22050u32.to_le_bytes()[..]);
+    test_runner.check(
+        "wav_byte_rate",
+        &bytes[28..32],
+        &(22050u32 * 4).to_le_bytes()[..],
+    );
+    test_runner.check("wav_data_size", &bytes[40..44], &8u32.to_le_bytes()[..]);
+    test_runner.check("wav_samples", &bytes[44..48], &[1u8, 0, 0xff, 0xff][..]);
+
+    let mut reader = WavReader::new(Cursor::new(bytes)).unwrap();
+    test_runner.check(
+        "reader_format",
+        (reader.sample_rate(), reader.channels()),
+        (22050, 2),
+    );
+    test_runner.check("reader_encoding", reader.encoding(), WavEncoding::Pcm(16));
+    test_runner.check("reader_len", reader.len(), 2);
+
+    let sound = Sound::decode(&mut reader).unwrap();
+    test_runner.check("reader_samples", sound.samples(), &[1, -1, 2, -2][..]);
+
+    let mut first = [0; 2];
+    reader.rewind().unwrap();
+    test_runner.check(
+        "reader_rewind",
+        reader.read(&mut first).map(|_| first),
+        Ok([1, -1]),
+    );
+
+    test_runner.check(
+        "reader_invalid",
+        WavReader::new(&b"RIFF\0\0\0\0AVI LIST"[..]).err(),
+        Some(psp::io::Error::InvalidData),
+    );
+
+    test_runner.check(
+        "mic_capacity",
+        Microphone::with_capacity(AudioInputFrequency::Khz44_1, 0, 0).err(),
+        Some(Error::ERRNO_INVALID_ARGUMENT),
+    );
+}
@@ -7,19 +7,14 @@ use psp::sys::{
     self, ScePspFVector3, DisplayPixelFormat, GuContextType, GuSyncMode, GuSyncBehavior,
     GuPrimitive, TextureFilter, TextureEffect, TextureColorComponent,
     FrontFaceDirection, ShadingModel, GuState, TexturePixelFormat, DepthFunc,
-    VertexType, ClearBuffer, MipmapLevel,
+    VertexType, ClearBuffer,
 };
+use psp::image::{png, TextureOptions};
 use psp::vram_alloc::get_vram_allocator;
 use psp::{BUF_WIDTH, SCREEN_WIDTH, SCREEN_HEIGHT};
 
 psp::module!("sample_cube", 1, 1);
 
-// Both width and height, this is a square image.
-const IMAGE_SIZE: usize = 128;
-
-// The image data *must* be aligned to a 16 byte boundary.
-static FERRIS: Align16<[u8; IMAGE_SIZE * IMAGE_SIZE * 4]> = Align16(*include_bytes!("../ferris.bin"));
-
 static mut LIST: Align16<[u32; 0x40000]> = Align16([0; 0x40000]);
 
 #[repr(C, align(4))]
@@ -88,10 +83,16 @@ fn psp_main() {
 unsafe fn psp_main_inner() {
     psp::enable_home_button();
 
-    let mut allocator = get_vram_allocator().unwrap();
-    let fbp0 = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).as_mut_ptr_from_zero();
-    let fbp1 = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).as_mut_ptr_from_zero();
-    let zbp = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm4444).as_mut_ptr_from_zero();
+    let allocator = get_vram_allocator();
+    let fbp0 = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).unwrap().leak();
+    let fbp1 = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).unwrap().leak();
+    let zbp = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm4444).unwrap().leak();
+
+    let ferris = png::decode(include_bytes!("../ferris.png")).unwrap();
+    let texture = TextureOptions::new(TexturePixelFormat::Psm8888)
+        .swizzle(true)
+        .convert(&ferris)
+        .unwrap();
 
     sys::sceGumLoadIdentity();
 
@@ -158,11 +159,15 @@ unsafe fn psp_main_inner() {
 
         // setup texture
 
-        sys::sceGuTexMode(TexturePixelFormat::Psm8888, 0, 0, 0);
-        sys::sceGuTexImage(MipmapLevel::None, 128, 128, 128, &FERRIS as *const _ as *const _);
+        texture.bind();
         sys::sceGuTexFunc(TextureEffect::Replace, TextureColorComponent::Rgb);
         sys::sceGuTexFilter(TextureFilter::Linear, TextureFilter::Linear);
-        sys::sceGuTexScale(1.0, 1.0);
+
+        // The texture is padded to 512x512, so only use the image's corner.
+        sys::sceGuTexScale(
+            texture.width() as f32 / texture.texture_width() as f32,
+            texture.height() as f32 / texture.texture_height() as f32,
+        );
         sys::sceGuTexOffset(0.0, 0.0);
 
         // draw cube
@@ -0,0 +1,22 @@
+#![no_std]
+#![no_main]
+
+use psp::callback::{self, Event, EventKinds};
+
+psp::module!("sample_system_events", 1, 1);
+
+fn psp_main() {
+    let events = callback::subscribe(EventKinds::all()).unwrap();
+    psp::dprintln!("Try the power switch, the UMD drive or the memory stick.");
+
+    loop {
+        match events.recv() {
+            Event::ExitRequested => {
+                psp::dprintln!("Saving before exiting...");
+                callback::exit_game();
+            }
+
+            event => psp::dprintln!("{:?}", event),
+        }
+    }
+}
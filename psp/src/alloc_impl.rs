@@ -1,109 +1,583 @@
+//! The global allocator.
+//!
+//! Memory is requested from the kernel in large regions with
+//! `sceKernelAllocPartitionMemory`, and carved up into blocks by a heap using
+//! segregated free lists. Each list holds the free blocks whose size lies in
+//! `[2^n, 2^(n+1))`, and a bitmap records which lists are non-empty, so finding
+//! a block is a constant-time operation in the common case.
+//!
+//! Every block carries a boundary tag, which lets neighbouring free blocks be
+//! merged on free and lets `realloc` grow into the following block in place.
+
 use alloc::alloc::{Layout, GlobalAlloc};
-use core::{ptr, mem};
+use core::{ptr, mem, cmp, cell::UnsafeCell};
 use crate::sys::{self, SceUid, SceSysMemPartitionId, SceSysMemBlockTypes};
 
-/// An allocator that hooks directly into the PSP OS memory allocator.
-struct SystemAlloc;
+/// Minimum size of a region requested from the kernel. Allocations which do
+/// not fit in a region of this size get a region of their own.
+const REGION_SIZE: usize = 256 * 1024;
 
-unsafe impl GlobalAlloc for SystemAlloc {
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let size = layout.size()
-            // We need to store the memory block ID.
-            + mem::size_of::<SceUid>()
+const WORD: usize = mem::size_of::<usize>();
+
+/// Every block begins with the size of the previous block (only valid while
+/// that block is free), followed by its own size and flags.
+const HEADER: usize = 2 * WORD;
+
+/// Free blocks also store their free list links, so they can't be any smaller
+/// than this.
+const MIN_BLOCK: usize = 4 * WORD;
+
+/// Block sizes and payload addresses are always a multiple of this.
+const GRANULE: usize = 2 * WORD;
+
+/// Set if the block is allocated.
+const USED: usize = 0b01;
+
+/// Set if the block before this one is allocated.
+const PREV_USED: usize = 0b10;
+
+const FLAGS: usize = USED | PREV_USED;
+
+const BIN_COUNT: usize = mem::size_of::<usize>() * 8;
+
+const fn round_up(value: usize, align: usize) -> usize {
+    (value + align - 1) & !(align - 1)
+}
+
+/// The block size needed to hold `size` bytes of payload.
+const fn block_size(size: usize) -> usize {
+    let size = round_up(size + HEADER, GRANULE);
+
+    if size < MIN_BLOCK {
+        MIN_BLOCK
+    } else {
+        size
+    }
+}
+
+/// Index of the free list holding blocks of `size` bytes.
+fn bin_index(size: usize) -> usize {
+    BIN_COUNT - 1 - size.leading_zeros() as usize
+}
+
+/// A chunk of memory obtained from the kernel.
+///
+/// This header is stored at the start of the region, and is followed by the
+/// blocks. The region ends with a zero-sized, permanently used block which
+/// stops coalescing from running off the end.
+#[repr(C)]
+struct Region {
+    uid: SceUid,
+    size: usize,
+    next: *mut Region,
+}
+
+const REGION_HEADER: usize = round_up(mem::size_of::<Region>(), GRANULE);
+
+/// A pointer to a block header.
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct Block(*mut u8);
+
+impl Block {
+    const NULL: Block = Block(ptr::null_mut());
+
+    fn is_null(self) -> bool {
+        self.0.is_null()
+    }
+
+    unsafe fn from_payload(ptr: *mut u8) -> Block {
+        Block(ptr.sub(HEADER))
+    }
+
+    unsafe fn payload(self) -> *mut u8 {
+        self.0.add(HEADER)
+    }
+
+    unsafe fn prev_size(self) -> usize {
+        *self.0.cast::<usize>()
+    }
+
+    unsafe fn set_prev_size(self, size: usize) {
+        *self.0.cast::<usize>() = size;
+    }
+
+    unsafe fn tag(self) -> usize {
+        *self.0.add(WORD).cast::<usize>()
+    }
+
+    unsafe fn set_tag(self, tag: usize) {
+        *self.0.add(WORD).cast::<usize>() = tag;
+    }
+
+    unsafe fn size(self) -> usize {
+        self.tag() & !FLAGS
+    }
+
+    unsafe fn is_used(self) -> bool {
+        self.tag() & USED != 0
+    }
+
+    unsafe fn is_prev_used(self) -> bool {
+        self.tag() & PREV_USED != 0
+    }
 
-            // We also store padding bytes, in case the block returned from the
-            // system is not aligned. The count of padding bytes is also stored
-            // here, in the last byte.
-            + layout.align();
+    unsafe fn next(self) -> Block {
+        Block(self.0.add(self.size()))
+    }
+
+    /// Only valid if the previous block is free.
+    unsafe fn prev(self) -> Block {
+        Block(self.0.sub(self.prev_size()))
+    }
+
+    unsafe fn next_free(self) -> Block {
+        *self.payload().cast::<Block>()
+    }
+
+    unsafe fn set_next_free(self, block: Block) {
+        *self.payload().cast::<Block>() = block;
+    }
+
+    unsafe fn prev_free(self) -> Block {
+        *self.payload().add(WORD).cast::<Block>()
+    }
+
+    unsafe fn set_prev_free(self, block: Block) {
+        *self.payload().add(WORD).cast::<Block>() = block;
+    }
+
+    /// Mark this block as free, with the given size. This also updates the
+    /// boundary tag of the following block.
+    unsafe fn make_free(self, size: usize) {
+        self.set_tag(size | (self.tag() & PREV_USED));
+
+        let next = self.next();
+        next.set_prev_size(size);
+        next.set_tag(next.tag() & !PREV_USED);
+    }
+
+    /// Mark this block as used, with the given size. This also updates the
+    /// boundary tag of the following block.
+    unsafe fn make_used(self, size: usize) {
+        self.set_tag(size | USED | (self.tag() & PREV_USED));
+
+        let next = self.next();
+        next.set_tag(next.tag() | PREV_USED);
+    }
+}
+
+/// Heap usage statistics, as returned by `heap_stats`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    /// Number of regions currently obtained from the kernel.
+    pub regions: usize,
+    /// Total bytes obtained from the kernel.
+    pub reserved: usize,
+    /// Bytes held by live allocations, including block headers.
+    pub in_use: usize,
+    /// Bytes held by free blocks, including block headers.
+    pub free: usize,
+    /// The highest value `in_use` has reached.
+    pub peak_in_use: usize,
+    /// Number of live allocations.
+    pub allocations: usize,
+}
+
+struct Heap {
+    bins: [Block; BIN_COUNT],
+    /// Bit `n` is set if `bins[n]` is non-empty.
+    bitmap: usize,
+    regions: *mut Region,
+    stats: HeapStats,
+}
+
+impl Heap {
+    const fn new() -> Self {
+        Self {
+            bins: [Block::NULL; BIN_COUNT],
+            bitmap: 0,
+            regions: ptr::null_mut(),
+            stats: HeapStats {
+                regions: 0,
+                reserved: 0,
+                in_use: 0,
+                free: 0,
+                peak_in_use: 0,
+                allocations: 0,
+            },
+        }
+    }
+
+    unsafe fn insert(&mut self, block: Block) {
+        let bin = bin_index(block.size());
+        let head = self.bins[bin];
+
+        block.set_prev_free(Block::NULL);
+        block.set_next_free(head);
+
+        if !head.is_null() {
+            head.set_prev_free(block);
+        }
+
+        self.bins[bin] = block;
+        self.bitmap |= 1 << bin;
+        self.stats.free += block.size();
+    }
+
+    unsafe fn unlink(&mut self, block: Block) {
+        let bin = bin_index(block.size());
+        let prev = block.prev_free();
+        let next = block.next_free();
+
+        if prev.is_null() {
+            self.bins[bin] = next;
+        } else {
+            prev.set_next_free(next);
+        }
+
+        if !next.is_null() {
+            next.set_prev_free(prev);
+        }
+
+        if self.bins[bin].is_null() {
+            self.bitmap &= !(1 << bin);
+        }
+
+        self.stats.free -= block.size();
+    }
+
+    /// Find and unlink a free block of at least `size` bytes.
+    unsafe fn find(&mut self, size: usize) -> Option<Block> {
+        let bin = bin_index(size);
+
+        // Blocks in the matching list may still be too small, so search it
+        // first-fit.
+        let mut block = self.bins[bin];
+        while !block.is_null() {
+            if block.size() >= size {
+                self.unlink(block);
+                return Some(block);
+            }
+
+            block = block.next_free();
+        }
 
-        // crate::debug::print_num(size);
+        // Any block in a larger list is big enough.
+        let larger = if bin + 1 < BIN_COUNT {
+            self.bitmap & !((1 << (bin + 1)) - 1)
+        } else {
+            0
+        };
 
-        let id = sys::sceKernelAllocPartitionMemory(
+        if larger == 0 {
+            return None;
+        }
+
+        let block = self.bins[larger.trailing_zeros() as usize];
+        self.unlink(block);
+        Some(block)
+    }
+
+    /// Obtain a new region from the kernel, returning a single free block
+    /// covering it which is at least `size` bytes. The block is not linked into
+    /// any free list.
+    unsafe fn grow(&mut self, size: usize) -> Option<Block> {
+        // Room for the region header, the end marker and aligning the start.
+        let overhead = REGION_HEADER + HEADER + GRANULE;
+        let region_size = cmp::max(REGION_SIZE, round_up(size.checked_add(overhead)?, 4096));
+
+        let uid = sys::sceKernelAllocPartitionMemory(
             SceSysMemPartitionId::SceKernelPrimaryUserPartition,
-            &b"block\0"[0],
+            &b"heap\0"[0],
             SceSysMemBlockTypes::Low,
-            size as u32,
+            region_size as u32,
             ptr::null_mut(),
         );
 
-        // TODO: Error handling.
-        let mut ptr: *mut u8 = sys::sceKernelGetBlockHeadAddr(id).cast();
-        *ptr.cast() = id;
+        if uid.0 < 0 {
+            return None;
+        }
+
+        let head = sys::sceKernelGetBlockHeadAddr(uid) as usize;
+        let start = round_up(head, GRANULE);
+        let end = (head + region_size) & !(GRANULE - 1);
+
+        let region = start as *mut Region;
+        *region = Region {
+            uid,
+            size: region_size,
+            next: self.regions,
+        };
+        self.regions = region;
+
+        let block = Block((start + REGION_HEADER) as *mut u8);
+        let block_size = end - HEADER - block.0 as usize;
+
+        // Nothing precedes the first block, so pretend it is in use to stop
+        // coalescing.
+        block.set_tag(block_size | PREV_USED);
+
+        let marker = block.next();
+        marker.set_prev_size(block_size);
+        marker.set_tag(USED);
 
-        ptr = ptr.add(mem::size_of::<SceUid>());
+        self.stats.regions += 1;
+        self.stats.reserved += region_size;
 
-        // We must add at least one, to store this value.
-        let align_padding = 1 + ptr.add(1).align_offset(layout.align());
-        *ptr.add(align_padding - 1) = align_padding as u8;
-        ptr.add(align_padding)
+        Some(block)
     }
 
-    #[inline(never)]
-    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
-        let align_padding = *ptr.sub(1);
+    /// Return the region to the kernel if `block` covers all of it, and it is
+    /// not the only region left.
+    unsafe fn try_shrink(&mut self, block: Block) -> bool {
+        if self.stats.regions <= 1 || block.next().size() != 0 {
+            return false;
+        }
+
+        let start = block.0.sub(REGION_HEADER) as *mut Region;
+        let mut link: *mut *mut Region = &mut self.regions;
 
-        let id = *ptr
-            .sub(align_padding as usize)
-            .cast::<SceUid>().offset(-1);
+        while !(*link).is_null() {
+            if *link == start {
+                let region = &*start;
+                *link = region.next;
 
-        // TODO: Error handling.
-        sys::sceKernelFreePartitionMemory(id);
+                self.stats.regions -= 1;
+                self.stats.reserved -= region.size;
+                sys::sceKernelFreePartitionMemory(region.uid);
+
+                return true;
+            }
+
+            link = &mut (**link).next;
+        }
+
+        false
     }
-}
 
-#[global_allocator]
-static ALLOC: SystemAlloc = SystemAlloc;
+    /// Merge a block which has just been marked as free with its free
+    /// neighbours, and put the result in a free list.
+    unsafe fn release(&mut self, mut block: Block) {
+        let mut size = block.size();
 
-#[cfg(not(feature = "std"))]
-#[alloc_error_handler]
-fn aeh(_: Layout) -> ! { loop {} }
+        let next = block.next();
+        if !next.is_used() {
+            self.unlink(next);
+            size += next.size();
+        }
+
+        if !block.is_prev_used() {
+            let prev = block.prev();
+            self.unlink(prev);
+            size += prev.size();
+            block = prev;
+        }
 
-#[no_mangle]
-#[cfg(not(feature = "stub-only"))]
-unsafe extern fn memset(ptr: *mut u8, value: u32, num: usize) -> *mut u8 {
-    for i in 0..num {
-        *ptr.add(i) = value as u8;
+        block.make_free(size);
+
+        if !self.try_shrink(block) {
+            self.insert(block);
+        }
     }
 
-    ptr
-}
+    /// Shrink a used block to `size` bytes, releasing the remainder if it is
+    /// large enough to form a block of its own.
+    unsafe fn split(&mut self, block: Block, size: usize) {
+        let total = block.size();
 
-#[no_mangle]
-#[cfg(not(feature = "stub-only"))]
-unsafe extern fn memcpy(dst: *mut u8, src: *const u8, num: isize) -> *mut u8 {
-    for i in 0..num {
-        *dst.offset(i) = *src.offset(i);
+        if total - size < MIN_BLOCK {
+            return;
+        }
+
+        block.set_tag(size | (block.tag() & FLAGS));
+
+        let rest = Block(block.0.add(size));
+        rest.set_tag((total - size) | PREV_USED);
+        self.release(rest);
     }
 
-    dst
-}
+    fn account(&mut self, old: usize, new: usize) {
+        self.stats.in_use = self.stats.in_use - old + new;
+        self.stats.peak_in_use = cmp::max(self.stats.peak_in_use, self.stats.in_use);
+    }
+
+    unsafe fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        if layout.size() > isize::MAX as usize / 2 {
+            return ptr::null_mut();
+        }
+
+        let size = block_size(layout.size());
+        let align = layout.align();
+
+        // Over-aligned requests need enough slack to split off a free block in
+        // front of the aligned payload.
+        let search = if align <= GRANULE {
+            size
+        } else {
+            size + align + MIN_BLOCK
+        };
+
+        let mut block = match self.find(search) {
+            Some(block) => block,
+            None => match self.grow(search) {
+                Some(block) => block,
+                None => return ptr::null_mut(),
+            },
+        };
+
+        if align > GRANULE {
+            let payload = block.payload() as usize;
+            let mut aligned = round_up(payload, align);
+
+            if aligned != payload && aligned - payload < MIN_BLOCK {
+                aligned = round_up(payload + MIN_BLOCK, align);
+            }
+
+            let lead = aligned - payload;
+
+            if lead > 0 {
+                let total = block.size();
+                let rest = Block(block.0.add(lead));
+
+                // `rest` is not linked into anything yet, so only its tag
+                // needs to be set before the leading block is marked as free.
+                rest.set_tag(total - lead);
+                block.make_free(lead);
+                self.insert(block);
+
+                block = rest;
+            }
+        }
+
+        block.make_used(block.size());
+        self.split(block, size);
+
+        self.stats.allocations += 1;
+        self.account(0, block.size());
+
+        block.payload()
+    }
+
+    unsafe fn dealloc(&mut self, ptr: *mut u8) {
+        let block = Block::from_payload(ptr);
+
+        self.stats.allocations -= 1;
+        self.account(block.size(), 0);
+
+        block.set_tag(block.tag() & !USED);
+        self.release(block);
+    }
+
+    /// Resize an allocation without moving it, if possible.
+    unsafe fn realloc_in_place(&mut self, ptr: *mut u8, new_size: usize) -> bool {
+        if new_size > isize::MAX as usize / 2 {
+            return false;
+        }
 
-#[no_mangle]
-#[cfg(not(feature = "stub-only"))]
-unsafe extern fn memcmp(ptr1: *mut u8, ptr2: *mut u8, num: isize) -> i32 {
-    for i in 0..num {
-        let diff = ptr1.offset(i) as i32 - ptr2.offset(i) as i32;
+        let block = Block::from_payload(ptr);
+        let size = block_size(new_size);
+        let old = block.size();
 
-        if diff != 0 {
-            return diff;
+        if size > old {
+            let next = block.next();
+
+            if next.is_used() || old + next.size() < size {
+                return false;
+            }
+
+            self.unlink(next);
+            block.make_used(old + next.size());
         }
+
+        self.split(block, size);
+        self.account(old, block.size());
+
+        true
     }
+}
 
-    0
+/// An allocator that hooks directly into the PSP OS memory allocator.
+struct SystemAlloc {
+    heap: UnsafeCell<Heap>,
 }
 
-#[no_mangle]
-#[cfg(not(feature = "stub-only"))]
-unsafe extern fn memmove(dst: *mut u8, src: *mut u8, num: isize) -> *mut u8 {
-    if dst < src {
-        for i in 0..num {
-            *dst.offset(i) = *src.offset(i);
+unsafe impl Sync for SystemAlloc {}
+
+impl SystemAlloc {
+    /// Run `f` with exclusive access to the heap.
+    ///
+    /// Interrupts are suspended for the duration, which also stops thread
+    /// switching. This means the allocator must not be used from interrupt
+    /// handlers (alarms, VTimers, ...), which can't be suspended this way.
+    fn with_heap<R>(&self, f: impl FnOnce(&mut Heap) -> R) -> R {
+        unsafe {
+            let flags = sys::sceKernelCpuSuspendIntr();
+            let result = f(&mut *self.heap.get());
+            sys::sceKernelCpuResumeIntr(flags);
+
+            result
         }
-    } else {
-        for i in num-1..=0 {
-            *dst.offset(i) = *src.offset(i);
+    }
+}
+
+unsafe impl GlobalAlloc for SystemAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.with_heap(|heap| heap.alloc(layout))
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        self.with_heap(|heap| heap.dealloc(ptr))
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if self.with_heap(|heap| heap.realloc_in_place(ptr, new_size)) {
+            return ptr;
+        }
+
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let new_ptr = self.alloc(new_layout);
+
+        if !new_ptr.is_null() {
+            ptr::copy_nonoverlapping(ptr, new_ptr, cmp::min(layout.size(), new_size));
+            self.dealloc(ptr, layout);
         }
+
+        new_ptr
     }
+}
+
+#[global_allocator]
+static ALLOC: SystemAlloc = SystemAlloc {
+    heap: UnsafeCell::new(Heap::new()),
+};
+
+/// Get statistics about the global heap.
+pub fn heap_stats() -> HeapStats {
+    ALLOC.with_heap(|heap| heap.stats)
+}
 
-    dst
+#[cfg(not(feature = "std"))]
+#[alloc_error_handler]
+fn aeh(layout: Layout) -> ! {
+    // We are out of memory, so this can't go through `panic!`, which needs to
+    // allocate the message. Printing to the debug screen does not.
+    let stats = heap_stats();
+
+    dprintln!(
+        "memory allocation of {} bytes (align {}) failed",
+        layout.size(),
+        layout.align(),
+    );
+    dprintln!(
+        "heap: {} bytes reserved in {} regions, {} in use, {} free",
+        stats.reserved,
+        stats.regions,
+        stats.in_use,
+        stats.free,
+    );
+
+    unsafe {
+        sys::sceKernelExitDeleteThread(1);
+        core::intrinsics::unreachable()
+    }
 }
@@ -0,0 +1,474 @@
+//! ATRAC3 and ATRAC3plus playback through `sceAtrac3plus`.
+
+use super::player::{self, read_full, Decoder, Pending};
+use super::{AudioChannel, AudioStream, Playback, PlaybackControl};
+use crate::cache::DmaBuffer;
+use crate::error::SceReturn;
+use crate::io::{self, Read, Seek, SeekFrom};
+use crate::sys::{self, Atrac3BufferInfo, Module};
+use crate::{Error, SceResult};
+use alloc::boxed::Box;
+use core::{mem, ptr};
+
+/// The most samples per channel in a frame, for ATRAC3plus.
+const MAX_FRAME_SAMPLES: usize = 2048;
+
+/// The size of the buffer a streamed file is read into.
+const STREAM_BUF_SIZE: usize = 64 * 1024;
+
+/// Top the stream buffer up once fewer frames than this are left in it.
+const REFILL_FRAMES: i32 = 32;
+
+const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+trait Source: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> Source for T {}
+
+fn load_modules() -> SceResult<()> {
+    crate::utility::load_module(Module::AvCodec)?;
+    crate::utility::load_module(Module::AvAtrac3Plus)
+}
+
+/// The sample rate from the RIFF header, if it can be found in `bytes`.
+fn header_sample_rate(bytes: &[u8]) -> Option<u32> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return None;
+    }
+
+    let le_u32 = |offset: usize| {
+        let mut word = [0; 4];
+        word.copy_from_slice(bytes.get(offset..offset + 4)?);
+        Some(u32::from_le_bytes(word))
+    };
+
+    let mut offset = 12;
+
+    while offset + 8 <= bytes.len() {
+        let size = le_u32(offset + 4)? as usize;
+
+        if &bytes[offset..offset + 4] == b"fmt " {
+            return le_u32(offset + 12);
+        }
+
+        // Chunks are padded to an even size.
+        offset += 8 + size + (size & 1);
+    }
+
+    None
+}
+
+struct AtracDecoder {
+    id: i32,
+    /// `None` once all the data is in memory.
+    reader: Option<Box<dyn Source>>,
+    // Referenced by the ID until it is released.
+    _data: DmaBuffer<u8>,
+    _second: Option<DmaBuffer<u8>>,
+    pcm: DmaBuffer<i16>,
+    channels: u32,
+    ended: bool,
+    error: Option<io::Error>,
+}
+
+impl AtracDecoder {
+    /// Read up to `len` bytes from `offset` in the file into `dst`, returning
+    /// how many were read.
+    fn load(&mut self, dst: *mut u8, len: u32, offset: u32) -> io::Result<u32> {
+        let reader = match &mut self.reader {
+            Some(reader) => reader,
+            None => return Ok(0),
+        };
+
+        if len == 0 {
+            return Ok(0);
+        }
+
+        reader.seek(SeekFrom::Start(offset as u64))?;
+
+        let buf = unsafe { core::slice::from_raw_parts_mut(dst, len as usize) };
+        let n = read_full(&mut **reader, buf)?;
+        crate::cache::writeback(&buf[..n]);
+
+        Ok(n as u32)
+    }
+
+    /// Top up the stream buffer when it is running low.
+    fn refill(&mut self) -> io::Result<()> {
+        if self.reader.is_none() {
+            return Ok(());
+        }
+
+        let mut remain = 0;
+        unsafe { sys::sceAtracGetRemainFrame(self.id, &mut remain) }.into_result()?;
+
+        // Negative means everything left is already in memory.
+        if remain < 0 || remain >= REFILL_FRAMES {
+            return Ok(());
+        }
+
+        let mut dst = ptr::null_mut();
+        let mut available = 0;
+        let mut offset = 0;
+
+        unsafe { sys::sceAtracGetStreamDataInfo(self.id, &mut dst, &mut available, &mut offset) }
+            .into_result()?;
+
+        let n = self.load(dst, available, offset)?;
+
+        if n > 0 {
+            unsafe { sys::sceAtracAddStreamData(self.id, n) }.into_result()?;
+        }
+
+        Ok(())
+    }
+
+    /// Load the data after the loop end, which a streamed file needs to play
+    /// to its end after looping.
+    fn set_second_buffer(&mut self) -> io::Result<()> {
+        let mut offset = 0;
+        let mut len = 0;
+
+        match unsafe { sys::sceAtracGetSecondBufferInfo(self.id, &mut offset, &mut len) }
+            .into_result()
+        {
+            Ok(_) if len > 0 => (),
+            Ok(_) | Err(Error::SCE_ATRAC_ERROR_SECOND_BUFFER_NOT_NEEDED) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut buffer = DmaBuffer::from_elem(0u8, len as usize);
+        let n = self.load(buffer.as_mut_ptr(), len, offset)?;
+
+        unsafe { sys::sceAtracSetSecondBuffer(self.id, buffer.as_device_ptr() as *mut u8, n) }
+            .into_result()?;
+
+        self._second = Some(buffer);
+        Ok(())
+    }
+
+    fn decode_frame(&mut self) -> io::Result<Option<Pending>> {
+        if self.ended {
+            return Ok(None);
+        }
+
+        self.refill()?;
+
+        let mut samples = 0;
+        let mut end = 0;
+        let mut remain = 0;
+        let out = self.pcm.as_device_ptr() as *mut u16;
+
+        let result =
+            unsafe { sys::sceAtracDecodeData(self.id, out, &mut samples, &mut end, &mut remain) };
+
+        match result.into_result() {
+            Ok(_) => (),
+            Err(Error::SCE_ATRAC_ERROR_ALL_DATA_DECODED) => {
+                self.ended = true;
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        self.pcm.sync_for_cpu();
+        self.ended = end != 0;
+
+        // Mono sources are decoded to stereo too.
+        Ok(Some(Pending::new(self.pcm.as_ptr(), samples as usize * 2)))
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        let mut info: Atrac3BufferInfo = unsafe { mem::zeroed() };
+        unsafe { sys::sceAtracGetBufferInfoForReseting(self.id, 0, &mut info) }.into_result()?;
+
+        let first = self.load(
+            info.puc_write_position_first_buf,
+            info.ui_writable_byte_first_buf,
+            info.ui_read_position_first_buf,
+        )?;
+
+        let second = self.load(
+            info.puc_write_position_second_buf,
+            info.ui_writable_byte_second_buf,
+            info.ui_read_position_second_buf,
+        )?;
+
+        unsafe { sys::sceAtracResetPlayPosition(self.id, 0, first, second) }.into_result()?;
+
+        self.ended = false;
+        Ok(())
+    }
+}
+
+impl Decoder for AtracDecoder {
+    fn decode(&mut self) -> Option<Pending> {
+        match self.decode_frame() {
+            Ok(frame) => frame,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    fn rewind(&mut self) -> bool {
+        match self.reset() {
+            Ok(()) => true,
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+}
+
+impl Drop for AtracDecoder {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceAtracReleaseAtracID(self.id);
+        }
+    }
+}
+
+/// Plays an AT3 or AT3+ file, decoded on the Media Engine, either from memory
+/// or streamed from a reader such as a `File`.
+///
+/// Output is always 16-bit stereo at `sample_rate()`. Call `fill` to decode
+/// into a buffer of any size, or `into_stream` to play it on an audio channel
+/// of its own.
+///
+/// Loop points come from the file's `smpl` chunk, and `set_loop_count` sets
+/// how many times the looped section repeats.
+///
+/// ```no_run
+/// use psp::audio::atrac::AtracPlayer;
+/// use psp::audio::Playback;
+/// use psp::fs::File;
+///
+/// let file = File::open("umd0:/BGM/stage1.at3").unwrap();
+/// let mut player = AtracPlayer::new(file).unwrap();
+/// player.set_loop_count(None).unwrap();
+///
+/// let control = player.control();
+/// let stream = player.into_stream().unwrap();
+/// ```
+pub struct AtracPlayer {
+    decoder: AtracDecoder,
+    pending: Pending,
+    control: PlaybackControl,
+    sample_rate: u32,
+}
+
+// The buffers are only touched through the player, and the pending samples
+// point into its own PCM buffer.
+unsafe impl Send for AtracPlayer {}
+
+impl AtracPlayer {
+    /// Play a whole file held in memory. The data is copied, so `bytes` can
+    /// be dropped afterwards.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        load_modules()?;
+
+        let mut data = DmaBuffer::from_elem(0u8, bytes.len());
+        data.copy_from_slice(bytes);
+
+        let id = unsafe { sys::sceAtracSetDataAndGetID(data.as_device_ptr(), bytes.len()) }
+            .into_result()?;
+        let sample_rate = header_sample_rate(bytes).unwrap_or(DEFAULT_SAMPLE_RATE);
+
+        Self::with_decoder(id, None, data, sample_rate)
+    }
+
+    /// Stream a file from `reader`, keeping only part of it in memory at a
+    /// time.
+    pub fn new<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        load_modules()?;
+
+        let len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let size = core::cmp::min(len, STREAM_BUF_SIZE as u64) as usize;
+        let mut data = DmaBuffer::from_elem(0u8, size);
+        let read = read_full(&mut reader, &mut data)?;
+
+        let id = unsafe {
+            sys::sceAtracSetHalfwayBufferAndGetID(
+                data.as_device_ptr() as *mut u8,
+                read as u32,
+                size as u32,
+            )
+        }
+        .into_result()?;
+
+        let sample_rate = header_sample_rate(&data[..read]).unwrap_or(DEFAULT_SAMPLE_RATE);
+
+        // A small file fits in the buffer whole, and needs no reader.
+        let reader: Option<Box<dyn Source>> = if read as u64 == len {
+            None
+        } else {
+            Some(Box::new(reader))
+        };
+
+        Self::with_decoder(id, reader, data, sample_rate)
+    }
+
+    fn with_decoder(
+        id: i32,
+        reader: Option<Box<dyn Source>>,
+        data: DmaBuffer<u8>,
+        sample_rate: u32,
+    ) -> io::Result<Self> {
+        // From here the decoder releases the ID.
+        let mut decoder = AtracDecoder {
+            id,
+            reader,
+            _data: data,
+            _second: None,
+            pcm: DmaBuffer::from_elem(0i16, MAX_FRAME_SAMPLES * 2),
+            channels: 2,
+            ended: false,
+            error: None,
+        };
+
+        unsafe { sys::sceAtracGetChannel(id, &mut decoder.channels) }.into_result()?;
+
+        // This fails for files without loop points, which play once anyway.
+        unsafe { sys::sceAtracSetLoopNum(id, 0) };
+
+        decoder.set_second_buffer()?;
+
+        Ok(Self {
+            decoder,
+            pending: Pending::EMPTY,
+            control: PlaybackControl::new(),
+            sample_rate,
+        })
+    }
+
+    /// Decode into `out`, which holds interleaved stereo samples, returning
+    /// how many were written. The rest of `out` is silence, while paused or
+    /// once the end is reached.
+    pub fn fill(&mut self, out: &mut [i16]) -> usize {
+        player::fill(out, &mut self.decoder, &mut self.pending, &self.control)
+    }
+
+    /// Play on a channel of its own, at the file's sample rate.
+    ///
+    /// Get a `control` first to pause or restart it while it plays.
+    pub fn into_stream(mut self) -> SceResult<AudioStream> {
+        let channel = AudioChannel::reserve_for_rate(self.sample_rate, MAX_FRAME_SAMPLES / 2)?;
+
+        AudioStream::start(channel, move |buffer| {
+            self.fill(buffer);
+        })
+    }
+
+    /// Set how many more times the looped section plays after the first, or
+    /// `None` to loop forever. Fails with
+    /// `SCE_ATRAC_ERROR_NO_LOOP_INFORMATION` if the file has no loop points.
+    pub fn set_loop_count(&mut self, count: Option<u32>) -> SceResult<()> {
+        let count = count.map(|n| n as i32).unwrap_or(-1);
+
+        unsafe { sys::sceAtracSetLoopNum(self.decoder.id, count) }
+            .into_result()
+            .map(drop)
+    }
+
+    /// How many loops are left, or `None` when looping forever.
+    pub fn loop_count(&self) -> SceResult<Option<u32>> {
+        let mut count = 0;
+        let mut status = 0;
+
+        unsafe { sys::sceAtracGetLoopStatus(self.decoder.id, &mut count, &mut status) }
+            .into_result()?;
+
+        Ok(if count < 0 { None } else { Some(count as u32) })
+    }
+
+    /// The first and last sample of the looped section, if there is one.
+    pub fn loop_points(&self) -> SceResult<Option<(u32, u32)>> {
+        let mut end = 0;
+        let mut loop_start = 0;
+        let mut loop_end = 0;
+
+        unsafe {
+            sys::sceAtracGetSoundSample(self.decoder.id, &mut end, &mut loop_start, &mut loop_end)
+        }
+        .into_result()?;
+
+        Ok(if loop_start < 0 {
+            None
+        } else {
+            Some((loop_start as u32, loop_end as u32))
+        })
+    }
+
+    /// The length, in samples per channel.
+    pub fn total_samples(&self) -> SceResult<u32> {
+        let mut end = 0;
+        let mut loop_start = 0;
+        let mut loop_end = 0;
+
+        unsafe {
+            sys::sceAtracGetSoundSample(self.decoder.id, &mut end, &mut loop_start, &mut loop_end)
+        }
+        .into_result()?;
+
+        Ok(end as u32 + 1)
+    }
+
+    /// The number of frames in the stream buffer not decoded yet, or `None`
+    /// when the rest of the file is all in memory.
+    pub fn remaining_frames(&self) -> SceResult<Option<u32>> {
+        let mut remain = 0;
+        unsafe { sys::sceAtracGetRemainFrame(self.decoder.id, &mut remain) }.into_result()?;
+
+        Ok(if remain < 0 {
+            None
+        } else {
+            Some(remain as u32)
+        })
+    }
+
+    /// The sample the next frame starts at.
+    pub fn position(&self) -> SceResult<u32> {
+        let mut position = 0;
+
+        unsafe { sys::sceAtracGetNextDecodePosition(self.decoder.id, &mut position) }
+            .into_result()?;
+
+        Ok(position)
+    }
+
+    /// The output sample rate, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The number of channels in the source, 1 or 2.
+    pub fn channels(&self) -> u32 {
+        self.decoder.channels
+    }
+
+    /// The bitrate, in kbit/s.
+    pub fn bitrate(&self) -> SceResult<u32> {
+        let mut bitrate = 0;
+        unsafe { sys::sceAtracGetBitrate(self.decoder.id, &mut bitrate) }.into_result()?;
+
+        Ok(bitrate as u32)
+    }
+}
+
+impl Playback for AtracPlayer {
+    fn playback_control(&self) -> &PlaybackControl {
+        &self.control
+    }
+
+    fn take_error(&mut self) -> Option<io::Error> {
+        self.decoder.error.take()
+    }
+}
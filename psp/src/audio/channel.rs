@@ -0,0 +1,268 @@
+use crate::error::SceReturn;
+use crate::sys::{self, AudioFormat, AudioOutputFrequency, AUDIO_SAMPLE_MAX, AUDIO_SAMPLE_MIN, AUDIO_VOLUME_MAX};
+use crate::{Error, SceResult};
+use core::ffi::c_void;
+
+/// The sample count limits of the SRC and Output2 channels.
+const SRC_SAMPLE_MIN: usize = 17;
+const SRC_SAMPLE_MAX: usize = 4111;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    /// One of the 8 regular channels, at 44.1 kHz.
+    Regular(i32),
+    /// The sample rate converting channel.
+    Src(AudioOutputFrequency),
+    /// The second output, at 44.1 kHz.
+    Output2,
+}
+
+/// A reserved audio output channel, released when dropped.
+///
+/// Samples are signed 16-bit PCM, with stereo samples interleaved left first.
+/// Each output call plays exactly `sample_count` samples per channel.
+///
+/// ```no_run
+/// use psp::audio::AudioChannel;
+/// use psp::sys::AudioFormat;
+///
+/// let mut channel = AudioChannel::reserve(AudioFormat::Stereo, 1024).unwrap();
+/// let silence = [0i16; 2048];
+/// channel.output_blocking(&silence).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct AudioChannel {
+    kind: Kind,
+    format: AudioFormat,
+    sample_count: usize,
+    left: u32,
+    right: u32,
+}
+
+impl AudioChannel {
+    /// Reserve the first free regular channel. `sample_count` must be a
+    /// multiple of 64, from `AUDIO_SAMPLE_MIN` to `AUDIO_SAMPLE_MAX`.
+    pub fn reserve(format: AudioFormat, sample_count: usize) -> SceResult<Self> {
+        Self::reserve_regular(sys::AUDIO_NEXT_CHANNEL, format, sample_count)
+    }
+
+    /// Reserve regular channel `index`, from 0 to 7.
+    pub fn reserve_at(index: u8, format: AudioFormat, sample_count: usize) -> SceResult<Self> {
+        if index as u32 >= sys::AUDIO_CHANNEL_MAX {
+            return Err(Error::SCE_AUDIO_ERROR_INVALID_CHANNEL);
+        }
+
+        Self::reserve_regular(index as i32, format, sample_count)
+    }
+
+    fn reserve_regular(index: i32, format: AudioFormat, sample_count: usize) -> SceResult<Self> {
+        let valid = sample_count >= AUDIO_SAMPLE_MIN as usize
+            && sample_count <= AUDIO_SAMPLE_MAX as usize
+            && sample_count % 64 == 0;
+
+        if !valid {
+            return Err(Error::SCE_AUDIO_ERROR_INVALID_SIZE);
+        }
+
+        let index = unsafe { sys::sceAudioChReserve(index, sample_count as i32, format) }.into_result()?;
+
+        Ok(Self::new(Kind::Regular(index), format, sample_count))
+    }
+
+    /// Reserve the sample rate converting channel, which plays stereo samples
+    /// at `frequency`. `sample_count` must be from 17 to 4111.
+    pub fn reserve_src(frequency: AudioOutputFrequency, sample_count: usize) -> SceResult<Self> {
+        check_src_sample_count(sample_count)?;
+
+        unsafe { sys::sceAudioSRCChReserve(sample_count as i32, frequency, 2) }.into_result()?;
+
+        Ok(Self::new(Kind::Src(frequency), AudioFormat::Stereo, sample_count))
+    }
+
+    /// Reserve the second output, which plays stereo samples at 44.1 kHz.
+    /// `sample_count` must be from 17 to 4111.
+    pub fn reserve_output2(sample_count: usize) -> SceResult<Self> {
+        check_src_sample_count(sample_count)?;
+
+        unsafe { sys::sceAudioOutput2Reserve(sample_count as i32) }.into_result()?;
+
+        Ok(Self::new(Kind::Output2, AudioFormat::Stereo, sample_count))
+    }
+
+    /// Reserve a stereo channel which plays `sample_rate` Hz: a regular
+    /// channel for 44.1 kHz, and the SRC channel for the other rates it
+    /// supports. `sample_count` is rounded up to a multiple of 64 for a
+    /// regular channel.
+    pub fn reserve_for_rate(sample_rate: u32, sample_count: usize) -> SceResult<Self> {
+        let frequency = match sample_rate {
+            44100 => {
+                let sample_count = sys::audio_sample_align(sample_count as i32) as usize;
+                return Self::reserve(AudioFormat::Stereo, sample_count);
+            }
+            48000 => AudioOutputFrequency::Khz48,
+            32000 => AudioOutputFrequency::Khz32,
+            24000 => AudioOutputFrequency::Khz24,
+            22050 => AudioOutputFrequency::Khz22_05,
+            16000 => AudioOutputFrequency::Khz16,
+            12000 => AudioOutputFrequency::Khz12,
+            11025 => AudioOutputFrequency::Khz11_025,
+            8000 => AudioOutputFrequency::Khz8,
+            _ => return Err(Error::SCE_AUDIO_ERROR_INVALID_FREQUENCY),
+        };
+
+        Self::reserve_src(frequency, sample_count)
+    }
+
+    fn new(kind: Kind, format: AudioFormat, sample_count: usize) -> Self {
+        Self {
+            kind,
+            format,
+            sample_count,
+            left: AUDIO_VOLUME_MAX,
+            right: AUDIO_VOLUME_MAX,
+        }
+    }
+
+    pub fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    /// The number of channels in each sample, 1 or 2.
+    pub fn channels(&self) -> usize {
+        match self.format {
+            AudioFormat::Mono => 1,
+            AudioFormat::Stereo => 2,
+        }
+    }
+
+    /// The number of samples per channel played by each output call.
+    pub fn sample_count(&self) -> usize {
+        self.sample_count
+    }
+
+    /// The number of `i16`s each output call takes.
+    pub fn buffer_len(&self) -> usize {
+        self.sample_count * self.channels()
+    }
+
+    /// The sample rate, in Hz.
+    pub fn frequency(&self) -> u32 {
+        match self.kind {
+            Kind::Src(frequency) => frequency as i32 as u32,
+            _ => 44100,
+        }
+    }
+
+    /// The left and right volumes, from 0 to `AUDIO_VOLUME_MAX`.
+    pub fn volume(&self) -> (u32, u32) {
+        (self.left, self.right)
+    }
+
+    /// Set the left and right volumes, from 0 to `AUDIO_VOLUME_MAX`. They
+    /// apply from the next output call.
+    ///
+    /// The SRC and Output2 channels have a single volume, so they use the
+    /// louder of the two.
+    pub fn set_volume(&mut self, left: u32, right: u32) {
+        self.left = core::cmp::min(left, AUDIO_VOLUME_MAX);
+        self.right = core::cmp::min(right, AUDIO_VOLUME_MAX);
+    }
+
+    /// Set `volume` split between left and right by `pan`, from -1 (left
+    /// only) to 1 (right only).
+    pub fn set_pan(&mut self, volume: u32, pan: f32) {
+        let (left, right) = pan_volume(volume, pan);
+        self.set_volume(left, right);
+    }
+
+    /// Play `samples`, waiting until the previous output has been taken by
+    /// the hardware. Returns once `samples` is queued, so the next buffer can
+    /// be filled while it plays.
+    ///
+    /// `samples` must hold exactly `buffer_len()` values.
+    pub fn output_blocking(&mut self, samples: &[i16]) -> SceResult<()> {
+        if samples.len() != self.buffer_len() {
+            return Err(Error::SCE_AUDIO_ERROR_INVALID_SIZE);
+        }
+
+        let buf = samples.as_ptr() as *mut c_void;
+        let volume = core::cmp::max(self.left, self.right) as i32;
+
+        let result = unsafe {
+            match self.kind {
+                Kind::Regular(index) => {
+                    sys::sceAudioOutputPannedBlocking(index, self.left as i32, self.right as i32, buf)
+                }
+                Kind::Src(_) => sys::sceAudioSRCOutputBlocking(volume, buf),
+                Kind::Output2 => sys::sceAudioOutput2OutputBlocking(volume, buf),
+            }
+        };
+
+        result.into_result().map(drop)
+    }
+
+    /// The number of samples queued but not played yet.
+    pub fn rest_samples(&self) -> SceResult<usize> {
+        let result = unsafe {
+            match self.kind {
+                Kind::Regular(index) => sys::sceAudioGetChannelRestLength(index),
+                Kind::Src(_) | Kind::Output2 => sys::sceAudioOutput2GetRestSample(),
+            }
+        };
+
+        result.into_result().map(|n| n as usize)
+    }
+
+    /// Wait until everything queued has played.
+    pub fn drain(&self) {
+        while let Ok(n) = self.rest_samples() {
+            if n == 0 {
+                break;
+            }
+
+            unsafe {
+                sys::sceKernelDelayThread(1000);
+            }
+        }
+    }
+}
+
+impl Drop for AudioChannel {
+    fn drop(&mut self) {
+        // A channel can't be released while it is playing.
+        self.drain();
+
+        unsafe {
+            match self.kind {
+                Kind::Regular(index) => sys::sceAudioChRelease(index),
+                Kind::Src(_) => sys::sceAudioSRCChRelease(),
+                Kind::Output2 => sys::sceAudioOutput2Release(),
+            };
+        }
+    }
+}
+
+fn check_src_sample_count(sample_count: usize) -> SceResult<()> {
+    if sample_count < SRC_SAMPLE_MIN || sample_count > SRC_SAMPLE_MAX {
+        Err(Error::SCE_AUDIO_ERROR_INVALID_SIZE)
+    } else {
+        Ok(())
+    }
+}
+
+/// Split `volume` between left and right, keeping the near side at full
+/// volume.
+pub(crate) fn pan_volume(volume: u32, pan: f32) -> (u32, u32) {
+    let pan = if pan < -1.0 {
+        -1.0
+    } else if pan > 1.0 {
+        1.0
+    } else {
+        pan
+    };
+
+    let left = if pan > 0.0 { volume as f32 * (1.0 - pan) } else { volume as f32 };
+    let right = if pan < 0.0 { volume as f32 * (1.0 + pan) } else { volume as f32 };
+
+    (left as u32, right as u32)
+}
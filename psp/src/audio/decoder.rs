@@ -0,0 +1,222 @@
+use super::player::{self, Decoder, Pending};
+use super::{AudioChannel, AudioStream, Playback, PlaybackControl, Sound};
+use crate::io;
+use crate::SceResult;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Stereo frames decoded per refill of a `PcmPlayer`.
+const CHUNK_FRAMES: usize = 1024;
+
+/// A software decoder producing 16-bit PCM.
+///
+/// The decoders behind the `wav`, `vorbis` and `tracker` features implement
+/// this, so they can be decoded to a `Sound` up front or streamed by a
+/// `PcmPlayer`.
+pub trait PcmDecoder {
+    /// The output sample rate, in Hz.
+    fn sample_rate(&self) -> u32;
+
+    /// The number of channels in the output, 1 or 2.
+    fn channels(&self) -> usize;
+
+    /// Decode into `out`, interleaved left first if stereo, returning the
+    /// number of samples written. This is always a whole number of frames,
+    /// and zero only at the end.
+    fn read(&mut self, out: &mut [i16]) -> io::Result<usize>;
+
+    /// Go back to the start.
+    fn rewind(&mut self) -> io::Result<()>;
+}
+
+impl<D: PcmDecoder + ?Sized> PcmDecoder for &mut D {
+    fn sample_rate(&self) -> u32 {
+        (**self).sample_rate()
+    }
+
+    fn channels(&self) -> usize {
+        (**self).channels()
+    }
+
+    fn read(&mut self, out: &mut [i16]) -> io::Result<usize> {
+        (**self).read(out)
+    }
+
+    fn rewind(&mut self) -> io::Result<()> {
+        (**self).rewind()
+    }
+}
+
+impl Sound {
+    /// Decode everything `decoder` has left, for sound effects and other
+    /// short sounds the mixer plays many times.
+    pub fn decode<D: PcmDecoder + ?Sized>(decoder: &mut D) -> io::Result<Self> {
+        let channels = decoder.channels();
+        let mut samples = Vec::new();
+        let mut chunk = [0; CHUNK_FRAMES * 2];
+
+        loop {
+            match decoder.read(&mut chunk[..CHUNK_FRAMES * channels])? {
+                0 => break,
+                n => samples.extend_from_slice(&chunk[..n]),
+            }
+        }
+
+        Ok(Sound::new(samples, channels, decoder.sample_rate()))
+    }
+}
+
+/// Decodes into a stereo buffer, for `player::fill`.
+struct Upmix<D> {
+    decoder: D,
+    buffer: Vec<i16>,
+    looping: bool,
+    error: Option<io::Error>,
+}
+
+impl<D: PcmDecoder> Upmix<D> {
+    fn decode_chunk(&mut self) -> io::Result<usize> {
+        let channels = self.decoder.channels();
+        let len = CHUNK_FRAMES * channels;
+
+        let mut n = self.decoder.read(&mut self.buffer[..len])?;
+
+        if n == 0 && self.looping {
+            self.decoder.rewind()?;
+            n = self.decoder.read(&mut self.buffer[..len])?;
+        }
+
+        if channels == 1 {
+            // Spread from the back, so nothing is overwritten before use.
+            for i in (0..n).rev() {
+                self.buffer[i * 2] = self.buffer[i];
+                self.buffer[i * 2 + 1] = self.buffer[i];
+            }
+
+            n *= 2;
+        }
+
+        Ok(n)
+    }
+}
+
+impl<D: PcmDecoder> Decoder for Upmix<D> {
+    fn decode(&mut self) -> Option<Pending> {
+        match self.decode_chunk() {
+            Ok(0) => None,
+            Ok(n) => Some(Pending::new(self.buffer.as_ptr(), n)),
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    fn rewind(&mut self) -> bool {
+        match self.decoder.rewind() {
+            Ok(()) => true,
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+}
+
+/// Streams a `PcmDecoder`, such as a `VorbisDecoder` over a `File`.
+///
+/// Output is always 16-bit stereo at the decoder's sample rate; mono is
+/// played on both sides. Call `fill` to decode into a buffer of any size, or
+/// `into_stream` to play it on an audio channel of its own.
+///
+/// ```no_run
+/// use psp::audio::vorbis::VorbisDecoder;
+/// use psp::audio::{PcmPlayer, Playback};
+/// use psp::fs::File;
+///
+/// let file = File::open("ms0:/MUSIC/theme.ogg").unwrap();
+/// let mut player = PcmPlayer::new(VorbisDecoder::new(file).unwrap());
+/// player.set_looping(true);
+///
+/// let control = player.control();
+/// let stream = player.into_stream().unwrap();
+///
+/// // Later, from the game loop.
+/// control.pause();
+/// ```
+pub struct PcmPlayer<D> {
+    upmix: Upmix<D>,
+    pending: Pending,
+    control: PlaybackControl,
+}
+
+// The pending samples point into the player's own buffer.
+unsafe impl<D: Send> Send for PcmPlayer<D> {}
+
+impl<D: PcmDecoder> PcmPlayer<D> {
+    pub fn new(decoder: D) -> Self {
+        Self {
+            upmix: Upmix {
+                decoder,
+                buffer: vec![0; CHUNK_FRAMES * 2],
+                looping: false,
+                error: None,
+            },
+            pending: Pending::EMPTY,
+            control: PlaybackControl::new(),
+        }
+    }
+
+    /// Decode into `out`, which holds interleaved stereo samples, returning
+    /// how many were written. The rest of `out` is silence, while paused or
+    /// once the end is reached.
+    pub fn fill(&mut self, out: &mut [i16]) -> usize {
+        player::fill(out, &mut self.upmix, &mut self.pending, &self.control)
+    }
+
+    /// Play on a channel of its own, at the decoder's sample rate.
+    ///
+    /// Get a `control` first to pause or restart it while it plays.
+    pub fn into_stream(mut self) -> SceResult<AudioStream>
+    where
+        D: Send + 'static,
+    {
+        let channel = AudioChannel::reserve_for_rate(self.sample_rate(), CHUNK_FRAMES)?;
+
+        AudioStream::start(channel, move |buffer| {
+            self.fill(buffer);
+        })
+    }
+
+    /// Whether to go back to the start at the end, rather than finish.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.upmix.looping = looping;
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.upmix.looping
+    }
+
+    /// The output sample rate, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.upmix.decoder.sample_rate()
+    }
+
+    pub fn get_ref(&self) -> &D {
+        &self.upmix.decoder
+    }
+
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.upmix.decoder
+    }
+}
+
+impl<D: PcmDecoder> Playback for PcmPlayer<D> {
+    fn playback_control(&self) -> &PlaybackControl {
+        &self.control
+    }
+
+    fn take_error(&mut self) -> Option<io::Error> {
+        self.upmix.error.take()
+    }
+}
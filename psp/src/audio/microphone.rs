@@ -0,0 +1,360 @@
+use super::wav::WavWriter;
+use crate::cache::DmaBuffer;
+use crate::error::SceReturn;
+use crate::io::{self, Seek, Write};
+use crate::sys::{self, AudioInputFrequency, SceUid, ThreadAttributes};
+use crate::{Error, SceResult};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::{cmp, mem, ptr};
+
+/// The same as the audio refill thread, so input isn't starved either.
+const PRIORITY: i32 = 16;
+const STACK_SIZE: i32 = 0x4000;
+
+/// Samples per input call.
+const GRAIN: usize = 256;
+
+/// How much is kept by default, in seconds.
+const DEFAULT_SECONDS: usize = 2;
+
+/// A fixed-size queue which drops its oldest samples when full.
+struct Ring {
+    buf: Vec<i16>,
+    start: usize,
+    len: usize,
+    dropped: usize,
+}
+
+impl Ring {
+    fn push(&mut self, samples: &[i16]) {
+        let capacity = self.buf.len();
+
+        for &sample in samples {
+            if self.len == capacity {
+                self.start = (self.start + 1) % capacity;
+                self.len -= 1;
+                self.dropped += 1;
+            }
+
+            self.buf[(self.start + self.len) % capacity] = sample;
+            self.len += 1;
+        }
+    }
+
+    fn pop_into(&mut self, out: &mut [i16]) -> usize {
+        let n = cmp::min(out.len(), self.len);
+
+        for sample in &mut out[..n] {
+            *sample = self.buf[self.start];
+            self.start = (self.start + 1) % self.buf.len();
+        }
+
+        self.len -= n;
+        n
+    }
+}
+
+/// State shared with the capture thread. The ring is guarded by `sema`.
+struct Shared {
+    sema: SceUid,
+    ring: UnsafeCell<Ring>,
+    frequency: AudioInputFrequency,
+    stop: AtomicBool,
+    finished: AtomicBool,
+    result: SceResult<()>,
+}
+
+impl Shared {
+    fn with_ring<T>(&self, f: impl FnOnce(&mut Ring) -> T) -> T {
+        unsafe {
+            sys::sceKernelWaitSema(self.sema, 1, ptr::null_mut());
+            let result = f(&mut *self.ring.get());
+            sys::sceKernelSignalSema(self.sema, 1);
+
+            result
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceKernelDeleteSema(self.sema);
+        }
+    }
+}
+
+unsafe extern "C" fn capture_thread(_args: usize, argp: *mut c_void) -> i32 {
+    let shared = *(argp as *const *mut Shared);
+    let mut buffer = DmaBuffer::from_elem(0i16, GRAIN);
+
+    while !(*shared).stop.load(Ordering::Acquire) {
+        let result =
+            sys::sceAudioInputBlocking(GRAIN as i32, (*shared).frequency, buffer.as_device_ptr());
+
+        if let Err(e) = result.into_result() {
+            (*shared).result = Err(e);
+            break;
+        }
+
+        buffer.sync_for_cpu();
+        (*shared).with_ring(|ring| ring.push(&buffer));
+    }
+
+    (*shared).finished.store(true, Ordering::Release);
+
+    0
+}
+
+/// Captures mono 16-bit samples from the headset microphone, on a thread of
+/// its own.
+///
+/// Captured samples wait in a ring buffer until they are read. If they
+/// aren't read in time, the oldest are dropped.
+///
+/// ```no_run
+/// use psp::audio::wav::WavWriter;
+/// use psp::audio::Microphone;
+/// use psp::sys::AudioInputFrequency;
+///
+/// let mic = Microphone::open(AudioInputFrequency::Khz22_05, 0x1000).unwrap();
+/// let mut wav = WavWriter::create("ms0:/memo.wav", mic.sample_rate(), 1).unwrap();
+///
+/// while wav.len() < mic.sample_rate() * 5 {
+///     mic.write_wav(&mut wav).unwrap();
+///     unsafe { psp::sys::sceDisplayWaitVblankStart() };
+/// }
+///
+/// wav.finish().unwrap();
+/// ```
+pub struct Microphone {
+    thread: SceUid,
+    shared: *mut Shared,
+}
+
+impl Microphone {
+    /// Whether a microphone is plugged in.
+    pub fn is_connected() -> bool {
+        unsafe { sys::sceHprmIsMicrophoneExist() == 1 }
+    }
+
+    /// Start capturing at `frequency`, with `gain` passed to
+    /// `sceAudioInputInit`, keeping up to two seconds of samples.
+    pub fn open(frequency: AudioInputFrequency, gain: i32) -> SceResult<Self> {
+        let capacity = frequency as i32 as usize * DEFAULT_SECONDS;
+        Self::with_capacity(frequency, gain, capacity)
+    }
+
+    /// Start capturing, keeping up to `capacity` samples.
+    ///
+    /// Fails with `ERRNO_DEVICE_NOT_FOUND` if no microphone is plugged in.
+    pub fn with_capacity(
+        frequency: AudioInputFrequency,
+        gain: i32,
+        capacity: usize,
+    ) -> SceResult<Self> {
+        if capacity == 0 {
+            return Err(Error::ERRNO_INVALID_ARGUMENT);
+        }
+
+        if !Self::is_connected() {
+            return Err(Error::ERRNO_DEVICE_NOT_FOUND);
+        }
+
+        unsafe { sys::sceAudioInputInit(0, gain, 0) }.into_result()?;
+
+        let sema =
+            unsafe { sys::sceKernelCreateSema(&b"microphone\0"[0], 0, 1, 1, ptr::null_mut()) }
+                .into_result()?;
+
+        // From here dropping the state deletes the lock.
+        let shared = Box::into_raw(Box::new(Shared {
+            sema,
+            ring: UnsafeCell::new(Ring {
+                buf: vec![0; capacity],
+                start: 0,
+                len: 0,
+                dropped: 0,
+            }),
+            frequency,
+            stop: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            result: Ok(()),
+        }));
+
+        let thread = unsafe {
+            sys::sceKernelCreateThread(
+                &b"microphone\0"[0],
+                capture_thread,
+                PRIORITY,
+                STACK_SIZE,
+                ThreadAttributes::USER,
+                ptr::null_mut(),
+            )
+        }
+        .into_result();
+
+        let thread = match thread {
+            Ok(thread) => thread,
+            Err(e) => {
+                drop(unsafe { Box::from_raw(shared) });
+                return Err(e);
+            }
+        };
+
+        // The kernel copies the pointer onto the new thread's stack.
+        let arg = shared;
+        let result = unsafe {
+            sys::sceKernelStartThread(
+                thread,
+                mem::size_of::<*mut Shared>(),
+                &arg as *const _ as *mut c_void,
+            )
+        };
+
+        if let Err(e) = result.into_result() {
+            unsafe {
+                sys::sceKernelDeleteThread(thread);
+                drop(Box::from_raw(shared));
+            }
+
+            return Err(e);
+        }
+
+        Ok(Self { thread, shared })
+    }
+
+    fn shared(&self) -> &Shared {
+        unsafe { &*self.shared }
+    }
+
+    /// The sample rate, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.shared().frequency as i32 as u32
+    }
+
+    /// The number of samples waiting to be read.
+    pub fn available(&self) -> usize {
+        self.shared().with_ring(|ring| ring.len)
+    }
+
+    /// The number of samples dropped because they weren't read in time.
+    pub fn dropped(&self) -> usize {
+        self.shared().with_ring(|ring| ring.dropped)
+    }
+
+    /// Move waiting samples into `out`, returning how many were moved.
+    pub fn read(&self, out: &mut [i16]) -> usize {
+        self.shared().with_ring(|ring| ring.pop_into(out))
+    }
+
+    /// Iterate over the samples waiting now, removing them.
+    pub fn samples(&self) -> Samples<'_> {
+        Samples {
+            mic: self,
+            batch: [0; GRAIN],
+            pos: 0,
+            len: 0,
+            remaining: self.available(),
+        }
+    }
+
+    /// Discard the samples waiting to be read.
+    pub fn clear(&self) {
+        self.shared().with_ring(|ring| {
+            ring.start = 0;
+            ring.len = 0;
+        })
+    }
+
+    /// Write the samples waiting now to `wav`, returning how many there were.
+    pub fn write_wav<W: Write + Seek>(&self, wav: &mut WavWriter<W>) -> io::Result<usize> {
+        let mut batch = [0; GRAIN];
+        let mut remaining = self.available();
+        let total = remaining;
+
+        while remaining > 0 {
+            let n = self.read(&mut batch[..cmp::min(GRAIN, remaining)]);
+
+            if n == 0 {
+                break;
+            }
+
+            wav.write_samples(&batch[..n])?;
+            remaining -= n;
+        }
+
+        Ok(total - remaining)
+    }
+
+    /// Whether capture stopped because input failed.
+    pub fn is_stopped(&self) -> bool {
+        self.shared().finished.load(Ordering::Acquire)
+    }
+
+    /// Stop capturing, returning any input error.
+    pub fn stop(mut self) -> SceResult<()> {
+        let shared = self.join();
+        mem::forget(self);
+
+        shared.result
+    }
+
+    fn join(&mut self) -> Box<Shared> {
+        unsafe {
+            (*self.shared).stop.store(true, Ordering::Release);
+            sys::sceKernelWaitThreadEnd(self.thread, ptr::null_mut());
+            sys::sceKernelDeleteThread(self.thread);
+
+            Box::from_raw(self.shared)
+        }
+    }
+}
+
+impl Drop for Microphone {
+    fn drop(&mut self) {
+        drop(self.join());
+    }
+}
+
+/// Iterates over the samples a `Microphone` had waiting when `samples` was
+/// called.
+pub struct Samples<'a> {
+    mic: &'a Microphone,
+    batch: [i16; GRAIN],
+    pos: usize,
+    len: usize,
+    remaining: usize,
+}
+
+impl Iterator for Samples<'_> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.pos == self.len {
+            if self.remaining == 0 {
+                return None;
+            }
+
+            let want = cmp::min(GRAIN, self.remaining);
+            self.len = self.mic.read(&mut self.batch[..want]);
+            self.pos = 0;
+
+            // Someone else may have read them in the meantime.
+            if self.len == 0 {
+                self.remaining = 0;
+                return None;
+            }
+
+            self.remaining -= self.len;
+        }
+
+        self.pos += 1;
+        Some(self.batch[self.pos - 1])
+    }
+}
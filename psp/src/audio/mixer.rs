@@ -0,0 +1,440 @@
+//! The mixing core. This only uses `alloc`, with no system calls, so it can
+//! run anywhere; `MixerStream` runs it on a refill thread.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// Sample data which voices play. Cloning is cheap, as the samples are
+/// shared.
+#[derive(Debug, Clone)]
+pub struct Sound {
+    samples: Arc<[i16]>,
+    channels: usize,
+    sample_rate: u32,
+}
+
+impl Sound {
+    /// Wrap signed 16-bit PCM, interleaved left first if stereo.
+    ///
+    /// # Panics
+    ///
+    /// If `channels` is not 1 or 2, or `sample_rate` is zero.
+    pub fn new(samples: Vec<i16>, channels: usize, sample_rate: u32) -> Self {
+        assert!(channels == 1 || channels == 2, "sounds must be mono or stereo");
+        assert!(sample_rate > 0, "sample rate must not be zero");
+
+        Self {
+            samples: samples.into(),
+            channels,
+            sample_rate,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The length, in samples per channel.
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+}
+
+/// How voices are resampled when their rate differs from the output's.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Cheap, but muffles high frequencies a little.
+    Linear,
+    /// Catmull-Rom, over four samples. Clearer, at about twice the cost.
+    Cubic,
+}
+
+/// How a voice plays.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VoiceParams {
+    /// From 0 to 1. Higher values amplify, and may clip.
+    pub volume: f32,
+    /// From -1 (left only) to 1 (right only).
+    pub pan: f32,
+    /// The playback speed, where 1 is the sound's own sample rate and 2 is
+    /// an octave up.
+    pub pitch: f32,
+    /// Whether to start again from the beginning after the end.
+    pub looping: bool,
+}
+
+impl Default for VoiceParams {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            pan: 0.0,
+            pitch: 1.0,
+            looping: false,
+        }
+    }
+}
+
+/// A handle to a playing voice. It stays valid after the voice ends, but no
+/// longer refers to anything.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct VoiceId {
+    slot: u32,
+    generation: u32,
+}
+
+/// Gains are fixed point, with 1.0 at `1 << GAIN_BITS`.
+const GAIN_BITS: u32 = 12;
+
+/// Positions are fixed point frame indices, with 32 fractional bits.
+const FRAC_BITS: u32 = 32;
+
+struct Voice {
+    generation: u32,
+    sound: Sound,
+    params: VoiceParams,
+    /// Fire-and-forget voices may be replaced when all slots are in use.
+    oneshot: bool,
+    /// When the voice started, to find the oldest.
+    started: u64,
+    position: u64,
+    step: u64,
+    left: i32,
+    right: i32,
+}
+
+impl Voice {
+    fn update(&mut self, output_rate: u32, master_volume: f32) {
+        let params = &self.params;
+        let pitch = if params.pitch > 0.0 { params.pitch } else { 0.0 };
+        let step = pitch * self.sound.sample_rate as f32 / output_rate as f32;
+        self.step = (step * (1u64 << FRAC_BITS) as f32) as u64;
+
+        let pan = if params.pan < -1.0 {
+            -1.0
+        } else if params.pan > 1.0 {
+            1.0
+        } else {
+            params.pan
+        };
+
+        let volume = params.volume * master_volume * (1 << GAIN_BITS) as f32;
+        let left = if pan > 0.0 { volume * (1.0 - pan) } else { volume };
+        let right = if pan < 0.0 { volume * (1.0 + pan) } else { volume };
+
+        self.left = left as i32;
+        self.right = right as i32;
+    }
+
+    /// The sample at `frame` of `channel`, wrapping if looping and silent
+    /// outside the sound otherwise.
+    fn sample(&self, frame: i64, channel: usize) -> i32 {
+        let frames = self.sound.frames() as i64;
+
+        let frame = if self.params.looping {
+            frame.rem_euclid(frames)
+        } else if frame < 0 || frame >= frames {
+            return 0;
+        } else {
+            frame
+        };
+
+        self.sound.samples[frame as usize * self.sound.channels + channel] as i32
+    }
+
+    fn interpolate(&self, interpolation: Interpolation, channel: usize) -> i32 {
+        let frame = (self.position >> FRAC_BITS) as i64;
+
+        // 15 bits of the fraction are plenty, and keep products in range.
+        let t = ((self.position >> (FRAC_BITS - 15)) & 0x7fff) as i64;
+        let s0 = self.sample(frame, channel) as i64;
+        let s1 = self.sample(frame + 1, channel) as i64;
+
+        match interpolation {
+            Interpolation::Linear => (s0 + (((s1 - s0) * t) >> 15)) as i32,
+            Interpolation::Cubic => {
+                let before = self.sample(frame - 1, channel) as i64;
+                let after = self.sample(frame + 2, channel) as i64;
+
+                let a = -before + 3 * s0 - 3 * s1 + after;
+                let b = 2 * before - 5 * s0 + 4 * s1 - after;
+                let c = s1 - before;
+
+                let value = ((((a * t) >> 15) + b) * t >> 15) + c;
+                let value = s0 + ((value * t) >> 16);
+
+                clamp(value as i32)
+            }
+        }
+    }
+
+    /// Add up to `frames` frames into `mix`, returning false once the voice
+    /// has ended.
+    fn mix_into(&mut self, mix: &mut [i32], interpolation: Interpolation) -> bool {
+        let frames = self.sound.frames() as u64;
+
+        if frames == 0 {
+            return false;
+        }
+
+        let end = frames << FRAC_BITS;
+        let stereo = self.sound.channels == 2;
+
+        for out in mix.chunks_exact_mut(2) {
+            if self.position >= end {
+                if !self.params.looping {
+                    return false;
+                }
+
+                self.position %= end;
+            }
+
+            let left = self.interpolate(interpolation, 0);
+            let right = if stereo {
+                self.interpolate(interpolation, 1)
+            } else {
+                left
+            };
+
+            out[0] += (left * self.left) >> GAIN_BITS;
+            out[1] += (right * self.right) >> GAIN_BITS;
+
+            self.position += self.step;
+        }
+
+        self.params.looping || self.position < end
+    }
+}
+
+fn clamp(value: i32) -> i32 {
+    if value < i16::MIN as i32 {
+        i16::MIN as i32
+    } else if value > i16::MAX as i32 {
+        i16::MAX as i32
+    } else {
+        value
+    }
+}
+
+/// Mixes many voices into one stereo output.
+///
+/// ```no_run
+/// use psp::audio::{Mixer, Sound, VoiceParams};
+///
+/// let beep = Sound::new(vec![0; 4410], 1, 22050);
+/// let mut mixer = Mixer::new(44100, 16);
+///
+/// // Fire and forget.
+/// mixer.play_oneshot(&beep, VoiceParams::default());
+///
+/// // Keep a handle, to change the voice as it plays.
+/// let engine = mixer
+///     .play(&beep, VoiceParams { looping: true, ..VoiceParams::default() })
+///     .unwrap();
+/// mixer.set_pitch(engine, 1.5);
+///
+/// let mut buffer = [0i16; 2048];
+/// mixer.mix(&mut buffer);
+/// ```
+pub struct Mixer {
+    sample_rate: u32,
+    interpolation: Interpolation,
+    master_volume: f32,
+    voices: Vec<Option<Voice>>,
+    next_generation: u32,
+    started: u64,
+    accumulator: Vec<i32>,
+}
+
+impl Mixer {
+    /// Create a mixer producing `sample_rate` Hz stereo, with at most
+    /// `max_voices` playing at once.
+    pub fn new(sample_rate: u32, max_voices: usize) -> Self {
+        let mut voices = Vec::with_capacity(max_voices);
+        voices.resize_with(max_voices, || None);
+
+        Self {
+            sample_rate,
+            interpolation: Interpolation::Linear,
+            master_volume: 1.0,
+            voices,
+            next_generation: 0,
+            started: 0,
+            accumulator: Vec::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+
+    /// Scale every voice's volume.
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume;
+
+        let (rate, master) = (self.sample_rate, self.master_volume);
+
+        for voice in self.voices.iter_mut().flatten() {
+            voice.update(rate, master);
+        }
+    }
+
+    /// Start playing `sound`, returning a handle to it, or `None` if every
+    /// slot is in use by a voice with a handle.
+    ///
+    /// If every slot is taken, the oldest fire-and-forget voice is replaced.
+    pub fn play(&mut self, sound: &Sound, params: VoiceParams) -> Option<VoiceId> {
+        self.start(sound, params, false)
+    }
+
+    /// Start playing `sound` without a handle. It can only be stopped by
+    /// `stop_all`, and may be replaced by newer voices when all slots are in
+    /// use. Returns false if it couldn't be played.
+    pub fn play_oneshot(&mut self, sound: &Sound, params: VoiceParams) -> bool {
+        self.start(sound, params, true).is_some()
+    }
+
+    fn start(&mut self, sound: &Sound, params: VoiceParams, oneshot: bool) -> Option<VoiceId> {
+        let slot = match self.voices.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None => self
+                .voices
+                .iter()
+                .enumerate()
+                .filter_map(|(i, v)| v.as_ref().filter(|v| v.oneshot).map(|v| (i, v.started)))
+                .min_by_key(|&(_, started)| started)?
+                .0,
+        };
+
+        let generation = self.next_generation;
+        self.next_generation = self.next_generation.wrapping_add(1);
+        self.started += 1;
+
+        let mut voice = Voice {
+            generation,
+            sound: sound.clone(),
+            params,
+            oneshot,
+            started: self.started,
+            position: 0,
+            step: 0,
+            left: 0,
+            right: 0,
+        };
+
+        voice.update(self.sample_rate, self.master_volume);
+        self.voices[slot] = Some(voice);
+
+        Some(VoiceId {
+            slot: slot as u32,
+            generation,
+        })
+    }
+
+    fn voice_mut(&mut self, id: VoiceId) -> Option<&mut Voice> {
+        self.voices
+            .get_mut(id.slot as usize)?
+            .as_mut()
+            .filter(|voice| voice.generation == id.generation)
+    }
+
+    /// Change a voice. Returns false if it has ended.
+    pub fn set_params(&mut self, id: VoiceId, params: VoiceParams) -> bool {
+        let (rate, master) = (self.sample_rate, self.master_volume);
+
+        match self.voice_mut(id) {
+            Some(voice) => {
+                voice.params = params;
+                voice.update(rate, master);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// A voice's current parameters, or `None` if it has ended.
+    pub fn params(&self, id: VoiceId) -> Option<VoiceParams> {
+        self.voices
+            .get(id.slot as usize)?
+            .as_ref()
+            .filter(|voice| voice.generation == id.generation)
+            .map(|voice| voice.params)
+    }
+
+    pub fn set_volume(&mut self, id: VoiceId, volume: f32) -> bool {
+        self.params(id).map_or(false, |params| self.set_params(id, VoiceParams { volume, ..params }))
+    }
+
+    pub fn set_pan(&mut self, id: VoiceId, pan: f32) -> bool {
+        self.params(id).map_or(false, |params| self.set_params(id, VoiceParams { pan, ..params }))
+    }
+
+    pub fn set_pitch(&mut self, id: VoiceId, pitch: f32) -> bool {
+        self.params(id).map_or(false, |params| self.set_params(id, VoiceParams { pitch, ..params }))
+    }
+
+    /// Turning looping off lets the voice play to the end and stop.
+    pub fn set_looping(&mut self, id: VoiceId, looping: bool) -> bool {
+        self.params(id).map_or(false, |params| self.set_params(id, VoiceParams { looping, ..params }))
+    }
+
+    pub fn is_playing(&self, id: VoiceId) -> bool {
+        self.params(id).is_some()
+    }
+
+    pub fn stop(&mut self, id: VoiceId) {
+        if self.voice_mut(id).is_some() {
+            self.voices[id.slot as usize] = None;
+        }
+    }
+
+    pub fn stop_all(&mut self) {
+        for voice in &mut self.voices {
+            *voice = None;
+        }
+    }
+
+    /// The number of voices playing.
+    pub fn active_voices(&self) -> usize {
+        self.voices.iter().filter(|v| v.is_some()).count()
+    }
+
+    /// Mix the next `output.len() / 2` stereo frames into `output`,
+    /// interleaved left first, and advance every voice.
+    pub fn mix(&mut self, output: &mut [i16]) {
+        self.accumulator.clear();
+        self.accumulator.resize(output.len() & !1, 0);
+
+        let interpolation = self.interpolation;
+
+        for slot in &mut self.voices {
+            let playing = match slot {
+                Some(voice) => voice.mix_into(&mut self.accumulator, interpolation),
+                None => continue,
+            };
+
+            if !playing {
+                *slot = None;
+            }
+        }
+
+        for (out, &mixed) in output.iter_mut().zip(&self.accumulator) {
+            *out = clamp(mixed) as i16;
+        }
+
+        if output.len() % 2 == 1 {
+            output[output.len() - 1] = 0;
+        }
+    }
+}
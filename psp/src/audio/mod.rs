@@ -0,0 +1,36 @@
+//! Audio output.
+//!
+//! `AudioChannel` reserves a hardware channel and checks the sample count
+//! rules for it. `AudioStream` keeps a channel fed from a closure, on a
+//! refill thread of its own, and `MixerStream` plays many voices at once on
+//! one channel through a `Mixer`. `sas` drives the hardware synthesizer, while
+//! `mp3` and `atrac` decode music on the Media Engine. `Microphone` captures
+//! from the headset microphone, and `wav` writes what it captures.
+//!
+//! Software decoders, each behind a cargo feature of the same name, cover
+//! the rest: `wav`, `vorbis` for Ogg Vorbis and `tracker` for MOD, XM and IT
+//! modules. They all implement `PcmDecoder`, so they can be decoded to a
+//! `Sound` for the mixer or streamed with a `PcmPlayer`.
+
+mod channel;
+mod decoder;
+mod microphone;
+mod mixer;
+mod player;
+mod stream;
+
+pub mod atrac;
+pub mod mp3;
+pub mod sas;
+#[cfg(feature = "tracker")]
+pub mod tracker;
+#[cfg(feature = "vorbis")]
+pub mod vorbis;
+pub mod wav;
+
+pub use channel::*;
+pub use decoder::*;
+pub use microphone::*;
+pub use mixer::*;
+pub use player::{Playback, PlaybackControl};
+pub use stream::*;
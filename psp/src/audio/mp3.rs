@@ -0,0 +1,355 @@
+//! MP3 playback through `sceMp3`.
+
+use super::player::{self, read_full, Decoder, Pending};
+use super::{AudioChannel, AudioStream, Playback, PlaybackControl};
+use crate::cache::DmaBuffer;
+use crate::error::SceReturn;
+use crate::io::{self, Read, Seek, SeekFrom};
+use crate::sys::{self, Module, Mp3Handle, SceMp3InitArg};
+use crate::{Error, SceResult};
+use core::ops::Range;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Room for several frames of stream data; `sceMp3` needs at least 8 KiB.
+const STREAM_BUF_SIZE: usize = 16 * 1024;
+
+/// Samples per channel in an MPEG-1 layer III frame.
+const FRAME_SAMPLES: usize = 1152;
+
+/// Room for two stereo frames; `sceMp3` needs at least 9216 bytes.
+const PCM_BUF_LEN: usize = FRAME_SAMPLES * 2 * 2;
+
+const ID3V2_HEADER_SIZE: u64 = 10;
+const ID3V1_SIZE: u64 = 128;
+
+/// The number of live handles, so the resources are freed with the last.
+static RESOURCE_USERS: AtomicUsize = AtomicUsize::new(0);
+
+fn acquire_resource() -> SceResult<()> {
+    crate::utility::load_module(Module::AvCodec)?;
+    crate::utility::load_module(Module::AvMp3)?;
+
+    if RESOURCE_USERS.fetch_add(1, Ordering::AcqRel) == 0 {
+        if let Err(e) = unsafe { sys::sceMp3InitResource() }.into_result() {
+            RESOURCE_USERS.fetch_sub(1, Ordering::AcqRel);
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+fn release_resource() {
+    if RESOURCE_USERS.fetch_sub(1, Ordering::AcqRel) == 1 {
+        unsafe {
+            sys::sceMp3TermResource();
+        }
+    }
+}
+
+/// The byte range of the MPEG audio in `reader`, leaving out ID3v2 tags at
+/// the start and an ID3v1 tag at the end.
+pub fn audio_range<R: Read + Seek>(reader: &mut R) -> io::Result<Range<u64>> {
+    let mut end = reader.seek(SeekFrom::End(0))?;
+    let mut start = 0;
+
+    // Editors sometimes prepend a new tag rather than rewrite the old one.
+    while end - start >= ID3V2_HEADER_SIZE {
+        let mut header = [0; ID3V2_HEADER_SIZE as usize];
+        reader.seek(SeekFrom::Start(start))?;
+        reader.read_exact(&mut header)?;
+
+        if &header[..3] != b"ID3" {
+            break;
+        }
+
+        // The size leaves out the header and footer, in 7 bits per byte.
+        let size = header[6..10]
+            .iter()
+            .fold(0, |size, &byte| size << 7 | (byte & 0x7f) as u64);
+
+        let footer = if header[5] & 0x10 != 0 {
+            ID3V2_HEADER_SIZE
+        } else {
+            0
+        };
+
+        start = core::cmp::min(start + ID3V2_HEADER_SIZE + size + footer, end);
+    }
+
+    if end - start >= ID3V1_SIZE {
+        let mut tag = [0; 3];
+        reader.seek(SeekFrom::Start(end - ID3V1_SIZE))?;
+        reader.read_exact(&mut tag)?;
+
+        if &tag == b"TAG" {
+            end -= ID3V1_SIZE;
+        }
+    }
+
+    Ok(start..end)
+}
+
+struct Mp3Decoder<R> {
+    reader: R,
+    handle: Mp3Handle,
+    // Referenced by the handle until it is released.
+    _stream_buf: DmaBuffer<u8>,
+    pcm_buf: DmaBuffer<i16>,
+    error: Option<io::Error>,
+}
+
+impl<R: Read + Seek> Mp3Decoder<R> {
+    /// Give `sceMp3` all the stream data it asks for.
+    fn feed(&mut self) -> io::Result<()> {
+        while unsafe { sys::sceMp3CheckStreamDataNeeded(self.handle) }.into_result()? > 0 {
+            let mut dst = ptr::null_mut();
+            let mut to_write = 0;
+            let mut src_pos = 0;
+
+            unsafe {
+                sys::sceMp3GetInfoToAddStreamData(
+                    self.handle,
+                    &mut dst,
+                    &mut to_write,
+                    &mut src_pos,
+                )
+            }
+            .into_result()?;
+
+            self.reader.seek(SeekFrom::Start(src_pos as u32 as u64))?;
+
+            let buf = unsafe { core::slice::from_raw_parts_mut(dst, to_write as usize) };
+            let n = read_full(&mut self.reader, buf)?;
+            crate::cache::writeback(&buf[..n]);
+
+            unsafe { sys::sceMp3NotifyAddStreamData(self.handle, n as i32) }.into_result()?;
+
+            // The stream end should stop the requests first, but a reader
+            // which shrank can't satisfy them.
+            if n == 0 {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn decode_frame(&mut self) -> io::Result<Option<Pending>> {
+        self.feed()?;
+
+        let mut out = ptr::null_mut();
+
+        match unsafe { sys::sceMp3Decode(self.handle, &mut out) }.into_result() {
+            Ok(0) | Err(Error::SCE_MP3_ERROR_END) => Ok(None),
+            Ok(bytes) => {
+                self.pcm_buf.sync_for_cpu();
+                Ok(Some(Pending::new(out, bytes as usize / 2)))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl<R: Read + Seek> Decoder for Mp3Decoder<R> {
+    fn decode(&mut self) -> Option<Pending> {
+        match self.decode_frame() {
+            Ok(frame) => frame,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    fn rewind(&mut self) -> bool {
+        match unsafe { sys::sceMp3ResetPlayPosition(self.handle) }.into_result() {
+            Ok(_) => true,
+            Err(e) => {
+                self.error = Some(e.into());
+                false
+            }
+        }
+    }
+}
+
+impl<R> Drop for Mp3Decoder<R> {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceMp3ReleaseMp3Handle(self.handle);
+        }
+
+        release_resource();
+    }
+}
+
+/// Streams an MP3 from a reader, such as a `File` or a `Cursor` over memory,
+/// decoding it on the Media Engine.
+///
+/// Output is always 16-bit stereo at `sample_rate()`, whatever the channel
+/// count of the source. Call `fill` to decode into a buffer of any size, or
+/// `into_stream` to play it on an audio channel of its own.
+///
+/// ```no_run
+/// use psp::audio::mp3::Mp3Player;
+/// use psp::audio::Playback;
+/// use psp::fs::File;
+///
+/// let file = File::open("ms0:/MUSIC/theme.mp3").unwrap();
+/// let mut player = Mp3Player::new(file).unwrap();
+/// player.set_loop_count(None).unwrap();
+///
+/// let control = player.control();
+/// let stream = player.into_stream().unwrap();
+///
+/// // Later, from the game loop.
+/// control.pause();
+/// ```
+pub struct Mp3Player<R> {
+    decoder: Mp3Decoder<R>,
+    pending: Pending,
+    control: PlaybackControl,
+    sample_rate: u32,
+    channels: u32,
+}
+
+// The pending samples point into the player's own PCM buffer.
+unsafe impl<R: Send> Send for Mp3Player<R> {}
+
+impl<R: Read + Seek> Mp3Player<R> {
+    /// Load the MP3 modules, and start decoding `reader` from its first
+    /// frame, after any ID3 tags.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let range = audio_range(&mut reader)?;
+
+        acquire_resource()?;
+
+        let mut stream_buf = DmaBuffer::from_elem(0u8, STREAM_BUF_SIZE);
+        let mut pcm_buf = DmaBuffer::from_elem(0i16, PCM_BUF_LEN);
+
+        let mut args = SceMp3InitArg {
+            mp3_stream_start: range.start as u32,
+            unk1: 0,
+            mp3_stream_end: range.end as u32,
+            unk2: 0,
+            mp3_buf: stream_buf.as_device_ptr(),
+            mp3_buf_size: STREAM_BUF_SIZE as i32,
+            pcm_buf: pcm_buf.as_device_ptr(),
+            pcm_buf_size: (PCM_BUF_LEN * 2) as i32,
+        };
+
+        let handle = match unsafe { sys::sceMp3ReserveMp3Handle(&mut args) }.into_result() {
+            Ok(handle) => Mp3Handle(handle),
+            Err(e) => {
+                release_resource();
+                return Err(e.into());
+            }
+        };
+
+        // From here the decoder releases the handle and resources.
+        let mut decoder = Mp3Decoder {
+            reader,
+            handle,
+            _stream_buf: stream_buf,
+            pcm_buf,
+            error: None,
+        };
+
+        decoder.feed()?;
+
+        unsafe { sys::sceMp3Init(handle) }.into_result()?;
+        unsafe { sys::sceMp3SetLoopNum(handle, 0) }.into_result()?;
+
+        let sample_rate = unsafe { sys::sceMp3GetSamplingRate(handle) }.into_result()? as u32;
+        let channels = unsafe { sys::sceMp3GetMp3ChannelNum(handle) }.into_result()? as u32;
+
+        Ok(Self {
+            decoder,
+            pending: Pending::EMPTY,
+            control: PlaybackControl::new(),
+            sample_rate,
+            channels,
+        })
+    }
+
+    /// Decode into `out`, which holds interleaved stereo samples, returning
+    /// how many were written. The rest of `out` is silence, while paused or
+    /// once the end is reached.
+    pub fn fill(&mut self, out: &mut [i16]) -> usize {
+        player::fill(out, &mut self.decoder, &mut self.pending, &self.control)
+    }
+
+    /// Play on a channel of its own, at the MP3's sample rate.
+    ///
+    /// Get a `control` first to pause or restart it while it plays.
+    pub fn into_stream(mut self) -> SceResult<AudioStream>
+    where
+        R: Send + 'static,
+    {
+        let channel = AudioChannel::reserve_for_rate(self.sample_rate, FRAME_SAMPLES)?;
+
+        AudioStream::start(channel, move |buffer| {
+            self.fill(buffer);
+        })
+    }
+
+    /// Set how many more times to play after the first, or `None` to loop
+    /// forever.
+    pub fn set_loop_count(&mut self, count: Option<u32>) -> SceResult<()> {
+        let count = count.map(|n| n as i32).unwrap_or(-1);
+
+        unsafe { sys::sceMp3SetLoopNum(self.decoder.handle, count) }
+            .into_result()
+            .map(drop)
+    }
+
+    /// How many loops are left, or `None` when looping forever.
+    pub fn loop_count(&self) -> SceResult<Option<u32>> {
+        let count = unsafe { sys::sceMp3GetLoopNum(self.decoder.handle) };
+
+        if count == -1 {
+            Ok(None)
+        } else {
+            count.into_result().map(|n| Some(n as u32))
+        }
+    }
+
+    /// The output sample rate, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The number of channels in the source, 1 or 2.
+    pub fn channels(&self) -> u32 {
+        self.channels
+    }
+
+    /// The bitrate of the current frame, in kbit/s.
+    pub fn bitrate(&self) -> SceResult<u32> {
+        unsafe { sys::sceMp3GetBitRate(self.decoder.handle) }
+            .into_result()
+            .map(|n| n as u32)
+    }
+
+    /// The number of samples per channel decoded so far.
+    pub fn decoded_samples(&self) -> SceResult<u32> {
+        unsafe { sys::sceMp3GetSumDecodedSample(self.decoder.handle) }
+            .into_result()
+            .map(|n| n as u32)
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.decoder.reader
+    }
+}
+
+impl<R: Read + Seek> Playback for Mp3Player<R> {
+    fn playback_control(&self) -> &PlaybackControl {
+        &self.control
+    }
+
+    fn take_error(&mut self) -> Option<io::Error> {
+        self.decoder.error.take()
+    }
+}
@@ -0,0 +1,205 @@
+use crate::io::{self, Read};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Debug, Default)]
+struct ControlState {
+    paused: AtomicBool,
+    restart: AtomicBool,
+    finished: AtomicBool,
+}
+
+/// Controls a player from another thread, e.g. while it runs in an
+/// `AudioStream`. Get one with the player's `control` method; clones control
+/// the same player.
+#[derive(Debug, Clone, Default)]
+pub struct PlaybackControl(Arc<ControlState>);
+
+impl PlaybackControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Output silence, without advancing, until `resume`.
+    pub fn pause(&self) {
+        self.0.paused.store(true, Ordering::Release);
+    }
+
+    pub fn resume(&self) {
+        self.0.paused.store(false, Ordering::Release);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.paused.load(Ordering::Acquire)
+    }
+
+    /// Go back to the start, before the next buffer is filled. This also
+    /// restarts a finished player.
+    pub fn restart(&self) {
+        self.0.restart.store(true, Ordering::Release);
+    }
+
+    /// Whether the player has reached the end, after any loops.
+    pub fn is_finished(&self) -> bool {
+        self.0.finished.load(Ordering::Acquire)
+    }
+
+    /// Take a pending restart request.
+    pub(crate) fn take_restart(&self) -> bool {
+        self.0.restart.swap(false, Ordering::AcqRel)
+    }
+
+    pub(crate) fn set_finished(&self, finished: bool) {
+        self.0.finished.store(finished, Ordering::Release);
+    }
+}
+
+/// The controls every player has, through its `PlaybackControl`.
+///
+/// `Mp3Player`, `AtracPlayer` and `PcmPlayer` all implement this, as does
+/// `video::VideoPlayer`.
+pub trait Playback {
+    /// The control shared with the handles `control` returns.
+    fn playback_control(&self) -> &PlaybackControl;
+
+    /// A handle to pause, resume or restart the player from another thread,
+    /// which keeps working after the player moves into an `AudioStream`.
+    fn control(&self) -> PlaybackControl {
+        self.playback_control().clone()
+    }
+
+    /// Output silence, without advancing, until `resume`.
+    fn pause(&self) {
+        self.playback_control().pause();
+    }
+
+    fn resume(&self) {
+        self.playback_control().resume();
+    }
+
+    fn is_paused(&self) -> bool {
+        self.playback_control().is_paused()
+    }
+
+    /// Go back to the start, before the next buffer is filled or frame is
+    /// shown. This also restarts a finished player.
+    fn restart(&self) {
+        self.playback_control().restart();
+    }
+
+    /// Whether the end has been reached, after any loops.
+    fn is_finished(&self) -> bool {
+        self.playback_control().is_finished()
+    }
+
+    /// Take the error which stopped playback early, if any. Players which
+    /// return their errors straight away never have one.
+    fn take_error(&mut self) -> Option<io::Error> {
+        None
+    }
+}
+
+/// Decoded samples waiting to be copied out, in a decoder's own buffer.
+pub(crate) struct Pending {
+    pub ptr: *const i16,
+    pub len: usize,
+    pub pos: usize,
+}
+
+impl Pending {
+    pub const EMPTY: Pending = Pending {
+        ptr: core::ptr::null(),
+        len: 0,
+        pos: 0,
+    };
+
+    pub fn new(ptr: *const i16, len: usize) -> Self {
+        Self { ptr, len, pos: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.len
+    }
+
+    /// Copy as much as fits into `out`, returning how many samples were
+    /// copied.
+    pub fn copy_to(&mut self, out: &mut [i16]) -> usize {
+        let n = core::cmp::min(out.len(), self.len - self.pos);
+
+        if n > 0 {
+            let src = unsafe { core::slice::from_raw_parts(self.ptr.add(self.pos), n) };
+            out[..n].copy_from_slice(src);
+            self.pos += n;
+        }
+
+        n
+    }
+}
+
+/// A hardware decoder producing stereo frames into a buffer of its own.
+pub(crate) trait Decoder {
+    /// Decode the next frame, or return `None` at the end. The samples stay
+    /// valid until the next call.
+    fn decode(&mut self) -> Option<Pending>;
+
+    /// Go back to the start, returning whether that worked.
+    fn rewind(&mut self) -> bool;
+}
+
+/// Fill `out` from `decoder`, following `control`'s pause and restart
+/// requests. Returns the number of samples written before the end; the rest
+/// of `out` is silence.
+pub(crate) fn fill<D: Decoder>(
+    out: &mut [i16],
+    decoder: &mut D,
+    pending: &mut Pending,
+    control: &PlaybackControl,
+) -> usize {
+    if control.take_restart() {
+        *pending = Pending::EMPTY;
+
+        if decoder.rewind() {
+            control.set_finished(false);
+        }
+    }
+
+    let mut written = 0;
+
+    if !control.is_paused() {
+        while written < out.len() && !control.is_finished() {
+            if pending.is_empty() {
+                match decoder.decode() {
+                    Some(frame) => *pending = frame,
+                    None => {
+                        *pending = Pending::EMPTY;
+                        control.set_finished(true);
+                        break;
+                    }
+                }
+            }
+
+            written += pending.copy_to(&mut out[written..]);
+        }
+    }
+
+    for sample in &mut out[written..] {
+        *sample = 0;
+    }
+
+    written
+}
+
+/// Read until `buf` is full or the stream ends, returning how many bytes were
+/// read.
+pub(crate) fn read_full<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+
+    Ok(filled)
+}
@@ -0,0 +1,521 @@
+//! The hardware sound synthesizer, `sceSasCore`.
+//!
+//! Sas mixes up to 32 voices of VAG ADPCM data with ADSR envelopes and a
+//! reverb effect, at 44.1 kHz. `cargo-psp`'s `wav2vag` converts WAV files to
+//! VAG.
+
+use crate::cache::DmaBuffer;
+use crate::error::SceReturn;
+use crate::sys::{
+    self, Module, SasAdsrCurveMode, SasAdsrFlags, SasCore, SasEffectType, SasLoopMode, SasOutputMode,
+    SAS_ENVELOPE_HEIGHT_MAX, SAS_ENVELOPE_RATE_MAX, SAS_PITCH_BASE, SAS_PITCH_MAX, SAS_PITCH_MIN, SAS_VOLUME_MAX,
+    SAS_VOICES_MAX,
+};
+use crate::{io, Error, SceResult};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::time::Duration;
+
+/// The Sas output sample rate.
+pub const SAS_SAMPLE_RATE: u32 = 44100;
+
+const VAG_MAGIC: &[u8; 4] = b"VAGp";
+const VAG_HEADER_SIZE: usize = 48;
+
+/// A mono sound in VAG ADPCM format, ready for Sas to play.
+pub struct Vag {
+    data: DmaBuffer<u8>,
+    sample_rate: u32,
+    name: [u8; 16],
+}
+
+// The data is never changed after it is written back.
+unsafe impl Send for Vag {}
+unsafe impl Sync for Vag {}
+
+impl Vag {
+    /// Parse a VAG file.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < VAG_HEADER_SIZE || &bytes[..4] != VAG_MAGIC {
+            return Err(io::Error::InvalidInput);
+        }
+
+        let be_u32 = |offset: usize| {
+            let mut word = [0; 4];
+            word.copy_from_slice(&bytes[offset..offset + 4]);
+            u32::from_be_bytes(word)
+        };
+
+        let size = be_u32(12) as usize;
+        let sample_rate = be_u32(16);
+
+        // ADPCM comes in 16-byte blocks.
+        let size = size & !15;
+        let data = bytes
+            .get(VAG_HEADER_SIZE..VAG_HEADER_SIZE + size)
+            .ok_or(io::Error::UnexpectedEof)?;
+
+        if data.is_empty() || sample_rate == 0 {
+            return Err(io::Error::InvalidInput);
+        }
+
+        let mut buffer = DmaBuffer::from_elem(0, data.len());
+        buffer.copy_from_slice(data);
+        buffer.as_device_ptr();
+
+        let mut name = [0; 16];
+        name.copy_from_slice(&bytes[32..48]);
+
+        Ok(Self {
+            data: buffer,
+            sample_rate,
+            name,
+        })
+    }
+
+    /// Load a VAG file.
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::from_bytes(&crate::fs::read(path)?)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The name stored in the header.
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(self.name.len());
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+
+    /// The length, in samples. Each 16-byte block holds 28.
+    pub fn samples(&self) -> usize {
+        self.data.len() / 16 * 28
+    }
+
+    /// The `__sceSasSetPitch` value which plays this sound at its own rate.
+    pub fn pitch(&self) -> i32 {
+        sample_rate_to_pitch(self.sample_rate)
+    }
+}
+
+/// Convert a sample rate to a Sas pitch, clamped to the supported range.
+pub fn sample_rate_to_pitch(sample_rate: u32) -> i32 {
+    let pitch = sample_rate as u64 * SAS_PITCH_BASE as u64 / SAS_SAMPLE_RATE as u64;
+
+    if pitch < SAS_PITCH_MIN as u64 {
+        SAS_PITCH_MIN
+    } else if pitch > SAS_PITCH_MAX as u64 {
+        SAS_PITCH_MAX
+    } else {
+        pitch as i32
+    }
+}
+
+/// One phase of an envelope.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EnvelopePhase {
+    pub curve: SasAdsrCurveMode,
+    /// How fast the envelope moves, up to `SAS_ENVELOPE_RATE_MAX`.
+    pub rate: i32,
+}
+
+/// A voice's volume envelope.
+///
+/// On key on, the envelope rises from zero during the attack, falls to the
+/// sustain level during the decay, and then follows the sustain phase until
+/// key off. The release phase then takes it back to zero.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Adsr {
+    pub attack: EnvelopePhase,
+    pub decay: EnvelopePhase,
+    pub sustain: EnvelopePhase,
+    pub release: EnvelopePhase,
+    /// The level the decay ends at, up to `SAS_ENVELOPE_HEIGHT_MAX`.
+    pub sustain_level: i32,
+}
+
+/// The linear rate which covers the full envelope height in `time`.
+fn linear_rate(time: Duration) -> i32 {
+    let samples = crate::time::as_micros_u64(time) * SAS_SAMPLE_RATE as u64 / 1_000_000;
+
+    if samples == 0 {
+        SAS_ENVELOPE_RATE_MAX
+    } else {
+        core::cmp::max(SAS_ENVELOPE_HEIGHT_MAX as u64 / samples, 1) as i32
+    }
+}
+
+impl Adsr {
+    /// A linear envelope which takes `attack` to reach full volume, `decay`
+    /// to fall to `sustain_level` (from 0 to 1), holds while the key is on,
+    /// and takes `release` to fall silent.
+    pub fn from_times(attack: Duration, decay: Duration, sustain_level: f32, release: Duration) -> Self {
+        let sustain_level = if sustain_level < 0.0 {
+            0.0
+        } else if sustain_level > 1.0 {
+            1.0
+        } else {
+            sustain_level
+        };
+
+        Self {
+            attack: EnvelopePhase {
+                curve: SasAdsrCurveMode::LinearIncrease,
+                rate: linear_rate(attack),
+            },
+            decay: EnvelopePhase {
+                curve: SasAdsrCurveMode::LinearDecrease,
+                rate: linear_rate(decay),
+            },
+            sustain: EnvelopePhase {
+                curve: SasAdsrCurveMode::LinearDecrease,
+                rate: 0,
+            },
+            release: EnvelopePhase {
+                curve: SasAdsrCurveMode::LinearDecrease,
+                rate: linear_rate(release),
+            },
+            sustain_level: (sustain_level * SAS_ENVELOPE_HEIGHT_MAX as f32) as i32,
+        }
+    }
+}
+
+impl Default for Adsr {
+    /// Full volume immediately, cut off 10ms after key off.
+    fn default() -> Self {
+        Self::from_times(Duration::from_secs(0), Duration::from_secs(0), 1.0, Duration::from_millis(10))
+    }
+}
+
+/// The reverb effect, applied to what each voice sends to it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Effect {
+    pub effect_type: SasEffectType,
+    /// For `Echo` and `Delay`, from 0 to 128.
+    pub delay: i32,
+    /// For `Echo` and `Delay`, from 0 to 128.
+    pub feedback: i32,
+    /// The effect output volume, up to `SAS_VOLUME_MAX`.
+    pub left_volume: i32,
+    pub right_volume: i32,
+    /// Whether the voices' dry signal is output.
+    pub dry: bool,
+    /// Whether the effect's output is output.
+    pub wet: bool,
+}
+
+impl Default for Effect {
+    fn default() -> Self {
+        Self {
+            effect_type: SasEffectType::Off,
+            delay: 0,
+            feedback: 0,
+            left_volume: SAS_VOLUME_MAX,
+            right_volume: SAS_VOLUME_MAX,
+            dry: true,
+            wet: false,
+        }
+    }
+}
+
+/// A voice's dry and effect send volumes, each up to `SAS_VOLUME_MAX`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VoiceVolume {
+    pub left: i32,
+    pub right: i32,
+    pub effect_left: i32,
+    pub effect_right: i32,
+}
+
+impl Default for VoiceVolume {
+    fn default() -> Self {
+        Self {
+            left: SAS_VOLUME_MAX,
+            right: SAS_VOLUME_MAX,
+            effect_left: 0,
+            effect_right: 0,
+        }
+    }
+}
+
+/// The Sas synthesizer. Only one can exist at a time.
+///
+/// Call `mix` once per grain and output the result, e.g. on an
+/// `AudioChannel` reserved with the same sample count.
+///
+/// ```no_run
+/// use psp::audio::sas::{Sas, Vag};
+/// use psp::audio::AudioChannel;
+/// use psp::sys::AudioFormat;
+/// use alloc::sync::Arc;
+///
+/// let mut sas = Sas::new(256, 8).unwrap();
+/// let mut channel = AudioChannel::reserve(AudioFormat::Stereo, 256).unwrap();
+///
+/// let jump = Arc::new(Vag::load("ms0:/PSP/GAME/MYGAME/jump.vag").unwrap());
+/// sas.set_voice(0, jump, false).unwrap();
+/// sas.key_on(0).unwrap();
+///
+/// let mut buffer = [0i16; 512];
+///
+/// loop {
+///     sas.mix(&mut buffer).unwrap();
+///     channel.output_blocking(&buffer).unwrap();
+/// }
+/// ```
+pub struct Sas {
+    core: Box<SasCore>,
+    grain: usize,
+    /// The data each voice plays, kept alive while Sas may read it.
+    voices: Vec<Option<Arc<Vag>>>,
+    volumes: Vec<VoiceVolume>,
+}
+
+impl Sas {
+    /// Load the Sas module and start it, mixing `grain` stereo samples per
+    /// call, a multiple of 32 from 64 to 2048, with up to 32 voices.
+    pub fn new(grain: usize, max_voices: usize) -> SceResult<Self> {
+        if grain < 64 || grain > 2048 || grain % 32 != 0 {
+            return Err(Error::SCE_SAS_ERROR_INVALID_GRAIN);
+        }
+
+        if max_voices == 0 || max_voices > SAS_VOICES_MAX as usize {
+            return Err(Error::SCE_SAS_ERROR_INVALID_MAX_VOICES);
+        }
+
+        crate::utility::load_module(Module::AvSascore)?;
+
+        let mut core = Box::new(SasCore { data: [0; 212] });
+
+        unsafe {
+            sys::__sceSasInit(
+                &mut *core,
+                grain as i32,
+                max_voices as i32,
+                SasOutputMode::Stereo,
+                SAS_SAMPLE_RATE as i32,
+            )
+        }
+        .into_result()?;
+
+        let mut voices = Vec::with_capacity(max_voices);
+        voices.resize_with(max_voices, || None);
+
+        Ok(Self {
+            core,
+            grain,
+            voices,
+            volumes: alloc::vec![VoiceVolume::default(); max_voices],
+        })
+    }
+
+    /// The number of stereo samples each `mix` produces.
+    pub fn grain(&self) -> usize {
+        self.grain
+    }
+
+    pub fn max_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    fn check_voice(&self, voice: usize) -> SceResult<i32> {
+        if voice < self.voices.len() {
+            Ok(voice as i32)
+        } else {
+            Err(Error::SCE_SAS_ERROR_INVALID_VOICE)
+        }
+    }
+
+    /// Mix the next grain into `out`, which must hold `grain() * 2` samples.
+    pub fn mix(&mut self, out: &mut [i16]) -> SceResult<()> {
+        if out.len() != self.grain * 2 {
+            return Err(Error::SCE_SAS_ERROR_INVALID_GRAIN);
+        }
+
+        unsafe { sys::__sceSasCore(&mut *self.core, out.as_mut_ptr() as *mut c_void) }
+            .into_result()
+            .map(drop)
+    }
+
+    /// Mix the next grain on top of the samples already in `out`, scaling
+    /// them by `left_volume` and `right_volume` first.
+    pub fn mix_into(&mut self, out: &mut [i16], left_volume: i32, right_volume: i32) -> SceResult<()> {
+        if out.len() != self.grain * 2 {
+            return Err(Error::SCE_SAS_ERROR_INVALID_GRAIN);
+        }
+
+        let buf = out.as_mut_ptr() as *mut c_void;
+
+        unsafe { sys::__sceSasCoreWithMix(&mut *self.core, buf, left_volume, right_volume) }
+            .into_result()
+            .map(drop)
+    }
+
+    /// Set `voice` to play `vag` at its own sample rate, from the start,
+    /// once key on. Fails if the voice is playing.
+    pub fn set_voice(&mut self, voice: usize, vag: Arc<Vag>, looping: bool) -> SceResult<()> {
+        let index = self.check_voice(voice)?;
+
+        if !self.is_ended(voice) {
+            return Err(Error::SCE_SAS_ERROR_BUSY);
+        }
+
+        let loop_mode = if looping { SasLoopMode::On } else { SasLoopMode::Off };
+        let data = vag.data.as_ptr() as *const c_void;
+
+        unsafe { sys::__sceSasSetVoice(&mut *self.core, index, data, vag.data.len() as i32, loop_mode) }
+            .into_result()?;
+
+        self.set_pitch(voice, vag.pitch())?;
+        self.voices[voice] = Some(vag);
+
+        Ok(())
+    }
+
+    /// Set `voice` to play noise, at a frequency up to `SAS_NOISE_FREQ_MAX`.
+    /// Fails if the voice is playing.
+    pub fn set_noise(&mut self, voice: usize, frequency: i32) -> SceResult<()> {
+        let index = self.check_voice(voice)?;
+
+        if !self.is_ended(voice) {
+            return Err(Error::SCE_SAS_ERROR_BUSY);
+        }
+
+        unsafe { sys::__sceSasSetNoise(&mut *self.core, index, frequency) }.into_result()?;
+        self.voices[voice] = None;
+
+        Ok(())
+    }
+
+    /// Start `voice`, from the start of the attack.
+    pub fn key_on(&mut self, voice: usize) -> SceResult<()> {
+        let index = self.check_voice(voice)?;
+        unsafe { sys::__sceSasSetKeyOn(&mut *self.core, index) }.into_result().map(drop)
+    }
+
+    /// Move `voice` to its release phase. It ends once the envelope reaches
+    /// zero.
+    pub fn key_off(&mut self, voice: usize) -> SceResult<()> {
+        let index = self.check_voice(voice)?;
+        unsafe { sys::__sceSasSetKeyOff(&mut *self.core, index) }.into_result().map(drop)
+    }
+
+    /// Whether `voice` has finished playing, or never started.
+    pub fn is_ended(&self, voice: usize) -> bool {
+        let core = &*self.core as *const SasCore as *mut SasCore;
+        let ended = unsafe { sys::__sceSasGetEndFlag(core) };
+
+        voice >= 32 || ended < 0 || ended & (1 << voice) != 0
+    }
+
+    /// The current envelope height of `voice`, up to
+    /// `SAS_ENVELOPE_HEIGHT_MAX`.
+    pub fn envelope_height(&self, voice: usize) -> SceResult<i32> {
+        let index = self.check_voice(voice)?;
+        let core = &*self.core as *const SasCore as *mut SasCore;
+
+        unsafe { sys::__sceSasGetEnvelopeHeight(core, index) }.into_result()
+    }
+
+    /// Set the playback rate, where `SAS_PITCH_BASE` plays at 44.1 kHz.
+    pub fn set_pitch(&mut self, voice: usize, pitch: i32) -> SceResult<()> {
+        let index = self.check_voice(voice)?;
+        unsafe { sys::__sceSasSetPitch(&mut *self.core, index, pitch) }.into_result().map(drop)
+    }
+
+    /// Play `voice` as if its data were recorded at `sample_rate`.
+    pub fn set_sample_rate(&mut self, voice: usize, sample_rate: u32) -> SceResult<()> {
+        self.set_pitch(voice, sample_rate_to_pitch(sample_rate))
+    }
+
+    /// Set the dry volume of `voice`, keeping its effect send.
+    pub fn set_volume(&mut self, voice: usize, left: i32, right: i32) -> SceResult<()> {
+        self.check_voice(voice)?;
+
+        let volume = VoiceVolume {
+            left,
+            right,
+            ..self.volumes[voice]
+        };
+
+        self.set_voice_volume(voice, volume)
+    }
+
+    /// Set the dry and effect send volumes of `voice`.
+    pub fn set_voice_volume(&mut self, voice: usize, volume: VoiceVolume) -> SceResult<()> {
+        let index = self.check_voice(voice)?;
+
+        unsafe {
+            sys::__sceSasSetVolume(
+                &mut *self.core,
+                index,
+                volume.left,
+                volume.right,
+                volume.effect_left,
+                volume.effect_right,
+            )
+        }
+        .into_result()?;
+
+        self.volumes[voice] = volume;
+        Ok(())
+    }
+
+    pub fn set_adsr(&mut self, voice: usize, adsr: &Adsr) -> SceResult<()> {
+        let index = self.check_voice(voice)?;
+        let core = &mut *self.core;
+
+        unsafe {
+            sys::__sceSasSetADSRmode(
+                core,
+                index,
+                SasAdsrFlags::all(),
+                adsr.attack.curve,
+                adsr.decay.curve,
+                adsr.sustain.curve,
+                adsr.release.curve,
+            )
+            .into_result()?;
+
+            sys::__sceSasSetADSR(
+                core,
+                index,
+                SasAdsrFlags::all(),
+                adsr.attack.rate,
+                adsr.decay.rate,
+                adsr.sustain.rate,
+                adsr.release.rate,
+            )
+            .into_result()?;
+
+            sys::__sceSasSetSL(core, index, adsr.sustain_level).into_result()?;
+        }
+
+        Ok(())
+    }
+
+    /// Pause or resume `voice`.
+    pub fn set_paused(&mut self, voice: usize, paused: bool) -> SceResult<()> {
+        self.check_voice(voice)?;
+
+        unsafe { sys::__sceSasSetPause(&mut *self.core, 1 << voice, paused as i32) }
+            .into_result()
+            .map(drop)
+    }
+
+    pub fn set_effect(&mut self, effect: &Effect) -> SceResult<()> {
+        let core = &mut *self.core;
+
+        unsafe {
+            sys::__sceSasRevType(core, effect.effect_type).into_result()?;
+            sys::__sceSasRevParam(core, effect.delay, effect.feedback).into_result()?;
+            sys::__sceSasRevEVOL(core, effect.left_volume, effect.right_volume).into_result()?;
+            sys::__sceSasRevVON(core, effect.dry as i32, effect.wet as i32).into_result()?;
+        }
+
+        Ok(())
+    }
+}
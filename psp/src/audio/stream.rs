@@ -0,0 +1,327 @@
+use super::{AudioChannel, Mixer};
+use crate::error::SceReturn;
+use crate::sys::{self, SceUid, ThreadAttributes};
+use crate::{Error, SceResult};
+use alloc::boxed::Box;
+use alloc::vec;
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::mem::{self, ManuallyDrop};
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// Above the callback thread's 17 and the main thread's 32, so a busy game
+/// loop doesn't starve the audio.
+const PRIORITY: i32 = 16;
+const STACK_SIZE: i32 = 0x10000;
+
+type Fill = dyn FnMut(&mut [i16]) + Send;
+
+/// State shared with the refill thread. Only the atomics are touched from
+/// outside it while it runs.
+struct State {
+    channel: AudioChannel,
+    fill: Box<Fill>,
+    stop: AtomicBool,
+    finished: AtomicBool,
+    /// The left volume in the high half, and the right in the low.
+    volume: AtomicU32,
+    result: SceResult<()>,
+}
+
+fn pack_volume(left: u32, right: u32) -> u32 {
+    left << 16 | right
+}
+
+unsafe extern "C" fn refill_thread(_args: usize, argp: *mut c_void) -> i32 {
+    let state = *(argp as *const *mut State);
+    let channel = &mut (*state).channel;
+    let fill = &mut (*state).fill;
+
+    let len = channel.buffer_len();
+    let mut buffers = [vec![0i16; len], vec![0i16; len]];
+    let mut current = 0;
+
+    while !(*state).stop.load(Ordering::Acquire) {
+        let buffer = &mut buffers[current];
+        fill(buffer);
+
+        let volume = (*state).volume.load(Ordering::Relaxed);
+        channel.set_volume(volume >> 16, volume & 0xffff);
+
+        // This returns once the previous buffer has finished, so the other
+        // buffer is free to fill while this one plays.
+        if let Err(e) = channel.output_blocking(buffer) {
+            (*state).result = Err(e);
+            break;
+        }
+
+        current ^= 1;
+    }
+
+    // The hardware may still be reading the buffers.
+    channel.drain();
+    (*state).finished.store(true, Ordering::Release);
+
+    0
+}
+
+/// Plays audio produced by a closure, on a thread of its own.
+///
+/// The closure is called to fill each buffer of `AudioChannel::buffer_len()`
+/// samples just before it is needed, while the previous buffer plays. It runs
+/// on the refill thread, so it may allocate and lock, but it must finish
+/// within one buffer's playing time to avoid gaps.
+///
+/// Dropping the stream stops it, after the buffers already queued have
+/// played.
+///
+/// ```no_run
+/// use psp::audio::{AudioChannel, AudioStream};
+/// use psp::sys::AudioFormat;
+///
+/// let channel = AudioChannel::reserve(AudioFormat::Stereo, 1024).unwrap();
+/// let mut phase = 0u32;
+///
+/// let stream = AudioStream::start(channel, move |buffer| {
+///     // A 441 Hz square wave.
+///     for frame in buffer.chunks_mut(2) {
+///         let value = if phase < 50 { 4000 } else { -4000 };
+///         frame[0] = value;
+///         frame[1] = value;
+///         phase = (phase + 1) % 100;
+///     }
+/// })
+/// .unwrap();
+/// ```
+pub struct AudioStream {
+    thread: SceUid,
+    state: *mut State,
+}
+
+impl AudioStream {
+    /// Start calling `fill` and playing what it produces on `channel`.
+    pub fn start<F>(channel: AudioChannel, fill: F) -> SceResult<Self>
+    where
+        F: FnMut(&mut [i16]) + Send + 'static,
+    {
+        let (left, right) = channel.volume();
+
+        let state = Box::into_raw(Box::new(State {
+            channel,
+            fill: Box::new(fill),
+            stop: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            volume: AtomicU32::new(pack_volume(left, right)),
+            result: Ok(()),
+        }));
+
+        let thread = unsafe {
+            sys::sceKernelCreateThread(
+                &b"audio_refill\0"[0],
+                refill_thread,
+                PRIORITY,
+                STACK_SIZE,
+                ThreadAttributes::USER,
+                ptr::null_mut(),
+            )
+        }
+        .into_result();
+
+        let thread = match thread {
+            Ok(thread) => thread,
+            Err(e) => {
+                drop(unsafe { Box::from_raw(state) });
+                return Err(e);
+            }
+        };
+
+        // The kernel copies the pointer onto the new thread's stack.
+        let arg = state;
+        let result = unsafe {
+            sys::sceKernelStartThread(thread, mem::size_of::<*mut State>(), &arg as *const _ as *mut c_void)
+        };
+
+        if let Err(e) = result.into_result() {
+            unsafe {
+                sys::sceKernelDeleteThread(thread);
+                drop(Box::from_raw(state));
+            }
+
+            return Err(e);
+        }
+
+        Ok(Self { thread, state })
+    }
+
+    /// Set the left and right volumes, from 0 to `AUDIO_VOLUME_MAX`. They
+    /// apply from the next buffer.
+    pub fn set_volume(&self, left: u32, right: u32) {
+        let left = core::cmp::min(left, sys::AUDIO_VOLUME_MAX);
+        let right = core::cmp::min(right, sys::AUDIO_VOLUME_MAX);
+
+        unsafe { &(*self.state).volume }.store(pack_volume(left, right), Ordering::Relaxed);
+    }
+
+    /// Set `volume` split between left and right by `pan`, from -1 (left
+    /// only) to 1 (right only).
+    pub fn set_pan(&self, volume: u32, pan: f32) {
+        let (left, right) = super::channel::pan_volume(volume, pan);
+        self.set_volume(left, right);
+    }
+
+    /// Whether the refill thread stopped because output failed.
+    pub fn is_stopped(&self) -> bool {
+        unsafe { &(*self.state).finished }.load(Ordering::Acquire)
+    }
+
+    /// Stop the stream, returning the channel and any output error.
+    pub fn stop(mut self) -> (AudioChannel, SceResult<()>) {
+        let State { channel, result, .. } = *self.join();
+        mem::forget(self);
+
+        (channel, result)
+    }
+
+    fn join(&mut self) -> Box<State> {
+        unsafe {
+            (*self.state).stop.store(true, Ordering::Release);
+            sys::sceKernelWaitThreadEnd(self.thread, ptr::null_mut());
+            sys::sceKernelDeleteThread(self.thread);
+
+            Box::from_raw(self.state)
+        }
+    }
+}
+
+impl Drop for AudioStream {
+    fn drop(&mut self) {
+        drop(self.join());
+    }
+}
+
+/// A mixer and the lock which guards it.
+struct SharedMixer {
+    sema: SceUid,
+    mixer: UnsafeCell<Mixer>,
+}
+
+impl SharedMixer {
+    fn lock(&self) -> MixerGuard<'_> {
+        unsafe {
+            sys::sceKernelWaitSema(self.sema, 1, ptr::null_mut());
+        }
+
+        MixerGuard { shared: self }
+    }
+}
+
+/// Lets the refill closure, which must be `Send`, use the mixer.
+struct SharedPtr(*const SharedMixer);
+
+unsafe impl Send for SharedPtr {}
+
+/// A `Mixer` playing on its own channel, from the refill thread.
+///
+/// ```no_run
+/// use psp::audio::{AudioChannel, MixerStream, Sound, VoiceParams};
+/// use psp::sys::AudioFormat;
+///
+/// let channel = AudioChannel::reserve(AudioFormat::Stereo, 512).unwrap();
+/// let mixer = MixerStream::start(channel, 16).unwrap();
+///
+/// # let sound: Sound = unimplemented!();
+/// mixer.lock().play_oneshot(&sound, VoiceParams::default());
+/// ```
+pub struct MixerStream {
+    // Dropped first, so the thread has stopped before the lock is deleted.
+    stream: ManuallyDrop<AudioStream>,
+    shared: Box<SharedMixer>,
+}
+
+impl MixerStream {
+    /// Start mixing up to `max_voices` voices into `channel`, which must be
+    /// stereo.
+    pub fn start(channel: AudioChannel, max_voices: usize) -> SceResult<Self> {
+        if channel.channels() != 2 {
+            return Err(Error::SCE_AUDIO_ERROR_INVALID_FORMAT);
+        }
+
+        let sema = unsafe { sys::sceKernelCreateSema(&b"audio_mixer\0"[0], 0, 1, 1, ptr::null_mut()) }
+            .into_result()?;
+
+        let shared = Box::new(SharedMixer {
+            sema,
+            mixer: UnsafeCell::new(Mixer::new(channel.frequency(), max_voices)),
+        });
+
+        let ptr = SharedPtr(&*shared);
+
+        let stream = AudioStream::start(channel, move |buffer| {
+            let shared = unsafe { &*ptr.0 };
+            shared.lock().mix(buffer);
+        });
+
+        match stream {
+            Ok(stream) => Ok(Self {
+                stream: ManuallyDrop::new(stream),
+                shared,
+            }),
+            Err(e) => {
+                unsafe {
+                    sys::sceKernelDeleteSema(sema);
+                }
+
+                Err(e)
+            }
+        }
+    }
+
+    /// Lock the mixer, to start or change voices. Mixing waits while it is
+    /// locked, so keep the lock briefly.
+    pub fn lock(&self) -> MixerGuard<'_> {
+        self.shared.lock()
+    }
+
+    /// The stream, e.g. to change the channel's volume.
+    pub fn stream(&self) -> &AudioStream {
+        &self.stream
+    }
+}
+
+impl Drop for MixerStream {
+    fn drop(&mut self) {
+        unsafe {
+            ManuallyDrop::drop(&mut self.stream);
+            sys::sceKernelDeleteSema(self.shared.sema);
+        }
+    }
+}
+
+/// Access to a `MixerStream`'s mixer. Unlocks when dropped.
+pub struct MixerGuard<'a> {
+    shared: &'a SharedMixer,
+}
+
+impl Deref for MixerGuard<'_> {
+    type Target = Mixer;
+
+    fn deref(&self) -> &Mixer {
+        unsafe { &*self.shared.mixer.get() }
+    }
+}
+
+impl DerefMut for MixerGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Mixer {
+        unsafe { &mut *self.shared.mixer.get() }
+    }
+}
+
+impl Drop for MixerGuard<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceKernelSignalSema(self.shared.sema, 1);
+        }
+    }
+}
@@ -0,0 +1,566 @@
+//! Impulse Tracker modules, including its compressed samples.
+
+use super::{
+    effect, name, Bytes, Cell, Envelope, Instrument, LoopKind, Module, ModuleFormat, Pattern,
+    Sample, SampleLoop, VolumeCommand, NOTE_CUT, NOTE_FADE, NOTE_OFF,
+};
+use crate::io::{self, Error};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The speeds of the volume column's tone portamento.
+const TONE_PORTAMENTO_SPEEDS: [u8; 10] = [0, 1, 4, 8, 16, 32, 64, 96, 128, 255];
+
+fn convert_volume(volume: u8) -> VolumeCommand {
+    match volume {
+        0..=64 => VolumeCommand::Volume(volume),
+        65..=74 => VolumeCommand::FineVolumeUp(volume - 65),
+        75..=84 => VolumeCommand::FineVolumeDown(volume - 75),
+        85..=94 => VolumeCommand::VolumeUp(volume - 85),
+        95..=104 => VolumeCommand::VolumeDown(volume - 95),
+        105..=114 => VolumeCommand::PortamentoDown(volume - 105),
+        115..=124 => VolumeCommand::PortamentoUp(volume - 115),
+        128..=192 => VolumeCommand::Panning(volume - 128),
+        193..=202 => VolumeCommand::TonePortamento(TONE_PORTAMENTO_SPEEDS[(volume - 193) as usize]),
+        203..=212 => VolumeCommand::VibratoDepth(volume - 203),
+        _ => VolumeCommand::None,
+    }
+}
+
+/// Read a pattern of up to 64 channels, returning it with the number of
+/// channels it uses.
+fn read_pattern(bytes: &[u8], offset: usize) -> io::Result<(Pattern, usize)> {
+    // Patterns which aren't stored are 64 empty rows.
+    if offset == 0 {
+        return Ok((
+            Pattern {
+                rows: 64,
+                cells: vec![Cell::EMPTY; 64 * 64],
+            },
+            0,
+        ));
+    }
+
+    let mut data = Bytes::at(bytes, offset)?;
+    let len = data.u16()? as usize;
+    let rows = core::cmp::min(core::cmp::max(data.u16()? as usize, 1), 256);
+    data.skip(4)?;
+
+    let mut packed = Bytes::new(data.take(len)?);
+    let mut cells = vec![Cell::EMPTY; rows * 64];
+    let mut used = 0;
+
+    // What each channel had last, to repeat.
+    let mut masks = [0u8; 64];
+    let mut last = [Cell::EMPTY; 64];
+
+    let mut row = 0;
+
+    while row < rows {
+        let channel_byte = packed.u8()?;
+
+        if channel_byte == 0 {
+            row += 1;
+            continue;
+        }
+
+        let channel = (channel_byte - 1) as usize & 63;
+        used = core::cmp::max(used, channel + 1);
+
+        if channel_byte & 0x80 != 0 {
+            masks[channel] = packed.u8()?;
+        }
+
+        let mask = masks[channel];
+        let last = &mut last[channel];
+        let cell = &mut cells[row * 64 + channel];
+
+        if mask & 0x01 != 0 {
+            last.note = match packed.u8()? {
+                note @ 0..=119 => note,
+                255 => NOTE_OFF,
+                254 => NOTE_CUT,
+                _ => NOTE_FADE,
+            };
+        }
+
+        if mask & 0x02 != 0 {
+            last.instrument = packed.u8()?;
+        }
+
+        if mask & 0x04 != 0 {
+            last.volume = convert_volume(packed.u8()?);
+        }
+
+        if mask & 0x08 != 0 {
+            let command = packed.u8()?;
+            let param = packed.u8()?;
+
+            // Commands are numbered as ours, and `Z` is unsupported.
+            let (effect, param) = match command {
+                1..=25 => (command, param),
+                _ => (effect::NONE, 0),
+            };
+
+            last.effect = effect;
+            last.param = param;
+        }
+
+        if mask & 0x11 != 0 {
+            cell.note = last.note;
+        }
+
+        if mask & 0x22 != 0 {
+            cell.instrument = last.instrument;
+        }
+
+        if mask & 0x44 != 0 {
+            cell.volume = last.volume;
+        }
+
+        if mask & 0x88 != 0 {
+            cell.effect = last.effect;
+            cell.param = last.param;
+        }
+    }
+
+    Ok((Pattern { rows, cells }, used))
+}
+
+/// An envelope of a new instrument, 82 bytes, with values offset by
+/// `bias`.
+fn read_envelope(data: &[u8], bias: i8) -> Option<Envelope> {
+    let flags = data[0];
+    let count = core::cmp::min(data[1] as usize, 25);
+
+    if flags & 1 == 0 || count == 0 {
+        return None;
+    }
+
+    let points = data[6..6 + count * 3]
+        .chunks_exact(3)
+        .map(|p| {
+            let value = (p[0] as i8).wrapping_add(bias);
+            (
+                u16::from_le_bytes([p[1], p[2]]),
+                core::cmp::min(core::cmp::max(value, 0), 64) as u8,
+            )
+        })
+        .collect();
+
+    let points_between = |start: u8, end: u8| {
+        let (start, end) = (start as usize, end as usize);
+
+        if start <= end && end < count {
+            Some((start, end))
+        } else {
+            None
+        }
+    };
+
+    Some(Envelope {
+        points,
+        envelope_loop: if flags & 2 != 0 {
+            points_between(data[2], data[3])
+        } else {
+            None
+        },
+        sustain: if flags & 4 != 0 {
+            points_between(data[4], data[5])
+        } else {
+            None
+        },
+    })
+}
+
+fn read_instrument(bytes: &[u8], offset: usize, old_format: bool) -> io::Result<Instrument> {
+    let data = Bytes::at(bytes, offset)?.take(0x1d4)?;
+
+    if &data[..4] != b"IMPI" {
+        return Err(Error::InvalidData);
+    }
+
+    let keyboard = data[0x40..0x130]
+        .chunks_exact(2)
+        .map(|k| {
+            (
+                core::cmp::min(k[0], 119),
+                k[1].checked_sub(1).map(u16::from),
+            )
+        })
+        .collect();
+
+    if old_format {
+        // Before 2.00: a volume envelope only, of nodes which end at a tick
+        // of 255, and a fadeout of 512 a tick.
+        let flags = data[0x11];
+        let points: Vec<(u16, u8)> = Bytes::at(bytes, offset + 0x1f8)?
+            .take(50)?
+            .chunks_exact(2)
+            .take_while(|node| node[0] != 0xff)
+            .map(|node| (node[0] as u16, core::cmp::min(node[1], 64)))
+            .collect();
+
+        let points_between = |start: u8, end: u8| {
+            let (start, end) = (start as usize, end as usize);
+
+            if start <= end && end < points.len() {
+                Some((start, end))
+            } else {
+                None
+            }
+        };
+
+        let volume_envelope = if flags & 1 != 0 && !points.is_empty() {
+            Some(Envelope {
+                envelope_loop: if flags & 2 != 0 {
+                    points_between(data[0x12], data[0x13])
+                } else {
+                    None
+                },
+                sustain: if flags & 4 != 0 {
+                    points_between(data[0x14], data[0x15])
+                } else {
+                    None
+                },
+                points,
+            })
+        } else {
+            None
+        };
+
+        return Ok(Instrument {
+            keyboard,
+            volume_envelope,
+            panning_envelope: None,
+            fadeout: u16::from_le_bytes([data[0x18], data[0x19]]) as u32 * 128,
+            global_volume: 128,
+            panning: None,
+        });
+    }
+
+    let envelopes = Bytes::at(bytes, offset + 0x130)?.take(164)?;
+    let panning = data[0x19];
+
+    Ok(Instrument {
+        keyboard,
+        volume_envelope: read_envelope(&envelopes[..82], 0),
+        panning_envelope: read_envelope(&envelopes[82..], 32),
+        // Of 1024 a tick.
+        fadeout: u16::from_le_bytes([data[0x14], data[0x15]]) as u32 * 64,
+        global_volume: core::cmp::min(data[0x18], 128),
+        panning: if panning & 0x80 == 0 {
+            Some(core::cmp::min(panning as u32 * 4, 255) as u8)
+        } else {
+            None
+        },
+    })
+}
+
+/// Reads bits from the bytes of a compressed block, lowest first.
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Bits<'_> {
+    fn read(&mut self, count: u32) -> io::Result<u32> {
+        let mut value = 0;
+
+        for i in 0..count {
+            let byte = *self.data.get(self.position / 8).ok_or(Error::InvalidData)?;
+            value |= ((byte >> (self.position % 8)) as u32 & 1) << i;
+            self.position += 1;
+        }
+
+        Ok(value)
+    }
+}
+
+/// Decompress `len` samples compressed as IT 2.14, or as 2.15 with
+/// `it215`, which stores the change of the change of each sample.
+///
+/// Each block holds values of a width which changes as it goes, and the
+/// running sums start again at every block.
+fn decompress(data: &mut Bytes, len: usize, wide: bool, it215: bool) -> io::Result<Vec<i16>> {
+    let (max_width, block_samples, width_bits, sample_bits) = if wide {
+        (17, 0x4000, 4, 16)
+    } else {
+        (9, 0x8000, 3, 8)
+    };
+
+    // Every value takes at least a bit, which bounds what `len` can claim.
+    let mut out = Vec::with_capacity(core::cmp::min(len, data.remaining().len() * 8));
+
+    while out.len() < len {
+        let block_len = data.u16()? as usize;
+        let mut bits = Bits {
+            data: data.take(block_len)?,
+            position: 0,
+        };
+
+        let end = core::cmp::min(len, out.len() + block_samples);
+        let mut width = max_width;
+        let (mut d1, mut d2) = (0i32, 0i32);
+
+        while out.len() < end {
+            if width == 0 || width > max_width {
+                return Err(Error::InvalidData);
+            }
+
+            let value = bits.read(width)?;
+
+            if width < 7 {
+                // A single value of the top bit alone changes the width.
+                if value == 1 << (width - 1) {
+                    let new_width = bits.read(width_bits)? + 1;
+                    width = if new_width < width {
+                        new_width
+                    } else {
+                        new_width + 1
+                    };
+                    continue;
+                }
+            } else if width < max_width {
+                // As do values around the top of the range.
+                let border =
+                    ((1u32 << sample_bits) - 1 >> (max_width - width)) - (1 << (width_bits - 1));
+
+                if value > border && value <= border + (1 << width_bits) {
+                    let new_width = value - border;
+                    width = if new_width < width {
+                        new_width
+                    } else {
+                        new_width + 1
+                    };
+                    continue;
+                }
+            } else if value & 1 << (max_width - 1) != 0 {
+                // And at full width, the extra bit marks a new width.
+                width = (value + 1) & 0xff;
+                continue;
+            }
+
+            // Sign-extend the value from its width.
+            let shift = 32 - core::cmp::min(width, sample_bits);
+            let delta = (value << shift) as i32 >> shift;
+
+            d1 = d1.wrapping_add(delta);
+            d2 = d2.wrapping_add(d1);
+
+            let sample = if it215 { d2 } else { d1 };
+
+            out.push(if wide {
+                sample as i16
+            } else {
+                (sample as i8 as i16) << 8
+            });
+        }
+    }
+
+    Ok(out)
+}
+
+fn read_sample(bytes: &[u8], offset: usize) -> io::Result<Sample> {
+    let mut header = Bytes::at(bytes, offset)?;
+
+    if header.take(4)? != b"IMPS" {
+        return Err(Error::InvalidData);
+    }
+
+    header.skip(13)?;
+    let global_volume = core::cmp::min(header.u8()?, 64);
+    let flags = header.u8()?;
+    let volume = core::cmp::min(header.u8()?, 64);
+    header.skip(26)?;
+    let convert = header.u8()?;
+    let panning = header.u8()?;
+    let len = header.u32()? as usize;
+    let loop_start = header.u32()? as usize;
+    let loop_end = header.u32()? as usize;
+    let c5_speed = header.u32()?;
+    let sustain_start = header.u32()? as usize;
+    let sustain_end = header.u32()? as usize;
+    let pointer = header.u32()? as usize;
+
+    let wide = flags & 0x02 != 0;
+    let signed = convert & 0x01 != 0;
+
+    let data = if flags & 0x01 == 0 || len == 0 {
+        Vec::new()
+    } else if flags & 0x08 != 0 {
+        // Only the left channel of stereo samples is played, which comes
+        // first.
+        decompress(
+            &mut Bytes::at(bytes, pointer)?,
+            len,
+            wide,
+            convert & 0x04 != 0,
+        )?
+    } else {
+        // Uncompressed samples cut short are kept.
+        let stored = Bytes::at(bytes, pointer)?.remaining();
+
+        if wide {
+            let stored = &stored[..core::cmp::min(len * 2, stored.len() & !1)];
+            stored
+                .chunks_exact(2)
+                .map(|b| {
+                    let sample = u16::from_le_bytes([b[0], b[1]]);
+                    (if signed { sample } else { sample ^ 0x8000 }) as i16
+                })
+                .collect()
+        } else {
+            let stored = &stored[..core::cmp::min(len, stored.len())];
+            stored
+                .iter()
+                .map(|&b| ((if signed { b } else { b ^ 0x80 }) as i8 as i16) << 8)
+                .collect()
+        }
+    };
+
+    let len = data.len();
+    let loop_kind = |ping_pong: bool| {
+        if ping_pong {
+            LoopKind::PingPong
+        } else {
+            LoopKind::Forward
+        }
+    };
+
+    Ok(Sample {
+        sample_loop: if flags & 0x10 != 0 {
+            SampleLoop::new(loop_start, loop_end, loop_kind(flags & 0x40 != 0), len)
+        } else {
+            None
+        },
+        sustain_loop: if flags & 0x20 != 0 {
+            SampleLoop::new(
+                sustain_start,
+                sustain_end,
+                loop_kind(flags & 0x80 != 0),
+                len,
+            )
+        } else {
+            None
+        },
+        data,
+        volume,
+        global_volume,
+        panning: if panning & 0x80 != 0 {
+            Some(core::cmp::min((panning & 0x7f) as u32 * 4, 255) as u8)
+        } else {
+            None
+        },
+        c5_speed,
+    })
+}
+
+pub(super) fn load(bytes: &[u8]) -> io::Result<Module> {
+    let mut header = Bytes::new(bytes);
+    header.skip(4)?;
+    let title = name(header.take(26)?);
+    header.skip(2)?;
+
+    let order_count = header.u16()? as usize;
+    let instrument_count = header.u16()? as usize;
+    let sample_count = header.u16()? as usize;
+    let pattern_count = header.u16()? as usize;
+    let _created_with = header.u16()?;
+    let compatible_with = header.u16()?;
+    let flags = header.u16()?;
+    let _special = header.u16()?;
+    let global_volume = core::cmp::min(header.u8()?, 128);
+    let mix_volume = core::cmp::min(header.u8()?, 128);
+    let speed = header.u8()?;
+    let tempo = header.u8()?;
+    header.skip(12)?;
+
+    let channel_panning = header.take(64)?;
+    let channel_volume = header.take(64)?;
+    let orders = header.take(order_count)?.to_vec();
+
+    let mut offsets = |count: usize| -> io::Result<Vec<usize>> {
+        (0..count).map(|_| Ok(header.u32()? as usize)).collect()
+    };
+
+    let instrument_offsets = offsets(instrument_count)?;
+    let sample_offsets = offsets(sample_count)?;
+    let pattern_offsets = offsets(pattern_count)?;
+
+    let samples = sample_offsets
+        .iter()
+        .map(|&offset| read_sample(bytes, offset))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let instruments = if flags & 0x04 != 0 {
+        instrument_offsets
+            .iter()
+            .map(|&offset| read_instrument(bytes, offset, compatible_with < 0x200))
+            .collect::<io::Result<Vec<_>>>()?
+    } else {
+        (0..sample_count as u16)
+            .map(Instrument::for_sample)
+            .collect()
+    };
+
+    let mut patterns = Vec::with_capacity(pattern_count);
+    let mut channels = 1;
+
+    for &offset in &pattern_offsets {
+        let (pattern, used) = read_pattern(bytes, offset)?;
+        channels = core::cmp::max(channels, used);
+        patterns.push(pattern);
+    }
+
+    // Keep only the channels which are used.
+    for pattern in &mut patterns {
+        let cells = core::mem::replace(&mut pattern.cells, Vec::new());
+        pattern.cells = cells
+            .chunks_exact(64)
+            .flat_map(|row| row[..channels].iter().copied())
+            .collect();
+    }
+
+    // Panning is 0 to 64, or 100 for surround, which is played as center.
+    // Disabled channels, with the top bit set, are silent.
+    let stereo = flags & 0x01 != 0;
+    let panning = channel_panning[..channels]
+        .iter()
+        .map(|&pan| match pan & 0x7f {
+            pan @ 0..=64 if stereo => core::cmp::min(pan as u32 * 4, 255) as u8,
+            _ => 128,
+        })
+        .collect();
+
+    let channel_volume = channel_volume[..channels]
+        .iter()
+        .zip(channel_panning)
+        .map(|(&volume, &pan)| {
+            if pan & 0x80 != 0 {
+                0
+            } else {
+                core::cmp::min(volume, 64)
+            }
+        })
+        .collect();
+
+    Ok(Module {
+        title,
+        format: ModuleFormat::It,
+        channels,
+        orders,
+        patterns,
+        instruments,
+        samples,
+        panning,
+        channel_volume,
+        speed: core::cmp::max(speed, 1),
+        tempo: core::cmp::max(tempo, 32),
+        global_volume,
+        mix_volume,
+        linear_slides: flags & 0x08 != 0,
+        separate_tone_portamento: flags & 0x20 == 0,
+        old_effects: flags & 0x10 != 0,
+    })
+}
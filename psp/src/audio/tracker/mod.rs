@@ -0,0 +1,374 @@
+//! A player for tracker modules: ProTracker MOD, FastTracker II XM and
+//! Impulse Tracker IT.
+//!
+//! A `Module` is loaded whole, then played by a `ModuleDecoder`, which
+//! renders it to 16-bit stereo at any sample rate. As a `PcmDecoder`, it can
+//! be streamed with a `PcmPlayer`.
+//!
+//! ```no_run
+//! use psp::audio::tracker::{Module, ModuleDecoder};
+//! use psp::audio::PcmPlayer;
+//! use psp::fs::File;
+//!
+//! let module = Module::read(File::open("ms0:/MUSIC/title.xm").unwrap()).unwrap();
+//! let mut player = PcmPlayer::new(ModuleDecoder::new(module, 44100));
+//! player.set_looping(true);
+//!
+//! let stream = player.into_stream().unwrap();
+//! ```
+//!
+//! Most effects of each format are played as their tracker would. New note
+//! actions are not: a new note always cuts the last one in its channel.
+//! Pitch envelopes, filters, and the auto-vibrato of XM and IT samples are
+//! ignored.
+
+mod it;
+mod play;
+mod protracker;
+mod xm;
+
+use crate::io::{self, Error, Read};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub use play::ModuleDecoder;
+
+/// The format a `Module` was loaded from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ModuleFormat {
+    /// ProTracker and compatible MODs, of 4 to 32 channels.
+    Mod,
+    /// FastTracker II.
+    Xm,
+    /// Impulse Tracker.
+    It,
+}
+
+/// No note at all.
+const NOTE_NONE: u8 = 0xff;
+/// Notes which play no pitch, but release, cut or fade out the last one.
+const NOTE_OFF: u8 = 0xfe;
+const NOTE_CUT: u8 = 0xfd;
+const NOTE_FADE: u8 = 0xfc;
+
+/// The note which plays a sample at its `c5_speed`.
+const MIDDLE_C: u8 = 60;
+
+/// One channel of one row.
+///
+/// Effects are stored as Impulse Tracker's, which cover MOD and XM's with a
+/// few additions, so the player only knows one set.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Cell {
+    /// 0 to 119, with `MIDDLE_C` at 60, or one of the `NOTE_` constants.
+    pub note: u8,
+    /// The instrument, from 1, or 0 for none.
+    pub instrument: u8,
+    pub volume: VolumeCommand,
+    pub effect: u8,
+    pub param: u8,
+}
+
+impl Cell {
+    pub const EMPTY: Cell = Cell {
+        note: NOTE_NONE,
+        instrument: 0,
+        volume: VolumeCommand::None,
+        effect: effect::NONE,
+        param: 0,
+    };
+}
+
+/// The volume column, as in Impulse Tracker. Its volume slides share one
+/// memory in IT, and its portamentos share the memory of the effects.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum VolumeCommand {
+    None,
+    /// Set the volume, 0 to 64.
+    Volume(u8),
+    /// Set the panning, 0 to 64.
+    Panning(u8),
+    FineVolumeUp(u8),
+    FineVolumeDown(u8),
+    VolumeUp(u8),
+    VolumeDown(u8),
+    PortamentoDown(u8),
+    PortamentoUp(u8),
+    /// At the speed of `Gxx`, which IT and XM store differently.
+    TonePortamento(u8),
+    VibratoDepth(u8),
+    /// XM only.
+    VibratoSpeed(u8),
+    PanningLeft(u8),
+    PanningRight(u8),
+}
+
+/// Effect commands, numbered as their Impulse Tracker letters from `A` as 1,
+/// with a few more after `Y`.
+///
+/// Parameters follow Impulse Tracker too: fine slides of MOD and XM are
+/// converted to their `Dxy`, `Exx` and `Fxx` forms, and speeds and offsets
+/// which MOD and XM only scale differently are converted as they load.
+mod effect {
+    pub const NONE: u8 = 0;
+    pub const SPEED: u8 = 1;
+    pub const JUMP: u8 = 2;
+    pub const BREAK: u8 = 3;
+    pub const VOLUME_SLIDE: u8 = 4;
+    pub const PORTAMENTO_DOWN: u8 = 5;
+    pub const PORTAMENTO_UP: u8 = 6;
+    pub const TONE_PORTAMENTO: u8 = 7;
+    pub const VIBRATO: u8 = 8;
+    pub const TREMOR: u8 = 9;
+    pub const ARPEGGIO: u8 = 10;
+    pub const VIBRATO_VOLUME_SLIDE: u8 = 11;
+    pub const TONE_PORTAMENTO_VOLUME_SLIDE: u8 = 12;
+    pub const CHANNEL_VOLUME: u8 = 13;
+    pub const CHANNEL_VOLUME_SLIDE: u8 = 14;
+    pub const OFFSET: u8 = 15;
+    pub const PANNING_SLIDE: u8 = 16;
+    pub const RETRIGGER: u8 = 17;
+    pub const TREMOLO: u8 = 18;
+    /// `Sxy`, whose `x` picks one of many.
+    pub const SPECIAL: u8 = 19;
+    pub const TEMPO: u8 = 20;
+    pub const FINE_VIBRATO: u8 = 21;
+    pub const GLOBAL_VOLUME: u8 = 22;
+    pub const GLOBAL_VOLUME_SLIDE: u8 = 23;
+    /// Set the panning, 0 to 255.
+    pub const PANNING: u8 = 24;
+    pub const PANBRELLO: u8 = 25;
+    /// Set the volume, 0 to 64: MOD `Cxx` and XM `Cxx`.
+    pub const VOLUME: u8 = 26;
+    /// XM `Kxx`: release the note on tick `xx`.
+    pub const KEY_OFF: u8 = 27;
+    /// XM `Lxx`: set the envelope position.
+    pub const ENVELOPE_POSITION: u8 = 28;
+    /// MOD and XM `E9x`: retrigger the note every `x` ticks of the row.
+    pub const NOTE_RETRIGGER: u8 = 29;
+}
+
+#[derive(Clone, Debug)]
+struct Pattern {
+    pub rows: usize,
+    /// `rows` rows of a cell per channel.
+    pub cells: Vec<Cell>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum LoopKind {
+    Forward,
+    PingPong,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct SampleLoop {
+    pub start: usize,
+    pub end: usize,
+    pub kind: LoopKind,
+}
+
+impl SampleLoop {
+    /// A loop of the samples from `start` to before `end`, if it's valid
+    /// for a sample of `len`.
+    pub fn new(start: usize, end: usize, kind: LoopKind, len: usize) -> Option<Self> {
+        let end = core::cmp::min(end, len);
+
+        if start < end {
+            Some(SampleLoop { start, end, kind })
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Sample {
+    pub data: Vec<i16>,
+    pub sample_loop: Option<SampleLoop>,
+    /// A loop played until the note is released. IT only.
+    pub sustain_loop: Option<SampleLoop>,
+    /// The default volume, 0 to 64.
+    pub volume: u8,
+    /// 0 to 64. IT only.
+    pub global_volume: u8,
+    /// The default panning, 0 to 255, if any.
+    pub panning: Option<u8>,
+    /// The sample rate at `MIDDLE_C`, including any finetune.
+    pub c5_speed: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Envelope {
+    /// Ticks and values, 0 to 64, in order of tick.
+    pub points: Vec<(u16, u8)>,
+    /// The points between which to loop, while the note is held.
+    pub sustain: Option<(usize, usize)>,
+    pub envelope_loop: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Debug)]
+struct Instrument {
+    /// The note and sample, from 0, played by each note.
+    pub keyboard: Vec<(u8, Option<u16>)>,
+    pub volume_envelope: Option<Envelope>,
+    /// Values are centered on 32.
+    pub panning_envelope: Option<Envelope>,
+    /// How much the volume falls each tick after release, of 65536.
+    pub fadeout: u32,
+    /// 0 to 128. IT only.
+    pub global_volume: u8,
+    /// The default panning, 0 to 255, overriding the sample's. IT only.
+    pub panning: Option<u8>,
+}
+
+impl Instrument {
+    /// An instrument which plays `sample` at the note asked for, for formats
+    /// with samples only.
+    fn for_sample(sample: u16) -> Self {
+        Instrument {
+            keyboard: (0..120).map(|note| (note, Some(sample))).collect(),
+            volume_envelope: None,
+            panning_envelope: None,
+            fadeout: 0,
+            global_volume: 128,
+            panning: None,
+        }
+    }
+}
+
+/// A tracker module, ready to play with a `ModuleDecoder`.
+#[derive(Clone, Debug)]
+pub struct Module {
+    title: String,
+    format: ModuleFormat,
+    channels: usize,
+    /// Patterns to play, in order. 254 and out of range patterns are skipped,
+    /// and 255 ends the song.
+    orders: Vec<u8>,
+    patterns: Vec<Pattern>,
+    instruments: Vec<Instrument>,
+    samples: Vec<Sample>,
+    /// The initial panning of each channel, 0 to 255.
+    panning: Vec<u8>,
+    /// The initial volume of each channel, 0 to 64.
+    channel_volume: Vec<u8>,
+    speed: u8,
+    tempo: u8,
+    /// 0 to 128.
+    global_volume: u8,
+    /// How loud to mix, of 128.
+    mix_volume: u8,
+    /// Whether pitch slides are in fractions of a semitone, rather than
+    /// Amiga periods.
+    linear_slides: bool,
+    /// IT: whether `Gxx` keeps its own memory, apart from `Exx` and `Fxx`.
+    separate_tone_portamento: bool,
+    /// IT: vibratos twice as deep, and updated on the first tick too.
+    old_effects: bool,
+}
+
+impl Module {
+    /// Load a module of any supported format from memory.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.starts_with(b"Extended Module: ") {
+            xm::load(bytes)
+        } else if bytes.starts_with(b"IMPM") {
+            it::load(bytes)
+        } else if protracker::is_mod(bytes) {
+            protracker::load(bytes)
+        } else {
+            Err(Error::InvalidData)
+        }
+    }
+
+    /// Read all of `reader`, and load the module it holds.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn format(&self) -> ModuleFormat {
+        self.format
+    }
+
+    /// The number of channels the patterns have.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+}
+
+/// Little-endian fields of a module, where running out is an error.
+struct Bytes<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Bytes<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Bytes { data }
+    }
+
+    /// The fields starting at `offset`.
+    pub fn at(data: &'a [u8], offset: usize) -> io::Result<Self> {
+        match data.get(offset..) {
+            Some(data) => Ok(Bytes { data }),
+            None => Err(Error::InvalidData),
+        }
+    }
+
+    pub fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if len > self.data.len() {
+            return Err(Error::InvalidData);
+        }
+
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+
+        Ok(taken)
+    }
+
+    pub fn skip(&mut self, len: usize) -> io::Result<()> {
+        self.take(len).map(|_| ())
+    }
+
+    pub fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> io::Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn u32(&mut self) -> io::Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+/// A name from a fixed-size field, which may be padded with NULs or spaces.
+fn name(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    let name: String = field[..end]
+        .iter()
+        .map(|&b| if b.is_ascii_graphic() { b as char } else { ' ' })
+        .collect();
+
+    String::from(name.trim_end())
+}
+
+/// The sample rate at `MIDDLE_C` of a sample which plays at 8363 Hz at
+/// `MIDDLE_C`, plus `fine` 128ths of a semitone.
+fn c5_speed(fine: i32) -> u32 {
+    (8363.0 * play::exp2(fine as f32 / (12.0 * 128.0))) as u32
+}
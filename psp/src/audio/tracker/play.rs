@@ -0,0 +1,1386 @@
+//! Playing a `Module`: the sequencer, effects, envelopes, and mixing the
+//! channels' samples together.
+
+use super::super::PcmDecoder;
+use super::{
+    effect, Cell, Envelope, Instrument, LoopKind, Module, ModuleFormat, Sample, SampleLoop,
+    VolumeCommand, MIDDLE_C, NOTE_CUT, NOTE_FADE, NOTE_OFF,
+};
+use crate::io;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Two to the power of `x`, to single precision.
+pub(super) fn exp2(x: f32) -> f32 {
+    let whole = x as i32;
+    let whole = if whole as f32 > x { whole - 1 } else { whole };
+    let y = (x - whole as f32) * core::f32::consts::LN_2;
+
+    let mut term = 1.0;
+    let mut sum = 1.0;
+
+    for k in 1..9 {
+        term *= y / k as f32;
+        sum += term;
+    }
+
+    let whole = if whole < -126 {
+        -126
+    } else if whole > 127 {
+        127
+    } else {
+        whole
+    };
+    sum * f32::from_bits(((whole + 127) as u32) << 23)
+}
+
+/// A quarter of ProTracker's vibrato sine, from 0 to 255.
+const SINE: [u8; 32] = [
+    0, 24, 49, 74, 97, 120, 141, 161, 180, 197, 212, 224, 235, 244, 250, 253, 255, 253, 250, 244,
+    235, 224, 212, 197, 180, 161, 141, 120, 97, 74, 49, 24,
+];
+
+/// A point of a vibrato, tremolo or panbrello waveform, -255 to 255, at
+/// `position` of 256 for a cycle.
+fn waveform(kind: u8, position: u8, random: &mut u32) -> i32 {
+    let half = position & 0x7f;
+
+    match kind & 3 {
+        0 => {
+            let value = SINE[(half >> 2) as usize] as i32;
+            if position < 0x80 {
+                value
+            } else {
+                -value
+            }
+        }
+        1 => 255 - position as i32 * 2,
+        2 => {
+            if position < 0x80 {
+                255
+            } else {
+                -255
+            }
+        }
+        _ => {
+            *random = random.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((*random >> 16) & 0x1ff) as i32 - 255
+        }
+    }
+}
+
+/// The value of `envelope` at `tick`.
+fn envelope_value(envelope: &Envelope, tick: u16) -> i32 {
+    let points = &envelope.points;
+
+    match points.iter().position(|&(t, _)| t > tick) {
+        None => points.last().map_or(64, |&(_, value)| value as i32),
+        Some(0) => points[0].1 as i32,
+        Some(i) => {
+            let (t0, v0) = points[i - 1];
+            let (t1, v1) = points[i];
+            let (t0, v0, t1, v1) = (t0 as i32, v0 as i32, t1 as i32, v1 as i32);
+
+            v0 + (v1 - v0) * (tick as i32 - t0) / (t1 - t0)
+        }
+    }
+}
+
+/// The tick after `tick` in `envelope`, following its loops. Returns
+/// `None` once at the end, where it stays.
+fn envelope_next(envelope: &Envelope, tick: u16, key_on: bool) -> Option<u16> {
+    let points = &envelope.points;
+    let next = tick.saturating_add(1);
+
+    let envelope_loop = match envelope.sustain {
+        Some(sustain) if key_on => Some(sustain),
+        _ => envelope.envelope_loop,
+    };
+
+    if let Some((start, end)) = envelope_loop {
+        if tick >= points[end].0 {
+            return Some(points[start].0);
+        }
+    }
+
+    let last = points.last().map_or(0, |&(t, _)| t);
+
+    if tick >= last {
+        None
+    } else {
+        Some(next)
+    }
+}
+
+/// One sample playing, and how loud it is in each ear.
+#[derive(Default)]
+struct Voice {
+    sample: Option<usize>,
+    /// In samples, with 16 bits of fraction.
+    position: u64,
+    step: u64,
+    backwards: bool,
+    /// Whether to play the sample's sustain loop, if it has one.
+    sustain: bool,
+    /// Gains of 4096 for full volume.
+    left: i32,
+    right: i32,
+}
+
+impl Voice {
+    fn stop(&mut self) {
+        self.sample = None;
+    }
+
+    fn start(&mut self, sample: usize, offset: usize) {
+        self.sample = Some(sample);
+        self.position = (offset as u64) << 16;
+        self.backwards = false;
+        self.sustain = true;
+    }
+
+    /// Add `frames` of the sample to `out`, which is interleaved stereo.
+    fn mix(&mut self, samples: &[Sample], out: &mut [i32]) {
+        let sample = match self.sample {
+            Some(sample) => &samples[sample],
+            None => return,
+        };
+
+        let data = &sample.data[..];
+
+        let sample_loop = match (self.sustain, sample.sustain_loop) {
+            (true, Some(sustain)) => Some(sustain),
+            _ => sample.sample_loop,
+        };
+
+        let end = match sample_loop {
+            Some(sample_loop) => sample_loop.end,
+            None => data.len(),
+        };
+
+        let (left, right) = (self.left, self.right);
+
+        for frame in out.chunks_exact_mut(2) {
+            let i = (self.position >> 16) as usize;
+
+            if i >= data.len() {
+                self.sample = None;
+                return;
+            }
+
+            let s0 = data[i] as i32;
+            let s1 = if i + 1 < end {
+                data[i + 1] as i32
+            } else {
+                match sample_loop {
+                    Some(SampleLoop {
+                        start,
+                        kind: LoopKind::Forward,
+                        ..
+                    }) => data[start] as i32,
+                    _ => s0,
+                }
+            };
+
+            // 15 bits of fraction, so full-scale steps can't overflow.
+            let fraction = (self.position & 0xffff) as i32 >> 1;
+            let s = s0 + ((s1 - s0) * fraction >> 15);
+
+            frame[0] += s * left >> 12;
+            frame[1] += s * right >> 12;
+
+            if self.backwards {
+                let start = sample_loop.map_or(0, |l| l.start as u64) << 16;
+
+                if self.position < start + self.step {
+                    // Bounce off the start of the loop.
+                    self.position = (2 * start + self.step).saturating_sub(self.position);
+                    self.backwards = false;
+                } else {
+                    self.position -= self.step;
+                }
+            } else {
+                self.position += self.step;
+            }
+
+            if self.position >= (end as u64) << 16 && !self.backwards {
+                match sample_loop {
+                    None => {
+                        self.sample = None;
+                        return;
+                    }
+                    Some(SampleLoop {
+                        start,
+                        end,
+                        kind: LoopKind::Forward,
+                    }) => {
+                        let len = ((end - start) as u64) << 16;
+                        let start = (start as u64) << 16;
+                        self.position = start + (self.position - start) % len;
+                    }
+                    Some(SampleLoop {
+                        start,
+                        end,
+                        kind: LoopKind::PingPong,
+                    }) => {
+                        let last = ((end - 1) as u64) << 16;
+                        let over = self.position - last;
+                        let start = (start as u64) << 16;
+                        self.position = core::cmp::max(last.saturating_sub(over), start);
+                        self.backwards = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The song position and everything else shared by the channels.
+struct Song {
+    order: usize,
+    row: usize,
+    tick: u32,
+    speed: u32,
+    tempo: u32,
+    /// 0 to 128.
+    global_volume: i32,
+    /// Extra rows to repeat the current row for, and extra ticks for it.
+    pattern_delay: Option<u32>,
+    extra_ticks: u32,
+    /// Where to go after this row, from jumps, breaks and pattern loops.
+    jump: Option<usize>,
+    break_row: Option<usize>,
+    loop_row: Option<usize>,
+    /// The order and row each pattern was entered at, to find the end of a
+    /// song which loops.
+    visited: Vec<u64>,
+}
+
+impl Song {
+    fn new(module: &Module) -> Self {
+        Song {
+            order: 0,
+            row: 0,
+            tick: 0,
+            speed: module.speed as u32,
+            tempo: module.tempo as u32,
+            global_volume: module.global_volume as i32,
+            pattern_delay: None,
+            extra_ticks: 0,
+            jump: None,
+            break_row: None,
+            loop_row: None,
+            visited: vec![0; module.orders.len() * 4 + 4],
+        }
+    }
+
+    /// Go to `row` of the pattern at `order`, skipping markers. Returns
+    /// false at the end of the song, or when a place is played twice.
+    fn enter(&mut self, module: &Module, mut order: usize, row: usize) -> bool {
+        loop {
+            match module.orders.get(order) {
+                None | Some(255) => return false,
+                Some(&pattern) if pattern as usize >= module.patterns.len() => order += 1,
+                Some(_) => break,
+            }
+        }
+
+        let pattern = &module.patterns[module.orders[order] as usize];
+        let row = if row < pattern.rows { row } else { 0 };
+
+        // Rows are up to 256 in all formats; a bit per quarter is enough to
+        // tell entry points apart, as breaks to most rows are rare.
+        let bit = order * 256 + row;
+        let (word, bit) = (bit / 64, bit % 64);
+
+        if word >= self.visited.len() {
+            self.visited.resize(word + 1, 0);
+        }
+
+        if self.visited[word] & 1 << bit != 0 {
+            return false;
+        }
+
+        self.visited[word] |= 1 << bit;
+        self.order = order;
+        self.row = row;
+
+        true
+    }
+
+    /// Move to the next row. Returns false at the end of the song.
+    fn next_row(&mut self, module: &Module) -> bool {
+        self.extra_ticks = 0;
+
+        if let Some(row) = self.loop_row.take() {
+            self.row = row;
+            self.jump = None;
+            self.break_row = None;
+            return true;
+        }
+
+        match (self.jump.take(), self.break_row.take()) {
+            (None, None) => {
+                let rows = module.patterns[module.orders[self.order] as usize].rows;
+
+                if self.row + 1 < rows {
+                    self.row += 1;
+                    true
+                } else {
+                    self.enter(module, self.order + 1, 0)
+                }
+            }
+            (jump, row) => {
+                let order = jump.unwrap_or(self.order + 1);
+                self.enter(module, order, row.unwrap_or(0))
+            }
+        }
+    }
+}
+
+/// The state of one channel of the song.
+#[derive(Default)]
+struct Channel {
+    instrument: Option<usize>,
+    sample: Option<usize>,
+    note: u8,
+    c5_speed: u32,
+    period: i32,
+    target_period: i32,
+    /// 0 to 64.
+    volume: i32,
+    channel_volume: i32,
+    /// 0 to 256.
+    panning: i32,
+
+    /// Offsets for this tick only, from vibrato, arpeggio and the like.
+    period_offset: i32,
+    volume_offset: i32,
+    panning_offset: i32,
+    muted: bool,
+
+    /// The effect of the current row, with any memory applied.
+    effect: u8,
+    param: u8,
+    volume_command: Option<VolumeCommand>,
+    /// A cell waiting for its tick.
+    delayed: Option<(u32, Cell)>,
+
+    vibrato_position: u8,
+    vibrato_speed: u8,
+    vibrato_depth: u8,
+    vibrato_waveform: u8,
+    /// Quarter depth, for IT `Uxy`.
+    fine_vibrato: bool,
+    tremolo_position: u8,
+    tremolo_speed: u8,
+    tremolo_depth: u8,
+    tremolo_waveform: u8,
+    panbrello_position: u8,
+    panbrello_speed: u8,
+    panbrello_depth: u8,
+    panbrello_waveform: u8,
+    tremor_counter: u8,
+    retrigger_counter: u8,
+    /// State for the random waveform.
+    random: u32,
+
+    volume_slide_memory: u8,
+    portamento_up_memory: u8,
+    portamento_down_memory: u8,
+    tone_portamento_memory: u8,
+    offset_memory: u8,
+    offset_high: u8,
+    panning_slide_memory: u8,
+    retrigger_memory: u8,
+    tremor_memory: u8,
+    arpeggio_memory: u8,
+    channel_volume_slide_memory: u8,
+    global_volume_slide_memory: u8,
+    tempo_memory: u8,
+    special_memory: u8,
+    volume_column_memory: u8,
+
+    pattern_loop_row: usize,
+    pattern_loop_count: u8,
+
+    key_on: bool,
+    fading: bool,
+    /// 0 to 65536.
+    fade_volume: i32,
+    volume_envelope_tick: Option<u16>,
+    panning_envelope_tick: Option<u16>,
+
+    voice: Voice,
+}
+
+/// Use `memory` for a zero parameter, or remember a non-zero one.
+fn remember(memory: &mut u8, param: u8) -> u8 {
+    if param == 0 {
+        *memory
+    } else {
+        *memory = param;
+        param
+    }
+}
+
+/// As `remember`, for each nibble on its own.
+fn remember_nibbles(memory: &mut u8, param: u8) -> u8 {
+    if param & 0xf0 != 0 {
+        *memory = *memory & 0x0f | param & 0xf0;
+    }
+
+    if param & 0x0f != 0 {
+        *memory = *memory & 0xf0 | param & 0x0f;
+    }
+
+    *memory
+}
+
+fn clamp(x: i32, min: i32, max: i32) -> i32 {
+    if x < min {
+        min
+    } else if x > max {
+        max
+    } else {
+        x
+    }
+}
+
+/// Apply a `Dxy` style slide to `value`, as up `x` or down `y` on later
+/// ticks, or fine up `x` with `y` of `F` or down `y` with `x` of `F` on the
+/// first.
+fn volume_slide(value: &mut i32, param: u8, first_tick: bool, scale: i32, max: i32) {
+    let (x, y) = ((param >> 4) as i32, (param & 0xf) as i32);
+
+    let change = match (x, y) {
+        (x, 0xf) if x != 0 => {
+            if first_tick {
+                x
+            } else {
+                0
+            }
+        }
+        (0xf, y) if y != 0 => {
+            if first_tick {
+                -y
+            } else {
+                0
+            }
+        }
+        (x, 0) if !first_tick => x,
+        (0, y) if !first_tick => -y,
+        _ => 0,
+    };
+
+    *value = clamp(*value + change * scale, 0, max);
+}
+
+impl Channel {
+    fn new(module: &Module, channel: usize) -> Self {
+        Channel {
+            channel_volume: module.channel_volume[channel] as i32,
+            panning: module.panning[channel] as i32,
+            c5_speed: 8363,
+            random: channel as u32 + 1,
+            ..Default::default()
+        }
+    }
+
+    fn instrument<'a>(&self, module: &'a Module) -> Option<&'a Instrument> {
+        self.instrument.and_then(|i| module.instruments.get(i))
+    }
+
+    /// The period of `note`, in 64ths of a semitone down from the top note
+    /// when slides are linear, and otherwise four times an Amiga period.
+    fn note_period(module: &Module, note: u8) -> i32 {
+        if module.linear_slides {
+            (120 - note as i32) * 64
+        } else {
+            (1712.0 * exp2((MIDDLE_C as f32 - note as f32) / 12.0)) as i32
+        }
+    }
+
+    /// Slide the period by `amount`, where positive is up in pitch.
+    fn slide(&mut self, module: &Module, amount: i32) {
+        self.period -= amount;
+
+        self.period = if module.format == ModuleFormat::Mod {
+            clamp(self.period, 113 * 4, 856 * 4)
+        } else {
+            clamp(self.period, 1, 0xffff)
+        };
+    }
+
+    /// Release the note: leave any sustain, and start to fade out.
+    fn note_off(&mut self, module: &Module) {
+        self.key_on = false;
+        self.voice.sustain = false;
+
+        let envelope = self
+            .instrument(module)
+            .and_then(|i| i.volume_envelope.as_ref());
+
+        match module.format {
+            // Without an envelope, nothing fades.
+            ModuleFormat::Xm if envelope.is_none() => self.volume = 0,
+            ModuleFormat::Xm => self.fading = true,
+            _ => {
+                if envelope.map_or(true, |e| e.envelope_loop.is_some()) {
+                    self.fading = true;
+                }
+            }
+        }
+    }
+
+    fn reset_envelopes(&mut self) {
+        self.key_on = true;
+        self.fading = false;
+        self.fade_volume = 65536;
+        self.volume_envelope_tick = Some(0);
+        self.panning_envelope_tick = Some(0);
+    }
+
+    /// Play a cell's note, instrument and effects, on the first tick of its
+    /// row, or its delayed tick.
+    fn play_cell(&mut self, module: &Module, song: &mut Song, cell: Cell) {
+        let it = module.format == ModuleFormat::It;
+
+        let tone_portamento = match cell.effect {
+            effect::TONE_PORTAMENTO | effect::TONE_PORTAMENTO_VOLUME_SLIDE => true,
+            _ => matches!(cell.volume, VolumeCommand::TonePortamento(_)),
+        };
+
+        if cell.instrument > 0 {
+            let instrument = cell.instrument as usize - 1;
+
+            if instrument < module.instruments.len() {
+                self.instrument = Some(instrument);
+            }
+        }
+
+        // Offsets use memory even when the note is being set.
+        let mut offset = 0;
+
+        if cell.effect == effect::OFFSET {
+            let param = remember(&mut self.offset_memory, cell.param) as usize;
+            offset = (self.offset_high as usize) << 16 | param << 8;
+        }
+
+        if cell.note < 120 {
+            let mapped = self
+                .instrument(module)
+                .and_then(|i| i.keyboard.get(cell.note as usize).copied())
+                .and_then(|(note, sample)| Some((note, sample? as usize)))
+                .filter(|&(_, sample)| sample < module.samples.len());
+
+            match mapped {
+                Some((note, _)) if tone_portamento && self.voice.sample.is_some() => {
+                    self.target_period = Self::note_period(module, note);
+                }
+                Some((note, sample)) => {
+                    self.note = note;
+                    self.sample = Some(sample);
+                    self.c5_speed = module.samples[sample].c5_speed;
+                    self.period = Self::note_period(module, note);
+                    self.target_period = self.period;
+
+                    if offset < module.samples[sample].data.len() {
+                        self.voice.start(sample, offset);
+                    } else {
+                        self.voice.stop();
+                    }
+
+                    if self.vibrato_waveform & 4 == 0 {
+                        self.vibrato_position = 0;
+                    }
+
+                    if self.tremolo_waveform & 4 == 0 {
+                        self.tremolo_position = 0;
+                    }
+
+                    self.retrigger_counter = 0;
+                    self.tremor_counter = 0;
+                    self.reset_envelopes();
+                }
+                // An instrument without a sample for the note stops the last.
+                None if it || cell.instrument > 0 => self.voice.stop(),
+                None => (),
+            }
+        }
+
+        if cell.instrument > 0 {
+            if let Some(sample) = self.sample {
+                let sample = &module.samples[sample];
+                self.volume = sample.volume as i32;
+
+                let panning = self
+                    .instrument(module)
+                    .and_then(|i| i.panning)
+                    .or(sample.panning);
+
+                if let Some(panning) = panning {
+                    self.panning = panning as i32;
+                }
+            }
+
+            if !it || cell.note < 120 {
+                self.reset_envelopes();
+            }
+        }
+
+        match cell.note {
+            NOTE_OFF => self.note_off(module),
+            NOTE_CUT => self.voice.stop(),
+            NOTE_FADE => self.fading = true,
+            _ => (),
+        }
+
+        self.volume_command = Some(cell.volume);
+        self.play_volume_command(module, true);
+        self.start_effect(module, song, cell.effect, cell.param);
+    }
+
+    /// The volume column, on the first tick or a later one.
+    fn play_volume_command(&mut self, module: &Module, first_tick: bool) {
+        let xm = module.format == ModuleFormat::Xm;
+
+        let command = match self.volume_command {
+            Some(command) => command,
+            None => return,
+        };
+
+        match command {
+            VolumeCommand::Volume(volume) if first_tick => self.volume = volume as i32,
+            VolumeCommand::Panning(panning) if first_tick => self.panning = panning as i32 * 4,
+            VolumeCommand::FineVolumeUp(x) if first_tick => {
+                let x = if xm {
+                    x
+                } else {
+                    remember(&mut self.volume_column_memory, x)
+                };
+                self.volume = clamp(self.volume + x as i32, 0, 64);
+            }
+            VolumeCommand::FineVolumeDown(x) if first_tick => {
+                let x = if xm {
+                    x
+                } else {
+                    remember(&mut self.volume_column_memory, x)
+                };
+                self.volume = clamp(self.volume - x as i32, 0, 64);
+            }
+            VolumeCommand::VolumeUp(x) if !first_tick => {
+                let x = if xm {
+                    x
+                } else {
+                    remember(&mut self.volume_column_memory, x)
+                };
+                self.volume = clamp(self.volume + x as i32, 0, 64);
+            }
+            VolumeCommand::VolumeDown(x) if !first_tick => {
+                let x = if xm {
+                    x
+                } else {
+                    remember(&mut self.volume_column_memory, x)
+                };
+                self.volume = clamp(self.volume - x as i32, 0, 64);
+            }
+            VolumeCommand::PortamentoDown(x) if !first_tick => {
+                let x = remember(&mut self.portamento_down_memory, x);
+                self.slide(module, -(x as i32) * 4);
+            }
+            VolumeCommand::PortamentoUp(x) if !first_tick => {
+                let x = remember(&mut self.portamento_up_memory, x);
+                self.slide(module, x as i32 * 4);
+            }
+            VolumeCommand::TonePortamento(speed) if !first_tick => {
+                let speed = remember(&mut self.tone_portamento_memory, speed);
+                self.tone_portamento(speed);
+            }
+            VolumeCommand::VibratoDepth(depth) => {
+                if depth != 0 {
+                    self.vibrato_depth = depth;
+                }
+
+                if !first_tick {
+                    self.vibrato(module);
+                }
+            }
+            VolumeCommand::VibratoSpeed(speed) if first_tick => {
+                if speed != 0 {
+                    self.vibrato_speed = speed;
+                }
+            }
+            VolumeCommand::PanningLeft(x) if !first_tick => {
+                self.panning = clamp(self.panning - x as i32, 0, 256)
+            }
+            VolumeCommand::PanningRight(x) if !first_tick => {
+                self.panning = clamp(self.panning + x as i32, 0, 256)
+            }
+            _ => (),
+        }
+    }
+
+    fn tone_portamento(&mut self, speed: u8) {
+        let speed = speed as i32 * 4;
+
+        if self.period < self.target_period {
+            self.period = core::cmp::min(self.period + speed, self.target_period);
+        } else {
+            self.period = core::cmp::max(self.period - speed, self.target_period);
+        }
+    }
+
+    fn vibrato(&mut self, module: &Module) {
+        let value = waveform(
+            self.vibrato_waveform,
+            self.vibrato_position,
+            &mut self.random,
+        );
+
+        let shift = match (module.format, module.old_effects) {
+            (ModuleFormat::It, false) => 6,
+            _ => 5,
+        } + if self.fine_vibrato { 2 } else { 0 };
+
+        self.period_offset = value * self.vibrato_depth as i32 >> shift;
+
+        let speed = if module.format == ModuleFormat::It {
+            1
+        } else {
+            4
+        };
+        self.vibrato_position = self
+            .vibrato_position
+            .wrapping_add(self.vibrato_speed.wrapping_mul(speed));
+    }
+
+    fn tremolo(&mut self, module: &Module) {
+        let value = waveform(
+            self.tremolo_waveform,
+            self.tremolo_position,
+            &mut self.random,
+        );
+        self.volume_offset = value * self.tremolo_depth as i32 >> 6;
+
+        let speed = if module.format == ModuleFormat::It {
+            1
+        } else {
+            4
+        };
+        self.tremolo_position = self
+            .tremolo_position
+            .wrapping_add(self.tremolo_speed.wrapping_mul(speed));
+    }
+
+    fn retrigger(&mut self, module: &Module) {
+        if let Some(sample) = self.sample {
+            self.voice.start(sample, 0);
+            self.voice.sustain = self.key_on;
+        }
+
+        if module.format != ModuleFormat::It {
+            self.reset_envelopes();
+        }
+    }
+
+    /// The first tick of an effect, resolving its memory.
+    fn start_effect(&mut self, module: &Module, song: &mut Song, effect: u8, param: u8) {
+        let format = module.format;
+        let it = format == ModuleFormat::It;
+
+        let param = match effect {
+            effect::VOLUME_SLIDE
+            | effect::VIBRATO_VOLUME_SLIDE
+            | effect::TONE_PORTAMENTO_VOLUME_SLIDE
+                if format != ModuleFormat::Mod =>
+            {
+                remember(&mut self.volume_slide_memory, param)
+            }
+            effect::PORTAMENTO_UP if it => {
+                let param = remember(&mut self.portamento_up_memory, param);
+                self.portamento_down_memory = param;
+
+                if !module.separate_tone_portamento {
+                    self.tone_portamento_memory = param;
+                }
+
+                param
+            }
+            effect::PORTAMENTO_DOWN if it => {
+                let param = remember(&mut self.portamento_down_memory, param);
+                self.portamento_up_memory = param;
+
+                if !module.separate_tone_portamento {
+                    self.tone_portamento_memory = param;
+                }
+
+                param
+            }
+            effect::PORTAMENTO_UP => remember(&mut self.portamento_up_memory, param),
+            effect::PORTAMENTO_DOWN => remember(&mut self.portamento_down_memory, param),
+            effect::TONE_PORTAMENTO => {
+                let param = remember(&mut self.tone_portamento_memory, param);
+
+                if it && !module.separate_tone_portamento {
+                    self.portamento_up_memory = param;
+                    self.portamento_down_memory = param;
+                }
+
+                param
+            }
+            effect::VIBRATO | effect::FINE_VIBRATO => {
+                let mut memory = self.vibrato_speed << 4 | self.vibrato_depth;
+                let param = remember_nibbles(&mut memory, param);
+                self.vibrato_speed = param >> 4;
+                self.vibrato_depth = param & 0xf;
+                self.fine_vibrato = effect == effect::FINE_VIBRATO;
+                param
+            }
+            effect::TREMOLO => {
+                let mut memory = self.tremolo_speed << 4 | self.tremolo_depth;
+                let param = remember_nibbles(&mut memory, param);
+                self.tremolo_speed = param >> 4;
+                self.tremolo_depth = param & 0xf;
+                param
+            }
+            effect::PANBRELLO => {
+                let mut memory = self.panbrello_speed << 4 | self.panbrello_depth;
+                let param = remember_nibbles(&mut memory, param);
+                self.panbrello_speed = param >> 4;
+                self.panbrello_depth = param & 0xf;
+                param
+            }
+            effect::PANNING_SLIDE => remember(&mut self.panning_slide_memory, param),
+            effect::RETRIGGER => remember(&mut self.retrigger_memory, param),
+            effect::TREMOR => remember(&mut self.tremor_memory, param),
+            effect::ARPEGGIO if it => remember(&mut self.arpeggio_memory, param),
+            effect::CHANNEL_VOLUME_SLIDE => remember(&mut self.channel_volume_slide_memory, param),
+            effect::GLOBAL_VOLUME_SLIDE => remember(&mut self.global_volume_slide_memory, param),
+            effect::TEMPO if param < 0x20 => remember(&mut self.tempo_memory, param),
+            effect::SPECIAL if it => remember(&mut self.special_memory, param),
+            _ => param,
+        };
+
+        self.effect = effect;
+        self.param = param;
+
+        let (x, y) = (param >> 4, param & 0xf);
+
+        match effect {
+            effect::SPEED if param > 0 => song.speed = param as u32,
+            effect::JUMP => song.jump = Some(param as usize),
+            effect::BREAK => song.break_row = Some(param as usize),
+            effect::VOLUME_SLIDE => volume_slide(&mut self.volume, param, true, 1, 64),
+            effect::PORTAMENTO_DOWN | effect::PORTAMENTO_UP => {
+                let sign = if effect == effect::PORTAMENTO_UP {
+                    1
+                } else {
+                    -1
+                };
+
+                match x {
+                    0xf => self.slide(module, sign * y as i32 * 4),
+                    0xe => self.slide(module, sign * y as i32),
+                    _ => (),
+                }
+            }
+            effect::VIBRATO | effect::FINE_VIBRATO if it && !module.old_effects => {
+                self.vibrato(module)
+            }
+            effect::CHANNEL_VOLUME => self.channel_volume = core::cmp::min(param as i32, 64),
+            effect::CHANNEL_VOLUME_SLIDE => {
+                volume_slide(&mut self.channel_volume, param, true, 1, 64)
+            }
+            effect::PANNING_SLIDE => self.panning_slide(module, true),
+            effect::GLOBAL_VOLUME => song.global_volume = core::cmp::min(param as i32, 128),
+            effect::GLOBAL_VOLUME_SLIDE => {
+                let scale = if format == ModuleFormat::Xm { 2 } else { 1 };
+                volume_slide(&mut song.global_volume, param, true, scale, 128);
+            }
+            effect::TEMPO if param >= 0x20 => song.tempo = param as u32,
+            effect::PANNING => self.panning = param as i32,
+            effect::VOLUME => self.volume = core::cmp::min(param as i32, 64),
+            effect::KEY_OFF if param == 0 => self.note_off(module),
+            effect::ENVELOPE_POSITION => {
+                self.volume_envelope_tick = Some(param as u16);
+                self.panning_envelope_tick = Some(param as u16);
+            }
+            effect::SPECIAL => match x {
+                0x3 => self.vibrato_waveform = y,
+                0x4 => self.tremolo_waveform = y,
+                0x5 => self.panbrello_waveform = y,
+                0x6 => song.extra_ticks += y as u32,
+                0x8 => self.panning = y as i32 * 17,
+                0xa => self.offset_high = y,
+                0xb if y == 0 => self.pattern_loop_row = song.row,
+                0xb => {
+                    if self.pattern_loop_count == 0 {
+                        self.pattern_loop_count = y;
+                        song.loop_row = Some(self.pattern_loop_row);
+                    } else {
+                        self.pattern_loop_count -= 1;
+
+                        if self.pattern_loop_count > 0 {
+                            song.loop_row = Some(self.pattern_loop_row);
+                        }
+                    }
+                }
+                0xc if y == 0 && it => self.voice.stop(),
+                0xe if song.pattern_delay.is_none() => song.pattern_delay = Some(y as u32),
+                _ => (),
+            },
+            _ => (),
+        }
+
+        // Effects which also work on the first tick.
+        match effect {
+            effect::ARPEGGIO => self.period_offset = 0,
+            effect::TREMOR => self.tremor(module),
+            effect::RETRIGGER if it => self.retrigger_tick(module),
+            _ => (),
+        }
+    }
+
+    fn panning_slide(&mut self, module: &Module, first_tick: bool) {
+        // IT pans from 0 to 64.
+        let scale = if module.format == ModuleFormat::It {
+            4
+        } else {
+            1
+        };
+        let (x, y) = ((self.param >> 4) as i32, (self.param & 0xf) as i32);
+
+        // Left is `x` and right `y`, with `F` in the other for a fine slide.
+        let change = match (x, y) {
+            (x, 0xf) if x != 0 => {
+                if first_tick {
+                    -x
+                } else {
+                    0
+                }
+            }
+            (0xf, y) if y != 0 => {
+                if first_tick {
+                    y
+                } else {
+                    0
+                }
+            }
+            (x, 0) if !first_tick => -x,
+            (0, y) if !first_tick => y,
+            _ => 0,
+        };
+
+        self.panning = clamp(self.panning + change * scale, 0, 256);
+    }
+
+    fn tremor(&mut self, module: &Module) {
+        let (mut on, mut off) = (self.param >> 4, self.param & 0xf);
+
+        if module.old_effects && module.format == ModuleFormat::It {
+            on += 1;
+            off += 1;
+        }
+
+        let (on, off) = (core::cmp::max(on, 1), core::cmp::max(off, 1));
+
+        self.muted = self.tremor_counter >= on;
+        self.tremor_counter = (self.tremor_counter + 1) % (on + off);
+    }
+
+    fn retrigger_tick(&mut self, module: &Module) {
+        let (x, y) = (self.param >> 4, self.param & 0xf);
+
+        self.retrigger_counter += 1;
+
+        if self.retrigger_counter < core::cmp::max(y, 1) {
+            return;
+        }
+
+        self.retrigger_counter = 0;
+
+        self.volume = clamp(
+            match x {
+                0x1..=0x5 => self.volume - (1 << (x - 1)),
+                0x6 => self.volume * 2 / 3,
+                0x7 => self.volume / 2,
+                0x9..=0xd => self.volume + (1 << (x - 9)),
+                0xe => self.volume * 3 / 2,
+                0xf => self.volume * 2,
+                _ => self.volume,
+            },
+            0,
+            64,
+        );
+
+        self.retrigger(module);
+    }
+
+    /// The later ticks of the row's effects.
+    fn tick(&mut self, module: &Module, song: &mut Song) {
+        let (effect, param) = (self.effect, self.param);
+        let (x, y) = (param >> 4, param & 0xf);
+        let tick = song.tick;
+
+        self.play_volume_command(module, false);
+
+        match effect {
+            effect::VOLUME_SLIDE => volume_slide(&mut self.volume, param, false, 1, 64),
+            effect::PORTAMENTO_DOWN | effect::PORTAMENTO_UP if x < 0xe => {
+                let sign = if effect == effect::PORTAMENTO_UP {
+                    1
+                } else {
+                    -1
+                };
+                self.slide(module, sign * param as i32 * 4);
+            }
+            effect::TONE_PORTAMENTO => self.tone_portamento(param),
+            effect::VIBRATO | effect::FINE_VIBRATO => self.vibrato(module),
+            effect::TREMOR => self.tremor(module),
+            effect::ARPEGGIO => {
+                let semitones = match tick % 3 {
+                    0 => 0,
+                    1 => x,
+                    _ => y,
+                } as i32;
+
+                self.period_offset = if module.linear_slides {
+                    -semitones * 64
+                } else {
+                    (self.period as f32 * exp2(-semitones as f32 / 12.0)) as i32 - self.period
+                };
+            }
+            effect::VIBRATO_VOLUME_SLIDE => {
+                self.vibrato(module);
+                volume_slide(&mut self.volume, param, false, 1, 64);
+            }
+            effect::TONE_PORTAMENTO_VOLUME_SLIDE => {
+                self.tone_portamento(self.tone_portamento_memory);
+                volume_slide(&mut self.volume, param, false, 1, 64);
+            }
+            effect::CHANNEL_VOLUME_SLIDE => {
+                volume_slide(&mut self.channel_volume, param, false, 1, 64)
+            }
+            effect::PANNING_SLIDE => self.panning_slide(module, false),
+            effect::RETRIGGER => self.retrigger_tick(module),
+            effect::NOTE_RETRIGGER if tick % param as u32 == 0 => self.retrigger(module),
+            effect::TREMOLO => self.tremolo(module),
+            effect::TEMPO if x == 0 => {
+                song.tempo = core::cmp::max(song.tempo.saturating_sub(y as u32), 32)
+            }
+            effect::TEMPO if x == 1 => song.tempo = core::cmp::min(song.tempo + y as u32, 255),
+            effect::GLOBAL_VOLUME_SLIDE => {
+                let scale = if module.format == ModuleFormat::Xm {
+                    2
+                } else {
+                    1
+                };
+                volume_slide(&mut song.global_volume, param, false, scale, 128);
+            }
+            effect::KEY_OFF if tick == param as u32 => self.note_off(module),
+            effect::SPECIAL if x == 0xc && tick == y as u32 => self.voice.stop(),
+            _ => (),
+        }
+
+        if effect == effect::PANBRELLO {
+            let value = waveform(
+                self.panbrello_waveform,
+                self.panbrello_position,
+                &mut self.random,
+            );
+            self.panning_offset = value * self.panbrello_depth as i32 >> 5;
+            self.panbrello_position = self.panbrello_position.wrapping_add(self.panbrello_speed);
+        }
+    }
+
+    /// Work out the pitch and volume to play this tick.
+    fn update(&mut self, module: &Module, song: &Song, sample_rate: u32) {
+        let sample = match self.voice.sample {
+            Some(sample) => &module.samples[sample],
+            None => return,
+        };
+
+        let instrument = self.instrument(module);
+
+        // Pitch.
+        let period = self.period + self.period_offset;
+
+        let frequency = if module.linear_slides {
+            self.c5_speed as f32 * exp2((3840 - period) as f32 / 768.0)
+        } else if period > 0 {
+            self.c5_speed as f32 * 1712.0 / period as f32
+        } else {
+            0.0
+        };
+
+        self.voice.step = (frequency * 65536.0 / sample_rate as f32) as u64;
+
+        // Volume.
+        let mut envelope_volume = 64;
+        let mut envelope_panning = 32;
+
+        if let Some(envelope) = instrument.and_then(|i| i.volume_envelope.as_ref()) {
+            let tick = self
+                .volume_envelope_tick
+                .unwrap_or_else(|| envelope.points.last().map_or(0, |p| p.0));
+            envelope_volume = envelope_value(envelope, tick);
+
+            if self.volume_envelope_tick.is_some() {
+                self.volume_envelope_tick = envelope_next(envelope, tick, self.key_on);
+
+                // At the end of an IT envelope, the note fades.
+                if self.volume_envelope_tick.is_none() && module.format == ModuleFormat::It {
+                    self.fading = true;
+
+                    if envelope_volume == 0 {
+                        self.voice.stop();
+                    }
+                }
+            }
+        }
+
+        if let Some(envelope) = instrument.and_then(|i| i.panning_envelope.as_ref()) {
+            let tick = self
+                .panning_envelope_tick
+                .unwrap_or_else(|| envelope.points.last().map_or(0, |p| p.0));
+            envelope_panning = envelope_value(envelope, tick);
+
+            if self.panning_envelope_tick.is_some() {
+                self.panning_envelope_tick = envelope_next(envelope, tick, self.key_on);
+            }
+        }
+
+        if self.fading {
+            let fadeout = instrument.map_or(0, |i| i.fadeout as i32);
+
+            // XM notes without a fadeout keep playing, while IT ones stop.
+            if fadeout == 0 && module.format == ModuleFormat::It {
+                self.fade_volume = 0;
+            }
+
+            self.fade_volume = core::cmp::max(self.fade_volume - fadeout, 0);
+        }
+
+        let volume = if self.muted {
+            0
+        } else {
+            clamp(self.volume + self.volume_offset, 0, 64)
+        };
+
+        let volume = volume as f32 / 64.0 * self.channel_volume as f32 / 64.0
+            * envelope_volume as f32
+            / 64.0
+            * self.fade_volume as f32
+            / 65536.0
+            * song.global_volume as f32
+            / 128.0
+            * sample.global_volume as f32
+            / 64.0
+            * instrument.map_or(128, |i| i.global_volume) as f32
+            / 128.0
+            * module.mix_volume as f32
+            / 128.0;
+
+        // The panning envelope can only go as far as the nearest side.
+        let panning = clamp(self.panning + self.panning_offset, 0, 256);
+        let room = 128 - (panning - 128).abs();
+        let panning = panning + (envelope_panning - 32) * room / 32;
+
+        let left = if panning > 128 {
+            (256 - panning) as f32 / 128.0
+        } else {
+            1.0
+        };
+        let right = if panning < 128 {
+            panning as f32 / 128.0
+        } else {
+            1.0
+        };
+
+        self.voice.left = (volume * left * 4096.0) as i32;
+        self.voice.right = (volume * right * 4096.0) as i32;
+    }
+}
+
+/// Plays a `Module`, as 16-bit stereo at a chosen sample rate.
+///
+/// Playback ends with the last pattern, or when the song loops back to
+/// where it's already been.
+pub struct ModuleDecoder {
+    module: Module,
+    sample_rate: u32,
+    song: Song,
+    channels: Vec<Channel>,
+    /// Frames left of the current tick, and the fraction of a frame carried
+    /// to the next.
+    tick_frames: usize,
+    tick_remainder: u32,
+    ended: bool,
+    mix: Vec<i32>,
+}
+
+impl ModuleDecoder {
+    pub fn new(module: Module, sample_rate: u32) -> Self {
+        let mut decoder = ModuleDecoder {
+            song: Song::new(&module),
+            channels: Vec::new(),
+            module,
+            sample_rate,
+            tick_frames: 0,
+            tick_remainder: 0,
+            ended: false,
+            mix: Vec::new(),
+        };
+
+        decoder.restart();
+        decoder
+    }
+
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// The order and row being played.
+    pub fn position(&self) -> (usize, usize) {
+        (self.song.order, self.song.row)
+    }
+
+    fn restart(&mut self) {
+        let module = &self.module;
+
+        self.song = Song::new(module);
+        self.channels = (0..module.channels)
+            .map(|ch| Channel::new(module, ch))
+            .collect();
+        self.tick_frames = 0;
+        self.tick_remainder = 0;
+        self.ended = !self.song.enter(module, 0, 0);
+    }
+
+    /// Play the next tick, returning false at the end of the song.
+    fn next_tick(&mut self) -> bool {
+        let module = &self.module;
+        let song = &mut self.song;
+
+        if song.tick == 0 {
+            song.loop_row = None;
+        }
+
+        let pattern = &module.patterns[module.orders[song.order] as usize];
+        let cells = &pattern.cells[song.row * module.channels..(song.row + 1) * module.channels];
+        let repeat = song.pattern_delay.map_or(false, |delay| delay < 0x100);
+
+        for (channel, &cell) in self.channels.iter_mut().zip(cells) {
+            channel.period_offset = 0;
+            channel.volume_offset = 0;
+            channel.panning_offset = 0;
+            channel.muted = false;
+
+            if song.tick == 0 && !repeat {
+                // `SDx` delays the whole cell to tick `x`.
+                let delay = match (cell.effect, cell.param >> 4) {
+                    (effect::SPECIAL, 0xd) if cell.param & 0xf != 0 => (cell.param & 0xf) as u32,
+                    _ => 0,
+                };
+
+                channel.volume_command = None;
+                channel.effect = effect::NONE;
+
+                if delay == 0 {
+                    channel.delayed = None;
+                    channel.play_cell(module, song, cell);
+                } else {
+                    channel.delayed = Some((delay, cell));
+                }
+            } else {
+                match channel.delayed {
+                    Some((tick, cell)) if tick == song.tick => {
+                        channel.delayed = None;
+                        channel.play_cell(module, song, cell);
+                    }
+                    _ => channel.tick(module, song),
+                }
+            }
+
+            channel.update(module, song, self.sample_rate);
+        }
+
+        song.tick += 1;
+
+        if song.tick >= song.speed + song.extra_ticks {
+            song.tick = 0;
+
+            // `SEx` repeats the row; its delay is marked as started by
+            // adding 0x100.
+            match song.pattern_delay {
+                Some(delay) if delay & 0xff > 0 => {
+                    song.pattern_delay = Some((delay & 0xff) - 1 | 0x100);
+                    return true;
+                }
+                _ => song.pattern_delay = None,
+            }
+
+            return song.next_row(module);
+        }
+
+        true
+    }
+
+    /// Mix `out.len() / 2` frames of the current tick.
+    fn mix(&mut self, out: &mut [i16]) {
+        self.mix.clear();
+        self.mix.resize(out.len(), 0);
+
+        for channel in &mut self.channels {
+            channel.voice.mix(&self.module.samples, &mut self.mix);
+        }
+
+        for (out, &mix) in out.iter_mut().zip(&self.mix) {
+            *out = clamp(mix, -32768, 32767) as i16;
+        }
+    }
+}
+
+impl PcmDecoder for ModuleDecoder {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> usize {
+        2
+    }
+
+    fn read(&mut self, out: &mut [i16]) -> io::Result<usize> {
+        let frames = out.len() / 2;
+        let mut done = 0;
+
+        while done < frames {
+            if self.tick_frames == 0 {
+                if self.ended {
+                    break;
+                }
+
+                self.ended = !self.next_tick();
+
+                // A tick lasts 2.5 ms at a tempo of 1 bpm.
+                let length = self.sample_rate * 5 + self.tick_remainder;
+                let divisor = self.song.tempo * 2;
+                self.tick_frames = (length / divisor) as usize;
+                self.tick_remainder = length % divisor;
+            }
+
+            let n = core::cmp::min(frames - done, self.tick_frames);
+            self.mix(&mut out[done * 2..(done + n) * 2]);
+            done += n;
+            self.tick_frames -= n;
+        }
+
+        Ok(done * 2)
+    }
+
+    fn rewind(&mut self) -> io::Result<()> {
+        self.restart();
+        Ok(())
+    }
+}
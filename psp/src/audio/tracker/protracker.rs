@@ -0,0 +1,215 @@
+//! ProTracker MODs, and the many trackers which wrote the same format with
+//! more channels.
+
+use super::{
+    c5_speed, effect, name, Bytes, Cell, Instrument, LoopKind, Module, ModuleFormat, Pattern,
+    Sample, SampleLoop, VolumeCommand, MIDDLE_C, NOTE_NONE,
+};
+use crate::io::{self, Error};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The size of everything before the patterns.
+const HEADER_SIZE: usize = 1084;
+
+/// The periods of the three octaves ProTracker plays, at finetune 0, from
+/// C-1.
+const PERIODS: [u16; 36] = [
+    856, 808, 762, 720, 678, 640, 604, 570, 538, 508, 480, 453, 428, 404, 381, 360, 339, 320, 302,
+    285, 269, 254, 240, 226, 214, 202, 190, 180, 170, 160, 151, 143, 135, 127, 120, 113,
+];
+
+/// The note ProTracker calls C-1, whose period is 856.
+const FIRST_NOTE: u8 = MIDDLE_C - 12;
+
+/// The number of channels a MOD's signature stands for.
+fn channels(signature: &[u8]) -> Option<usize> {
+    let digit = |b: u8| {
+        if b.is_ascii_digit() {
+            Some((b - b'0') as usize)
+        } else {
+            None
+        }
+    };
+
+    match signature {
+        b"M.K." | b"M!K!" | b"M&K!" | b"N.T." | b"FLT4" | b"4CHN" => Some(4),
+        b"FLT8" | b"OCTA" | b"CD81" => Some(8),
+        [n, b'C', b'H', b'N'] => digit(*n),
+        [a, b, b'C', b'H'] | [a, b, b'C', b'N'] => Some(digit(*a)? * 10 + digit(*b)?),
+        [b'T', b'D', b'Z', n] => digit(*n),
+        _ => None,
+    }
+    .filter(|&channels| channels > 0 && channels <= 32)
+}
+
+pub(super) fn is_mod(bytes: &[u8]) -> bool {
+    bytes.len() >= HEADER_SIZE && channels(&bytes[1080..1084]).is_some()
+}
+
+/// The note nearest `period`, as the table's periods are sometimes rounded
+/// differently in files.
+fn note(period: u16) -> u8 {
+    let nearest = PERIODS
+        .iter()
+        .enumerate()
+        .min_by_key(|&(_, &p)| (p as i32 - period as i32).abs())
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+
+    FIRST_NOTE + nearest as u8
+}
+
+/// Convert a ProTracker effect to its Impulse Tracker equivalent.
+///
+/// ProTracker slides have no memory, so those with a zero parameter do
+/// nothing at all.
+pub(super) fn convert_effect(command: u8, param: u8) -> (u8, u8) {
+    let (x, y) = (param >> 4, param & 0xf);
+
+    // A volume slide with both nibbles slides up.
+    let volume_slide = if x > 0 { x << 4 } else { y };
+
+    match command {
+        0x0 if param != 0 => (effect::ARPEGGIO, param),
+        0x1 if param != 0 => (effect::PORTAMENTO_UP, param),
+        0x2 if param != 0 => (effect::PORTAMENTO_DOWN, param),
+        0x3 => (effect::TONE_PORTAMENTO, param),
+        0x4 => (effect::VIBRATO, param),
+        0x5 => (effect::TONE_PORTAMENTO_VOLUME_SLIDE, volume_slide),
+        0x6 => (effect::VIBRATO_VOLUME_SLIDE, volume_slide),
+        0x7 => (effect::TREMOLO, param),
+        0x8 => (effect::PANNING, param),
+        0x9 => (effect::OFFSET, param),
+        0xa if param != 0 => (effect::VOLUME_SLIDE, volume_slide),
+        0xb => (effect::JUMP, param),
+        0xc => (effect::VOLUME, core::cmp::min(param, 64)),
+        // Decimal, for some reason.
+        0xd => (effect::BREAK, x * 10 + y),
+        0xe => match x {
+            0x1 if y != 0 => (effect::PORTAMENTO_UP, 0xf0 | y),
+            0x2 if y != 0 => (effect::PORTAMENTO_DOWN, 0xf0 | y),
+            0x3 => (effect::SPECIAL, 0x10 | y),
+            0x4 => (effect::SPECIAL, 0x30 | y),
+            0x6 => (effect::SPECIAL, 0xb0 | y),
+            0x7 => (effect::SPECIAL, 0x40 | y),
+            0x8 => (effect::SPECIAL, 0x80 | y),
+            0x9 if y != 0 => (effect::NOTE_RETRIGGER, y),
+            0xa if y != 0 => (effect::VOLUME_SLIDE, y << 4 | 0xf),
+            0xb if y != 0 => (effect::VOLUME_SLIDE, 0xf0 | y),
+            0xc => (effect::SPECIAL, 0xc0 | y),
+            0xd => (effect::SPECIAL, 0xd0 | y),
+            0xe => (effect::SPECIAL, 0xe0 | y),
+            _ => (effect::NONE, 0),
+        },
+        0xf if param == 0 => (effect::NONE, 0),
+        0xf if param < 0x20 => (effect::SPEED, param),
+        0xf => (effect::TEMPO, param),
+        _ => (effect::NONE, 0),
+    }
+}
+
+pub(super) fn load(bytes: &[u8]) -> io::Result<Module> {
+    let channels = channels(&bytes[1080..1084]).ok_or(Error::InvalidData)?;
+    let mut header = Bytes::new(bytes);
+    let title = name(header.take(20)?);
+
+    let mut samples = Vec::with_capacity(31);
+    let mut lengths = Vec::with_capacity(31);
+
+    for _ in 0..31 {
+        header.skip(22)?;
+
+        let be = |b: &[u8]| u16::from_be_bytes([b[0], b[1]]) as usize * 2;
+        let len = be(header.take(2)?);
+        let finetune = (header.u8()? << 4) as i8 >> 4;
+        let volume = core::cmp::min(header.u8()?, 64);
+        let loop_start = be(header.take(2)?);
+        let loop_len = be(header.take(2)?);
+
+        lengths.push(len);
+        samples.push(Sample {
+            data: Vec::new(),
+            // A loop of one word is how ProTracker says there's none.
+            sample_loop: if loop_len > 2 {
+                SampleLoop::new(loop_start, loop_start + loop_len, LoopKind::Forward, len)
+            } else {
+                None
+            },
+            sustain_loop: None,
+            volume,
+            global_volume: 64,
+            panning: None,
+            // Finetune is in eighths of a semitone.
+            c5_speed: c5_speed(finetune as i32 * 16),
+        });
+    }
+
+    let song_length = core::cmp::min(header.u8()?, 128) as usize;
+    let _restart = header.u8()?;
+    let orders = header.take(128)?;
+    let pattern_count = orders.iter().max().map_or(0, |&max| max as usize + 1);
+
+    let mut data = Bytes::at(bytes, HEADER_SIZE)?;
+    let mut patterns = Vec::with_capacity(pattern_count);
+
+    for _ in 0..pattern_count {
+        let mut cells = Vec::with_capacity(64 * channels);
+
+        for b in data.take(64 * channels * 4)?.chunks_exact(4) {
+            let period = u16::from_be_bytes([b[0] & 0x0f, b[1]]);
+            let instrument = b[0] & 0xf0 | b[2] >> 4;
+            let (effect, param) = convert_effect(b[2] & 0x0f, b[3]);
+
+            cells.push(Cell {
+                note: if period > 0 { note(period) } else { NOTE_NONE },
+                instrument,
+                volume: VolumeCommand::None,
+                effect,
+                param,
+            });
+        }
+
+        patterns.push(Pattern { rows: 64, cells });
+    }
+
+    // Samples cut short at the end of the file are common; keep what's
+    // there.
+    let mut rest = data.remaining();
+
+    for (sample, len) in samples.iter_mut().zip(lengths) {
+        let len = core::cmp::min(len, rest.len());
+        sample.data = rest[..len].iter().map(|&b| (b as i8 as i16) << 8).collect();
+        rest = &rest[len..];
+
+        if let Some(sample_loop) = sample.sample_loop {
+            sample.sample_loop =
+                SampleLoop::new(sample_loop.start, sample_loop.end, sample_loop.kind, len);
+        }
+    }
+
+    // Amiga channels are hard left or right, as left, right, right, left;
+    // played at half that width, as that's tiring on headphones.
+    let panning = (0..channels)
+        .map(|ch| if (ch + 1) & 2 == 0 { 64 } else { 192 })
+        .collect();
+
+    Ok(Module {
+        title,
+        format: ModuleFormat::Mod,
+        channels,
+        orders: orders[..song_length].to_vec(),
+        patterns,
+        instruments: (0..31).map(Instrument::for_sample).collect(),
+        samples,
+        panning,
+        channel_volume: vec![64; channels],
+        speed: 6,
+        tempo: 125,
+        global_volume: 128,
+        mix_volume: 48,
+        linear_slides: false,
+        separate_tone_portamento: true,
+        old_effects: true,
+    })
+}
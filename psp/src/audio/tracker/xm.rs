@@ -0,0 +1,384 @@
+//! FastTracker II's Extended Modules.
+
+use super::{
+    c5_speed, effect, name, Bytes, Cell, Envelope, Instrument, LoopKind, Module, ModuleFormat,
+    Pattern, Sample, SampleLoop, VolumeCommand, NOTE_NONE, NOTE_OFF,
+};
+use crate::io::{self, Error};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// XM's C-0, note 1, is our C-1.
+const FIRST_NOTE: u8 = 12;
+
+/// Convert an XM effect to its Impulse Tracker equivalent.
+fn convert_effect(command: u8, param: u8) -> (u8, u8) {
+    let (x, y) = (param >> 4, param & 0xf);
+
+    // A slide with both nibbles slides up.
+    let slide = if x > 0 { x << 4 } else { y };
+
+    match command {
+        0x0 if param != 0 => (effect::ARPEGGIO, param),
+        0x1 => (effect::PORTAMENTO_UP, param),
+        0x2 => (effect::PORTAMENTO_DOWN, param),
+        0x3 => (effect::TONE_PORTAMENTO, param),
+        0x4 => (effect::VIBRATO, param),
+        0x5 => (effect::TONE_PORTAMENTO_VOLUME_SLIDE, slide),
+        0x6 => (effect::VIBRATO_VOLUME_SLIDE, slide),
+        0x7 => (effect::TREMOLO, param),
+        0x8 => (effect::PANNING, param),
+        0x9 => (effect::OFFSET, param),
+        0xa => (effect::VOLUME_SLIDE, slide),
+        0xb => (effect::JUMP, param),
+        0xc => (effect::VOLUME, core::cmp::min(param, 64)),
+        0xd => (effect::BREAK, x * 10 + y),
+        0xe => match x {
+            0x1 if y != 0 => (effect::PORTAMENTO_UP, 0xf0 | y),
+            0x2 if y != 0 => (effect::PORTAMENTO_DOWN, 0xf0 | y),
+            0x4 => (effect::SPECIAL, 0x30 | y),
+            0x6 => (effect::SPECIAL, 0xb0 | y),
+            0x7 => (effect::SPECIAL, 0x40 | y),
+            0x8 => (effect::SPECIAL, 0x80 | y),
+            0x9 if y != 0 => (effect::NOTE_RETRIGGER, y),
+            0xa if y != 0 => (effect::VOLUME_SLIDE, y << 4 | 0xf),
+            0xb if y != 0 => (effect::VOLUME_SLIDE, 0xf0 | y),
+            0xc => (effect::SPECIAL, 0xc0 | y),
+            0xd => (effect::SPECIAL, 0xd0 | y),
+            0xe => (effect::SPECIAL, 0xe0 | y),
+            _ => (effect::NONE, 0),
+        },
+        0xf if param == 0 => (effect::NONE, 0),
+        0xf if param < 0x20 => (effect::SPEED, param),
+        0xf => (effect::TEMPO, param),
+        // G: global volume, 0 to 64.
+        16 => (effect::GLOBAL_VOLUME, core::cmp::min(param, 64) * 2),
+        // H: doubled as it plays.
+        17 => (effect::GLOBAL_VOLUME_SLIDE, slide),
+        // K and L.
+        20 => (effect::KEY_OFF, param),
+        21 => (effect::ENVELOPE_POSITION, param),
+        // P: right is `x`, where IT's is `y`.
+        25 => (effect::PANNING_SLIDE, if x > 0 { x } else { y << 4 }),
+        // R.
+        27 => (effect::RETRIGGER, param),
+        // T: on for `x + 1` ticks and off for `y + 1`.
+        29 => (
+            effect::TREMOR,
+            core::cmp::min(x + 1, 15) << 4 | core::cmp::min(y + 1, 15),
+        ),
+        // X1 and X2: extra fine portamento.
+        33 => match x {
+            0x1 if y != 0 => (effect::PORTAMENTO_UP, 0xe0 | y),
+            0x2 if y != 0 => (effect::PORTAMENTO_DOWN, 0xe0 | y),
+            _ => (effect::NONE, 0),
+        },
+        _ => (effect::NONE, 0),
+    }
+}
+
+fn convert_volume(volume: u8) -> VolumeCommand {
+    let y = volume & 0xf;
+
+    match volume {
+        0x10..=0x50 => VolumeCommand::Volume(volume - 0x10),
+        0x60..=0x6f => VolumeCommand::VolumeDown(y),
+        0x70..=0x7f => VolumeCommand::VolumeUp(y),
+        0x80..=0x8f => VolumeCommand::FineVolumeDown(y),
+        0x90..=0x9f => VolumeCommand::FineVolumeUp(y),
+        0xa0..=0xaf => VolumeCommand::VibratoSpeed(y),
+        0xb0..=0xbf => VolumeCommand::VibratoDepth(y),
+        0xc0..=0xcf => VolumeCommand::Panning(y * 4),
+        0xd0..=0xdf => VolumeCommand::PanningLeft(y),
+        0xe0..=0xef => VolumeCommand::PanningRight(y),
+        0xf0..=0xff => VolumeCommand::TonePortamento(y << 4),
+        _ => VolumeCommand::None,
+    }
+}
+
+fn read_pattern(data: &mut Bytes, channels: usize) -> io::Result<Pattern> {
+    let header_len = data.u32()? as usize;
+    let _packing = data.u8()?;
+    let rows = data.u16()? as usize;
+    let packed_len = data.u16()? as usize;
+    data.skip(header_len.saturating_sub(9))?;
+
+    // Rows are 1 to 256; an empty pattern may have none stored.
+    let rows = if rows == 0 || rows > 256 { 64 } else { rows };
+    let mut packed = Bytes::new(data.take(packed_len)?);
+    let mut cells = vec![Cell::EMPTY; rows * channels];
+
+    if packed_len == 0 {
+        return Ok(Pattern { rows, cells });
+    }
+
+    for cell in cells.iter_mut() {
+        // A first byte with the top bit set says which fields follow.
+        let first = packed.u8()?;
+        let flags = if first & 0x80 != 0 { first } else { 0x1f };
+        let field = |packed: &mut Bytes, bit: u8, first: &mut Option<u8>| -> io::Result<u8> {
+            if flags & bit == 0 {
+                Ok(0)
+            } else if let Some(b) = first.take() {
+                Ok(b)
+            } else {
+                packed.u8()
+            }
+        };
+
+        let mut first = if first & 0x80 != 0 { None } else { Some(first) };
+        let note = field(&mut packed, 0x01, &mut first)?;
+        let instrument = field(&mut packed, 0x02, &mut first)?;
+        let volume = field(&mut packed, 0x04, &mut first)?;
+        let command = field(&mut packed, 0x08, &mut first)?;
+        let param = field(&mut packed, 0x10, &mut first)?;
+        let (effect, param) = convert_effect(command, param);
+
+        *cell = Cell {
+            note: match note {
+                1..=96 => note - 1 + FIRST_NOTE,
+                97 => NOTE_OFF,
+                _ => NOTE_NONE,
+            },
+            instrument,
+            volume: convert_volume(volume),
+            effect,
+            param,
+        };
+    }
+
+    Ok(Pattern { rows, cells })
+}
+
+/// An envelope from the instrument header, given the offsets of its points
+/// and of its sustain point, which is followed by its loop points.
+fn read_envelope(
+    header: &[u8],
+    points: usize,
+    count: u8,
+    sustain: usize,
+    flags: u8,
+) -> Option<Envelope> {
+    let count = core::cmp::min(count as usize, 12);
+
+    if flags & 1 == 0 || count == 0 {
+        return None;
+    }
+
+    let points = header[points..points + count * 4]
+        .chunks_exact(4)
+        .map(|p| (u16::from_le_bytes([p[0], p[1]]), core::cmp::min(p[2], 64)))
+        .collect();
+
+    let points_at = |i: u8| {
+        if (i as usize) < count {
+            Some(i as usize)
+        } else {
+            None
+        }
+    };
+    let (sustain_point, loop_start, loop_end) =
+        (header[sustain], header[sustain + 1], header[sustain + 2]);
+
+    Some(Envelope {
+        points,
+        sustain: if flags & 2 != 0 {
+            points_at(sustain_point).map(|p| (p, p))
+        } else {
+            None
+        },
+        envelope_loop: if flags & 4 != 0 {
+            match (points_at(loop_start), points_at(loop_end)) {
+                (Some(start), Some(end)) if start <= end => Some((start, end)),
+                _ => None,
+            }
+        } else {
+            None
+        },
+    })
+}
+
+/// Read an instrument and its samples, which are appended to `samples`.
+fn read_instrument(data: &mut Bytes, samples: &mut Vec<Sample>) -> io::Result<Instrument> {
+    let start = data.remaining();
+    let header_len = data.u32()? as usize;
+    let _name = data.take(22)?;
+    let _kind = data.u8()?;
+    let sample_count = data.u16()? as usize;
+
+    let mut instrument = Instrument {
+        keyboard: vec![(0, None); 120],
+        volume_envelope: None,
+        panning_envelope: None,
+        fadeout: 0,
+        global_volume: 128,
+        panning: None,
+    };
+
+    if sample_count == 0 {
+        *data = Bytes::at(start, header_len)?;
+        return Ok(instrument);
+    }
+
+    // The rest of the header, which is always there when there are samples,
+    // whatever size the header says.
+    let mut rest = Bytes::new(start);
+    rest.skip(29)?;
+    let sample_header_len = rest.u32()? as usize;
+    let header = rest.take(210)?;
+
+    let first_sample = samples.len();
+
+    for (i, &sample) in header[..96].iter().enumerate() {
+        if (sample as usize) < sample_count {
+            let note = FIRST_NOTE + i as u8;
+            instrument.keyboard[note as usize] =
+                (note, Some((first_sample + sample as usize) as u16));
+        }
+    }
+
+    // Offsets within `header`, which starts at the keyboard.
+    instrument.volume_envelope = read_envelope(header, 96, header[192], 194, header[200]);
+    instrument.panning_envelope = read_envelope(header, 144, header[193], 197, header[201]);
+
+    // Fadeout is of 32768 a tick.
+    instrument.fadeout = u16::from_le_bytes([header[206], header[207]]) as u32 * 2;
+
+    *data = Bytes::at(start, header_len)?;
+
+    let mut lengths = Vec::with_capacity(sample_count);
+
+    for _ in 0..sample_count {
+        let mut sample_header = Bytes::new(data.take(sample_header_len)?);
+        let len = sample_header.u32()? as usize;
+        let loop_start = sample_header.u32()? as usize;
+        let loop_len = sample_header.u32()? as usize;
+        let volume = core::cmp::min(sample_header.u8()?, 64);
+        let finetune = sample_header.u8()? as i8;
+        let flags = sample_header.u8()?;
+        let panning = sample_header.u8()?;
+        let relative_note = sample_header.u8()? as i8;
+
+        let wide = flags & 0x10 != 0;
+        let (len, loop_start, loop_len) = if wide {
+            (len / 2, loop_start / 2, loop_len / 2)
+        } else {
+            (len, loop_start, loop_len)
+        };
+
+        let kind = match flags & 3 {
+            1 => Some(LoopKind::Forward),
+            2 | 3 => Some(LoopKind::PingPong),
+            _ => None,
+        };
+
+        let loop_end = loop_start
+            .checked_add(loop_len)
+            .ok_or(Error::InvalidData)?;
+
+        lengths.push((len, wide));
+        samples.push(Sample {
+            data: Vec::new(),
+            sample_loop: kind.and_then(|kind| SampleLoop::new(loop_start, loop_end, kind, len)),
+            sustain_loop: None,
+            volume,
+            global_volume: 64,
+            panning: Some(panning),
+            c5_speed: c5_speed(relative_note as i32 * 128 + finetune as i32),
+        });
+    }
+
+    for (sample, (len, wide)) in samples[first_sample..].iter_mut().zip(lengths) {
+        // Samples are stored as differences from the last.
+        let mut old = 0i16;
+
+        sample.data = if wide {
+            let bytes = data.take(len * 2)?;
+            bytes
+                .chunks_exact(2)
+                .map(|b| {
+                    old = old.wrapping_add(i16::from_le_bytes([b[0], b[1]]));
+                    old
+                })
+                .collect()
+        } else {
+            let bytes = data.take(len)?;
+            bytes
+                .iter()
+                .map(|&b| {
+                    old = old.wrapping_add((b as i8 as i16) << 8);
+                    old
+                })
+                .collect()
+        };
+    }
+
+    Ok(instrument)
+}
+
+pub(super) fn load(bytes: &[u8]) -> io::Result<Module> {
+    let mut header = Bytes::at(bytes, 17)?;
+    let title = name(header.take(20)?);
+    header.skip(1 + 20 + 2)?;
+
+    let header_len = header.u32()? as usize;
+    let song_length = header.u16()? as usize;
+    let _restart = header.u16()?;
+    let channels = header.u16()? as usize;
+    let pattern_count = header.u16()? as usize;
+    let instrument_count = header.u16()? as usize;
+    let flags = header.u16()?;
+    let speed = header.u16()?;
+    let tempo = header.u16()?;
+    let orders = header.take(256)?;
+
+    if channels == 0 || channels > 64 {
+        return Err(Error::InvalidData);
+    }
+
+    let orders = orders[..core::cmp::min(song_length, 256)].to_vec();
+
+    // Header sizes are counted from their own size field.
+    let mut data = Bytes::at(bytes, 60 + header_len)?;
+    let mut patterns = Vec::with_capacity(pattern_count);
+
+    for _ in 0..pattern_count {
+        patterns.push(read_pattern(&mut data, channels)?);
+    }
+
+    // Orders may name patterns which aren't stored; they're empty.
+    let used = orders.iter().max().map_or(0, |&max| max as usize + 1);
+
+    while patterns.len() < used {
+        patterns.push(Pattern {
+            rows: 64,
+            cells: vec![Cell::EMPTY; 64 * channels],
+        });
+    }
+
+    let mut instruments = Vec::with_capacity(instrument_count);
+    let mut samples = Vec::new();
+
+    for _ in 0..instrument_count {
+        instruments.push(read_instrument(&mut data, &mut samples)?);
+    }
+
+    Ok(Module {
+        title,
+        format: ModuleFormat::Xm,
+        channels,
+        orders,
+        patterns,
+        instruments,
+        samples,
+        panning: vec![128; channels],
+        channel_volume: vec![64; channels],
+        speed: core::cmp::max(core::cmp::min(speed, 31), 1) as u8,
+        tempo: core::cmp::max(core::cmp::min(tempo, 255), 32) as u8,
+        global_volume: 128,
+        mix_volume: 48,
+        linear_slides: flags & 1 != 0,
+        separate_tone_portamento: true,
+        old_effects: false,
+    })
+}
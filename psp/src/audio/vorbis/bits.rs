@@ -0,0 +1,90 @@
+/// Reads the fields of a packet, which Vorbis packs from the least
+/// significant bit of each byte up.
+///
+/// Once a read runs past the end of the packet, it and every later read
+/// fail, as the end of a packet is a normal way to end its audio early.
+pub(super) struct BitReader<'a> {
+    data: &'a [u8],
+    /// The position in bits.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// The length of the packet in bits.
+    pub fn len(&self) -> usize {
+        self.data.len() * 8
+    }
+
+    /// The number of bits left to read.
+    pub fn bits_left(&self) -> usize {
+        self.len().saturating_sub(self.pos)
+    }
+
+    /// The next 32 bits, with zeros past the end.
+    #[inline]
+    pub fn peek(&self) -> u32 {
+        let byte = self.pos >> 3;
+
+        let word = if byte + 5 <= self.data.len() {
+            let b = &self.data[byte..byte + 5];
+            b[0] as u64
+                | (b[1] as u64) << 8
+                | (b[2] as u64) << 16
+                | (b[3] as u64) << 24
+                | (b[4] as u64) << 32
+        } else {
+            let mut word = 0;
+
+            for (i, &b) in self.data.iter().skip(byte).enumerate() {
+                word |= (b as u64) << (i * 8);
+            }
+
+            word
+        };
+
+        (word >> (self.pos & 7)) as u32
+    }
+
+    /// Move past `bits` bits, returning false if that was past the end.
+    #[inline]
+    pub fn skip(&mut self, bits: u32) -> bool {
+        let end = self.pos + bits as usize;
+
+        if end > self.len() {
+            self.pos = self.len() + 1;
+            false
+        } else {
+            self.pos = end;
+            true
+        }
+    }
+
+    /// Read an unsigned field of up to 32 bits.
+    #[inline]
+    pub fn read(&mut self, bits: u32) -> Option<u32> {
+        if bits == 0 {
+            return if self.pos > self.len() { None } else { Some(0) };
+        }
+
+        let value = self.peek() & (!0 >> (32 - bits));
+
+        if self.skip(bits) {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    pub fn read_bool(&mut self) -> Option<bool> {
+        self.read(1).map(|bit| bit != 0)
+    }
+}
+
+/// The number of bits needed to hold `value`.
+pub(super) fn ilog(value: u32) -> u32 {
+    32 - value.leading_zeros()
+}
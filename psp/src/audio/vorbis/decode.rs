@@ -0,0 +1,679 @@
+//! Audio packets: floors, residues, channel coupling, the inverse MDCT and
+//! overlapping each block with the last.
+
+use super::bits::{ilog, BitReader};
+use super::mdct::{self, Imdct};
+use super::setup::{Codebook, Floor, Ident, Residue, Setup};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The range of floor values for each multiplier.
+const FLOOR1_RANGES: [i32; 4] = [256, 128, 86, 64];
+
+/// The gain of each floor value, from the specification.
+#[rustfmt::skip]
+static FLOOR1_INVERSE_DB: [f32; 256] = [
+    1.0649863e-07, 1.1341951e-07, 1.2079015e-07, 1.2863978e-07,
+    1.3699951e-07, 1.4590251e-07, 1.5538408e-07, 1.6548181e-07,
+    1.7623575e-07, 1.8768855e-07, 1.9988561e-07, 2.1287530e-07,
+    2.2670913e-07, 2.4144197e-07, 2.5713223e-07, 2.7384213e-07,
+    2.9163793e-07, 3.1059021e-07, 3.3077411e-07, 3.5226968e-07,
+    3.7516214e-07, 3.9954229e-07, 4.2550680e-07, 4.5315863e-07,
+    4.8260743e-07, 5.1396998e-07, 5.4737065e-07, 5.8294187e-07,
+    6.2082472e-07, 6.6116941e-07, 7.0413592e-07, 7.4989464e-07,
+    7.9862701e-07, 8.5052630e-07, 9.0579828e-07, 9.6466216e-07,
+    1.0273513e-06, 1.0941144e-06, 1.1652161e-06, 1.2409384e-06,
+    1.3215816e-06, 1.4074654e-06, 1.4989305e-06, 1.5963394e-06,
+    1.7000785e-06, 1.8105592e-06, 1.9282195e-06, 2.0535261e-06,
+    2.1869758e-06, 2.3290978e-06, 2.4804557e-06, 2.6416497e-06,
+    2.8133190e-06, 2.9961443e-06, 3.1908506e-06, 3.3982101e-06,
+    3.6190449e-06, 3.8542308e-06, 4.1047004e-06, 4.3714470e-06,
+    4.6555282e-06, 4.9580707e-06, 5.2802740e-06, 5.6234160e-06,
+    5.9888572e-06, 6.3780469e-06, 6.7925283e-06, 7.2339451e-06,
+    7.7040476e-06, 8.2047000e-06, 8.7378876e-06, 9.3057248e-06,
+    9.9104632e-06, 1.0554501e-05, 1.1240392e-05, 1.1970856e-05,
+    1.2748789e-05, 1.3577278e-05, 1.4459606e-05, 1.5399272e-05,
+    1.6400004e-05, 1.7465768e-05, 1.8600792e-05, 1.9809576e-05,
+    2.1096914e-05, 2.2467911e-05, 2.3928002e-05, 2.5482978e-05,
+    2.7139006e-05, 2.8902651e-05, 3.0780908e-05, 3.2781225e-05,
+    3.4911534e-05, 3.7180282e-05, 3.9596466e-05, 4.2169667e-05,
+    4.4910090e-05, 4.7828601e-05, 5.0936773e-05, 5.4246931e-05,
+    5.7772202e-05, 6.1526565e-05, 6.5524908e-05, 6.9783085e-05,
+    7.4317983e-05, 7.9147585e-05, 8.4291040e-05, 8.9768747e-05,
+    9.5602426e-05, 0.00010181521, 0.00010843174, 0.00011547824,
+    0.00012298267, 0.00013097477, 0.00013948625, 0.00014855085,
+    0.00015820453, 0.00016848555, 0.00017943469, 0.00019109536,
+    0.00020351382, 0.00021673929, 0.00023082423, 0.00024582449,
+    0.00026179955, 0.00027881276, 0.00029693158, 0.00031622787,
+    0.00033677814, 0.00035866388, 0.00038197188, 0.00040679456,
+    0.00043323036, 0.00046138411, 0.00049136745, 0.00052329927,
+    0.00055730621, 0.00059352311, 0.00063209358, 0.00067317058,
+    0.00071691700, 0.00076350630, 0.00081312324, 0.00086596457,
+    0.00092223983, 0.00098217216, 0.0010459992, 0.0011139742,
+    0.0011863665, 0.0012634633, 0.0013455702, 0.0014330129,
+    0.0015261382, 0.0016253153, 0.0017309374, 0.0018434235,
+    0.0019632195, 0.0020908006, 0.0022266726, 0.0023713743,
+    0.0025254795, 0.0026895994, 0.0028643847, 0.0030505286,
+    0.0032487691, 0.0034598925, 0.0036847358, 0.0039241906,
+    0.0041792066, 0.0044507950, 0.0047400328, 0.0050480668,
+    0.0053761186, 0.0057254891, 0.0060975636, 0.0064938176,
+    0.0069158225, 0.0073652516, 0.0078438871, 0.0083536271,
+    0.0088964928, 0.009474637, 0.010090352, 0.010746080,
+    0.011444421, 0.012188144, 0.012980198, 0.013823725,
+    0.014722068, 0.015678791, 0.016697687, 0.017782797,
+    0.018938423, 0.020169149, 0.021479854, 0.022875735,
+    0.024362330, 0.025945531, 0.027631618, 0.029427276,
+    0.031339626, 0.033376252, 0.035545228, 0.037855157,
+    0.040315199, 0.042935108, 0.045725273, 0.048696758,
+    0.051861348, 0.055231591, 0.058820850, 0.062643361,
+    0.066714279, 0.071049749, 0.075666962, 0.080584227,
+    0.085821044, 0.091398179, 0.097337747, 0.10366330,
+    0.11039993, 0.11757434, 0.12521498, 0.13335215,
+    0.14201813, 0.15124727, 0.16107617, 0.17154380,
+    0.18269168, 0.19456402, 0.20720788, 0.22067342,
+    0.23501402, 0.25028656, 0.26655159, 0.28387361,
+    0.30232132, 0.32196786, 0.34289114, 0.36517414,
+    0.38890521, 0.41417847, 0.44109412, 0.46975890,
+    0.50028648, 0.53279791, 0.56742212, 0.60429640,
+    0.64356699, 0.68538959, 0.72993007, 0.77736504,
+    0.82788260, 0.88168307, 0.9389798, 1.,
+];
+
+/// Decodes audio packets into interleaved 16-bit samples, keeping the
+/// right half of each block to overlap with the next.
+pub(super) struct Synthesis {
+    channels: usize,
+    block_sizes: [usize; 2],
+    windows: [Vec<f32>; 2],
+    imdct: [Imdct; 2],
+    /// The spectrum of each channel, half a long block apart.
+    spectra: Vec<f32>,
+    /// The output of the inverse MDCT, for one channel at a time.
+    time: Vec<f32>,
+    /// The unwindowed right half of the last block of each channel.
+    previous: Vec<Vec<f32>>,
+    has_previous: bool,
+    /// Per channel floor values, and whether the floor is used.
+    floor_ys: Vec<Vec<i32>>,
+    floor_used: Vec<bool>,
+    /// Scratch for floor curves and residues.
+    final_ys: Vec<i32>,
+    step2: Vec<bool>,
+    interleaved: Vec<f32>,
+    classifications: Vec<u32>,
+}
+
+impl Synthesis {
+    pub fn new(ident: &Ident) -> Self {
+        let [short, long] = ident.block_sizes;
+        let channels = ident.channels;
+
+        Self {
+            channels,
+            block_sizes: ident.block_sizes,
+            windows: [mdct::window(short), mdct::window(long)],
+            imdct: [Imdct::new(short), Imdct::new(long)],
+            spectra: vec![0.0; channels * long / 2],
+            time: vec![0.0; long],
+            previous: vec![Vec::with_capacity(long / 2); channels],
+            has_previous: false,
+            floor_ys: vec![Vec::new(); channels],
+            floor_used: vec![false; channels],
+            final_ys: Vec::new(),
+            step2: Vec::new(),
+            interleaved: Vec::new(),
+            classifications: Vec::new(),
+        }
+    }
+
+    /// Forget the last block, as after seeking.
+    pub fn reset(&mut self) {
+        self.has_previous = false;
+    }
+
+    /// Decode `packet`, appending its samples to `out` and returning how
+    /// many frames there were. The first block after a reset only primes the
+    /// overlap, and has none.
+    ///
+    /// Packets which aren't audio, or are too broken to decode, are skipped.
+    pub fn decode(&mut self, setup: &Setup, packet: &[u8], out: &mut Vec<i16>) -> usize {
+        let mut r = BitReader::new(packet);
+
+        // Zero-length packets, and headers, are ignored.
+        match r.read_bool() {
+            Some(false) => (),
+            _ => return 0,
+        }
+
+        let mode = match r.read(ilog(setup.modes.len() as u32 - 1)) {
+            Some(mode) => mode as usize,
+            None => return 0,
+        };
+
+        let mode = match setup.modes.get(mode) {
+            Some(mode) => mode,
+            None => return 0,
+        };
+
+        let (previous_long, next_long) = if mode.long {
+            match (r.read_bool(), r.read_bool()) {
+                (Some(previous), Some(next)) => (previous, next),
+                _ => return 0,
+            }
+        } else {
+            (false, false)
+        };
+
+        let n = self.block_sizes[mode.long as usize];
+        let half = n / 2;
+        let stride = self.block_sizes[1] / 2;
+        let mapping = &setup.mappings[mode.mapping];
+
+        for ch in 0..self.channels {
+            let (floor, _) = mapping.submaps[mapping.mux[ch]];
+            let ys = &mut self.floor_ys[ch];
+            self.floor_used[ch] = read_floor1(&mut r, &setup.books, &setup.floors[floor], ys);
+        }
+
+        // Coupled channels need both residues if either is used.
+        let mut no_residue: [bool; 256] = [false; 256];
+
+        for ch in 0..self.channels {
+            no_residue[ch] = !self.floor_used[ch];
+        }
+
+        for &(magnitude, angle) in &mapping.couplings {
+            if !(no_residue[magnitude] && no_residue[angle]) {
+                no_residue[magnitude] = false;
+                no_residue[angle] = false;
+            }
+        }
+
+        for ch in 0..self.channels {
+            for x in &mut self.spectra[ch * stride..ch * stride + half] {
+                *x = 0.0;
+            }
+        }
+
+        for (submap, &(_, residue)) in mapping.submaps.iter().enumerate() {
+            let mut vectors = [0; 256];
+            let mut skip = [false; 256];
+            let mut count = 0;
+
+            for ch in 0..self.channels {
+                if mapping.mux[ch] == submap {
+                    vectors[count] = ch * stride;
+                    skip[count] = no_residue[ch];
+                    count += 1;
+                }
+            }
+
+            let residue = &setup.residues[residue];
+
+            if residue.kind == 2 {
+                if skip[..count].iter().all(|&skip| skip) {
+                    continue;
+                }
+
+                self.interleaved.clear();
+                self.interleaved.resize(count * half, 0.0);
+
+                read_residue(
+                    &mut r,
+                    &setup.books,
+                    residue,
+                    &mut self.interleaved,
+                    &[0],
+                    &[false],
+                    count * half,
+                    &mut self.classifications,
+                );
+
+                for (i, frame) in self.interleaved.chunks_exact(count).enumerate() {
+                    for (j, &x) in frame.iter().enumerate() {
+                        self.spectra[vectors[j] + i] = x;
+                    }
+                }
+            } else {
+                read_residue(
+                    &mut r,
+                    &setup.books,
+                    residue,
+                    &mut self.spectra,
+                    &vectors[..count],
+                    &skip[..count],
+                    half,
+                    &mut self.classifications,
+                );
+            }
+        }
+
+        for &(magnitude, angle) in mapping.couplings.iter().rev() {
+            for i in 0..half {
+                let m = self.spectra[magnitude * stride + i];
+                let a = self.spectra[angle * stride + i];
+
+                let (m, a) = if m > 0.0 {
+                    if a > 0.0 {
+                        (m, m - a)
+                    } else {
+                        (m + a, m)
+                    }
+                } else if a > 0.0 {
+                    (m, m + a)
+                } else {
+                    (m - a, m)
+                };
+
+                self.spectra[magnitude * stride + i] = m;
+                self.spectra[angle * stride + i] = a;
+            }
+        }
+
+        // The window: each side is either a full half block, or the short
+        // slope in the middle of a long block's quarter.
+        let short = self.block_sizes[0];
+
+        let (left_start, left_window) = if mode.long && !previous_long {
+            ((n - short) / 4, &self.windows[0])
+        } else {
+            (0, &self.windows[mode.long as usize])
+        };
+
+        let (right_start, right_end) = if mode.long && !next_long {
+            ((3 * n - short) / 4, (3 * n + short) / 4)
+        } else {
+            (half, n)
+        };
+
+        let frames = if self.has_previous {
+            right_start - left_start
+        } else {
+            0
+        };
+
+        // A previous block longer than this one's slope means the stream
+        // jumped; start afresh rather than overlap what doesn't fit.
+        let overlap = self.previous[0].len();
+        let frames = if overlap > left_window.len() {
+            0
+        } else {
+            frames
+        };
+
+        let first = out.len();
+        out.resize(first + frames * self.channels, 0);
+
+        for ch in 0..self.channels {
+            let spectrum = &mut self.spectra[ch * stride..ch * stride + half];
+
+            if self.floor_used[ch] {
+                let floor = &setup.floors[mapping.submaps[mapping.mux[ch]].0];
+                apply_floor1(
+                    floor,
+                    &self.floor_ys[ch],
+                    &mut self.final_ys,
+                    &mut self.step2,
+                    spectrum,
+                );
+            } else {
+                for x in spectrum.iter_mut() {
+                    *x = 0.0;
+                }
+            }
+
+            let time = &mut self.time[..n];
+            self.imdct[mode.long as usize].inverse(spectrum, time);
+
+            if frames > 0 {
+                let previous = &self.previous[ch];
+
+                for i in 0..overlap {
+                    time[left_start + i] = time[left_start + i] * left_window[i]
+                        + previous[i] * left_window[overlap - 1 - i];
+                }
+
+                let samples = out[first..].iter_mut().skip(ch).step_by(self.channels);
+
+                for (sample, &x) in samples.zip(&time[left_start..right_start]) {
+                    *sample = to_i16(x);
+                }
+            }
+
+            let previous = &mut self.previous[ch];
+            previous.clear();
+            previous.extend_from_slice(&time[right_start..right_end]);
+        }
+
+        self.has_previous = true;
+
+        frames
+    }
+}
+
+#[inline]
+fn to_i16(x: f32) -> i16 {
+    let x = x * 32768.0;
+
+    if x > 32767.0 {
+        32767
+    } else if x < -32768.0 {
+        -32768
+    } else {
+        x as i16
+    }
+}
+
+/// Read a channel's floor values into `ys`, returning false if the floor is
+/// unused, which makes the channel silent.
+fn read_floor1(r: &mut BitReader, books: &[Codebook], floor: &Floor, ys: &mut Vec<i32>) -> bool {
+    ys.clear();
+
+    // Running out of packet here also means an unused floor.
+    if r.read_bool() != Some(true) {
+        return false;
+    }
+
+    let range = FLOOR1_RANGES[floor.multiplier as usize - 1];
+    let bits = ilog(range as u32 - 1);
+
+    for _ in 0..2 {
+        match r.read(bits) {
+            Some(y) => ys.push(y as i32),
+            None => return false,
+        }
+    }
+
+    for &class in &floor.partitions {
+        let class = &floor.classes[class as usize];
+        let mask = (1 << class.subclass_bits) - 1;
+
+        let mut subclasses = if class.subclass_bits > 0 {
+            match books[class.masterbook].decode(r) {
+                Some(entry) => entry,
+                None => return false,
+            }
+        } else {
+            0
+        };
+
+        for _ in 0..class.dimensions {
+            let book = class.subclass_books[(subclasses & mask) as usize];
+            subclasses >>= class.subclass_bits;
+
+            let y = match book {
+                Some(book) => match books[book].decode(r) {
+                    Some(y) => y,
+                    None => return false,
+                },
+                None => 0,
+            };
+
+            ys.push(y as i32);
+        }
+    }
+
+    true
+}
+
+/// Predict a point on the line between two others.
+///
+/// Corrupt packets can push Y out of range, so this wraps rather than
+/// overflows; the final values are clamped.
+fn render_point(x0: u32, y0: i32, x1: u32, y1: i32, x: u32) -> i32 {
+    let dy = y1.wrapping_sub(y0);
+    let adx = x1 - x0;
+    let err = (dy.wrapping_abs() as u32).wrapping_mul(x - x0);
+    let off = (err / adx) as i32;
+
+    if dy < 0 {
+        y0.wrapping_sub(off)
+    } else {
+        y0.wrapping_add(off)
+    }
+}
+
+/// Multiply `spectrum` by the floor curve through the decoded points.
+fn apply_floor1(
+    floor: &Floor,
+    ys: &[i32],
+    final_ys: &mut Vec<i32>,
+    step2: &mut Vec<bool>,
+    spectrum: &mut [f32],
+) {
+    let range = FLOOR1_RANGES[floor.multiplier as usize - 1];
+    let xs = &floor.xs;
+
+    final_ys.clear();
+    final_ys.extend_from_slice(&ys[..2]);
+    step2.clear();
+    step2.extend_from_slice(&[true, true]);
+
+    // Each point is an offset from the line between its neighbors.
+    for (i, &(low, high)) in floor.neighbors.iter().enumerate() {
+        let i = i + 2;
+        let (low, high) = (low as usize, high as usize);
+
+        let predicted = render_point(xs[low], final_ys[low], xs[high], final_ys[high], xs[i]);
+        let value = ys[i];
+        let high_room = range.wrapping_sub(predicted);
+        let low_room = predicted;
+        let room = core::cmp::min(high_room, low_room).wrapping_mul(2);
+
+        if value != 0 {
+            step2[low] = true;
+            step2[high] = true;
+            step2.push(true);
+
+            final_ys.push(if value >= room {
+                if high_room > low_room {
+                    value.wrapping_sub(low_room).wrapping_add(predicted)
+                } else {
+                    predicted
+                        .wrapping_sub(value)
+                        .wrapping_add(high_room)
+                        .wrapping_sub(1)
+                }
+            } else if value & 1 == 1 {
+                predicted.wrapping_sub((value + 1) >> 1)
+            } else {
+                predicted.wrapping_add(value >> 1)
+            });
+        } else {
+            step2.push(false);
+            final_ys.push(predicted);
+        }
+    }
+
+    // Out of range values, including negative ones, go to the top.
+    for y in final_ys.iter_mut() {
+        if *y as u32 >= range as u32 {
+            *y = range - 1;
+        }
+    }
+
+    let multiplier = floor.multiplier as i32;
+    let n = spectrum.len();
+    let (mut lx, mut ly) = (0, final_ys[floor.sorted[0] as usize] * multiplier);
+    let (mut hx, mut hy) = (0, 0);
+
+    for &i in &floor.sorted[1..] {
+        let i = i as usize;
+
+        if step2[i] {
+            hx = xs[i] as usize;
+            hy = final_ys[i] * multiplier;
+            render_line(lx, ly, hx, hy, spectrum);
+            lx = hx;
+            ly = hy;
+        }
+    }
+
+    if hx < n {
+        render_line(hx, hy, n, hy, spectrum);
+    }
+}
+
+/// Multiply `spectrum` from `x0` up to `x1` by the floor line between
+/// `y0` and `y1`, stopping at its end.
+fn render_line(x0: usize, y0: i32, x1: usize, y1: i32, spectrum: &mut [f32]) {
+    let dy = y1 - y0;
+    let adx = (x1 - x0) as i32;
+    let base = dy / adx;
+    let step = if dy < 0 { base - 1 } else { base + 1 };
+    let ady = dy.abs() - base.abs() * adx;
+
+    let end = core::cmp::min(x1, spectrum.len());
+    let mut y = y0;
+    let mut err = 0;
+
+    for x in x0..end {
+        spectrum[x] *= FLOOR1_INVERSE_DB[y as usize];
+
+        err += ady;
+
+        if err >= adx {
+            err -= adx;
+            y += step;
+        } else {
+            y += base;
+        }
+    }
+}
+
+/// Add the residue vectors at `vectors` in `data`, each `len` long, for
+/// those not marked to `skip`. The end of the packet ends them early.
+#[allow(clippy::too_many_arguments)]
+fn read_residue(
+    r: &mut BitReader,
+    books: &[Codebook],
+    residue: &Residue,
+    data: &mut [f32],
+    vectors: &[usize],
+    skip: &[bool],
+    len: usize,
+    classifications: &mut Vec<u32>,
+) {
+    let begin = core::cmp::min(residue.begin, len);
+    let end = core::cmp::min(residue.end, len);
+    let partitions = (end - begin) / residue.partition_size;
+
+    if partitions == 0 {
+        return;
+    }
+
+    let classbook = &books[residue.classbook];
+    let per_codeword = classbook.dimensions;
+    let class_stride = partitions + per_codeword;
+
+    classifications.clear();
+    classifications.resize(vectors.len() * class_stride, 0);
+
+    for pass in 0..8 {
+        let mut partition = 0;
+
+        while partition < partitions {
+            if pass == 0 {
+                for (j, &skip) in skip.iter().enumerate() {
+                    if skip {
+                        continue;
+                    }
+
+                    let mut entry = match classbook.decode(r) {
+                        Some(entry) => entry,
+                        None => return,
+                    };
+
+                    for i in (0..per_codeword).rev() {
+                        classifications[j * class_stride + partition + i] =
+                            entry % residue.classifications;
+                        entry /= residue.classifications;
+                    }
+                }
+            }
+
+            for _ in 0..per_codeword {
+                if partition >= partitions {
+                    break;
+                }
+
+                for (j, &skip) in skip.iter().enumerate() {
+                    if skip {
+                        continue;
+                    }
+
+                    let class = classifications[j * class_stride + partition] as usize;
+
+                    let book = match residue.books[class][pass] {
+                        Some(book) => &books[book],
+                        None => continue,
+                    };
+
+                    let start = vectors[j] + begin + partition * residue.partition_size;
+                    let v = &mut data[start..vectors[j] + len];
+
+                    let ok = if residue.kind == 0 {
+                        read_partition_interleaved(r, book, residue.partition_size, v)
+                    } else {
+                        read_partition(r, book, residue.partition_size, v)
+                    };
+
+                    if !ok {
+                        return;
+                    }
+                }
+
+                partition += 1;
+            }
+        }
+    }
+}
+
+/// Add a residue 0 partition, whose vectors are interleaved.
+fn read_partition_interleaved(
+    r: &mut BitReader,
+    book: &Codebook,
+    size: usize,
+    v: &mut [f32],
+) -> bool {
+    let step = size / book.dimensions;
+
+    for i in 0..step {
+        let entry = match book.decode_vector(r) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        for (j, &x) in entry.iter().enumerate() {
+            v[i + j * step] += x;
+        }
+    }
+
+    true
+}
+
+/// Add a residue 1 or 2 partition, whose vectors follow each other.
+fn read_partition(r: &mut BitReader, book: &Codebook, size: usize, v: &mut [f32]) -> bool {
+    let mut i = 0;
+
+    while i < size {
+        let entry = match book.decode_vector(r) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        // A vector running past the end is dropped.
+        let v = match v.get_mut(i..i + entry.len()) {
+            Some(v) => v,
+            None => break,
+        };
+
+        for (v, &x) in v.iter_mut().zip(entry) {
+            *v += x;
+        }
+
+        i += entry.len();
+    }
+
+    true
+}
@@ -0,0 +1,185 @@
+//! The inverse MDCT, through a complex FFT of a quarter of the block size,
+//! and the trigonometry to set it up, as `core` has none.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::f64::consts::{FRAC_PI_2, PI};
+
+/// Sine and cosine of `x`, to double precision.
+pub(super) fn sin_cos(x: f64) -> (f64, f64) {
+    // Reduce to within a quarter turn of zero.
+    let q = x / FRAC_PI_2;
+    let q = if q < 0.0 { q - 0.5 } else { q + 0.5 } as i64;
+    let r = x - q as f64 * FRAC_PI_2;
+
+    let r2 = r * r;
+    let mut sin = 0.0;
+    let mut cos = 0.0;
+
+    // Taylor series, which are exact to double precision by the 17th power
+    // over a quarter turn.
+    for k in (0..9).rev() {
+        let k = k as f64;
+        sin = 1.0 - sin * r2 / ((2.0 * k + 2.0) * (2.0 * k + 3.0));
+        cos = 1.0 - cos * r2 / ((2.0 * k + 1.0) * (2.0 * k + 2.0));
+    }
+
+    let sin = sin * r;
+
+    match q & 3 {
+        0 => (sin, cos),
+        1 => (cos, -sin),
+        2 => (-sin, -cos),
+        _ => (-cos, sin),
+    }
+}
+
+/// The rising half of the Vorbis window for a block of `n` samples.
+pub(super) fn window(n: usize) -> Vec<f32> {
+    let half = n / 2;
+
+    (0..half)
+        .map(|i| {
+            let (s, _) = sin_cos((i as f64 + 0.5) / half as f64 * FRAC_PI_2);
+            sin_cos(FRAC_PI_2 * s * s).0 as f32
+        })
+        .collect()
+}
+
+#[derive(Copy, Clone, Default)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+    #[inline]
+    fn mul(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
+
+/// The inverse MDCT of one block size.
+pub(super) struct Imdct {
+    n: usize,
+    /// `e^(-i·2π(k + 1/8)/n)`, rotating before and after the FFT.
+    twiddle: Vec<Complex>,
+    /// `e^(-i·2πk/(n/4))` for the first half of the FFT.
+    roots: Vec<Complex>,
+    bit_reverse: Vec<u16>,
+    scratch: Vec<Complex>,
+}
+
+impl Imdct {
+    pub fn new(n: usize) -> Self {
+        let n4 = n / 4;
+
+        let twiddle = (0..n4)
+            .map(|k| {
+                let (s, c) = sin_cos(2.0 * PI * (k as f64 + 0.125) / n as f64);
+                Complex {
+                    re: c as f32,
+                    im: -s as f32,
+                }
+            })
+            .collect();
+
+        let roots = (0..n4 / 2)
+            .map(|k| {
+                let (s, c) = sin_cos(2.0 * PI * k as f64 / n4 as f64);
+                Complex {
+                    re: c as f32,
+                    im: -s as f32,
+                }
+            })
+            .collect();
+
+        let bits = n4.trailing_zeros();
+        let bit_reverse = (0..n4)
+            .map(|i| ((i as u32).reverse_bits() >> (32 - bits)) as u16)
+            .collect();
+
+        Self {
+            n,
+            twiddle,
+            roots,
+            bit_reverse,
+            scratch: vec![Complex::default(); n4],
+        }
+    }
+
+    /// An in-place radix-2 FFT of `scratch`, already in bit-reversed order.
+    fn fft(&mut self) {
+        let len = self.scratch.len();
+        let z = &mut self.scratch[..];
+        let mut size = 2;
+
+        while size <= len {
+            let half = size / 2;
+            let stride = len / size;
+
+            for start in (0..len).step_by(size) {
+                for k in 0..half {
+                    let w = self.roots[k * stride];
+                    let a = z[start + k];
+                    let b = z[start + k + half].mul(w);
+
+                    z[start + k] = Complex {
+                        re: a.re + b.re,
+                        im: a.im + b.im,
+                    };
+                    z[start + k + half] = Complex {
+                        re: a.re - b.re,
+                        im: a.im - b.im,
+                    };
+                }
+            }
+
+            size *= 2;
+        }
+    }
+
+    /// Transform the `n / 2` coefficients in `input` to the `n` samples of
+    /// `output`.
+    pub fn inverse(&mut self, input: &[f32], output: &mut [f32]) {
+        let n = self.n;
+        let (n2, n4) = (n / 2, n / 4);
+
+        // A DCT-IV of the coefficients, pairing even ones with odd ones from
+        // the other end.
+        for k in 0..n4 {
+            let x = Complex {
+                re: input[2 * k],
+                im: input[n2 - 1 - 2 * k],
+            };
+
+            self.scratch[self.bit_reverse[k] as usize] = x.mul(self.twiddle[k]);
+        }
+
+        self.fft();
+
+        for k in 0..n4 {
+            let w = self.scratch[k].mul(self.twiddle[k]);
+            self.place(output, 2 * k, w.re);
+            self.place(output, n2 - 1 - 2 * k, -w.im);
+        }
+    }
+
+    /// Unfold value `m` of the DCT-IV into the three places it goes in the
+    /// output.
+    #[inline]
+    fn place(&self, output: &mut [f32], m: usize, value: f32) {
+        let n4 = self.n / 4;
+
+        output[3 * n4 - 1 - m] = -value;
+
+        if m >= n4 {
+            output[m - n4] = value;
+        } else {
+            output[m + 3 * n4] = -value;
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! An Ogg Vorbis decoder in pure Rust.
+//!
+//! `VorbisDecoder` reads a mono or stereo Ogg Vorbis file from any reader,
+//! and is a `PcmDecoder`, so it can be decoded up front to a `Sound` or
+//! streamed with a `PcmPlayer`. It uses no hardware, leaving the Media Engine
+//! free for `mp3` and `atrac`.
+//!
+//! ```no_run
+//! use psp::audio::vorbis::VorbisDecoder;
+//! use psp::audio::Sound;
+//! use psp::fs::File;
+//!
+//! let mut decoder = VorbisDecoder::new(File::open("ms0:/SFX/door.ogg").unwrap()).unwrap();
+//! let door = Sound::decode(&mut decoder).unwrap();
+//! ```
+
+mod bits;
+mod decode;
+mod mdct;
+mod ogg;
+mod setup;
+
+use super::PcmDecoder;
+use crate::io::{self, Error, Read, Seek};
+use alloc::string::String;
+use alloc::vec::Vec;
+use decode::Synthesis;
+use ogg::PacketReader;
+use setup::{Ident, Setup};
+
+/// Decodes an Ogg Vorbis stream to 16-bit samples.
+///
+/// Only the first logical stream of a file is played. Streams with more than
+/// two channels are rejected.
+pub struct VorbisDecoder<R> {
+    packets: PacketReader<R>,
+    ident: Ident,
+    setup: Setup,
+    synthesis: Synthesis,
+    comments: Vec<String>,
+    /// Where the first page of audio starts.
+    audio_start: u64,
+    /// The granule position the last packet decoded ended at, once known.
+    granule: Option<u64>,
+    /// Decoded samples not read yet.
+    buffer: Vec<i16>,
+    buffered: usize,
+}
+
+impl<R: Read> VorbisDecoder<R> {
+    /// Read the headers from `reader`, leaving it at the start of the audio.
+    pub fn new(reader: R) -> io::Result<Self> {
+        let mut packets = PacketReader::new(reader);
+
+        next_header(&mut packets)?;
+        let ident = Ident::read(packets.packet())?;
+
+        if ident.channels > 2 {
+            return Err(Error::InvalidData);
+        }
+
+        next_header(&mut packets)?;
+        let comments = setup::read_comments(packets.packet())?;
+
+        next_header(&mut packets)?;
+        let setup = Setup::read(packets.packet(), ident.channels)?;
+
+        // Audio starts on a page of its own, so it can be found again.
+        packets.skip_page();
+
+        Ok(Self {
+            audio_start: packets.position(),
+            synthesis: Synthesis::new(&ident),
+            packets,
+            ident,
+            setup,
+            comments,
+            granule: None,
+            buffer: Vec::new(),
+            buffered: 0,
+        })
+    }
+
+    /// The sample rate, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.ident.sample_rate
+    }
+
+    /// The number of channels, 1 or 2.
+    pub fn channels(&self) -> usize {
+        self.ident.channels
+    }
+
+    /// The average bitrate the encoder aimed for, in bits per second, if it
+    /// said.
+    pub fn nominal_bitrate(&self) -> Option<u32> {
+        if self.ident.nominal_bitrate > 0 {
+            Some(self.ident.nominal_bitrate as u32)
+        } else {
+            None
+        }
+    }
+
+    /// The `KEY=value` comments, such as `TITLE=Overworld`.
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// The value of the first comment with `key`, ignoring case.
+    pub fn comment(&self, key: &str) -> Option<&str> {
+        self.comments.iter().find_map(|comment| {
+            let split = comment.find('=')?;
+            let (k, value) = comment.split_at(split);
+
+            if k.eq_ignore_ascii_case(key) {
+                Some(&value[1..])
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.packets.get_ref()
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        self.packets.get_mut()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.packets.into_inner()
+    }
+
+    /// Decode the next packet into `buffer`, returning false at the end.
+    fn decode_packet(&mut self) -> io::Result<bool> {
+        let info = match self.packets.next_packet()? {
+            Some(info) => info,
+            None => return Ok(false),
+        };
+
+        self.buffer.clear();
+        self.buffered = 0;
+
+        let mut frames =
+            self.synthesis
+                .decode(&self.setup, self.packets.packet(), &mut self.buffer);
+
+        // The last page's granule position says where the audio really ends,
+        // within the last block.
+        if let (Some(granule), Some(end), true) = (self.granule, info.granule, info.end_of_stream) {
+            frames = core::cmp::min(frames, end.saturating_sub(granule) as usize);
+            self.buffer.truncate(frames * self.ident.channels);
+        }
+
+        self.granule = match (info.granule, self.granule) {
+            (Some(end), _) => Some(end),
+            (None, Some(granule)) => Some(granule + frames as u64),
+            (None, None) => None,
+        };
+
+        Ok(true)
+    }
+}
+
+/// Read the next of the three headers.
+fn next_header<R: Read>(packets: &mut PacketReader<R>) -> io::Result<()> {
+    match packets.next_packet()? {
+        Some(_) => Ok(()),
+        None => Err(Error::UnexpectedEof),
+    }
+}
+
+impl<R: Read + Seek> PcmDecoder for VorbisDecoder<R> {
+    fn sample_rate(&self) -> u32 {
+        self.ident.sample_rate
+    }
+
+    fn channels(&self) -> usize {
+        self.ident.channels
+    }
+
+    fn read(&mut self, out: &mut [i16]) -> io::Result<usize> {
+        let len = out.len() - out.len() % self.ident.channels;
+        let mut written = 0;
+
+        while written < len {
+            if self.buffered == self.buffer.len() && !self.decode_packet()? {
+                break;
+            }
+
+            let n = core::cmp::min(len - written, self.buffer.len() - self.buffered);
+            out[written..written + n]
+                .copy_from_slice(&self.buffer[self.buffered..self.buffered + n]);
+            written += n;
+            self.buffered += n;
+        }
+
+        Ok(written)
+    }
+
+    fn rewind(&mut self) -> io::Result<()> {
+        self.packets.seek(self.audio_start)?;
+        self.synthesis.reset();
+        self.granule = None;
+        self.buffer.clear();
+        self.buffered = 0;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+//! Reassembling packets from the pages of an Ogg stream.
+
+use super::super::player::read_full;
+use crate::io::{self, Error, Read, Seek, SeekFrom};
+use alloc::vec::Vec;
+
+/// The CRC-32 of a page, with polynomial 0x04c11db7 and no reflection.
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+static CRC_TABLE: [u32; 256] = crc_table();
+
+fn crc(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc = (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ b) as usize];
+    }
+
+    crc
+}
+
+/// Where a packet ended.
+pub(super) struct PacketInfo {
+    /// The granule position of the page, if this was the last packet to end
+    /// on it.
+    pub granule: Option<u64>,
+    /// Whether this was the last packet of the stream.
+    pub end_of_stream: bool,
+}
+
+/// Reads the packets of the first logical stream in an Ogg file, skipping
+/// pages of any other.
+pub(super) struct PacketReader<R> {
+    reader: R,
+    /// Bytes read from `reader` so far.
+    position: u64,
+    serial: Option<u32>,
+    granule: u64,
+    end_of_stream: bool,
+    lacing: [u8; 255],
+    segments: usize,
+    segment: usize,
+    body: Vec<u8>,
+    offset: usize,
+    /// The packet being assembled, which may have started on an earlier page.
+    packet: Vec<u8>,
+    /// Whether `packet` is partway through, rather than empty or whole.
+    partial: bool,
+}
+
+impl<R: Read> PacketReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            position: 0,
+            serial: None,
+            granule: 0,
+            end_of_stream: false,
+            lacing: [0; 255],
+            segments: 0,
+            segment: 0,
+            body: Vec::new(),
+            offset: 0,
+            packet: Vec::new(),
+            partial: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// The number of bytes read from the underlying reader, which is where
+    /// the next page starts once `skip_page` has been called.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// The last packet returned by `next_packet`.
+    pub fn packet(&self) -> &[u8] {
+        &self.packet
+    }
+
+    /// Drop the rest of the current page, and any packet in progress.
+    pub fn skip_page(&mut self) {
+        self.segment = self.segments;
+        self.packet.clear();
+        self.partial = false;
+    }
+
+    /// Assemble the next packet, to get with `packet`. Returns `None` at the
+    /// end of the file.
+    pub fn next_packet(&mut self) -> io::Result<Option<PacketInfo>> {
+        if !self.partial {
+            self.packet.clear();
+        }
+
+        loop {
+            while self.segment < self.segments {
+                let len = self.lacing[self.segment] as usize;
+                self.packet
+                    .extend_from_slice(&self.body[self.offset..self.offset + len]);
+                self.offset += len;
+                self.segment += 1;
+
+                // A segment shorter than 255 bytes ends the packet.
+                if len < 255 {
+                    self.partial = false;
+
+                    let rest = &self.lacing[self.segment..self.segments];
+                    let last = rest.iter().all(|&len| len == 255);
+
+                    return Ok(Some(PacketInfo {
+                        granule: if last { Some(self.granule) } else { None },
+                        end_of_stream: last && self.end_of_stream,
+                    }));
+                }
+
+                self.partial = true;
+            }
+
+            if !self.read_page()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Read the next page of our stream, returning false at the end of the
+    /// file.
+    fn read_page(&mut self) -> io::Result<bool> {
+        loop {
+            let mut header = [0; 27];
+
+            // A page cut short at the end of the file is ignored.
+            if read_full(&mut self.reader, &mut header)? < header.len() {
+                return Ok(false);
+            }
+
+            if &header[..4] != b"OggS" || header[4] != 0 {
+                return Err(Error::InvalidData);
+            }
+
+            let flags = header[5];
+            let mut word = [0; 8];
+            word.copy_from_slice(&header[6..14]);
+            let granule = u64::from_le_bytes(word);
+            let serial = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
+            let checksum = u32::from_le_bytes([header[22], header[23], header[24], header[25]]);
+            let segments = header[26] as usize;
+
+            if read_full(&mut self.reader, &mut self.lacing[..segments])? < segments {
+                return Ok(false);
+            }
+
+            let len = self.lacing[..segments]
+                .iter()
+                .map(|&len| len as usize)
+                .sum();
+            self.body.resize(len, 0);
+
+            if read_full(&mut self.reader, &mut self.body)? < len {
+                return Ok(false);
+            }
+
+            self.position += (header.len() + segments + len) as u64;
+
+            for b in &mut header[22..26] {
+                *b = 0;
+            }
+
+            let actual = crc(crc(crc(0, &header), &self.lacing[..segments]), &self.body);
+
+            if actual != checksum {
+                return Err(Error::InvalidData);
+            }
+
+            if *self.serial.get_or_insert(serial) != serial {
+                continue;
+            }
+
+            self.granule = granule;
+            self.end_of_stream = flags & 0x04 != 0;
+            self.segments = segments;
+            self.segment = 0;
+            self.offset = 0;
+
+            let continued = flags & 0x01 != 0;
+
+            if continued != self.partial {
+                // Either the start of this packet was never read, as after
+                // seeking, or its end is missing; drop it.
+                self.packet.clear();
+                self.partial = false;
+
+                if continued {
+                    while self.segment < self.segments {
+                        let len = self.lacing[self.segment] as usize;
+                        self.offset += len;
+                        self.segment += 1;
+
+                        if len < 255 {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            return Ok(true);
+        }
+    }
+}
+
+impl<R: Read + Seek> PacketReader<R> {
+    /// Go back to `position`, as returned by `position` earlier.
+    pub fn seek(&mut self, position: u64) -> io::Result<()> {
+        let start = self.reader.stream_position()? - self.position;
+        self.reader.seek(SeekFrom::Start(start + position))?;
+        self.position = position;
+        self.segments = 0;
+        self.segment = 0;
+        self.end_of_stream = false;
+        self.packet.clear();
+        self.partial = false;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,858 @@
+//! The identification and setup headers, and the codebooks, floors,
+//! residues, mappings and modes the setup header describes.
+
+use super::bits::{ilog, BitReader};
+use crate::io::{self, Error};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Codewords up to this long are found with one table lookup.
+const FAST_BITS: u32 = 10;
+
+fn invalid() -> Error {
+    Error::InvalidData
+}
+
+/// Turn running out of header into an error, as headers have no reason to
+/// end early.
+trait OrInvalid<T> {
+    fn or_invalid(self) -> io::Result<T>;
+}
+
+impl<T> OrInvalid<T> for Option<T> {
+    fn or_invalid(self) -> io::Result<T> {
+        self.ok_or(Error::InvalidData)
+    }
+}
+
+/// Check the packet type and the `vorbis` signature which start each header.
+fn read_header_start(r: &mut BitReader, kind: u32) -> io::Result<()> {
+    if r.read(8).or_invalid()? != kind {
+        return Err(invalid());
+    }
+
+    for &c in b"vorbis" {
+        if r.read(8).or_invalid()? != c as u32 {
+            return Err(invalid());
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) struct Ident {
+    pub channels: usize,
+    pub sample_rate: u32,
+    pub nominal_bitrate: i32,
+    /// The short and long block sizes.
+    pub block_sizes: [usize; 2],
+}
+
+impl Ident {
+    pub fn read(packet: &[u8]) -> io::Result<Self> {
+        let mut r = BitReader::new(packet);
+        read_header_start(&mut r, 1)?;
+
+        let version = r.read(32).or_invalid()?;
+        let channels = r.read(8).or_invalid()? as usize;
+        let sample_rate = r.read(32).or_invalid()?;
+        let _maximum_bitrate = r.read(32).or_invalid()?;
+        let nominal_bitrate = r.read(32).or_invalid()? as i32;
+        let _minimum_bitrate = r.read(32).or_invalid()?;
+        let short = r.read(4).or_invalid()?;
+        let long = r.read(4).or_invalid()?;
+        let framing = r.read_bool().or_invalid()?;
+
+        let valid = version == 0
+            && channels > 0
+            && sample_rate > 0
+            && short >= 6
+            && long <= 13
+            && short <= long
+            && framing;
+
+        if !valid {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            channels,
+            sample_rate,
+            nominal_bitrate,
+            block_sizes: [1 << short, 1 << long],
+        })
+    }
+}
+
+/// Take a little-endian `u32` from the front of `rest`.
+fn take_u32(rest: &mut &[u8]) -> io::Result<u32> {
+    let b = rest.get(..4).or_invalid()?;
+    let value = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    *rest = &rest[4..];
+
+    Ok(value)
+}
+
+/// Take a length-prefixed string from the front of `rest`.
+fn take_string<'a>(rest: &mut &'a [u8]) -> io::Result<&'a [u8]> {
+    let len = take_u32(rest)? as usize;
+    let field = rest.get(..len).or_invalid()?;
+    *rest = &rest[len..];
+
+    Ok(field)
+}
+
+/// Read the comment header's `KEY=value` comments, skipping the vendor.
+pub(super) fn read_comments(packet: &[u8]) -> io::Result<Vec<String>> {
+    read_header_start(&mut BitReader::new(packet), 3)?;
+
+    // Unlike the other headers, this one is whole bytes.
+    let mut rest = &packet[7..];
+    let _vendor = take_string(&mut rest)?;
+    let count = take_u32(&mut rest)?;
+
+    let mut comments = Vec::new();
+
+    for _ in 0..count {
+        comments.push(String::from_utf8_lossy(take_string(&mut rest)?).into_owned());
+    }
+
+    Ok(comments)
+}
+
+pub(super) struct Codebook {
+    pub dimensions: usize,
+    /// Codewords of up to `fast_bits` bits, indexed by the next bits of the
+    /// packet, as `entry << 6 | length`. Zero means the codeword is longer.
+    fast: Vec<u32>,
+    fast_bits: u32,
+    /// All the codewords, most significant bit first, left aligned and
+    /// sorted, with their entries and lengths.
+    sorted: Vec<(u32, u32, u8)>,
+    /// The vectors of each entry, `dimensions` wide.
+    pub values: Option<Vec<f32>>,
+}
+
+impl Codebook {
+    fn read(r: &mut BitReader) -> io::Result<Self> {
+        if r.read(24).or_invalid()? != 0x564342 {
+            return Err(invalid());
+        }
+
+        let dimensions = r.read(16).or_invalid()? as usize;
+        let entries = r.read(24).or_invalid()? as usize;
+
+        // Real codebooks are far smaller than the packet holding them, in
+        // bits, so anything bigger is corrupt and shouldn't be allocated.
+        if entries > r.len() {
+            return Err(invalid());
+        }
+
+        let mut lengths = Vec::with_capacity(entries);
+
+        if r.read_bool().or_invalid()? {
+            // Ordered: runs of increasing lengths.
+            let mut length = r.read(5).or_invalid()? + 1;
+
+            while lengths.len() < entries {
+                let count = r
+                    .read(ilog((entries - lengths.len()) as u32))
+                    .or_invalid()? as usize;
+
+                if length > 32 || count > entries - lengths.len() {
+                    return Err(invalid());
+                }
+
+                lengths.extend((0..count).map(|_| length as u8));
+                length += 1;
+            }
+        } else {
+            let sparse = r.read_bool().or_invalid()?;
+
+            for _ in 0..entries {
+                let used = !sparse || r.read_bool().or_invalid()?;
+                let length = if used {
+                    r.read(5).or_invalid()? as u8 + 1
+                } else {
+                    0
+                };
+                lengths.push(length);
+            }
+        }
+
+        let lookup_type = r.read(4).or_invalid()?;
+
+        let values = match lookup_type {
+            0 => None,
+            1 | 2 => {
+                let minimum = float32_unpack(r.read(32).or_invalid()?);
+                let delta = float32_unpack(r.read(32).or_invalid()?);
+                let value_bits = r.read(4).or_invalid()? + 1;
+                let sequence_p = r.read_bool().or_invalid()?;
+
+                if dimensions == 0 {
+                    return Err(invalid());
+                }
+
+                // The same goes for the vectors unpacked from the values.
+                let table_len = entries
+                    .checked_mul(dimensions)
+                    .filter(|&len| len <= r.len())
+                    .ok_or_else(invalid)?;
+
+                let count = if lookup_type == 1 {
+                    lookup1_values(entries, dimensions)
+                } else {
+                    table_len
+                };
+
+                // Don't trust a huge count until the packet shows it has the
+                // bits to back it up.
+                if count.saturating_mul(value_bits as usize) > r.bits_left() {
+                    return Err(invalid());
+                }
+
+                let mut multiplicands = Vec::with_capacity(count);
+
+                for _ in 0..count {
+                    multiplicands.push(r.read(value_bits).or_invalid()?);
+                }
+
+                Some(unpack_values(
+                    &multiplicands,
+                    lookup_type,
+                    entries,
+                    dimensions,
+                    minimum,
+                    delta,
+                    sequence_p,
+                ))
+            }
+            _ => return Err(invalid()),
+        };
+
+        let (fast, fast_bits, sorted) = build_huffman(&lengths)?;
+
+        Ok(Self {
+            dimensions,
+            fast,
+            fast_bits,
+            sorted,
+            values,
+        })
+    }
+
+    /// Read a codeword, returning its entry.
+    #[inline]
+    pub fn decode(&self, r: &mut BitReader) -> Option<u32> {
+        let bits = r.peek();
+
+        let fast = self.fast[(bits & ((1 << self.fast_bits) - 1)) as usize];
+
+        let (entry, length) = if fast != 0 {
+            (fast >> 6, fast & 0x3f)
+        } else {
+            self.decode_slow(bits)?
+        };
+
+        if r.skip(length) {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    fn decode_slow(&self, bits: u32) -> Option<(u32, u32)> {
+        let code = bits.reverse_bits();
+
+        // The last codeword at or before `code` is the one it starts with,
+        // as the tree is complete.
+        let i = match self.sorted.binary_search_by(|&(c, _, _)| c.cmp(&code)) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+
+        let (_, entry, length) = self.sorted[i];
+        Some((entry, length as u32))
+    }
+
+    /// Read a codeword, returning the vector of its entry.
+    #[inline]
+    pub fn decode_vector(&self, r: &mut BitReader) -> Option<&[f32]> {
+        let entry = self.decode(r)? as usize;
+        let values = self.values.as_ref()?;
+
+        Some(&values[entry * self.dimensions..(entry + 1) * self.dimensions])
+    }
+}
+
+/// Assign codewords to the used entries in order, each taking the first
+/// free codeword of its length, and build lookup tables for them.
+#[allow(clippy::type_complexity)]
+fn build_huffman(lengths: &[u8]) -> io::Result<(Vec<u32>, u32, Vec<(u32, u32, u8)>)> {
+    let mut sorted = Vec::new();
+    let used = lengths.iter().filter(|&&length| length > 0).count();
+
+    if used == 1 {
+        // A lone codeword takes one bit, whatever its value.
+        let entry = lengths.iter().position(|&length| length > 0).unwrap();
+
+        if lengths[entry] != 1 {
+            return Err(invalid());
+        }
+
+        let fast = ((entry as u32) << 6) | 1;
+        return Ok((vec![fast, fast], 1, vec![(0, entry as u32, 1)]));
+    }
+
+    // The free codeword at each depth, left aligned; only the lowest free
+    // codeword of a depth is ever needed, and there is at most one.
+    let mut available = [0u32; 33];
+    let mut first = true;
+
+    for (entry, &length) in lengths.iter().enumerate() {
+        if length == 0 {
+            continue;
+        }
+
+        let length = length as usize;
+
+        let code = if first {
+            first = false;
+
+            for (depth, slot) in available.iter_mut().enumerate().take(length + 1).skip(1) {
+                *slot = 1 << (32 - depth);
+            }
+
+            0
+        } else {
+            let mut depth = length;
+
+            while depth > 0 && available[depth] == 0 {
+                depth -= 1;
+            }
+
+            // No free codeword left: overspecified.
+            if depth == 0 {
+                return Err(invalid());
+            }
+
+            let code = available[depth];
+            available[depth] = 0;
+
+            // Splitting a shorter free codeword frees its right halves.
+            for (d, slot) in available
+                .iter_mut()
+                .enumerate()
+                .take(length + 1)
+                .skip(depth + 1)
+            {
+                *slot = code + (1 << (32 - d));
+            }
+
+            code
+        };
+
+        sorted.push((code, entry as u32, length as u8));
+    }
+
+    // Underspecified trees would leave codes which decode to nothing.
+    if available.iter().any(|&code| code != 0) {
+        return Err(invalid());
+    }
+
+    let max_length = sorted
+        .iter()
+        .map(|&(_, _, length)| length as u32)
+        .max()
+        .unwrap_or(1);
+    let fast_bits = core::cmp::min(max_length, FAST_BITS);
+    let mut fast = vec![0; 1 << fast_bits];
+
+    for &(code, entry, length) in &sorted {
+        let length = length as u32;
+
+        if length <= fast_bits {
+            // Packets are read least significant bit first.
+            let reversed = code.reverse_bits();
+
+            for high in 0..1 << (fast_bits - length) {
+                fast[(reversed | high << length) as usize] = entry << 6 | length;
+            }
+        }
+    }
+
+    sorted.sort_unstable_by_key(|&(code, _, _)| code);
+
+    Ok((fast, fast_bits, sorted))
+}
+
+/// Unpack the float format of codebook headers.
+fn float32_unpack(x: u32) -> f32 {
+    let mantissa = (x & 0x1f_ffff) as f64;
+    let exponent = ((x >> 21) & 0x3ff) as i32 - 788;
+    let mut value = if x & 0x8000_0000 != 0 {
+        -mantissa
+    } else {
+        mantissa
+    };
+
+    // Scale in steps which stay within range.
+    let mut exponent = exponent;
+
+    while exponent > 0 {
+        let step = core::cmp::min(exponent, 60);
+        value *= (1u64 << step) as f64;
+        exponent -= step;
+    }
+
+    while exponent < 0 {
+        let step = core::cmp::min(-exponent, 60);
+        value /= (1u64 << step) as f64;
+        exponent += step;
+    }
+
+    value as f32
+}
+
+/// The largest number whose `dimensions`th power is at most `entries`.
+fn lookup1_values(entries: usize, dimensions: usize) -> usize {
+    let fits = |base: usize| {
+        let mut power: usize = 1;
+
+        for _ in 0..dimensions {
+            power = match power.checked_mul(base) {
+                Some(power) if power <= entries => power,
+                _ => return false,
+            };
+        }
+
+        true
+    };
+
+    let (mut low, mut high) = (0, entries + 1);
+
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+
+        if fits(mid) {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    low
+}
+
+fn unpack_values(
+    multiplicands: &[u32],
+    lookup_type: u32,
+    entries: usize,
+    dimensions: usize,
+    minimum: f32,
+    delta: f32,
+    sequence_p: bool,
+) -> Vec<f32> {
+    let mut values = Vec::with_capacity(entries * dimensions);
+
+    for entry in 0..entries {
+        let mut last = 0.0;
+        let mut divisor = 1;
+
+        for i in 0..dimensions {
+            let offset = if lookup_type == 1 {
+                (entry / divisor) % multiplicands.len()
+            } else {
+                entry * dimensions + i
+            };
+
+            let value = multiplicands[offset] as f32 * delta + minimum + last;
+
+            if sequence_p {
+                last = value;
+            }
+
+            values.push(value);
+            divisor = divisor.saturating_mul(multiplicands.len());
+        }
+    }
+
+    values
+}
+
+pub(super) struct Floor {
+    pub multiplier: u32,
+    /// The class of each partition.
+    pub partitions: Vec<u8>,
+    pub classes: Vec<FloorClass>,
+    /// The X of each point, in the order they are read.
+    pub xs: Vec<u32>,
+    /// The point indices, sorted by X.
+    pub sorted: Vec<u8>,
+    /// For each point from the third, the points either side it is predicted
+    /// from.
+    pub neighbors: Vec<(u8, u8)>,
+}
+
+pub(super) struct FloorClass {
+    pub dimensions: u32,
+    pub subclass_bits: u32,
+    pub masterbook: usize,
+    /// The book for each subclass, or `None` for zeros.
+    pub subclass_books: Vec<Option<usize>>,
+}
+
+impl Floor {
+    fn read(r: &mut BitReader, books: &[Codebook]) -> io::Result<Self> {
+        match r.read(16).or_invalid()? {
+            1 => (),
+            // Floor 0 hasn't been produced by encoders since before Vorbis
+            // 1.0.
+            _ => return Err(invalid()),
+        }
+
+        let partition_count = r.read(5).or_invalid()?;
+        let mut partitions = Vec::new();
+
+        for _ in 0..partition_count {
+            partitions.push(r.read(4).or_invalid()? as u8);
+        }
+
+        let class_count = partitions
+            .iter()
+            .map(|&c| c as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let mut classes = Vec::new();
+
+        for _ in 0..class_count {
+            let dimensions = r.read(3).or_invalid()? + 1;
+            let subclass_bits = r.read(2).or_invalid()?;
+
+            let masterbook = if subclass_bits > 0 {
+                r.read(8).or_invalid()? as usize
+            } else {
+                0
+            };
+
+            if subclass_bits > 0 && masterbook >= books.len() {
+                return Err(invalid());
+            }
+
+            let mut subclass_books = Vec::new();
+
+            for _ in 0..1 << subclass_bits {
+                let book = r.read(8).or_invalid()? as usize;
+
+                subclass_books.push(match book {
+                    0 => None,
+                    book if book - 1 < books.len() => Some(book - 1),
+                    _ => return Err(invalid()),
+                });
+            }
+
+            classes.push(FloorClass {
+                dimensions,
+                subclass_bits,
+                masterbook,
+                subclass_books,
+            });
+        }
+
+        let multiplier = r.read(2).or_invalid()? + 1;
+        let range_bits = r.read(4).or_invalid()?;
+
+        let mut xs = vec![0, 1 << range_bits];
+
+        for &class in &partitions {
+            for _ in 0..classes[class as usize].dimensions {
+                xs.push(r.read(range_bits).or_invalid()?);
+            }
+        }
+
+        if xs.len() > 65 {
+            return Err(invalid());
+        }
+
+        let mut sorted: Vec<u8> = (0..xs.len() as u8).collect();
+        sorted.sort_by_key(|&i| xs[i as usize]);
+
+        if sorted
+            .windows(2)
+            .any(|w| xs[w[0] as usize] == xs[w[1] as usize])
+        {
+            return Err(invalid());
+        }
+
+        // The nearest earlier points below and above each X; the first two
+        // points are at either end, so both always exist.
+        let neighbors = (2..xs.len())
+            .map(|i| {
+                let x = xs[i];
+                let mut low = 0;
+                let mut high = 1;
+
+                for j in 0..i {
+                    if xs[j] < x && xs[j] > xs[low] {
+                        low = j;
+                    }
+
+                    if xs[j] > x && xs[j] < xs[high] {
+                        high = j;
+                    }
+                }
+
+                (low as u8, high as u8)
+            })
+            .collect();
+
+        Ok(Self {
+            multiplier,
+            partitions,
+            classes,
+            xs,
+            sorted,
+            neighbors,
+        })
+    }
+}
+
+pub(super) struct Residue {
+    pub kind: u32,
+    pub begin: usize,
+    pub end: usize,
+    pub partition_size: usize,
+    pub classifications: u32,
+    pub classbook: usize,
+    /// The book for each classification and pass.
+    pub books: Vec<[Option<usize>; 8]>,
+}
+
+impl Residue {
+    fn read(r: &mut BitReader, books: &[Codebook]) -> io::Result<Self> {
+        let kind = r.read(16).or_invalid()?;
+        let begin = r.read(24).or_invalid()? as usize;
+        let end = r.read(24).or_invalid()? as usize;
+        let partition_size = r.read(24).or_invalid()? as usize + 1;
+        let classifications = r.read(6).or_invalid()? + 1;
+        let classbook = r.read(8).or_invalid()? as usize;
+
+        if kind > 2 || begin > end || classbook >= books.len() {
+            return Err(invalid());
+        }
+
+        // A classbook of no dimensions would read no classifications.
+        if books[classbook].dimensions == 0 {
+            return Err(invalid());
+        }
+
+        let mut cascades = Vec::new();
+
+        for _ in 0..classifications {
+            let low = r.read(3).or_invalid()?;
+            let high = if r.read_bool().or_invalid()? {
+                r.read(5).or_invalid()?
+            } else {
+                0
+            };
+            cascades.push(high << 3 | low);
+        }
+
+        let mut residue_books = Vec::new();
+
+        for cascade in cascades {
+            let mut passes = [None; 8];
+
+            for (pass, book) in passes.iter_mut().enumerate() {
+                if cascade & 1 << pass != 0 {
+                    let index = r.read(8).or_invalid()? as usize;
+
+                    match books.get(index) {
+                        Some(b) if b.values.is_some() => *book = Some(index),
+                        _ => return Err(invalid()),
+                    }
+                }
+            }
+
+            residue_books.push(passes);
+        }
+
+        Ok(Self {
+            kind,
+            begin,
+            end,
+            partition_size,
+            classifications,
+            classbook,
+            books: residue_books,
+        })
+    }
+}
+
+pub(super) struct Mapping {
+    /// Magnitude and angle channel pairs, applied in reverse.
+    pub couplings: Vec<(usize, usize)>,
+    /// The submap of each channel.
+    pub mux: Vec<usize>,
+    /// The floor and residue of each submap.
+    pub submaps: Vec<(usize, usize)>,
+}
+
+impl Mapping {
+    fn read(
+        r: &mut BitReader,
+        channels: usize,
+        floors: usize,
+        residues: usize,
+    ) -> io::Result<Self> {
+        if r.read(16).or_invalid()? != 0 {
+            return Err(invalid());
+        }
+
+        let submap_count = if r.read_bool().or_invalid()? {
+            r.read(4).or_invalid()? as usize + 1
+        } else {
+            1
+        };
+
+        let mut couplings = Vec::new();
+
+        if r.read_bool().or_invalid()? {
+            let steps = r.read(8).or_invalid()? + 1;
+            let bits = ilog(channels as u32 - 1);
+
+            for _ in 0..steps {
+                let magnitude = r.read(bits).or_invalid()? as usize;
+                let angle = r.read(bits).or_invalid()? as usize;
+
+                if magnitude == angle || magnitude >= channels || angle >= channels {
+                    return Err(invalid());
+                }
+
+                couplings.push((magnitude, angle));
+            }
+        }
+
+        if r.read(2).or_invalid()? != 0 {
+            return Err(invalid());
+        }
+
+        let mut mux = vec![0; channels];
+
+        if submap_count > 1 {
+            for submap in &mut mux {
+                *submap = r.read(4).or_invalid()? as usize;
+
+                if *submap >= submap_count {
+                    return Err(invalid());
+                }
+            }
+        }
+
+        let mut submaps = Vec::new();
+
+        for _ in 0..submap_count {
+            let _time = r.read(8).or_invalid()?;
+            let floor = r.read(8).or_invalid()? as usize;
+            let residue = r.read(8).or_invalid()? as usize;
+
+            if floor >= floors || residue >= residues {
+                return Err(invalid());
+            }
+
+            submaps.push((floor, residue));
+        }
+
+        Ok(Self {
+            couplings,
+            mux,
+            submaps,
+        })
+    }
+}
+
+pub(super) struct Mode {
+    pub long: bool,
+    pub mapping: usize,
+}
+
+pub(super) struct Setup {
+    pub books: Vec<Codebook>,
+    pub floors: Vec<Floor>,
+    pub residues: Vec<Residue>,
+    pub mappings: Vec<Mapping>,
+    pub modes: Vec<Mode>,
+}
+
+impl Setup {
+    pub fn read(packet: &[u8], channels: usize) -> io::Result<Self> {
+        let mut r = BitReader::new(packet);
+        read_header_start(&mut r, 5)?;
+
+        let mut books = Vec::new();
+
+        for _ in 0..r.read(8).or_invalid()? + 1 {
+            books.push(Codebook::read(&mut r)?);
+        }
+
+        // Time domain transforms, which are placeholders.
+        for _ in 0..r.read(6).or_invalid()? + 1 {
+            if r.read(16).or_invalid()? != 0 {
+                return Err(invalid());
+            }
+        }
+
+        let mut floors = Vec::new();
+
+        for _ in 0..r.read(6).or_invalid()? + 1 {
+            floors.push(Floor::read(&mut r, &books)?);
+        }
+
+        let mut residues = Vec::new();
+
+        for _ in 0..r.read(6).or_invalid()? + 1 {
+            residues.push(Residue::read(&mut r, &books)?);
+        }
+
+        let mut mappings = Vec::new();
+
+        for _ in 0..r.read(6).or_invalid()? + 1 {
+            mappings.push(Mapping::read(
+                &mut r,
+                channels,
+                floors.len(),
+                residues.len(),
+            )?);
+        }
+
+        let mut modes = Vec::new();
+
+        for _ in 0..r.read(6).or_invalid()? + 1 {
+            let long = r.read_bool().or_invalid()?;
+            let window = r.read(16).or_invalid()?;
+            let transform = r.read(16).or_invalid()?;
+            let mapping = r.read(8).or_invalid()? as usize;
+
+            if window != 0 || transform != 0 || mapping >= mappings.len() {
+                return Err(invalid());
+            }
+
+            modes.push(Mode { long, mapping });
+        }
+
+        if !r.read_bool().or_invalid()? {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            books,
+            floors,
+            residues,
+            mappings,
+            modes,
+        })
+    }
+}
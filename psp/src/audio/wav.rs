@@ -0,0 +1,529 @@
+//! WAV files: `WavWriter` writes 16-bit PCM, and `WavReader`, behind the
+//! `wav` feature, decodes PCM, float and IMA ADPCM.
+
+use crate::fs::File;
+use crate::io::{self, Seek, SeekFrom, Write};
+
+#[cfg(feature = "wav")]
+use super::{player::read_full, PcmDecoder};
+#[cfg(feature = "wav")]
+use crate::io::{Error, Read};
+#[cfg(feature = "wav")]
+use alloc::{vec, vec::Vec};
+
+const HEADER_SIZE: u32 = 44;
+
+/// The RIFF header of a 16-bit PCM WAV file holding `data_size` bytes of
+/// samples.
+fn header(sample_rate: u32, channels: u16, data_size: u32) -> [u8; HEADER_SIZE as usize] {
+    let block_align = channels * 2;
+    let mut header = [0; HEADER_SIZE as usize];
+
+    header[0..4].copy_from_slice(b"RIFF");
+    header[4..8].copy_from_slice(&(HEADER_SIZE - 8 + data_size).to_le_bytes());
+    header[8..12].copy_from_slice(b"WAVE");
+
+    header[12..16].copy_from_slice(b"fmt ");
+    header[16..20].copy_from_slice(&16u32.to_le_bytes());
+    header[20..22].copy_from_slice(&1u16.to_le_bytes());
+    header[22..24].copy_from_slice(&channels.to_le_bytes());
+    header[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+    header[28..32].copy_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    header[32..34].copy_from_slice(&block_align.to_le_bytes());
+    header[34..36].copy_from_slice(&16u16.to_le_bytes());
+
+    header[36..40].copy_from_slice(b"data");
+    header[40..44].copy_from_slice(&data_size.to_le_bytes());
+
+    header
+}
+
+/// Writes 16-bit PCM samples to a WAV file as they arrive. The sizes in the
+/// header are filled in by `finish`.
+///
+/// ```no_run
+/// use psp::audio::wav::WavWriter;
+///
+/// let mut wav = WavWriter::create("ms0:/memo.wav", 22050, 1).unwrap();
+/// wav.write_samples(&[0; 22050]).unwrap();
+/// wav.finish().unwrap();
+/// ```
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    start: u64,
+    sample_rate: u32,
+    channels: u16,
+    data_size: u32,
+}
+
+impl WavWriter<File> {
+    /// Create a WAV file at `path`, replacing any existing file.
+    pub fn create(path: &str, sample_rate: u32, channels: u16) -> io::Result<Self> {
+        Self::new(File::create(path)?, sample_rate, channels)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Start a WAV file at the current position of `writer`. Stereo samples
+    /// are interleaved, left first.
+    pub fn new(mut writer: W, sample_rate: u32, channels: u16) -> io::Result<Self> {
+        if channels == 0 || sample_rate == 0 {
+            return Err(io::Error::InvalidInput);
+        }
+
+        let start = writer.stream_position()?;
+        writer.write_all(&header(sample_rate, channels, 0))?;
+
+        Ok(Self {
+            writer,
+            start,
+            sample_rate,
+            channels,
+            data_size: 0,
+        })
+    }
+
+    pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        let mut bytes = [0; 512];
+
+        for chunk in samples.chunks(bytes.len() / 2) {
+            for (sample, out) in chunk.iter().zip(bytes.chunks_exact_mut(2)) {
+                out.copy_from_slice(&sample.to_le_bytes());
+            }
+
+            self.writer.write_all(&bytes[..chunk.len() * 2])?;
+            self.data_size += chunk.len() as u32 * 2;
+        }
+
+        Ok(())
+    }
+
+    /// The number of samples per channel written so far.
+    pub fn len(&self) -> u32 {
+        self.data_size / (self.channels as u32 * 2)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data_size == 0
+    }
+
+    /// Fill in the header, and return the writer, positioned after the data.
+    pub fn finish(mut self) -> io::Result<W> {
+        let end = self.writer.stream_position()?;
+
+        self.writer.seek(SeekFrom::Start(self.start))?;
+        self.writer
+            .write_all(&header(self.sample_rate, self.channels, self.data_size))?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+/// How the samples of a `WavReader` are stored.
+#[cfg(feature = "wav")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WavEncoding {
+    /// Integer PCM of 8, 16, 24 or 32 bits. 8-bit samples are unsigned.
+    Pcm(u16),
+    /// 32-bit float PCM.
+    Float,
+    /// IMA ADPCM, 4 bits per sample in blocks of `block_align` bytes.
+    ImaAdpcm,
+}
+
+#[cfg(feature = "wav")]
+const IMA_STEPS: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+#[cfg(feature = "wav")]
+const IMA_INDEX_STEPS: [i32; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+/// One channel of an IMA ADPCM decoder.
+#[cfg(feature = "wav")]
+struct ImaChannel {
+    predictor: i32,
+    index: i32,
+}
+
+#[cfg(feature = "wav")]
+impl ImaChannel {
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let step = IMA_STEPS[self.index as usize];
+        let mut diff = step >> 3;
+
+        if nibble & 1 != 0 {
+            diff += step >> 2;
+        }
+
+        if nibble & 2 != 0 {
+            diff += step >> 1;
+        }
+
+        if nibble & 4 != 0 {
+            diff += step;
+        }
+
+        if nibble & 8 != 0 {
+            diff = -diff;
+        }
+
+        self.predictor = clamp(self.predictor + diff, -32768, 32767);
+        self.index = clamp(self.index + IMA_INDEX_STEPS[(nibble & 7) as usize], 0, 88);
+
+        self.predictor as i16
+    }
+}
+
+#[cfg(feature = "wav")]
+fn clamp(x: i32, min: i32, max: i32) -> i32 {
+    if x < min {
+        min
+    } else if x > max {
+        max
+    } else {
+        x
+    }
+}
+
+#[cfg(feature = "wav")]
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+#[cfg(feature = "wav")]
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// Decodes a mono or stereo WAV file to 16-bit samples.
+///
+/// Samples of more than 16 bits keep their top 16, and float samples are
+/// clipped.
+///
+/// ```no_run
+/// use psp::audio::wav::WavReader;
+/// use psp::audio::Sound;
+/// use psp::fs::File;
+///
+/// let mut wav = WavReader::new(File::open("ms0:/SFX/jump.wav").unwrap()).unwrap();
+/// let jump = Sound::decode(&mut wav).unwrap();
+/// ```
+#[cfg(feature = "wav")]
+pub struct WavReader<R> {
+    reader: R,
+    encoding: WavEncoding,
+    channels: usize,
+    sample_rate: u32,
+    block_align: usize,
+    /// The bytes of the data chunk read so far, and in all.
+    position: u64,
+    data_size: u64,
+    bytes: Vec<u8>,
+    /// Decoded ADPCM samples not read yet.
+    block: Vec<i16>,
+    block_pos: usize,
+}
+
+#[cfg(feature = "wav")]
+impl<R: Read> WavReader<R> {
+    /// Read the header from `reader`, leaving it at the start of the samples.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut riff = [0; 12];
+        reader.read_exact(&mut riff)?;
+
+        if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+            return Err(Error::InvalidData);
+        }
+
+        let mut format = None;
+
+        let data_size = loop {
+            let mut chunk = [0; 8];
+            reader.read_exact(&mut chunk)?;
+            let size = u32_at(&chunk, 4) as u64;
+
+            if &chunk[0..4] == b"data" {
+                break size;
+            }
+
+            // Chunks are padded to an even size.
+            let mut rest = size + (size & 1);
+
+            if &chunk[0..4] == b"fmt " {
+                let mut fmt = [0; 40];
+                let len = core::cmp::min(rest, fmt.len() as u64) as usize;
+
+                if len < 16 {
+                    return Err(Error::InvalidData);
+                }
+
+                reader.read_exact(&mut fmt[..len])?;
+                rest -= len as u64;
+                format = Some((fmt, len));
+            }
+
+            let mut skipped = [0; 64];
+
+            while rest > 0 {
+                let len = core::cmp::min(rest, skipped.len() as u64) as usize;
+                reader.read_exact(&mut skipped[..len])?;
+                rest -= len as u64;
+            }
+        };
+
+        let (fmt, len) = format.ok_or(Error::InvalidData)?;
+        let mut tag = u16_at(&fmt, 0);
+        let channels = u16_at(&fmt, 2) as usize;
+        let sample_rate = u32_at(&fmt, 4);
+        let block_align = u16_at(&fmt, 12) as usize;
+        let bits = u16_at(&fmt, 14);
+
+        // WAVE_FORMAT_EXTENSIBLE keeps the real format in its subformat.
+        if tag == 0xfffe && len >= 26 {
+            tag = u16_at(&fmt, 24);
+        }
+
+        let encoding = match (tag, bits) {
+            (1, 8) | (1, 16) | (1, 24) | (1, 32) => WavEncoding::Pcm(bits),
+            (3, 32) => WavEncoding::Float,
+            (0x11, 4) => WavEncoding::ImaAdpcm,
+            _ => return Err(Error::InvalidData),
+        };
+
+        let valid_align = match encoding {
+            WavEncoding::ImaAdpcm => {
+                block_align > 4 * channels && block_align % (4 * channels) == 0
+            }
+            _ => block_align == channels * bits as usize / 8,
+        };
+
+        if channels == 0 || channels > 2 || sample_rate == 0 || !valid_align {
+            return Err(Error::InvalidData);
+        }
+
+        Ok(Self {
+            reader,
+            encoding,
+            channels,
+            sample_rate,
+            block_align,
+            position: 0,
+            data_size,
+            bytes: vec![0; core::cmp::max(block_align, 1024)],
+            block: Vec::new(),
+            block_pos: 0,
+        })
+    }
+
+    /// The sample rate, in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The number of channels, 1 or 2.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn encoding(&self) -> WavEncoding {
+        self.encoding
+    }
+
+    /// The number of samples per channel, or frames.
+    pub fn len(&self) -> u64 {
+        match self.encoding {
+            WavEncoding::ImaAdpcm => {
+                let blocks = self.data_size / self.block_align as u64;
+                let partial = self.data_size % self.block_align as u64;
+
+                blocks * self.frames_per_block(self.block_align) as u64
+                    + self.frames_per_block(partial as usize) as u64
+            }
+            _ => self.data_size / self.block_align as u64,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Read up to `len` bytes of the data chunk into `bytes`.
+    fn read_data(&mut self, len: usize) -> io::Result<usize> {
+        let len = core::cmp::min(len as u64, self.data_size - self.position) as usize;
+        let n = read_full(&mut self.reader, &mut self.bytes[..len])?;
+        self.position += n as u64;
+
+        Ok(n)
+    }
+
+    /// The frames in an ADPCM block of `len` bytes: one in the header, then
+    /// eight per four bytes of each channel.
+    fn frames_per_block(&self, len: usize) -> usize {
+        if len < 4 * self.channels {
+            0
+        } else {
+            1 + (len - 4 * self.channels) / (4 * self.channels) * 8
+        }
+    }
+
+    /// Decode the next ADPCM block into `block`.
+    fn decode_block(&mut self) -> io::Result<()> {
+        let len = self.read_data(self.block_align)?;
+        let frames = self.frames_per_block(len);
+        let channels = self.channels;
+        let bytes = &self.bytes[..len];
+
+        self.block.clear();
+        self.block.resize(frames * channels, 0);
+        self.block_pos = 0;
+
+        if frames == 0 {
+            return Ok(());
+        }
+
+        for ch in 0..channels {
+            let header = &bytes[ch * 4..ch * 4 + 4];
+            let mut state = ImaChannel {
+                predictor: u16_at(header, 0) as i16 as i32,
+                index: clamp(header[2] as i32, 0, 88),
+            };
+
+            self.block[ch] = state.predictor as i16;
+
+            // Each channel has four bytes of eight samples at a time, low
+            // nibble first.
+            let groups = bytes[4 * channels..]
+                .chunks_exact(4)
+                .skip(ch)
+                .step_by(channels)
+                .take((frames - 1) / 8);
+
+            for (group, group_bytes) in groups.enumerate() {
+                for (i, &byte) in group_bytes.iter().enumerate() {
+                    let frame = 1 + group * 8 + i * 2;
+                    self.block[frame * channels + ch] = state.decode(byte & 0xf);
+                    self.block[(frame + 1) * channels + ch] = state.decode(byte >> 4);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_adpcm(&mut self, out: &mut [i16]) -> io::Result<usize> {
+        let mut written = 0;
+
+        while written < out.len() {
+            if self.block_pos == self.block.len() {
+                self.decode_block()?;
+
+                if self.block.is_empty() {
+                    break;
+                }
+            }
+
+            let n = core::cmp::min(out.len() - written, self.block.len() - self.block_pos);
+            out[written..written + n]
+                .copy_from_slice(&self.block[self.block_pos..self.block_pos + n]);
+            written += n;
+            self.block_pos += n;
+        }
+
+        Ok(written)
+    }
+
+    fn read_pcm(&mut self, out: &mut [i16]) -> io::Result<usize> {
+        let size = self.block_align / self.channels;
+        let mut written = 0;
+
+        while written < out.len() {
+            let frames = (out.len() - written) / self.channels;
+            let len = core::cmp::min(
+                frames * self.block_align,
+                self.bytes.len() / self.block_align * self.block_align,
+            );
+            let len = self.read_data(len)?;
+
+            // A frame cut short at the end is dropped.
+            let samples = len / self.block_align * self.channels;
+
+            if samples == 0 {
+                break;
+            }
+
+            let bytes = self.bytes[..samples * size].chunks_exact(size);
+
+            for (out, b) in out[written..written + samples].iter_mut().zip(bytes) {
+                *out = match self.encoding {
+                    WavEncoding::Pcm(8) => ((b[0] as i16) - 128) << 8,
+                    WavEncoding::Float => {
+                        let x = f32::from_bits(u32_at(b, 0)) * 32768.0;
+
+                        if x >= 32767.0 {
+                            32767
+                        } else if x <= -32768.0 {
+                            -32768
+                        } else {
+                            x as i16
+                        }
+                    }
+                    // The top 16 bits of the little endian sample.
+                    _ => i16::from_le_bytes([b[size - 2], b[size - 1]]),
+                };
+            }
+
+            written += samples;
+        }
+
+        Ok(written)
+    }
+}
+
+#[cfg(feature = "wav")]
+impl<R: Read + Seek> PcmDecoder for WavReader<R> {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> usize {
+        self.channels
+    }
+
+    fn read(&mut self, out: &mut [i16]) -> io::Result<usize> {
+        let len = out.len() - out.len() % self.channels;
+
+        match self.encoding {
+            WavEncoding::ImaAdpcm => self.read_adpcm(&mut out[..len]),
+            _ => self.read_pcm(&mut out[..len]),
+        }
+    }
+
+    fn rewind(&mut self) -> io::Result<()> {
+        self.reader
+            .seek(SeekFrom::Current(-(self.position as i64)))?;
+        self.position = 0;
+        self.block.clear();
+        self.block_pos = 0;
+
+        Ok(())
+    }
+}
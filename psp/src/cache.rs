@@ -0,0 +1,346 @@
+//! Data cache coherency helpers.
+//!
+//! The CPU data cache is write-back, but the GE, the Media Engine codecs and
+//! the audio hardware read and write main memory directly. Data written by the
+//! CPU may still be sitting in the cache when hardware reads it, and the CPU
+//! may read stale cache lines after hardware has written to memory.
+//!
+//! There are two ways around this:
+//!
+//! - Access the memory through its uncached alias (`UncachedPtr`,
+//!   `UncachedSlice`), which bypasses the cache entirely. This is best for
+//!   data written once and read by hardware, such as display lists.
+//! - Keep using the cache, and write back or invalidate it when handing a
+//!   buffer to or from hardware (`DmaBuffer`). This is best for data the CPU
+//!   works on heavily.
+
+use crate::sys;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::mem;
+
+#[cfg(not(feature = "stub-only"))]
+use alloc::alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout};
+#[cfg(not(feature = "stub-only"))]
+use core::{ops::{Deref, DerefMut}, ptr::NonNull, slice};
+
+/// Size of a data cache line, in bytes.
+pub const CACHE_LINE_SIZE: usize = 64;
+
+/// Setting this bit on a user address gives its uncached alias.
+const UNCACHED_USER: usize = 0x4000_0000;
+
+/// Setting these bits on a kernel address gives its uncached alias.
+const UNCACHED_KERNEL: usize = 0xA000_0000;
+
+const KERNEL: usize = 0x8000_0000;
+
+/// Get the uncached alias of a pointer.
+///
+/// http://uofw.github.io/upspd/docs/hardware/PSPTEK.htm#memmap
+pub fn uncached<T>(ptr: *mut T) -> *mut T {
+    let addr = ptr as usize;
+
+    if addr & KERNEL != 0 {
+        (addr | UNCACHED_KERNEL) as *mut T
+    } else {
+        (addr | UNCACHED_USER) as *mut T
+    }
+}
+
+/// Get the cached alias of a pointer. This undoes `uncached`.
+pub fn cached<T>(ptr: *mut T) -> *mut T {
+    let addr = ptr as usize;
+
+    if addr & KERNEL != 0 {
+        (addr & !(UNCACHED_KERNEL & !KERNEL)) as *mut T
+    } else {
+        (addr & !UNCACHED_USER) as *mut T
+    }
+}
+
+/// Write back the cache lines covering `data` to memory.
+///
+/// Call this after writing data with the CPU, before hardware reads it.
+pub fn writeback<T>(data: &[T]) {
+    unsafe {
+        sys::sceKernelDcacheWritebackRange(
+            data.as_ptr() as *const c_void,
+            mem::size_of_val(data) as u32,
+        );
+    }
+}
+
+/// Write back and invalidate the cache lines covering `data`.
+///
+/// Afterwards, the CPU reads `data` from memory again. Unlike `invalidate`,
+/// this never discards writes.
+pub fn writeback_invalidate<T>(data: &[T]) {
+    unsafe {
+        sys::sceKernelDcacheWritebackInvalidateRange(
+            data.as_ptr() as *const c_void,
+            mem::size_of_val(data) as u32,
+        );
+    }
+}
+
+/// Invalidate the cache lines covering `data`, without writing them back.
+///
+/// Call this after hardware has written to `data`, before the CPU reads it.
+///
+/// # Safety
+///
+/// Whole cache lines are discarded. If `data` does not start and end on a
+/// `CACHE_LINE_SIZE` boundary, pending CPU writes to whatever shares the first
+/// and last line are lost. `DmaBuffer` avoids this.
+pub unsafe fn invalidate<T>(data: &mut [T]) {
+    sys::sceKernelDcacheInvalidateRange(
+        data.as_ptr() as *const c_void,
+        mem::size_of_val(data) as u32,
+    );
+}
+
+/// Write back the entire data cache.
+pub fn writeback_all() {
+    unsafe { sys::sceKernelDcacheWritebackAll() }
+}
+
+/// A pointer which accesses memory through its uncached alias.
+#[derive(Debug)]
+pub struct UncachedPtr<T>(*mut T);
+
+impl<T> Clone for UncachedPtr<T> {
+    fn clone(&self) -> Self {
+        Self(self.0)
+    }
+}
+
+impl<T> Copy for UncachedPtr<T> {}
+
+impl<T> UncachedPtr<T> {
+    /// Create an uncached pointer from a cached or uncached one.
+    pub fn new(ptr: *mut T) -> Self {
+        Self(uncached(ptr))
+    }
+
+    /// The uncached address.
+    pub fn as_ptr(self) -> *mut T {
+        self.0
+    }
+
+    /// # Safety
+    ///
+    /// Same as `core::ptr::read_volatile`.
+    pub unsafe fn read(self) -> T {
+        self.0.read_volatile()
+    }
+
+    /// # Safety
+    ///
+    /// Same as `core::ptr::write_volatile`.
+    pub unsafe fn write(self, value: T) {
+        self.0.write_volatile(value)
+    }
+
+    /// # Safety
+    ///
+    /// Same as `pointer::add`.
+    pub unsafe fn add(self, count: usize) -> Self {
+        Self(self.0.add(count))
+    }
+}
+
+/// A slice accessed through its uncached alias.
+///
+/// Writes go straight to memory, so hardware sees them immediately, and reads
+/// always see what hardware has written.
+pub struct UncachedSlice<'a, T> {
+    ptr: *mut T,
+    len: usize,
+    _marker: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T: Copy> UncachedSlice<'a, T> {
+    /// Access `data` through its uncached alias.
+    ///
+    /// The cached copy is written back and invalidated first, so that dirty
+    /// lines can't later be evicted over data written through this view.
+    pub fn new(data: &'a mut [T]) -> Self {
+        writeback_invalidate(data);
+
+        Self {
+            ptr: uncached(data.as_mut_ptr()),
+            len: data.len(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` must be valid for reads and writes of `len` elements for `'a`,
+    /// and no cache lines covering it may be dirty.
+    pub unsafe fn from_raw_parts(ptr: *mut T, len: usize) -> Self {
+        Self {
+            ptr: uncached(ptr),
+            len,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The uncached address of the first element.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.ptr
+    }
+
+    /// Read an element. Panics if `index` is out of bounds.
+    pub fn get(&self, index: usize) -> T {
+        assert!(index < self.len);
+        unsafe { self.ptr.add(index).read_volatile() }
+    }
+
+    /// Write an element. Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, value: T) {
+        assert!(index < self.len);
+        unsafe { self.ptr.add(index).write_volatile(value) }
+    }
+
+    /// Copy all elements from `src`, which must have the same length.
+    pub fn copy_from_slice(&mut self, src: &[T]) {
+        assert_eq!(src.len(), self.len);
+
+        for (i, value) in src.iter().enumerate() {
+            unsafe { self.ptr.add(i).write_volatile(*value) }
+        }
+    }
+
+    /// Copy all elements into `dst`, which must have the same length.
+    pub fn copy_to_slice(&self, dst: &mut [T]) {
+        assert_eq!(dst.len(), self.len);
+
+        for (i, value) in dst.iter_mut().enumerate() {
+            *value = unsafe { self.ptr.add(i).read_volatile() };
+        }
+    }
+}
+
+/// A heap buffer shared with hardware.
+///
+/// The buffer starts and ends on a cache line boundary, so invalidating it
+/// can't discard writes to neighbouring data. Use `as_device_ptr` to get the
+/// pointer to hand to hardware, which first writes back the CPU's changes, and
+/// call `sync_for_cpu` once hardware has finished writing to it.
+#[cfg(not(feature = "stub-only"))]
+pub struct DmaBuffer<T: Copy> {
+    ptr: NonNull<T>,
+    len: usize,
+}
+
+#[cfg(not(feature = "stub-only"))]
+impl<T: Copy> DmaBuffer<T> {
+    /// Allocate a zeroed buffer of `len` elements.
+    ///
+    /// # Safety
+    ///
+    /// All zeroes must be a valid value of `T`.
+    pub unsafe fn zeroed(len: usize) -> Self {
+        let layout = Self::layout(len);
+        let ptr = alloc_zeroed(layout) as *mut T;
+
+        let ptr = match NonNull::new(ptr) {
+            Some(ptr) => ptr,
+            None => handle_alloc_error(layout),
+        };
+
+        // The buffer was zeroed through the cache.
+        let buffer = Self { ptr, len };
+        writeback_invalidate(buffer.whole());
+        buffer
+    }
+
+    /// Allocate a buffer of `len` copies of `value`.
+    pub fn from_elem(value: T, len: usize) -> Self {
+        unsafe {
+            let mut buffer = Self::zeroed(len);
+
+            for elem in buffer.iter_mut() {
+                *elem = value;
+            }
+
+            buffer
+        }
+    }
+
+    fn layout(len: usize) -> Layout {
+        let size = mem::size_of::<T>()
+            .checked_mul(len)
+            .expect("DMA buffer size overflow");
+
+        let size = (size + CACHE_LINE_SIZE - 1) & !(CACHE_LINE_SIZE - 1);
+        let align = core::cmp::max(CACHE_LINE_SIZE, mem::align_of::<T>());
+
+        Layout::from_size_align(size.max(CACHE_LINE_SIZE), align).unwrap()
+    }
+
+    /// The whole allocation as bytes, including the padding to the end of the
+    /// last cache line.
+    fn whole(&self) -> &[u8] {
+        let size = Self::layout(self.len).size();
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr() as *const u8, size) }
+    }
+
+    /// Write back the CPU's changes, and return the pointer to pass to
+    /// hardware.
+    ///
+    /// The cache lines are also invalidated, so the CPU will see anything the
+    /// hardware writes after `sync_for_cpu`.
+    pub fn as_device_ptr(&mut self) -> *mut c_void {
+        writeback_invalidate(self.whole());
+        self.ptr.as_ptr() as *mut c_void
+    }
+
+    /// Discard cached data, so the CPU sees what hardware has written since
+    /// `as_device_ptr`.
+    pub fn sync_for_cpu(&mut self) {
+        let size = Self::layout(self.len).size();
+
+        unsafe {
+            sys::sceKernelDcacheInvalidateRange(self.ptr.as_ptr() as *const c_void, size as u32);
+        }
+    }
+
+    /// Access the buffer through its uncached alias.
+    pub fn uncached(&mut self) -> UncachedSlice<'_, T> {
+        UncachedSlice::new(&mut *self)
+    }
+}
+
+#[cfg(not(feature = "stub-only"))]
+impl<T: Copy> Deref for DmaBuffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+#[cfg(not(feature = "stub-only"))]
+impl<T: Copy> DerefMut for DmaBuffer<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+#[cfg(not(feature = "stub-only"))]
+impl<T: Copy> Drop for DmaBuffer<T> {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.ptr.as_ptr() as *mut u8, Self::layout(self.len)) }
+    }
+}
@@ -0,0 +1,324 @@
+//! System events: exit requests, power changes, UMD and memory stick
+//! insertion.
+//!
+//! Kernel callbacks only run on the thread which created them, while it sleeps
+//! in a `*CB` function. The first call to `subscribe` (or `start`) spawns a
+//! callback thread which owns the callbacks and sleeps in
+//! `sceKernelSleepThreadCB`, and which forwards events to every `Receiver`
+//! subscribed to them.
+//!
+//! ```no_run
+//! use psp::callback::{self, Event, EventKinds};
+//!
+//! let events = callback::subscribe(EventKinds::EXIT | EventKinds::MEMORY_STICK).unwrap();
+//!
+//! loop {
+//!     while let Some(event) = events.try_recv() {
+//!         match event {
+//!             Event::ExitRequested => {
+//!                 // Save the game...
+//!                 callback::exit_game();
+//!             }
+//!             _ => (),
+//!         }
+//!     }
+//!
+//!     // Run a frame...
+//! }
+//! ```
+//!
+//! # Exit requests
+//!
+//! While no receiver is subscribed to `EventKinds::EXIT`, choosing "Exit" from
+//! the home menu quits immediately. Otherwise, the game keeps running until it
+//! calls `exit_game`, so it can save first.
+
+use crate::error::SceReturn;
+use crate::sys::{self, EventFlagAttributes, EventFlagWaitTypes, PowerInfo, SceUid, ThreadAttributes, UmdStateFlags};
+use crate::SceResult;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::ptr;
+
+bitflags::bitflags! {
+    /// The kinds of event a `Receiver` is subscribed to.
+    pub struct EventKinds: u32 {
+        const EXIT = 1;
+        const POWER = 2;
+        const UMD = 4;
+        const MEMORY_STICK = 8;
+    }
+}
+
+/// A system event.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The user chose "Exit" from the home menu. Call `exit_game` when ready.
+    ExitRequested,
+    /// The power state changed, e.g. the unit is suspending or resuming, or
+    /// the AC adapter was plugged in.
+    Power(PowerInfo),
+    /// The UMD drive state changed, e.g. a disc was inserted or ejected.
+    Umd(UmdStateFlags),
+    /// A memory stick was inserted or ejected.
+    MemoryStick(MemoryStickEvent),
+}
+
+impl Event {
+    fn kind(&self) -> EventKinds {
+        match self {
+            Event::ExitRequested => EventKinds::EXIT,
+            Event::Power(_) => EventKinds::POWER,
+            Event::Umd(_) => EventKinds::UMD,
+            Event::MemoryStick(_) => EventKinds::MEMORY_STICK,
+        }
+    }
+}
+
+/// See `sys::MsCbEvent`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MemoryStickEvent {
+    Inserted,
+    Ejected,
+}
+
+/// The event flag bit set when a receiver's queue becomes non-empty.
+const QUEUED: u32 = 1;
+
+struct Subscriber {
+    id: usize,
+    kinds: EventKinds,
+    flag: SceUid,
+    queue: VecDeque<Event>,
+}
+
+struct State {
+    started: bool,
+    next_id: usize,
+    subscribers: Vec<Subscriber>,
+}
+
+struct Manager(UnsafeCell<State>);
+
+// Only accessed with interrupts suspended.
+unsafe impl Sync for Manager {}
+
+static MANAGER: Manager = Manager(UnsafeCell::new(State {
+    started: false,
+    next_id: 0,
+    subscribers: Vec::new(),
+}));
+
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    unsafe {
+        let flags = sys::sceKernelCpuSuspendIntr();
+        let result = f(&mut *MANAGER.0.get());
+        sys::sceKernelCpuResumeIntr(flags);
+
+        result
+    }
+}
+
+/// Queue `event` for its subscribers. Returns false if there were none.
+fn dispatch(event: Event) -> bool {
+    with_state(|state| {
+        let mut delivered = false;
+
+        for subscriber in &mut state.subscribers {
+            if subscriber.kinds.contains(event.kind()) {
+                subscriber.queue.push_back(event);
+                delivered = true;
+
+                unsafe {
+                    sys::sceKernelSetEventFlag(subscriber.flag, QUEUED);
+                }
+            }
+        }
+
+        delivered
+    })
+}
+
+unsafe extern "C" fn on_exit(_arg1: i32, _arg2: i32, _arg: *mut c_void) -> i32 {
+    if !dispatch(Event::ExitRequested) {
+        sys::sceKernelExitGame();
+    }
+
+    0
+}
+
+unsafe extern "C" fn on_power(_count: i32, power_info: i32, _arg: *mut c_void) -> i32 {
+    dispatch(Event::Power(PowerInfo::from_bits_truncate(power_info as u32)));
+    0
+}
+
+unsafe extern "C" fn on_umd(_arg1: i32, state: i32, _arg: *mut c_void) -> i32 {
+    dispatch(Event::Umd(UmdStateFlags::from_bits_truncate(state)));
+    0
+}
+
+unsafe extern "C" fn on_memory_stick(_arg1: i32, event: i32, _arg: *mut c_void) -> i32 {
+    let event = match event {
+        e if e == sys::MsCbEvent::Inserted as i32 => MemoryStickEvent::Inserted,
+        e if e == sys::MsCbEvent::Ejected as i32 => MemoryStickEvent::Ejected,
+        _ => return 0,
+    };
+
+    dispatch(Event::MemoryStick(event));
+    0
+}
+
+unsafe extern "C" fn callback_thread(_args: usize, _argp: *mut c_void) -> i32 {
+    let exit = sys::sceKernelCreateCallback(&b"exit_callback\0"[0], on_exit, ptr::null_mut());
+    sys::sceKernelRegisterExitCallback(exit);
+
+    let power = sys::sceKernelCreateCallback(&b"power_callback\0"[0], on_power, ptr::null_mut());
+    sys::scePowerRegisterCallback(-1, power);
+
+    let umd = sys::sceKernelCreateCallback(&b"umd_callback\0"[0], on_umd, ptr::null_mut());
+    sys::sceUmdRegisterUMDCallBack(umd.0);
+
+    let ms = sys::sceKernelCreateCallback(&b"ms_callback\0"[0], on_memory_stick, ptr::null_mut());
+    sys::MScmRegisterMSInsertEjectCallback(ms);
+
+    // Callbacks run while sleeping here. Nothing wakes the thread, so it
+    // sleeps for the rest of the program.
+    loop {
+        sys::sceKernelSleepThreadCB();
+    }
+}
+
+/// Start the callback thread, if it isn't running yet.
+///
+/// `subscribe` calls this, so it's only needed to make the home menu's "Exit"
+/// work without subscribing to anything.
+pub fn start() -> SceResult<()> {
+    if with_state(|state| core::mem::replace(&mut state.started, true)) {
+        return Ok(());
+    }
+
+    let result = unsafe {
+        let id = sys::sceKernelCreateThread(
+            &b"callback_thread\0"[0],
+            callback_thread,
+            // Above the main thread's 32, so events are delivered promptly.
+            17,
+            0x4000,
+            ThreadAttributes::USER,
+            ptr::null_mut(),
+        )
+        .into_result();
+
+        id.and_then(|id| sys::sceKernelStartThread(id, 0, ptr::null_mut()).into_result())
+    };
+
+    if let Err(e) = result {
+        with_state(|state| state.started = false);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// Subscribe to some kinds of event. Events are queued from the time of this
+/// call until the `Receiver` is dropped.
+pub fn subscribe(kinds: EventKinds) -> SceResult<Receiver> {
+    start()?;
+
+    let flag = unsafe {
+        sys::sceKernelCreateEventFlag(
+            &b"callback_events\0"[0],
+            EventFlagAttributes::empty(),
+            0,
+            ptr::null_mut(),
+        )
+    }
+    .into_result()?;
+
+    let id = with_state(|state| {
+        let id = state.next_id;
+        state.next_id += 1;
+
+        state.subscribers.push(Subscriber {
+            id,
+            kinds,
+            flag,
+            queue: VecDeque::new(),
+        });
+
+        id
+    });
+
+    Ok(Receiver { id, flag })
+}
+
+/// Quit to the XMB. Call this after handling `Event::ExitRequested`.
+pub fn exit_game() {
+    unsafe { sys::sceKernelExitGame() }
+}
+
+/// Receives the events it was subscribed to, in the order they happened.
+pub struct Receiver {
+    id: usize,
+    flag: SceUid,
+}
+
+impl Receiver {
+    /// Take the next event, if any.
+    pub fn try_recv(&self) -> Option<Event> {
+        let id = self.id;
+
+        with_state(|state| {
+            state
+                .subscribers
+                .iter_mut()
+                .find(|s| s.id == id)
+                .and_then(|s| s.queue.pop_front())
+        })
+    }
+
+    /// Wait for the next event. Callbacks registered on the calling thread run
+    /// while waiting.
+    pub fn recv(&self) -> Event {
+        loop {
+            if let Some(event) = self.try_recv() {
+                return event;
+            }
+
+            unsafe {
+                sys::sceKernelWaitEventFlagCB(
+                    self.flag,
+                    QUEUED,
+                    EventFlagWaitTypes::OR | EventFlagWaitTypes::CLEAR,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                );
+            }
+        }
+    }
+
+    /// An iterator over the events queued so far.
+    pub fn try_iter(&self) -> impl Iterator<Item = Event> + '_ {
+        core::iter::from_fn(move || self.try_recv())
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        let id = self.id;
+
+        // The queue is freed outside the critical section.
+        let subscriber = with_state(|state| {
+            let index = state.subscribers.iter().position(|s| s.id == id)?;
+            Some(state.subscribers.swap_remove(index))
+        });
+
+        drop(subscriber);
+
+        unsafe {
+            sys::sceKernelDeleteEventFlag(self.flag);
+        }
+    }
+}
@@ -107,8 +107,7 @@ unsafe fn put_str<T: Font>(s: &[u8], x: usize, y: usize, color: u32) {
 }
 
 unsafe fn init() {
-    // The OR operation here specifies the address bypasses cache.
-    VRAM_BASE = (0x4000_0000u32 | sys::sceGeEdramGetAddr() as u32) as *mut u32;
+    VRAM_BASE = crate::cache::uncached(sys::sceGeEdramGetAddr() as *mut u32);
 
     // TODO: Change sys types to usize.
     sys::sceDisplaySetMode(sys::DisplayMode::Lcd, DISPLAY_WIDTH, DISPLAY_HEIGHT);
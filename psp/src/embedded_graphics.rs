@@ -18,7 +18,7 @@ impl Framebuffer {
     pub fn new() -> Self {
         unsafe {
             sys::sceDisplaySetMode(sys::DisplayMode::Lcd, 480, 272);
-            let vram_base = (0x4000_0000u32 | sys::sceGeEdramGetAddr() as u32) as *mut u16;
+            let vram_base = crate::cache::uncached(sys::sceGeEdramGetAddr() as *mut u16);
             sys::sceDisplaySetFrameBuf(
                 vram_base as *const u8,
                 BUF_WIDTH as usize,
@@ -0,0 +1,369 @@
+//! Errors returned by the PSP OS.
+//!
+//! System functions signal errors by returning a negative value, laid out as
+//! follows:
+//!
+//! ```txt
+//! 31  30  29..28  27..16    15..0
+//! E   C   -       facility  code
+//! ```
+//!
+//! - `E`: Always set for errors.
+//! - `C`: Set for critical errors.
+//! - `facility`: The subsystem which raised the error.
+//! - `code`: The error code within that facility. For the `Errno` facility,
+//!   this is a POSIX `errno` value.
+
+use crate::sys::SceUid;
+use core::fmt;
+
+/// A `Result` carrying an `Error` returned by the PSP OS.
+pub type SceResult<T> = Result<T, Error>;
+
+/// Return values which are negative to signal an error.
+pub trait SceReturn: Sized {
+    /// Convert a return value to `Ok` if it is non-negative, otherwise `Err`.
+    fn into_result(self) -> SceResult<Self>;
+}
+
+impl SceReturn for i32 {
+    fn into_result(self) -> SceResult<i32> {
+        if self < 0 {
+            Err(Error(self))
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+impl SceReturn for SceUid {
+    fn into_result(self) -> SceResult<SceUid> {
+        self.0.into_result().map(SceUid)
+    }
+}
+
+/// The subsystem which raised an error.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Facility {
+    Null,
+    /// POSIX `errno` values, mostly from the I/O manager.
+    Errno,
+    /// The kernel.
+    Kernel,
+    /// `sceUtility` dialogs and modules.
+    Utility,
+    /// `sceAudio`.
+    Audio,
+    /// `sceSasCore`.
+    Sas,
+    /// `sceMp3`.
+    Mp3,
+    /// Media Engine codecs, e.g. `sceMpeg`.
+    Codec,
+    /// `sceAtrac3plus`.
+    Atrac,
+    /// `sceJpeg`.
+    Jpeg,
+    /// Any other facility, by number.
+    Other(u16),
+}
+
+impl Facility {
+    fn from_raw(raw: u16) -> Self {
+        match raw {
+            0x000 => Facility::Null,
+            0x001 => Facility::Errno,
+            0x002 => Facility::Kernel,
+            0x011 => Facility::Utility,
+            0x026 => Facility::Audio,
+            0x042 => Facility::Sas,
+            0x061 | 0x062 => Facility::Codec,
+            0x063 => Facility::Atrac,
+            0x065 => Facility::Jpeg,
+            0x067 => Facility::Mp3,
+            _ => Facility::Other(raw),
+        }
+    }
+}
+
+/// An error code returned by the PSP OS.
+///
+/// Known codes are available as associated constants, which can be used in
+/// patterns:
+///
+/// ```ignore
+/// match file {
+///     Err(Error::ERRNO_FILE_NOT_FOUND) => create_save(),
+///     Err(e) => panic!("{}", e),
+///     Ok(file) => load_save(file),
+/// }
+/// ```
+#[repr(transparent)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Error(i32);
+
+impl Error {
+    /// Create an error from a raw (negative) return value.
+    pub const fn from_raw(raw: i32) -> Self {
+        Self(raw)
+    }
+
+    /// The raw return value.
+    pub const fn raw(self) -> i32 {
+        self.0
+    }
+
+    /// Whether the critical bit is set.
+    pub fn is_critical(self) -> bool {
+        self.0 as u32 & 0x4000_0000 != 0
+    }
+
+    /// The subsystem which raised this error.
+    pub fn facility(self) -> Facility {
+        Facility::from_raw(((self.0 as u32 >> 16) & 0xfff) as u16)
+    }
+
+    /// The error code within the facility.
+    pub fn code(self) -> u16 {
+        self.0 as u16
+    }
+
+    /// The name of this error, if it is known.
+    pub fn name(self) -> Option<&'static str> {
+        ERRORS.iter().find(|(e, _, _)| *e == self).map(|(_, name, _)| *name)
+    }
+
+    /// A description of this error, if it is known.
+    pub fn description(self) -> Option<&'static str> {
+        ERRORS.iter().find(|(e, _, _)| *e == self).map(|(_, _, desc)| *desc)
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "Error({}, {:#010x})", name, self.0),
+            None => write!(f, "Error({:#010x})", self.0),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.description() {
+            Some(desc) => write!(f, "{} ({:#010x})", desc, self.0),
+            None => write!(f, "{:?} error {:#06x} ({:#010x})", self.facility(), self.code(), self.0),
+        }
+    }
+}
+
+macro_rules! errors {
+    ($($name:ident = $code:literal, $desc:literal;)*) => {
+        impl Error {
+            $(
+                #[doc = $desc]
+                pub const $name: Error = Error($code as u32 as i32);
+            )*
+        }
+
+        const ERRORS: &[(Error, &str, &str)] = &[
+            $((Error::$name, stringify!($name), $desc),)*
+        ];
+    }
+}
+
+errors! {
+    ERRNO_OPERATION_NOT_PERMITTED = 0x8001_0001, "Operation not permitted";
+    ERRNO_FILE_NOT_FOUND = 0x8001_0002, "No such file or directory";
+    ERRNO_IO_ERROR = 0x8001_0005, "I/O error";
+    ERRNO_BAD_FILE_DESCRIPTOR = 0x8001_0009, "Bad file descriptor";
+    ERRNO_RESOURCE_UNAVAILABLE = 0x8001_000B, "Resource temporarily unavailable";
+    ERRNO_NO_MEMORY = 0x8001_000C, "Out of memory";
+    ERRNO_PERMISSION_DENIED = 0x8001_000D, "Permission denied";
+    ERRNO_BAD_ADDRESS = 0x8001_000E, "Bad address";
+    ERRNO_DEVICE_BUSY = 0x8001_0010, "Device or resource busy";
+    ERRNO_FILE_ALREADY_EXISTS = 0x8001_0011, "File already exists";
+    ERRNO_CROSS_DEVICE_LINK = 0x8001_0012, "Cross-device link";
+    ERRNO_DEVICE_NOT_FOUND = 0x8001_0013, "No such device";
+    ERRNO_NOT_A_DIRECTORY = 0x8001_0014, "Not a directory";
+    ERRNO_IS_A_DIRECTORY = 0x8001_0015, "Is a directory";
+    ERRNO_INVALID_ARGUMENT = 0x8001_0016, "Invalid argument";
+    ERRNO_TOO_MANY_OPEN_FILES = 0x8001_0018, "Too many open files";
+    ERRNO_FILE_TOO_LARGE = 0x8001_001B, "File too large";
+    ERRNO_NO_SPACE = 0x8001_001C, "No space left on device";
+    ERRNO_READ_ONLY = 0x8001_001E, "Read-only file system";
+    ERRNO_DIRECTORY_NOT_EMPTY = 0x8001_005A, "Directory not empty";
+    ERRNO_NAME_TOO_LONG = 0x8001_005B, "File name too long";
+    ERRNO_TIMED_OUT = 0x8001_0074, "Timed out";
+    ERRNO_IN_PROGRESS = 0x8001_0077, "Operation in progress";
+    ERRNO_ALREADY = 0x8001_0078, "Operation already in progress";
+    ERRNO_NOT_SUPPORTED = 0x8001_0086, "Operation not supported";
+    ERRNO_NO_MEDIA = 0x8001_0087, "No medium found";
+
+    SCE_KERNEL_ERROR_ERROR = 0x8002_0001, "Generic kernel error";
+    SCE_KERNEL_ERROR_NOT_IMPLEMENTED = 0x8002_0002, "Not implemented";
+    SCE_KERNEL_ERROR_ILLEGAL_CONTEXT = 0x8002_0064, "Called from an illegal context";
+    SCE_KERNEL_ERROR_ILLEGAL_INTRCODE = 0x8002_0065, "Illegal interrupt code";
+    SCE_KERNEL_ERROR_ILLEGAL_ADDRESS = 0x8002_006A, "Illegal address";
+    SCE_KERNEL_ERROR_UNKNOWN_UID = 0x8002_00CB, "Unknown UID";
+    SCE_KERNEL_ERROR_UNMATCH_UID_TYPE = 0x8002_00CC, "UID refers to an object of another type";
+    SCE_KERNEL_ERROR_ILLEGAL_PERM = 0x8002_00D1, "Illegal permission";
+    SCE_KERNEL_ERROR_ILLEGAL_ARGUMENT = 0x8002_00D2, "Illegal argument";
+    SCE_KERNEL_ERROR_ILLEGAL_ADDR = 0x8002_00D3, "Illegal address";
+    SCE_KERNEL_ERROR_OUT_OF_RANGE = 0x8002_00D4, "Out of range";
+    SCE_KERNEL_ERROR_ILLEGAL_PARTITION = 0x8002_00D6, "Illegal memory partition";
+    SCE_KERNEL_ERROR_ILLEGAL_MEMBLOCKTYPE = 0x8002_00D8, "Illegal memory block type";
+    SCE_KERNEL_ERROR_MEMBLOCK_ALLOC_FAILED = 0x8002_00D9, "Memory block allocation failed";
+    SCE_KERNEL_ERROR_LINKERR = 0x8002_012C, "Module link error";
+    SCE_KERNEL_ERROR_ILLEGAL_OBJECT = 0x8002_012D, "Illegal module object";
+    SCE_KERNEL_ERROR_UNKNOWN_MODULE = 0x8002_012E, "Unknown module";
+    SCE_KERNEL_ERROR_NOFILE = 0x8002_012F, "Module file not found";
+    SCE_KERNEL_ERROR_FILEERR = 0x8002_0130, "Module file error";
+    SCE_KERNEL_ERROR_ALREADY_STARTED = 0x8002_0133, "Module already started";
+    SCE_KERNEL_ERROR_NOT_STARTED = 0x8002_0134, "Module not started";
+    SCE_KERNEL_ERROR_ALREADY_STOPPED = 0x8002_0135, "Module already stopped";
+    SCE_KERNEL_ERROR_EXCLUSIVE_LOAD = 0x8002_0139, "Module can only be loaded once";
+    SCE_KERNEL_ERROR_LIBRARY_NOTFOUND = 0x8002_013C, "Library not found";
+    SCE_KERNEL_ERROR_NO_MEMORY = 0x8002_0190, "Out of kernel memory";
+    SCE_KERNEL_ERROR_ILLEGAL_ATTR = 0x8002_0191, "Illegal attribute";
+    SCE_KERNEL_ERROR_ILLEGAL_ENTRY = 0x8002_0192, "Illegal entry point";
+    SCE_KERNEL_ERROR_ILLEGAL_PRIORITY = 0x8002_0193, "Illegal priority";
+    SCE_KERNEL_ERROR_ILLEGAL_STACK_SIZE = 0x8002_0194, "Illegal stack size";
+    SCE_KERNEL_ERROR_ILLEGAL_MODE = 0x8002_0195, "Illegal mode";
+    SCE_KERNEL_ERROR_ILLEGAL_MASK = 0x8002_0196, "Illegal mask";
+    SCE_KERNEL_ERROR_ILLEGAL_THID = 0x8002_0197, "Illegal thread ID";
+    SCE_KERNEL_ERROR_UNKNOWN_THID = 0x8002_0198, "Unknown thread ID";
+    SCE_KERNEL_ERROR_UNKNOWN_SEMID = 0x8002_0199, "Unknown semaphore ID";
+    SCE_KERNEL_ERROR_UNKNOWN_EVFID = 0x8002_019A, "Unknown event flag ID";
+    SCE_KERNEL_ERROR_UNKNOWN_MBXID = 0x8002_019B, "Unknown message box ID";
+    SCE_KERNEL_ERROR_UNKNOWN_VPLID = 0x8002_019C, "Unknown variable pool ID";
+    SCE_KERNEL_ERROR_UNKNOWN_FPLID = 0x8002_019D, "Unknown fixed pool ID";
+    SCE_KERNEL_ERROR_UNKNOWN_MPPID = 0x8002_019E, "Unknown message pipe ID";
+    SCE_KERNEL_ERROR_UNKNOWN_ALMID = 0x8002_019F, "Unknown alarm ID";
+    SCE_KERNEL_ERROR_UNKNOWN_TEID = 0x8002_01A0, "Unknown thread event handler ID";
+    SCE_KERNEL_ERROR_UNKNOWN_CBID = 0x8002_01A1, "Unknown callback ID";
+    SCE_KERNEL_ERROR_DORMANT = 0x8002_01A2, "Thread is dormant";
+    SCE_KERNEL_ERROR_SUSPEND = 0x8002_01A3, "Thread is suspended";
+    SCE_KERNEL_ERROR_NOT_DORMANT = 0x8002_01A4, "Thread is not dormant";
+    SCE_KERNEL_ERROR_NOT_SUSPEND = 0x8002_01A5, "Thread is not suspended";
+    SCE_KERNEL_ERROR_NOT_WAIT = 0x8002_01A6, "Thread is not waiting";
+    SCE_KERNEL_ERROR_CAN_NOT_WAIT = 0x8002_01A7, "Waiting is not allowed";
+    SCE_KERNEL_ERROR_WAIT_TIMEOUT = 0x8002_01A8, "Wait timed out";
+    SCE_KERNEL_ERROR_WAIT_CANCEL = 0x8002_01A9, "Wait cancelled";
+    SCE_KERNEL_ERROR_RELEASE_WAIT = 0x8002_01AA, "Wait released";
+    SCE_KERNEL_ERROR_NOTIFY_CALLBACK = 0x8002_01AB, "Wait interrupted by a callback";
+    SCE_KERNEL_ERROR_THREAD_TERMINATED = 0x8002_01AC, "Thread terminated";
+    SCE_KERNEL_ERROR_SEMA_ZERO = 0x8002_01AD, "Semaphore count is zero";
+    SCE_KERNEL_ERROR_SEMA_OVF = 0x8002_01AE, "Semaphore count overflow";
+    SCE_KERNEL_ERROR_EVF_COND = 0x8002_01AF, "Event flag condition not met";
+    SCE_KERNEL_ERROR_EVF_MULTI = 0x8002_01B0, "Event flag already waited on";
+    SCE_KERNEL_ERROR_EVF_ILPAT = 0x8002_01B1, "Illegal event flag pattern";
+    SCE_KERNEL_ERROR_MBOX_NOMSG = 0x8002_01B2, "Message box is empty";
+    SCE_KERNEL_ERROR_MPP_FULL = 0x8002_01B3, "Message pipe is full";
+    SCE_KERNEL_ERROR_MPP_EMPTY = 0x8002_01B4, "Message pipe is empty";
+    SCE_KERNEL_ERROR_WAIT_DELETE = 0x8002_01B5, "Waited-on object was deleted";
+    SCE_KERNEL_ERROR_ILLEGAL_MEMBLOCK = 0x8002_01B6, "Illegal memory block";
+    SCE_KERNEL_ERROR_ILLEGAL_MEMSIZE = 0x8002_01B7, "Illegal memory size";
+    SCE_KERNEL_ERROR_ILLEGAL_TYPE = 0x8002_01BB, "Illegal type";
+    SCE_KERNEL_ERROR_ILLEGAL_SIZE = 0x8002_01BC, "Illegal size";
+    SCE_KERNEL_ERROR_ILLEGAL_COUNT = 0x8002_01BD, "Illegal count";
+    SCE_KERNEL_ERROR_UNKNOWN_VTID = 0x8002_01BE, "Unknown VTimer ID";
+    SCE_KERNEL_ERROR_ILLEGAL_VTID = 0x8002_01BF, "Illegal VTimer ID";
+
+    SCE_AUDIO_ERROR_NOT_INITIALIZED = 0x8026_0001, "Audio channel not initialized";
+    SCE_AUDIO_ERROR_OUTPUT_BUSY = 0x8026_0002, "Audio channel is busy";
+    SCE_AUDIO_ERROR_INVALID_CHANNEL = 0x8026_0003, "Invalid audio channel";
+    SCE_AUDIO_ERROR_PRIV_REQUIRED = 0x8026_0004, "Audio operation requires privileges";
+    SCE_AUDIO_ERROR_NO_CHANNELS_AVAILABLE = 0x8026_0005, "No audio channels available";
+    SCE_AUDIO_ERROR_INVALID_SIZE = 0x8026_0006, "Sample count is not a multiple of 64";
+    SCE_AUDIO_ERROR_INVALID_FORMAT = 0x8026_0007, "Invalid audio format";
+    SCE_AUDIO_ERROR_NOT_RESERVED = 0x8026_0008, "Audio channel not reserved";
+    SCE_AUDIO_ERROR_NOT_OUTPUT = 0x8026_0009, "Audio channel is not outputting";
+    SCE_AUDIO_ERROR_INVALID_FREQUENCY = 0x8026_000A, "Invalid sample rate";
+    SCE_AUDIO_ERROR_INVALID_VOLUME = 0x8026_000B, "Invalid volume";
+
+    SCE_UTILITY_ERROR_MODULE_ALREADY_LOADED = 0x8011_1102, "Module already loaded";
+    SCE_UTILITY_ERROR_MODULE_NOT_LOADED = 0x8011_1103, "Module not loaded";
+
+    SCE_SAS_ERROR_INVALID_GRAIN = 0x8042_0001, "Invalid Sas grain size";
+    SCE_SAS_ERROR_INVALID_MAX_VOICES = 0x8042_0002, "Invalid number of Sas voices";
+    SCE_SAS_ERROR_INVALID_OUTPUT_MODE = 0x8042_0003, "Invalid Sas output mode";
+    SCE_SAS_ERROR_INVALID_SAMPLE_RATE = 0x8042_0004, "Invalid Sas sample rate";
+    SCE_SAS_ERROR_BAD_ADDRESS = 0x8042_0005, "Bad Sas address";
+    SCE_SAS_ERROR_INVALID_VOICE = 0x8042_0010, "Invalid Sas voice";
+    SCE_SAS_ERROR_INVALID_NOISE_FREQ = 0x8042_0011, "Invalid Sas noise frequency";
+    SCE_SAS_ERROR_INVALID_PITCH = 0x8042_0012, "Invalid Sas pitch";
+    SCE_SAS_ERROR_INVALID_ADSR_CURVE_MODE = 0x8042_0013, "Invalid Sas envelope curve";
+    SCE_SAS_ERROR_INVALID_PARAMETER = 0x8042_0014, "Invalid Sas parameter";
+    SCE_SAS_ERROR_INVALID_LOOP_POS = 0x8042_0015, "Invalid Sas loop position";
+    SCE_SAS_ERROR_VOICE_PAUSED = 0x8042_0016, "Sas voice is paused";
+    SCE_SAS_ERROR_INVALID_VOLUME = 0x8042_0018, "Invalid Sas volume";
+    SCE_SAS_ERROR_INVALID_ADSR_RATE = 0x8042_0019, "Invalid Sas envelope rate";
+    SCE_SAS_ERROR_INVALID_PCM_SIZE = 0x8042_001A, "Invalid Sas PCM size";
+    SCE_SAS_ERROR_REV_INVALID_TYPE = 0x8042_0020, "Invalid Sas effect type";
+    SCE_SAS_ERROR_REV_INVALID_FEEDBACK = 0x8042_0021, "Invalid Sas effect feedback";
+    SCE_SAS_ERROR_REV_INVALID_DELAY_TIME = 0x8042_0022, "Invalid Sas effect delay";
+    SCE_SAS_ERROR_REV_INVALID_VOLUME = 0x8042_0023, "Invalid Sas effect volume";
+    SCE_SAS_ERROR_BUSY = 0x8042_0030, "Sas is busy";
+    SCE_SAS_ERROR_NOT_INIT = 0x8042_0100, "Sas not initialized";
+    SCE_SAS_ERROR_ALREADY_INIT = 0x8042_0101, "Sas already initialized";
+
+    SCE_MPEG_ERROR_BAD_VERSION = 0x8061_0002, "Unsupported MPEG version";
+    SCE_MPEG_ERROR_NO_MEMORY = 0x8061_0022, "Not enough memory for MPEG";
+    SCE_MPEG_ERROR_INVALID_ADDR = 0x8061_0103, "Invalid MPEG address";
+    SCE_MPEG_ERROR_INVALID_VALUE = 0x8061_01FE, "Invalid MPEG value";
+    SCE_MPEG_ERROR_NO_DATA = 0x8061_8001, "No MPEG data available";
+    SCE_MPEG_ERROR_ALREADY_INIT = 0x8061_8005, "MPEG already initialized";
+    SCE_MPEG_ERROR_NOT_YET_INIT = 0x8061_8009, "MPEG not initialized";
+    SCE_MPEG_ERROR_AVC_INVALID_VALUE = 0x8062_01FE, "Invalid AVC value";
+    SCE_MPEG_ERROR_AVC_DECODE_FATAL = 0x8062_8002, "Fatal AVC decoding error";
+
+    SCE_PSMF_ERROR_NOT_INITIALIZED = 0x8061_5001, "PSMF not initialized";
+    SCE_PSMF_ERROR_INVALID_ID = 0x8061_5100, "Invalid PSMF stream or EP ID";
+    SCE_PSMF_ERROR_INVALID_VALUE = 0x8061_51FE, "Invalid PSMF value";
+    SCE_PSMF_ERROR_INVALID_TIMESTAMP = 0x8061_5500, "Invalid PSMF timestamp";
+    SCE_PSMF_ERROR_INVALID_PSMF = 0x8061_5501, "Not a PSMF header";
+    SCE_PSMF_PLAYER_ERROR_INVALID_STATUS = 0x8061_6001, "Invalid PSMF player status";
+    SCE_PSMF_PLAYER_ERROR_INVALID_STREAM = 0x8061_6003, "Invalid PSMF player stream";
+    SCE_PSMF_PLAYER_ERROR_BUFFER_SIZE = 0x8061_6005, "PSMF player buffer too small";
+    SCE_PSMF_PLAYER_ERROR_INVALID_CONFIG = 0x8061_6006, "Invalid PSMF player config";
+    SCE_PSMF_PLAYER_ERROR_INVALID_PARAM = 0x8061_6008, "Invalid PSMF player parameter";
+    SCE_PSMF_PLAYER_ERROR_NO_MORE_DATA = 0x8061_600C, "No more PSMF player data";
+
+    SCE_ATRAC_ERROR_PARAM_FAIL = 0x8063_0001, "Invalid Atrac parameter";
+    SCE_ATRAC_ERROR_API_FAIL = 0x8063_0002, "Atrac call failed";
+    SCE_ATRAC_ERROR_NO_ATRACID = 0x8063_0003, "No Atrac IDs available";
+    SCE_ATRAC_ERROR_BAD_CODEC_TYPE = 0x8063_0004, "Bad Atrac codec type";
+    SCE_ATRAC_ERROR_BAD_ATRACID = 0x8063_0005, "Bad Atrac ID";
+    SCE_ATRAC_ERROR_UNKNOWN_FORMAT = 0x8063_0006, "Unknown Atrac format";
+    SCE_ATRAC_ERROR_BAD_CODEC_PARAMS = 0x8063_0008, "Bad Atrac codec parameters";
+    SCE_ATRAC_ERROR_ALL_DATA_LOADED = 0x8063_0009, "All Atrac data is loaded";
+    SCE_ATRAC_ERROR_NO_DATA = 0x8063_0010, "No Atrac data set";
+    SCE_ATRAC_ERROR_SIZE_TOO_SMALL = 0x8063_0011, "Atrac buffer too small";
+    SCE_ATRAC_ERROR_SECOND_BUFFER_NEEDED = 0x8063_0012, "Atrac second buffer needed";
+    SCE_ATRAC_ERROR_INCORRECT_READ_SIZE = 0x8063_0013, "Incorrect Atrac read size";
+    SCE_ATRAC_ERROR_BAD_SAMPLE = 0x8063_0015, "Bad Atrac sample position";
+    SCE_ATRAC_ERROR_ADD_DATA_IS_TOO_BIG = 0x8063_0018, "Too much Atrac stream data added";
+    SCE_ATRAC_ERROR_NO_LOOP_INFORMATION = 0x8063_0021, "Atrac data has no loop points";
+    SCE_ATRAC_ERROR_SECOND_BUFFER_NOT_NEEDED = 0x8063_0022, "Atrac second buffer not needed";
+    SCE_ATRAC_ERROR_BUFFER_IS_EMPTY = 0x8063_0023, "Atrac buffer is empty";
+    SCE_ATRAC_ERROR_ALL_DATA_DECODED = 0x8063_0024, "All Atrac data is decoded";
+
+    SCE_JPEG_ERROR_INVALID_DATA = 0x8065_0004, "Invalid JPEG data";
+    SCE_JPEG_ERROR_INVALID_COLORSPACE = 0x8065_0013, "Unsupported JPEG color space";
+    SCE_JPEG_ERROR_INVALID_SIZE = 0x8065_0020, "Unsupported JPEG size";
+    SCE_JPEG_ERROR_NO_SOI = 0x8065_0023, "No JPEG start of image marker";
+    SCE_JPEG_ERROR_INVALID_STATE = 0x8065_0039, "Invalid JPEG decoder state";
+    SCE_JPEG_ERROR_OUT_OF_MEMORY = 0x8065_0041, "Not enough memory for JPEG";
+    SCE_JPEG_ERROR_INVALID_VALUE = 0x8065_0051, "Invalid JPEG value";
+
+    SCE_MP3_ERROR_INVALID_HANDLE = 0x8067_1001, "Invalid MP3 handle";
+    SCE_MP3_ERROR_BAD_ADDRESS = 0x8067_1002, "Bad MP3 buffer address";
+    SCE_MP3_ERROR_BAD_SIZE = 0x8067_1003, "Bad MP3 buffer size";
+    SCE_MP3_ERROR_UNRESERVED_HANDLE = 0x8067_1102, "MP3 handle not reserved";
+    SCE_MP3_ERROR_NOT_INITIALIZED = 0x8067_1103, "MP3 handle not initialized";
+    SCE_MP3_ERROR_NO_RESOURCE = 0x8067_1201, "No MP3 handles available";
+    SCE_MP3_ERROR_BAD_SAMPLE_RATE = 0x8067_1302, "Unsupported MP3 sample rate";
+    SCE_MP3_ERROR_END = 0x8067_1402, "End of MP3 stream";
+    SCE_MP3_ERROR_BAD_RESET_FRAME = 0x8067_1501, "Bad MP3 reset frame";
+}
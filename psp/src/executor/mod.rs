@@ -0,0 +1,354 @@
+//! A small single-threaded executor for `async` code.
+//!
+//! Instead of one kernel thread per job, a game loop can run vblank waits,
+//! timers, asynchronous file I/O and audio refills as tasks on one thread.
+//! When no task can make progress, the executor sleeps in
+//! `sceKernelWaitEventFlagCB`, so it uses no CPU time and kernel callbacks
+//! (such as the ones `fs::AsyncFile` registers) still run.
+//!
+//! ```no_run
+//! use psp::executor::{self, Timer};
+//! use core::time::Duration;
+//!
+//! executor::block_on(async {
+//!     loop {
+//!         executor::vblank().await;
+//!         // Draw a frame...
+//!         Timer::after(Duration::from_millis(100)).await;
+//!     }
+//! });
+//! ```
+//!
+//! # Wakers
+//!
+//! Waking a task only sets a flag and signals the executor's event flag, so
+//! `Waker::wake_by_ref` is safe to call from interrupt handlers such as alarms
+//! and VTimers. Wakers can be cloned and dropped on any thread, as their
+//! reference count is atomic, but not in interrupt handlers, since dropping
+//! the last one frees memory.
+
+use crate::error::SceReturn;
+use crate::sys::{self, EventFlagAttributes, EventFlagWaitTypes, SceUid};
+use crate::SceResult;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+mod sync;
+mod timer;
+mod vblank;
+
+pub use sync::*;
+pub use timer::*;
+pub use vblank::*;
+
+/// The event flag bit signalled when any task is woken.
+const WAKE: u32 = 1;
+
+/// Per-task state shared with its wakers.
+struct Header {
+    refs: AtomicUsize,
+    woken: AtomicBool,
+    flag: SceUid,
+}
+
+impl Header {
+    /// Allocate a header with one reference, already woken so that the task
+    /// is polled once.
+    fn new(flag: SceUid) -> NonNull<Header> {
+        let header = Box::new(Header {
+            refs: AtomicUsize::new(1),
+            woken: AtomicBool::new(true),
+            flag,
+        });
+
+        unsafe { NonNull::new_unchecked(Box::into_raw(header)) }
+    }
+
+    unsafe fn retain(header: *const Header) {
+        (*header).refs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    unsafe fn release(header: *const Header) {
+        // As in `Arc`, the last release must see every other thread's use.
+        if (*header).refs.fetch_sub(1, Ordering::AcqRel) == 1 {
+            drop(Box::from_raw(header as *mut Header));
+        }
+    }
+
+    fn waker(header: NonNull<Header>) -> Waker {
+        unsafe {
+            Header::retain(header.as_ptr());
+            Waker::from_raw(RawWaker::new(header.as_ptr() as *const (), &VTABLE))
+        }
+    }
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop_waker);
+
+unsafe fn clone(data: *const ()) -> RawWaker {
+    Header::retain(data as *const Header);
+    RawWaker::new(data, &VTABLE)
+}
+
+unsafe fn wake(data: *const ()) {
+    wake_by_ref(data);
+    drop_waker(data);
+}
+
+unsafe fn wake_by_ref(data: *const ()) {
+    let header = &*(data as *const Header);
+    header.woken.store(true, Ordering::Release);
+
+    // Fails harmlessly if the executor is gone.
+    sys::sceKernelSetEventFlag(header.flag, WAKE);
+}
+
+unsafe fn drop_waker(data: *const ()) {
+    Header::release(data as *const Header);
+}
+
+struct Task {
+    header: NonNull<Header>,
+    future: Pin<Box<dyn Future<Output = ()>>>,
+}
+
+impl Task {
+    fn new(flag: SceUid, future: impl Future<Output = ()> + 'static) -> Self {
+        Self {
+            header: Header::new(flag),
+            future: Box::pin(future),
+        }
+    }
+
+    fn is_woken(&self) -> bool {
+        unsafe { self.header.as_ref().woken.load(Ordering::Acquire) }
+    }
+
+    /// Poll the task if it has been woken. Returns true once it completes.
+    fn run(&mut self) -> bool {
+        let woken = unsafe { &self.header.as_ref().woken };
+
+        // Clear the flag before polling, so wakes during the poll are kept.
+        if !woken.swap(false, Ordering::AcqRel) {
+            return false;
+        }
+
+        let waker = Header::waker(self.header);
+        let mut cx = Context::from_waker(&waker);
+
+        self.future.as_mut().poll(&mut cx).is_ready()
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        // Wakers held by the future keep the header alive until they drop.
+        unsafe { Header::release(self.header.as_ptr()) }
+    }
+}
+
+struct Inner {
+    flag: SceUid,
+    tasks: RefCell<Vec<Task>>,
+    /// Tasks spawned while `tasks` is being run.
+    spawned: RefCell<Vec<Task>>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.spawned.get_mut().clear();
+        self.tasks.get_mut().clear();
+
+        unsafe {
+            sys::sceKernelDeleteEventFlag(self.flag);
+        }
+    }
+}
+
+/// A single-threaded executor.
+///
+/// Tasks run on whichever thread calls `block_on` or `run`. The executor is
+/// not `Send`, so it stays on the thread which created it.
+pub struct Executor {
+    inner: Rc<Inner>,
+}
+
+impl Executor {
+    /// Create an executor, which allocates a kernel event flag.
+    pub fn new() -> SceResult<Self> {
+        let flag = unsafe {
+            sys::sceKernelCreateEventFlag(
+                &b"executor\0"[0],
+                EventFlagAttributes::empty(),
+                0,
+                ptr::null_mut(),
+            )
+        }
+        .into_result()?;
+
+        Ok(Self {
+            inner: Rc::new(Inner {
+                flag,
+                tasks: RefCell::new(Vec::new()),
+                spawned: RefCell::new(Vec::new()),
+            }),
+        })
+    }
+
+    /// Spawn a task, which runs alongside the future passed to `block_on`,
+    /// or in `run`.
+    pub fn spawn(&self, future: impl Future<Output = ()> + 'static) {
+        self.spawner().spawn(future)
+    }
+
+    /// A handle which tasks can use to spawn more tasks.
+    pub fn spawner(&self) -> Spawner {
+        Spawner {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Run `future` to completion, along with any spawned tasks. Spawned tasks
+    /// which haven't finished when `future` completes are kept, and continue
+    /// in the next call to `block_on` or `run`.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let mut future = future;
+
+        // `future` is shadowed, so it can't be moved again.
+        let mut future = unsafe { Pin::new_unchecked(&mut future) };
+        let header = Header::new(self.inner.flag);
+
+        let result = loop {
+            if unsafe { header.as_ref().woken.swap(false, Ordering::AcqRel) } {
+                let waker = Header::waker(header);
+                let mut cx = Context::from_waker(&waker);
+
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    break output;
+                }
+            }
+
+            self.run_tasks();
+
+            if !unsafe { header.as_ref().woken.load(Ordering::Acquire) } {
+                self.wait();
+            }
+        };
+
+        unsafe { Header::release(header.as_ptr()) }
+
+        result
+    }
+
+    /// Run spawned tasks until they have all completed.
+    pub fn run(&self) {
+        loop {
+            self.run_tasks();
+
+            if self.inner.tasks.borrow().is_empty() && self.inner.spawned.borrow().is_empty() {
+                return;
+            }
+
+            self.wait();
+        }
+    }
+
+    /// Poll all woken tasks once, dropping the ones which complete.
+    fn run_tasks(&self) {
+        let mut tasks = self.inner.tasks.borrow_mut();
+        tasks.append(&mut self.inner.spawned.borrow_mut());
+
+        let mut i = 0;
+
+        while i < tasks.len() {
+            if tasks[i].run() {
+                tasks.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Sleep until a task is woken, unless one already has been.
+    fn wait(&self) {
+        let woken = self.inner.tasks.borrow().iter().any(Task::is_woken);
+
+        if woken || !self.inner.spawned.borrow().is_empty() {
+            return;
+        }
+
+        // Callbacks run while waiting here. A wake after the check above has
+        // already set the flag, so this returns immediately.
+        unsafe {
+            sys::sceKernelWaitEventFlagCB(
+                self.inner.flag,
+                WAKE,
+                EventFlagWaitTypes::OR | EventFlagWaitTypes::CLEAR,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+        }
+    }
+}
+
+/// A handle for spawning tasks onto an `Executor`, from inside other tasks.
+#[derive(Clone)]
+pub struct Spawner {
+    inner: Rc<Inner>,
+}
+
+impl Spawner {
+    /// Spawn a task. It is first polled on the executor's next iteration.
+    pub fn spawn(&self, future: impl Future<Output = ()> + 'static) {
+        let task = Task::new(self.inner.flag, future);
+        self.inner.spawned.borrow_mut().push(task);
+
+        unsafe {
+            sys::sceKernelSetEventFlag(self.inner.flag, WAKE);
+        }
+    }
+}
+
+/// Run `future` to completion on a new executor.
+///
+/// # Panics
+///
+/// Panics if the executor's event flag can't be created.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    Executor::new()
+        .expect("failed to create executor")
+        .block_on(future)
+}
+
+/// Yield to other tasks once.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// The future returned by `yield_now`.
+#[must_use = "futures do nothing unless polled"]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+
+        Poll::Pending
+    }
+}
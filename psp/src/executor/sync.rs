@@ -0,0 +1,88 @@
+use super::Timer;
+use crate::sys::{self, EventFlagWaitTypes, SceUid};
+use crate::{Error, SceResult};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// How often event flags and semaphores are polled while a task waits on them.
+///
+/// The kernel can't notify an executor when these change, so waiting tasks
+/// check again after this many microseconds.
+pub const POLL_INTERVAL_MICROS: u64 = 1000;
+
+/// Wait for an event flag, as `sceKernelWaitEventFlag` does, without blocking
+/// the thread. Resolves to the flag's bits as they were when the wait was
+/// satisfied.
+pub fn wait_event_flag(id: SceUid, bits: u32, wait: EventFlagWaitTypes) -> WaitEventFlag {
+    WaitEventFlag { id, bits, wait, retry: None }
+}
+
+/// The future returned by `wait_event_flag`.
+#[must_use = "futures do nothing unless polled"]
+pub struct WaitEventFlag {
+    id: SceUid,
+    bits: u32,
+    wait: EventFlagWaitTypes,
+    retry: Option<Timer>,
+}
+
+impl Future for WaitEventFlag {
+    type Output = SceResult<u32>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            if let Some(retry) = &mut self.retry {
+                if Pin::new(retry).poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+            }
+
+            let mut out_bits = 0;
+
+            match unsafe { sys::sceKernelPollEventFlag(self.id, self.bits, self.wait, &mut out_bits) } {
+                0 => return Poll::Ready(Ok(out_bits)),
+                e if Error::from_raw(e) == Error::SCE_KERNEL_ERROR_EVF_COND => {
+                    self.retry = Some(Timer::after_micros(POLL_INTERVAL_MICROS));
+                }
+                e => return Poll::Ready(Err(Error::from_raw(e))),
+            }
+        }
+    }
+}
+
+/// Take `count` from a semaphore, as `sceKernelWaitSema` does, without
+/// blocking the thread.
+pub fn wait_sema(id: SceUid, count: i32) -> WaitSema {
+    WaitSema { id, count, retry: None }
+}
+
+/// The future returned by `wait_sema`.
+#[must_use = "futures do nothing unless polled"]
+pub struct WaitSema {
+    id: SceUid,
+    count: i32,
+    retry: Option<Timer>,
+}
+
+impl Future for WaitSema {
+    type Output = SceResult<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            if let Some(retry) = &mut self.retry {
+                if Pin::new(retry).poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+            }
+
+            match unsafe { sys::sceKernelPollSema(self.id, self.count) } {
+                0 => return Poll::Ready(Ok(())),
+                e if Error::from_raw(e) == Error::SCE_KERNEL_ERROR_SEMA_ZERO => {
+                    self.retry = Some(Timer::after_micros(POLL_INTERVAL_MICROS));
+                }
+                e => return Poll::Ready(Err(Error::from_raw(e))),
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::error::SceReturn;
+use crate::sys::{self, SceUid};
+use crate::time::{self, Instant};
+use alloc::boxed::Box;
+use core::cell::{Cell, UnsafeCell};
+use core::ffi::c_void;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+/// State shared with the alarm handler. Boxed so its address is stable.
+struct Alarm {
+    fired: Cell<bool>,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+unsafe extern "C" fn on_alarm(common: *mut c_void) -> u32 {
+    let alarm = &*(common as *const Alarm);
+    alarm.fired.set(true);
+
+    if let Some(waker) = &*alarm.waker.get() {
+        waker.wake_by_ref();
+    }
+
+    // Don't reschedule.
+    0
+}
+
+/// A future which completes after a delay, using a kernel alarm.
+///
+/// The alarm handler runs in interrupt context and wakes the task with
+/// `Waker::wake_by_ref`, which `Executor` supports. Other executors must also
+/// allow this.
+#[must_use = "futures do nothing unless polled"]
+pub struct Timer {
+    deadline: Instant,
+    alarm: Option<(SceUid, Box<Alarm>)>,
+}
+
+impl Timer {
+    /// Complete after `duration`.
+    pub fn after(duration: Duration) -> Self {
+        Self::after_micros(time::as_micros_u64(duration))
+    }
+
+    /// Complete after `micros` microseconds.
+    pub fn after_micros(micros: u64) -> Self {
+        let now = Instant::now().as_micros();
+        Self::at(Instant::from_micros(now.saturating_add(micros)))
+    }
+
+    /// Complete at `deadline`.
+    pub fn at(deadline: Instant) -> Self {
+        Self { deadline, alarm: None }
+    }
+
+    fn cancel(&mut self) {
+        if let Some((id, alarm)) = self.alarm.take() {
+            if !alarm.fired.get() {
+                unsafe {
+                    sys::sceKernelCancelAlarm(id);
+                }
+            }
+        }
+    }
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let now = Instant::now();
+
+        if now >= self.deadline {
+            self.cancel();
+            return Poll::Ready(());
+        }
+
+        match &self.alarm {
+            Some((_, alarm)) if !alarm.fired.get() => {
+                // The handler can't run while interrupts are suspended.
+                unsafe {
+                    let flags = sys::sceKernelCpuSuspendIntr();
+                    let waker = &mut *alarm.waker.get();
+
+                    if !waker.as_ref().map_or(false, |w| w.will_wake(cx.waker())) {
+                        *waker = Some(cx.waker().clone());
+                    }
+
+                    sys::sceKernelCpuResumeIntr(flags);
+                }
+
+                return Poll::Pending;
+            }
+
+            // Alarms can fire a little early. Arm a new one for the rest.
+            _ => self.cancel(),
+        }
+
+        let alarm = Box::new(Alarm {
+            fired: Cell::new(false),
+            waker: UnsafeCell::new(Some(cx.waker().clone())),
+        });
+
+        let remaining = time::as_micros_u32(self.deadline - now);
+        let common = &*alarm as *const Alarm as *mut c_void;
+
+        match unsafe { sys::sceKernelSetAlarm(remaining, on_alarm, common) }.into_result() {
+            Ok(id) => self.alarm = Some((id, alarm)),
+
+            // Fall back to polling the clock.
+            Err(_) => cx.waker().wake_by_ref(),
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
@@ -0,0 +1,134 @@
+use crate::sys::{self, Interrupt};
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::future::Future;
+use core::pin::Pin;
+use core::ptr;
+use core::task::{Context, Poll, Waker};
+
+/// The vblank sub-interrupt slot used to wake waiting tasks.
+pub const VBLANK_SUB_INTERRUPT: i32 = 15;
+
+/// Tasks waiting for the next vblank, keyed by an id unique to each future.
+struct Waiters {
+    installed: bool,
+    next_id: usize,
+    wakers: Vec<(usize, Waker)>,
+}
+
+struct VblankWaiters(UnsafeCell<Waiters>);
+
+// Only accessed with interrupts suspended, or from the vblank handler.
+unsafe impl Sync for VblankWaiters {}
+
+static WAITERS: VblankWaiters = VblankWaiters(UnsafeCell::new(Waiters {
+    installed: false,
+    next_id: 0,
+    wakers: Vec::new(),
+}));
+
+unsafe extern "C" fn on_vblank(_sub: i32, _arg: *mut c_void) -> i32 {
+    // Wakers are removed by their futures, never here, so nothing is freed
+    // in interrupt context.
+    for (_, waker) in &(*WAITERS.0.get()).wakers {
+        waker.wake_by_ref();
+    }
+
+    0
+}
+
+fn with_waiters<R>(f: impl FnOnce(&mut Waiters) -> R) -> R {
+    unsafe {
+        let flags = sys::sceKernelCpuSuspendIntr();
+        let result = f(&mut *WAITERS.0.get());
+        sys::sceKernelCpuResumeIntr(flags);
+
+        result
+    }
+}
+
+/// Wait for the start of the next vertical blank, like
+/// `sceDisplayWaitVblankStart` but without blocking the thread.
+///
+/// The first call installs a vblank sub-interrupt handler in slot
+/// `VBLANK_SUB_INTERRUPT`, which stays installed.
+pub fn vblank() -> WaitVblank {
+    WaitVblank { vcount: None, id: None }
+}
+
+/// The future returned by `vblank`.
+#[must_use = "futures do nothing unless polled"]
+pub struct WaitVblank {
+    vcount: Option<u32>,
+    id: Option<usize>,
+}
+
+impl WaitVblank {
+    fn unregister(&mut self) {
+        if let Some(id) = self.id.take() {
+            with_waiters(|waiters| waiters.wakers.retain(|(other, _)| *other != id));
+        }
+    }
+}
+
+impl Future for WaitVblank {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // The vblank count increments as each vblank starts.
+        let vcount = unsafe { sys::sceDisplayGetVcount() };
+
+        match self.vcount {
+            None => self.vcount = Some(vcount),
+            Some(start) if start != vcount => {
+                self.unregister();
+                return Poll::Ready(());
+            }
+            Some(_) => (),
+        }
+
+        let id = self.id;
+
+        self.id = Some(with_waiters(|waiters| {
+            if !waiters.installed {
+                unsafe {
+                    let int = Interrupt::Vblank as i32;
+                    let handler = on_vblank as *mut c_void;
+                    sys::sceKernelRegisterSubIntrHandler(int, VBLANK_SUB_INTERRUPT, handler, ptr::null_mut());
+                    sys::sceKernelEnableSubIntr(int, VBLANK_SUB_INTERRUPT);
+                }
+
+                waiters.installed = true;
+            }
+
+            let existing = id.and_then(|id| waiters.wakers.iter_mut().find(|(other, _)| *other == id));
+
+            match existing {
+                Some((id, waker)) => {
+                    if !waker.will_wake(cx.waker()) {
+                        *waker = cx.waker().clone();
+                    }
+
+                    *id
+                }
+
+                None => {
+                    let id = waiters.next_id;
+                    waiters.next_id = waiters.next_id.wrapping_add(1);
+                    waiters.wakers.push((id, cx.waker().clone()));
+
+                    id
+                }
+            }
+        }));
+
+        Poll::Pending
+    }
+}
+
+impl Drop for WaitVblank {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
@@ -0,0 +1,368 @@
+//! Asynchronous file I/O over `sceIo*Async`.
+//!
+//! Requests run on the I/O manager's own thread, so the calling thread can
+//! keep rendering while level data streams in from UMD or memory stick.
+
+use super::{File, OpenOptions};
+use crate::cache::DmaBuffer;
+use crate::error::SceReturn;
+use crate::io::{self, Seek, SeekFrom};
+use crate::sys::{self, SceUid};
+use crate::{Error, SceResult};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+/// A buffer which can be handed to the I/O manager.
+///
+/// # Safety
+///
+/// The pointer must stay valid, and must not change, when the buffer is moved.
+/// This holds for heap buffers, but not for arrays stored inline.
+pub unsafe trait IoBuf: Unpin {
+    fn io_ptr(&self) -> *const u8;
+    fn io_len(&self) -> usize;
+}
+
+/// A buffer which the I/O manager can write into.
+///
+/// # Safety
+///
+/// See `IoBuf`.
+pub unsafe trait IoBufMut: IoBuf {
+    fn io_mut_ptr(&mut self) -> *mut u8;
+}
+
+unsafe impl IoBuf for Vec<u8> {
+    fn io_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn io_len(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBufMut for Vec<u8> {
+    fn io_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+}
+
+unsafe impl IoBuf for Box<[u8]> {
+    fn io_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn io_len(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBufMut for Box<[u8]> {
+    fn io_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+}
+
+unsafe impl IoBuf for DmaBuffer<u8> {
+    fn io_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn io_len(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBufMut for DmaBuffer<u8> {
+    fn io_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+}
+
+unsafe impl IoBuf for &'static [u8] {
+    fn io_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn io_len(&self) -> usize {
+        self.len()
+    }
+}
+
+/// How a pending request finds out it has completed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Completion {
+    /// Register an `sceIoSetAsyncCallback` callback which wakes the task.
+    ///
+    /// Callbacks only run while the thread which started the first request
+    /// sleeps in a `*CB` wait, such as `sceKernelWaitEventFlagCB`, which is
+    /// what `psp::executor` does when idle.
+    Callback,
+    /// Wake the task again immediately, so the executor keeps polling until
+    /// the request completes. This works with any executor, at the cost of
+    /// busy-waiting when there is nothing else to do.
+    Poll,
+}
+
+/// State shared with the completion callback. Boxed so its address is stable.
+struct Waiter {
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+unsafe extern "C" fn on_complete(_arg1: i32, _arg2: i32, arg: *mut c_void) -> i32 {
+    let waiter = &*(arg as *const Waiter);
+
+    if let Some(waker) = (*waiter.waker.get()).take() {
+        waker.wake();
+    }
+
+    // Keep the callback registered.
+    0
+}
+
+/// A file whose reads and writes return futures.
+///
+/// Only one request can be in flight per file, which the borrow checker
+/// enforces: each future holds a mutable borrow of the file until it
+/// completes or is dropped.
+pub struct AsyncFile {
+    file: File,
+    completion: Completion,
+    waiter: Box<Waiter>,
+    callback: Option<SceUid>,
+}
+
+impl AsyncFile {
+    /// Open a file for asynchronous reading.
+    pub fn open(path: &str) -> io::Result<AsyncFile> {
+        File::open(path).map(AsyncFile::from)
+    }
+
+    /// Open a file for asynchronous I/O with the given options.
+    pub fn with_options(path: &str, options: &OpenOptions) -> io::Result<AsyncFile> {
+        options.open(path).map(AsyncFile::from)
+    }
+
+    /// Choose how completion is detected. Defaults to `Completion::Callback`.
+    pub fn set_completion(&mut self, completion: Completion) {
+        self.completion = completion;
+    }
+
+    /// Change the priority of the I/O manager thread servicing this file.
+    pub fn set_priority(&mut self, priority: i32) -> SceResult<()> {
+        unsafe { sys::sceIoChangeAsyncPriority(self.file.as_raw_fd(), priority) }
+            .into_result()
+            .map(drop)
+    }
+
+    /// The size of the file.
+    pub fn len(&mut self) -> io::Result<u64> {
+        self.file.len()
+    }
+
+    /// Go back to blocking I/O.
+    pub fn into_file(mut self) -> File {
+        self.unregister();
+
+        // `AsyncFile` has a destructor, so the file can't be moved out.
+        unsafe {
+            let file = core::ptr::read(&self.file);
+            let waiter = core::ptr::read(&self.waiter);
+            core::mem::forget(self);
+            drop(waiter);
+            file
+        }
+    }
+
+    /// Read into `buf`, starting at `offset`. The buffer is handed back once
+    /// the read completes, along with the number of bytes read.
+    pub fn read_at<B: IoBufMut>(&mut self, buf: B, offset: u64) -> AsyncIo<'_, B> {
+        AsyncIo::new(self, buf, offset, |fd, buf| unsafe {
+            sys::sceIoReadAsync(fd, buf.io_mut_ptr() as *mut c_void, buf.io_len() as u32)
+        })
+    }
+
+    /// Write `buf`, starting at `offset`. The buffer is handed back once the
+    /// write completes, along with the number of bytes written.
+    pub fn write_at<B: IoBuf>(&mut self, buf: B, offset: u64) -> AsyncIo<'_, B> {
+        AsyncIo::new(self, buf, offset, |fd, buf| unsafe {
+            sys::sceIoWriteAsync(fd, buf.io_ptr() as *const c_void, buf.io_len() as u32)
+        })
+    }
+
+    fn register(&mut self) -> SceResult<()> {
+        if self.callback.is_some() || self.completion != Completion::Callback {
+            return Ok(());
+        }
+
+        let arg = &*self.waiter as *const Waiter as *mut c_void;
+
+        unsafe {
+            let cbid = sys::sceKernelCreateCallback(&b"async_io\0"[0], on_complete, arg)
+                .into_result()?;
+
+            if let Err(e) = sys::sceIoSetAsyncCallback(self.file.as_raw_fd(), cbid, arg).into_result() {
+                sys::sceKernelDeleteCallback(cbid);
+                return Err(e);
+            }
+
+            self.callback = Some(cbid);
+        }
+
+        Ok(())
+    }
+
+    fn unregister(&mut self) {
+        if let Some(cbid) = self.callback.take() {
+            unsafe {
+                sys::sceKernelDeleteCallback(cbid);
+            }
+        }
+    }
+}
+
+impl From<File> for AsyncFile {
+    fn from(file: File) -> Self {
+        Self {
+            file,
+            completion: Completion::Callback,
+            waiter: Box::new(Waiter {
+                waker: UnsafeCell::new(None),
+            }),
+            callback: None,
+        }
+    }
+}
+
+impl Drop for AsyncFile {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum State {
+    Idle,
+    Pending,
+    Done,
+}
+
+/// A pending asynchronous read or write.
+///
+/// Dropping this before it completes cancels the request with `sceIoCancel`.
+/// If the request can no longer be cancelled, dropping blocks until it
+/// finishes, so the buffer is never freed while the I/O manager uses it.
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncIo<'a, B> {
+    file: &'a mut AsyncFile,
+    buf: Option<B>,
+    offset: u64,
+    start: fn(SceUid, &mut B) -> i32,
+    state: State,
+}
+
+impl<'a, B: IoBuf> AsyncIo<'a, B> {
+    fn new(file: &'a mut AsyncFile, buf: B, offset: u64, start: fn(SceUid, &mut B) -> i32) -> Self {
+        Self {
+            file,
+            buf: Some(buf),
+            offset,
+            start,
+            state: State::Idle,
+        }
+    }
+
+    fn start(&mut self) -> io::Result<()> {
+        self.file.register()?;
+        self.file.file.seek(SeekFrom::Start(self.offset))?;
+
+        let fd = self.file.file.as_raw_fd();
+        let buf = self.buf.as_mut().unwrap();
+        (self.start)(fd, buf).into_result()?;
+
+        Ok(())
+    }
+
+    fn finish(&mut self, result: io::Result<usize>) -> Poll<(io::Result<usize>, B)> {
+        self.state = State::Done;
+        Poll::Ready((result, self.buf.take().unwrap()))
+    }
+}
+
+impl<B: IoBuf> Future for AsyncIo<'_, B> {
+    type Output = (io::Result<usize>, B);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        match this.state {
+            State::Idle => {
+                if let Err(e) = this.start() {
+                    return this.finish(Err(e));
+                }
+
+                this.state = State::Pending;
+            }
+            State::Pending => (),
+            State::Done => panic!("`AsyncIo` polled after completion"),
+        }
+
+        // Store the waker before checking, in case the callback runs between
+        // the check and returning.
+        unsafe {
+            *this.file.waiter.waker.get() = Some(cx.waker().clone());
+        }
+
+        let mut result: i64 = 0;
+        let fd = this.file.file.as_raw_fd();
+
+        match unsafe { sys::sceIoPollAsync(fd, &mut result) } {
+            0 => {
+                unsafe {
+                    *this.file.waiter.waker.get() = None;
+                }
+
+                if result < 0 {
+                    this.finish(Err(Error::from_raw(result as i32).into()))
+                } else {
+                    this.finish(Ok(result as usize))
+                }
+            }
+
+            1 => {
+                if this.file.completion == Completion::Poll {
+                    cx.waker().wake_by_ref();
+                }
+
+                Poll::Pending
+            }
+
+            e => this.finish(Err(Error::from_raw(e).into())),
+        }
+    }
+}
+
+impl<B> Drop for AsyncIo<'_, B> {
+    fn drop(&mut self) {
+        if self.state != State::Pending {
+            return;
+        }
+
+        let fd = self.file.file.as_raw_fd();
+
+        unsafe {
+            *self.file.waiter.waker.get() = None;
+
+            if sys::sceIoCancel(fd) < 0 {
+                let mut result = 0;
+                sys::sceIoWaitAsync(fd, &mut result);
+            }
+        }
+    }
+}
@@ -0,0 +1,507 @@
+//! Filesystem access over `sceIo*`.
+//!
+//! Paths include the device, e.g. `ms0:/PSP/SAVEDATA/save.bin`,
+//! `host0:/log.txt` or `disc0:/PSP_GAME/USRDIR/level1.dat`.
+
+use crate::error::SceReturn;
+use crate::io::{self, Read, Seek, SeekFrom, Write};
+use crate::sys::{self, IoOpenFlags, IoPermissions, IoStatAttr, IoStatMode, IoWhence};
+use crate::sys::{SceIoDirent, SceIoStat, ScePspDateTime, SceUid};
+use crate::time::{DateTime, SystemTime};
+use crate::Error;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::{ffi::c_void, mem};
+
+mod async_file;
+pub use async_file::*;
+
+/// Run `f` with a NUL-terminated copy of `path`.
+pub(crate) fn with_c_path<R>(path: &str, f: impl FnOnce(*const u8) -> R) -> io::Result<R> {
+    if path.as_bytes().contains(&0) {
+        return Err(io::Error::InvalidInput);
+    }
+
+    let mut c_path = Vec::with_capacity(path.len() + 1);
+    c_path.extend_from_slice(path.as_bytes());
+    c_path.push(0);
+
+    Ok(f(c_path.as_ptr()))
+}
+
+/// Options for opening a file, mirroring `IoOpenFlags`.
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+    mode: IoPermissions,
+}
+
+impl OpenOptions {
+    /// Options with every flag unset, and permissions of `0o777`.
+    pub fn new() -> Self {
+        Self {
+            read: false,
+            write: false,
+            append: false,
+            truncate: false,
+            create: false,
+            create_new: false,
+            mode: 0o777,
+        }
+    }
+
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    /// Write to the end of the file. Implies `write`.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Create the file if it does not exist.
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    /// Create the file, failing if it already exists.
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+
+    /// Permissions for newly created files, in octal unix form.
+    pub fn mode(&mut self, mode: IoPermissions) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The `sceIoOpen` flags these options correspond to.
+    pub fn flags(&self) -> IoOpenFlags {
+        let mut flags = IoOpenFlags::empty();
+
+        if self.read {
+            flags |= IoOpenFlags::RD_ONLY;
+        }
+
+        if self.write || self.append {
+            flags |= IoOpenFlags::WR_ONLY;
+        }
+
+        if self.append {
+            flags |= IoOpenFlags::APPEND;
+        }
+
+        if self.truncate {
+            flags |= IoOpenFlags::TRUNC;
+        }
+
+        if self.create || self.create_new {
+            flags |= IoOpenFlags::CREAT;
+        }
+
+        if self.create_new {
+            flags |= IoOpenFlags::EXCL;
+        }
+
+        flags
+    }
+
+    pub fn open(&self, path: &str) -> io::Result<File> {
+        let fd = with_c_path(path, |path| unsafe {
+            sys::sceIoOpen(path, self.flags(), self.mode)
+        })?;
+
+        Ok(File { fd: fd.into_result()? })
+    }
+}
+
+/// An open file. It is closed when dropped.
+#[derive(Debug)]
+pub struct File {
+    fd: SceUid,
+}
+
+impl File {
+    /// Open a file for reading.
+    pub fn open(path: &str) -> io::Result<File> {
+        OpenOptions::new().read(true).open(path)
+    }
+
+    /// Open a file for writing, creating it or truncating it.
+    pub fn create(path: &str) -> io::Result<File> {
+        OpenOptions::new().write(true).create(true).truncate(true).open(path)
+    }
+
+    /// Take ownership of an open file descriptor.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be open, and not closed by anything else.
+    pub unsafe fn from_raw_fd(fd: SceUid) -> File {
+        File { fd }
+    }
+
+    /// The underlying file descriptor.
+    pub fn as_raw_fd(&self) -> SceUid {
+        self.fd
+    }
+
+    /// Give up ownership of the file descriptor without closing it.
+    pub fn into_raw_fd(self) -> SceUid {
+        let fd = self.fd;
+        mem::forget(self);
+        fd
+    }
+
+    /// The size of the file, found by seeking to the end and back.
+    pub fn len(&mut self) -> io::Result<u64> {
+        let pos = self.stream_position()?;
+        let len = self.seek(SeekFrom::End(0))?;
+        self.seek(SeekFrom::Start(pos))?;
+
+        Ok(len)
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        (&*self).seek(pos)
+    }
+}
+
+// Like `std`, a shared reference to a file can also be read from and written
+// to. The file position is shared.
+impl Read for &File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(i32::MAX as usize) as u32;
+        let ret = unsafe { sys::sceIoRead(self.fd, buf.as_mut_ptr() as *mut c_void, len) };
+
+        Ok(ret.into_result()? as usize)
+    }
+}
+
+impl Write for &File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(i32::MAX as usize);
+        let ret = unsafe { sys::sceIoWrite(self.fd, buf.as_ptr() as *const c_void, len) };
+
+        Ok(ret.into_result()? as usize)
+    }
+}
+
+impl Seek for &File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (offset, whence) = match pos {
+            SeekFrom::Start(n) => (n as i64, IoWhence::Set),
+            SeekFrom::End(n) => (n, IoWhence::End),
+            SeekFrom::Current(n) => (n, IoWhence::Cur),
+        };
+
+        let ret = unsafe { sys::sceIoLseek(self.fd, offset, whence) };
+
+        if ret < 0 {
+            Err(Error::from_raw(ret as i32).into())
+        } else {
+            Ok(ret as u64)
+        }
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceIoClose(self.fd);
+        }
+    }
+}
+
+/// The type of a filesystem entry.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+/// Information about a file or directory.
+#[derive(Debug, Copy, Clone)]
+pub struct Metadata {
+    stat: SceIoStat,
+}
+
+impl Metadata {
+    pub fn file_type(&self) -> FileType {
+        let mode = self.stat.st_mode;
+
+        if mode.contains(IoStatMode::IFLNK) {
+            FileType::Symlink
+        } else if mode.contains(IoStatMode::IFDIR) {
+            FileType::Dir
+        } else if mode.contains(IoStatMode::IFREG) {
+            FileType::File
+        } else {
+            FileType::Other
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type() == FileType::Dir
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type() == FileType::File
+    }
+
+    /// The size in bytes.
+    pub fn len(&self) -> u64 {
+        self.stat.st_size as u64
+    }
+
+    /// Unix permission bits, e.g. `0o644`.
+    pub fn permissions(&self) -> u32 {
+        (self.stat.st_mode.bits() & 0o7777) as u32
+    }
+
+    /// Whether the file is read-only, according to the FAT attributes.
+    pub fn is_readonly(&self) -> bool {
+        !self.stat.st_attr.contains(IoStatAttr::IWOTH)
+    }
+
+    /// When the file was created.
+    ///
+    /// Returns `io::Error::InvalidData` if the filesystem doesn't record a
+    /// valid time, as with some memory sticks and `host0:`.
+    pub fn created(&self) -> io::Result<SystemTime> {
+        stat_time(self.stat.st_ctime)
+    }
+
+    /// When the file was last accessed, or `io::Error::InvalidData` as for
+    /// `created`.
+    pub fn accessed(&self) -> io::Result<SystemTime> {
+        stat_time(self.stat.st_atime)
+    }
+
+    /// When the file was last modified, or `io::Error::InvalidData` as for
+    /// `created`.
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        stat_time(self.stat.st_mtime)
+    }
+
+    /// The raw `sceIoGetstat` result.
+    pub fn as_raw(&self) -> &SceIoStat {
+        &self.stat
+    }
+}
+
+/// Convert a time from `sceIoGetstat`, which is in local time.
+fn stat_time(time: ScePspDateTime) -> io::Result<SystemTime> {
+    DateTime::try_from(time)
+        .map(|local| SystemTime::from(local.to_utc()))
+        .map_err(|_| io::Error::InvalidData)
+}
+
+/// Get information about a file or directory.
+pub fn metadata(path: &str) -> io::Result<Metadata> {
+    let mut stat = mem::MaybeUninit::<SceIoStat>::zeroed();
+    let ret = with_c_path(path, |path| unsafe { sys::sceIoGetstat(path, stat.as_mut_ptr()) })?;
+    ret.into_result()?;
+
+    Ok(Metadata { stat: unsafe { stat.assume_init() } })
+}
+
+/// Whether a file or directory exists at `path`.
+pub fn exists(path: &str) -> bool {
+    metadata(path).is_ok()
+}
+
+/// An entry returned by `read_dir`.
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    name: String,
+    path: String,
+    metadata: Metadata,
+}
+
+impl DirEntry {
+    /// The name of the entry, without the directory.
+    pub fn file_name(&self) -> &str {
+        &self.name
+    }
+
+    /// The full path of the entry.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.metadata.file_type()
+    }
+}
+
+/// An iterator over the entries of a directory, skipping `.` and `..`.
+#[derive(Debug)]
+pub struct ReadDir {
+    fd: SceUid,
+    dir: String,
+}
+
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // This also makes `d_private` null, as otherwise the driver writes
+            // through it.
+            let mut dirent: SceIoDirent = unsafe { mem::zeroed() };
+
+            let ret = unsafe { sys::sceIoDread(self.fd, &mut dirent) };
+
+            match ret.into_result() {
+                Ok(0) => return None,
+                Ok(_) => (),
+                Err(e) => return Some(Err(e.into())),
+            }
+
+            let len = dirent.d_name.iter().position(|&c| c == 0).unwrap_or(dirent.d_name.len());
+            let name = String::from_utf8_lossy(&dirent.d_name[..len]).into_owned();
+
+            if name == "." || name == ".." {
+                continue;
+            }
+
+            let mut path = self.dir.clone();
+            if !path.ends_with('/') {
+                path.push('/');
+            }
+            path.push_str(&name);
+
+            return Some(Ok(DirEntry {
+                name,
+                path,
+                metadata: Metadata { stat: dirent.d_stat },
+            }));
+        }
+    }
+}
+
+impl Drop for ReadDir {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceIoDclose(self.fd);
+        }
+    }
+}
+
+/// Iterate over the entries of a directory.
+pub fn read_dir(path: &str) -> io::Result<ReadDir> {
+    let fd = with_c_path(path, |path| unsafe { sys::sceIoDopen(path) })?;
+
+    Ok(ReadDir {
+        fd: fd.into_result()?,
+        dir: String::from(path),
+    })
+}
+
+/// Create a directory.
+pub fn create_dir(path: &str) -> io::Result<()> {
+    let ret = with_c_path(path, |path| unsafe { sys::sceIoMkdir(path, 0o777) })?;
+    ret.into_result()?;
+
+    Ok(())
+}
+
+/// Create a directory and any missing parents.
+pub fn create_dir_all(path: &str) -> io::Result<()> {
+    // Skip over the device, e.g. `ms0:/`.
+    let start = path.find(":/").map(|i| i + 2).unwrap_or(0);
+
+    let mut end = start;
+    while end < path.len() {
+        end = path[end..].find('/').map(|i| end + i).unwrap_or(path.len());
+
+        if end > start && !path[..end].ends_with('/') {
+            match create_dir(&path[..end]) {
+                Ok(()) | Err(io::Error::Sce(Error::ERRNO_FILE_ALREADY_EXISTS)) => (),
+                Err(e) => return Err(e),
+            }
+        }
+
+        end += 1;
+    }
+
+    Ok(())
+}
+
+/// Remove a file.
+pub fn remove_file(path: &str) -> io::Result<()> {
+    let ret = with_c_path(path, |path| unsafe { sys::sceIoRemove(path) })?;
+    ret.into_result()?;
+
+    Ok(())
+}
+
+/// Remove an empty directory.
+pub fn remove_dir(path: &str) -> io::Result<()> {
+    let ret = with_c_path(path, |path| unsafe { sys::sceIoRmdir(path) })?;
+    ret.into_result()?;
+
+    Ok(())
+}
+
+/// Rename or move a file or directory.
+pub fn rename(from: &str, to: &str) -> io::Result<()> {
+    let ret = with_c_path(from, |from| with_c_path(to, |to| unsafe { sys::sceIoRename(from, to) }))??;
+    ret.into_result()?;
+
+    Ok(())
+}
+
+/// Read an entire file.
+pub fn read(path: &str) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+
+    Ok(data)
+}
+
+/// Write an entire file, replacing it if it exists.
+pub fn write(path: &str, data: &[u8]) -> io::Result<()> {
+    File::create(path)?.write_all(data)
+}
@@ -0,0 +1,168 @@
+use crate::cache::{self, DmaBuffer, CACHE_LINE_SIZE};
+use crate::vram_alloc::{get_vram_allocator, VramAllocError, VramMemChunk};
+use core::ffi::c_void;
+use core::{ptr, slice};
+
+/// The largest width or height the decoders accept. A 1024x1024 image takes
+/// 4 MiB, a good part of the PSP's memory.
+pub const MAX_DIMENSION: u32 = 1024;
+
+/// Where the pixels of an `Image` are kept.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Storage {
+    /// Main memory.
+    Ram,
+    /// VRAM, from the VRAM allocator. The GE reads textures in VRAM faster
+    /// than textures in main memory, but there is only 2 MiB of it.
+    Vram,
+}
+
+enum Pixels {
+    Ram(DmaBuffer<u32>),
+    Vram(VramMemChunk),
+}
+
+/// A 32-bit image, laid out like a `Psm8888` texture: each pixel is
+/// `0xAABBGGRR`, and rows are `stride` pixels apart.
+///
+/// The buffer is ready for hardware: pass `as_device_ptr` to `sceGuTexImage`
+/// with `stride` as the buffer width, or to a decoder writing into it.
+pub struct Image {
+    width: u32,
+    height: u32,
+    stride: u32,
+    pixels: Pixels,
+}
+
+impl Image {
+    /// Create an image of transparent black pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is less than `width`.
+    pub fn new(
+        width: u32,
+        height: u32,
+        stride: u32,
+        storage: Storage,
+    ) -> Result<Self, VramAllocError> {
+        assert!(stride >= width, "image stride is less than its width");
+
+        let len = stride as usize * height as usize;
+
+        let pixels = match storage {
+            Storage::Ram => Pixels::Ram(DmaBuffer::from_elem(0, len)),
+            Storage::Vram => {
+                // Whole cache lines, so the CPU's copy can be invalidated once
+                // hardware has written to it.
+                let size = (len * 4 + CACHE_LINE_SIZE - 1) & !(CACHE_LINE_SIZE - 1);
+                let chunk =
+                    get_vram_allocator().alloc_aligned(size as u32, CACHE_LINE_SIZE as u32)?;
+
+                unsafe { ptr::write_bytes(chunk.as_mut_ptr_direct_to_vram(), 0, size) };
+                Pixels::Vram(chunk)
+            }
+        };
+
+        Ok(Self {
+            width,
+            height,
+            stride,
+            pixels,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The number of pixels from the start of one row to the next.
+    pub fn stride(&self) -> u32 {
+        self.stride
+    }
+
+    pub fn storage(&self) -> Storage {
+        match self.pixels {
+            Pixels::Ram(_) => Storage::Ram,
+            Pixels::Vram(_) => Storage::Vram,
+        }
+    }
+
+    /// All rows of pixels, including the padding at the end of each row.
+    pub fn pixels(&self) -> &[u32] {
+        match &self.pixels {
+            Pixels::Ram(buffer) => buffer,
+            Pixels::Vram(chunk) => unsafe {
+                slice::from_raw_parts(chunk.as_mut_ptr_direct_to_vram() as *const u32, self.len())
+            },
+        }
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u32] {
+        let len = self.len();
+
+        match &mut self.pixels {
+            Pixels::Ram(buffer) => buffer,
+            Pixels::Vram(chunk) => unsafe {
+                slice::from_raw_parts_mut(chunk.as_mut_ptr_direct_to_vram() as *mut u32, len)
+            },
+        }
+    }
+
+    /// One row of pixels, without its padding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is not less than the height.
+    pub fn row(&self, y: u32) -> &[u32] {
+        assert!(y < self.height, "image row out of bounds");
+
+        let start = (y * self.stride) as usize;
+        &self.pixels()[start..start + self.width as usize]
+    }
+
+    /// Write back the CPU's changes, and return the pointer to pass to
+    /// hardware.
+    pub fn as_device_ptr(&mut self) -> *mut c_void {
+        match &mut self.pixels {
+            Pixels::Ram(buffer) => buffer.as_device_ptr(),
+            Pixels::Vram(chunk) => {
+                let bytes = unsafe {
+                    slice::from_raw_parts(chunk.as_mut_ptr_direct_to_vram(), chunk.len() as usize)
+                };
+
+                cache::writeback_invalidate(bytes);
+                chunk.as_mut_ptr_direct_to_vram() as *mut c_void
+            }
+        }
+    }
+
+    /// Discard cached pixels, so the CPU sees what hardware has written since
+    /// `as_device_ptr`.
+    pub fn sync_for_cpu(&mut self) {
+        match &mut self.pixels {
+            Pixels::Ram(buffer) => buffer.sync_for_cpu(),
+            // Allocated in whole cache lines, by `new`.
+            Pixels::Vram(chunk) => unsafe {
+                cache::invalidate(slice::from_raw_parts_mut(
+                    chunk.as_mut_ptr_direct_to_vram(),
+                    chunk.len() as usize,
+                ));
+            },
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.stride as usize * self.height as usize
+    }
+}
+
+/// The stride of a decoded image `width` pixels wide: a multiple of 16
+/// pixels, which keeps each row 64-byte aligned.
+pub(crate) fn stride(width: u32) -> u32 {
+    (width + 15) & !15
+}
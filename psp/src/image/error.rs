@@ -0,0 +1,30 @@
+use crate::vram_alloc::VramAllocError;
+use core::fmt;
+
+/// An error from decoding an image, or converting it to a texture.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageError {
+    /// The data is not an image of the expected kind, or is corrupt.
+    InvalidData,
+    /// The image uses a feature the decoder doesn't handle, is larger than
+    /// `MAX_DIMENSION`, or doesn't fit the requested texture.
+    Unsupported,
+    /// There is not enough VRAM for the image.
+    Vram(VramAllocError),
+}
+
+impl From<VramAllocError> for ImageError {
+    fn from(e: VramAllocError) -> Self {
+        ImageError::Vram(e)
+    }
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::InvalidData => write!(f, "invalid image data"),
+            ImageError::Unsupported => write!(f, "unsupported image"),
+            ImageError::Vram(e) => write!(f, "{}", e),
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use crate::io::{self, Error, Read, Seek, SeekFrom};
+use alloc::{vec, vec::Vec};
+use core::time::Duration;
+
+/// The largest `hdrl` list read, in bytes.
+const MAX_HEADER_SIZE: u32 = 1 << 20;
+
+/// The largest frame read, in bytes.
+const MAX_FRAME_SIZE: u32 = 4 << 20;
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// The chunks in the body of a RIFF list, as `(id, data)`. A truncated chunk
+/// ends the list.
+fn chunks(mut bytes: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> + '_ {
+    core::iter::from_fn(move || {
+        if bytes.len() < 8 {
+            return None;
+        }
+
+        let size = u32_at(bytes, 4) as usize;
+        let id = &bytes[..4];
+        let data = bytes[8..].get(..size)?;
+
+        // Chunks are padded to an even size.
+        bytes = &bytes[core::cmp::min(8 + size + (size & 1), bytes.len())..];
+        Some((id, data))
+    })
+}
+
+fn is_mjpeg(fourcc: &[u8]) -> bool {
+    fourcc.eq_ignore_ascii_case(b"MJPG")
+}
+
+fn skip<R: Seek>(reader: &mut R, len: u64) -> io::Result<()> {
+    reader.seek(SeekFrom::Current(len as i64))?;
+    Ok(())
+}
+
+/// What the headers say about the video stream.
+struct Video {
+    width: u32,
+    height: u32,
+    frame_duration: Duration,
+    frame_count: u32,
+    stream: u32,
+}
+
+/// Find the first Motion-JPEG stream in an `hdrl` list.
+fn find_video(list: &[u8]) -> Option<Video> {
+    let mut main = None;
+    let mut stream = 0;
+
+    for (id, data) in chunks(list) {
+        if id == b"avih" && data.len() >= 40 {
+            main = Some(data);
+        }
+
+        if id != b"LIST" || data.len() < 4 || &data[..4] != b"strl" {
+            continue;
+        }
+
+        let mut header = None;
+        let mut format = None;
+
+        for (id, data) in chunks(&data[4..]) {
+            match id {
+                b"strh" if data.len() >= 36 => header = Some(data),
+                b"strf" if data.len() >= 20 => format = Some(data),
+                _ => {}
+            }
+        }
+
+        if let (Some(header), Some(format), Some(main)) = (header, format, main) {
+            if &header[..4] == b"vids" && (is_mjpeg(&header[4..8]) || is_mjpeg(&format[16..20])) {
+                let scale = u32_at(header, 20) as u64;
+                let rate = u32_at(header, 24) as u64;
+
+                let frame_duration = match (scale * 1_000_000_000).checked_div(rate) {
+                    Some(nanos) => Duration::from_nanos(nanos),
+                    None => Duration::from_micros(u32_at(main, 0) as u64),
+                };
+
+                return Some(Video {
+                    width: u32_at(main, 32),
+                    height: u32_at(main, 36),
+                    frame_duration,
+                    frame_count: u32_at(header, 32),
+                    stream,
+                });
+            }
+        }
+
+        stream += 1;
+    }
+
+    None
+}
+
+/// Reads the frames of a Motion-JPEG AVI file, each a JPEG for a `Decoder`.
+///
+/// Motion-JPEG takes more space than the AVC video of a PMF file, but is
+/// simple to play: any frame can be decoded on its own, and `sceJpeg` decodes
+/// a 480x272 frame in a few milliseconds. `ffmpeg` makes such files with
+/// `-c:v mjpeg -q:v 5 -an`. Other streams, such as audio, are skipped.
+///
+/// ```no_run
+/// use psp::fs::File;
+/// use psp::image::jpeg::{AviReader, Decoder};
+/// use psp::image::{Image, Storage};
+///
+/// let file = File::open("umd0:/PSP_GAME/USRDIR/intro.avi").unwrap();
+/// let mut avi = AviReader::new(file).unwrap();
+/// let mut decoder = Decoder::new(avi.width(), avi.height()).unwrap();
+///
+/// let (width, height, stride) = (avi.width(), avi.height(), decoder.stride());
+/// let mut image = Image::new(width, height, stride, Storage::Vram).unwrap();
+/// let mut frame = Vec::new();
+///
+/// while avi.read_frame(&mut frame).unwrap() {
+///     if !frame.is_empty() {
+///         decoder.decode_into(&frame, &mut image).unwrap();
+///     }
+///
+///     // Draw `image`, then wait until `avi.frame_duration()` has passed.
+/// }
+/// ```
+pub struct AviReader<R> {
+    reader: R,
+    width: u32,
+    height: u32,
+    frame_duration: Duration,
+    frame_count: u32,
+    /// The first two characters of the video stream's chunk IDs, e.g. `00`
+    /// for `00dc`.
+    stream_id: [u8; 2],
+    /// Where the `movi` list's frames start in the file.
+    movi_start: u64,
+    /// The bytes of the `movi` list read so far, and in all.
+    position: u64,
+    movi_size: u64,
+}
+
+impl<R: Read + Seek> AviReader<R> {
+    /// Read the headers from `reader`, leaving it at the first frame.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut riff = [0; 12];
+        reader.read_exact(&mut riff)?;
+
+        if &riff[0..4] != b"RIFF" || &riff[8..12] != b"AVI " {
+            return Err(Error::InvalidData);
+        }
+
+        let mut video = None;
+
+        let movi_size = loop {
+            let mut chunk = [0; 12];
+            reader.read_exact(&mut chunk[..8])?;
+            let size = u32_at(&chunk, 4);
+            let padded = size as u64 + (size & 1) as u64;
+
+            if &chunk[..4] != b"LIST" {
+                skip(&mut reader, padded)?;
+                continue;
+            }
+
+            if size < 4 {
+                return Err(Error::InvalidData);
+            }
+
+            reader.read_exact(&mut chunk[8..])?;
+
+            match &chunk[8..] {
+                b"movi" => break size - 4,
+                b"hdrl" if size <= MAX_HEADER_SIZE => {
+                    let mut list = vec![0; size as usize - 4];
+                    reader.read_exact(&mut list)?;
+                    skip(&mut reader, padded - size as u64)?;
+
+                    video = find_video(&list);
+                }
+                _ => skip(&mut reader, padded - 4)?,
+            }
+        };
+
+        let video = video.ok_or(Error::InvalidData)?;
+        let movi_start = reader.seek(SeekFrom::Current(0))?;
+
+        if video.stream > 99 || video.width == 0 || video.height == 0 {
+            return Err(Error::InvalidData);
+        }
+
+        Ok(Self {
+            reader,
+            width: video.width,
+            height: video.height,
+            frame_duration: video.frame_duration,
+            frame_count: video.frame_count,
+            stream_id: [
+                b'0' + (video.stream / 10) as u8,
+                b'0' + (video.stream % 10) as u8,
+            ],
+            movi_start,
+            position: 0,
+            movi_size: movi_size as u64,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// How long each frame is shown.
+    pub fn frame_duration(&self) -> Duration {
+        self.frame_duration
+    }
+
+    /// The number of frames, as the headers give it.
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    /// Read the next frame into `frame`, replacing what it held. Returns
+    /// `false` at the end of the video.
+    ///
+    /// A frame may be empty, when it was dropped while recording. The
+    /// previous frame should be shown again.
+    pub fn read_frame(&mut self, frame: &mut Vec<u8>) -> io::Result<bool> {
+        loop {
+            if self.position + 8 > self.movi_size {
+                return Ok(false);
+            }
+
+            let mut chunk = [0; 8];
+            self.reader.read_exact(&mut chunk)?;
+            self.position += 8;
+
+            let size = u32_at(&chunk, 4);
+            let padded = size as u64 + (size & 1) as u64;
+
+            // `rec ` lists group the chunks of each frame. Read the chunks
+            // inside as if the list weren't there.
+            if &chunk[..4] == b"LIST" {
+                skip(&mut self.reader, 4)?;
+                self.position += 4;
+                continue;
+            }
+
+            if chunk[..2] != self.stream_id || &chunk[2..4] != b"dc" {
+                skip(&mut self.reader, padded)?;
+                self.position += padded;
+                continue;
+            }
+
+            if size > MAX_FRAME_SIZE {
+                return Err(Error::InvalidData);
+            }
+
+            frame.clear();
+            frame.resize(size as usize, 0);
+            self.reader.read_exact(frame)?;
+            skip(&mut self.reader, padded - size as u64)?;
+            self.position += padded;
+
+            return Ok(true);
+        }
+    }
+
+    /// Go back to the first frame.
+    pub fn rewind(&mut self) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(self.movi_start))?;
+        self.position = 0;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+//! JPEG decoding.
+//!
+//! `decode` uses the Media Engine's Motion-JPEG decoder, `sceJpeg`, which
+//! handles baseline JPEGs. With the `jpeg` feature, it falls back to a
+//! software decoder when the `avcodec` module can't be loaded, which is much
+//! slower but works anywhere. `Decoder` keeps `sceJpeg` set up between
+//! images, for decoding the frames of an `AviReader` one after another.
+//!
+//! Decoded images are opaque, with rows padded to a multiple of 16 pixels.
+
+mod avi;
+#[cfg(feature = "jpeg")]
+pub mod software;
+
+pub use avi::*;
+
+use super::buffer::stride;
+use super::{Image, Storage, MAX_DIMENSION};
+use crate::cache::DmaBuffer;
+use crate::error::SceReturn;
+use crate::sys::{self, Module};
+use crate::vram_alloc::VramAllocError;
+use core::fmt;
+
+/// An error from decoding a JPEG.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JpegError {
+    /// The data is not a JPEG, or is corrupt.
+    InvalidData,
+    /// The JPEG uses a feature the decoder doesn't handle, such as
+    /// progressive scans or CMYK, or is larger than `MAX_DIMENSION`.
+    Unsupported,
+    /// The image is not the size of the `Image` it is decoded into.
+    SizeMismatch,
+    /// There is not enough VRAM for the image.
+    Vram(VramAllocError),
+    /// Any other error from `sceJpeg`, or from loading its module.
+    Sce(crate::Error),
+}
+
+impl From<VramAllocError> for JpegError {
+    fn from(e: VramAllocError) -> Self {
+        JpegError::Vram(e)
+    }
+}
+
+impl From<crate::Error> for JpegError {
+    fn from(e: crate::Error) -> Self {
+        match e {
+            crate::Error::SCE_JPEG_ERROR_INVALID_DATA | crate::Error::SCE_JPEG_ERROR_NO_SOI => {
+                JpegError::InvalidData
+            }
+            crate::Error::SCE_JPEG_ERROR_INVALID_COLORSPACE
+            | crate::Error::SCE_JPEG_ERROR_INVALID_SIZE => JpegError::Unsupported,
+            e => JpegError::Sce(e),
+        }
+    }
+}
+
+impl fmt::Display for JpegError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JpegError::InvalidData => write!(f, "invalid JPEG data"),
+            JpegError::Unsupported => write!(f, "unsupported JPEG"),
+            JpegError::SizeMismatch => write!(f, "JPEG is not the size of the image"),
+            JpegError::Vram(e) => write!(f, "{}", e),
+            JpegError::Sce(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// The frame header of a JPEG.
+struct Frame {
+    width: u32,
+    height: u32,
+    /// Whether this is a baseline or extended sequential, Huffman coded
+    /// frame of 8-bit samples.
+    sequential: bool,
+}
+
+/// Find the frame header in `data`.
+fn frame(data: &[u8]) -> Result<Frame, JpegError> {
+    if data.len() < 4 || data[..2] != [0xff, 0xd8] {
+        return Err(JpegError::InvalidData);
+    }
+
+    let mut pos = 2;
+
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xff {
+            return Err(JpegError::InvalidData);
+        }
+
+        let marker = data[pos + 1];
+
+        // Fill bytes, and markers without a length.
+        if marker == 0xff {
+            pos += 1;
+            continue;
+        } else if marker == 0x01 || (0xd0..=0xd7).contains(&marker) {
+            pos += 2;
+            continue;
+        }
+
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let segment = data
+            .get(pos + 4..pos + 2 + len)
+            .ok_or(JpegError::InvalidData)?;
+
+        match marker {
+            0xc0..=0xcf if marker != 0xc4 && marker != 0xc8 && marker != 0xcc => {
+                if segment.len() < 6 {
+                    return Err(JpegError::InvalidData);
+                }
+
+                let height = u16::from_be_bytes([segment[1], segment[2]]) as u32;
+                let width = u16::from_be_bytes([segment[3], segment[4]]) as u32;
+
+                if width == 0 || height == 0 {
+                    return Err(JpegError::InvalidData);
+                }
+
+                if width > MAX_DIMENSION || height > MAX_DIMENSION {
+                    return Err(JpegError::Unsupported);
+                }
+
+                return Ok(Frame {
+                    width,
+                    height,
+                    sequential: (marker == 0xc0 || marker == 0xc1) && segment[0] == 8,
+                });
+            }
+            0xd9 | 0xda => break,
+            _ => pos += 2 + len,
+        }
+    }
+
+    Err(JpegError::InvalidData)
+}
+
+/// The width and height of a JPEG, read from its header.
+pub fn dimensions(data: &[u8]) -> Result<(u32, u32), JpegError> {
+    frame(data).map(|frame| (frame.width, frame.height))
+}
+
+/// Decode a JPEG into main memory.
+pub fn decode(data: &[u8]) -> Result<Image, JpegError> {
+    decode_with(data, Storage::Ram)
+}
+
+/// Decode a JPEG into main memory or VRAM.
+pub fn decode_with(data: &[u8], storage: Storage) -> Result<Image, JpegError> {
+    let frame = frame(data)?;
+
+    match Decoder::new(frame.width, frame.height) {
+        Ok(mut decoder) => decoder.decode_with(data, storage),
+        #[cfg(feature = "jpeg")]
+        Err(JpegError::Sce(_)) => software::decode_with(data, storage),
+        Err(e) => Err(e),
+    }
+}
+
+/// The Media Engine's JPEG decoder, set up for images up to a given size.
+///
+/// `sceJpeg` has a single decoder, so only one `Decoder` can exist at a time.
+pub struct Decoder {
+    max_width: u32,
+    max_height: u32,
+    /// The JPEG being decoded, which the Media Engine reads from memory.
+    input: DmaBuffer<u8>,
+}
+
+impl Decoder {
+    /// Load `avcodec` and set up `sceJpeg` for images up to `max_width` by
+    /// `max_height` pixels.
+    pub fn new(max_width: u32, max_height: u32) -> Result<Self, JpegError> {
+        if max_width > MAX_DIMENSION || max_height > MAX_DIMENSION {
+            return Err(JpegError::Unsupported);
+        }
+
+        crate::utility::load_module(Module::AvCodec)?;
+
+        let max_width = stride(max_width);
+
+        unsafe {
+            sys::sceJpegInitMJpeg().into_result()?;
+
+            if let Err(e) =
+                sys::sceJpegCreateMJpeg(max_width as i32, max_height as i32).into_result()
+            {
+                sys::sceJpegFinishMJpeg();
+                return Err(e.into());
+            }
+        }
+
+        Ok(Self {
+            max_width,
+            max_height,
+            input: DmaBuffer::from_elem(0, 0),
+        })
+    }
+
+    /// The stride of the images this decoder writes.
+    pub fn stride(&self) -> u32 {
+        self.max_width
+    }
+
+    /// Decode a JPEG into main memory or VRAM.
+    pub fn decode_with(&mut self, data: &[u8], storage: Storage) -> Result<Image, JpegError> {
+        let frame = frame(data)?;
+
+        if frame.width > self.max_width || frame.height > self.max_height {
+            return Err(JpegError::SizeMismatch);
+        }
+
+        let mut image = Image::new(frame.width, frame.height, self.max_width, storage)?;
+        self.decode_frame(data, &frame, &mut image)?;
+
+        Ok(image)
+    }
+
+    /// Decode a JPEG into an existing image, such as the previous frame of a
+    /// video. The image must have the same dimensions as the JPEG, and the
+    /// stride of this decoder.
+    pub fn decode_into(&mut self, data: &[u8], image: &mut Image) -> Result<(), JpegError> {
+        let frame = frame(data)?;
+
+        if frame.width != image.width()
+            || frame.height != image.height()
+            || image.stride() != self.max_width
+        {
+            return Err(JpegError::SizeMismatch);
+        }
+
+        self.decode_frame(data, &frame, image)
+    }
+
+    fn decode_frame(
+        &mut self,
+        data: &[u8],
+        frame: &Frame,
+        image: &mut Image,
+    ) -> Result<(), JpegError> {
+        if !frame.sequential {
+            return Err(JpegError::Unsupported);
+        }
+
+        if self.input.len() < data.len() {
+            self.input = DmaBuffer::from_elem(0, data.len());
+        }
+
+        self.input[..data.len()].copy_from_slice(data);
+
+        unsafe {
+            sys::sceJpegDecodeMJpeg(
+                self.input.as_device_ptr() as *mut u8,
+                data.len(),
+                image.as_device_ptr(),
+                0,
+            )
+            .into_result()?;
+        }
+
+        image.sync_for_cpu();
+        Ok(())
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceJpegDeleteMJpeg();
+            sys::sceJpegFinishMJpeg();
+        }
+    }
+}
@@ -0,0 +1,684 @@
+//! A software JPEG decoder, for when `sceJpeg` is not available.
+//!
+//! It decodes baseline and extended sequential JPEGs with Huffman coding and
+//! 8-bit samples, in grayscale or YCbCr with any chroma subsampling. Chroma is
+//! upsampled by repeating samples.
+
+use super::JpegError;
+use crate::image::buffer::stride;
+use crate::image::{Image, Storage, MAX_DIMENSION};
+use alloc::{vec, vec::Vec};
+
+/// The position in a block of each coefficient, in the order they are coded.
+const ZIGZAG: [u8; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// `IDCT[x * 8 + u]` is the weight of frequency `u` at sample `x`.
+#[rustfmt::skip]
+#[allow(clippy::excessive_precision)]
+const IDCT: [f32; 64] = [
+    0.35355339, 0.49039264, 0.46193977, 0.41573481, 0.35355339, 0.27778512, 0.19134172, 0.09754516,
+    0.35355339, 0.41573481, 0.19134172, -0.09754516, -0.35355339, -0.49039264, -0.46193977, -0.27778512,
+    0.35355339, 0.27778512, -0.19134172, -0.49039264, -0.35355339, 0.09754516, 0.46193977, 0.41573481,
+    0.35355339, 0.09754516, -0.46193977, -0.27778512, 0.35355339, 0.41573481, -0.19134172, -0.49039264,
+    0.35355339, -0.09754516, -0.46193977, 0.27778512, 0.35355339, -0.41573481, -0.19134172, 0.49039264,
+    0.35355339, -0.27778512, -0.19134172, 0.49039264, -0.35355339, -0.09754516, 0.46193977, -0.41573481,
+    0.35355339, -0.41573481, 0.19134172, 0.09754516, -0.35355339, 0.49039264, -0.46193977, 0.27778512,
+    0.35355339, -0.49039264, 0.46193977, -0.41573481, 0.35355339, -0.27778512, 0.19134172, -0.09754516,
+];
+
+/// Bits looked up at once when decoding Huffman codes.
+const LOOKUP_BITS: u32 = 8;
+
+/// A Huffman table, from a DHT segment.
+struct Huffman {
+    /// `(length << 8) | value` for each code of up to `LOOKUP_BITS` bits,
+    /// indexed by the code padded with any bits. Zero for longer codes.
+    lookup: [u16; 1 << LOOKUP_BITS],
+    /// One more than the largest code of each length, or zero.
+    max_code: [u32; 17],
+    /// The index in `values` of the first code of each length, minus that
+    /// code.
+    offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8], values: &[u8]) -> Result<Self, JpegError> {
+        let mut table = Self {
+            lookup: [0; 1 << LOOKUP_BITS],
+            max_code: [0; 17],
+            offset: [0; 17],
+            values: values.to_vec(),
+        };
+
+        let mut code = 0u32;
+        let mut index = 0;
+
+        for len in 1..=16 {
+            table.offset[len] = index as i32 - code as i32;
+
+            for _ in 0..counts[len - 1] {
+                if code >= 1 << len {
+                    return Err(JpegError::InvalidData);
+                }
+
+                if len as u32 <= LOOKUP_BITS {
+                    let shift = LOOKUP_BITS - len as u32;
+                    let entry = (len as u16) << 8 | values[index] as u16;
+
+                    for padding in 0..1 << shift {
+                        table.lookup[((code << shift) | padding) as usize] = entry;
+                    }
+                }
+
+                code += 1;
+                index += 1;
+            }
+
+            table.max_code[len] = code;
+            code <<= 1;
+        }
+
+        Ok(table)
+    }
+}
+
+/// Reads the entropy-coded data of a scan, a bit at a time.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Bits not read yet, from the top.
+    buffer: u32,
+    count: u32,
+    /// Whether a marker has been reached. Zero bits are read after it.
+    marker: bool,
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            buffer: 0,
+            count: 0,
+            marker: false,
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.count <= 24 {
+            let mut byte = 0;
+
+            if !self.marker && self.pos < self.data.len() {
+                byte = self.data[self.pos];
+
+                if byte != 0xff {
+                    self.pos += 1;
+                } else if self.data.get(self.pos + 1) == Some(&0) {
+                    // A stuffed zero byte after 0xFF.
+                    self.pos += 2;
+                } else {
+                    self.marker = true;
+                    byte = 0;
+                }
+            }
+
+            self.buffer |= (byte as u32) << (24 - self.count);
+            self.count += 8;
+        }
+    }
+
+    fn consume(&mut self, bits: u32) {
+        self.buffer <<= bits;
+        self.count -= bits;
+    }
+
+    fn decode(&mut self, table: &Huffman) -> Result<u8, JpegError> {
+        self.fill();
+
+        let entry = table.lookup[(self.buffer >> (32 - LOOKUP_BITS)) as usize];
+
+        if entry != 0 {
+            self.consume((entry >> 8) as u32);
+            return Ok(entry as u8);
+        }
+
+        for len in LOOKUP_BITS as usize + 1..=16 {
+            let code = self.buffer >> (32 - len);
+
+            if code < table.max_code[len] {
+                self.consume(len as u32);
+
+                let index = (code as i32 + table.offset[len]) as usize;
+                return table
+                    .values
+                    .get(index)
+                    .copied()
+                    .ok_or(JpegError::InvalidData);
+            }
+        }
+
+        Err(JpegError::InvalidData)
+    }
+
+    /// Read a `bits`-bit value, and extend it to its signed value.
+    fn receive_extend(&mut self, bits: u8) -> i32 {
+        if bits == 0 {
+            return 0;
+        }
+
+        self.fill();
+
+        let bits = bits as u32;
+        let value = (self.buffer >> (32 - bits)) as i32;
+        self.consume(bits);
+
+        if value < 1 << (bits - 1) {
+            value - (1 << bits) + 1
+        } else {
+            value
+        }
+    }
+
+    /// Skip to after the next restart marker.
+    fn restart(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+        self.marker = false;
+
+        while self.pos + 1 < self.data.len() {
+            let marker = self.data[self.pos + 1];
+            self.pos += 1;
+
+            if self.data[self.pos - 1] == 0xff && (0xd0..=0xd7).contains(&marker) {
+                self.pos += 1;
+                return;
+            }
+        }
+    }
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant: usize,
+    dc: usize,
+    ac: usize,
+    prediction: i32,
+    /// The decoded samples, covering whole MCUs.
+    plane: Vec<u8>,
+    plane_width: usize,
+}
+
+struct Jpeg {
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+    max_h: usize,
+    max_v: usize,
+    quant: [[u16; 64]; 4],
+    dc: [Option<Huffman>; 4],
+    ac: [Option<Huffman>; 4],
+    restart_interval: usize,
+}
+
+fn be_u16(bytes: &[u8], at: usize) -> usize {
+    u16::from_be_bytes([bytes[at], bytes[at + 1]]) as usize
+}
+
+/// Inverse transform a block of coefficients, in natural order, to samples.
+fn idct(block: &[i32; 64], out: &mut [u8], stride: usize) {
+    if block[1..].iter().all(|&c| c == 0) {
+        let sample = clamp_sample(block[0] as f32 / 8.0);
+
+        for row in out.chunks_mut(stride).take(8) {
+            for x in &mut row[..8] {
+                *x = sample;
+            }
+        }
+
+        return;
+    }
+
+    let mut rows = [0f32; 64];
+
+    for v in 0..8 {
+        for x in 0..8 {
+            let mut sum = 0.0;
+
+            for u in 0..8 {
+                sum += IDCT[x * 8 + u] * block[v * 8 + u] as f32;
+            }
+
+            rows[v * 8 + x] = sum;
+        }
+    }
+
+    for (y, row) in out.chunks_mut(stride).take(8).enumerate() {
+        for x in 0..8 {
+            let mut sum = 0.0;
+
+            for v in 0..8 {
+                sum += IDCT[y * 8 + v] * rows[v * 8 + x];
+            }
+
+            row[x] = clamp_sample(sum);
+        }
+    }
+}
+
+/// Level shift and round a sample.
+fn clamp_sample(value: f32) -> u8 {
+    let value = value + 128.5;
+
+    if value <= 0.0 {
+        0
+    } else if value >= 255.0 {
+        255
+    } else {
+        value as u8
+    }
+}
+
+fn clamp(value: i32) -> u32 {
+    if value < 0 {
+        0
+    } else if value > 255 {
+        255
+    } else {
+        value as u32
+    }
+}
+
+impl Jpeg {
+    fn new() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            components: Vec::new(),
+            max_h: 1,
+            max_v: 1,
+            quant: [[0; 64]; 4],
+            dc: [None, None, None, None],
+            ac: [None, None, None, None],
+            restart_interval: 0,
+        }
+    }
+
+    fn mcus_x(&self) -> usize {
+        (self.width + 8 * self.max_h - 1) / (8 * self.max_h)
+    }
+
+    fn mcus_y(&self) -> usize {
+        (self.height + 8 * self.max_v - 1) / (8 * self.max_v)
+    }
+
+    fn read_quant(&mut self, mut segment: &[u8]) -> Result<(), JpegError> {
+        while !segment.is_empty() {
+            let precision = segment[0] >> 4;
+            let id = (segment[0] & 0xf) as usize;
+            let size = if precision == 0 { 64 } else { 128 };
+
+            if id > 3 || precision > 1 || segment.len() < 1 + size {
+                return Err(JpegError::InvalidData);
+            }
+
+            for k in 0..64 {
+                self.quant[id][k] = if precision == 0 {
+                    segment[1 + k] as u16
+                } else {
+                    be_u16(segment, 1 + 2 * k) as u16
+                };
+            }
+
+            segment = &segment[1 + size..];
+        }
+
+        Ok(())
+    }
+
+    fn read_huffman(&mut self, mut segment: &[u8]) -> Result<(), JpegError> {
+        while !segment.is_empty() {
+            if segment.len() < 17 {
+                return Err(JpegError::InvalidData);
+            }
+
+            let class = segment[0] >> 4;
+            let id = (segment[0] & 0xf) as usize;
+            let counts = &segment[1..17];
+            let total = counts.iter().map(|&count| count as usize).sum::<usize>();
+
+            if class > 1 || id > 3 || segment.len() < 17 + total {
+                return Err(JpegError::InvalidData);
+            }
+
+            let table = Some(Huffman::new(counts, &segment[17..17 + total])?);
+
+            if class == 0 {
+                self.dc[id] = table;
+            } else {
+                self.ac[id] = table;
+            }
+
+            segment = &segment[17 + total..];
+        }
+
+        Ok(())
+    }
+
+    fn read_frame(&mut self, marker: u8, segment: &[u8]) -> Result<(), JpegError> {
+        if marker != 0xc0 && marker != 0xc1 {
+            return Err(JpegError::Unsupported);
+        }
+
+        if !self.components.is_empty() || segment.len() < 6 {
+            return Err(JpegError::InvalidData);
+        }
+
+        if segment[0] != 8 {
+            return Err(JpegError::Unsupported);
+        }
+
+        self.height = be_u16(segment, 1);
+        self.width = be_u16(segment, 3);
+        let count = segment[5] as usize;
+
+        if count != 1 && count != 3 {
+            return Err(JpegError::Unsupported);
+        }
+
+        if self.width == 0 || self.height == 0 || segment.len() < 6 + 3 * count {
+            return Err(JpegError::InvalidData);
+        }
+
+        if self.width > MAX_DIMENSION as usize || self.height > MAX_DIMENSION as usize {
+            return Err(JpegError::Unsupported);
+        }
+
+        for info in segment[6..6 + 3 * count].chunks(3) {
+            let h = (info[1] >> 4) as usize;
+            let v = (info[1] & 0xf) as usize;
+
+            if h == 0 || h > 4 || v == 0 || v > 4 || info[2] > 3 {
+                return Err(JpegError::InvalidData);
+            }
+
+            self.max_h = core::cmp::max(self.max_h, h);
+            self.max_v = core::cmp::max(self.max_v, v);
+
+            self.components.push(Component {
+                id: info[0],
+                h,
+                v,
+                quant: info[2] as usize,
+                dc: 0,
+                ac: 0,
+                prediction: 0,
+                plane: Vec::new(),
+                plane_width: 0,
+            });
+        }
+
+        let (mcus_x, mcus_y) = (self.mcus_x(), self.mcus_y());
+
+        for component in &mut self.components {
+            component.plane_width = mcus_x * component.h * 8;
+            component.plane = vec![0; component.plane_width * mcus_y * component.v * 8];
+        }
+
+        Ok(())
+    }
+
+    fn decode_block(
+        &mut self,
+        bits: &mut Bits<'_>,
+        index: usize,
+        block_x: usize,
+        block_y: usize,
+    ) -> Result<(), JpegError> {
+        let component = &mut self.components[index];
+        let dc = self.dc[component.dc]
+            .as_ref()
+            .ok_or(JpegError::InvalidData)?;
+        let ac = self.ac[component.ac]
+            .as_ref()
+            .ok_or(JpegError::InvalidData)?;
+        let quant = &self.quant[component.quant];
+
+        let mut block = [0i32; 64];
+
+        let size = bits.decode(dc)?;
+
+        if size > 15 {
+            return Err(JpegError::InvalidData);
+        }
+
+        // Corrupt data may overflow, but can't do worse than decode to noise.
+        component.prediction = component.prediction.wrapping_add(bits.receive_extend(size));
+        block[0] = component.prediction.wrapping_mul(quant[0] as i32);
+
+        let mut k = 1;
+
+        while k < 64 {
+            let rs = bits.decode(ac)?;
+            let (run, size) = ((rs >> 4) as usize, rs & 0xf);
+
+            if size == 0 {
+                if run != 15 {
+                    break;
+                }
+
+                k += 16;
+                continue;
+            }
+
+            k += run;
+
+            if k > 63 {
+                return Err(JpegError::InvalidData);
+            }
+
+            block[ZIGZAG[k] as usize] = bits.receive_extend(size) * quant[k] as i32;
+            k += 1;
+        }
+
+        let stride = component.plane_width;
+        let start = block_y * 8 * stride + block_x * 8;
+        idct(&block, &mut component.plane[start..], stride);
+
+        Ok(())
+    }
+
+    /// Decode the scan starting at `data`, and return the length of its
+    /// entropy-coded data.
+    fn decode_scan(&mut self, header: &[u8], data: &[u8]) -> Result<usize, JpegError> {
+        let count = header[0] as usize;
+
+        if count == 0 || header.len() < 1 + 2 * count {
+            return Err(JpegError::InvalidData);
+        }
+
+        let mut scan = Vec::with_capacity(count);
+
+        for info in header[1..1 + 2 * count].chunks(2) {
+            let index = self
+                .components
+                .iter()
+                .position(|component| component.id == info[0])
+                .ok_or(JpegError::InvalidData)?;
+
+            let component = &mut self.components[index];
+            component.dc = (info[1] >> 4) as usize;
+            component.ac = (info[1] & 0xf) as usize;
+            component.prediction = 0;
+
+            if component.dc > 3 || component.ac > 3 {
+                return Err(JpegError::InvalidData);
+            }
+
+            scan.push(index);
+        }
+
+        let mut bits = Bits::new(data);
+
+        // A single component is coded block by block, covering only the
+        // blocks within the image. Otherwise blocks are grouped into MCUs.
+        let (units_x, units_y) = if count == 1 {
+            let component = &self.components[scan[0]];
+            let width = (self.width * component.h + self.max_h - 1) / self.max_h;
+            let height = (self.height * component.v + self.max_v - 1) / self.max_v;
+
+            ((width + 7) / 8, (height + 7) / 8)
+        } else {
+            (self.mcus_x(), self.mcus_y())
+        };
+
+        for unit in 0..units_x * units_y {
+            if self.restart_interval > 0 && unit > 0 && unit % self.restart_interval == 0 {
+                bits.restart();
+
+                for &index in &scan {
+                    self.components[index].prediction = 0;
+                }
+            }
+
+            let (x, y) = (unit % units_x, unit / units_x);
+
+            if count == 1 {
+                self.decode_block(&mut bits, scan[0], x, y)?;
+                continue;
+            }
+
+            for &index in &scan {
+                let (h, v) = (self.components[index].h, self.components[index].v);
+
+                for block in 0..h * v {
+                    self.decode_block(&mut bits, index, x * h + block % h, y * v + block / h)?;
+                }
+            }
+        }
+
+        Ok(bits.pos)
+    }
+
+    fn to_image(&self, storage: Storage) -> Result<Image, JpegError> {
+        let (width, height) = (self.width as u32, self.height as u32);
+        let mut image = Image::new(width, height, stride(width), storage)?;
+        let image_stride = image.stride() as usize;
+        let pixels = image.pixels_mut();
+
+        let sample = |component: &Component, x: usize, y: usize| {
+            let x = x * component.h / self.max_h;
+            let y = y * component.v / self.max_v;
+
+            component.plane[y * component.plane_width + x] as i32
+        };
+
+        for y in 0..self.height {
+            let row = &mut pixels[y * image_stride..y * image_stride + self.width];
+
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let luma = sample(&self.components[0], x, y);
+
+                *pixel = if self.components.len() == 1 {
+                    let luma = luma as u32;
+                    0xff00_0000 | luma << 16 | luma << 8 | luma
+                } else {
+                    let cb = sample(&self.components[1], x, y) - 128;
+                    let cr = sample(&self.components[2], x, y) - 128;
+                    let luma = (luma << 16) + 0x8000;
+
+                    let r = clamp((luma + 91881 * cr) >> 16);
+                    let g = clamp((luma - 22554 * cb - 46802 * cr) >> 16);
+                    let b = clamp((luma + 116130 * cb) >> 16);
+
+                    0xff00_0000 | b << 16 | g << 8 | r
+                };
+            }
+        }
+
+        Ok(image)
+    }
+}
+
+/// Decode a JPEG into main memory.
+pub fn decode(data: &[u8]) -> Result<Image, JpegError> {
+    decode_with(data, Storage::Ram)
+}
+
+/// Decode a JPEG into main memory or VRAM.
+pub fn decode_with(data: &[u8], storage: Storage) -> Result<Image, JpegError> {
+    if data.len() < 4 || data[..2] != [0xff, 0xd8] {
+        return Err(JpegError::InvalidData);
+    }
+
+    let mut jpeg = Jpeg::new();
+    let mut scanned = false;
+    let mut pos = 2;
+
+    loop {
+        if pos + 2 > data.len() || data[pos] != 0xff {
+            return Err(JpegError::InvalidData);
+        }
+
+        let marker = data[pos + 1];
+
+        if marker == 0xff {
+            pos += 1;
+            continue;
+        } else if marker == 0xd9 {
+            break;
+        } else if marker == 0x01 || (0xd0..=0xd7).contains(&marker) {
+            pos += 2;
+            continue;
+        }
+
+        if pos + 4 > data.len() {
+            return Err(JpegError::InvalidData);
+        }
+
+        let len = be_u16(data, pos + 2);
+        let segment = data
+            .get(pos + 4..pos + 2 + len)
+            .ok_or(JpegError::InvalidData)?;
+        pos += 2 + len;
+
+        match marker {
+            0xdb => jpeg.read_quant(segment)?,
+            0xc4 => jpeg.read_huffman(segment)?,
+            0xdd if segment.len() >= 2 => jpeg.restart_interval = be_u16(segment, 0),
+            0xc0..=0xcf if marker != 0xc8 && marker != 0xcc => jpeg.read_frame(marker, segment)?,
+            0xda if !jpeg.components.is_empty() && !segment.is_empty() => {
+                pos += jpeg.decode_scan(segment, &data[pos..])?;
+                scanned = true;
+
+                // Skip anything left before the next marker.
+                while pos + 1 < data.len()
+                    && (data[pos] != 0xff
+                        || data[pos + 1] == 0
+                        || (0xd0..=0xd7).contains(&data[pos + 1]))
+                {
+                    pos += 1;
+                }
+            }
+            0xda | 0xdd => return Err(JpegError::InvalidData),
+            _ => {}
+        }
+
+        // Some encoders leave out the end marker.
+        if scanned && pos + 2 > data.len() {
+            break;
+        }
+    }
+
+    if !scanned {
+        return Err(JpegError::InvalidData);
+    }
+
+    jpeg.to_image(storage)
+}
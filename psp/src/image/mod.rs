@@ -0,0 +1,44 @@
+//! Image decoding.
+//!
+//! Decoders produce an `Image` of 32-bit pixels, kept in main memory or in
+//! VRAM and laid out so the GE can use it as a `Psm8888` texture as it is.
+//! `TextureOptions` converts an image into a `Texture` of another format,
+//! such as 16-bit color or palettized, padded to power-of-two dimensions and
+//! optionally swizzled.
+//!
+//! `jpeg` decodes JPEG files on the Media Engine, and reads Motion-JPEG AVI
+//! files for cheap video. With the `png` and `tga` features, `png` and `tga`
+//! decode PNG and TGA files in software.
+//!
+//! ```no_run
+//! use psp::image::{jpeg, Storage};
+//!
+//! let bytes = psp::fs::read("ms0:/PSP/GAME/demo/title.jpg").unwrap();
+//! let mut title = jpeg::decode_with(&bytes, Storage::Vram).unwrap();
+//!
+//! unsafe {
+//!     psp::sys::sceGuTexMode(psp::sys::TexturePixelFormat::Psm8888, 0, 0, 0);
+//!     psp::sys::sceGuTexImage(
+//!         psp::sys::MipmapLevel::None,
+//!         512,
+//!         512,
+//!         title.stride() as i32,
+//!         title.as_device_ptr(),
+//!     );
+//! }
+//! ```
+
+mod buffer;
+mod error;
+mod quantize;
+mod texture;
+
+pub mod jpeg;
+#[cfg(feature = "png")]
+pub mod png;
+#[cfg(feature = "tga")]
+pub mod tga;
+
+pub use buffer::*;
+pub use error::*;
+pub use texture::*;
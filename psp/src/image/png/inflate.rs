@@ -0,0 +1,422 @@
+//! A zlib (RFC 1950) and DEFLATE (RFC 1951) decompressor.
+
+use crate::image::ImageError;
+use alloc::vec::Vec;
+
+/// The bits of the fast lookup in a `Huffman` table.
+const LOOKUP_BITS: u32 = 9;
+
+/// The base length of each length code, from 257, and its extra bits.
+#[rustfmt::skip]
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+#[rustfmt::skip]
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// The base distance of each distance code, and its extra bits.
+#[rustfmt::skip]
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+#[rustfmt::skip]
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+/// The order code length code lengths are stored in.
+#[rustfmt::skip]
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Reads bits from a DEFLATE stream, least significant first.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+    /// The bits of zeros added to the buffer past the end of the data.
+    padding: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            buffer: 0,
+            count: 0,
+            padding: 0,
+        }
+    }
+
+    /// Fill the buffer with at least 24 bits, padding with zeros at the end
+    /// of the data so codes near the end can be looked up.
+    fn fill(&mut self) {
+        while self.count <= 24 {
+            match self.data.get(self.pos) {
+                Some(&byte) => {
+                    self.buffer |= (byte as u32) << self.count;
+                    self.pos += 1;
+                }
+                None => self.padding += 8,
+            }
+
+            self.count += 8;
+        }
+    }
+
+    fn consume(&mut self, len: u32) -> Result<(), ImageError> {
+        self.buffer >>= len;
+        self.count -= len;
+
+        if self.count < self.padding {
+            return Err(ImageError::InvalidData);
+        }
+
+        Ok(())
+    }
+
+    fn bits(&mut self, len: u32) -> Result<u32, ImageError> {
+        self.fill();
+        let value = self.buffer & ((1 << len) - 1);
+        self.consume(len)?;
+
+        Ok(value)
+    }
+
+    /// Skip to the next whole byte.
+    fn align(&mut self) -> Result<(), ImageError> {
+        self.consume(self.count % 8)
+    }
+
+    /// The data after the last byte read. The buffer must be empty or
+    /// aligned.
+    fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos - (self.count - self.padding) as usize / 8..]
+    }
+
+    /// Move `len` bytes ahead of an aligned position.
+    fn skip_bytes(&mut self, len: usize) {
+        self.pos = self.pos - (self.count - self.padding) as usize / 8 + len;
+        self.buffer = 0;
+        self.count = 0;
+        self.padding = 0;
+    }
+}
+
+/// A canonical Huffman code.
+struct Huffman {
+    /// For the next `LOOKUP_BITS` bits, the symbol and its code length as
+    /// `length << 12 | symbol`, or 0 for a longer code.
+    lookup: [u16; 1 << LOOKUP_BITS],
+    /// The number of codes of each length.
+    counts: [u16; 16],
+    /// The symbols, ordered by code.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, ImageError> {
+        let mut counts = [0u16; 16];
+
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+
+        counts[0] = 0;
+
+        // Reject over-subscribed codes. Incomplete ones are allowed, which a
+        // distance code with a single symbol is.
+        let mut left = 1i32;
+
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+
+            if left < 0 {
+                return Err(ImageError::InvalidData);
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = alloc::vec![0; lengths.len()];
+        let mut lookup = [0; 1 << LOOKUP_BITS];
+        let mut next_code = [0u32; 16];
+        let mut code = 0;
+
+        for len in 1..16 {
+            code = (code + counts[len - 1] as u32) << 1;
+            next_code[len] = code;
+        }
+
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len == 0 {
+                continue;
+            }
+
+            let len = len as usize;
+            symbols[offsets[len] as usize] = symbol as u16;
+            offsets[len] += 1;
+
+            let code = next_code[len];
+            next_code[len] += 1;
+
+            if len as u32 <= LOOKUP_BITS {
+                // Codes are stored most significant bit first.
+                let reversed = code.reverse_bits() >> (32 - len);
+
+                for index in (reversed as usize..lookup.len()).step_by(1 << len) {
+                    lookup[index] = (len as u16) << 12 | symbol as u16;
+                }
+            }
+        }
+
+        Ok(Self {
+            lookup,
+            counts,
+            symbols,
+        })
+    }
+
+    fn decode(&self, bits: &mut Bits<'_>) -> Result<u16, ImageError> {
+        bits.fill();
+        let entry = self.lookup[(bits.buffer & ((1 << LOOKUP_BITS) - 1)) as usize];
+
+        if entry != 0 {
+            bits.consume((entry >> 12) as u32)?;
+            return Ok(entry & 0xfff);
+        }
+
+        // Walk the code one bit at a time, as the lengths are canonical.
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+
+        for len in 1..16 {
+            code |= ((bits.buffer >> (len - 1)) & 1) as i32;
+            let count = self.counts[len as usize] as i32;
+
+            if code - first < count {
+                bits.consume(len)?;
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(ImageError::InvalidData)
+    }
+}
+
+/// The codes of a fixed Huffman block.
+fn fixed() -> (Huffman, Huffman) {
+    let mut lengths = [0; 288];
+    lengths[..144].iter_mut().for_each(|len| *len = 8);
+    lengths[144..256].iter_mut().for_each(|len| *len = 9);
+    lengths[256..280].iter_mut().for_each(|len| *len = 7);
+    lengths[280..].iter_mut().for_each(|len| *len = 8);
+
+    // Neither is over-subscribed.
+    (
+        Huffman::new(&lengths).unwrap(),
+        Huffman::new(&[5; 30]).unwrap(),
+    )
+}
+
+/// Read the codes of a dynamic Huffman block.
+fn dynamic(bits: &mut Bits<'_>) -> Result<(Huffman, Huffman), ImageError> {
+    let literals = bits.bits(5)? as usize + 257;
+    let distances = bits.bits(5)? as usize + 1;
+    let code_lengths = bits.bits(4)? as usize + 4;
+
+    let mut lengths = [0; 19];
+
+    for &symbol in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[symbol] = bits.bits(3)? as u8;
+    }
+
+    let code_length_code = Huffman::new(&lengths)?;
+    let mut lengths = [0; 288 + 32];
+    let mut i = 0;
+
+    while i < literals + distances {
+        let (value, repeat) = match code_length_code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 if i > 0 => (lengths[i - 1], 3 + bits.bits(2)?),
+            17 => (0, 3 + bits.bits(3)?),
+            18 => (0, 11 + bits.bits(7)?),
+            _ => return Err(ImageError::InvalidData),
+        };
+
+        let end = i + repeat as usize;
+
+        if end > literals + distances {
+            return Err(ImageError::InvalidData);
+        }
+
+        lengths[i..end].iter_mut().for_each(|len| *len = value);
+        i = end;
+    }
+
+    // Without end-of-block, the block can't end.
+    if lengths[256] == 0 {
+        return Err(ImageError::InvalidData);
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literals])?,
+        Huffman::new(&lengths[literals..literals + distances])?,
+    ))
+}
+
+/// Decompress a DEFLATE stream of no more than `limit` bytes. Returns the
+/// data, and the rest of the input.
+fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, &[u8]), ImageError> {
+    // Grown as data arrives, as `limit` comes from the untrusted header.
+    let mut out = Vec::new();
+    let mut bits = Bits::new(data);
+
+    loop {
+        let last = bits.bits(1)? == 1;
+
+        let (literal, distance) = match bits.bits(2)? {
+            0 => {
+                bits.align()?;
+                let header = bits.remaining();
+
+                if header.len() < 4 {
+                    return Err(ImageError::InvalidData);
+                }
+
+                let len = u16::from_le_bytes([header[0], header[1]]);
+
+                if len != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err(ImageError::InvalidData);
+                }
+
+                let stored = header[4..]
+                    .get(..len as usize)
+                    .ok_or(ImageError::InvalidData)?;
+
+                if out.len() + stored.len() > limit {
+                    return Err(ImageError::InvalidData);
+                }
+
+                out.extend_from_slice(stored);
+                bits.skip_bytes(4 + len as usize);
+
+                if last {
+                    break;
+                }
+
+                continue;
+            }
+            1 => fixed(),
+            2 => dynamic(&mut bits)?,
+            _ => return Err(ImageError::InvalidData),
+        };
+
+        loop {
+            let symbol = literal.decode(&mut bits)? as usize;
+
+            if symbol < 256 {
+                if out.len() == limit {
+                    return Err(ImageError::InvalidData);
+                }
+
+                out.push(symbol as u8);
+                continue;
+            }
+
+            if symbol == 256 {
+                break;
+            }
+
+            let code = symbol - 257;
+
+            if code >= LENGTH_BASE.len() {
+                return Err(ImageError::InvalidData);
+            }
+
+            let len = LENGTH_BASE[code] as usize + bits.bits(LENGTH_EXTRA[code] as u32)? as usize;
+            let code = distance.decode(&mut bits)? as usize;
+
+            if code >= DISTANCE_BASE.len() {
+                return Err(ImageError::InvalidData);
+            }
+
+            let distance =
+                DISTANCE_BASE[code] as usize + bits.bits(DISTANCE_EXTRA[code] as u32)? as usize;
+
+            if distance > out.len() || out.len() + len > limit {
+                return Err(ImageError::InvalidData);
+            }
+
+            // The copy may overlap what it adds, to repeat a run.
+            let start = out.len() - distance;
+
+            for i in start..start + len {
+                let byte = out[i];
+                out.push(byte);
+            }
+        }
+
+        if last {
+            break;
+        }
+    }
+
+    bits.align()?;
+    Ok((out, bits.remaining()))
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    // The most bytes that can be summed before `b` could overflow.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+
+        a %= 65521;
+        b %= 65521;
+    }
+
+    b << 16 | a
+}
+
+/// Decompress a zlib stream of no more than `limit` bytes.
+pub(super) fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, ImageError> {
+    if data.len() < 2 || u16::from_be_bytes([data[0], data[1]]) % 31 != 0 {
+        return Err(ImageError::InvalidData);
+    }
+
+    // DEFLATE, without a preset dictionary.
+    if data[0] & 0xf != 8 || data[1] & 0x20 != 0 {
+        return Err(ImageError::InvalidData);
+    }
+
+    let (out, rest) = inflate(&data[2..], limit)?;
+
+    if rest.len() < 4 || u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) != adler32(&out) {
+        return Err(ImageError::InvalidData);
+    }
+
+    Ok(out)
+}
@@ -0,0 +1,359 @@
+//! PNG decoding.
+//!
+//! All PNGs are handled: every color type and bit depth, transparency from
+//! `tRNS` chunks, and interlacing. 16-bit samples are reduced to 8 bits, and
+//! other ancillary chunks, such as gamma, are ignored. Chunk CRCs are not
+//! checked, but zlib's checksum catches corrupt image data.
+
+mod inflate;
+
+use super::buffer::stride;
+use super::{Image, ImageError, Storage, MAX_DIMENSION};
+use alloc::{vec, vec::Vec};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// The Adam7 passes, as `(x, y, dx, dy)`: the first pixel of each, and the
+/// distance between pixels.
+const ADAM7: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+fn be_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+fn be_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([bytes[at], bytes[at + 1]])
+}
+
+/// The `IHDR` chunk.
+struct Header {
+    width: u32,
+    height: u32,
+    depth: u8,
+    color: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn read(data: &[u8]) -> Result<Self, ImageError> {
+        if data.len() < 33 || data[..8] != SIGNATURE || &data[12..16] != b"IHDR" {
+            return Err(ImageError::InvalidData);
+        }
+
+        let header = Self {
+            width: be_u32(data, 16),
+            height: be_u32(data, 20),
+            depth: data[24],
+            color: data[25],
+            interlaced: data[28] == 1,
+        };
+
+        let depth_valid = match header.color {
+            0 => [1, 2, 4, 8, 16].contains(&header.depth),
+            3 => [1, 2, 4, 8].contains(&header.depth),
+            2 | 4 | 6 => [8, 16].contains(&header.depth),
+            _ => false,
+        };
+
+        // Compression, filter and interlace methods.
+        if !depth_valid || data[26] != 0 || data[27] != 0 || data[28] > 1 {
+            return Err(ImageError::InvalidData);
+        }
+
+        if header.width == 0 || header.height == 0 {
+            return Err(ImageError::InvalidData);
+        }
+
+        if header.width > MAX_DIMENSION || header.height > MAX_DIMENSION {
+            return Err(ImageError::Unsupported);
+        }
+
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.color {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    /// The bytes in a row `width` pixels wide, without its filter type.
+    fn row_bytes(&self, width: u32) -> usize {
+        (width as usize * self.channels() * self.depth as usize + 7) / 8
+    }
+
+    /// The distance filters look back, in bytes.
+    fn filter_distance(&self) -> usize {
+        core::cmp::max(1, self.channels() * self.depth as usize / 8)
+    }
+
+    /// The passes of the image, as `(x, y, dx, dy, width, height)`. Passes
+    /// without pixels are left out.
+    fn passes(&self) -> Vec<(u32, u32, u32, u32, u32, u32)> {
+        let passes: &[_] = if self.interlaced {
+            &ADAM7
+        } else {
+            &[(0, 0, 1, 1)]
+        };
+
+        passes
+            .iter()
+            .filter(|&&(x, y, _, _)| x < self.width && y < self.height)
+            .map(|&(x, y, dx, dy)| {
+                let width = (self.width - x + dx - 1) / dx;
+                let height = (self.height - y + dy - 1) / dy;
+                (x, y, dx, dy, width, height)
+            })
+            .collect()
+    }
+
+    /// Sample `index` of a row, counting each channel of each pixel.
+    fn sample(&self, row: &[u8], index: usize) -> u16 {
+        match self.depth {
+            8 => row[index] as u16,
+            16 => be_u16(row, index * 2),
+            depth => {
+                let bit = index * depth as usize;
+                let shift = 8 - depth as usize - bit % 8;
+                (row[bit / 8] >> shift) as u16 & ((1 << depth) - 1)
+            }
+        }
+    }
+
+    /// Scale a sample to 8 bits.
+    fn scale(&self, sample: u16) -> u32 {
+        match self.depth {
+            16 => (sample >> 8) as u32,
+            8 => sample as u32,
+            depth => sample as u32 * 255 / ((1 << depth) - 1),
+        }
+    }
+}
+
+/// The transparency of an image.
+enum Transparency {
+    None,
+    /// Pixels of this gray level or RGB color are transparent.
+    Key([u16; 3]),
+    /// A palette, with alpha.
+    Palette(Vec<u32>),
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let (a16, b16, c16) = (a as i16, b as i16, c as i16);
+    let p = a16 + b16 - c16;
+    let (pa, pb, pc) = ((p - a16).abs(), (p - b16).abs(), (p - c16).abs());
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Undo the filter of a row, given the row before it.
+fn unfilter(
+    filter: u8,
+    line: &[u8],
+    previous: &[u8],
+    current: &mut [u8],
+    distance: usize,
+) -> Result<(), ImageError> {
+    if filter > 4 {
+        return Err(ImageError::InvalidData);
+    }
+
+    for i in 0..line.len() {
+        let a = if i >= distance {
+            current[i - distance]
+        } else {
+            0
+        };
+        let b = previous[i];
+        let c = if i >= distance {
+            previous[i - distance]
+        } else {
+            0
+        };
+
+        let predicted = match filter {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            _ => paeth(a, b, c),
+        };
+
+        current[i] = line[i].wrapping_add(predicted);
+    }
+
+    Ok(())
+}
+
+/// The width and height of a PNG, read from its header.
+pub fn dimensions(data: &[u8]) -> Result<(u32, u32), ImageError> {
+    Header::read(data).map(|header| (header.width, header.height))
+}
+
+/// Decode a PNG into main memory.
+pub fn decode(data: &[u8]) -> Result<Image, ImageError> {
+    decode_with(data, Storage::Ram)
+}
+
+/// Decode a PNG into main memory or VRAM.
+pub fn decode_with(data: &[u8], storage: Storage) -> Result<Image, ImageError> {
+    let header = Header::read(data)?;
+    let mut transparency = Transparency::None;
+    let mut compressed = Vec::new();
+    let mut pos = 8;
+
+    loop {
+        let chunk = data.get(pos..pos + 8).ok_or(ImageError::InvalidData)?;
+        let len = be_u32(chunk, 0) as usize;
+        let kind = &chunk[4..];
+        let body = data[pos + 8..].get(..len).ok_or(ImageError::InvalidData)?;
+
+        // With its CRC.
+        pos += 12 + len;
+
+        match kind {
+            b"IHDR" => {}
+            b"PLTE" => {
+                if len % 3 != 0 || len > 3 * 256 {
+                    return Err(ImageError::InvalidData);
+                }
+
+                let palette = body
+                    .chunks(3)
+                    .map(|rgb| {
+                        0xff00_0000 | (rgb[2] as u32) << 16 | (rgb[1] as u32) << 8 | rgb[0] as u32
+                    })
+                    .collect();
+
+                transparency = Transparency::Palette(palette);
+            }
+            b"tRNS" => match (header.color, &mut transparency) {
+                (3, Transparency::Palette(palette)) => {
+                    for (entry, &alpha) in palette.iter_mut().zip(body) {
+                        *entry = *entry & 0xff_ffff | (alpha as u32) << 24;
+                    }
+                }
+                (0, _) if len >= 2 => {
+                    let gray = be_u16(body, 0);
+                    transparency = Transparency::Key([gray; 3]);
+                }
+                (2, _) if len >= 6 => {
+                    let key = [be_u16(body, 0), be_u16(body, 2), be_u16(body, 4)];
+                    transparency = Transparency::Key(key);
+                }
+                _ => {}
+            },
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            // An unknown critical chunk, which changes how the image is read.
+            _ if kind[0] & 0x20 == 0 => return Err(ImageError::Unsupported),
+            _ => {}
+        }
+    }
+
+    let palette = match &transparency {
+        Transparency::Palette(palette) => &palette[..],
+        _ if header.color == 3 => return Err(ImageError::InvalidData),
+        _ => &[],
+    };
+
+    let key = match &transparency {
+        Transparency::Key(key) => Some(*key),
+        _ => None,
+    };
+
+    let passes = header.passes();
+    let size = passes
+        .iter()
+        .map(|&(_, _, _, _, width, height)| height as usize * (1 + header.row_bytes(width)))
+        .sum();
+
+    let raw = inflate::decompress(&compressed, size)?;
+
+    if raw.len() != size {
+        return Err(ImageError::InvalidData);
+    }
+
+    drop(compressed);
+
+    let (width, height) = (header.width, header.height);
+    let mut image = Image::new(width, height, stride(width), storage)?;
+    let image_stride = image.stride() as usize;
+    let pixels = image.pixels_mut();
+
+    let channels = header.channels();
+    let distance = header.filter_distance();
+    let mut rows = &raw[..];
+
+    for (x, y, dx, dy, width, height) in passes {
+        let row_bytes = header.row_bytes(width);
+        let mut previous = vec![0; row_bytes];
+        let mut current = vec![0; row_bytes];
+
+        for row in 0..height {
+            let (line, rest) = rows.split_at(1 + row_bytes);
+            rows = rest;
+
+            unfilter(line[0], &line[1..], &previous, &mut current, distance)?;
+
+            let start = (y + row * dy) as usize * image_stride;
+
+            for i in 0..width as usize {
+                let sample = |channel| header.sample(&current, i * channels + channel);
+
+                let pixel = match header.color {
+                    0 | 4 => {
+                        let gray = header.scale(sample(0)) * 0x01_0101;
+
+                        let alpha = match (header.color, key) {
+                            (4, _) => header.scale(sample(1)),
+                            (_, Some(key)) if key[0] == sample(0) => 0,
+                            _ => 0xff,
+                        };
+
+                        alpha << 24 | gray
+                    }
+                    2 | 6 => {
+                        let rgb = [sample(0), sample(1), sample(2)];
+
+                        let alpha = match (header.color, key) {
+                            (6, _) => header.scale(sample(3)),
+                            (_, Some(key)) if key == rgb => 0,
+                            _ => 0xff,
+                        };
+
+                        let [r, g, b] = rgb;
+                        alpha << 24 | header.scale(b) << 16 | header.scale(g) << 8 | header.scale(r)
+                    }
+                    _ => *palette
+                        .get(sample(0) as usize)
+                        .ok_or(ImageError::InvalidData)?,
+                };
+
+                pixels[start + (x + i as u32 * dx) as usize] = pixel;
+            }
+
+            core::mem::swap(&mut previous, &mut current);
+        }
+    }
+
+    Ok(image)
+}
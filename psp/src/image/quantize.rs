@@ -0,0 +1,162 @@
+//! Palettes for `PsmT4` and `PsmT8` textures.
+
+use super::Image;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+fn channel(color: u32, channel: u32) -> u32 {
+    (color >> (channel * 8)) & 0xff
+}
+
+/// The channel of `colors` with the widest range of values, and its range.
+fn widest_channel(colors: &[(u32, u32)]) -> (u32, u32) {
+    (0..4)
+        .map(|c| {
+            let values = colors.iter().map(|&(color, _)| channel(color, c));
+            let min = values.clone().min().unwrap_or(0);
+            let max = values.max().unwrap_or(0);
+            (max - min, c)
+        })
+        .max()
+        .map(|(range, c)| (c, range))
+        .unwrap_or((0, 0))
+}
+
+/// A group of colors that will share a palette entry, with the channel it
+/// would be split along and that channel's range.
+struct Bucket {
+    colors: Range<usize>,
+    channel: u32,
+    range: u32,
+}
+
+impl Bucket {
+    fn new(colors: &[(u32, u32)], range: Range<usize>) -> Self {
+        let (channel, channel_range) = widest_channel(&colors[range.clone()]);
+
+        Self {
+            colors: range,
+            channel,
+            range: channel_range,
+        }
+    }
+}
+
+/// A palette of 32-bit colors for an image, and the entry for each of the
+/// image's colors.
+pub(super) struct Palette {
+    pub colors: Vec<u32>,
+    /// Each color of the image, sorted, with its entry.
+    entries: Vec<(u32, u8)>,
+}
+
+impl Palette {
+    /// Make a palette of up to `size` colors. Images with more colors are
+    /// reduced with median cut.
+    pub fn new(image: &Image, size: usize) -> Self {
+        let mut pixels = Vec::with_capacity(image.width() as usize * image.height() as usize);
+
+        for y in 0..image.height() {
+            pixels.extend_from_slice(image.row(y));
+        }
+
+        pixels.sort_unstable();
+
+        // Each color, and how many pixels have it.
+        let mut colors: Vec<(u32, u32)> = Vec::new();
+
+        for pixel in pixels {
+            match colors.last_mut() {
+                Some((color, count)) if *color == pixel => *count += 1,
+                _ => colors.push((pixel, 1)),
+            }
+        }
+
+        if colors.len() <= size {
+            return Self {
+                colors: colors.iter().map(|&(color, _)| color).collect(),
+                entries: colors
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &(color, _))| (color, i as u8))
+                    .collect(),
+            };
+        }
+
+        let mut buckets = alloc::vec![Bucket::new(&colors, 0..colors.len())];
+
+        while buckets.len() < size {
+            // Split the bucket with the widest range in one channel.
+            let widest = buckets
+                .iter()
+                .enumerate()
+                .filter(|(_, bucket)| bucket.colors.len() > 1)
+                .max_by_key(|(_, bucket)| bucket.range)
+                .map(|(i, _)| i);
+
+            let i = match widest {
+                Some(i) => i,
+                None => break,
+            };
+
+            let range = buckets[i].colors.clone();
+            let c = buckets[i].channel;
+            let bucket = &mut colors[range.clone()];
+            bucket.sort_unstable_by_key(|&(color, _)| channel(color, c));
+
+            // At the median pixel, keeping at least one color on each side.
+            let total: u64 = bucket.iter().map(|&(_, count)| count as u64).sum();
+            let mut seen = 0;
+            let mut split = 1;
+
+            for (j, &(_, count)) in bucket.iter().enumerate() {
+                seen += count as u64;
+
+                if seen * 2 >= total {
+                    split = j + 1;
+                    break;
+                }
+            }
+
+            let split = range.start + core::cmp::min(split, bucket.len() - 1);
+
+            buckets[i] = Bucket::new(&colors, range.start..split);
+            buckets.push(Bucket::new(&colors, split..range.end));
+        }
+
+        let mut palette = Vec::with_capacity(buckets.len());
+        let mut entries = Vec::with_capacity(colors.len());
+
+        for (i, bucket) in buckets.iter().enumerate() {
+            let colors = &colors[bucket.colors.clone()];
+            let total: u64 = colors.iter().map(|&(_, count)| count as u64).sum();
+
+            // The average of the bucket's pixels.
+            let color = (0..4).fold(0, |color, c| {
+                let sum: u64 = colors
+                    .iter()
+                    .map(|&(color, count)| channel(color, c) as u64 * count as u64)
+                    .sum();
+
+                color | (((sum + total / 2) / total) as u32) << (c * 8)
+            });
+
+            palette.push(color);
+            entries.extend(colors.iter().map(|&(color, _)| (color, i as u8)));
+        }
+
+        entries.sort_unstable();
+
+        Self {
+            colors: palette,
+            entries,
+        }
+    }
+
+    /// The entry for a color of the image.
+    pub fn entry(&self, color: u32) -> u8 {
+        self.entries
+            .binary_search_by_key(&color, |&(color, _)| color)
+            .map_or(0, |i| self.entries[i].1)
+    }
+}
@@ -0,0 +1,338 @@
+use super::quantize::Palette;
+use super::{Image, ImageError, Storage};
+use crate::cache::{self, DmaBuffer, CACHE_LINE_SIZE};
+use crate::sys::{self, ClutPixelFormat, MipmapLevel, TexturePixelFormat};
+use crate::vram_alloc::{get_vram_allocator, VramMemChunk};
+use alloc::{vec, vec::Vec};
+use core::ffi::c_void;
+use core::{ptr, slice};
+
+/// The largest width or height of a texture the GE can sample.
+pub const MAX_TEXTURE_SIZE: u32 = 512;
+
+/// The bits per pixel of the formats a `Texture` can be converted to.
+fn bits_per_pixel(format: TexturePixelFormat) -> Option<u32> {
+    match format {
+        TexturePixelFormat::Psm8888 => Some(32),
+        TexturePixelFormat::Psm5650 | TexturePixelFormat::Psm5551 | TexturePixelFormat::Psm4444 => {
+            Some(16)
+        }
+        TexturePixelFormat::PsmT8 => Some(8),
+        TexturePixelFormat::PsmT4 => Some(4),
+        _ => None,
+    }
+}
+
+/// Convert a `0xAABBGGRR` pixel to a 16-bit format.
+fn to_16_bit(format: TexturePixelFormat, pixel: u32) -> u16 {
+    let [r, g, b, a] = pixel.to_le_bytes();
+    let (r, g, b, a) = (r as u16, g as u16, b as u16, a as u16);
+
+    match format {
+        TexturePixelFormat::Psm5650 => r >> 3 | (g >> 2) << 5 | (b >> 3) << 11,
+        TexturePixelFormat::Psm5551 => r >> 3 | (g >> 3) << 5 | (b >> 3) << 10 | (a >> 7) << 15,
+        _ => r >> 4 | (g >> 4) << 4 | (b >> 4) << 8 | (a >> 4) << 12,
+    }
+}
+
+/// Rearrange rows of `row_bytes` into blocks of 16 bytes by 8 rows, each
+/// stored whole, in the order the GE reads a swizzled texture.
+fn swizzle(linear: &[u8], row_bytes: usize) -> Vec<u8> {
+    let mut swizzled = vec![0; linear.len()];
+    let mut blocks = swizzled.chunks_mut(16);
+
+    for rows in linear.chunks(row_bytes * 8) {
+        for x in (0..row_bytes).step_by(16) {
+            for y in 0..8 {
+                let start = y * row_bytes + x;
+                blocks
+                    .next()
+                    .unwrap()
+                    .copy_from_slice(&rows[start..start + 16]);
+            }
+        }
+    }
+
+    swizzled
+}
+
+/// Options for converting an `Image` into a `Texture`, in the style of
+/// `OpenOptions`.
+///
+/// ```no_run
+/// use psp::image::{png, Storage, TextureOptions};
+/// use psp::sys::TexturePixelFormat;
+///
+/// let bytes = psp::fs::read("ms0:/PSP/GAME/demo/font.png").unwrap();
+/// let image = png::decode(&bytes).unwrap();
+/// let texture = TextureOptions::new(TexturePixelFormat::PsmT8)
+///     .swizzle(true)
+///     .storage(Storage::Vram)
+///     .convert(&image)
+///     .unwrap();
+///
+/// unsafe { texture.bind() };
+/// ```
+#[derive(Debug, Clone)]
+pub struct TextureOptions {
+    format: TexturePixelFormat,
+    swizzle: bool,
+    storage: Storage,
+}
+
+impl TextureOptions {
+    /// Options for an unswizzled texture in main memory.
+    pub fn new(format: TexturePixelFormat) -> Self {
+        Self {
+            format,
+            swizzle: false,
+            storage: Storage::Ram,
+        }
+    }
+
+    /// Swizzle the pixels, which the GE reads faster.
+    pub fn swizzle(&mut self, swizzle: bool) -> &mut Self {
+        self.swizzle = swizzle;
+        self
+    }
+
+    /// Keep the texture in main memory or VRAM.
+    pub fn storage(&mut self, storage: Storage) -> &mut Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Convert an image into a texture with these options.
+    ///
+    /// Returns `ImageError::Unsupported` if the format is not `Psm8888`,
+    /// `Psm5650`, `Psm5551`, `Psm4444`, `PsmT8` or `PsmT4`, or if the image is
+    /// larger than `MAX_TEXTURE_SIZE`.
+    pub fn convert(&self, image: &Image) -> Result<Texture, ImageError> {
+        Texture::new(image, self)
+    }
+}
+
+enum Data {
+    Ram(DmaBuffer<u8>),
+    Vram(VramMemChunk),
+}
+
+/// An image converted for the GE: in any of the common texture formats, with
+/// power-of-two dimensions, and ready to pass to `sceGuTexImage`.
+///
+/// The image is in the top left corner, and the padding to the right and
+/// below it is transparent black. Scale texture coordinates by `width` over
+/// `texture_width`, and `height` over `texture_height`, to draw only the
+/// image, e.g. with `sceGuTexScale`.
+///
+/// `PsmT4` and `PsmT8` textures come with a `Psm8888` CLUT. It holds every
+/// color of the image if there are few enough, or a palette chosen by median
+/// cut if not. If the texture has padding, entry 0 is kept for it, as
+/// transparent black.
+pub struct Texture {
+    width: u32,
+    height: u32,
+    texture_width: u32,
+    texture_height: u32,
+    buffer_width: u32,
+    format: TexturePixelFormat,
+    swizzled: bool,
+    data: Data,
+    len: usize,
+    clut: Option<DmaBuffer<u32>>,
+}
+
+impl Texture {
+    fn new(image: &Image, options: &TextureOptions) -> Result<Self, ImageError> {
+        let bits = bits_per_pixel(options.format).ok_or(ImageError::Unsupported)?;
+        let (width, height) = (image.width(), image.height());
+
+        if width > MAX_TEXTURE_SIZE || height > MAX_TEXTURE_SIZE {
+            return Err(ImageError::Unsupported);
+        }
+
+        let texture_width = width.next_power_of_two();
+        let texture_height = height.next_power_of_two();
+
+        // Rows of at least 16 bytes, and whole blocks when swizzled.
+        let buffer_width = core::cmp::max(texture_width, 128 / bits);
+        let rows = if options.swizzle {
+            core::cmp::max(texture_height, 8)
+        } else {
+            texture_height
+        };
+
+        let row_bytes = (buffer_width * bits / 8) as usize;
+        let mut linear = vec![0u8; row_bytes * rows as usize];
+
+        // The padding is entry 0, so the image's colors start after it.
+        let padded = width < buffer_width || height < rows;
+        let first = padded as u8;
+
+        let palette = match bits {
+            4 => Some(Palette::new(image, 16 - first as usize)),
+            8 => Some(Palette::new(image, 256 - first as usize)),
+            _ => None,
+        };
+
+        for y in 0..height {
+            let row = &mut linear[y as usize * row_bytes..(y as usize + 1) * row_bytes];
+            let pixels = image.row(y);
+
+            match (bits, &palette) {
+                (32, _) => {
+                    for (out, pixel) in row.chunks_mut(4).zip(pixels) {
+                        out.copy_from_slice(&pixel.to_le_bytes());
+                    }
+                }
+                (16, _) => {
+                    for (out, &pixel) in row.chunks_mut(2).zip(pixels) {
+                        out.copy_from_slice(&to_16_bit(options.format, pixel).to_le_bytes());
+                    }
+                }
+                (8, Some(palette)) => {
+                    for (out, &pixel) in row.iter_mut().zip(pixels) {
+                        *out = palette.entry(pixel) + first;
+                    }
+                }
+                (_, Some(palette)) => {
+                    // The first of each pair of pixels is in the low nibble.
+                    for (out, pair) in row.iter_mut().zip(pixels.chunks(2)) {
+                        let high = pair.get(1).map_or(0, |&pixel| palette.entry(pixel) + first);
+                        *out = (palette.entry(pair[0]) + first) | high << 4;
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        if options.swizzle {
+            linear = swizzle(&linear, row_bytes);
+        }
+
+        let len = linear.len();
+
+        let data = match options.storage {
+            Storage::Ram => {
+                let mut buffer = DmaBuffer::from_elem(0, len);
+                buffer.copy_from_slice(&linear);
+                buffer.as_device_ptr();
+                Data::Ram(buffer)
+            }
+            Storage::Vram => {
+                let size = (len + CACHE_LINE_SIZE - 1) & !(CACHE_LINE_SIZE - 1);
+                let chunk =
+                    get_vram_allocator().alloc_aligned(size as u32, CACHE_LINE_SIZE as u32)?;
+
+                unsafe {
+                    let vram = chunk.as_mut_ptr_direct_to_vram();
+                    ptr::copy_nonoverlapping(linear.as_ptr(), vram, len);
+                    cache::writeback_invalidate(slice::from_raw_parts(vram, size));
+                }
+
+                Data::Vram(chunk)
+            }
+        };
+
+        let clut = palette.map(|palette| {
+            let mut clut = DmaBuffer::from_elem(0, 1 << bits);
+            let start = first as usize;
+            clut[start..start + palette.colors.len()].copy_from_slice(&palette.colors);
+            clut.as_device_ptr();
+            clut
+        });
+
+        Ok(Self {
+            width,
+            height,
+            texture_width,
+            texture_height,
+            buffer_width,
+            format: options.format,
+            swizzled: options.swizzle,
+            data,
+            len,
+            clut,
+        })
+    }
+
+    /// The width of the image.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height of the image.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The width to pass to `sceGuTexImage`: the image's width, rounded up to
+    /// a power of two.
+    pub fn texture_width(&self) -> u32 {
+        self.texture_width
+    }
+
+    /// The height to pass to `sceGuTexImage`: the image's height, rounded up
+    /// to a power of two.
+    pub fn texture_height(&self) -> u32 {
+        self.texture_height
+    }
+
+    /// The number of pixels from the start of one row to the next. This is
+    /// `texture_width`, unless that is too narrow for a row of 16 bytes.
+    pub fn buffer_width(&self) -> u32 {
+        self.buffer_width
+    }
+
+    pub fn format(&self) -> TexturePixelFormat {
+        self.format
+    }
+
+    pub fn is_swizzled(&self) -> bool {
+        self.swizzled
+    }
+
+    pub fn storage(&self) -> Storage {
+        match self.data {
+            Data::Ram(_) => Storage::Ram,
+            Data::Vram(_) => Storage::Vram,
+        }
+    }
+
+    /// The pixels, as the GE reads them.
+    pub fn data(&self) -> &[u8] {
+        match &self.data {
+            Data::Ram(buffer) => buffer,
+            Data::Vram(chunk) => unsafe {
+                slice::from_raw_parts(chunk.as_mut_ptr_direct_to_vram(), self.len)
+            },
+        }
+    }
+
+    /// The CLUT of a `PsmT4` or `PsmT8` texture, with 16 or 256 entries.
+    pub fn clut(&self) -> Option<&[u32]> {
+        self.clut.as_deref()
+    }
+
+    /// Make this the current texture, loading its CLUT if it has one.
+    ///
+    /// # Safety
+    ///
+    /// This adds commands to the current display list. The texture must live
+    /// until the GE has finished drawing with it.
+    pub unsafe fn bind(&self) {
+        if let Some(clut) = &self.clut {
+            sys::sceGuClutMode(ClutPixelFormat::Psm8888, 0, 0xff, 0);
+            sys::sceGuClutLoad(clut.len() as i32 / 8, clut.as_ptr() as *const c_void);
+        }
+
+        sys::sceGuTexMode(self.format, 0, 0, self.swizzled as i32);
+        sys::sceGuTexImage(
+            MipmapLevel::None,
+            self.texture_width as i32,
+            self.texture_height as i32,
+            self.buffer_width as i32,
+            self.data().as_ptr() as *const c_void,
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! TGA decoding.
+//!
+//! Color-mapped, true-color and grayscale images are handled, with or
+//! without run-length encoding, in any of the usual pixel depths and either
+//! vertical or horizontal order. 16-bit pixels are opaque unless the header
+//! gives them an alpha bit.
+
+use super::buffer::stride;
+use super::{Image, ImageError, Storage, MAX_DIMENSION};
+use alloc::vec::Vec;
+
+fn le_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+/// The fields of the 18-byte header used in decoding.
+struct Header {
+    id_len: usize,
+    map_type: u8,
+    image_type: u8,
+    map_first: usize,
+    map_len: usize,
+    map_depth: u8,
+    width: u32,
+    height: u32,
+    depth: u8,
+    alpha_bits: u8,
+    right_to_left: bool,
+    top_to_bottom: bool,
+}
+
+impl Header {
+    fn read(data: &[u8]) -> Result<Self, ImageError> {
+        if data.len() < 18 {
+            return Err(ImageError::InvalidData);
+        }
+
+        let header = Self {
+            id_len: data[0] as usize,
+            map_type: data[1],
+            image_type: data[2],
+            map_first: le_u16(data, 3) as usize,
+            map_len: le_u16(data, 5) as usize,
+            map_depth: data[7],
+            width: le_u16(data, 12) as u32,
+            height: le_u16(data, 14) as u32,
+            depth: data[16],
+            alpha_bits: data[17] & 0xf,
+            right_to_left: data[17] & 0x10 != 0,
+            top_to_bottom: data[17] & 0x20 != 0,
+        };
+
+        // TGA files have no signature, so check what can be.
+        let valid = match header.image_type & !8 {
+            1 => header.map_type == 1 && [8, 16].contains(&header.depth),
+            2 => [15, 16, 24, 32].contains(&header.depth),
+            3 => [8, 16].contains(&header.depth),
+            _ => false,
+        };
+
+        let map_valid = match header.map_type {
+            0 => true,
+            1 => [15, 16, 24, 32].contains(&header.map_depth),
+            _ => false,
+        };
+
+        if !valid || !map_valid || header.image_type > 11 {
+            return Err(ImageError::InvalidData);
+        }
+
+        if header.width == 0 || header.height == 0 {
+            return Err(ImageError::InvalidData);
+        }
+
+        if header.width > MAX_DIMENSION || header.height > MAX_DIMENSION {
+            return Err(ImageError::Unsupported);
+        }
+
+        Ok(header)
+    }
+}
+
+/// Convert a 15, 16, 24 or 32-bit little-endian BGR(A) pixel. 16-bit pixels
+/// only use their top bit for alpha with `alpha`.
+fn color(bytes: &[u8], alpha: bool) -> u32 {
+    match bytes.len() {
+        2 => {
+            let pixel = le_u16(bytes, 0) as u32;
+            let expand = |value: u32| (value & 0x1f) << 3 | (value & 0x1f) >> 2;
+
+            let opaque = !alpha || pixel & 0x8000 != 0;
+            let a = if opaque { 0xff } else { 0 };
+
+            a << 24 | expand(pixel) << 16 | expand(pixel >> 5) << 8 | expand(pixel >> 10)
+        }
+        3 => 0xff00_0000 | (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32,
+        _ => {
+            let [b, g, r, a] = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            u32::from_le_bytes([r, g, b, a])
+        }
+    }
+}
+
+/// The width and height of a TGA, read from its header.
+pub fn dimensions(data: &[u8]) -> Result<(u32, u32), ImageError> {
+    Header::read(data).map(|header| (header.width, header.height))
+}
+
+/// Decode a TGA into main memory.
+pub fn decode(data: &[u8]) -> Result<Image, ImageError> {
+    decode_with(data, Storage::Ram)
+}
+
+/// Decode a TGA into main memory or VRAM.
+pub fn decode_with(data: &[u8], storage: Storage) -> Result<Image, ImageError> {
+    let header = Header::read(data)?;
+    let mut pos = 18 + header.id_len;
+
+    let mut palette = Vec::new();
+
+    if header.map_type == 1 {
+        let entry_size = (header.map_depth as usize + 7) / 8;
+        let map = data
+            .get(pos..pos + header.map_len * entry_size)
+            .ok_or(ImageError::InvalidData)?;
+
+        let alpha = header.map_depth == 32 || header.alpha_bits > 0;
+        palette = map
+            .chunks(entry_size)
+            .map(|entry| color(entry, alpha))
+            .collect();
+        pos += map.len();
+    }
+
+    let pixel_size = (header.depth as usize + 7) / 8;
+    let count = header.width as usize * header.height as usize;
+
+    let alpha = header.depth == 32 || header.alpha_bits > 0;
+    let convert = |bytes: &[u8]| -> Result<u32, ImageError> {
+        match header.image_type & !8 {
+            1 => {
+                let index = if pixel_size == 2 {
+                    le_u16(bytes, 0) as usize
+                } else {
+                    bytes[0] as usize
+                };
+
+                index
+                    .checked_sub(header.map_first)
+                    .and_then(|index| palette.get(index))
+                    .copied()
+                    .ok_or(ImageError::InvalidData)
+            }
+            2 => Ok(color(bytes, alpha)),
+            _ => {
+                let gray = bytes[0] as u32 * 0x01_0101;
+                let a = if pixel_size == 2 {
+                    bytes[1] as u32
+                } else {
+                    0xff
+                };
+                Ok(a << 24 | gray)
+            }
+        }
+    };
+
+    // Pixels in the order they are stored. Only grown as far as the data
+    // goes, however many pixels the header claims.
+    let mut pixels = Vec::new();
+
+    if header.image_type & 8 == 0 {
+        let bytes = data
+            .get(pos..pos + count * pixel_size)
+            .ok_or(ImageError::InvalidData)?;
+
+        pixels.reserve(count);
+
+        for bytes in bytes.chunks(pixel_size) {
+            pixels.push(convert(bytes)?);
+        }
+    } else {
+        // Each packet is a run of one pixel repeated, or of raw pixels.
+        while pixels.len() < count {
+            let packet = *data.get(pos).ok_or(ImageError::InvalidData)?;
+            let len = (packet & 0x7f) as usize + 1;
+            let run = packet & 0x80 != 0;
+            pos += 1;
+
+            let stored = if run { 1 } else { len };
+            let bytes = data
+                .get(pos..pos + stored * pixel_size)
+                .ok_or(ImageError::InvalidData)?;
+            pos += bytes.len();
+
+            // Runs may cross rows, but not the end of the image.
+            let len = core::cmp::min(len, count - pixels.len());
+
+            if run {
+                let pixel = convert(bytes)?;
+                pixels.extend(core::iter::repeat(pixel).take(len));
+            } else {
+                for bytes in bytes.chunks(pixel_size).take(len) {
+                    pixels.push(convert(bytes)?);
+                }
+            }
+        }
+    }
+
+    let (width, height) = (header.width, header.height);
+    let mut image = Image::new(width, height, stride(width), storage)?;
+    let image_stride = image.stride() as usize;
+    let out = image.pixels_mut();
+
+    for (y, row) in pixels.chunks(width as usize).enumerate() {
+        // Rows are stored bottom to top unless the header says otherwise.
+        let y = if header.top_to_bottom {
+            y
+        } else {
+            height as usize - 1 - y
+        };
+
+        let out = &mut out[y * image_stride..y * image_stride + width as usize];
+        out.copy_from_slice(row);
+
+        if header.right_to_left {
+            out.reverse();
+        }
+    }
+
+    Ok(image)
+}
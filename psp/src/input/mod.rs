@@ -0,0 +1,456 @@
+//! Controller and headphone remote input.
+//!
+//! `Controller` reads the buttons, analog stick and remote once per frame,
+//! and tracks which buttons were pressed or released since the last frame.
+//! It can also read from a `Replay` of input saved by a `Recorder`, to
+//! reproduce bugs or drive tests without a human.
+//!
+//! ```no_run
+//! use psp::input::{Controller, Repeat};
+//! use psp::sys::CtrlButtons;
+//! use core::time::Duration;
+//!
+//! let mut controller = Controller::new();
+//! controller.set_repeat(Some(Repeat::new(Duration::from_millis(400), Duration::from_millis(80))));
+//!
+//! loop {
+//!     controller.update();
+//!
+//!     if controller.buttons().repeated(CtrlButtons::DOWN) {
+//!         // Move a menu cursor...
+//!     }
+//!
+//!     let stick = controller.analog();
+//!     // Move the player by (stick.x, stick.y)...
+//! }
+//! ```
+
+use crate::sys::{CtrlButtons, HprmKey};
+use core::time::Duration;
+
+mod replay;
+mod source;
+
+pub use replay::*;
+pub use source::*;
+
+/// One reading of the controller and headphone remote.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Sample {
+    /// When the sample was taken, in microseconds. This wraps around.
+    pub timestamp: u32,
+    /// The buttons held down.
+    pub buttons: CtrlButtons,
+    /// Buttons pressed since the previous sample, even if already released.
+    /// Only set when latch reads are enabled.
+    pub made: CtrlButtons,
+    /// Buttons released since the previous sample, even if pressed again.
+    /// Only set when latch reads are enabled.
+    pub broken: CtrlButtons,
+    /// The raw analog stick position, with 0 at the top left.
+    pub lx: u8,
+    pub ly: u8,
+    /// The headphone remote keys held down.
+    pub remote: HprmKey,
+}
+
+/// Button sets whose changes can be tracked.
+pub trait ButtonSet: Copy + PartialEq {
+    fn to_bits(self) -> u32;
+    fn from_bits_truncated(bits: u32) -> Self;
+}
+
+impl ButtonSet for CtrlButtons {
+    fn to_bits(self) -> u32 {
+        self.bits()
+    }
+
+    fn from_bits_truncated(bits: u32) -> Self {
+        Self::from_bits_truncate(bits)
+    }
+}
+
+impl ButtonSet for HprmKey {
+    fn to_bits(self) -> u32 {
+        self.bits()
+    }
+
+    fn from_bits_truncated(bits: u32) -> Self {
+        Self::from_bits_truncate(bits)
+    }
+}
+
+/// Key repeat timing, for menus and text entry.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Repeat {
+    /// How long a button must be held before it starts repeating.
+    pub delay: Duration,
+    /// The time between repeats.
+    pub rate: Duration,
+}
+
+impl Repeat {
+    pub fn new(delay: Duration, rate: Duration) -> Self {
+        Self { delay, rate }
+    }
+}
+
+/// The state of a set of buttons this frame.
+#[derive(Debug, Copy, Clone)]
+pub struct ButtonState<T> {
+    held: u32,
+    pressed: u32,
+    released: u32,
+    repeated: u32,
+    /// When each held button next repeats, by bit index.
+    next_repeat: [u32; 32],
+    _marker: core::marker::PhantomData<T>,
+}
+
+impl<T: ButtonSet> ButtonState<T> {
+    fn new() -> Self {
+        Self {
+            held: 0,
+            pressed: 0,
+            released: 0,
+            repeated: 0,
+            next_repeat: [0; 32],
+            _marker: core::marker::PhantomData,
+        }
+    }
+
+    fn update(&mut self, now: u32, held: u32, made: u32, broken: u32, repeat: Option<Repeat>) {
+        let prev = self.held;
+
+        self.pressed = (held & !prev) | made;
+        self.released = (!held & prev) | broken;
+        self.held = held;
+        self.repeated = self.pressed;
+
+        let repeat = match repeat {
+            Some(repeat) => repeat,
+            None => return,
+        };
+
+        let delay = crate::time::as_micros_u32(repeat.delay);
+        let rate = core::cmp::max(crate::time::as_micros_u32(repeat.rate), 1);
+
+        for bit in 0..32 {
+            let mask = 1 << bit;
+
+            if self.pressed & mask != 0 {
+                self.next_repeat[bit] = now.wrapping_add(delay);
+            } else if held & mask != 0 && now.wrapping_sub(self.next_repeat[bit]) as i32 >= 0 {
+                self.repeated |= mask;
+                self.next_repeat[bit] = self.next_repeat[bit].wrapping_add(rate);
+
+                // Don't fire a burst of repeats after a long frame.
+                if now.wrapping_sub(self.next_repeat[bit]) as i32 >= 0 {
+                    self.next_repeat[bit] = now.wrapping_add(rate);
+                }
+            }
+        }
+    }
+
+    /// Whether all of `buttons` are held down.
+    pub fn is_held(&self, buttons: T) -> bool {
+        let bits = buttons.to_bits();
+        self.held & bits == bits
+    }
+
+    /// Whether any of `buttons` was pressed this frame.
+    pub fn just_pressed(&self, buttons: T) -> bool {
+        self.pressed & buttons.to_bits() != 0
+    }
+
+    /// Whether any of `buttons` was released this frame.
+    pub fn just_released(&self, buttons: T) -> bool {
+        self.released & buttons.to_bits() != 0
+    }
+
+    /// Whether any of `buttons` was pressed, or repeated while held, this
+    /// frame. This is the same as `just_pressed` when repeat is disabled.
+    pub fn repeated(&self, buttons: T) -> bool {
+        self.repeated & buttons.to_bits() != 0
+    }
+
+    /// All buttons held down.
+    pub fn held(&self) -> T {
+        T::from_bits_truncated(self.held)
+    }
+
+    /// All buttons pressed this frame.
+    pub fn pressed(&self) -> T {
+        T::from_bits_truncated(self.pressed)
+    }
+
+    /// All buttons released this frame.
+    pub fn released(&self) -> T {
+        T::from_bits_truncated(self.released)
+    }
+}
+
+/// A button, either on the unit or on the headphone remote.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Button {
+    Ctrl(CtrlButtons),
+    Remote(HprmKey),
+}
+
+/// What happened to a button this frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ButtonEventKind {
+    Pressed,
+    Repeated,
+    Released,
+}
+
+/// A change to a single button.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ButtonEvent {
+    pub button: Button,
+    pub kind: ButtonEventKind,
+}
+
+/// Analog stick calibration and deadzone.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AnalogConfig {
+    /// The raw position the stick rests at.
+    pub center: (u8, u8),
+    /// The radius around the center, from 0 to 1, which reads as zero. Sticks
+    /// rarely rest exactly at the center, and drift a little.
+    pub deadzone: f32,
+}
+
+impl Default for AnalogConfig {
+    fn default() -> Self {
+        Self {
+            center: (128, 128),
+            deadzone: 0.2,
+        }
+    }
+}
+
+/// The analog stick position, from -1 to 1 on each axis, with positive `y`
+/// pointing down.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Analog {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Map a raw axis to -1..=1, treating `center` as 0.
+fn normalize_axis(raw: u8, center: u8) -> f32 {
+    let offset = raw as f32 - center as f32;
+
+    let range = if offset < 0.0 {
+        center as f32
+    } else {
+        255.0 - center as f32
+    };
+
+    if range <= 0.0 {
+        0.0
+    } else {
+        offset / range
+    }
+}
+
+impl AnalogConfig {
+    /// Apply calibration and a radial deadzone to a raw position.
+    ///
+    /// The deadzone is circular rather than per axis, so diagonals aren't
+    /// snapped to the axes. Outside it, the magnitude is rescaled to start
+    /// from zero at its edge, so there is no jump.
+    pub fn apply(&self, lx: u8, ly: u8) -> Analog {
+        let x = normalize_axis(lx, self.center.0);
+        let y = normalize_axis(ly, self.center.1);
+        let magnitude = unsafe { core::intrinsics::sqrtf32(x * x + y * y) };
+
+        if magnitude <= self.deadzone || self.deadzone >= 1.0 {
+            return Analog::default();
+        }
+
+        let scaled = (magnitude - self.deadzone) / (1.0 - self.deadzone);
+        let scale = (if scaled > 1.0 { 1.0 } else { scaled }) / magnitude;
+
+        Analog {
+            x: x * scale,
+            y: y * scale,
+        }
+    }
+}
+
+/// Reads input once per frame, tracking button changes.
+///
+/// Samples come from the real controller by default, or from any other
+/// `InputSource`, such as a `Replay`.
+pub struct Controller<S = Live> {
+    source: S,
+    mode: ReadMode,
+    latch: bool,
+    repeat: Option<Repeat>,
+    analog_config: AnalogConfig,
+    sample: Sample,
+    buttons: ButtonState<CtrlButtons>,
+    remote: ButtonState<HprmKey>,
+}
+
+impl Controller {
+    /// Read the real controller, with no buttons held yet.
+    pub fn new() -> Self {
+        Self::with_source(Live::new())
+    }
+}
+
+impl<S: InputSource> Controller<S> {
+    /// Read samples from `source`, with no buttons held yet.
+    pub fn with_source(source: S) -> Self {
+        Self {
+            source,
+            mode: ReadMode::Peek,
+            latch: false,
+            repeat: None,
+            analog_config: AnalogConfig::default(),
+            sample: Sample::default(),
+            buttons: ButtonState::new(),
+            remote: ButtonState::new(),
+        }
+    }
+
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    pub fn source_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    pub fn into_source(self) -> S {
+        self.source
+    }
+
+    pub fn set_read_mode(&mut self, mode: ReadMode) {
+        self.mode = mode;
+    }
+
+    /// Use `sceCtrlReadLatch` to also catch presses and releases which
+    /// happened between frames. Without this, a button tapped for less than a
+    /// frame can be missed when the game runs slower than the sampling rate.
+    pub fn set_latch(&mut self, enabled: bool) {
+        self.latch = enabled;
+    }
+
+    /// Enable or disable key repeat, for both the unit and remote buttons.
+    pub fn set_repeat(&mut self, repeat: Option<Repeat>) {
+        self.repeat = repeat;
+    }
+
+    pub fn set_analog_config(&mut self, config: AnalogConfig) {
+        self.analog_config = config;
+    }
+
+    pub fn analog_config(&self) -> AnalogConfig {
+        self.analog_config
+    }
+
+    /// Take the current stick position as its center. Call this while the
+    /// stick is at rest.
+    pub fn calibrate(&mut self) {
+        self.analog_config.center = (self.sample.lx, self.sample.ly);
+    }
+
+    /// Read the controller and remote.
+    pub fn update(&mut self) {
+        let sample = self.read();
+        self.update_from(sample);
+    }
+
+    /// Take a sample from the source without updating any state.
+    pub fn read(&mut self) -> Sample {
+        self.source.read(self.mode, self.latch)
+    }
+
+    /// Update the state from a sample, which may come from somewhere other
+    /// than `read`.
+    pub fn update_from(&mut self, sample: Sample) {
+        let now = sample.timestamp;
+
+        self.buttons.update(
+            now,
+            sample.buttons.bits(),
+            sample.made.bits(),
+            sample.broken.bits(),
+            self.repeat,
+        );
+
+        self.remote.update(now, sample.remote.bits(), 0, 0, self.repeat);
+        self.sample = sample;
+    }
+
+    /// The sample from the last update.
+    pub fn sample(&self) -> &Sample {
+        &self.sample
+    }
+
+    /// The unit's buttons.
+    pub fn buttons(&self) -> &ButtonState<CtrlButtons> {
+        &self.buttons
+    }
+
+    /// The headphone remote's keys.
+    pub fn remote(&self) -> &ButtonState<HprmKey> {
+        &self.remote
+    }
+
+    /// The analog stick, calibrated and with the deadzone applied.
+    pub fn analog(&self) -> Analog {
+        self.analog_config.apply(self.sample.lx, self.sample.ly)
+    }
+
+    /// The raw analog stick position.
+    pub fn raw_analog(&self) -> (u8, u8) {
+        (self.sample.lx, self.sample.ly)
+    }
+
+    /// Every press, repeat and release this frame, from the unit's buttons and
+    /// then the remote's keys.
+    pub fn events(&self) -> impl Iterator<Item = ButtonEvent> + '_ {
+        fn changes<'a, T: ButtonSet>(
+            state: &'a ButtonState<T>,
+            wrap: fn(T) -> Button,
+        ) -> impl Iterator<Item = ButtonEvent> + 'a {
+            (0..32).flat_map(move |bit| {
+                let mask = 1 << bit;
+                let button = wrap(T::from_bits_truncated(mask));
+
+                let kind = if state.pressed & mask != 0 {
+                    Some(ButtonEventKind::Pressed)
+                } else if state.repeated & mask != 0 {
+                    Some(ButtonEventKind::Repeated)
+                } else {
+                    None
+                };
+
+                let released = if state.released & mask != 0 {
+                    Some(ButtonEventKind::Released)
+                } else {
+                    None
+                };
+
+                // A latched tap can be both pressed and released in one frame.
+                kind.into_iter()
+                    .chain(released)
+                    .map(move |kind| ButtonEvent { button, kind })
+            })
+        }
+
+        changes(&self.buttons, Button::Ctrl).chain(changes(&self.remote, Button::Remote))
+    }
+}
+
+impl Default for Controller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
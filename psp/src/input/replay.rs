@@ -0,0 +1,367 @@
+use super::{InputSource, Live, ReadMode, Sample};
+use crate::io;
+use crate::sys::{self, CtrlButtons, HprmKey};
+use alloc::vec::Vec;
+
+/// The start of a recording file, followed by a format version.
+const MAGIC: &[u8; 7] = b"PSPCTRL";
+const VERSION: u8 = 1;
+
+// Which fields changed since the previous sample. Unchanged fields are left
+// out, so a frame where nothing happened takes two bytes.
+const BUTTONS: u8 = 1;
+const MADE: u8 = 2;
+const BROKEN: u8 = 4;
+const LX: u8 = 8;
+const LY: u8 = 16;
+const REMOTE: u8 = 32;
+
+fn write_varint(data: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        data.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    data.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> io::Result<u32> {
+    let mut value = 0u32;
+
+    for shift in (0..35).step_by(7) {
+        let byte = *data.get(*pos).ok_or(io::Error::UnexpectedEof)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u32) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(io::Error::InvalidInput)
+}
+
+fn read_u8(data: &[u8], pos: &mut usize) -> io::Result<u8> {
+    let byte = *data.get(*pos).ok_or(io::Error::UnexpectedEof)?;
+    *pos += 1;
+
+    Ok(byte)
+}
+
+/// Decode the sample at `pos`, which follows `prev`.
+fn decode(data: &[u8], pos: &mut usize, prev: &Sample) -> io::Result<Sample> {
+    let changed = read_u8(data, pos)?;
+    let mut sample = *prev;
+
+    sample.timestamp = prev.timestamp.wrapping_add(read_varint(data, pos)?);
+
+    // Latched changes only apply to one sample.
+    sample.made = CtrlButtons::empty();
+    sample.broken = CtrlButtons::empty();
+
+    if changed & BUTTONS != 0 {
+        sample.buttons = CtrlButtons::from_bits_truncate(read_varint(data, pos)?);
+    }
+
+    if changed & MADE != 0 {
+        sample.made = CtrlButtons::from_bits_truncate(read_varint(data, pos)?);
+    }
+
+    if changed & BROKEN != 0 {
+        sample.broken = CtrlButtons::from_bits_truncate(read_varint(data, pos)?);
+    }
+
+    if changed & LX != 0 {
+        sample.lx = read_u8(data, pos)?;
+    }
+
+    if changed & LY != 0 {
+        sample.ly = read_u8(data, pos)?;
+    }
+
+    if changed & REMOTE != 0 {
+        sample.remote = HprmKey::from_bits_truncate(read_varint(data, pos)?);
+    }
+
+    Ok(sample)
+}
+
+/// A sequence of samples, stored compactly.
+///
+/// ```no_run
+/// use psp::input::{Controller, Live, Recorder};
+///
+/// let mut controller = Controller::with_source(Recorder::new(Live::new()));
+///
+/// for _ in 0..600 {
+///     controller.update();
+///     // Run a frame...
+/// }
+///
+/// controller.source().recording().save("ms0:/PSP/GAME/MYGAME/input.rec").unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Recording {
+    data: Vec<u8>,
+    len: usize,
+    last: Sample,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        let mut data = Vec::with_capacity(4096);
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+
+        Self {
+            data,
+            len: 0,
+            last: Sample::default(),
+        }
+    }
+
+    /// Parse a recording, e.g. one read from a file.
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<Self> {
+        if data.len() < MAGIC.len() + 1 || &data[..MAGIC.len()] != MAGIC {
+            return Err(io::Error::InvalidInput);
+        }
+
+        if data[MAGIC.len()] != VERSION {
+            return Err(io::Error::InvalidInput);
+        }
+
+        // Check the whole recording up front, so a replay can't fail midway.
+        let mut pos = MAGIC.len() + 1;
+        let mut len = 0;
+        let mut last = Sample::default();
+
+        while pos < data.len() {
+            last = decode(&data, &mut pos, &last)?;
+            len += 1;
+        }
+
+        Ok(Self { data, len, last })
+    }
+
+    /// Load a recording saved with `save`.
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::from_bytes(crate::fs::read(path)?)
+    }
+
+    /// Write the recording to a file, replacing it if it exists. This can be
+    /// called while still recording, e.g. every few seconds, so the input up
+    /// to a crash is kept.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        crate::fs::write(path, &self.data)
+    }
+
+    /// Append a sample.
+    pub fn push(&mut self, sample: &Sample) {
+        let last = &self.last;
+        let mut changed = 0;
+
+        if sample.buttons != last.buttons {
+            changed |= BUTTONS;
+        }
+
+        if !sample.made.is_empty() {
+            changed |= MADE;
+        }
+
+        if !sample.broken.is_empty() {
+            changed |= BROKEN;
+        }
+
+        if sample.lx != last.lx {
+            changed |= LX;
+        }
+
+        if sample.ly != last.ly {
+            changed |= LY;
+        }
+
+        if sample.remote != last.remote {
+            changed |= REMOTE;
+        }
+
+        self.data.push(changed);
+        write_varint(&mut self.data, sample.timestamp.wrapping_sub(last.timestamp));
+
+        if changed & BUTTONS != 0 {
+            write_varint(&mut self.data, sample.buttons.bits());
+        }
+
+        if changed & MADE != 0 {
+            write_varint(&mut self.data, sample.made.bits());
+        }
+
+        if changed & BROKEN != 0 {
+            write_varint(&mut self.data, sample.broken.bits());
+        }
+
+        if changed & LX != 0 {
+            self.data.push(sample.lx);
+        }
+
+        if changed & LY != 0 {
+            self.data.push(sample.ly);
+        }
+
+        if changed & REMOTE != 0 {
+            write_varint(&mut self.data, sample.remote.bits());
+        }
+
+        self.last = *sample;
+        self.len += 1;
+    }
+
+    /// The number of samples.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The encoded recording, as written by `save`.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// An iterator over the samples.
+    pub fn iter(&self) -> impl Iterator<Item = Sample> + '_ {
+        let mut pos = MAGIC.len() + 1;
+        let mut last = Sample::default();
+
+        core::iter::from_fn(move || {
+            if pos >= self.data.len() {
+                return None;
+            }
+
+            // Recordings are checked when they are built.
+            last = decode(&self.data, &mut pos, &last).ok()?;
+            Some(last)
+        })
+    }
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An input source which records every sample read from another source.
+pub struct Recorder<S = Live> {
+    source: S,
+    recording: Recording,
+}
+
+impl<S: InputSource> Recorder<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            recording: Recording::new(),
+        }
+    }
+
+    /// The samples recorded so far.
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    /// Stop recording, returning the source and the recording.
+    pub fn into_parts(self) -> (S, Recording) {
+        (self.source, self.recording)
+    }
+}
+
+impl<S: InputSource> InputSource for Recorder<S> {
+    fn read(&mut self, mode: ReadMode, latch: bool) -> Sample {
+        let sample = self.source.read(mode, latch);
+        self.recording.push(&sample);
+
+        sample
+    }
+}
+
+/// An input source which plays back a recording, in place of live input.
+///
+/// Each `read` returns the next recorded sample, timestamps included, so code
+/// which only depends on its input and sample timestamps behaves exactly as
+/// it did when recorded. After the end, the stick rests where it was and no
+/// buttons are held.
+///
+/// In `ReadMode::Read`, each read waits for a vblank, as reading the real
+/// controller would.
+pub struct Replay {
+    recording: Recording,
+    pos: usize,
+    position: usize,
+    last: Sample,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            pos: MAGIC.len() + 1,
+            position: 0,
+            last: Sample::default(),
+        }
+    }
+
+    /// Load a recording saved with `Recording::save`.
+    pub fn load(path: &str) -> io::Result<Self> {
+        Recording::load(path).map(Self::new)
+    }
+
+    /// How many samples have been played.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Whether every sample has been played.
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.recording.len()
+    }
+
+    /// Start again from the first sample.
+    pub fn rewind(&mut self) {
+        self.pos = MAGIC.len() + 1;
+        self.position = 0;
+        self.last = Sample::default();
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+}
+
+impl InputSource for Replay {
+    fn read(&mut self, mode: ReadMode, _latch: bool) -> Sample {
+        if mode == ReadMode::Read {
+            unsafe {
+                sys::sceDisplayWaitVblankStart();
+            }
+        }
+
+        if self.is_finished() {
+            return Sample {
+                timestamp: self.last.timestamp,
+                lx: self.last.lx,
+                ly: self.last.ly,
+                ..Sample::default()
+            };
+        }
+
+        // Recordings are checked when they are built.
+        if let Ok(sample) = decode(&self.recording.data, &mut self.pos, &self.last) {
+            self.last = sample;
+        }
+
+        self.position += 1;
+        self.last
+    }
+}
@@ -0,0 +1,80 @@
+use super::Sample;
+use crate::sys::{self, CtrlButtons, CtrlMode, HprmKey, SceCtrlData, SceCtrlLatch};
+
+/// How a source's `read` should behave.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReadMode {
+    /// Return the latest sample immediately.
+    Peek,
+    /// Wait for the next sample, which are taken once per vblank. This can
+    /// pace a game loop instead of `sceDisplayWaitVblankStart`.
+    Read,
+}
+
+/// Where a `Controller` gets its samples from.
+pub trait InputSource {
+    /// Take the next sample. When `latch` is true, `Sample::made` and
+    /// `Sample::broken` should be filled in.
+    fn read(&mut self, mode: ReadMode, latch: bool) -> Sample;
+}
+
+impl<S: InputSource + ?Sized> InputSource for &mut S {
+    fn read(&mut self, mode: ReadMode, latch: bool) -> Sample {
+        (**self).read(mode, latch)
+    }
+}
+
+/// The real controller and headphone remote.
+#[derive(Debug)]
+pub struct Live {
+    _private: (),
+}
+
+impl Live {
+    /// Set up analog sampling once per vblank.
+    pub fn new() -> Self {
+        unsafe {
+            sys::sceCtrlSetSamplingCycle(0);
+            sys::sceCtrlSetSamplingMode(CtrlMode::Analog);
+        }
+
+        Self { _private: () }
+    }
+}
+
+impl Default for Live {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputSource for Live {
+    fn read(&mut self, mode: ReadMode, latch: bool) -> Sample {
+        let mut data = SceCtrlData::default();
+        let mut latch_data = SceCtrlLatch::default();
+        let mut remote = HprmKey::empty();
+
+        unsafe {
+            match mode {
+                ReadMode::Peek => sys::sceCtrlPeekBufferPositive(&mut data, 1),
+                ReadMode::Read => sys::sceCtrlReadBufferPositive(&mut data, 1),
+            };
+
+            if latch {
+                sys::sceCtrlReadLatch(&mut latch_data);
+            }
+
+            sys::sceHprmPeekCurrentKey(&mut remote);
+        }
+
+        Sample {
+            timestamp: data.timestamp,
+            buttons: data.buttons,
+            made: CtrlButtons::from_bits_truncate(latch_data.ui_make),
+            broken: CtrlButtons::from_bits_truncate(latch_data.ui_break),
+            lx: data.lx,
+            ly: data.ly,
+            remote,
+        }
+    }
+}
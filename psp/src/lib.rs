@@ -33,11 +33,26 @@ pub mod debug;
 #[macro_use] mod vfpu;
 mod eabi;
 pub mod math;
+pub mod cache;
+pub mod error;
+pub use error::{Error, SceResult};
 pub mod sys;
 #[cfg(not(feature = "stub-only"))] pub mod test_runner;
+#[cfg(not(feature = "stub-only"))] pub mod io;
+#[cfg(not(feature = "stub-only"))] pub mod fs;
+#[cfg(not(feature = "stub-only"))] pub mod image;
+#[cfg(not(feature = "stub-only"))] pub mod audio;
+#[cfg(not(feature = "stub-only"))] pub mod executor;
+#[cfg(not(feature = "stub-only"))] pub mod callback;
+#[cfg(not(feature = "stub-only"))] pub mod input;
+#[cfg(not(feature = "stub-only"))] pub mod time;
+#[cfg(not(feature = "stub-only"))] pub mod utility;
+#[cfg(not(feature = "stub-only"))] pub mod video;
 #[cfg(not(feature = "stub-only"))] pub mod vram_alloc;
 
 #[cfg(not(feature = "stub-only"))] mod alloc_impl;
+#[cfg(not(feature = "stub-only"))] pub use alloc_impl::{heap_stats, HeapStats};
+#[cfg(not(feature = "stub-only"))] pub mod mem;
 #[cfg(not(feature = "stub-only"))] pub mod panic;
 
 #[cfg(not(feature = "stub-only"))] mod screenshot;
@@ -191,8 +206,18 @@ macro_rules! module {
 
 /// Enable the home button.
 ///
-/// This API does not have destructor support yet. You can manually setup an
-/// exit callback if you need this, see the source code of this function.
+/// This starts the `callback` thread. Choosing "Exit" from the home menu quits
+/// immediately, unless a `callback::Receiver` is subscribed to exit requests.
+#[cfg(not(feature = "stub-only"))]
+pub fn enable_home_button() {
+    let _ = callback::start();
+}
+
+/// Enable the home button.
+///
+/// Without the `callback` module, this starts a thread of its own which quits
+/// when "Exit" is chosen from the home menu.
+#[cfg(feature = "stub-only")]
 pub fn enable_home_button() {
     use core::{ptr, ffi::c_void};
     use sys::ThreadAttributes;
@@ -0,0 +1,235 @@
+//! The memory intrinsics.
+//!
+//! The compiler lowers copies, fills and comparisons to calls to `memcpy`,
+//! `memmove`, `memset` and `memcmp`, which are provided here. Large operations
+//! work a word at a time, unrolled by four, once the destination is aligned.
+//!
+//! Note that LLVM recognises simple copy and fill loops and turns them back
+//! into calls to these same functions. It will not do so inside functions
+//! named `memcpy` or `memset`, which is why the loops below are written out in
+//! place rather than shared through helpers.
+//!
+//! `ci/host_tests` compiles this file for the host, where it must not replace
+//! the C library's own intrinsics, so the symbols are only exported outside
+//! tests.
+
+use core::mem;
+
+const WORD: usize = mem::size_of::<u32>();
+
+/// Operations smaller than this are done byte by byte.
+const SMALL: usize = 16;
+
+#[cfg_attr(not(test), no_mangle)]
+pub(crate) unsafe extern fn memset(ptr: *mut u8, value: i32, num: usize) -> *mut u8 {
+    let mut dst = ptr;
+    let mut num = num;
+    let byte = value as u8;
+
+    if num >= SMALL {
+        while dst as usize % WORD != 0 {
+            *dst = byte;
+            dst = dst.add(1);
+            num -= 1;
+        }
+
+        let word = u32::from_ne_bytes([byte; WORD]);
+        let mut dst_word = dst as *mut u32;
+
+        while num >= 4 * WORD {
+            *dst_word = word;
+            *dst_word.add(1) = word;
+            *dst_word.add(2) = word;
+            *dst_word.add(3) = word;
+            dst_word = dst_word.add(4);
+            num -= 4 * WORD;
+        }
+
+        while num >= WORD {
+            *dst_word = word;
+            dst_word = dst_word.add(1);
+            num -= WORD;
+        }
+
+        dst = dst_word as *mut u8;
+    }
+
+    while num > 0 {
+        *dst = byte;
+        dst = dst.add(1);
+        num -= 1;
+    }
+
+    ptr
+}
+
+#[cfg_attr(not(test), no_mangle)]
+pub(crate) unsafe extern fn memcpy(dst: *mut u8, src: *const u8, num: usize) -> *mut u8 {
+    let ret = dst;
+    let mut dst = dst;
+    let mut src = src;
+    let mut num = num;
+
+    if num >= SMALL {
+        while dst as usize % WORD != 0 {
+            *dst = *src;
+            dst = dst.add(1);
+            src = src.add(1);
+            num -= 1;
+        }
+
+        let mut dst_word = dst as *mut u32;
+        let offset = src as usize % WORD;
+
+        if offset == 0 {
+            let mut src_word = src as *const u32;
+
+            while num >= 4 * WORD {
+                *dst_word = *src_word;
+                *dst_word.add(1) = *src_word.add(1);
+                *dst_word.add(2) = *src_word.add(2);
+                *dst_word.add(3) = *src_word.add(3);
+                dst_word = dst_word.add(4);
+                src_word = src_word.add(4);
+                num -= 4 * WORD;
+            }
+
+            while num >= WORD {
+                *dst_word = *src_word;
+                dst_word = dst_word.add(1);
+                src_word = src_word.add(1);
+                num -= WORD;
+            }
+
+            src = src_word as *const u8;
+        } else {
+            // The source is misaligned. Read aligned words and shift them
+            // together, rather than doing four byte loads per word. The PSP is
+            // little-endian, so lower addresses are the low bits.
+            let shift = (offset * 8) as u32;
+            let mut src_word = src.sub(offset) as *const u32;
+            let mut current = *src_word;
+
+            // Each output word needs the aligned word after it, so stop while
+            // that word still lies entirely inside the source.
+            while num >= 2 * WORD {
+                let next = *src_word.add(1);
+                *dst_word = (current >> shift) | (next << (32 - shift));
+                current = next;
+                dst_word = dst_word.add(1);
+                src_word = src_word.add(1);
+                num -= WORD;
+            }
+
+            src = (src_word as *const u8).add(offset);
+        }
+
+        dst = dst_word as *mut u8;
+    }
+
+    while num > 0 {
+        *dst = *src;
+        dst = dst.add(1);
+        src = src.add(1);
+        num -= 1;
+    }
+
+    ret
+}
+
+#[cfg_attr(not(test), no_mangle)]
+pub(crate) unsafe extern fn memmove(dst: *mut u8, src: *const u8, num: usize) -> *mut u8 {
+    // Copying forwards is fine unless the destination starts inside the source.
+    if (dst as usize).wrapping_sub(src as usize) >= num {
+        return memcpy(dst, src, num);
+    }
+
+    // Copy backwards, from the end.
+    let mut dst_end = dst.add(num);
+    let mut src_end = src.add(num);
+    let mut num = num;
+
+    if num >= SMALL && (dst_end as usize) % WORD == (src_end as usize) % WORD {
+        while dst_end as usize % WORD != 0 {
+            dst_end = dst_end.sub(1);
+            src_end = src_end.sub(1);
+            *dst_end = *src_end;
+            num -= 1;
+        }
+
+        let mut dst_word = dst_end as *mut u32;
+        let mut src_word = src_end as *const u32;
+
+        while num >= 4 * WORD {
+            dst_word = dst_word.sub(4);
+            src_word = src_word.sub(4);
+            *dst_word.add(3) = *src_word.add(3);
+            *dst_word.add(2) = *src_word.add(2);
+            *dst_word.add(1) = *src_word.add(1);
+            *dst_word = *src_word;
+            num -= 4 * WORD;
+        }
+
+        while num >= WORD {
+            dst_word = dst_word.sub(1);
+            src_word = src_word.sub(1);
+            *dst_word = *src_word;
+            num -= WORD;
+        }
+
+        dst_end = dst_word as *mut u8;
+        src_end = src_word as *const u8;
+    }
+
+    while num > 0 {
+        dst_end = dst_end.sub(1);
+        src_end = src_end.sub(1);
+        *dst_end = *src_end;
+        num -= 1;
+    }
+
+    dst
+}
+
+#[cfg_attr(not(test), no_mangle)]
+pub(crate) unsafe extern fn memcmp(ptr1: *const u8, ptr2: *const u8, num: usize) -> i32 {
+    let mut a = ptr1;
+    let mut b = ptr2;
+    let mut num = num;
+
+    // Skip over equal words when both sides can be read word-aligned.
+    if num >= SMALL && a as usize % WORD == b as usize % WORD {
+        while a as usize % WORD != 0 {
+            if *a != *b {
+                return *a as i32 - *b as i32;
+            }
+
+            a = a.add(1);
+            b = b.add(1);
+            num -= 1;
+        }
+
+        while num >= WORD && *(a as *const u32) == *(b as *const u32) {
+            a = a.add(WORD);
+            b = b.add(WORD);
+            num -= WORD;
+        }
+    }
+
+    while num > 0 {
+        if *a != *b {
+            return *a as i32 - *b as i32;
+        }
+
+        a = a.add(1);
+        b = b.add(1);
+        num -= 1;
+    }
+
+    0
+}
+
+#[cfg_attr(not(test), no_mangle)]
+pub(crate) unsafe extern fn bcmp(ptr1: *const u8, ptr2: *const u8, num: usize) -> i32 {
+    memcmp(ptr1, ptr2, num)
+}
@@ -0,0 +1,56 @@
+//! Memory intrinsics, and a VFPU copy for large blocks.
+
+mod intrinsics;
+
+/// Copy memory using VFPU quadword loads and stores, 64 bytes at a time.
+///
+/// This is faster than `memcpy` for large, aligned blocks, such as textures
+/// and vertex buffers.
+///
+/// # Safety
+///
+/// In addition to the requirements of `core::ptr::copy_nonoverlapping`:
+///
+/// - `dst` and `src` must be 16-byte aligned.
+/// - The current thread must have been created with `ThreadAttributes::VFPU`.
+/// - This clobbers VFPU matrix 7 (`C700` to `C730`), which must not hold
+///   anything the caller needs.
+pub unsafe fn copy_vfpu(dst: *mut u8, src: *const u8, num: usize) {
+    let mut dst = dst;
+    let mut src = src;
+    let mut num = num;
+
+    while num >= 64 {
+        vfpu_asm!(
+            lv_q C700, a1;
+            lv_q C710, 16(a1);
+            lv_q C720, 32(a1);
+            lv_q C730, 48(a1);
+            sv_q C700, a0;
+            sv_q C710, 16(a0);
+            sv_q C720, 32(a0);
+            sv_q C730, 48(a0);
+
+            : : "{a0}"(dst), "{a1}"(src) : "memory" : "volatile"
+        );
+
+        dst = dst.add(64);
+        src = src.add(64);
+        num -= 64;
+    }
+
+    while num >= 16 {
+        vfpu_asm!(
+            lv_q C700, a1;
+            sv_q C700, a0;
+
+            : : "{a0}"(dst), "{a1}"(src) : "memory" : "volatile"
+        );
+
+        dst = dst.add(16);
+        src = src.add(16);
+        num -= 16;
+    }
+
+    intrinsics::memcpy(dst, src, num);
+}
@@ -89,16 +89,7 @@ pub fn screenshot_argb_be() -> alloc::vec::Vec<u32> {
         );
     }
 
-    // http://uofw.github.io/upspd/docs/hardware/PSPTEK.htm#memmap
-
-    // If this is a kernel address...
-    if top_addr as u32 & 0x80000000 != 0 {
-        // Set the kernel cache-through bit.
-        top_addr = (top_addr as u32 | 0xA0000000) as _;
-    } else {
-        // Else set the regular cache-through bit.
-        top_addr = (top_addr as u32 | 0x40000000) as _;
-    }
+    top_addr = crate::cache::uncached(top_addr);
 
     for x in 0..SCREEN_WIDTH {
         for y in 0..SCREEN_HEIGHT {
@@ -6,6 +6,7 @@ pub const AUDIO_NEXT_CHANNEL: i32 = -1;
 pub const AUDIO_SAMPLE_MIN: u32 = 64;
 pub const AUDIO_SAMPLE_MAX: u32 = 65472;
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u32)]
 pub enum AudioFormat {
     /// Channel set to stereo output
@@ -30,6 +31,7 @@ pub struct AudioInputParams {
 
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(i32)]
 pub enum AudioOutputFrequency {
     Khz48 = 48000,
@@ -43,6 +45,7 @@ pub enum AudioOutputFrequency {
     Khz8 = 8000,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(i32)]
 pub enum AudioInputFrequency {
     Khz44_1 = 44100,
@@ -340,7 +343,7 @@ psp_extern! {
     /// # Return value
     ///
     /// 0 on success, <0 on error.
-    pub fn sceAudioInputBlocking(sample_count: i32, freq: AudioInputFrequency, buf: *mut c_void);
+    pub fn sceAudioInputBlocking(sample_count: i32, freq: AudioInputFrequency, buf: *mut c_void) -> i32;
 
     #[psp(0x6D4BEC68)]
     /// Perform audio input
@@ -354,7 +357,7 @@ psp_extern! {
     /// # Return value
     ///
     /// 0 on success, <0 on error.
-    pub fn sceAudioInput(sample_count: i32, freq: AudioInputFrequency, buf: *mut c_void);
+    pub fn sceAudioInput(sample_count: i32, freq: AudioInputFrequency, buf: *mut c_void) -> i32;
 
     #[psp(0xA708C6A6)]
     /// Get the number of samples that were acquired
@@ -1647,7 +1647,7 @@ pub unsafe extern "C" fn sceGuGetMemory(mut size: i32) -> *mut c_void {
 #[no_mangle]
 pub unsafe extern "C" fn sceGuStart(context_type: GuContextType, list: *mut c_void) {
     let mut context = &mut CONTEXTS[context_type as usize];
-    let local_list = ((list as u32) | 0x4000_0000) as *mut u32;
+    let local_list = crate::cache::uncached(list as *mut u32);
 
     // setup display list
     context.list.start = local_list;
@@ -1,6 +1,7 @@
 //! Headphone Remote
 
 bitflags::bitflags! {
+    #[derive(Default)]
     #[repr(transparent)]
     pub struct Key: u32 {
         const PLAY_PAUSE  = 0x1;
@@ -23,6 +23,7 @@
 //!     - `sceGu`: Graphics API (Similar to OpenGL)
 //!     - `sceGum`: Matrix utility functions
 //!     - `sceMp3`: MP3 decoder API
+//!     - `sceSasCore`: Hardware sound synthesizer API
 //!     - `sceRegistry`: PSP OS Registry API
 //!     - `sceOpenPSID`: Console identification API (unique to every console)
 //!     - `sceUtility`: Various utilities such as msg dialogs and savedata 
@@ -74,8 +75,13 @@ pub use umd::*;
 mod mpeg;
 pub use mpeg::*;
 
+mod psmf;
+pub use psmf::*;
+
 mod hprm;
 pub use hprm::*;
+// `registry` has a `Key` too.
+pub use hprm::Key as HprmKey;
 
 mod gu;
 pub use gu::*;
@@ -88,6 +94,11 @@ pub use types::*;
 
 mod mp3;
 pub use mp3::*;
+// `registry` has a `Handle` too.
+pub use mp3::Handle as Mp3Handle;
+
+mod sas;
+pub use sas::*;
 
 mod registry;
 pub use registry::*;
@@ -0,0 +1,488 @@
+//! The `scePsmf` PMF header library and the `scePsmfPlayer` library built on
+//! it.
+//!
+//! Neither is part of the firmware's utility modules: games ship
+//! `libpsmf.prx` and `psmf_player.prx` themselves, and load them with
+//! `sceKernelLoadModule` after `Module::AvCodec` and `Module::AvMpegBase`.
+
+use core::ffi::c_void;
+
+/// The type of a stream in a PMF file.
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PsmfStreamType {
+    Avc = 0,
+    Atrac = 1,
+    Pcm = 2,
+    Data = 3,
+    /// Either ATRAC or PCM, when selecting a stream.
+    Audio = 15,
+}
+
+/// Header state, filled in by `scePsmfSetPsmf`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ScePsmf {
+    pub version: u32,
+    pub header_size: u32,
+    pub header_offset: u32,
+    pub stream_size: u32,
+    pub stream_num: u32,
+    pub unk1: u32,
+    pub unk2: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ScePsmfVideoInfo {
+    pub width: i32,
+    pub height: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ScePsmfAudioInfo {
+    pub channel_configuration: i32,
+    pub sample_frequency: i32,
+}
+
+/// An entry point, where decoding can start, from the EP map.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ScePsmfEntry {
+    /// Presentation timestamp, at 90 kHz.
+    pub pts: u32,
+    /// Offset into the stream, in 2048 byte packets.
+    pub offset: u32,
+    pub index: u32,
+    pub pic_offset: u32,
+}
+
+psp_extern! {
+    #![name = "scePsmf"]
+    #![flags = 0x0009]
+    #![version = (0x00, 0x00)]
+
+    #[psp(0xC22C8327)]
+    /// Parse a PMF header.
+    ///
+    /// # Parameters
+    ///
+    /// - `psmf`: will be filled in
+    /// - `header`: the first 2048 bytes of the file
+    ///
+    /// # Return Value
+    ///
+    /// 0 if success, < 0 on error.
+    pub fn scePsmfSetPsmf(psmf: *mut ScePsmf, header: *mut c_void) -> i32;
+
+    #[psp(0x2673646B)]
+    /// Check that `header` is a PMF header.
+    ///
+    /// # Return Value
+    ///
+    /// 0 if it is, < 0 otherwise.
+    pub fn scePsmfVerifyPsmf(header: *mut c_void) -> i32;
+
+    #[psp(0x5B70FCC1)]
+    /// # Parameters
+    ///
+    /// - `header`: the first 2048 bytes of the file
+    /// - `offset`: will contain the stream offset, in bytes
+    ///
+    /// # Return Value
+    ///
+    /// 0 if success, < 0 on error.
+    pub fn scePsmfQueryStreamOffset(header: *mut c_void, offset: *mut u32) -> i32;
+
+    #[psp(0x9553CC91)]
+    /// # Parameters
+    ///
+    /// - `header`: the first 2048 bytes of the file
+    /// - `size`: will contain the stream size, in bytes
+    ///
+    /// # Return Value
+    ///
+    /// 0 if success, < 0 on error.
+    pub fn scePsmfQueryStreamSize(header: *mut c_void, size: *mut u32) -> i32;
+
+    #[psp(0xE1283895)]
+    /// # Return Value
+    ///
+    /// The PMF version, e.g. 15 for `"0015"`, or < 0 on error.
+    pub fn scePsmfGetPsmfVersion(psmf: *mut ScePsmf) -> i32;
+
+    #[psp(0xB78EB9E9)]
+    pub fn scePsmfGetHeaderSize(psmf: *mut ScePsmf, size: *mut u32) -> i32;
+
+    #[psp(0xA5EBFE81)]
+    pub fn scePsmfGetStreamSize(psmf: *mut ScePsmf, size: *mut u32) -> i32;
+
+    #[psp(0x76D3AEBA)]
+    /// # Parameters
+    ///
+    /// - `psmf`: header state
+    /// - `start_time`: will contain the first timestamp, at 90 kHz
+    pub fn scePsmfGetPresentationStartTime(psmf: *mut ScePsmf, start_time: *mut u32) -> i32;
+
+    #[psp(0xBD8AE0D8)]
+    /// # Parameters
+    ///
+    /// - `psmf`: header state
+    /// - `end_time`: will contain the last timestamp, at 90 kHz
+    pub fn scePsmfGetPresentationEndTime(psmf: *mut ScePsmf, end_time: *mut u32) -> i32;
+
+    #[psp(0xEAED89CD)]
+    /// # Return Value
+    ///
+    /// The number of streams, or < 0 on error.
+    pub fn scePsmfGetNumberOfStreams(psmf: *mut ScePsmf) -> i32;
+
+    #[psp(0x68D42328)]
+    /// # Return Value
+    ///
+    /// The number of streams of `type_`, or < 0 on error.
+    pub fn scePsmfGetNumberOfSpecificStreams(psmf: *mut ScePsmf, type_: PsmfStreamType) -> i32;
+
+    #[psp(0x4BC9BDE0)]
+    /// Select the stream the other functions describe, by its index.
+    pub fn scePsmfSpecifyStream(psmf: *mut ScePsmf, stream_num: i32) -> i32;
+
+    #[psp(0x1E6D9013)]
+    /// Select the stream the other functions describe, by its type and
+    /// channel.
+    pub fn scePsmfSpecifyStreamWithStreamType(
+        psmf: *mut ScePsmf,
+        type_: PsmfStreamType,
+        channel: i32,
+    ) -> i32;
+
+    #[psp(0x0C120E1D)]
+    /// Select the stream the other functions describe, by its type and its
+    /// index among streams of that type.
+    pub fn scePsmfSpecifyStreamWithStreamTypeNumber(
+        psmf: *mut ScePsmf,
+        type_: PsmfStreamType,
+        type_num: i32,
+    ) -> i32;
+
+    #[psp(0x28240568)]
+    /// # Return Value
+    ///
+    /// The index of the selected stream, or < 0 on error.
+    pub fn scePsmfGetCurrentStreamNumber(psmf: *mut ScePsmf) -> i32;
+
+    #[psp(0xC7DB3A5B)]
+    /// Get the selected stream's `PsmfStreamType` and channel.
+    pub fn scePsmfGetCurrentStreamType(
+        psmf: *mut ScePsmf,
+        type_: *mut i32,
+        channel: *mut i32,
+    ) -> i32;
+
+    #[psp(0x0BA514E5)]
+    /// Get the dimensions of the selected video stream.
+    pub fn scePsmfGetVideoInfo(psmf: *mut ScePsmf, info: *mut ScePsmfVideoInfo) -> i32;
+
+    #[psp(0xA83F7113)]
+    /// Get the format of the selected audio stream.
+    pub fn scePsmfGetAudioInfo(psmf: *mut ScePsmf, info: *mut ScePsmfAudioInfo) -> i32;
+
+    #[psp(0x971A3A90)]
+    /// # Return Value
+    ///
+    /// 0 if the selected stream has an EP map, < 0 otherwise.
+    pub fn scePsmfCheckEPMap(psmf: *mut ScePsmf) -> i32;
+
+    #[psp(0x7491C438)]
+    /// # Return Value
+    ///
+    /// The number of entries in the selected stream's EP map, or < 0 on
+    /// error.
+    pub fn scePsmfGetNumberOfEPentries(psmf: *mut ScePsmf) -> i32;
+
+    #[psp(0x4E624A34)]
+    pub fn scePsmfGetEPWithId(psmf: *mut ScePsmf, id: i32, entry: *mut ScePsmfEntry) -> i32;
+
+    #[psp(0x7C0E7AC3)]
+    /// Get the last entry point at or before `pts`.
+    pub fn scePsmfGetEPWithTimestamp(
+        psmf: *mut ScePsmf,
+        pts: u32,
+        entry: *mut ScePsmfEntry,
+    ) -> i32;
+
+    #[psp(0x5F457515)]
+    /// # Return Value
+    ///
+    /// The ID of the last entry point at or before `pts`, or < 0 on error.
+    pub fn scePsmfGetEPidWithTimestamp(psmf: *mut ScePsmf, pts: u32) -> i32;
+}
+
+/// Storage for a player handle, filled in by `scePsmfPlayerCreate`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct ScePsmfPlayer {
+    pub handle: *mut c_void,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct PsmfPlayerCreateData {
+    /// Working memory for the player, 64-byte aligned.
+    pub buffer: *mut c_void,
+    /// Usually 0x30_0000 bytes.
+    pub buffer_size: u32,
+    /// Priority of the player's decoding thread.
+    pub thread_priority: i32,
+}
+
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PsmfPlayerMode {
+    Play = 0,
+    SlowMotion = 1,
+    StepFrame = 2,
+    Pause = 3,
+    Forward = 4,
+    Rewind = 5,
+}
+
+/// What `scePsmfPlayerStart` plays.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct PsmfPlayerData {
+    pub video_codec: PsmfStreamType,
+    pub video_stream_num: i32,
+    pub audio_codec: PsmfStreamType,
+    pub audio_stream_num: i32,
+    pub play_mode: PsmfPlayerMode,
+    pub play_speed: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct PsmfPlayerVideoData {
+    /// Width of `display_buf`, in pixels.
+    pub frame_width: i32,
+    /// Where the frame is written.
+    pub display_buf: *mut c_void,
+    /// Will contain the frame's timestamp, at 90 kHz.
+    pub display_pts: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct PsmfPlayerInfo {
+    /// Timestamp of the last frame, at 90 kHz.
+    pub last_frame_ts: u32,
+    pub num_video_streams: i32,
+    pub num_audio_streams: i32,
+    pub num_pcm_streams: i32,
+    pub player_version: i32,
+}
+
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PsmfPlayerStatus {
+    None = 0,
+    Init = 1,
+    Standby = 2,
+    Playing = 4,
+    Error = 0x100,
+    PlayingFinished = 0x200,
+}
+
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PsmfPlayerConfigMode {
+    /// Set with `PSMF_PLAYER_CONFIG_LOOP` or `PSMF_PLAYER_CONFIG_NO_LOOP`.
+    Loop = 0,
+    /// Set with a `DisplayPixelFormat`.
+    PixelType = 1,
+}
+
+pub const PSMF_PLAYER_CONFIG_LOOP: i32 = 0;
+pub const PSMF_PLAYER_CONFIG_NO_LOOP: i32 = 1;
+
+/// The size of the buffer `scePsmfPlayerGetAudioData` fills, in bytes.
+pub const PSMF_PLAYER_AUDIO_OUT_SIZE: usize = 0x2000;
+
+psp_extern! {
+    #![name = "scePsmfPlayer"]
+    #![flags = 0x0009]
+    #![version = (0x00, 0x00)]
+
+    #[psp(0x235D8787)]
+    /// # Parameters
+    ///
+    /// - `player`: will be filled in
+    /// - `data`: working memory and thread priority
+    ///
+    /// # Return Value
+    ///
+    /// 0 if success, < 0 on error.
+    pub fn scePsmfPlayerCreate(
+        player: *mut ScePsmfPlayer,
+        data: *mut PsmfPlayerCreateData,
+    ) -> i32;
+
+    #[psp(0x9B71A274)]
+    pub fn scePsmfPlayerDelete(player: *mut ScePsmfPlayer) -> i32;
+
+    #[psp(0x3D6D25A9)]
+    /// Open a PMF file by path.
+    pub fn scePsmfPlayerSetPsmf(player: *mut ScePsmfPlayer, path: *const u8) -> i32;
+
+    #[psp(0x58B83577)]
+    /// Like `scePsmfPlayerSetPsmf`, but callbacks can run while the file is
+    /// read.
+    pub fn scePsmfPlayerSetPsmfCB(player: *mut ScePsmfPlayer, path: *const u8) -> i32;
+
+    #[psp(0x76C0F4AE)]
+    /// Open a PMF stored at `offset` bytes into a file, e.g. in a package.
+    pub fn scePsmfPlayerSetPsmfOffset(
+        player: *mut ScePsmfPlayer,
+        path: *const u8,
+        offset: i32,
+    ) -> i32;
+
+    #[psp(0xA72DB4F9)]
+    pub fn scePsmfPlayerSetPsmfOffsetCB(
+        player: *mut ScePsmfPlayer,
+        path: *const u8,
+        offset: i32,
+    ) -> i32;
+
+    #[psp(0xE792CD94)]
+    /// Close the file opened with `scePsmfPlayerSetPsmf`.
+    pub fn scePsmfPlayerReleasePsmf(player: *mut ScePsmfPlayer) -> i32;
+
+    #[psp(0x2D0E4E0A)]
+    pub fn scePsmfPlayerSetTempBuf(
+        player: *mut ScePsmfPlayer,
+        buf: *mut c_void,
+        size: u32,
+    ) -> i32;
+
+    #[psp(0x1E57A8E7)]
+    /// # Parameters
+    ///
+    /// - `player`: player handle
+    /// - `mode`: what to configure
+    /// - `attr`: the value, see `PsmfPlayerConfigMode`
+    pub fn scePsmfPlayerConfigPlayer(
+        player: *mut ScePsmfPlayer,
+        mode: PsmfPlayerConfigMode,
+        attr: i32,
+    ) -> i32;
+
+    #[psp(0x95A84EE5)]
+    /// # Parameters
+    ///
+    /// - `player`: player handle
+    /// - `data`: the streams to play, and how
+    /// - `init_pts`: the timestamp to start from, at 90 kHz
+    pub fn scePsmfPlayerStart(
+        player: *mut ScePsmfPlayer,
+        data: *mut PsmfPlayerData,
+        init_pts: i32,
+    ) -> i32;
+
+    #[psp(0x1078C008)]
+    pub fn scePsmfPlayerStop(player: *mut ScePsmfPlayer) -> i32;
+
+    #[psp(0x2BEB1569)]
+    /// Stop playback straight away, e.g. before `scePsmfPlayerReleasePsmf`.
+    pub fn scePsmfPlayerBreak(player: *mut ScePsmfPlayer) -> i32;
+
+    #[psp(0xA0B8CA55)]
+    /// Feed the decoders. Call this once per frame while playing.
+    pub fn scePsmfPlayerUpdate(player: *mut ScePsmfPlayer) -> i32;
+
+    #[psp(0x46F61F8B)]
+    /// Decode the frame that is due, if any, into `data.display_buf`.
+    pub fn scePsmfPlayerGetVideoData(
+        player: *mut ScePsmfPlayer,
+        data: *mut PsmfPlayerVideoData,
+    ) -> i32;
+
+    #[psp(0xB9848A74)]
+    /// Decode `PSMF_PLAYER_AUDIO_OUT_SIZE` bytes of stereo audio into `data`.
+    pub fn scePsmfPlayerGetAudioData(player: *mut ScePsmfPlayer, data: *mut c_void) -> i32;
+
+    #[psp(0x3EA82A4B)]
+    /// # Return Value
+    ///
+    /// The size `scePsmfPlayerGetAudioData` writes, in bytes, or < 0 on
+    /// error.
+    pub fn scePsmfPlayerGetAudioOutSize(player: *mut ScePsmfPlayer) -> i32;
+
+    #[psp(0x3ED62233)]
+    /// Get the timestamp of the last frame shown, at 90 kHz.
+    pub fn scePsmfPlayerGetCurrentPts(player: *mut ScePsmfPlayer, pts: *mut u32) -> i32;
+
+    #[psp(0xF8EF08A6)]
+    /// # Return Value
+    ///
+    /// A `PsmfPlayerStatus`, or < 0 on error.
+    pub fn scePsmfPlayerGetCurrentStatus(player: *mut ScePsmfPlayer) -> i32;
+
+    #[psp(0xDF089680)]
+    pub fn scePsmfPlayerGetPsmfInfo(
+        player: *mut ScePsmfPlayer,
+        info: *mut PsmfPlayerInfo,
+        width: *mut i32,
+        height: *mut i32,
+    ) -> i32;
+
+    #[psp(0xA3D81169)]
+    pub fn scePsmfPlayerChangePlayMode(
+        player: *mut ScePsmfPlayer,
+        mode: PsmfPlayerMode,
+        speed: i32,
+    ) -> i32;
+
+    #[psp(0xF3EFAA91)]
+    pub fn scePsmfPlayerGetCurrentPlayMode(
+        player: *mut ScePsmfPlayer,
+        mode: *mut i32,
+        speed: *mut i32,
+    ) -> i32;
+
+    #[psp(0x8A9EBDCD)]
+    /// Switch to the next video stream.
+    pub fn scePsmfPlayerSelectVideo(player: *mut ScePsmfPlayer) -> i32;
+
+    #[psp(0xB8D10C56)]
+    /// Switch to the next audio stream.
+    pub fn scePsmfPlayerSelectAudio(player: *mut ScePsmfPlayer) -> i32;
+
+    #[psp(0x75F03FA2)]
+    pub fn scePsmfPlayerSelectSpecificVideo(
+        player: *mut ScePsmfPlayer,
+        codec: PsmfStreamType,
+        stream_num: i32,
+    ) -> i32;
+
+    #[psp(0x85461EFF)]
+    pub fn scePsmfPlayerSelectSpecificAudio(
+        player: *mut ScePsmfPlayer,
+        codec: PsmfStreamType,
+        stream_num: i32,
+    ) -> i32;
+
+    #[psp(0x9FF2B2E7)]
+    pub fn scePsmfPlayerGetCurrentVideoStream(
+        player: *mut ScePsmfPlayer,
+        codec: *mut i32,
+        stream_num: *mut i32,
+    ) -> i32;
+
+    #[psp(0x68F07175)]
+    pub fn scePsmfPlayerGetCurrentAudioStream(
+        player: *mut ScePsmfPlayer,
+        codec: *mut i32,
+        stream_num: *mut i32,
+    ) -> i32;
+}
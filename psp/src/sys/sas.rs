@@ -0,0 +1,357 @@
+//! The `sceSasCore` hardware sound synthesizer.
+//!
+//! The library is not loaded by default: load `Module::AvSascore` with
+//! `sceUtilityLoadModule` first.
+
+use crate::eabi::{i5, i6, i7};
+use core::ffi::c_void;
+
+/// The maximum number of voices.
+pub const SAS_VOICES_MAX: i32 = 32;
+/// The default number of samples mixed per `__sceSasCore` call.
+pub const SAS_GRAIN_SAMPLES: i32 = 256;
+/// The maximum volume of a voice or effect.
+pub const SAS_VOLUME_MAX: i32 = 0x1000;
+/// The lowest pitch.
+pub const SAS_PITCH_MIN: i32 = 0x1;
+/// The pitch at which a sample plays at 44.1 kHz.
+pub const SAS_PITCH_BASE: i32 = 0x1000;
+/// The highest pitch, two octaves above `SAS_PITCH_BASE`.
+pub const SAS_PITCH_MAX: i32 = 0x4000;
+/// The highest noise frequency.
+pub const SAS_NOISE_FREQ_MAX: i32 = 0x3f;
+/// The highest envelope height, and the highest sustain level.
+pub const SAS_ENVELOPE_HEIGHT_MAX: i32 = 0x4000_0000;
+/// The highest envelope rate.
+pub const SAS_ENVELOPE_RATE_MAX: i32 = 0x7fff_ffff;
+
+/// Memory for a Sas core. It must be 64-byte aligned.
+#[repr(C, align(64))]
+#[derive(Copy, Clone)]
+pub struct SasCore {
+    pub data: [i32; 212],
+}
+
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SasOutputMode {
+    Stereo = 0,
+    Multichannel = 1,
+}
+
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SasLoopMode {
+    Off = 0,
+    On = 1,
+}
+
+bitflags::bitflags! {
+    /// Which of the ADSR parameters a call sets.
+    #[repr(transparent)]
+    pub struct SasAdsrFlags: i32 {
+        const ATTACK = 1;
+        const DECAY = 2;
+        const SUSTAIN = 4;
+        const RELEASE = 8;
+    }
+}
+
+/// The shape of an envelope phase.
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SasAdsrCurveMode {
+    LinearIncrease = 0,
+    LinearDecrease = 1,
+    /// Linear, but slower near the top.
+    LinearBent = 2,
+    ExponentDecrease = 3,
+    ExponentIncrease = 4,
+    /// Jump straight to the target.
+    Direct = 5,
+}
+
+/// The reverb effect type.
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SasEffectType {
+    Off = -1,
+    Room = 0,
+    Unknown1 = 1,
+    Unknown2 = 2,
+    Unknown3 = 3,
+    Hall = 4,
+    Space = 5,
+    Echo = 6,
+    Delay = 7,
+    Pipe = 8,
+}
+
+psp_extern! {
+    #![name = "sceSasCore"]
+    #![flags = 0x0009]
+    #![version = (0x00, 0x00)]
+
+    #[psp(0x42778A9F, i5)]
+    /// Initialize a Sas core.
+    ///
+    /// # Parameters
+    ///
+    /// - `core`: Pointer to 64-byte aligned memory for the core.
+    /// - `grain_samples`: The number of samples mixed per `__sceSasCore` call,
+    ///   a multiple of 32 from 64 to 2048.
+    /// - `max_voices`: The number of voices, up to `SAS_VOICES_MAX`.
+    /// - `output_mode`: One of `SasOutputMode`.
+    /// - `sample_rate`: The output sample rate. Must be 44100.
+    ///
+    /// # Return Value
+    ///
+    /// 0 on success, < 0 on error.
+    pub fn __sceSasInit(
+        core: *mut SasCore,
+        grain_samples: i32,
+        max_voices: i32,
+        output_mode: SasOutputMode,
+        sample_rate: i32,
+    ) -> i32;
+
+    #[psp(0xA3589D81)]
+    /// Mix one grain of all voices.
+    ///
+    /// # Parameters
+    ///
+    /// - `core`: The Sas core.
+    /// - `out`: Buffer for `grain_samples` stereo samples.
+    ///
+    /// # Return Value
+    ///
+    /// 0 on success, < 0 on error.
+    pub fn __sceSasCore(core: *mut SasCore, out: *mut c_void) -> i32;
+
+    #[psp(0x50A14DFC)]
+    /// Mix one grain of all voices into existing samples.
+    ///
+    /// # Parameters
+    ///
+    /// - `core`: The Sas core.
+    /// - `in_out`: Buffer of `grain_samples` stereo samples, mixed into.
+    /// - `left_volume`: The volume of the existing left samples.
+    /// - `right_volume`: The volume of the existing right samples.
+    ///
+    /// # Return Value
+    ///
+    /// 0 on success, < 0 on error.
+    pub fn __sceSasCoreWithMix(
+        core: *mut SasCore,
+        in_out: *mut c_void,
+        left_volume: i32,
+        right_volume: i32,
+    ) -> i32;
+
+    #[psp(0x68A46B95)]
+    /// Get which voices have finished playing.
+    ///
+    /// # Return Value
+    ///
+    /// A bitmask with a bit set for each voice which has ended.
+    pub fn __sceSasGetEndFlag(core: *mut SasCore) -> i32;
+
+    #[psp(0x440CA7D8, i6)]
+    /// Set the volume of a voice.
+    ///
+    /// # Parameters
+    ///
+    /// - `core`: The Sas core.
+    /// - `voice`: The voice number.
+    /// - `left_volume`, `right_volume`: The dry volumes, up to `SAS_VOLUME_MAX`.
+    /// - `effect_left_volume`, `effect_right_volume`: The volumes sent to the
+    ///   effect, up to `SAS_VOLUME_MAX`.
+    ///
+    /// # Return Value
+    ///
+    /// 0 on success, < 0 on error.
+    pub fn __sceSasSetVolume(
+        core: *mut SasCore,
+        voice: i32,
+        left_volume: i32,
+        right_volume: i32,
+        effect_left_volume: i32,
+        effect_right_volume: i32,
+    ) -> i32;
+
+    #[psp(0xAD84D37F)]
+    /// Set the pitch of a voice, from `SAS_PITCH_MIN` to `SAS_PITCH_MAX`.
+    pub fn __sceSasSetPitch(core: *mut SasCore, voice: i32, pitch: i32) -> i32;
+
+    #[psp(0x99944089, i5)]
+    /// Set a voice to play VAG ADPCM data.
+    ///
+    /// # Parameters
+    ///
+    /// - `core`: The Sas core.
+    /// - `voice`: The voice number.
+    /// - `vag`: The ADPCM data, after the VAG file's 48-byte header.
+    /// - `size`: The size of the data in bytes, a multiple of 16.
+    /// - `loop_mode`: One of `SasLoopMode`.
+    ///
+    /// # Return Value
+    ///
+    /// 0 on success, < 0 on error.
+    pub fn __sceSasSetVoice(
+        core: *mut SasCore,
+        voice: i32,
+        vag: *const c_void,
+        size: i32,
+        loop_mode: SasLoopMode,
+    ) -> i32;
+
+    #[psp(0xE1CD9561, i5)]
+    /// Set a voice to play 16-bit mono PCM.
+    ///
+    /// # Parameters
+    ///
+    /// - `core`: The Sas core.
+    /// - `voice`: The voice number.
+    /// - `pcm`: The samples.
+    /// - `samples`: The number of samples.
+    /// - `loop_position`: The sample to loop back to, or -1 to not loop.
+    ///
+    /// # Return Value
+    ///
+    /// 0 on success, < 0 on error.
+    pub fn __sceSasSetVoicePCM(
+        core: *mut SasCore,
+        voice: i32,
+        pcm: *const c_void,
+        samples: i32,
+        loop_position: i32,
+    ) -> i32;
+
+    #[psp(0xB7660A23)]
+    /// Set a voice to play noise, at a frequency up to `SAS_NOISE_FREQ_MAX`.
+    pub fn __sceSasSetNoise(core: *mut SasCore, voice: i32, frequency: i32) -> i32;
+
+    #[psp(0x019B25EB, i7)]
+    /// Set the rates of a voice's envelope phases.
+    ///
+    /// # Parameters
+    ///
+    /// - `core`: The Sas core.
+    /// - `voice`: The voice number.
+    /// - `flags`: Which rates to set, from `SasAdsrFlags`.
+    /// - `attack`, `decay`, `sustain`, `release`: The rates, up to
+    ///   `SAS_ENVELOPE_RATE_MAX`.
+    ///
+    /// # Return Value
+    ///
+    /// 0 on success, < 0 on error.
+    pub fn __sceSasSetADSR(
+        core: *mut SasCore,
+        voice: i32,
+        flags: SasAdsrFlags,
+        attack: i32,
+        decay: i32,
+        sustain: i32,
+        release: i32,
+    ) -> i32;
+
+    #[psp(0x9EC3676A, i7)]
+    /// Set the curves of a voice's envelope phases.
+    ///
+    /// # Parameters
+    ///
+    /// - `core`: The Sas core.
+    /// - `voice`: The voice number.
+    /// - `flags`: Which curves to set, from `SasAdsrFlags`.
+    /// - `attack`, `decay`, `sustain`, `release`: One of `SasAdsrCurveMode`.
+    ///
+    /// # Return Value
+    ///
+    /// 0 on success, < 0 on error.
+    pub fn __sceSasSetADSRmode(
+        core: *mut SasCore,
+        voice: i32,
+        flags: SasAdsrFlags,
+        attack: SasAdsrCurveMode,
+        decay: SasAdsrCurveMode,
+        sustain: SasAdsrCurveMode,
+        release: SasAdsrCurveMode,
+    ) -> i32;
+
+    #[psp(0x5F9529F6)]
+    /// Set the level the decay phase ends at, up to `SAS_ENVELOPE_HEIGHT_MAX`.
+    pub fn __sceSasSetSL(core: *mut SasCore, voice: i32, level: i32) -> i32;
+
+    #[psp(0xCBCD4F79)]
+    /// Set a voice's envelope from two packed words, in the SPU2's format.
+    pub fn __sceSasSetSimpleADSR(core: *mut SasCore, voice: i32, env1: u32, env2: u32) -> i32;
+
+    #[psp(0x74AE582A)]
+    /// Get the current envelope height of a voice.
+    pub fn __sceSasGetEnvelopeHeight(core: *mut SasCore, voice: i32) -> i32;
+
+    #[psp(0x07F58C24)]
+    /// Get the envelope heights of every voice.
+    ///
+    /// # Parameters
+    ///
+    /// - `core`: The Sas core.
+    /// - `heights`: Array of `SAS_VOICES_MAX` heights.
+    pub fn __sceSasGetAllEnvelopeHeights(core: *mut SasCore, heights: *mut i32) -> i32;
+
+    #[psp(0x76F01ACA)]
+    /// Start a voice, from the attack phase.
+    pub fn __sceSasSetKeyOn(core: *mut SasCore, voice: i32) -> i32;
+
+    #[psp(0xA0CF2FA4)]
+    /// Release a voice, moving to the release phase.
+    pub fn __sceSasSetKeyOff(core: *mut SasCore, voice: i32) -> i32;
+
+    #[psp(0x787D04D5)]
+    /// Pause or resume voices.
+    ///
+    /// # Parameters
+    ///
+    /// - `core`: The Sas core.
+    /// - `voices`: A bitmask of voices.
+    /// - `pause`: 1 to pause, 0 to resume.
+    pub fn __sceSasSetPause(core: *mut SasCore, voices: i32, pause: i32) -> i32;
+
+    #[psp(0x2C8E6AB3)]
+    /// Get a bitmask of the paused voices.
+    pub fn __sceSasGetPauseFlag(core: *mut SasCore) -> i32;
+
+    #[psp(0x33D4AB37)]
+    /// Set the effect type, one of `SasEffectType`.
+    pub fn __sceSasRevType(core: *mut SasCore, effect_type: SasEffectType) -> i32;
+
+    #[psp(0x267A6DD2)]
+    /// Set the delay and feedback of the echo and delay effects, from 0 to
+    /// 128.
+    pub fn __sceSasRevParam(core: *mut SasCore, delay: i32, feedback: i32) -> i32;
+
+    #[psp(0xD5A229C9)]
+    /// Set the effect output volume, up to `SAS_VOLUME_MAX`.
+    pub fn __sceSasRevEVOL(core: *mut SasCore, left_volume: i32, right_volume: i32) -> i32;
+
+    #[psp(0xF983B186)]
+    /// Set whether the dry and effect (wet) signals are output, 1 for on and
+    /// 0 for off.
+    pub fn __sceSasRevVON(core: *mut SasCore, dry: i32, wet: i32) -> i32;
+
+    #[psp(0xE175EF66)]
+    /// Get the output mode, one of `SasOutputMode`.
+    pub fn __sceSasGetOutputmode(core: *mut SasCore) -> i32;
+
+    #[psp(0xE855BF76)]
+    /// Set the output mode.
+    pub fn __sceSasSetOutputmode(core: *mut SasCore, output_mode: SasOutputMode) -> i32;
+
+    #[psp(0xBD11B7C2)]
+    /// Get the number of samples mixed per `__sceSasCore` call.
+    pub fn __sceSasGetGrain(core: *mut SasCore) -> i32;
+
+    #[psp(0xD1E0A01E)]
+    /// Set the number of samples mixed per `__sceSasCore` call.
+    pub fn __sceSasSetGrain(core: *mut SasCore, grain_samples: i32) -> i32;
+}
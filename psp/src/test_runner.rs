@@ -1,5 +1,6 @@
-use crate::sys::{self, SceUid};
-use core::ffi::c_void;
+use crate::fs::{File, OpenOptions};
+use crate::io::Write;
+use crate::sys;
 
 pub const OUTPUT_FILENAME: &str = "psp_output_file.log";
 pub const OUTPUT_FIFO: &str = "psp_output_pipe.fifo";
@@ -9,6 +10,7 @@ pub const SUCCESS_TOKEN: &str = "FINAL_SUCCESS";
 pub const FAILURE_TOKEN: &str = "FINAL_FAILURE";
 
 use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::fmt::Arguments;
 
@@ -19,8 +21,8 @@ pub struct TestRunner<'a> {
 }
 
 enum TestRunnerMode {
-    FIFO(SceUid),
-    FILE(SceUid),
+    FIFO(File),
+    FILE(File),
     Dprintln,
 }
 
@@ -168,9 +170,10 @@ impl<'a> TestRunner<'a> {
     }
 
     pub fn write_args(&self, args: Arguments) {
-        match self.mode {
-            TestRunnerMode::FILE(fd) | TestRunnerMode::FIFO(fd) => {
-                write_to_psp_output_fd(fd, &format!("{}", args));
+        match &self.mode {
+            TestRunnerMode::FILE(file) | TestRunnerMode::FIFO(file) => {
+                let mut file: &File = file;
+                let _ = file.write_fmt(args);
             }
             TestRunnerMode::Dprintln => {
                 crate::dprintln!("{}", args);
@@ -181,8 +184,8 @@ impl<'a> TestRunner<'a> {
 
     fn quit(self) {
         match self.mode {
-            TestRunnerMode::FILE(fd) | TestRunnerMode::FIFO(fd) => {
-                close_psp_file(fd);
+            TestRunnerMode::FILE(file) | TestRunnerMode::FIFO(file) => {
+                drop(file);
                 quit_game();
             }
             TestRunnerMode::Dprintln => {
@@ -192,55 +195,33 @@ impl<'a> TestRunner<'a> {
     }
 }
 
-fn get_test_output_pipe() -> SceUid {
-    unsafe {
-        let fd = sys::sceIoOpen(
-            psp_filename(OUTPUT_FIFO),
-            sys::IoOpenFlags::APPEND | sys::IoOpenFlags::WR_ONLY,
-            0o777,
-        );
-        if fd.0 < 0 {
-            panic!(
-                "Unable to open pipe \"{}\" for output! \
-                You must create it yourself with `mkfifo`."
-            );
-        }
-        return fd;
+fn get_test_output_pipe() -> File {
+    match OpenOptions::new().append(true).open(&psp_filename(OUTPUT_FIFO)) {
+        Ok(file) => file,
+        Err(e) => panic!(
+            "Unable to open pipe \"{}\" for output: {}. \
+            You must create it yourself with `mkfifo`.",
+            OUTPUT_FIFO, e,
+        ),
     }
 }
 
-fn get_test_output_file() -> SceUid {
-    unsafe {
-        let fd = sys::sceIoOpen(
-            psp_filename(OUTPUT_FILENAME),
-            sys::IoOpenFlags::TRUNC | sys::IoOpenFlags::CREAT | sys::IoOpenFlags::RD_WR,
-            0o777,
-        );
-        if fd.0 < 0 {
-            panic!("Unable to open file \"{}\" for output!", OUTPUT_FILENAME);
-        }
-        return fd;
-    }
-}
-
-fn psp_filename(filename: &str) -> *const u8 {
-    format!("host0:/{}\0", filename).as_bytes().as_ptr()
-}
+fn get_test_output_file() -> File {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&psp_filename(OUTPUT_FILENAME));
 
-fn write_to_psp_output_fd(fd: SceUid, msg: &str) {
-    unsafe {
-        sys::sceIoWrite(
-            fd,
-            msg.as_bytes().as_ptr() as *const u8 as *const c_void,
-            msg.len(),
-        );
+    match file {
+        Ok(file) => file,
+        Err(e) => panic!("Unable to open file \"{}\" for output: {}", OUTPUT_FILENAME, e),
     }
 }
 
-fn close_psp_file(fd: SceUid) {
-    unsafe {
-        sys::sceIoClose(fd);
-    }
+fn psp_filename(filename: &str) -> String {
+    format!("host0:/{}", filename)
 }
 
 fn quit_game() {
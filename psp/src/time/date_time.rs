@@ -0,0 +1,776 @@
+//! Calendar dates and times.
+//!
+//! The calendar arithmetic, formatting and parsing here are pure Rust and match
+//! the RTC library's results, so they also run on the host. Only reading the
+//! clock and the time zone call into `sceRtc*`.
+
+use super::{SystemTime, TICKS_PER_SECOND};
+use crate::sys::ScePspDateTime;
+use alloc::string::String;
+use core::convert::TryFrom;
+use core::fmt::{self, Write};
+use core::ops::{Add, Sub};
+use core::str::FromStr;
+use core::time::Duration;
+
+const TICKS_PER_MINUTE: i64 = 60 * TICKS_PER_SECOND as i64;
+const TICKS_PER_DAY: i64 = 24 * 60 * TICKS_PER_MINUTE;
+
+/// Days from 0001-01-01 to 1970-01-01.
+const UNIX_EPOCH_DAYS: i64 = 719_162;
+
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Whether `year` is a leap year in the Gregorian calendar.
+pub fn is_leap_year(year: u16) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+/// The number of days in `month` (1 to 12) of `year`.
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: u16, month: u8, day: u8) -> i64 {
+    let y = year as i64 - (month <= 2) as i64;
+    let m = month as i64;
+
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+/// The date `days` after 1970-01-01, as (year, month, day).
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u8;
+
+    (yoe + era * 400 + (month <= 2) as i64, month, day)
+}
+
+/// RTC ticks as a `Duration`.
+pub(super) fn ticks_to_duration(ticks: u64) -> Duration {
+    let nanos_per_tick = 1_000_000_000 / TICKS_PER_SECOND;
+
+    Duration::new(
+        ticks / TICKS_PER_SECOND,
+        ((ticks % TICKS_PER_SECOND) * nanos_per_tick) as u32,
+    )
+}
+
+/// A `Duration` in RTC ticks, or `None` if that overflows.
+pub(super) fn duration_to_ticks(duration: Duration) -> Option<u64> {
+    let nanos_per_tick = 1_000_000_000 / TICKS_PER_SECOND;
+
+    duration
+        .as_secs()
+        .checked_mul(TICKS_PER_SECOND)?
+        .checked_add(duration.subsec_nanos() as u64 / nanos_per_tick)
+}
+
+/// A day of the week.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    fn from_index(index: i64) -> Self {
+        match index {
+            0 => Weekday::Monday,
+            1 => Weekday::Tuesday,
+            2 => Weekday::Wednesday,
+            3 => Weekday::Thursday,
+            4 => Weekday::Friday,
+            5 => Weekday::Saturday,
+            _ => Weekday::Sunday,
+        }
+    }
+
+    /// The English abbreviation, as used in RFC 2822.
+    pub fn short_name(self) -> &'static str {
+        match self {
+            Weekday::Monday => "Mon",
+            Weekday::Tuesday => "Tue",
+            Weekday::Wednesday => "Wed",
+            Weekday::Thursday => "Thu",
+            Weekday::Friday => "Fri",
+            Weekday::Saturday => "Sat",
+            Weekday::Sunday => "Sun",
+        }
+    }
+}
+
+/// Returned when a string isn't a valid date and time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ParseDateTimeError;
+
+impl fmt::Display for ParseDateTimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid date and time")
+    }
+}
+
+/// A date and time of day, from 0001-01-01 to 9999-12-31, with microsecond
+/// precision.
+///
+/// A `DateTime` doesn't record its time zone. Functions returning one say
+/// whether it is UTC or local time, and `to_local`/`to_utc` convert between
+/// the two using the system settings.
+///
+/// `Display` and `FromStr` use RFC 3339, treating the value as UTC.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DateTime {
+    // Field order gives the derived `Ord` chronological order.
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    microsecond: u32,
+}
+
+impl DateTime {
+    /// The earliest representable time, 0001-01-01 00:00:00.
+    pub const MIN: DateTime = DateTime {
+        year: 1,
+        month: 1,
+        day: 1,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        microsecond: 0,
+    };
+
+    /// The latest representable time, 9999-12-31 23:59:59.999999.
+    pub const MAX: DateTime = DateTime {
+        year: 9999,
+        month: 12,
+        day: 31,
+        hour: 23,
+        minute: 59,
+        second: 59,
+        microsecond: 999_999,
+    };
+
+    /// Create a date and time, or `None` if any component is out of range.
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Option<Self> {
+        let valid = (1..=9999).contains(&year)
+            && (1..=12).contains(&month)
+            && day >= 1
+            && day <= days_in_month(year, month)
+            && hour < 24
+            && minute < 60
+            && second < 60;
+
+        if valid {
+            Some(DateTime {
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                microsecond: 0,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Midnight at the start of a date.
+    pub fn from_date(year: u16, month: u8, day: u8) -> Option<Self> {
+        Self::new(year, month, day, 0, 0, 0)
+    }
+
+    /// Replace the microseconds, which must be less than one million.
+    pub fn with_microsecond(self, microsecond: u32) -> Option<Self> {
+        if microsecond < 1_000_000 {
+            Some(DateTime { microsecond, ..self })
+        } else {
+            None
+        }
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// The month, from 1 to 12.
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// The day of the month, from 1.
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+
+    pub fn microsecond(&self) -> u32 {
+        self.microsecond
+    }
+
+    pub fn weekday(&self) -> Weekday {
+        // 1970-01-01 was a Thursday.
+        let days = days_from_civil(self.year, self.month, self.day);
+        Weekday::from_index((days + 3).rem_euclid(7))
+    }
+
+    /// The day of the year, from 1.
+    pub fn ordinal(&self) -> u16 {
+        let start = days_from_civil(self.year, 1, 1);
+        (days_from_civil(self.year, self.month, self.day) - start + 1) as u16
+    }
+
+    pub fn is_leap_year(&self) -> bool {
+        is_leap_year(self.year)
+    }
+
+    /// Create a date and time from RTC ticks, microseconds since 0001-01-01.
+    pub fn from_ticks(ticks: u64) -> Option<Self> {
+        if ticks > DateTime::MAX.ticks() {
+            return None;
+        }
+
+        Some(Self::from_ticks_i64(ticks as i64))
+    }
+
+    fn from_ticks_i64(ticks: i64) -> Self {
+        let days = ticks.div_euclid(TICKS_PER_DAY);
+        let time = ticks.rem_euclid(TICKS_PER_DAY);
+        let (year, month, day) = civil_from_days(days - UNIX_EPOCH_DAYS);
+        let seconds = time / TICKS_PER_SECOND as i64;
+
+        DateTime {
+            year: year as u16,
+            month,
+            day,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+            microsecond: (time % TICKS_PER_SECOND as i64) as u32,
+        }
+    }
+
+    /// RTC ticks, microseconds since 0001-01-01.
+    pub fn ticks(&self) -> u64 {
+        let days = days_from_civil(self.year, self.month, self.day) + UNIX_EPOCH_DAYS;
+        let seconds = self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+
+        (days * TICKS_PER_DAY + seconds * TICKS_PER_SECOND as i64 + self.microsecond as i64) as u64
+    }
+
+    /// The time `seconds` after 1970-01-01 00:00:00.
+    pub fn from_unix_timestamp(seconds: i64) -> Option<Self> {
+        let ticks = seconds
+            .checked_add(UNIX_EPOCH_DAYS * 86_400)?
+            .checked_mul(TICKS_PER_SECOND as i64)?;
+
+        if ticks < 0 {
+            return None;
+        }
+
+        Self::from_ticks(ticks as u64)
+    }
+
+    /// Seconds since 1970-01-01 00:00:00, ignoring the microseconds.
+    pub fn unix_timestamp(&self) -> i64 {
+        self.ticks() as i64 / TICKS_PER_SECOND as i64 - UNIX_EPOCH_DAYS * 86_400
+    }
+
+    /// The current time in UTC.
+    pub fn now_utc() -> Self {
+        Self::from(SystemTime::now())
+    }
+
+    /// The current local time, using the system time zone.
+    pub fn now_local() -> Self {
+        Self::now_utc().to_local()
+    }
+
+    /// The system's offset from UTC, in minutes, including daylight saving.
+    ///
+    /// This is always zero when not running on a PSP.
+    pub fn local_offset_minutes() -> i32 {
+        let utc = Self::now_utc();
+        let local = utc.to_local();
+
+        ((local.ticks() as i64 - utc.ticks() as i64) / TICKS_PER_MINUTE) as i32
+    }
+
+    /// Convert a UTC time to local time, using `sceRtcConvertUtcToLocalTime`.
+    ///
+    /// Returns `self` unchanged when not running on a PSP.
+    pub fn to_local(self) -> Self {
+        #[cfg(target_os = "psp")]
+        {
+            let mut local = 0;
+
+            unsafe {
+                if crate::sys::sceRtcConvertUtcToLocalTime(&self.ticks(), &mut local) >= 0 {
+                    return Self::from_ticks(local).unwrap_or(self);
+                }
+            }
+        }
+
+        self
+    }
+
+    /// Convert a local time to UTC, using `sceRtcConvertLocalTimeToUTC`.
+    ///
+    /// Returns `self` unchanged when not running on a PSP.
+    pub fn to_utc(self) -> Self {
+        #[cfg(target_os = "psp")]
+        {
+            let mut utc = 0;
+
+            unsafe {
+                if crate::sys::sceRtcConvertLocalTimeToUTC(&self.ticks(), &mut utc) >= 0 {
+                    return Self::from_ticks(utc).unwrap_or(self);
+                }
+            }
+        }
+
+        self
+    }
+
+    fn checked_add_ticks(self, ticks: i64) -> Option<Self> {
+        let ticks = (self.ticks() as i64).checked_add(ticks)?;
+
+        if ticks < 0 || ticks > DateTime::MAX.ticks() as i64 {
+            return None;
+        }
+
+        Some(Self::from_ticks_i64(ticks))
+    }
+
+    pub fn checked_add(self, duration: Duration) -> Option<Self> {
+        let ticks = duration_to_ticks(duration)?;
+
+        if ticks > i64::MAX as u64 {
+            return None;
+        }
+
+        self.checked_add_ticks(ticks as i64)
+    }
+
+    pub fn checked_sub(self, duration: Duration) -> Option<Self> {
+        let ticks = duration_to_ticks(duration)?;
+
+        if ticks > i64::MAX as u64 {
+            return None;
+        }
+
+        self.checked_add_ticks(-(ticks as i64))
+    }
+
+    /// Add a number of days, which may be negative.
+    pub fn add_days(self, days: i64) -> Option<Self> {
+        self.checked_add_ticks(days.checked_mul(TICKS_PER_DAY)?)
+    }
+
+    /// Add a number of months, which may be negative. The day is clamped to
+    /// the end of the resulting month, so 2020-01-31 plus one month is
+    /// 2020-02-29.
+    pub fn add_months(self, months: i32) -> Option<Self> {
+        let index = self.year as i64 * 12 + self.month as i64 - 1 + months as i64;
+        let year = index.div_euclid(12);
+        let month = (index.rem_euclid(12) + 1) as u8;
+
+        if year < 1 || year > 9999 {
+            return None;
+        }
+
+        let year = year as u16;
+        let day = core::cmp::min(self.day, days_in_month(year, month));
+
+        Some(DateTime { year, month, day, ..self })
+    }
+
+    /// Add a number of years, which may be negative. February 29th becomes
+    /// February 28th in non-leap years.
+    pub fn add_years(self, years: i32) -> Option<Self> {
+        self.add_months(years.checked_mul(12)?)
+    }
+
+    /// The time elapsed since `earlier`, or `None` if it is later.
+    pub fn duration_since(&self, earlier: DateTime) -> Option<Duration> {
+        self.ticks()
+            .checked_sub(earlier.ticks())
+            .map(ticks_to_duration)
+    }
+
+    /// Format a UTC time as RFC 3339, in the time zone `offset_minutes` east of
+    /// UTC, e.g. `2020-01-02T12:34:56+09:00`. Non-zero microseconds are
+    /// included as a fraction.
+    pub fn to_rfc3339(&self, offset_minutes: i32) -> String {
+        let mut s = String::new();
+        let _ = self.write_rfc3339(&mut s, offset_minutes);
+        s
+    }
+
+    fn write_rfc3339(&self, w: &mut impl Write, offset_minutes: i32) -> fmt::Result {
+        let local = self.shift_minutes(offset_minutes);
+
+        write!(
+            w,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            local.year, local.month, local.day, local.hour, local.minute, local.second,
+        )?;
+
+        if local.microsecond != 0 {
+            write!(w, ".{:06}", local.microsecond)?;
+        }
+
+        if offset_minutes == 0 {
+            w.write_char('Z')
+        } else {
+            let (sign, offset) = split_offset(offset_minutes);
+            write!(w, "{}{:02}:{:02}", sign, offset / 60, offset % 60)
+        }
+    }
+
+    /// Format a UTC time as RFC 2822, in the time zone `offset_minutes` east of
+    /// UTC, e.g. `Thu, 02 Jan 2020 12:34:56 +0900`.
+    pub fn to_rfc2822(&self, offset_minutes: i32) -> String {
+        let local = self.shift_minutes(offset_minutes);
+        let (sign, offset) = split_offset(offset_minutes);
+
+        let mut s = String::new();
+
+        let _ = write!(
+            s,
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} {}{:02}{:02}",
+            local.weekday().short_name(),
+            local.day,
+            MONTH_NAMES[local.month as usize - 1],
+            local.year,
+            local.hour,
+            local.minute,
+            local.second,
+            sign,
+            offset / 60,
+            offset % 60,
+        );
+
+        s
+    }
+
+    /// Parse an RFC 3339 date and time, returning it in UTC.
+    pub fn parse_rfc3339(s: &str) -> Result<Self, ParseDateTimeError> {
+        let mut p = Parser(s.as_bytes());
+
+        let year = p.number(4, 4)?;
+        p.expect(b"-")?;
+        let month = p.number(2, 2)?;
+        p.expect(b"-")?;
+        let day = p.number(2, 2)?;
+        p.expect(b"Tt ")?;
+        let hour = p.number(2, 2)?;
+        p.expect(b":")?;
+        let minute = p.number(2, 2)?;
+        p.expect(b":")?;
+        let second = p.number(2, 2)?;
+
+        let microsecond = if p.eat(b".") {
+            p.fraction()?
+        } else {
+            0
+        };
+
+        let offset = if p.eat(b"Zz") {
+            0
+        } else {
+            let negative = p.sign()?;
+            let hours = p.number(2, 2)? as i32;
+            p.expect(b":")?;
+            let minutes = p.number(2, 2)? as i32;
+
+            apply_sign(negative, hours * 60 + minutes)
+        };
+
+        p.end()?;
+
+        DateTime::new(year as u16, month as u8, day as u8, hour as u8, minute as u8, second as u8)
+            .and_then(|t| t.with_microsecond(microsecond))
+            .and_then(|t| t.checked_add_ticks(-(offset as i64) * TICKS_PER_MINUTE))
+            .ok_or(ParseDateTimeError)
+    }
+
+    /// Parse an RFC 2822 date and time, returning it in UTC. The day of the
+    /// week is optional, and isn't checked against the date.
+    pub fn parse_rfc2822(s: &str) -> Result<Self, ParseDateTimeError> {
+        let mut p = Parser(s.trim().as_bytes());
+
+        if p.0.get(3) == Some(&b',') {
+            p.0 = &p.0[4..];
+        }
+
+        p.skip_spaces();
+        let day = p.number(1, 2)?;
+        p.skip_spaces();
+        let month = p.month()?;
+        p.skip_spaces();
+        let year = p.number(4, 4)?;
+        p.skip_spaces();
+        let hour = p.number(2, 2)?;
+        p.expect(b":")?;
+        let minute = p.number(2, 2)?;
+
+        let second = if p.eat(b":") {
+            p.number(2, 2)?
+        } else {
+            0
+        };
+
+        p.skip_spaces();
+
+        let offset = match p.0 {
+            b"GMT" | b"UT" | b"UTC" | b"Z" => 0,
+            _ => {
+                let negative = p.sign()?;
+                let hours = p.number(2, 2)? as i32;
+                let minutes = p.number(2, 2)? as i32;
+                p.end()?;
+
+                apply_sign(negative, hours * 60 + minutes)
+            }
+        };
+
+        DateTime::new(year as u16, month, day as u8, hour as u8, minute as u8, second as u8)
+            .and_then(|t| t.checked_add_ticks(-(offset as i64) * TICKS_PER_MINUTE))
+            .ok_or(ParseDateTimeError)
+    }
+
+    /// Shift by a time zone offset for display, saturating at the ends of the
+    /// representable range.
+    fn shift_minutes(self, minutes: i32) -> Self {
+        self.checked_add_ticks(minutes as i64 * TICKS_PER_MINUTE)
+            .unwrap_or(if minutes < 0 { DateTime::MIN } else { DateTime::MAX })
+    }
+}
+
+fn split_offset(offset_minutes: i32) -> (char, u32) {
+    if offset_minutes < 0 {
+        ('-', (-(offset_minutes as i64)) as u32)
+    } else {
+        ('+', offset_minutes as u32)
+    }
+}
+
+fn apply_sign(negative: bool, value: i32) -> i32 {
+    if negative {
+        -value
+    } else {
+        value
+    }
+}
+
+/// A cursor over the bytes of a date string.
+struct Parser<'a>(&'a [u8]);
+
+impl Parser<'_> {
+    /// Consume one byte if it is one of `any`.
+    fn eat(&mut self, any: &[u8]) -> bool {
+        match self.0.first() {
+            Some(b) if any.contains(b) => {
+                self.0 = &self.0[1..];
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, any: &[u8]) -> Result<(), ParseDateTimeError> {
+        if self.eat(any) {
+            Ok(())
+        } else {
+            Err(ParseDateTimeError)
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.eat(b" \t") {}
+    }
+
+    fn end(&self) -> Result<(), ParseDateTimeError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(ParseDateTimeError)
+        }
+    }
+
+    /// Parse `min` to `max` decimal digits.
+    fn number(&mut self, min: usize, max: usize) -> Result<u32, ParseDateTimeError> {
+        let len = self.0.iter().take(max).take_while(|b| b.is_ascii_digit()).count();
+
+        if len < min {
+            return Err(ParseDateTimeError);
+        }
+
+        let value = self.0[..len].iter().fold(0, |n, b| n * 10 + (b - b'0') as u32);
+        self.0 = &self.0[len..];
+
+        Ok(value)
+    }
+
+    /// Parse the digits of a fraction of a second as microseconds. Digits past
+    /// the sixth are ignored.
+    fn fraction(&mut self) -> Result<u32, ParseDateTimeError> {
+        let len = self.0.iter().take_while(|b| b.is_ascii_digit()).count();
+
+        if len == 0 {
+            return Err(ParseDateTimeError);
+        }
+
+        let mut micros = 0;
+
+        for i in 0..6 {
+            let digit = self.0.get(i).filter(|_| i < len).map_or(0, |b| b - b'0');
+            micros = micros * 10 + digit as u32;
+        }
+
+        self.0 = &self.0[len..];
+
+        Ok(micros)
+    }
+
+    /// Parse `+` or `-`, returning whether it was negative.
+    fn sign(&mut self) -> Result<bool, ParseDateTimeError> {
+        if self.eat(b"+") {
+            Ok(false)
+        } else if self.eat(b"-") {
+            Ok(true)
+        } else {
+            Err(ParseDateTimeError)
+        }
+    }
+
+    fn month(&mut self) -> Result<u8, ParseDateTimeError> {
+        let name = self.0.get(..3).ok_or(ParseDateTimeError)?;
+
+        let index = MONTH_NAMES
+            .iter()
+            .position(|m| m.as_bytes().eq_ignore_ascii_case(name))
+            .ok_or(ParseDateTimeError)?;
+
+        self.0 = &self.0[3..];
+
+        Ok(index as u8 + 1)
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_rfc3339(f, 0)
+    }
+}
+
+impl FromStr for DateTime {
+    type Err = ParseDateTimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_rfc3339(s)
+    }
+}
+
+impl Add<Duration> for DateTime {
+    type Output = DateTime;
+
+    fn add(self, duration: Duration) -> DateTime {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to date")
+    }
+}
+
+impl Sub<Duration> for DateTime {
+    type Output = DateTime;
+
+    fn sub(self, duration: Duration) -> DateTime {
+        self.checked_sub(duration)
+            .expect("overflow when subtracting duration from date")
+    }
+}
+
+impl From<SystemTime> for DateTime {
+    /// Times past the year 9999 are clamped to `DateTime::MAX`.
+    fn from(time: SystemTime) -> Self {
+        Self::from_ticks(time.ticks()).unwrap_or(DateTime::MAX)
+    }
+}
+
+impl From<DateTime> for SystemTime {
+    fn from(date: DateTime) -> Self {
+        SystemTime::from_ticks(date.ticks())
+    }
+}
+
+impl From<DateTime> for ScePspDateTime {
+    fn from(date: DateTime) -> Self {
+        ScePspDateTime {
+            year: date.year,
+            month: date.month as u16,
+            day: date.day as u16,
+            hour: date.hour as u16,
+            minutes: date.minute as u16,
+            seconds: date.second as u16,
+            microseconds: date.microsecond,
+        }
+    }
+}
+
+impl TryFrom<ScePspDateTime> for DateTime {
+    type Error = ParseDateTimeError;
+
+    fn try_from(date: ScePspDateTime) -> Result<Self, Self::Error> {
+        let narrow = |n: u16| if n > u8::MAX as u16 { u8::MAX } else { n as u8 };
+
+        DateTime::new(
+            date.year,
+            narrow(date.month),
+            narrow(date.day),
+            narrow(date.hour),
+            narrow(date.minutes),
+            narrow(date.seconds),
+        )
+        .and_then(|d| d.with_microsecond(date.microseconds))
+        .ok_or(ParseDateTimeError)
+    }
+}
@@ -0,0 +1,270 @@
+//! Monotonic and wall-clock time, and sleeping.
+//!
+//! - `Instant` reads the kernel's microsecond system clock, which starts at
+//!   boot and never goes backwards. Use it to measure durations.
+//! - `SystemTime` reads the real-time clock, as RTC ticks since 0001-01-01 UTC.
+//!   Use it for timestamps, e.g. in save files.
+//! - `DateTime` splits RTC ticks into a calendar date and time of day.
+//! - `Alarm` and `VTimer` run closures after a delay, from interrupt context.
+//!
+//! Everything here uses integer arithmetic only.
+
+use crate::error::SceReturn;
+use crate::sys::{self, ScePspDateTime};
+use crate::SceResult;
+use core::fmt;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+use core::time::Duration;
+
+mod date_time;
+mod timer;
+use date_time::{duration_to_ticks, ticks_to_duration};
+pub use date_time::*;
+pub use timer::*;
+
+/// The number of RTC ticks per second. `sceRtcGetTickResolution` returns
+/// this on every model, so RTC ticks are microseconds.
+pub const TICKS_PER_SECOND: u64 = 1_000_000;
+
+/// Seconds from 0001-01-01 to 1970-01-01.
+const UNIX_EPOCH_SECONDS: u64 = 62_135_596_800;
+
+/// Convert a duration to whole microseconds, saturating at `u64::MAX`.
+pub fn as_micros_u64(duration: Duration) -> u64 {
+    duration
+        .as_secs()
+        .checked_mul(1_000_000)
+        .and_then(|micros| micros.checked_add(duration.subsec_micros() as u64))
+        .unwrap_or(u64::MAX)
+}
+
+/// Convert a duration to whole microseconds for a kernel timeout or delay,
+/// saturating at `u32::MAX` (a little over 71 minutes).
+pub fn as_micros_u32(duration: Duration) -> u32 {
+    core::cmp::min(as_micros_u64(duration), u32::MAX as u64) as u32
+}
+
+/// A reading of the monotonic system clock, with microsecond precision.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(u64);
+
+impl Instant {
+    /// The current time.
+    pub fn now() -> Self {
+        Instant(unsafe { sys::sceKernelGetSystemTimeWide() } as u64)
+    }
+
+    /// Microseconds since the system clock started.
+    pub fn as_micros(self) -> u64 {
+        self.0
+    }
+
+    /// Create an `Instant` from a system clock value in microseconds, e.g. one
+    /// read with `sceKernelGetSystemTimeWide`.
+    pub fn from_micros(micros: u64) -> Self {
+        Instant(micros)
+    }
+
+    /// The time elapsed since `earlier`. Panics if `earlier` is later than
+    /// `self`.
+    pub fn duration_since(self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier)
+            .expect("supplied instant is later than self")
+    }
+
+    /// The time elapsed since `earlier`, or `None` if it is later than `self`.
+    pub fn checked_duration_since(self, earlier: Instant) -> Option<Duration> {
+        self.0.checked_sub(earlier.0).map(Duration::from_micros)
+    }
+
+    /// The time elapsed since `earlier`, or zero if it is later than `self`.
+    pub fn saturating_duration_since(self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or_default()
+    }
+
+    /// The time elapsed since this instant.
+    pub fn elapsed(self) -> Duration {
+        Instant::now().saturating_duration_since(self)
+    }
+
+    pub fn checked_add(self, duration: Duration) -> Option<Instant> {
+        self.0.checked_add(as_micros_u64(duration)).map(Instant)
+    }
+
+    pub fn checked_sub(self, duration: Duration) -> Option<Instant> {
+        self.0.checked_sub(as_micros_u64(duration)).map(Instant)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, duration: Duration) {
+        *self = *self + duration;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, duration: Duration) -> Instant {
+        self.checked_sub(duration)
+            .expect("overflow when subtracting duration from instant")
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, duration: Duration) {
+        *self = *self - duration;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+/// A reading of the real-time clock, in RTC ticks since 0001-01-01 UTC.
+///
+/// Unlike `Instant`, this can jump when the user changes the system clock.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SystemTime(u64);
+
+/// 1970-01-01 00:00:00 UTC.
+pub const UNIX_EPOCH: SystemTime = SystemTime(UNIX_EPOCH_SECONDS * TICKS_PER_SECOND);
+
+/// Returned by `SystemTime::duration_since` when the given time is later.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SystemTimeError(Duration);
+
+impl SystemTimeError {
+    /// How much later the given time was.
+    pub fn duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl fmt::Display for SystemTimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "second time provided was later than self by {:?}", self.0)
+    }
+}
+
+impl SystemTime {
+    pub const UNIX_EPOCH: SystemTime = UNIX_EPOCH;
+
+    /// The current time, from `sceRtcGetCurrentTick`.
+    pub fn now() -> Self {
+        let mut tick = 0;
+        unsafe { sys::sceRtcGetCurrentTick(&mut tick) };
+
+        SystemTime(tick)
+    }
+
+    /// The raw RTC tick count.
+    pub fn ticks(self) -> u64 {
+        self.0
+    }
+
+    /// Create a `SystemTime` from a raw RTC tick count.
+    pub fn from_ticks(ticks: u64) -> Self {
+        SystemTime(ticks)
+    }
+
+    /// Seconds since the UNIX epoch, via `sceRtcGetTime64_t`.
+    pub fn unix_timestamp(self) -> SceResult<u64> {
+        let mut date = ScePspDateTime::default();
+        let mut seconds = 0;
+
+        unsafe {
+            sys::sceRtcSetTick(&mut date, &self.0).into_result()?;
+            sys::sceRtcGetTime64_t(&date, &mut seconds).into_result()?;
+        }
+
+        Ok(seconds)
+    }
+
+    /// The time `seconds` after the UNIX epoch, via `sceRtcSetTime64_t`.
+    pub fn from_unix_timestamp(seconds: u64) -> SceResult<Self> {
+        let mut date = ScePspDateTime::default();
+        let mut tick = 0;
+
+        unsafe {
+            sys::sceRtcSetTime64_t(&mut date, seconds).into_result()?;
+            sys::sceRtcGetTick(&date, &mut tick).into_result()?;
+        }
+
+        Ok(SystemTime(tick))
+    }
+
+    /// The time elapsed since `earlier`, or how much later it is as an error.
+    pub fn duration_since(self, earlier: SystemTime) -> Result<Duration, SystemTimeError> {
+        match self.0.checked_sub(earlier.0) {
+            Some(ticks) => Ok(ticks_to_duration(ticks)),
+            None => Err(SystemTimeError(ticks_to_duration(earlier.0 - self.0))),
+        }
+    }
+
+    /// The time elapsed since this time.
+    pub fn elapsed(self) -> Result<Duration, SystemTimeError> {
+        SystemTime::now().duration_since(self)
+    }
+
+    pub fn checked_add(self, duration: Duration) -> Option<SystemTime> {
+        self.0.checked_add(duration_to_ticks(duration)?).map(SystemTime)
+    }
+
+    pub fn checked_sub(self, duration: Duration) -> Option<SystemTime> {
+        self.0.checked_sub(duration_to_ticks(duration)?).map(SystemTime)
+    }
+}
+
+impl Add<Duration> for SystemTime {
+    type Output = SystemTime;
+
+    fn add(self, duration: Duration) -> SystemTime {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to system time")
+    }
+}
+
+impl Sub<Duration> for SystemTime {
+    type Output = SystemTime;
+
+    fn sub(self, duration: Duration) -> SystemTime {
+        self.checked_sub(duration)
+            .expect("overflow when subtracting duration from system time")
+    }
+}
+
+/// Block the current thread for at least `duration`.
+pub fn sleep(duration: Duration) {
+    delay(duration, |micros| unsafe { sys::sceKernelDelayThread(micros) });
+}
+
+/// Block the current thread for at least `duration`, running any callbacks
+/// registered on it (such as the exit callback) in the meantime.
+pub fn sleep_cb(duration: Duration) {
+    delay(duration, |micros| unsafe { sys::sceKernelDelayThreadCB(micros) });
+}
+
+fn delay(duration: Duration, delay_thread: impl Fn(u32) -> i32) {
+    // Kernel delays are limited to `u32::MAX` microseconds.
+    let mut remaining = as_micros_u64(duration);
+
+    while remaining > 0 {
+        let micros = core::cmp::min(remaining, u32::MAX as u64) as u32;
+        delay_thread(micros);
+        remaining -= micros as u64;
+    }
+}
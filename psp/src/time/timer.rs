@@ -0,0 +1,288 @@
+//! Alarms and VTimers running closures.
+//!
+//! Both run their handlers in interrupt context, so handlers must be short
+//! and must not:
+//!
+//! - allocate or free memory (the global allocator suspends interrupts, which
+//!   can't be done from a handler),
+//! - block, e.g. by waiting on a semaphore or sleeping,
+//! - print with `dprintln!`.
+//!
+//! Setting an event flag, signalling a semaphore or waking an
+//! `executor::Executor` task with `Waker::wake_by_ref` is fine. Handlers must
+//! be `Send + 'static`, as they run outside the thread which set them up.
+//!
+//! Handlers are only dropped along with the `Alarm` or `VTimer` which owns
+//! them, on the thread dropping it, so they may own memory such as a `Waker`.
+//! This is why even the handlers run once are `FnMut`: calling an `FnOnce`
+//! would drop what it captured inside the interrupt.
+
+use super::as_micros_u32;
+use crate::error::SceReturn;
+use crate::sys::{self, SceUid};
+use crate::SceResult;
+use alloc::boxed::Box;
+use core::cell::Cell;
+use core::ffi::c_void;
+use core::ptr;
+use core::time::Duration;
+
+/// A handler, and whether the kernel will still call it. The closure is boxed
+/// again so that the kernel gets a thin pointer.
+struct Handler<F: ?Sized> {
+    done: Cell<bool>,
+    f: Box<F>,
+}
+
+/// Convert a handler's result to the kernel's "reschedule after" value, where
+/// zero means stop.
+fn reschedule(next: Option<Duration>) -> u32 {
+    match next {
+        Some(delay) => core::cmp::max(as_micros_u32(delay), 1),
+        None => 0,
+    }
+}
+
+type AlarmHandler = Handler<dyn FnMut() -> Option<Duration> + Send>;
+
+unsafe extern "C" fn on_alarm(common: *mut c_void) -> u32 {
+    let handler = &mut *(common as *mut AlarmHandler);
+    let next = reschedule((handler.f)());
+
+    if next == 0 {
+        handler.done.set(true);
+    }
+
+    next
+}
+
+/// A closure run once, or repeatedly, after a delay, using a kernel alarm.
+///
+/// Dropping the `Alarm` cancels it. The closure runs in interrupt context; see
+/// the module documentation for what it may do.
+///
+/// ```no_run
+/// use psp::time::Alarm;
+/// use core::time::Duration;
+///
+/// let alarm = Alarm::periodic(Duration::from_millis(500), move || {
+///     // Toggle a blinking cursor...
+/// });
+/// ```
+pub struct Alarm {
+    id: SceUid,
+    handler: *mut AlarmHandler,
+}
+
+impl Alarm {
+    /// Run `f` once, after `delay`. `f` is dropped with the `Alarm`.
+    pub fn after<F>(delay: Duration, mut f: F) -> SceResult<Self>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        Self::with_schedule(delay, move || {
+            f();
+            None
+        })
+    }
+
+    /// Run `f` every `period`, starting one period from now.
+    pub fn periodic<F>(period: Duration, mut f: F) -> SceResult<Self>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        Self::with_schedule(period, move || {
+            f();
+            Some(period)
+        })
+    }
+
+    /// Run `f` after `delay`, and then again after each delay it returns until
+    /// it returns `None`.
+    pub fn with_schedule<F>(delay: Duration, f: F) -> SceResult<Self>
+    where
+        F: FnMut() -> Option<Duration> + Send + 'static,
+    {
+        let handler: Box<AlarmHandler> = Box::new(Handler {
+            done: Cell::new(false),
+            f: Box::new(f),
+        });
+
+        let handler = Box::into_raw(handler);
+        let micros = core::cmp::max(as_micros_u32(delay), 1);
+
+        match unsafe { sys::sceKernelSetAlarm(micros, on_alarm, handler as *mut c_void) }.into_result() {
+            Ok(id) => Ok(Self { id, handler }),
+            Err(e) => {
+                drop(unsafe { Box::from_raw(handler) });
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether the closure will run again.
+    pub fn is_pending(&self) -> bool {
+        unsafe { !(*self.handler).done.get() }
+    }
+
+    /// Cancel the alarm. This is the same as dropping it.
+    pub fn cancel(self) {}
+}
+
+impl Drop for Alarm {
+    fn drop(&mut self) {
+        unsafe {
+            // If the alarm fires between the check and the cancel, the
+            // handler runs to completion first and the cancel fails.
+            if self.is_pending() {
+                sys::sceKernelCancelAlarm(self.id);
+            }
+
+            drop(Box::from_raw(self.handler));
+        }
+    }
+}
+
+type VTimerHandler = Handler<dyn FnMut(Duration) -> Option<Duration> + Send>;
+
+unsafe extern "C" fn on_vtimer(_uid: SceUid, schedule: i64, _actual: i64, common: *mut c_void) -> u32 {
+    let handler = &mut *(common as *mut VTimerHandler);
+    let next = reschedule((handler.f)(Duration::from_micros(schedule as u64)));
+
+    if next == 0 {
+        handler.done.set(true);
+    }
+
+    next
+}
+
+/// A virtual timer: a clock which counts microseconds only while started, and
+/// can run a closure when it reaches a given time.
+///
+/// Dropping the `VTimer` cancels its handler and deletes it. The handler runs
+/// in interrupt context; see the module documentation for what it may do.
+pub struct VTimer {
+    uid: SceUid,
+    handler: Option<*mut VTimerHandler>,
+}
+
+impl VTimer {
+    /// Create a stopped VTimer at time zero.
+    pub fn new() -> SceResult<Self> {
+        let uid = unsafe { sys::sceKernelCreateVTimer(&b"vtimer\0"[0], ptr::null_mut()) }.into_result()?;
+
+        Ok(Self { uid, handler: None })
+    }
+
+    /// Start counting.
+    pub fn start(&mut self) -> SceResult<()> {
+        unsafe { sys::sceKernelStartVTimer(self.uid) }.into_result().map(drop)
+    }
+
+    /// Stop counting. The time is kept.
+    pub fn stop(&mut self) -> SceResult<()> {
+        unsafe { sys::sceKernelStopVTimer(self.uid) }.into_result().map(drop)
+    }
+
+    /// The time counted so far.
+    pub fn time(&self) -> Duration {
+        Duration::from_micros(unsafe { sys::sceKernelGetVTimerTimeWide(self.uid) } as u64)
+    }
+
+    /// Set the time, returning the previous time.
+    pub fn set_time(&mut self, time: Duration) -> Duration {
+        let micros = super::as_micros_u64(time) as i64;
+        Duration::from_micros(unsafe { sys::sceKernelSetVTimerTimeWide(self.uid, micros) } as u64)
+    }
+
+    /// Run `f` once, when the timer reaches `at`. Replaces any previous
+    /// handler. `f` is dropped when it is replaced, or with the `VTimer`.
+    pub fn set_handler_once<F>(&mut self, at: Duration, mut f: F) -> SceResult<()>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.set_handler(at, move |_| {
+            f();
+            None
+        })
+    }
+
+    /// Run `f` each time the timer advances by `period`, starting one period
+    /// from its current time. Replaces any previous handler.
+    pub fn set_periodic<F>(&mut self, period: Duration, mut f: F) -> SceResult<()>
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let at = self.time() + period;
+
+        self.set_handler(at, move |_| {
+            f();
+            Some(period)
+        })
+    }
+
+    /// Run `f` when the timer reaches `at`. `f` receives the scheduled time,
+    /// and returns how much further the timer must advance before it runs
+    /// again, or `None` to stop. Replaces any previous handler.
+    pub fn set_handler<F>(&mut self, at: Duration, f: F) -> SceResult<()>
+    where
+        F: FnMut(Duration) -> Option<Duration> + Send + 'static,
+    {
+        self.cancel_handler()?;
+
+        let handler: Box<VTimerHandler> = Box::new(Handler {
+            done: Cell::new(false),
+            f: Box::new(f),
+        });
+
+        let handler = Box::into_raw(handler);
+        let at = super::as_micros_u64(at) as i64;
+
+        let result = unsafe {
+            sys::sceKernelSetVTimerHandlerWide(self.uid, at, on_vtimer, handler as *mut c_void)
+        };
+
+        match result.into_result() {
+            Ok(_) => {
+                self.handler = Some(handler);
+                Ok(())
+            }
+            Err(e) => {
+                drop(unsafe { Box::from_raw(handler) });
+                Err(e)
+            }
+        }
+    }
+
+    /// Remove the handler, if any.
+    pub fn cancel_handler(&mut self) -> SceResult<()> {
+        if let Some(handler) = self.handler.take() {
+            unsafe {
+                if !(*handler).done.get() {
+                    if let Err(e) = sys::sceKernelCancelVTimerHandler(self.uid).into_result() {
+                        // Keep the handler alive, the kernel may still call it.
+                        self.handler = Some(handler);
+                        return Err(e);
+                    }
+                }
+
+                drop(Box::from_raw(handler));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for VTimer {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceKernelCancelVTimerHandler(self.uid);
+            sys::sceKernelDeleteVTimer(self.uid);
+        }
+
+        if let Some(handler) = self.handler.take() {
+            drop(unsafe { Box::from_raw(handler) });
+        }
+    }
+}
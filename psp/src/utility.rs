@@ -0,0 +1,20 @@
+//! Optional system modules.
+
+use crate::error::SceReturn;
+use crate::sys::{self, Module};
+use crate::{Error, SceResult};
+
+/// Load one of the optional modules, such as `Module::AvMp3`, which must be
+/// loaded before its functions can be called. Loading a module which is
+/// already loaded succeeds.
+pub fn load_module(module: Module) -> SceResult<()> {
+    match unsafe { sys::sceUtilityLoadModule(module) }.into_result() {
+        Ok(_) | Err(Error::SCE_UTILITY_ERROR_MODULE_ALREADY_LOADED) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Unload a module loaded with `load_module`.
+pub fn unload_module(module: Module) -> SceResult<()> {
+    unsafe { sys::sceUtilityUnloadModule(module) }.into_result().map(drop)
+}
@@ -0,0 +1,41 @@
+//! Video playback.
+//!
+//! `VideoPlayer` plays PMF files, the PSP's MPEG-PS container holding an AVC
+//! video track and usually an ATRAC3plus audio track, as used for game
+//! cutscenes and the animated `ICON1.PMF` shown in the XMB. Video is decoded on
+//! the Media Engine straight into a framebuffer or texture, and the audio plays
+//! on an audio channel of its own, kept in step with the video.
+//!
+//! `pmf` reads the header of a PMF file: its streams, their dimensions and
+//! its duration.
+//!
+//! ```no_run
+//! use core::ffi::c_void;
+//! use psp::audio::Playback;
+//! use psp::fs::File;
+//! use psp::sys::{self, DisplayPixelFormat, DisplaySetBufSync};
+//! use psp::video::VideoPlayer;
+//!
+//! let file = File::open("umd0:/PSP_GAME/USRDIR/MOVIE/intro.pmf").unwrap();
+//! let mut player = VideoPlayer::new(file, DisplayPixelFormat::Psm8888).unwrap();
+//!
+//! unsafe {
+//!     let framebuffer = sys::sceGeEdramGetAddr();
+//!     sys::sceDisplaySetFrameBuf(
+//!         framebuffer,
+//!         512,
+//!         DisplayPixelFormat::Psm8888,
+//!         DisplaySetBufSync::NextFrame,
+//!     );
+//!
+//!     while !player.is_finished() {
+//!         player.update(framebuffer as *mut c_void, 512).unwrap();
+//!         sys::sceDisplayWaitVblankStart();
+//!     }
+//! }
+//! ```
+
+mod player;
+pub mod pmf;
+
+pub use player::*;
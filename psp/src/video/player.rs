@@ -0,0 +1,769 @@
+use super::pmf::{PmfHeader, HEADER_SIZE};
+use crate::audio::{AudioChannel, AudioStream, Playback, PlaybackControl};
+use crate::cache::DmaBuffer;
+use crate::error::SceReturn;
+use crate::io::{self, Read, Seek, SeekFrom};
+use crate::sys::{
+    self, DisplayPixelFormat, Module, SceMpeg, SceMpegAu, SceMpegAvcMode, SceMpegRingbuffer,
+    SceMpegStream,
+};
+use crate::Error;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+use core::{mem, ptr, slice};
+
+/// The size of each packet of the stream, as the ringbuffer holds them.
+const PACKET_SIZE: usize = 2048;
+
+/// How many packets the ringbuffer holds, for 512 KiB.
+const RINGBUFFER_PACKETS: i32 = 256;
+
+/// The frame width the decoder is created with. Frames can still be decoded
+/// with any stride.
+const MAX_FRAME_WIDTH: i32 = 512;
+
+const VIDEO_STREAM: i32 = 0;
+const AUDIO_STREAM: i32 = 1;
+
+/// PMF audio is always ATRAC3plus at this rate, in stereo.
+const AUDIO_SAMPLE_RATE: u32 = 44100;
+
+/// Samples per channel in each buffer the audio channel plays.
+const AUDIO_BUFFER_FRAMES: usize = 1024;
+
+/// Stereo samples decoded ahead of the audio channel, about 0.75 seconds.
+const AUDIO_QUEUE_LEN: usize = 64 * 1024;
+
+/// The most frames decoded by one `update` while catching up.
+const MAX_CATCH_UP_FRAMES: usize = 4;
+
+const PTS_RATE: u64 = super::pmf::PTS_RATE as u64;
+
+trait Source: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> Source for T {}
+
+fn load_modules() -> io::Result<()> {
+    crate::utility::load_module(Module::AvCodec)?;
+    crate::utility::load_module(Module::AvMpegBase)?;
+    Ok(())
+}
+
+fn au_pts(au: &SceMpegAu) -> u64 {
+    (au.pts_msb as u64) << 32 | au.pts as u64
+}
+
+/// The part of the file after the header, which the ringbuffer callback reads
+/// from.
+struct Feed {
+    reader: Box<dyn Source>,
+    offset: u64,
+    size: u64,
+    remaining: u64,
+    error: Option<io::Error>,
+}
+
+impl Feed {
+    fn rewind(&mut self) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(self.offset))?;
+        self.remaining = self.size;
+        Ok(())
+    }
+
+    /// Read up to `packets` packets into `dst`, returning how many were read.
+    fn read_packets(&mut self, dst: *mut u8, packets: usize) -> io::Result<usize> {
+        let max = (packets * PACKET_SIZE) as u64;
+        let len = core::cmp::min(max, self.remaining) as usize;
+
+        if len == 0 {
+            return Ok(0);
+        }
+
+        // A short last packet is padded out to a whole one.
+        let padded = (len + PACKET_SIZE - 1) / PACKET_SIZE * PACKET_SIZE;
+        let buf = unsafe { slice::from_raw_parts_mut(dst, padded) };
+
+        self.reader.read_exact(&mut buf[..len])?;
+        self.remaining -= len as u64;
+
+        for byte in &mut buf[len..] {
+            *byte = 0;
+        }
+
+        crate::cache::writeback(buf);
+
+        Ok(padded / PACKET_SIZE)
+    }
+}
+
+unsafe extern "C" fn ringbuffer_callback(
+    data: *mut c_void,
+    packets: i32,
+    param: *mut c_void,
+) -> i32 {
+    let feed = &mut *(param as *mut Feed);
+
+    match feed.read_packets(data as *mut u8, packets as usize) {
+        Ok(n) => n as i32,
+        Err(e) => {
+            feed.error = Some(e);
+            -1
+        }
+    }
+}
+
+/// Keeps the library initialized while a player exists.
+struct Library {
+    /// Whether `init` started the library, rather than finding it already
+    /// started by someone else, who will finish it.
+    initialized: bool,
+}
+
+impl Library {
+    fn init() -> io::Result<Self> {
+        load_modules()?;
+
+        match unsafe { sys::sceMpegInit() }.into_result() {
+            Ok(_) => Ok(Library { initialized: true }),
+            Err(Error::SCE_MPEG_ERROR_ALREADY_INIT) => Ok(Library { initialized: false }),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        if self.initialized {
+            unsafe { sys::sceMpegFinish() };
+        }
+    }
+}
+
+struct Ringbuffer {
+    inner: Box<SceMpegRingbuffer>,
+    // Referenced by the ringbuffer until it is destructed.
+    _data: DmaBuffer<u8>,
+    feed: Box<Feed>,
+}
+
+impl Ringbuffer {
+    fn new(feed: Feed) -> io::Result<Self> {
+        let size =
+            unsafe { sys::sceMpegRingbufferQueryMemSize(RINGBUFFER_PACKETS) }.into_result()?;
+
+        let mut data = DmaBuffer::from_elem(0u8, size as usize);
+        let mut inner: Box<SceMpegRingbuffer> = Box::new(unsafe { mem::zeroed() });
+        let mut feed = Box::new(feed);
+
+        unsafe {
+            sys::sceMpegRingbufferConstruct(
+                &mut *inner,
+                RINGBUFFER_PACKETS,
+                data.as_device_ptr(),
+                size,
+                Some(ringbuffer_callback),
+                &mut *feed as *mut Feed as *mut c_void,
+            )
+        }
+        .into_result()?;
+
+        Ok(Self {
+            inner,
+            _data: data,
+            feed,
+        })
+    }
+
+    /// Read as much of the stream as fits.
+    fn fill(&mut self) -> io::Result<()> {
+        if self.feed.remaining == 0 {
+            return Ok(());
+        }
+
+        let available =
+            unsafe { sys::sceMpegRingbufferAvailableSize(&mut *self.inner) }.into_result()?;
+
+        if available > 0 {
+            let result =
+                unsafe { sys::sceMpegRingbufferPut(&mut *self.inner, available, available) };
+
+            if let Some(e) = self.feed.error.take() {
+                return Err(e);
+            }
+
+            result.into_result()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Ringbuffer {
+    fn drop(&mut self) {
+        unsafe { sys::sceMpegRingbufferDestruct(&mut *self.inner) };
+    }
+}
+
+/// A decoder instance, with the streams and buffers registered with it.
+struct Mpeg {
+    handle: Box<*mut c_void>,
+    _data: DmaBuffer<u8>,
+    streams: Vec<SceMpegStream>,
+    avc_es: *mut c_void,
+    // Dropped after the instance is deleted.
+    ringbuffer: Ringbuffer,
+}
+
+impl Mpeg {
+    fn new(mut ringbuffer: Ringbuffer) -> io::Result<Self> {
+        let size = unsafe { sys::sceMpegQueryMemSize(0) }.into_result()?;
+        let mut data = DmaBuffer::from_elem(0u8, size as usize);
+        let mut handle = Box::new(ptr::null_mut());
+
+        unsafe {
+            sys::sceMpegCreate(
+                SceMpeg::from_ptr(&mut *handle),
+                data.as_device_ptr(),
+                size,
+                &mut *ringbuffer.inner,
+                MAX_FRAME_WIDTH,
+                0,
+                0,
+            )
+        }
+        .into_result()?;
+
+        Ok(Self {
+            handle,
+            _data: data,
+            streams: Vec::new(),
+            avc_es: ptr::null_mut(),
+            ringbuffer,
+        })
+    }
+
+    fn handle(&mut self) -> SceMpeg {
+        SceMpeg::from_ptr(&mut *self.handle)
+    }
+
+    fn register(&mut self, id: i32) -> io::Result<SceMpegStream> {
+        let stream = unsafe { sys::sceMpegRegistStream(self.handle(), id, 0) };
+
+        if stream.is_null() {
+            return Err(Error::SCE_MPEG_ERROR_INVALID_VALUE.into());
+        }
+
+        self.streams.push(stream);
+        Ok(stream)
+    }
+}
+
+impl Drop for Mpeg {
+    fn drop(&mut self) {
+        let handle = self.handle();
+
+        unsafe {
+            for &stream in &self.streams {
+                sys::sceMpegUnRegistStream(handle, stream);
+            }
+
+            if !self.avc_es.is_null() {
+                sys::sceMpegFreeAvcEsBuf(handle, self.avc_es);
+            }
+
+            sys::sceMpegDelete(handle);
+        }
+    }
+}
+
+/// Decoded audio on its way from `update` to the audio thread.
+struct PcmQueue {
+    buf: UnsafeCell<Box<[i16]>>,
+    /// Counts of samples pushed and popped, which wrap around. Only `update`
+    /// pushes, and only the audio thread pops.
+    pushed: AtomicUsize,
+    popped: AtomicUsize,
+}
+
+// Each side only touches the part of the buffer the counts give it.
+unsafe impl Sync for PcmQueue {}
+
+impl PcmQueue {
+    fn new() -> Self {
+        Self {
+            buf: UnsafeCell::new(vec![0; AUDIO_QUEUE_LEN].into_boxed_slice()),
+            pushed: AtomicUsize::new(0),
+            popped: AtomicUsize::new(0),
+        }
+    }
+
+    fn buf(&self) -> *mut i16 {
+        unsafe { (*self.buf.get()).as_mut_ptr() }
+    }
+
+    fn free(&self) -> usize {
+        let queued = self
+            .pushed
+            .load(Ordering::Relaxed)
+            .wrapping_sub(self.popped.load(Ordering::Acquire));
+
+        AUDIO_QUEUE_LEN - queued
+    }
+
+    /// How many samples the audio thread has taken, which wraps around.
+    fn popped(&self) -> usize {
+        self.popped.load(Ordering::Acquire)
+    }
+
+    /// Queue all of `samples`, which must fit in `free()`.
+    fn push(&self, samples: &[i16]) {
+        let pushed = self.pushed.load(Ordering::Relaxed);
+
+        for (i, &sample) in samples.iter().enumerate() {
+            let index = pushed.wrapping_add(i) % AUDIO_QUEUE_LEN;
+            unsafe { *self.buf().add(index) = sample };
+        }
+
+        self.pushed
+            .store(pushed.wrapping_add(samples.len()), Ordering::Release);
+    }
+
+    /// Drop everything queued, once the audio thread has stopped.
+    fn clear(&self) {
+        let pushed = self.pushed.load(Ordering::Relaxed);
+        self.popped.store(pushed, Ordering::Release);
+    }
+
+    /// Take as many samples as are queued and fit in `out`, returning how
+    /// many were taken.
+    fn pop(&self, out: &mut [i16]) -> usize {
+        let popped = self.popped.load(Ordering::Relaxed);
+        let queued = self.pushed.load(Ordering::Acquire).wrapping_sub(popped);
+        let n = core::cmp::min(queued, out.len());
+
+        for (i, sample) in out[..n].iter_mut().enumerate() {
+            let index = popped.wrapping_add(i) % AUDIO_QUEUE_LEN;
+            *sample = unsafe { *self.buf().add(index) };
+        }
+
+        self.popped.store(popped.wrapping_add(n), Ordering::Release);
+        n
+    }
+}
+
+/// Plays a PMF video, streamed from a reader such as a `File`.
+///
+/// Frames are decoded by `update`, which should be called once per vertical
+/// blank. It shows each frame when its timestamp comes due, and decodes
+/// several at once to catch up if it falls behind. The audio track, if
+/// there is one, is played on a channel of its own, reserved once the first
+/// audio is decoded, and frames are timed by how much of it has played so
+/// the two stay in sync. Without audio, or once it runs out, frames are
+/// timed by the system clock.
+///
+/// Only one player can exist at a time, as they share the `sceMpeg`
+/// library.
+pub struct VideoPlayer {
+    header: Vec<u8>,
+    video: SceMpegStream,
+    audio: SceMpegStream,
+    video_au: SceMpegAu,
+    audio_au: SceMpegAu,
+    /// Whether `video_au` holds a frame which is not yet due.
+    video_pending: bool,
+    // Referenced by `audio_au`.
+    atrac_es: DmaBuffer<u8>,
+    pcm: DmaBuffer<i16>,
+    audio_started: bool,
+    queue: Arc<PcmQueue>,
+    stream: Option<AudioStream>,
+    /// The timestamp of the first audio queued since the stream started,
+    /// and the queue's `popped` count when it started.
+    audio_pts: Option<u64>,
+    audio_base: usize,
+    control: PlaybackControl,
+    looping: bool,
+    first_pts: Option<u64>,
+    position: u64,
+    /// When the first frame was shown, in microseconds of system time, moved
+    /// forward by the time spent paused.
+    start: Option<i64>,
+    paused_at: Option<i64>,
+    // Dropped after the audio stream stops, and before the library.
+    mpeg: Mpeg,
+    _library: Library,
+}
+
+// The buffers and handles are only touched through the player.
+unsafe impl Send for VideoPlayer {}
+
+impl VideoPlayer {
+    /// Open a PMF file from `reader`, to be decoded in `format`.
+    ///
+    /// Fails with `InvalidData` if it doesn't start with a PMF header.
+    pub fn new<R>(mut reader: R, format: DisplayPixelFormat) -> io::Result<Self>
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        let mut header = vec![0u8; HEADER_SIZE];
+        reader.read_exact(&mut header).map_err(|e| match e {
+            io::Error::UnexpectedEof => io::Error::InvalidData,
+            e => e,
+        })?;
+
+        PmfHeader::parse(&header).map_err(|_| io::Error::InvalidData)?;
+
+        let library = Library::init()?;
+
+        let feed = Feed {
+            reader: Box::new(reader),
+            offset: 0,
+            size: 0,
+            remaining: 0,
+            error: None,
+        };
+
+        let mut mpeg = Mpeg::new(Ringbuffer::new(feed)?)?;
+
+        let mut offset = 0;
+        let mut size = 0;
+        let header_ptr = header.as_mut_ptr() as *mut c_void;
+
+        unsafe { sys::sceMpegQueryStreamOffset(mpeg.handle(), header_ptr, &mut offset) }
+            .into_result()?;
+        unsafe { sys::sceMpegQueryStreamSize(header_ptr, &mut size) }.into_result()?;
+
+        let feed = &mut mpeg.ringbuffer.feed;
+        feed.offset = offset as u64;
+        feed.size = size as u64;
+        feed.rewind()?;
+
+        let video = mpeg.register(VIDEO_STREAM)?;
+        let audio = mpeg.register(AUDIO_STREAM)?;
+
+        mpeg.avc_es = unsafe { sys::sceMpegMallocAvcEsBuf(mpeg.handle()) };
+
+        if mpeg.avc_es.is_null() {
+            return Err(Error::SCE_MPEG_ERROR_NO_MEMORY.into());
+        }
+
+        let mut es_size = 0;
+        let mut out_size = 0;
+
+        unsafe { sys::sceMpegQueryAtracEsSize(mpeg.handle(), &mut es_size, &mut out_size) }
+            .into_result()?;
+
+        let mut player = Self {
+            header,
+            video,
+            audio,
+            video_au: unsafe { mem::zeroed() },
+            audio_au: unsafe { mem::zeroed() },
+            video_pending: false,
+            atrac_es: DmaBuffer::from_elem(0, es_size as usize),
+            pcm: DmaBuffer::from_elem(0, out_size as usize / 2),
+            audio_started: false,
+            queue: Arc::new(PcmQueue::new()),
+            stream: None,
+            audio_pts: None,
+            audio_base: 0,
+            control: PlaybackControl::new(),
+            looping: false,
+            first_pts: None,
+            position: 0,
+            start: None,
+            paused_at: None,
+            mpeg,
+            _library: library,
+        };
+
+        let handle = player.mpeg.handle();
+        let atrac_es = player.atrac_es.as_device_ptr();
+
+        unsafe {
+            sys::sceMpegInitAu(handle, player.mpeg.avc_es, &mut player.video_au).into_result()?;
+            sys::sceMpegInitAu(handle, atrac_es, &mut player.audio_au).into_result()?;
+
+            let mut mode = SceMpegAvcMode {
+                unk0: -1,
+                pixel_format: format,
+            };
+
+            sys::sceMpegAvcDecodeMode(handle, &mut mode).into_result()?;
+        }
+
+        player.mpeg.ringbuffer.fill()?;
+
+        Ok(player)
+    }
+
+    /// Decode the frames which have come due into `buffer`, returning whether
+    /// it now holds a new frame. Does nothing while paused or once finished.
+    ///
+    /// `stride` is the width of `buffer` in pixels, e.g. 512 for the
+    /// framebuffer.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be valid for writes of `stride` pixels, in the player's
+    /// pixel format, for each row of the video. It is written by the Media
+    /// Engine, so it should be in VRAM, or have its cache lines invalidated
+    /// before being read back by the CPU.
+    pub unsafe fn update(&mut self, buffer: *mut c_void, stride: u32) -> io::Result<bool> {
+        if self.control.take_restart() {
+            self.rewind()?;
+        }
+
+        if self.control.is_finished() {
+            return Ok(false);
+        }
+
+        let now = sys::sceKernelGetSystemTimeWide();
+
+        if self.control.is_paused() {
+            self.paused_at.get_or_insert(now);
+            return Ok(false);
+        }
+
+        if let Some(paused_at) = self.paused_at.take() {
+            if let Some(start) = &mut self.start {
+                *start += now - paused_at;
+            }
+        }
+
+        self.mpeg.ringbuffer.fill()?;
+        self.decode_audio()?;
+
+        let mut shown = false;
+
+        for _ in 0..MAX_CATCH_UP_FRAMES {
+            if !self.video_pending && !self.next_video_au()? {
+                if self.mpeg.ringbuffer.feed.remaining == 0 {
+                    shown |= self.end(buffer, stride)?;
+                }
+
+                break;
+            }
+
+            let pts = au_pts(&self.video_au);
+            let first = *self.first_pts.get_or_insert(pts);
+
+            if pts > self.clock(now, first) {
+                break;
+            }
+
+            let mut buffer = buffer;
+            let mut frame = 0;
+
+            sys::sceMpegAvcDecode(
+                self.mpeg.handle(),
+                &mut self.video_au,
+                stride as i32,
+                &mut buffer as *mut *mut c_void as *mut c_void,
+                &mut frame,
+            )
+            .into_result()?;
+
+            self.video_pending = false;
+            self.position = pts.saturating_sub(first);
+            shown |= frame != 0;
+        }
+
+        Ok(shown)
+    }
+
+    /// The timestamp playback has reached: that of the audio being played,
+    /// or, without any, `first` plus the system time since the first frame.
+    fn clock(&mut self, now: i64, first: u64) -> u64 {
+        let audio_pts = match self.audio_pts {
+            Some(pts) if self.stream.is_some() && self.queue.free() < AUDIO_QUEUE_LEN => pts,
+            _ => {
+                let start = *self.start.get_or_insert(now);
+                return first + (now - start) as u64 * PTS_RATE / 1_000_000;
+            }
+        };
+
+        let frames = self.queue.popped().wrapping_sub(self.audio_base) / 2;
+        let clock = audio_pts + frames as u64 * PTS_RATE / AUDIO_SAMPLE_RATE as u64;
+
+        // Keep the system clock in step, to carry on from if the audio runs
+        // out before the video.
+        let elapsed = clock.saturating_sub(first) * 1_000_000 / PTS_RATE;
+        self.start = Some(now - elapsed as i64);
+
+        clock
+    }
+
+    /// Fetch the next video access unit, returning `false` if none is
+    /// available yet.
+    fn next_video_au(&mut self) -> io::Result<bool> {
+        let mut unused = 0;
+
+        let result = unsafe {
+            sys::sceMpegGetAvcAu(
+                self.mpeg.handle(),
+                self.video,
+                &mut self.video_au,
+                &mut unused,
+            )
+        };
+
+        match result.into_result() {
+            Ok(_) => {
+                self.video_pending = true;
+                Ok(true)
+            }
+            Err(Error::SCE_MPEG_ERROR_NO_DATA) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Decode audio until the queue is full or the ringbuffer runs dry.
+    fn decode_audio(&mut self) -> io::Result<()> {
+        while self.queue.free() >= self.pcm.len() {
+            let handle = self.mpeg.handle();
+            let mut attr = 0i32;
+
+            let result = unsafe {
+                sys::sceMpegGetAtracAu(
+                    handle,
+                    self.audio,
+                    &mut self.audio_au,
+                    &mut attr as *mut i32 as *mut c_void,
+                )
+            };
+
+            match result.into_result() {
+                Ok(_) => (),
+                Err(Error::SCE_MPEG_ERROR_NO_DATA) => break,
+                Err(e) => return Err(e.into()),
+            }
+
+            let init = if self.audio_started { 0 } else { 1 };
+
+            unsafe {
+                sys::sceMpegAtracDecode(handle, &mut self.audio_au, self.pcm.as_device_ptr(), init)
+            }
+            .into_result()?;
+
+            self.audio_started = true;
+            self.audio_pts.get_or_insert(au_pts(&self.audio_au));
+            self.pcm.sync_for_cpu();
+            self.queue.push(&self.pcm);
+
+            if self.stream.is_none() {
+                self.audio_base = self.queue.popped();
+                self.stream = Some(self.start_audio()?);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn start_audio(&self) -> io::Result<AudioStream> {
+        let channel = AudioChannel::reserve_for_rate(AUDIO_SAMPLE_RATE, AUDIO_BUFFER_FRAMES)?;
+        let queue = self.queue.clone();
+        let control = self.control.clone();
+
+        let stream = AudioStream::start(channel, move |buffer| {
+            let n = if control.is_paused() {
+                0
+            } else {
+                queue.pop(buffer)
+            };
+
+            for sample in &mut buffer[n..] {
+                *sample = 0;
+            }
+        })?;
+
+        Ok(stream)
+    }
+
+    /// Flush the frames still in the decoder into `buffer` at the end of the
+    /// video, then loop or finish.
+    unsafe fn end(&mut self, buffer: *mut c_void, stride: u32) -> io::Result<bool> {
+        let mut buffer = buffer;
+        let mut frames = 0;
+
+        sys::sceMpegAvcDecodeStop(
+            self.mpeg.handle(),
+            stride as i32,
+            &mut buffer as *mut *mut c_void as *mut c_void,
+            &mut frames,
+        )
+        .into_result()?;
+
+        if self.looping {
+            self.rewind()?;
+        } else {
+            self.control.set_finished(true);
+        }
+
+        Ok(frames > 0)
+    }
+
+    /// Stop the audio thread and drop the audio it hasn't played yet.
+    fn stop_audio(&mut self) {
+        self.stream = None;
+        self.queue.clear();
+        self.audio_pts = None;
+    }
+
+    fn rewind(&mut self) -> io::Result<()> {
+        // The rest of the old audio would play out of step with the new
+        // video, so the audio starts over with the first newly decoded.
+        self.stop_audio();
+        self.audio_started = false;
+
+        unsafe { sys::sceMpegFlushAllStream(self.mpeg.handle()) }.into_result()?;
+
+        self.mpeg.ringbuffer.feed.rewind()?;
+        self.mpeg.ringbuffer.fill()?;
+
+        self.video_pending = false;
+        self.first_pts = None;
+        self.position = 0;
+        self.start = None;
+        self.control.set_finished(false);
+
+        Ok(())
+    }
+
+    /// The file's header, with its streams, dimensions and duration.
+    pub fn header(&self) -> PmfHeader<'_> {
+        // Checked in `new`.
+        PmfHeader::parse(&self.header).unwrap()
+    }
+
+    /// Play from the start again after the end, e.g. for an XMB icon,
+    /// instead of finishing.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// Stop playing straight away, e.g. when a cutscene is skipped. The
+    /// player is then finished, until it is restarted.
+    pub fn skip(&mut self) {
+        self.stop_audio();
+        self.video_pending = false;
+        self.control.set_finished(true);
+    }
+
+    /// The timestamp of the last frame shown, from the start of the video.
+    pub fn position(&self) -> Duration {
+        Duration::from_micros(self.position * 1_000_000 / PTS_RATE)
+    }
+}
+
+impl Playback for VideoPlayer {
+    fn playback_control(&self) -> &PlaybackControl {
+        &self.control
+    }
+}
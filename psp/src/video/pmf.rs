@@ -0,0 +1,306 @@
+//! A parser for PMF headers.
+//!
+//! This only uses `core`, so host tools can include it as it is: cargo-psp
+//! checks `xmb_icon_pmf` with it when packaging, and `ci/host_tests` tests it.
+//! What cargo-psp doesn't use is marked `allow(dead_code)`, for its build.
+
+use core::fmt;
+use core::time::Duration;
+
+/// The size of a PMF header.
+#[allow(dead_code)]
+pub const HEADER_SIZE: usize = 2048;
+
+/// Timestamps count at 90 kHz.
+pub const PTS_RATE: u32 = 90_000;
+
+const STREAMS_OFFSET: usize = 0x82;
+const STREAM_INFO_SIZE: usize = 16;
+const EP_ENTRY_SIZE: usize = 10;
+
+/// How many video timestamps `frame_duration` looks at.
+const SCAN_FRAMES: usize = 32;
+
+/// An error from parsing a PMF header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PmfError {
+    /// The data doesn't start with a PMF header.
+    NotPmf,
+    /// The header ends early, or points past its end.
+    Truncated,
+}
+
+impl fmt::Display for PmfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PmfError::NotPmf => write!(f, "not a PMF file"),
+            PmfError::Truncated => write!(f, "truncated PMF header"),
+        }
+    }
+}
+
+/// A stream listed in the header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PmfStream {
+    Avc {
+        channel: u8,
+        width: u32,
+        height: u32,
+    },
+    Atrac {
+        channel: u8,
+        channels: u8,
+        sample_rate: Option<u32>,
+    },
+    Pcm {
+        channel: u8,
+        channels: u8,
+        sample_rate: Option<u32>,
+    },
+}
+
+/// An entry point in the video stream, where decoding can start.
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EpEntry {
+    /// Presentation timestamp, at 90 kHz.
+    pub pts: u32,
+    /// Offset into the stream, in packets of `HEADER_SIZE` bytes.
+    pub offset: u32,
+}
+
+fn be_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn be_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut word = [0; 4];
+    word.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_be_bytes(word)
+}
+
+/// A 48-bit timestamp.
+#[allow(dead_code)]
+fn be_u48(bytes: &[u8], offset: usize) -> u64 {
+    (be_u16(bytes, offset) as u64) << 32 | be_u32(bytes, offset + 2) as u64
+}
+
+fn sample_rate(code: u8) -> Option<u32> {
+    match code {
+        2 => Some(44100),
+        _ => None,
+    }
+}
+
+/// A parsed PMF header, borrowing the bytes it was parsed from.
+#[derive(Debug, Copy, Clone)]
+pub struct PmfHeader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> PmfHeader<'a> {
+    /// Parse the header at the start of `bytes`, which may also hold the
+    /// rest of the file.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, PmfError> {
+        if bytes.len() < 8 {
+            return Err(PmfError::Truncated);
+        }
+
+        if &bytes[..4] != b"PSMF" || !bytes[4..8].iter().all(u8::is_ascii_digit) {
+            return Err(PmfError::NotPmf);
+        }
+
+        if bytes.len() < STREAMS_OFFSET {
+            return Err(PmfError::Truncated);
+        }
+
+        let header = Self { bytes };
+        let count = be_u16(bytes, STREAMS_OFFSET - 2) as usize;
+
+        if bytes.len() < STREAMS_OFFSET + count * STREAM_INFO_SIZE {
+            return Err(PmfError::Truncated);
+        }
+
+        header.ep_map_range()?;
+        Ok(header)
+    }
+
+    /// The format version, e.g. 15 for `"0015"`.
+    #[allow(dead_code)]
+    pub fn version(&self) -> u32 {
+        self.bytes[4..8]
+            .iter()
+            .fold(0, |version, digit| version * 10 + (digit - b'0') as u32)
+    }
+
+    /// Where the stream starts in the file, in bytes.
+    pub fn stream_offset(&self) -> u32 {
+        be_u32(self.bytes, 8)
+    }
+
+    /// The size of the stream, in bytes.
+    pub fn stream_size(&self) -> u32 {
+        be_u32(self.bytes, 12)
+    }
+
+    /// The first presentation timestamp, at 90 kHz.
+    #[allow(dead_code)]
+    pub fn start_pts(&self) -> u64 {
+        be_u48(self.bytes, 0x54)
+    }
+
+    /// The last presentation timestamp, at 90 kHz.
+    #[allow(dead_code)]
+    pub fn end_pts(&self) -> u64 {
+        be_u48(self.bytes, 0x5a)
+    }
+
+    #[allow(dead_code)]
+    pub fn duration(&self) -> Duration {
+        let pts = self.end_pts().saturating_sub(self.start_pts());
+        let rate = PTS_RATE as u64;
+
+        // Whole seconds first, as 48-bit timestamps in microseconds overflow.
+        Duration::from_secs(pts / rate) + Duration::from_micros(pts % rate * 1_000_000 / rate)
+    }
+
+    fn stream_infos(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let count = be_u16(self.bytes, STREAMS_OFFSET - 2) as usize;
+        let end = STREAMS_OFFSET + count * STREAM_INFO_SIZE;
+
+        self.bytes[STREAMS_OFFSET..end].chunks(STREAM_INFO_SIZE)
+    }
+
+    /// The streams the file holds. Streams of unknown types are left out.
+    pub fn streams(&self) -> impl Iterator<Item = PmfStream> + 'a {
+        self.stream_infos()
+            .filter_map(|info| match (info[0], info[1]) {
+                (0xe0..=0xef, _) => Some(PmfStream::Avc {
+                    channel: info[0] & 0xf,
+                    width: info[12] as u32 * 16,
+                    height: info[13] as u32 * 16,
+                }),
+                (0xbd, 0x00..=0x0f) => Some(PmfStream::Atrac {
+                    channel: info[1],
+                    channels: info[14],
+                    sample_rate: sample_rate(info[15]),
+                }),
+                (0xbd, _) => Some(PmfStream::Pcm {
+                    channel: info[1] & 0xf,
+                    channels: info[14],
+                    sample_rate: sample_rate(info[15]),
+                }),
+                _ => None,
+            })
+    }
+
+    /// The width and height of the first video stream.
+    pub fn video_size(&self) -> Option<(u32, u32)> {
+        self.streams().find_map(|stream| match stream {
+            PmfStream::Avc { width, height, .. } => Some((width, height)),
+            _ => None,
+        })
+    }
+
+    /// Where the first video stream's EP map starts and ends, if there is a
+    /// video stream. Fails with `Truncated` if it ends past the header.
+    fn ep_map_range(&self) -> Result<Option<(usize, usize)>, PmfError> {
+        let info = match self.stream_infos().find(|info| info[0] & 0xf0 == 0xe0) {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+
+        let offset = be_u32(info, 4) as usize;
+        let entries = be_u32(info, 8) as usize;
+
+        entries
+            .checked_mul(EP_ENTRY_SIZE)
+            .and_then(|size| offset.checked_add(size))
+            .filter(|&end| end <= self.bytes.len())
+            .map(|end| Some((offset, end)))
+            .ok_or(PmfError::Truncated)
+    }
+
+    /// The first video stream's entry points, in order.
+    #[allow(dead_code)]
+    pub fn ep_map(&self) -> impl Iterator<Item = EpEntry> + 'a {
+        // Checked in `parse`.
+        let (start, end) = self.ep_map_range().ok().flatten().unwrap_or((0, 0));
+        let bytes = &self.bytes[start..end];
+
+        bytes.chunks(EP_ENTRY_SIZE).map(|entry| EpEntry {
+            pts: be_u32(entry, 2),
+            offset: be_u32(entry, 6),
+        })
+    }
+}
+
+/// The presentation timestamp in the PES packet header at the start of
+/// `packet`, if it has one.
+fn pes_pts(packet: &[u8]) -> Option<u64> {
+    if packet.len() < 14 || packet[6] & 0xc0 != 0x80 || packet[7] & 0x80 == 0 {
+        return None;
+    }
+
+    // 33 bits, split up by marker bits.
+    let pts = &packet[9..14];
+    let value = (((pts[0] >> 1) & 7) as u64) << 30
+        | (pts[1] as u64) << 22
+        | ((pts[2] >> 1) as u64) << 15
+        | (pts[3] as u64) << 7
+        | (pts[4] >> 1) as u64;
+
+    Some(value)
+}
+
+/// Work out the time between frames, at 90 kHz, from the timestamps of the
+/// first frames of the first video stream. `stream` is the data from
+/// `stream_offset()`; a few packets are enough.
+///
+/// 29.97 fps video gives 3003.
+pub fn frame_duration(stream: &[u8]) -> Option<u32> {
+    let mut pts = [0u64; SCAN_FRAMES];
+    let mut found = 0;
+    let mut pos = 0;
+
+    while pos + 6 <= stream.len() && found < SCAN_FRAMES {
+        if stream[pos..pos + 3] != [0, 0, 1] {
+            pos += 1;
+            continue;
+        }
+
+        match stream[pos + 3] {
+            // A pack header, either MPEG-2 with stuffing or MPEG-1.
+            0xba if pos + 14 <= stream.len() && stream[pos + 4] >> 6 == 1 => {
+                pos += 14 + (stream[pos + 13] & 7) as usize;
+            }
+            0xba => pos += 12,
+            0xb9 => break,
+            id if id >= 0xbb => {
+                let len = be_u16(stream, pos + 4) as usize;
+                let end = core::cmp::min(pos + 6 + len, stream.len());
+
+                if id == 0xe0 {
+                    if let Some(value) = pes_pts(&stream[pos..end]) {
+                        pts[found] = value;
+                        found += 1;
+                    }
+                }
+
+                pos += 6 + len;
+            }
+            _ => pos += 4,
+        }
+    }
+
+    // Frames may be stored out of order, so look at the smallest gap between
+    // sorted timestamps.
+    let pts = &mut pts[..found];
+    pts.sort_unstable();
+
+    pts.windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .filter(|&gap| gap > 0)
+        .min()
+        .map(|gap| gap as u32)
+}
@@ -1,32 +1,94 @@
+//! A VRAM allocator.
+//!
+//! VRAM is managed with a free list of offsets, kept in main memory. Chunks
+//! are returned to the allocator when they are dropped, so textures for a
+//! level can be released and replaced by the next level's set.
+
 use crate::sys::TexturePixelFormat;
-use crate::sys::{sceGeEdramGetAddr, sceGeEdramGetSize};
-use core::mem::size_of;
+use crate::sys::{self, sceGeEdramGetAddr, sceGeEdramGetSize};
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::mem::{align_of, size_of};
+use core::ops::{Deref, DerefMut};
 use core::ptr::null_mut;
+use core::{cmp, fmt};
 
-type VramAllocator = SimpleVramAllocator;
+/// Default alignment of VRAM allocations, in bytes.
+pub const DEFAULT_ALIGNMENT: u32 = 16;
 
-#[derive(Debug)]
-pub struct VramAllocatorInUseError {}
+/// Alignment required by the GE for texture data, in bytes.
+pub const TEXTURE_ALIGNMENT: u32 = 16;
 
-static mut VRAM_ALLOCATOR: VramAllocatorSingleton = VramAllocatorSingleton {
-    alloc: Some(VramAllocator::new()),
-};
+/// An error returned when allocating VRAM.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VramAllocError {
+    /// No free range is large enough.
+    OutOfMemory {
+        /// The requested size, in bytes.
+        requested: u32,
+        /// The largest free range, in bytes.
+        largest_free: u32,
+    },
+    /// The alignment was not a power of two.
+    InvalidAlignment(u32),
+}
 
-pub fn get_vram_allocator() -> Result<VramAllocator, VramAllocatorInUseError> {
-    let opt_alloc = unsafe { VRAM_ALLOCATOR.get_vram_alloc() };
-    opt_alloc.ok_or(VramAllocatorInUseError {})
+impl fmt::Display for VramAllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VramAllocError::OutOfMemory { requested, largest_free } => write!(
+                f,
+                "out of VRAM: requested {} bytes, largest free range is {} bytes",
+                requested, largest_free,
+            ),
+            VramAllocError::InvalidAlignment(align) => {
+                write!(f, "VRAM alignment {} is not a power of two", align)
+            }
+        }
+    }
 }
 
-pub struct VramAllocatorSingleton {
-    alloc: Option<VramAllocator>,
+/// VRAM usage statistics.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VramStats {
+    /// Total VRAM size, in bytes.
+    pub total: u32,
+    /// Bytes held by live chunks, including alignment padding.
+    pub used: u32,
+    /// Bytes not held by any chunk.
+    pub free: u32,
+    /// Size of the largest free range, in bytes.
+    pub largest_free: u32,
+    /// Number of separate free ranges.
+    pub free_ranges: u32,
 }
 
-impl VramAllocatorSingleton {
-    pub fn get_vram_alloc(&mut self) -> Option<VramAllocator> {
-        self.alloc.take()
+impl VramStats {
+    /// How fragmented free VRAM is, from 0 (a single free range) to 1.
+    ///
+    /// This is the fraction of free memory which lies outside the largest free
+    /// range, i.e. which can't be used for one large allocation.
+    pub fn fragmentation(&self) -> f32 {
+        if self.free == 0 {
+            0.0
+        } else {
+            1.0 - self.largest_free as f32 / self.free as f32
+        }
     }
 }
 
+static VRAM_ALLOCATOR: VramAllocator = VramAllocator {
+    free_list: UnsafeCell::new(FreeList::new()),
+};
+
+/// Get the VRAM allocator.
+pub fn get_vram_allocator() -> &'static VramAllocator {
+    &VRAM_ALLOCATOR
+}
+
+/// A range of VRAM. It is freed when dropped.
+#[derive(Debug)]
 pub struct VramMemChunk {
     start: u32,
     len: u32,
@@ -37,10 +99,13 @@ impl VramMemChunk {
         Self { start, len }
     }
 
+    /// The address of this chunk, relative to the start of VRAM. This is the
+    /// form the `sceGu*` buffer functions expect.
     pub fn as_mut_ptr_from_zero(&self) -> *mut u8 {
         unsafe { vram_start_addr_zero().add(self.start as usize) }
     }
 
+    /// The absolute address of this chunk.
     pub fn as_mut_ptr_direct_to_vram(&self) -> *mut u8 {
         unsafe { vram_start_addr_direct().add(self.start as usize) }
     }
@@ -48,64 +113,259 @@ impl VramMemChunk {
     pub fn len(&self) -> u32 {
         self.len
     }
+
+    /// Keep this chunk allocated forever, e.g. for framebuffers which live for
+    /// the rest of the program.
+    pub fn leak(self) -> *mut u8 {
+        let ptr = self.as_mut_ptr_from_zero();
+        core::mem::forget(self);
+        ptr
+    }
 }
 
-// A dead-simple VRAM bump allocator.
-// TODO: pin?
-#[derive(Debug)]
-pub struct SimpleVramAllocator {
-    offset: u32,
+impl Drop for VramMemChunk {
+    fn drop(&mut self) {
+        VRAM_ALLOCATOR.with_free_list(|list| list.free(self.start, self.len));
+    }
 }
 
-impl SimpleVramAllocator {
-    const fn new() -> Self {
-        Self { offset: 0 }
+/// A value stored in VRAM, dropped and freed along with this box.
+pub struct VramBox<T> {
+    chunk: VramMemChunk,
+    _marker: PhantomData<T>,
+}
+
+impl<T> VramBox<T> {
+    pub fn chunk(&self) -> &VramMemChunk {
+        &self.chunk
     }
+}
 
-    // TODO: return a Result instead of panicking
-    pub fn alloc(&mut self, size: u32) -> VramMemChunk {
-        let old_offset = self.offset;
-        self.offset += size;
+impl<T> Deref for VramBox<T> {
+    type Target = T;
 
-        if self.offset > self.total_mem() {
-            panic!("Total VRAM size exceeded!");
+    fn deref(&self) -> &T {
+        unsafe { &*(self.chunk.as_mut_ptr_direct_to_vram() as *const T) }
+    }
+}
+
+impl<T> DerefMut for VramBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *(self.chunk.as_mut_ptr_direct_to_vram() as *mut T) }
+    }
+}
+
+impl<T> Drop for VramBox<T> {
+    fn drop(&mut self) {
+        unsafe { (self.chunk.as_mut_ptr_direct_to_vram() as *mut T).drop_in_place() }
+    }
+}
+
+/// A first-fit VRAM allocator over a sorted list of free ranges.
+pub struct VramAllocator {
+    free_list: UnsafeCell<FreeList>,
+}
+
+unsafe impl Sync for VramAllocator {}
+
+impl VramAllocator {
+    fn with_free_list<R>(&self, f: impl FnOnce(&mut FreeList) -> R) -> R {
+        unsafe {
+            let flags = sys::sceKernelCpuSuspendIntr();
+            let list = &mut *self.free_list.get();
+
+            if !list.initialized {
+                list.init(total_vram_size());
+            }
+
+            let result = f(list);
+            sys::sceKernelCpuResumeIntr(flags);
+
+            result
+        }
+    }
+
+    /// Allocate `size` bytes, aligned to `DEFAULT_ALIGNMENT`.
+    pub fn alloc(&self, size: u32) -> Result<VramMemChunk, VramAllocError> {
+        self.alloc_aligned(size, DEFAULT_ALIGNMENT)
+    }
+
+    /// Allocate `size` bytes with the given alignment, which must be a power
+    /// of two.
+    pub fn alloc_aligned(&self, size: u32, align: u32) -> Result<VramMemChunk, VramAllocError> {
+        if !align.is_power_of_two() {
+            return Err(VramAllocError::InvalidAlignment(align));
         }
 
-        VramMemChunk::new(old_offset, size)
+        // Keep every range a multiple of the default alignment, so the free
+        // list does not fill up with unusable slivers.
+        let size = round_up(cmp::max(size, 1), DEFAULT_ALIGNMENT)
+            .ok_or_else(|| self.out_of_memory(size))?;
+        let align = cmp::max(align, DEFAULT_ALIGNMENT);
+
+        self.with_free_list(|list| list.alloc(size, align))
+            .map(|start| VramMemChunk::new(start, size))
     }
 
-    // TODO: ensure 16-bit alignment?
-    pub fn alloc_sized<T: Sized>(&mut self, count: u32) -> VramMemChunk {
-        let size = size_of::<T>() as u32;
-        self.alloc(count * size)
+    /// Allocate space for `count` values of type `T`.
+    pub fn alloc_sized<T: Sized>(&self, count: u32) -> Result<VramMemChunk, VramAllocError> {
+        let size = (size_of::<T>() as u32)
+            .checked_mul(count)
+            .ok_or_else(|| self.out_of_memory(u32::MAX))?;
+
+        self.alloc_aligned(size, align_of::<T>() as u32)
     }
 
+    /// Allocate a texture or framebuffer of the given dimensions and format.
     pub fn alloc_texture_pixels(
-        &mut self,
+        &self,
         width: u32,
         height: u32,
         psm: TexturePixelFormat,
-    ) -> VramMemChunk {
-        let size = get_memory_size(width, height, psm);
-        self.alloc(size)
+    ) -> Result<VramMemChunk, VramAllocError> {
+        let size =
+            get_memory_size(width, height, psm).ok_or_else(|| self.out_of_memory(u32::MAX))?;
+
+        self.alloc_aligned(size, TEXTURE_ALIGNMENT)
     }
 
-    // TODO: write, or write_volatile?
-    // TODO: result instead of unwrap?
-    // TODO: Keep track of the allocated chunk
-    // TODO: determine unsafety of this
-    pub unsafe fn move_to_vram<T: Sized>(&mut self, obj: T) -> &mut T {
-        let chunk = self.alloc_sized::<T>(1);
-        let ptr = chunk.as_mut_ptr_direct_to_vram() as *mut T;
-        ptr.write(obj);
-        ptr.as_mut().unwrap()
+    /// Move a value into VRAM.
+    pub fn move_to_vram<T: Sized>(&self, obj: T) -> Result<VramBox<T>, VramAllocError> {
+        let chunk = self.alloc_sized::<T>(1)?;
+
+        unsafe {
+            (chunk.as_mut_ptr_direct_to_vram() as *mut T).write(obj);
+        }
+
+        Ok(VramBox { chunk, _marker: PhantomData })
+    }
+
+    /// Get current usage statistics.
+    pub fn stats(&self) -> VramStats {
+        self.with_free_list(|list| list.stats())
+    }
+
+    /// The error for a request which can't be met, including one whose size
+    /// overflows, given as `u32::MAX`.
+    fn out_of_memory(&self, requested: u32) -> VramAllocError {
+        VramAllocError::OutOfMemory {
+            requested,
+            largest_free: self.with_free_list(|list| list.largest_free()),
+        }
+    }
+}
+
+/// Free ranges of VRAM, as `(offset, len)` pairs sorted by offset. Adjacent
+/// ranges are always merged.
+struct FreeList {
+    ranges: Vec<(u32, u32)>,
+    total: u32,
+    initialized: bool,
+}
+
+impl FreeList {
+    const fn new() -> Self {
+        Self {
+            ranges: Vec::new(),
+            total: 0,
+            initialized: false,
+        }
+    }
+
+    fn init(&mut self, total: u32) {
+        self.ranges.push((0, total));
+        self.total = total;
+        self.initialized = true;
+    }
+
+    fn alloc(&mut self, size: u32, align: u32) -> Result<u32, VramAllocError> {
+        for i in 0..self.ranges.len() {
+            let (start, len) = self.ranges[i];
+
+            let aligned = match round_up(start, align) {
+                Some(aligned) => aligned,
+                None => continue,
+            };
+
+            let padding = aligned - start;
+
+            if padding.saturating_add(size) > len {
+                continue;
+            }
+
+            let tail_start = aligned + size;
+            let tail_len = start + len - tail_start;
+
+            // Keep the padding in front of the chunk and whatever is left
+            // behind it as separate free ranges.
+            match (padding > 0, tail_len > 0) {
+                (false, false) => {
+                    self.ranges.remove(i);
+                }
+                (true, false) => self.ranges[i] = (start, padding),
+                (false, true) => self.ranges[i] = (tail_start, tail_len),
+                (true, true) => {
+                    self.ranges[i] = (start, padding);
+                    self.ranges.insert(i + 1, (tail_start, tail_len));
+                }
+            }
+
+            return Ok(aligned);
+        }
+
+        Err(VramAllocError::OutOfMemory {
+            requested: size,
+            largest_free: self.largest_free(),
+        })
     }
 
-    fn total_mem(&self) -> u32 {
-        total_vram_size()
+    fn free(&mut self, start: u32, len: u32) {
+        let i = self.ranges.iter().position(|&(s, _)| s > start).unwrap_or(self.ranges.len());
+
+        let merge_prev = i > 0 && {
+            let (s, l) = self.ranges[i - 1];
+            s + l == start
+        };
+
+        let merge_next = i < self.ranges.len() && start + len == self.ranges[i].0;
+
+        match (merge_prev, merge_next) {
+            (false, false) => self.ranges.insert(i, (start, len)),
+            (true, false) => self.ranges[i - 1].1 += len,
+            (false, true) => {
+                let next = &mut self.ranges[i];
+                *next = (start, len + next.1);
+            }
+            (true, true) => {
+                let (_, next_len) = self.ranges.remove(i);
+                self.ranges[i - 1].1 += len + next_len;
+            }
+        }
+    }
+
+    fn largest_free(&self) -> u32 {
+        self.ranges.iter().map(|&(_, len)| len).max().unwrap_or(0)
+    }
+
+    fn stats(&self) -> VramStats {
+        let free = self.ranges.iter().map(|&(_, len)| len).sum();
+
+        VramStats {
+            total: self.total,
+            used: self.total - free,
+            free,
+            largest_free: self.largest_free(),
+            free_ranges: self.ranges.len() as u32,
+        }
     }
 }
 
+/// Round `value` up to a multiple of `align`, a power of two, or `None` if
+/// that overflows.
+fn round_up(value: u32, align: u32) -> Option<u32> {
+    value.checked_add(align - 1).map(|value| value & !(align - 1))
+}
+
 fn total_vram_size() -> u32 {
     unsafe { sceGeEdramGetSize() }
 }
@@ -121,18 +381,21 @@ fn vram_start_addr_direct() -> *mut u8 {
     unsafe { sceGeEdramGetAddr() }
 }
 
-fn get_memory_size(width: u32, height: u32, psm: TexturePixelFormat) -> u32 {
+/// The size of a texture in bytes, or `None` if it doesn't fit in a `u32`.
+fn get_memory_size(width: u32, height: u32, psm: TexturePixelFormat) -> Option<u32> {
+    let pixels = width.checked_mul(height)?;
+
     match psm {
-        TexturePixelFormat::PsmT4 => (width * height) >> 1,
-        TexturePixelFormat::PsmT8 => width * height,
+        TexturePixelFormat::PsmT4 | TexturePixelFormat::PsmDxt1 => Some(pixels >> 1),
+        TexturePixelFormat::PsmT8 | TexturePixelFormat::PsmDxt3 | TexturePixelFormat::PsmDxt5 => {
+            Some(pixels)
+        }
 
         TexturePixelFormat::Psm5650
         | TexturePixelFormat::Psm5551
         | TexturePixelFormat::Psm4444
-        | TexturePixelFormat::PsmT16 => 2 * width * height,
-
-        TexturePixelFormat::Psm8888 | TexturePixelFormat::PsmT32 => 4 * width * height,
+        | TexturePixelFormat::PsmT16 => pixels.checked_mul(2),
 
-        _ => unimplemented!(),
+        TexturePixelFormat::Psm8888 | TexturePixelFormat::PsmT32 => pixels.checked_mul(4),
     }
 }